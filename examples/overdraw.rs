@@ -0,0 +1,107 @@
+extern crate nalgebra;
+
+#[macro_use]
+extern crate softrender;
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pipeline::SortMode;
+
+struct GlobalUniforms {
+    view_projection: Matrix4<f32>,
+}
+
+declare_uniforms! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct Uniforms {
+        pub position: Vector4<f32>,
+    }
+}
+
+/// Stands in for lighting and texturing, so shading each fragment costs something noticeable
+fn expensive_shading(p: Vector4<f32>) -> f32 {
+    let mut value = 0.0;
+
+    for i in 0..64 {
+        value += (p.x * i as f32 + p.y).sin() * (p.z * 0.1).cos();
+    }
+
+    (value / 64.0).abs()
+}
+
+fn main() {
+    let dimensions = Dimensions::new(256, 256);
+
+    let framebuffer = RenderBuffer::<ColorDepthAttachments<RGBAf32Color, f32>>::with_dimensions(dimensions);
+
+    let camera = Point3::new(0.0, 0.0, 5.0);
+
+    let view = nalgebra::Isometry3::look_at_rh(&camera, &Point3::origin(), &Vector3::new(0.0, 1.0, 0.0)).to_homogeneous();
+
+    let projection = nalgebra::Perspective3::new(1.0, 60.0f32.to_radians(), 0.1, 100.0).to_homogeneous();
+
+    let mut pipeline = Pipeline::from_framebuffer(framebuffer, GlobalUniforms {
+        view_projection: projection * view,
+    });
+
+    // A stack of overlapping opaque layers given from back to front, the worst case for overdraw
+    let layers = 32;
+
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+
+    for layer in 0..layers {
+        let z = -(layers - layer) as f32 * 0.5;
+        let base = vertices.len();
+
+        for &(x, y) in &[(-10.0, -10.0), (10.0, -10.0), (10.0, 10.0), (-10.0, 10.0)] {
+            vertices.push(SimpleVertex { position: Point3::new(x, y, z), data: () });
+        }
+
+        indices.extend([0, 1, 2, 0, 2, 3].iter().map(|i| base + i));
+    }
+
+    let mesh = Arc::new(Mesh { vertices, indices });
+
+    for &sort_mode in &[SortMode::None, SortMode::FrontToBack] {
+        pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 1.0));
+
+        let shaded = AtomicUsize::new(0);
+
+        let start = Instant::now();
+
+        let vertex_shader = pipeline.render_mesh(Triangle, mesh.clone(), None);
+
+        let geometry_shader = vertex_shader.run(|vertex: &SimpleVertex<f32, ()>, global_uniforms: &GlobalUniforms| -> ClipVertex<f32, Uniforms> {
+            let position = global_uniforms.view_projection * vertex.position.to_homogeneous();
+
+            ClipVertex::new(position, Uniforms { position: vertex.position.to_homogeneous() })
+        });
+
+        let fragment_shader = geometry_shader.clip_primitives().finish_default().with_sort_mode(sort_mode);
+
+        fragment_shader.run(|screen_vertex, _| {
+            shaded.fetch_add(1, Ordering::Relaxed);
+
+            let value = expensive_shading(screen_vertex.uniforms.position);
+
+            Fragment::Color(RGBAf32Color::new(value, value, value, 1.0))
+        });
+
+        let elapsed = start.elapsed();
+
+        let shaded = shaded.into_inner();
+
+        println!("{:?}: {} fragments shaded ({:.2}x overdraw) in {:.1}ms",
+                 sort_mode, shaded,
+                 shaded as f64 / dimensions.area() as f64,
+                 elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1e6);
+    }
+}
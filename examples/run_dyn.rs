@@ -0,0 +1,98 @@
+//! Compares `FragmentShader::run` with `FragmentShader::run_dyn` on a trivial and a textured shader.
+//!
+//! Run with `cargo run --release --example run_dyn`, debug builds don't inline either way.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pixels::ColorBuffer;
+use softrender::texture::{Filter, sample_channels};
+
+struct GlobalUniforms {
+    texture: ColorBuffer<RGBAf32Color>,
+}
+
+type BenchPipeline = Pipeline<GlobalUniforms, RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+const FRAMES: u32 = 50;
+
+fn vertex_shader(vertex: &SimpleVertex<f32, ()>, _: &GlobalUniforms) -> ClipVertex<f32, ()> {
+    ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ())
+}
+
+/// Average milliseconds per frame
+fn time<F>(mut frame: F) -> f64 where F: FnMut() {
+    // Warm up the thread pool and caches
+    frame();
+
+    let start = Instant::now();
+
+    for _ in 0..FRAMES {
+        frame();
+    }
+
+    let elapsed = start.elapsed();
+
+    (elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1e6) / FRAMES as f64
+}
+
+fn main() {
+    let dimensions = Dimensions::new(512, 512);
+
+    let texture = ColorBuffer::from_fn(Dimensions::new(64, 64), |coord| {
+        let checker = ((coord.x / 8 + coord.y / 8) % 2) as f32;
+
+        RGBAf32Color::new(checker, 0.5, 1.0 - checker, 1.0)
+    });
+
+    let mut pipeline: BenchPipeline = Pipeline::from_framebuffer(RenderBuffer::with_dimensions(dimensions),
+                                                                 GlobalUniforms { texture });
+
+    // Full-screen quad, so every pixel is shaded once per frame
+    let mesh = Arc::new(Mesh {
+        indices: vec![0, 1, 2, 2, 1, 3],
+        vertices: [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].iter().map(|&(x, y)| {
+            SimpleVertex { position: Point3::new(x, y, 0.5), data: () }
+        }).collect(),
+    });
+
+    let trivial = |_: &ScreenVertex<f32, ()>, _: &GlobalUniforms| Fragment::Color(RGBAf32Color::new(1.0, 0.5, 0.25, 1.0));
+
+    let textured = |v: &ScreenVertex<f32, ()>, u: &GlobalUniforms| {
+        let c = sample_channels(&u.texture, v.position.x * 0.25, v.position.y * 0.25, Filter::Bilinear);
+
+        Fragment::Color(RGBAf32Color::new(c[0], c[1], c[2], c[3]))
+    };
+
+    macro_rules! draw {
+        ($method:ident, $shader:expr) => {
+            time(|| {
+                pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 1.0));
+
+                pipeline.render_mesh(Triangle, mesh.clone(), None)
+                        .run(vertex_shader)
+                        .finish_default()
+                        .$method($shader);
+            })
+        }
+    }
+
+    let trivial_static = draw!(run, &trivial);
+    let trivial_dyn = draw!(run_dyn, &trivial);
+    let textured_static = draw!(run, &textured);
+    let textured_dyn = draw!(run_dyn, &textured);
+
+    println!("{} fragments per frame, average of {} frames", dimensions.area(), FRAMES);
+
+    for &(name, run, run_dyn) in &[("trivial", trivial_static, trivial_dyn), ("textured", textured_static, textured_dyn)] {
+        println!("{:>8}: run {:.2}ms, run_dyn {:.2}ms ({:+.1}%)", name, run, run_dyn, (run_dyn / run - 1.0) * 100.0);
+    }
+}
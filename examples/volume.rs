@@ -0,0 +1,132 @@
+extern crate nalgebra;
+
+#[macro_use]
+extern crate softrender;
+
+use std::io;
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::{RGBAf32Color, Rf32Color};
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::texture::{Filter, Texture3D};
+use softrender::terminal::write_truecolor;
+
+struct GlobalUniforms {
+    camera: Point3<f32>,
+    // Transforms normalized device coordinates back into world space, to build a ray for every fragment
+    inverse_view_projection: Matrix4<f32>,
+    volume: Texture3D<Rf32Color>,
+}
+
+declare_uniforms! {
+    #[derive(Debug, Clone, Copy)]
+    pub struct Uniforms {
+        pub ndc: Vector4<f32>,
+    }
+}
+
+/// Intersects a ray with the unit cube from `(0, 0, 0)` to `(1, 1, 1)`, returning the entry and exit distances
+fn intersect_unit_cube(origin: Point3<f32>, direction: Vector3<f32>) -> Option<(f32, f32)> {
+    let mut near = 0.0f32;
+    let mut far = ::std::f32::INFINITY;
+
+    for i in 0..3 {
+        let t0 = (0.0 - origin[i]) / direction[i];
+        let t1 = (1.0 - origin[i]) / direction[i];
+
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+    }
+
+    if near < far { Some((near, far)) } else { None }
+}
+
+fn main() {
+    let dimensions = Dimensions::new(96, 64);
+
+    let mut framebuffer = RenderBuffer::<ColorDepthAttachments<RGBAf32Color, f32>>::with_dimensions(dimensions);
+
+    framebuffer.clear(RGBAf32Color::new(0.0, 0.0, 0.0, 1.0));
+
+    // A fuzzy ball of smoke, with some ripples to show off the filtering
+    let volume = Texture3D::from_fn(Dimensions::new(32, 32), 32, |coord, z| {
+        let p = Vector3::new(coord.x as f32, coord.y as f32, z as f32) / 31.0 - Vector3::new(0.5, 0.5, 0.5);
+
+        let ripple = (p.x * 20.0).sin() * (p.y * 20.0).sin() * 0.05;
+
+        Rf32Color::new((1.0 - (p.norm() + ripple) * 2.2).max(0.0))
+    });
+
+    let camera = Point3::new(1.8, 1.4, 2.2);
+
+    let view = nalgebra::Isometry3::look_at_rh(&camera, &Point3::new(0.5, 0.5, 0.5), &Vector3::new(0.0, 1.0, 0.0)).to_homogeneous();
+
+    let projection = nalgebra::Perspective3::new(dimensions.width as f32 / dimensions.height as f32,
+                                                 50.0f32.to_radians(), 0.1, 100.0).to_homogeneous();
+
+    let mut pipeline = Pipeline::from_framebuffer(framebuffer, GlobalUniforms {
+        camera,
+        inverse_view_projection: (projection * view).try_inverse().unwrap(),
+        volume,
+    });
+
+    // A quad covering the whole screen, so the fragment shader runs for every pixel
+    let quad = Arc::new(Mesh {
+        vertices: [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)].iter().map(|&(x, y)| SimpleVertex {
+            position: Point3::new(x, y, 0.0f32),
+            data: (),
+        }).collect(),
+        indices: vec![0, 1, 2, 0, 2, 3],
+    });
+
+    let vertex_shader = pipeline.render_mesh(Triangle, quad, None);
+
+    let geometry_shader = vertex_shader.run(|vertex: &SimpleVertex<f32, ()>, _: &GlobalUniforms| -> ClipVertex<f32, Uniforms> {
+        let ndc = vertex.position.to_homogeneous();
+
+        ClipVertex::new(ndc, Uniforms { ndc })
+    });
+
+    let fragment_shader = geometry_shader.clip_primitives().finish_default();
+
+    fragment_shader.run(|screen_vertex, global_uniforms| {
+        let GlobalUniforms { camera, ref inverse_view_projection, ref volume } = *global_uniforms;
+
+        let ndc = screen_vertex.uniforms.ndc;
+
+        // Unproject a point on the far plane to find the direction of the ray
+        let far = inverse_view_projection * Vector4::new(ndc.x, ndc.y, 1.0, 1.0);
+        let direction = (Point3::from_homogeneous(far).unwrap() - camera).normalize();
+
+        let (near, far) = match intersect_unit_cube(camera, direction) {
+            Some(range) => range,
+            None => return Fragment::Discard,
+        };
+
+        // March through the volume, accumulating light scattered towards the camera
+        let steps = 64;
+        let step = (far - near) / steps as f32;
+
+        let mut transmittance = 1.0;
+        let mut light = 0.0;
+
+        for i in 0..steps {
+            let p = camera + direction * (near + (i as f32 + 0.5) * step);
+
+            let density = volume.sample(p.coords, Filter::Bilinear)[0] * 8.0;
+
+            let absorbed = transmittance * (1.0 - (-density * step).exp());
+
+            // Brighter towards the top of the volume
+            light += absorbed * (0.3 + 0.7 * p.y);
+            transmittance -= absorbed;
+        }
+
+        Fragment::Color(RGBAf32Color::new(light, light * 0.8, light * 0.6, 1.0))
+    });
+
+    write_truecolor(pipeline.framebuffer(), &mut io::stdout()).unwrap();
+}
@@ -0,0 +1,10 @@
+//! Draws arbitrary meshes with arbitrary render state, which must never panic or write outside of the draw bounds
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate softrender;
+
+fuzz_target!(|data: &[u8]| {
+    softrender::fuzzing::fuzz_rasterize(data);
+});
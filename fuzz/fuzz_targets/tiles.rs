@@ -0,0 +1,10 @@
+//! Generates tiles for arbitrary framebuffer and tile sizes, which must stay within the framebuffer
+#![no_main]
+
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate softrender;
+
+fuzz_target!(|data: &[u8]| {
+    softrender::fuzzing::fuzz_tiles(data);
+});
@@ -0,0 +1,281 @@
+//! Image analysis helpers
+//!
+//! Computes histograms and statistics over any readable pixel buffer, split across a thread pool.
+//! Useful for auto-exposure, where the log-average luminance of the previous frame drives the next frame's exposure,
+//! and for debugging renders.
+
+use parking_lot::Mutex;
+
+use scoped_threadpool::Pool;
+
+use ::color::ToChannels;
+use ::geometry::Coordinate;
+use ::pixels::PixelRead;
+
+/// Relative luminance of linear RGB channels, using Rec. 709 coefficients
+#[inline]
+pub fn luminance(c: &[f32; 4]) -> f32 {
+    0.2126 * c[0] + 0.7152 * c[1] + 0.0722 * c[2]
+}
+
+/// Small offset used to avoid taking the logarithm of zero for black pixels
+const LOG_DELTA: f32 = 1e-4;
+
+/// Per-channel histograms of a buffer, with values in `[0, 1]` split into equally sized bins.
+///
+/// Values outside of `[0, 1]` are counted in the first or last bin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    /// Red, green, blue and alpha histograms
+    pub channels: [Vec<u32>; 4],
+    /// Luminance histogram
+    pub luminance: Vec<u32>,
+}
+
+impl Histogram {
+    fn new(bins: usize) -> Histogram {
+        Histogram {
+            channels: [vec![0; bins], vec![0; bins], vec![0; bins], vec![0; bins]],
+            luminance: vec![0; bins],
+        }
+    }
+
+    /// Number of bins in each histogram
+    #[inline]
+    pub fn bins(&self) -> usize { self.luminance.len() }
+
+    #[inline]
+    fn bin(&self, value: f32) -> usize {
+        let bins = self.bins();
+        let bin = (value.max(0.0) * bins as f32) as usize;
+
+        if bin >= bins { bins - 1 } else { bin }
+    }
+
+    fn add(&mut self, c: &[f32; 4]) {
+        for i in 0..4 {
+            let bin = self.bin(c[i]);
+            self.channels[i][bin] += 1;
+        }
+
+        let bin = self.bin(luminance(c));
+        self.luminance[bin] += 1;
+    }
+
+    fn merge(&mut self, other: &Histogram) {
+        for i in 0..4 {
+            for (a, b) in self.channels[i].iter_mut().zip(other.channels[i].iter()) {
+                *a += *b;
+            }
+        }
+
+        for (a, b) in self.luminance.iter_mut().zip(other.luminance.iter()) {
+            *a += *b;
+        }
+    }
+
+    /// Returns the luminance below which the given fraction of pixels fall,
+    /// which is more robust to a few very bright pixels than the maximum.
+    pub fn luminance_percentile(&self, fraction: f32) -> f32 {
+        let total: u32 = self.luminance.iter().sum();
+        let target = (total as f32 * fraction.max(0.0).min(1.0)) as u32;
+
+        let mut count = 0;
+
+        for (bin, &n) in self.luminance.iter().enumerate() {
+            count += n;
+
+            if count >= target {
+                return (bin + 1) as f32 / self.bins() as f32;
+            }
+        }
+
+        1.0
+    }
+}
+
+/// Summary statistics of a buffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Statistics {
+    /// Smallest value of each channel
+    pub min: [f32; 4],
+    /// Largest value of each channel
+    pub max: [f32; 4],
+    /// Average of each channel
+    pub mean: [f32; 4],
+    /// Average luminance
+    pub average_luminance: f32,
+    /// Geometric mean of the luminance, which is commonly used as the key value for tone mapping
+    pub log_average_luminance: f32,
+}
+
+#[derive(Clone, Copy)]
+struct PartialStatistics {
+    count: usize,
+    min: [f32; 4],
+    max: [f32; 4],
+    sum: [f64; 4],
+    luminance_sum: f64,
+    log_luminance_sum: f64,
+}
+
+impl PartialStatistics {
+    fn new() -> PartialStatistics {
+        PartialStatistics {
+            count: 0,
+            min: [::std::f32::INFINITY; 4],
+            max: [::std::f32::NEG_INFINITY; 4],
+            sum: [0.0; 4],
+            luminance_sum: 0.0,
+            log_luminance_sum: 0.0,
+        }
+    }
+
+    fn add(&mut self, c: &[f32; 4]) {
+        for i in 0..4 {
+            self.min[i] = self.min[i].min(c[i]);
+            self.max[i] = self.max[i].max(c[i]);
+            self.sum[i] += c[i] as f64;
+        }
+
+        let l = luminance(c);
+
+        self.luminance_sum += l as f64;
+        self.log_luminance_sum += (LOG_DELTA + l.max(0.0)).ln() as f64;
+        self.count += 1;
+    }
+
+    fn merge(&mut self, other: &PartialStatistics) {
+        for i in 0..4 {
+            self.min[i] = self.min[i].min(other.min[i]);
+            self.max[i] = self.max[i].max(other.max[i]);
+            self.sum[i] += other.sum[i];
+        }
+
+        self.luminance_sum += other.luminance_sum;
+        self.log_luminance_sum += other.log_luminance_sum;
+        self.count += other.count;
+    }
+}
+
+/// Splits the rows of a buffer into one contiguous range per thread,
+/// calls `f` for each range in parallel, and merges the results together with `merge`.
+fn parallel_reduce<P, T, F, M>(pool: &mut Pool, buffer: &P, init: T, f: F, merge: M) -> T
+    where P: PixelRead + Sync, T: Send, F: Fn(&P, ::std::ops::Range<usize>) -> T + Sync, M: Fn(&mut T, &T) {
+    let rows = buffer.dimensions().height as usize;
+    let threads = pool.thread_count() as usize;
+    let chunk = (rows + threads - 1) / threads.max(1);
+
+    let results = Mutex::new(Vec::with_capacity(threads));
+
+    pool.scoped(|scope| {
+        for t in 0..threads {
+            let start = t * chunk;
+            let end = if start + chunk > rows { rows } else { start + chunk };
+
+            if start >= end {
+                continue;
+            }
+
+            let (f, results) = (&f, &results);
+
+            scope.execute(move || {
+                let result = f(buffer, start..end);
+
+                results.lock().push(result);
+            });
+        }
+    });
+
+    let mut total = init;
+
+    for result in &results.into_inner() {
+        merge(&mut total, result);
+    }
+
+    total
+}
+
+/// Computes per-channel and luminance histograms with the given number of bins
+pub fn histogram<P>(pool: &mut Pool, buffer: &P, bins: usize) -> Histogram where P: PixelRead + Sync, P::Color: ToChannels {
+    assert!(bins > 0, "Histograms must have at least one bin");
+
+    parallel_reduce(pool, buffer, Histogram::new(bins), |buffer, range| {
+        let mut histogram = Histogram::new(bins);
+
+        for y in range {
+            for x in 0..buffer.dimensions().width {
+                let index = buffer.index_of(Coordinate::new(x, y as u32));
+
+                histogram.add(&unsafe { buffer.get_pixel_unchecked(index) }.to_channels());
+            }
+        }
+
+        histogram
+    }, Histogram::merge)
+}
+
+/// Computes minimum, maximum, mean and luminance statistics
+pub fn statistics<P>(pool: &mut Pool, buffer: &P) -> Statistics where P: PixelRead + Sync, P::Color: ToChannels {
+    let total = parallel_reduce(pool, buffer, PartialStatistics::new(), |buffer, range| {
+        let mut stats = PartialStatistics::new();
+
+        for y in range {
+            for x in 0..buffer.dimensions().width {
+                let index = buffer.index_of(Coordinate::new(x, y as u32));
+
+                stats.add(&unsafe { buffer.get_pixel_unchecked(index) }.to_channels());
+            }
+        }
+
+        stats
+    }, PartialStatistics::merge);
+
+    let count = total.count.max(1) as f64;
+
+    let mut mean = [0.0; 4];
+
+    for i in 0..4 {
+        mean[i] = (total.sum[i] / count) as f32;
+    }
+
+    Statistics {
+        min: total.min,
+        max: total.max,
+        mean,
+        average_luminance: (total.luminance_sum / count) as f32,
+        log_average_luminance: ((total.log_luminance_sum / count).exp() as f32 - LOG_DELTA).max(0.0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use nalgebra::Vector4;
+
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+
+    #[test]
+    fn test_statistics() {
+        let mut pool = Pool::new(3);
+
+        let buffer = ColorBuffer::from_fn(Dimensions::new(10, 7), |coord| {
+            let v = if coord.x < 5 { 0.0 } else { 1.0f32 };
+            Vector4::new(v, v, v, 1.0)
+        });
+
+        let stats = statistics(&mut pool, &buffer);
+
+        assert_eq!(stats.min, [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(stats.max, [1.0, 1.0, 1.0, 1.0]);
+        assert!((stats.mean[0] - 0.5).abs() < 1e-6);
+        assert!((stats.average_luminance - 0.5).abs() < 1e-5);
+
+        let histogram = histogram(&mut pool, &buffer, 4);
+
+        assert_eq!(histogram.channels[0], vec![35, 0, 0, 35]);
+        assert_eq!(histogram.luminance.iter().sum::<u32>(), 70);
+    }
+}
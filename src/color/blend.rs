@@ -3,7 +3,14 @@
 use std::sync::Arc;
 use std::marker::PhantomData;
 
-use super::Color;
+use num_traits::Float;
+
+use nalgebra::{Vector3, Vector4, Scalar};
+
+use ::behavior::ThreadSafeCopyable;
+
+use super::{Color, ColorAlpha, AlphaMultiply, ToChannels, FromChannels};
+use super::srgb::{decode_srgb, encode_srgb};
 
 /// Defines some kind of color blending function
 pub trait Blend<C: Color>: Send + Sync {
@@ -72,4 +79,85 @@ impl<C: Color, F> Blend<C> for GenericBlend<C, F> where F: Fn(C, C) -> C + Send
     fn blend(&self, a: C, b: C) -> C {
         (self.blend_func)(a, b)
     }
-}
\ No newline at end of file
+}
+
+/// Common blend functions which can be selected at runtime, such as from a render state description.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum BlendPreset {
+    /// Overwrite the existing color with the source color, same as `()`
+    Replace,
+    /// Standard alpha compositing of the source color *over* the existing color, with straight alpha.
+    AlphaOver,
+    /// Add the source color to the existing color
+    Additive,
+    /// Multiply the source color with the existing color
+    Multiply,
+    /// Alpha compositing of the source color *over* the existing color, where both have premultiplied alpha.
+    ///
+    /// Use this with textures converted by `srgb::premultiply_buffer` to avoid dark fringes around transparent edges.
+    PremultipliedOver,
+}
+
+impl Default for BlendPreset {
+    fn default() -> BlendPreset { BlendPreset::Replace }
+}
+
+impl<N> Blend<Vector4<N>> for BlendPreset where N: Scalar + Float + AlphaMultiply + ColorAlpha {
+    fn blend(&self, a: Vector4<N>, b: Vector4<N>) -> Vector4<N> {
+        match *self {
+            BlendPreset::Replace => a,
+            BlendPreset::AlphaOver => {
+                let alpha = a.w + b.w * (N::one() - a.w);
+
+                if alpha <= N::zero() {
+                    Vector4::from_element(N::zero())
+                } else {
+                    let blend_channel = |ca: N, cb: N| (ca * a.w + cb * b.w * (N::one() - a.w)) / alpha;
+
+                    Vector4::new(blend_channel(a.x, b.x),
+                                 blend_channel(a.y, b.y),
+                                 blend_channel(a.z, b.z),
+                                 alpha)
+                }
+            }
+            BlendPreset::Additive => Vector4::new(a.x + b.x, a.y + b.y, a.z + b.z, a.w + b.w),
+            BlendPreset::Multiply => Vector4::new(a.x * b.x, a.y * b.y, a.z * b.z, a.w * b.w),
+            BlendPreset::PremultipliedOver => {
+                let inv = N::one() - a.w;
+
+                Vector4::new(a.x + b.x * inv, a.y + b.y * inv, a.z + b.z * inv, a.w + b.w * inv)
+            }
+        }
+    }
+}
+
+impl<N> Blend<Vector3<N>> for BlendPreset where N: Scalar + Float + ThreadSafeCopyable + Default {
+    fn blend(&self, a: Vector3<N>, b: Vector3<N>) -> Vector3<N> {
+        match *self {
+            // Without an alpha channel, alpha compositing is the same as replacing
+            BlendPreset::Replace | BlendPreset::AlphaOver | BlendPreset::PremultipliedOver => a,
+            BlendPreset::Additive => Vector3::new(a.x + b.x, a.y + b.y, a.z + b.z),
+            BlendPreset::Multiply => Vector3::new(a.x * b.x, a.y * b.y, a.z * b.z),
+        }
+    }
+}
+
+/// Blends sRGB-encoded colors in linear space.
+///
+/// Blending sRGB values directly darkens and shifts partially transparent edges.
+/// This wrapper decodes both colors to linear RGBA, blends them with the inner blend function,
+/// then encodes the result again, at the cost of a few `powf` calls per blended fragment.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LinearBlend<B>(pub B);
+
+impl<B, C> Blend<C> for LinearBlend<B> where C: Color + ToChannels + FromChannels, B: Blend<Vector4<f32>> {
+    fn blend(&self, a: C, b: C) -> C {
+        let a = decode_srgb(a.to_channels());
+        let b = decode_srgb(b.to_channels());
+
+        let c = self.0.blend(Vector4::new(a[0], a[1], a[2], a[3]), Vector4::new(b[0], b[1], b[2], b[3]));
+
+        C::from_channels(encode_srgb([c.x, c.y, c.z, c.w]))
+    }
+}
@@ -0,0 +1,103 @@
+//! Conversion between colors and normalized RGBA channels
+//!
+//! Image comparison and post-processing work on normalized `f32` channels,
+//! so they can be applied to any color format.
+
+use nalgebra::{Vector1, Vector2, Vector3, Vector4};
+
+/// Converts a color into normalized RGBA channels.
+///
+/// Missing channels are filled in with zero, or one for alpha.
+pub trait ToChannels {
+    fn to_channels(&self) -> [f32; 4];
+}
+
+/// Converts normalized RGBA channels back into a color.
+///
+/// Channels the color doesn't have are dropped, and integer colors are clamped to their range.
+pub trait FromChannels {
+    fn from_channels(channels: [f32; 4]) -> Self;
+}
+
+impl ToChannels for () {
+    #[inline]
+    fn to_channels(&self) -> [f32; 4] { [0.0, 0.0, 0.0, 1.0] }
+}
+
+impl FromChannels for () {
+    #[inline]
+    fn from_channels(_: [f32; 4]) -> () { () }
+}
+
+macro_rules! impl_channels {
+    ($($t:ty => $scale:expr, $from:expr),+) => {
+        $(
+            impl ToChannels for Vector4<$t> {
+                #[inline]
+                fn to_channels(&self) -> [f32; 4] {
+                    [self.x as f32 / $scale, self.y as f32 / $scale, self.z as f32 / $scale, self.w as f32 / $scale]
+                }
+            }
+
+            impl ToChannels for Vector3<$t> {
+                #[inline]
+                fn to_channels(&self) -> [f32; 4] {
+                    [self.x as f32 / $scale, self.y as f32 / $scale, self.z as f32 / $scale, 1.0]
+                }
+            }
+
+            impl ToChannels for Vector2<$t> {
+                #[inline]
+                fn to_channels(&self) -> [f32; 4] {
+                    [self.x as f32 / $scale, self.y as f32 / $scale, 0.0, 1.0]
+                }
+            }
+
+            impl ToChannels for Vector1<$t> {
+                #[inline]
+                fn to_channels(&self) -> [f32; 4] {
+                    [self.x as f32 / $scale, 0.0, 0.0, 1.0]
+                }
+            }
+
+            impl FromChannels for Vector4<$t> {
+                #[inline]
+                fn from_channels(c: [f32; 4]) -> Vector4<$t> {
+                    let from: fn(f32) -> $t = $from;
+                    Vector4::new(from(c[0]), from(c[1]), from(c[2]), from(c[3]))
+                }
+            }
+
+            impl FromChannels for Vector3<$t> {
+                #[inline]
+                fn from_channels(c: [f32; 4]) -> Vector3<$t> {
+                    let from: fn(f32) -> $t = $from;
+                    Vector3::new(from(c[0]), from(c[1]), from(c[2]))
+                }
+            }
+
+            impl FromChannels for Vector2<$t> {
+                #[inline]
+                fn from_channels(c: [f32; 4]) -> Vector2<$t> {
+                    let from: fn(f32) -> $t = $from;
+                    Vector2::new(from(c[0]), from(c[1]))
+                }
+            }
+
+            impl FromChannels for Vector1<$t> {
+                #[inline]
+                fn from_channels(c: [f32; 4]) -> Vector1<$t> {
+                    let from: fn(f32) -> $t = $from;
+                    Vector1::new(from(c[0]))
+                }
+            }
+        )+
+    }
+}
+
+impl_channels! {
+    f32 => 1.0, |c| c,
+    f64 => 1.0, |c| c as f64,
+    u8 => 255.0, |c| (c.max(0.0).min(1.0) * 255.0).round() as u8,
+    u16 => 65535.0, |c| (c.max(0.0).min(1.0) * 65535.0).round() as u16
+}
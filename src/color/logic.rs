@@ -0,0 +1,127 @@
+//! Bitwise logic operations for integer color attachments
+//!
+//! Like `glLogicOp`, these combine the bits of the source color with the existing color instead of blending them,
+//! which is useful for generating masks, highlighting selections by inverting them, and retro effects.
+//! Use a `LogicOp` anywhere a blend function is accepted, such as `FragmentShader::with_blend`.
+
+use num_traits::PrimInt;
+
+use nalgebra::{Vector1, Vector2, Vector3, Vector4, Scalar};
+
+use ::behavior::ThreadSafeCopyable;
+
+use super::{ColorAlpha, AlphaMultiply};
+use super::blend::Blend;
+
+/// Bitwise operation between the source color `s` and the existing color `d`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum LogicOp {
+    /// `0`
+    Clear,
+    /// `s & d`
+    And,
+    /// `s & !d`
+    AndReverse,
+    /// `s`
+    Copy,
+    /// `!s & d`
+    AndInverted,
+    /// `d`
+    Noop,
+    /// `s ^ d`
+    Xor,
+    /// `s | d`
+    Or,
+    /// `!(s | d)`
+    Nor,
+    /// `!(s ^ d)`
+    Equiv,
+    /// `!d`
+    Invert,
+    /// `s | !d`
+    OrReverse,
+    /// `!s`
+    CopyInverted,
+    /// `!s | d`
+    OrInverted,
+    /// `!(s & d)`
+    Nand,
+    /// All bits set
+    Set,
+}
+
+impl Default for LogicOp {
+    fn default() -> LogicOp { LogicOp::Copy }
+}
+
+impl LogicOp {
+    /// Applies the operation to a single channel
+    #[inline]
+    pub fn apply<T: PrimInt>(self, s: T, d: T) -> T {
+        match self {
+            LogicOp::Clear => T::zero(),
+            LogicOp::And => s & d,
+            LogicOp::AndReverse => s & !d,
+            LogicOp::Copy => s,
+            LogicOp::AndInverted => !s & d,
+            LogicOp::Noop => d,
+            LogicOp::Xor => s ^ d,
+            LogicOp::Or => s | d,
+            LogicOp::Nor => !(s | d),
+            LogicOp::Equiv => !(s ^ d),
+            LogicOp::Invert => !d,
+            LogicOp::OrReverse => s | !d,
+            LogicOp::CopyInverted => !s,
+            LogicOp::OrInverted => !s | d,
+            LogicOp::Nand => !(s & d),
+            LogicOp::Set => !T::zero(),
+        }
+    }
+}
+
+impl<T> Blend<Vector4<T>> for LogicOp where T: Scalar + PrimInt + AlphaMultiply + ColorAlpha {
+    fn blend(&self, a: Vector4<T>, b: Vector4<T>) -> Vector4<T> {
+        Vector4::new(self.apply(a.x, b.x), self.apply(a.y, b.y), self.apply(a.z, b.z), self.apply(a.w, b.w))
+    }
+}
+
+macro_rules! impl_vector_logic_op {
+    ($name:ident) => {
+        impl<T> Blend<$name<T>> for LogicOp where T: Scalar + PrimInt + ThreadSafeCopyable + Default {
+            fn blend(&self, a: $name<T>, b: $name<T>) -> $name<T> {
+                let mut out = b;
+
+                for i in 0..out.len() {
+                    out[i] = self.apply(a[i], b[i]);
+                }
+
+                out
+            }
+        }
+    }
+}
+
+impl_vector_logic_op!(Vector1);
+impl_vector_logic_op!(Vector2);
+impl_vector_logic_op!(Vector3);
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::{RGBAu8Color, Ru8Color};
+
+    use super::*;
+
+    #[test]
+    fn test_logic_ops() {
+        let s = RGBAu8Color::new(0b1100, 0xFF, 0, 1);
+        let d = RGBAu8Color::new(0b1010, 0x0F, 0, 1);
+
+        assert_eq!(LogicOp::Xor.blend(s, d), RGBAu8Color::new(0b0110, 0xF0, 0, 0));
+        assert_eq!(LogicOp::Or.blend(s, d), RGBAu8Color::new(0b1110, 0xFF, 0, 1));
+        assert_eq!(LogicOp::And.blend(s, d), RGBAu8Color::new(0b1000, 0x0F, 0, 1));
+        assert_eq!(LogicOp::Invert.blend(s, d), RGBAu8Color::new(!0b1010, 0xF0, 0xFF, 0xFE));
+
+        assert_eq!(LogicOp::Nand.blend(Ru8Color::new(0xFF), Ru8Color::new(0x0F)), Ru8Color::new(0xF0));
+    }
+}
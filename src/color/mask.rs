@@ -0,0 +1,82 @@
+//! Per-channel color write masks
+
+/// Selects which color channels are written to the framebuffer.
+///
+/// Channels that are masked out keep their existing value, after blending. Depth and stencil values
+/// are written as usual, so masking out every channel renders only depth and stencil.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct ColorMask {
+    pub r: bool,
+    pub g: bool,
+    pub b: bool,
+    pub a: bool,
+}
+
+impl ColorMask {
+    /// Writes every channel
+    pub fn all() -> ColorMask {
+        ColorMask { r: true, g: true, b: true, a: true }
+    }
+
+    /// Writes no color channels at all
+    pub fn none() -> ColorMask {
+        ColorMask { r: false, g: false, b: false, a: false }
+    }
+
+    /// Writes only the red, green and blue channels
+    pub fn rgb() -> ColorMask {
+        ColorMask { a: false, ..ColorMask::all() }
+    }
+
+    /// Writes only the alpha channel
+    pub fn alpha() -> ColorMask {
+        ColorMask { a: true, ..ColorMask::none() }
+    }
+
+    #[inline]
+    pub fn is_all(&self) -> bool {
+        self.r && self.g && self.b && self.a
+    }
+
+    #[inline]
+    pub fn is_none(&self) -> bool {
+        !(self.r || self.g || self.b || self.a)
+    }
+
+    /// Whether the channel at the given index is written, in red, green, blue, alpha order
+    #[inline]
+    pub fn channel(&self, index: usize) -> bool {
+        match index {
+            0 => self.r,
+            1 => self.g,
+            2 => self.b,
+            _ => self.a,
+        }
+    }
+}
+
+impl Default for ColorMask {
+    fn default() -> ColorMask { ColorMask::all() }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::Color;
+    use ::color::predefined::formats::{RGBAf32Color, RGf32Color};
+
+    use super::ColorMask;
+
+    #[test]
+    fn test_mask_channels() {
+        let new = RGBAf32Color::new(1.0, 1.0, 1.0, 1.0);
+        let old = RGBAf32Color::new(0.0, 0.0, 0.0, 0.5);
+
+        assert_eq!(new.mask_channels(old, ColorMask::rgb()), RGBAf32Color::new(1.0, 1.0, 1.0, 0.5));
+        assert_eq!(new.mask_channels(old, ColorMask::alpha()), RGBAf32Color::new(0.0, 0.0, 0.0, 1.0));
+
+        let masked = (new, RGf32Color::new(1.0, 1.0)).mask_channels((old, RGf32Color::new(0.0, 0.0)), ColorMask { g: false, ..ColorMask::all() });
+
+        assert_eq!(masked, (RGBAf32Color::new(1.0, 0.0, 1.0, 1.0), RGf32Color::new(1.0, 0.0)));
+    }
+}
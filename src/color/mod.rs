@@ -1,22 +1,41 @@
 //! Color type definitions, for both framebuffer usage and general usage
 
-use num_traits::NumCast;
+use num_traits::{NumCast, Bounded};
 
+use ::error::RenderResult;
+use ::validation::checked_cast;
 use ::behavior::ThreadSafeCopyable;
 use ::numeric::FloatScalar;
 
 pub mod blend;
 pub mod helper;
+pub mod channels;
+pub mod srgb;
+pub mod mask;
+pub mod logic;
 
 pub use self::helper::AlphaMultiply;
+pub use self::channels::{ToChannels, FromChannels};
+pub use self::mask::ColorMask;
+pub use self::logic::LogicOp;
 
 pub trait ColorAlpha: ThreadSafeCopyable + Default {
+    /// Values out of range of the alpha type are saturated, and NaN becomes the minimum value.
     fn from_scalar<N: FloatScalar>(n: N) -> Self;
+
+    /// Like `from_scalar`, but fails if the value can't be represented exactly.
+    ///
+    /// The default implementation never fails, and saturates like `from_scalar`.
+    #[inline]
+    fn try_from_scalar<N: FloatScalar>(n: N) -> RenderResult<Self> {
+        Ok(Self::from_scalar(n))
+    }
 }
 
 impl ColorAlpha for () {
     #[inline(always)]
     fn from_scalar<N: FloatScalar>(_: N) -> () { () }
+
 }
 
 macro_rules! impl_color_alpha {
@@ -25,7 +44,14 @@ macro_rules! impl_color_alpha {
             impl ColorAlpha for $t {
                 #[inline(always)]
                 fn from_scalar<N: FloatScalar>(n: N) -> $t {
-                    <$t as NumCast>::from(n).expect("Invalid Cast")
+                    <$t as NumCast>::from(n).unwrap_or_else(|| {
+                        if n > N::zero() { <$t as Bounded>::max_value() } else { <$t as Bounded>::min_value() }
+                    })
+                }
+
+                #[inline(always)]
+                fn try_from_scalar<N: FloatScalar>(n: N) -> RenderResult<$t> {
+                    checked_cast(n)
                 }
             }
         )+
@@ -46,6 +72,23 @@ pub trait Color: ThreadSafeCopyable {
     fn mul_alpha(self, alpha: Self::Alpha) -> Self;
     /// Get the alpha of the color
     fn get_alpha(&self) -> Self::Alpha;
+
+    /// Combine two pixels by render target, taking the targets with their bit set in `mask` from `self`
+    /// and the others from `other`.
+    ///
+    /// A single color is a single render target, selected by the lowest bit.
+    #[inline]
+    fn select_targets(self, other: Self, mask: u32) -> Self {
+        if mask & 1 != 0 { self } else { other }
+    }
+
+    /// Combine two pixels by channel, taking the channels enabled in `mask` from `self` and the others from `other`.
+    ///
+    /// Colors without a known channel layout are taken from `self` entirely unless every channel is masked out.
+    #[inline]
+    fn mask_channels(self, other: Self, mask: ColorMask) -> Self {
+        if mask.is_none() { other } else { self }
+    }
 }
 
 impl Color for () {
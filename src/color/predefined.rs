@@ -7,7 +7,7 @@ use nalgebra::coordinates::XYZW;
 
 use ::behavior::ThreadSafeCopyable;
 
-use super::{Color, ColorAlpha};
+use super::{Color, ColorAlpha, ColorMask};
 use super::helper::AlphaMultiply;
 
 pub mod formats {
@@ -87,6 +87,16 @@ impl<T> Color for Vector4<T> where T: Scalar + Num + AlphaMultiply + ColorAlpha
 
     #[inline]
     fn get_alpha(&self) -> T { self.w }
+
+    #[inline]
+    fn mask_channels(self, other: Vector4<T>, mask: ColorMask) -> Vector4<T> {
+        if mask.is_all() { return self; }
+
+        Vector4::new(if mask.r { self.x } else { other.x },
+                     if mask.g { self.y } else { other.y },
+                     if mask.b { self.z } else { other.z },
+                     if mask.a { self.w } else { other.w })
+    }
 }
 
 macro_rules! impl_vector_color_without_alpha {
@@ -105,6 +115,19 @@ macro_rules! impl_vector_color_without_alpha {
 
             #[inline(always)]
             fn get_alpha(&self) -> () { () }
+
+            #[inline]
+            fn mask_channels(self, other: $name<T>, mask: ColorMask) -> $name<T> {
+                let mut out = other;
+
+                for i in 0..out.len() {
+                    if mask.channel(i) {
+                        out[i] = self[i];
+                    }
+                }
+
+                out
+            }
         }
     }
 }
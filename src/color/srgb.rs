@@ -0,0 +1,115 @@
+//! sRGB transfer functions and alpha conversions
+//!
+//! Blending and filtering are only correct on linear values, but 8-bit framebuffers and images
+//! are almost always stored with the sRGB transfer function applied.
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Dimensions, Coordinate};
+use ::pixels::PixelWrite;
+
+/// Converts a single sRGB-encoded channel into linear space
+#[inline]
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+}
+
+/// Converts a single linear channel into sRGB encoding
+#[inline]
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 { c * 12.92 } else { 1.055 * c.powf(1.0 / 2.4) - 0.055 }
+}
+
+/// Decodes the color channels of sRGB-encoded RGBA channels, leaving alpha as-is, since alpha is always linear
+#[inline]
+pub fn decode_srgb(c: [f32; 4]) -> [f32; 4] {
+    [srgb_to_linear(c[0]), srgb_to_linear(c[1]), srgb_to_linear(c[2]), c[3]]
+}
+
+/// Encodes the color channels of linear RGBA channels, leaving alpha as-is
+#[inline]
+pub fn encode_srgb(c: [f32; 4]) -> [f32; 4] {
+    [linear_to_srgb(c[0]), linear_to_srgb(c[1]), linear_to_srgb(c[2]), c[3]]
+}
+
+/// Multiplies the color channels by alpha
+#[inline]
+pub fn premultiply(c: [f32; 4]) -> [f32; 4] {
+    [c[0] * c[3], c[1] * c[3], c[2] * c[3], c[3]]
+}
+
+/// Divides the color channels by alpha, with fully transparent colors becoming transparent black
+#[inline]
+pub fn unpremultiply(c: [f32; 4]) -> [f32; 4] {
+    if c[3] <= 0.0 {
+        [0.0; 4]
+    } else {
+        [c[0] / c[3], c[1] / c[3], c[2] / c[3], c[3]]
+    }
+}
+
+/// Converts every pixel of a straight-alpha buffer, such as a freshly loaded texture, to premultiplied alpha.
+///
+/// Premultiplied textures filter correctly, without dark fringes where opaque texels meet transparent ones,
+/// and must then be blended with `BlendPreset::PremultipliedOver`.
+pub fn premultiply_buffer<P>(buffer: &mut P) where P: PixelWrite, P::Color: ToChannels + FromChannels {
+    let Dimensions { width, height } = buffer.dimensions();
+
+    buffer.prepare_write();
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = buffer.index_of(Coordinate::new(x, y));
+
+            unsafe {
+                let c = buffer.get_pixel_unchecked(index).to_channels();
+                buffer.set_pixel_unchecked(index, P::Color::from_channels(premultiply(c)));
+            }
+        }
+    }
+}
+
+/// Converts every pixel of a premultiplied buffer back to straight alpha, such as before saving it to a file
+pub fn unpremultiply_buffer<P>(buffer: &mut P) where P: PixelWrite, P::Color: ToChannels + FromChannels {
+    let Dimensions { width, height } = buffer.dimensions();
+
+    buffer.prepare_write();
+
+    for y in 0..height {
+        for x in 0..width {
+            let index = buffer.index_of(Coordinate::new(x, y));
+
+            unsafe {
+                let c = buffer.get_pixel_unchecked(index).to_channels();
+                buffer.set_pixel_unchecked(index, P::Color::from_channels(unpremultiply(c)));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_srgb_roundtrip() {
+        for i in 0..256 {
+            let c = i as f32 / 255.0;
+
+            assert!((linear_to_srgb(srgb_to_linear(c)) - c).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_premultiply_roundtrip() {
+        let c = [0.2, 0.4, 0.8, 0.5];
+
+        let p = premultiply(c);
+        assert_eq!(p, [0.1, 0.2, 0.4, 0.5]);
+
+        let u = unpremultiply(p);
+
+        for i in 0..4 {
+            assert!((u[i] - c[i]).abs() < 1e-6);
+        }
+    }
+}
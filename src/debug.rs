@@ -0,0 +1,192 @@
+//! Debug visualization helpers
+//!
+//! These are meant for inspecting how the renderer schedules work, not for producing final images.
+
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::PixelWrite;
+use ::pipeline::stages::rasterization::Tile;
+
+/// Records how many primitives were binned to each tile of a render.
+///
+/// A primitive is binned to a tile if its screen-space bounding box overlaps the tile,
+/// which is the same conservative test a tiled rasterizer would use to skip work.
+#[derive(Debug, Clone)]
+pub struct TileBinning {
+    /// The tile size used to split up the framebuffer
+    pub tile_size: Dimensions,
+    /// All tiles, in the same order they are scheduled in
+    pub tiles: Vec<Tile>,
+    /// Number of primitives binned to each tile, parallel to `tiles`
+    pub counts: Vec<usize>,
+}
+
+impl TileBinning {
+    /// Create a new `TileBinning` with zero primitives in every tile
+    pub fn new(tile_size: Dimensions, tiles: Vec<Tile>) -> TileBinning {
+        TileBinning {
+            tile_size,
+            counts: vec![0; tiles.len()],
+            tiles,
+        }
+    }
+
+    /// Bins a primitive with the given inclusive screen-space bounding box
+    pub fn bin(&mut self, min: (f64, f64), max: (f64, f64)) {
+        for (tile, count) in self.tiles.iter().zip(self.counts.iter_mut()) {
+            let (start, end) = *tile;
+
+            if max.0 >= start.x as f64 && min.0 <= end.x as f64 + 1.0 &&
+                max.1 >= start.y as f64 && min.1 <= end.y as f64 + 1.0 {
+                *count += 1;
+            }
+        }
+    }
+
+    /// The largest number of primitives binned to any single tile
+    pub fn max_count(&self) -> usize {
+        self.counts.iter().cloned().max().unwrap_or(0)
+    }
+
+    /// The total number of primitive-tile pairs, which is the amount of work the rasterizer has to do
+    pub fn total_count(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    /// Ratio between the busiest tile and the average tile.
+    ///
+    /// A value close to `1.0` means the work is evenly spread between tiles,
+    /// while large values mean a few tiles will keep their threads busy long after the others are done.
+    pub fn imbalance(&self) -> f64 {
+        if self.tiles.is_empty() {
+            return 1.0;
+        }
+
+        let mean = self.total_count() as f64 / self.tiles.len() as f64;
+
+        if mean > 0.0 { self.max_count() as f64 / mean } else { 1.0 }
+    }
+
+    /// Returns the load of each tile relative to the busiest tile, in the range `[0, 1]`
+    pub fn loads(&self) -> Vec<f32> {
+        let max = self.max_count();
+
+        self.counts.iter().map(|&count| {
+            if max == 0 { 0.0 } else { count as f32 / max as f32 }
+        }).collect()
+    }
+}
+
+/// Draws the tile grid and tints each tile by the number of primitives binned to it.
+///
+/// The `tint` function is given the existing pixel color and the relative load of the tile it is in,
+/// from `0.0` for empty tiles to `1.0` for the busiest tile, and returns the new color.
+///
+/// If `grid` is given, the tile boundaries are drawn with that color on top of the tinted tiles.
+pub fn draw_tile_overlay<P, T>(buffer: &mut P, binning: &TileBinning, grid: Option<P::Color>, tint: T)
+    where P: PixelWrite, T: Fn(P::Color, f32) -> P::Color {
+    let dimensions = buffer.dimensions();
+
+    let loads = binning.loads();
+
+    buffer.prepare_write();
+
+    for (&(start, end), &load) in binning.tiles.iter().zip(loads.iter()) {
+        // Neighboring tiles share their boundary pixels, so only the last row and column
+        // of the framebuffer include the end coordinate to avoid tinting pixels twice.
+        let xend = if end.x + 1 >= dimensions.width { end.x + 1 } else { end.x };
+        let yend = if end.y + 1 >= dimensions.height { end.y + 1 } else { end.y };
+
+        for y in start.y..yend {
+            for x in start.x..xend {
+                let coord = Coordinate::new(x, y);
+
+                if !dimensions.in_bounds(coord) {
+                    continue;
+                }
+
+                let index = buffer.index_of(coord);
+
+                let on_edge = x == start.x || x == end.x || y == start.y || y == end.y;
+
+                unsafe {
+                    let color = match grid {
+                        Some(grid) if on_edge => grid,
+                        _ => tint(buffer.get_pixel_unchecked(index), load),
+                    };
+
+                    buffer.set_pixel_unchecked(index, color);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use nalgebra::Vector4;
+
+    use ::pixels::ColorBuffer;
+    use ::pipeline::stages::rasterization::generate_tiles;
+
+    fn binning() -> TileBinning {
+        // Four tiles sharing the row and column at 4
+        let mut binning = TileBinning::new(Dimensions::new(4, 4), generate_tiles(Dimensions::new(8, 8), Dimensions::new(4, 4)));
+
+        // Two primitives in the top-left tile, one in the bottom-right tile
+        binning.bin((0.5, 0.5), (2.0, 2.0));
+        binning.bin((0.5, 0.5), (2.0, 2.0));
+        binning.bin((5.5, 5.5), (6.0, 6.0));
+
+        binning
+    }
+
+    fn draw(grid: Option<Vector4<f32>>) -> ColorBuffer<Vector4<f32>> {
+        let mut buffer = ColorBuffer::filled(Dimensions::new(8, 8), Vector4::new(0.0, 0.0, 0.0, 1.0));
+
+        // Adds the load, so pixels tinted twice would show up
+        draw_tile_overlay(&mut buffer, &binning(), grid, |color, load| color + Vector4::new(load, 0.0, 0.0, 0.0));
+
+        buffer
+    }
+
+    fn get(buffer: &ColorBuffer<Vector4<f32>>, x: u32, y: u32) -> Vector4<f32> {
+        buffer.as_slice()[(y * 8 + x) as usize]
+    }
+
+    #[test]
+    fn test_tile_overlay() {
+        assert_eq!(binning().counts, vec![2, 0, 0, 1]);
+
+        let grid = Vector4::new(0.0, 0.0, 1.0, 1.0);
+
+        let buffer = draw(Some(grid));
+
+        for y in 0..8 {
+            for x in 0..8 {
+                let pixel = get(&buffer, x, y);
+
+                if x == 0 || x == 4 || x == 7 || y == 0 || y == 4 || y == 7 {
+                    assert_eq!(pixel, grid, "({}, {})", x, y);
+                } else {
+                    let load = match (x < 4, y < 4) {
+                        (true, true) => 1.0,
+                        (false, false) => 0.5,
+                        _ => 0.0,
+                    };
+
+                    assert_eq!(pixel, Vector4::new(load, 0.0, 0.0, 1.0), "({}, {})", x, y);
+                }
+            }
+        }
+
+        // Without the grid, the shared boundary pixels belong to the tile after them and are tinted once
+        let buffer = draw(None);
+
+        assert_eq!(get(&buffer, 3, 3).x, 1.0);
+        assert_eq!(get(&buffer, 4, 4).x, 0.5);
+        assert_eq!(get(&buffer, 4, 3).x, 0.0);
+        assert_eq!(get(&buffer, 7, 7).x, 0.5);
+    }
+}
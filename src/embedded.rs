@@ -0,0 +1,126 @@
+//! Compatibility with the `embedded-graphics` crate
+//!
+//! `DrawTargetAdapter` lets the 2D drawing primitives and text of `embedded-graphics` draw directly into
+//! any pixel buffer of this crate, such as a framebuffer after rendering a 3D scene,
+//! and `blit` copies a pixel buffer onto any `DrawTarget`, such as a display driver.
+//!
+//! Colors are exchanged as `Rgb888`, which converts into and from all the other `embedded-graphics` color types.
+//!
+//! Both directions take a `RowOrder`, for displays or buffers that expect the bottom row first.
+//!
+//! For e-ink and 1-bit LCD displays, `blit_monochrome` draws a dithered `MonochromeBuffer` as `BinaryColor`.
+
+extern crate embedded_graphics;
+
+use std::convert::Infallible;
+
+use self::embedded_graphics::Pixel;
+use self::embedded_graphics::draw_target::DrawTarget;
+use self::embedded_graphics::geometry::{OriginDimensions, Size, Point};
+use self::embedded_graphics::pixelcolor::{Rgb888, RgbColor, BinaryColor};
+
+use ::color::{ToChannels, FromChannels};
+use ::framebuffer::MonochromeBuffer;
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite, RowOrder};
+
+fn to_rgb888(channels: [f32; 4]) -> Rgb888 {
+    let byte = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    Rgb888::new(byte(channels[0]), byte(channels[1]), byte(channels[2]))
+}
+
+fn from_rgb888(color: Rgb888) -> [f32; 4] {
+    [color.r() as f32 / 255.0, color.g() as f32 / 255.0, color.b() as f32 / 255.0, 1.0]
+}
+
+/// Implements `DrawTarget` for a pixel buffer, so `embedded-graphics` can draw into it.
+///
+/// Pixels outside of the buffer are ignored, and drawn pixels are fully opaque.
+pub struct DrawTargetAdapter<'a, P: 'a> {
+    buffer: &'a mut P,
+    rows: RowOrder,
+}
+
+impl<'a, P> DrawTargetAdapter<'a, P> where P: PixelWrite, P::Color: FromChannels {
+    pub fn new(buffer: &'a mut P) -> DrawTargetAdapter<'a, P> {
+        DrawTargetAdapter::with_row_order(buffer, RowOrder::TopDown)
+    }
+
+    /// Creates an adapter where the top of the drawing is at the given end of the buffer
+    pub fn with_row_order(buffer: &'a mut P, rows: RowOrder) -> DrawTargetAdapter<'a, P> {
+        DrawTargetAdapter { buffer, rows }
+    }
+
+    /// Returns the underlying pixel buffer
+    pub fn into_inner(self) -> &'a mut P { self.buffer }
+}
+
+impl<'a, P> OriginDimensions for DrawTargetAdapter<'a, P> where P: PixelWrite {
+    fn size(&self) -> Size {
+        let dimensions = self.buffer.dimensions();
+
+        Size::new(dimensions.width, dimensions.height)
+    }
+}
+
+impl<'a, P> DrawTarget for DrawTargetAdapter<'a, P> where P: PixelWrite, P::Color: FromChannels {
+    type Color = Rgb888;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible> where I: IntoIterator<Item = Pixel<Rgb888>> {
+        let dimensions = self.buffer.dimensions();
+
+        self.buffer.prepare_write();
+
+        for Pixel(point, color) in pixels {
+            if point.x >= 0 && point.y >= 0 {
+                let coord = Coordinate::new(point.x as u32, point.y as u32);
+
+                if dimensions.in_bounds(coord) {
+                    unsafe {
+                        let index = self.buffer.index_of(Coordinate::new(coord.x, self.rows.source_row(coord.y, dimensions.height)));
+
+                        self.buffer.set_pixel_unchecked(index, P::Color::from_channels(from_rgb888(color)));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Draws every pixel of `buffer` onto `target`, with the top-left corner at `offset`,
+/// starting from the bottom row of the buffer instead if `rows` is `RowOrder::BottomUp`
+pub fn blit<P, T>(buffer: &P, target: &mut T, offset: Point, rows: RowOrder) -> Result<(), T::Error>
+    where P: PixelRead,
+          P::Color: ToChannels,
+          T: DrawTarget,
+          T::Color: From<Rgb888> {
+    let dimensions = buffer.dimensions();
+
+    let pixels = (0..dimensions.height).flat_map(|y| (0..dimensions.width).map(move |x| Coordinate::new(x, y))).map(|coord| {
+        let source = Coordinate::new(coord.x, rows.source_row(coord.y, dimensions.height));
+
+        let color = unsafe { buffer.get_pixel_unchecked(buffer.index_of(source)) };
+
+        Pixel(offset + Point::new(coord.x as i32, coord.y as i32), T::Color::from(to_rgb888(color.to_channels())))
+    });
+
+    target.draw_iter(pixels)
+}
+
+/// Draws every pixel of a monochrome buffer onto `target`, with white pixels as `BinaryColor::On`
+pub fn blit_monochrome<T>(buffer: &MonochromeBuffer, target: &mut T, offset: Point, rows: RowOrder) -> Result<(), T::Error>
+    where T: DrawTarget<Color = BinaryColor> {
+    let dimensions = buffer.dimensions();
+
+    let pixels = (0..dimensions.height).flat_map(|y| (0..dimensions.width).map(move |x| Coordinate::new(x, y))).map(|coord| {
+        let source = Coordinate::new(coord.x, rows.source_row(coord.y, dimensions.height));
+
+        Pixel(offset + Point::new(coord.x as i32, coord.y as i32), BinaryColor::from(buffer.get(source)))
+    });
+
+    target.draw_iter(pixels)
+}
@@ -12,7 +12,18 @@ pub type RenderResult<T> = TraceResult<T, RenderError>;
 #[derive(Debug)]
 pub enum RenderError {
     /// An invalid coordinate was used to access a pixel
-    InvalidPixelCoordinate
+    InvalidPixelCoordinate,
+    /// Buffers used together did not have compatible dimensions
+    DimensionMismatch,
+    /// Dimensions were zero or otherwise unusable
+    InvalidDimensions,
+    /// A vertex position contained NaN or infinite components
+    InvalidVertex,
+    /// A mesh index referred to a vertex that doesn't exist,
+    /// or the index count was not a multiple of the primitive size
+    IndexOutOfRange,
+    /// A numeric value could not be represented in the target type
+    InvalidCast,
 }
 
 impl Display for RenderError {
@@ -24,7 +35,12 @@ impl Display for RenderError {
 impl Error for RenderError {
     fn description(&self) -> &str {
         match *self {
-            RenderError::InvalidPixelCoordinate => "Invalid Pixel Coordinate"
+            RenderError::InvalidPixelCoordinate => "Invalid Pixel Coordinate",
+            RenderError::DimensionMismatch => "Dimension Mismatch",
+            RenderError::InvalidDimensions => "Invalid Dimensions",
+            RenderError::InvalidVertex => "Invalid Vertex",
+            RenderError::IndexOutOfRange => "Index Out Of Range",
+            RenderError::InvalidCast => "Invalid Cast",
         }
     }
 }
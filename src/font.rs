@@ -0,0 +1,259 @@
+//! TrueType and OpenType font rasterization
+//!
+//! Glyph outlines are parsed with `ttf-parser` and filled with the crate's own `Path` filler
+//! at whatever size is requested, so text can be drawn without pre-baked bitmap font atlases:
+//!
+//! ```ignore
+//! let data = std::fs::read("DejaVuSans.ttf")?;
+//! let font = Font::from_bytes(&data)?;
+//!
+//! // Baseline of the first line 24 pixels below the top
+//! font.draw_text(&mut framebuffer.color, "Hello, world!", 24.0, 8.0, 24.0, [1.0, 1.0, 1.0, 1.0]);
+//! ```
+//!
+//! Individual glyphs can also be rasterized into coverage bitmaps with `Font::rasterize`,
+//! for building atlases or baking signed distance fields with `DistanceField::from_alpha`.
+
+extern crate ttf_parser;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use self::ttf_parser::{Face, FaceParsingError, GlyphId, OutlineBuilder};
+
+use ::color::{Color, ToChannels, FromChannels};
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelWrite, ColorBuffer};
+use ::path::{Path, fill_path};
+use ::memory::{MemoryReport, MemoryUsage};
+
+#[derive(Debug)]
+pub enum FontError {
+    /// The data is not a valid TrueType or OpenType font
+    Parse(FaceParsingError),
+}
+
+impl Display for FontError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            FontError::Parse(ref err) => write!(f, "Font Parse Error: {}", err),
+        }
+    }
+}
+
+impl Error for FontError {
+    fn description(&self) -> &str {
+        match *self {
+            FontError::Parse(_) => "Font Parse Error",
+        }
+    }
+}
+
+impl From<FaceParsingError> for FontError {
+    fn from(err: FaceParsingError) -> FontError { FontError::Parse(err) }
+}
+
+/// Vertical metrics of a font at a given size, in pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineMetrics {
+    /// Distance from the baseline to the top of the tallest glyphs
+    pub ascent: f32,
+    /// Distance from the baseline to the bottom of the lowest glyphs, usually negative
+    pub descent: f32,
+    /// Extra space between lines
+    pub line_gap: f32,
+}
+
+impl LineMetrics {
+    /// Distance between the baselines of consecutive lines
+    #[inline]
+    pub fn line_height(&self) -> f32 { self.ascent - self.descent + self.line_gap }
+}
+
+/// Coverage bitmap of a single rasterized glyph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Glyph {
+    dimensions: Dimensions,
+    /// Horizontal offset of the left edge of the bitmap from the pen position
+    pub left: i32,
+    /// Vertical offset of the top edge of the bitmap from the baseline, negative above it
+    pub top: i32,
+    /// Distance to move the pen after this glyph
+    pub advance: f32,
+    coverage: Vec<f32>,
+}
+
+impl HasDimensions for Glyph {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl MemoryUsage for Glyph {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("coverage", &self.coverage);
+
+        report
+    }
+}
+
+impl Glyph {
+    /// Coverage of each pixel, row by row
+    #[inline]
+    pub fn coverage(&self) -> &[f32] { &self.coverage }
+
+    /// Converts the glyph into white pixels with the coverage as alpha
+    pub fn to_buffer<C>(&self) -> ColorBuffer<C> where C: Color + FromChannels {
+        ColorBuffer::from_vec(self.dimensions, self.coverage.iter().map(|&c| C::from_channels([1.0, 1.0, 1.0, c])).collect())
+    }
+}
+
+/// Adds glyph outlines in font units to a path, scaled and flipped into pixel coordinates
+struct PathBuilder<'a> {
+    path: &'a mut Path,
+    scale: f32,
+    x: f32,
+    y: f32,
+}
+
+impl<'a> PathBuilder<'a> {
+    #[inline]
+    fn map(&self, x: f32, y: f32) -> (f32, f32) {
+        (self.x + x * self.scale, self.y - y * self.scale)
+    }
+}
+
+impl<'a> OutlineBuilder for PathBuilder<'a> {
+    fn move_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.map(x, y);
+        self.path.move_to(x, y);
+    }
+
+    fn line_to(&mut self, x: f32, y: f32) {
+        let (x, y) = self.map(x, y);
+        self.path.line_to(x, y);
+    }
+
+    fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let ((x1, y1), (x, y)) = (self.map(x1, y1), self.map(x, y));
+        self.path.quad_to(x1, y1, x, y);
+    }
+
+    fn curve_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let ((x1, y1), (x2, y2), (x, y)) = (self.map(x1, y1), self.map(x2, y2), self.map(x, y));
+        self.path.cubic_to(x1, y1, x2, y2, x, y);
+    }
+
+    fn close(&mut self) {
+        self.path.close();
+    }
+}
+
+/// Font borrowing its file data
+pub struct Font<'a> {
+    face: Face<'a>,
+}
+
+impl<'a> Font<'a> {
+    /// Parses a TrueType or OpenType font
+    pub fn from_bytes(data: &'a [u8]) -> Result<Font<'a>, FontError> {
+        Font::from_collection(data, 0)
+    }
+
+    /// Parses the font at `index` in a TrueType collection
+    pub fn from_collection(data: &'a [u8], index: u32) -> Result<Font<'a>, FontError> {
+        Ok(Font { face: Face::parse(data, index)? })
+    }
+
+    #[inline]
+    fn scale(&self, size: f32) -> f32 {
+        size / self.face.units_per_em() as f32
+    }
+
+    /// Vertical metrics for text `size` pixels tall
+    pub fn metrics(&self, size: f32) -> LineMetrics {
+        let scale = self.scale(size);
+
+        LineMetrics {
+            ascent: self.face.ascender() as f32 * scale,
+            descent: self.face.descender() as f32 * scale,
+            line_gap: self.face.line_gap() as f32 * scale,
+        }
+    }
+
+    /// Returns true if the font has a glyph for the character
+    #[inline]
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.face.glyph_index(c).is_some()
+    }
+
+    /// Glyph for a character, falling back to the missing glyph
+    #[inline]
+    fn glyph_id(&self, c: char) -> GlyphId {
+        self.face.glyph_index(c).unwrap_or(GlyphId(0))
+    }
+
+    /// Horizontal advance of a character at `size`
+    pub fn advance(&self, c: char, size: f32) -> f32 {
+        self.face.glyph_hor_advance(self.glyph_id(c)).unwrap_or(0) as f32 * self.scale(size)
+    }
+
+    /// Width of a single line of text at `size`
+    pub fn measure(&self, text: &str, size: f32) -> f32 {
+        text.chars().map(|c| self.advance(c, size)).sum()
+    }
+
+    /// Adds the outline of a character to `path`, with the pen at `(x, y)` on the baseline,
+    /// returning false if the glyph has no outline, like spaces
+    pub fn outline(&self, c: char, size: f32, x: f32, y: f32, path: &mut Path) -> bool {
+        let mut builder = PathBuilder { path, scale: self.scale(size), x, y };
+
+        self.face.outline_glyph(self.glyph_id(c), &mut builder).is_some()
+    }
+
+    /// Rasterizes a character `size` pixels tall
+    pub fn rasterize(&self, c: char, size: f32) -> Glyph {
+        let id = self.glyph_id(c);
+        let scale = self.scale(size);
+
+        let advance = self.face.glyph_hor_advance(id).unwrap_or(0) as f32 * scale;
+
+        let bounds = match self.face.glyph_bounding_box(id) {
+            Some(bounds) => bounds,
+            None => return Glyph { dimensions: Dimensions::new(0, 0), left: 0, top: 0, advance, coverage: Vec::new() },
+        };
+
+        let left = (bounds.x_min as f32 * scale).floor() as i32;
+        let top = (-(bounds.y_max as f32) * scale).floor() as i32;
+
+        let right = (bounds.x_max as f32 * scale).ceil() as i32;
+        let bottom = (-(bounds.y_min as f32) * scale).ceil() as i32;
+
+        let dimensions = Dimensions::new((right - left).max(0) as u32, (bottom - top).max(0) as u32);
+
+        let mut path = Path::new();
+
+        self.outline(c, size, -left as f32, -top as f32, &mut path);
+
+        Glyph { dimensions, left, top, advance, coverage: path.coverage(dimensions) }
+    }
+
+    /// Draws a single line of text into `target`, blending it over by coverage and alpha,
+    /// with the pen starting at `(x, y)` on the baseline. Returns the pen position after the last character.
+    pub fn draw_text<P>(&self, target: &mut P, text: &str, size: f32, x: f32, y: f32, color: [f32; 4]) -> f32 where P: PixelWrite,
+                                                                                                                    P::Color: ToChannels + FromChannels {
+        let mut path = Path::new();
+        let mut pen = x;
+
+        for c in text.chars() {
+            self.outline(c, size, pen, y, &mut path);
+
+            pen += self.advance(c, size);
+        }
+
+        fill_path(target, &path, color);
+
+        pen
+    }
+}
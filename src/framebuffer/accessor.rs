@@ -40,6 +40,8 @@ impl<'a, F: 'a> FramebufferAccessor<'a, F> where F: Framebuffer {
 impl<'a, F: 'a> FramebufferAccessorMut<'a, F> where F: Framebuffer {
     #[inline]
     pub ( in ::framebuffer) fn new(index: usize, buffer: &'a mut F) -> FramebufferAccessorMut<'a, F> {
+        buffer.prepare_write();
+
         FramebufferAccessorMut { buffer, index }
     }
 
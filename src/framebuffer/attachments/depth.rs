@@ -2,6 +2,8 @@
 
 use num_traits::{NumCast, Bounded};
 
+use ::error::RenderResult;
+use ::validation::checked_cast;
 use ::numeric::FloatScalar;
 
 /// Defines a depth buffer attachment.
@@ -12,7 +14,17 @@ pub trait Depth: super::Attachment + PartialOrd {
     fn far() -> Self;
 
     /// Create the depth value from some scalar value, as derived from the vertex data.
+    ///
+    /// Values out of range of the depth type are saturated, and NaN becomes `Depth::far()`.
     fn from_scalar<N: FloatScalar>(n: N) -> Self;
+
+    /// Create the depth value from some scalar value, failing if it can't be represented exactly.
+    ///
+    /// The default implementation never fails, and saturates like `from_scalar`.
+    #[inline]
+    fn try_from_scalar<N: FloatScalar>(n: N) -> RenderResult<Self> {
+        Ok(Self::from_scalar(n))
+    }
 }
 
 impl Depth for () {
@@ -21,6 +33,7 @@ impl Depth for () {
 
     #[inline(always)]
     fn from_scalar<N: FloatScalar>(_: N) -> () { () }
+
 }
 
 macro_rules! impl_depth_primitives {
@@ -32,11 +45,64 @@ macro_rules! impl_depth_primitives {
 
                 #[inline(always)]
                 fn from_scalar<N: FloatScalar>(n: N) -> $t {
-                    <$t as NumCast>::from(n).expect("Invalid Cast")
+                    <$t as NumCast>::from(n).unwrap_or_else(|| {
+                        if n > N::zero() { <$t as Bounded>::max_value() } else { <$t as Bounded>::min_value() }
+                    })
+                }
+
+                #[inline(always)]
+                fn try_from_scalar<N: FloatScalar>(n: N) -> RenderResult<$t> {
+                    checked_cast(n)
                 }
             }
         )+
     }
 }
 
-impl_depth_primitives!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize, f32, f64);
\ No newline at end of file
+impl_depth_primitives!(i8, i16, i32, i64, u8, u16, u32, u64, isize, usize, f32, f64);
+
+/// Defines the comparison performed between a fragment depth and the existing depth in the framebuffer.
+///
+/// Depth values increase towards the camera, with `Depth::far()` being the smallest possible value,
+/// so the default test of `GreaterThanEq` passes for fragments nearer or equally near to existing fragments.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum DepthTest {
+    /// Always pass
+    Always,
+    /// Never pass
+    Never,
+    /// Pass if the new value is less than the previous
+    LessThan,
+    /// Pass if the new value is greater than the previous
+    GreaterThan,
+    /// Pass if the new value is less than or equal to the previous
+    LessThanEq,
+    /// Pass if the new value is greater than or equal to the previous
+    GreaterThanEq,
+    /// Pass only if the new value is equal to the previous
+    Equal,
+    /// Pass only if the new value is NOT equal to the previous
+    NotEqual,
+}
+
+impl Default for DepthTest {
+    fn default() -> DepthTest { DepthTest::GreaterThanEq }
+}
+
+impl DepthTest {
+    /// Performs the depth test of the new fragment depth against the existing depth
+    #[inline]
+    pub fn test<D>(&self, new: D, existing: D) -> bool where D: Depth {
+        match *self {
+            DepthTest::Always => true,
+            DepthTest::Never => false,
+            DepthTest::LessThan => new < existing,
+            DepthTest::LessThanEq => new <= existing,
+            DepthTest::GreaterThan => new > existing,
+            DepthTest::GreaterThanEq => new >= existing,
+            DepthTest::Equal => new == existing,
+            DepthTest::NotEqual => new != existing,
+        }
+    }
+}
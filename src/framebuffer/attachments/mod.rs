@@ -10,7 +10,7 @@ pub trait Attachment: ThreadSafeCopyable {}
 impl<T> Attachment for T where T: ThreadSafeCopyable {}
 
 pub use self::color::Color;
-pub use self::depth::Depth;
+pub use self::depth::{Depth, DepthTest};
 pub use self::stencil::{Stencil, StencilOp, StencilTest, StencilConfig, GenericStencilConfig};
 
 /// Marker trait only defined for `()`, an empty tuple.
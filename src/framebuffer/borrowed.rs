@@ -0,0 +1,148 @@
+//! Framebuffer over borrowed bytes
+//!
+//! Renders directly into memory owned by someone else, such as a locked SDL or X11 surface,
+//! so finished frames don't have to be converted and copied every frame.
+//!
+//! Surfaces often pad their rows to some alignment, so the row pitch in bytes can be given separately from the width.
+//! Pixel indices then include the padding, as described by `HasDimensions::stride`.
+
+use ::color::predefined::formats::RGBAu8Color;
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::pixels::bytes::ByteOrder;
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::{FramebufferBase, UnsafeFramebuffer, Framebuffer};
+use super::attachments::{Depth, ColorDepthAttachments};
+
+/// Framebuffer writing 8-bit colors straight into a user-provided byte slice,
+/// with an owned depth buffer of type `D` and no stencil buffer.
+pub struct BorrowedFramebuffer<'a, D: Depth = f32> {
+    bytes: &'a mut [u8],
+    dimensions: Dimensions,
+    stride: usize,
+    order: ByteOrder,
+    depth: Vec<D>,
+}
+
+impl<'a, D: Depth> BorrowedFramebuffer<'a, D> {
+    /// Wraps `bytes` as a framebuffer of the given dimensions,
+    /// where each row starts `pitch` bytes after the previous one, or is tightly packed if `None`.
+    ///
+    /// Panics if the pitch is not a multiple of four bytes, or if the slice is too small for the dimensions and pitch.
+    pub fn new(bytes: &'a mut [u8], dimensions: Dimensions, pitch: Option<usize>, order: ByteOrder) -> BorrowedFramebuffer<'a, D> {
+        let width = dimensions.width as usize;
+        let pitch = pitch.unwrap_or(width * 4);
+
+        assert!(pitch >= width * 4, "Pitch must be at least four bytes per pixel");
+        assert_eq!(pitch % 4, 0, "Pitch must be a multiple of four bytes");
+
+        let stride = pitch / 4;
+
+        if dimensions.height > 0 {
+            assert!(bytes.len() >= pitch * (dimensions.height as usize - 1) + width * 4, "Byte slice is too small for the dimensions");
+        }
+
+        BorrowedFramebuffer {
+            bytes,
+            dimensions,
+            stride,
+            order,
+            depth: vec![D::far(); stride * dimensions.height as usize],
+        }
+    }
+
+    /// Returns the number of bytes between the start of one row and the next
+    #[inline]
+    pub fn pitch(&self) -> usize { self.stride * 4 }
+
+    #[inline]
+    pub fn order(&self) -> ByteOrder { self.order }
+
+    /// Returns the borrowed bytes
+    pub fn into_inner(self) -> &'a mut [u8] { self.bytes }
+}
+
+impl<'a, D: Depth> HasDimensions for BorrowedFramebuffer<'a, D> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+
+    #[inline]
+    fn stride(&self) -> usize { self.stride }
+}
+
+impl<'a, D: Depth> PixelBuffer for BorrowedFramebuffer<'a, D> {
+    type Color = RGBAu8Color;
+}
+
+impl<'a, D: Depth> PixelRead for BorrowedFramebuffer<'a, D> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> RGBAu8Color {
+        let offset = index * 4;
+        let offsets = self.order.offsets();
+
+        RGBAu8Color::new(*self.bytes.get_unchecked(offset + offsets[0]),
+                         *self.bytes.get_unchecked(offset + offsets[1]),
+                         *self.bytes.get_unchecked(offset + offsets[2]),
+                         *self.bytes.get_unchecked(offset + offsets[3]))
+    }
+}
+
+impl<'a, D: Depth> PixelWrite for BorrowedFramebuffer<'a, D> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: RGBAu8Color) {
+        let offset = index * 4;
+        let offsets = self.order.offsets();
+
+        *self.bytes.get_unchecked_mut(offset + offsets[0]) = color.x;
+        *self.bytes.get_unchecked_mut(offset + offsets[1]) = color.y;
+        *self.bytes.get_unchecked_mut(offset + offsets[2]) = color.z;
+        *self.bytes.get_unchecked_mut(offset + offsets[3]) = color.w;
+    }
+}
+
+impl<'a, D: Depth> FramebufferBase for BorrowedFramebuffer<'a, D> {
+    type Attachments = ColorDepthAttachments<RGBAu8Color, D>;
+}
+
+impl<'a, D: Depth> UnsafeFramebuffer for BorrowedFramebuffer<'a, D> {
+    #[inline]
+    unsafe fn get_depth_unchecked(&self, index: usize) -> D {
+        *self.depth.get_unchecked(index)
+    }
+
+    #[inline]
+    unsafe fn set_depth_unchecked(&mut self, index: usize, depth: D) {
+        *self.depth.get_unchecked_mut(index) = depth;
+    }
+
+    #[inline(always)]
+    unsafe fn get_stencil_unchecked(&self, _: usize) -> () { () }
+    #[inline(always)]
+    unsafe fn set_stencil_unchecked(&mut self, _: usize, _: ()) {}
+}
+
+impl<'a, D: Depth> MemoryUsage for BorrowedFramebuffer<'a, D> {
+    /// Only the owned depth buffer, since the color bytes are borrowed
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("depth", &self.depth);
+
+        report
+    }
+}
+
+impl<'a, D: Depth> Framebuffer for BorrowedFramebuffer<'a, D> {
+    fn clear(&mut self, color: RGBAu8Color) {
+        for y in 0..self.dimensions.height as usize {
+            for index in y * self.stride..y * self.stride + self.dimensions.width as usize {
+                unsafe { self.set_pixel_unchecked(index, color); }
+            }
+        }
+
+        for depth in &mut self.depth {
+            *depth = D::far();
+        }
+    }
+}
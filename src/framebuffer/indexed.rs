@@ -0,0 +1,258 @@
+//! Indexed color framebuffer
+//!
+//! Stores an 8-bit palette index per pixel, like the framebuffers of old consoles and fantasy consoles.
+//! Shaders and blending work on full RGBA colors, which are reduced to the nearest palette entry as they are written,
+//! optionally with ordered dithering to approximate the colors in between.
+
+use ::color::predefined::formats::{RGBAf32Color, RGBAu8Color};
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::{FramebufferBase, UnsafeFramebuffer, Framebuffer};
+use super::attachments::{Depth, ColorDepthAttachments};
+
+/// 4x4 Bayer matrix for ordered dithering
+pub ( in ::framebuffer ) const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Up to 256 colors indexed by the pixels of an `IndexedFramebuffer`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    colors: Vec<RGBAu8Color>,
+}
+
+impl Palette {
+    /// Creates a palette from the given colors.
+    ///
+    /// Panics if there are no colors or more than 256.
+    pub fn new(colors: Vec<RGBAu8Color>) -> Palette {
+        assert!(!colors.is_empty() && colors.len() <= 256, "Palettes must have between 1 and 256 colors");
+
+        Palette { colors }
+    }
+
+    /// The 16 color palette of the PICO-8 fantasy console
+    pub fn pico8() -> Palette {
+        let rgb = [
+            0x000000, 0x1D2B53, 0x7E2553, 0x008751, 0xAB5236, 0x5F574F, 0xC2C3C7, 0xFFF1E8,
+            0xFF004D, 0xFFA300, 0xFFEC27, 0x00E436, 0x29ADFF, 0x83769C, 0xFF77A8, 0xFFCCAA,
+        ];
+
+        Palette::new(rgb.iter().map(|&c: &u32| RGBAu8Color::new((c >> 16) as u8, (c >> 8) as u8, c as u8, 255)).collect())
+    }
+
+    #[inline]
+    pub fn colors(&self) -> &[RGBAu8Color] { &self.colors }
+
+    #[inline]
+    pub fn len(&self) -> usize { self.colors.len() }
+
+    /// Color of the given palette entry, or transparent black for indices past the end of the palette
+    #[inline]
+    pub fn get(&self, index: u8) -> RGBAu8Color {
+        self.colors.get(index as usize).cloned().unwrap_or(RGBAu8Color::new(0, 0, 0, 0))
+    }
+
+    /// Index of the palette entry nearest to the given color, by squared distance of all four channels
+    pub fn nearest(&self, color: RGBAf32Color) -> u8 {
+        let target = color * 255.0;
+
+        let mut best = (0, ::std::f32::INFINITY);
+
+        for (i, c) in self.colors.iter().enumerate() {
+            let d = RGBAf32Color::new(c.x as f32, c.y as f32, c.z as f32, c.w as f32) - target;
+
+            let distance = d.dot(&d);
+
+            if distance < best.1 {
+                best = (i, distance);
+            }
+        }
+
+        best.0 as u8
+    }
+}
+
+/// Dithering applied when colors are reduced to the palette
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Dither {
+    /// Always use the nearest color
+    None,
+    /// Offset colors by a 4x4 Bayer matrix before choosing the nearest color,
+    /// with the offsets spanning `strength` around zero.
+    ///
+    /// A good strength is about the distance between neighboring palette colors, such as `0.25` for a 4 level ramp.
+    Ordered(f32),
+}
+
+impl Default for Dither {
+    fn default() -> Dither { Dither::None }
+}
+
+/// Framebuffer storing palette indices, with an owned depth buffer of type `D` and no stencil buffer.
+#[derive(Debug, Clone)]
+pub struct IndexedFramebuffer<D: Depth = f32> {
+    dimensions: Dimensions,
+    palette: Palette,
+    dither: Dither,
+    indices: Vec<u8>,
+    depth: Vec<D>,
+}
+
+impl<D: Depth> IndexedFramebuffer<D> {
+    /// Creates a framebuffer with every pixel set to the first palette entry
+    pub fn with_dimensions(dimensions: Dimensions, palette: Palette) -> IndexedFramebuffer<D> {
+        IndexedFramebuffer {
+            dimensions,
+            palette,
+            dither: Dither::None,
+            indices: vec![0; dimensions.area()],
+            depth: vec![D::far(); dimensions.area()],
+        }
+    }
+
+    #[inline]
+    pub fn palette(&self) -> &Palette { &self.palette }
+
+    /// Replaces the palette. Existing indices are kept, so this can be used for palette cycling effects.
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    #[inline]
+    pub fn dither(&self) -> Dither { self.dither }
+
+    pub fn set_dither(&mut self, dither: Dither) {
+        self.dither = dither;
+    }
+
+    /// Palette index of every pixel, in row-major order
+    #[inline]
+    pub fn indices(&self) -> &[u8] { &self.indices }
+
+    #[inline]
+    pub fn indices_mut(&mut self) -> &mut [u8] { &mut self.indices }
+
+    /// Palette index for a color written at the given pixel index
+    fn quantize(&self, index: usize, color: RGBAf32Color) -> u8 {
+        match self.dither {
+            Dither::None => self.palette.nearest(color),
+            Dither::Ordered(strength) => {
+                let Coordinate { x, y } = Coordinate::from_index(index, self.dimensions);
+
+                let threshold = (BAYER_4X4[(y % 4) as usize][(x % 4) as usize] as f32 + 0.5) / 16.0 - 0.5;
+
+                let offset = threshold * strength;
+
+                self.palette.nearest(color + RGBAf32Color::new(offset, offset, offset, 0.0))
+            }
+        }
+    }
+}
+
+impl<D: Depth> HasDimensions for IndexedFramebuffer<D> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<D: Depth> PixelBuffer for IndexedFramebuffer<D> {
+    type Color = RGBAf32Color;
+}
+
+impl<D: Depth> PixelRead for IndexedFramebuffer<D> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> RGBAf32Color {
+        let c = self.palette.get(*self.indices.get_unchecked(index));
+
+        RGBAf32Color::new(c.x as f32, c.y as f32, c.z as f32, c.w as f32) / 255.0
+    }
+}
+
+impl<D: Depth> PixelWrite for IndexedFramebuffer<D> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: RGBAf32Color) {
+        let i = self.quantize(index, color);
+
+        *self.indices.get_unchecked_mut(index) = i;
+    }
+}
+
+impl<D: Depth> FramebufferBase for IndexedFramebuffer<D> {
+    type Attachments = ColorDepthAttachments<RGBAf32Color, D>;
+}
+
+impl<D: Depth> UnsafeFramebuffer for IndexedFramebuffer<D> {
+    #[inline]
+    unsafe fn get_depth_unchecked(&self, index: usize) -> D {
+        *self.depth.get_unchecked(index)
+    }
+
+    #[inline]
+    unsafe fn set_depth_unchecked(&mut self, index: usize, depth: D) {
+        *self.depth.get_unchecked_mut(index) = depth;
+    }
+
+    #[inline(always)]
+    unsafe fn get_stencil_unchecked(&self, _: usize) -> () { () }
+    #[inline(always)]
+    unsafe fn set_stencil_unchecked(&mut self, _: usize, _: ()) {}
+}
+
+impl<D: Depth> MemoryUsage for IndexedFramebuffer<D> {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("indices", &self.indices);
+        report.add_vec("palette", &self.palette.colors);
+        report.add_vec("depth", &self.depth);
+
+        report
+    }
+}
+
+impl<D: Depth> Framebuffer for IndexedFramebuffer<D> {
+    /// Clears to the palette entry nearest to `color`, without dithering
+    fn clear(&mut self, color: RGBAf32Color) {
+        let index = self.palette.nearest(color);
+
+        for i in &mut self.indices {
+            *i = index;
+        }
+
+        for depth in &mut self.depth {
+            *depth = D::far();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_quantize() {
+        let palette = Palette::new(vec![RGBAu8Color::new(0, 0, 0, 255), RGBAu8Color::new(255, 255, 255, 255)]);
+
+        let mut framebuffer = IndexedFramebuffer::<f32>::with_dimensions(Dimensions::new(4, 4), palette);
+
+        unsafe { framebuffer.set_pixel_unchecked(0, RGBAf32Color::new(0.8, 0.8, 0.8, 1.0)); }
+
+        assert_eq!(framebuffer.indices()[0], 1);
+
+        framebuffer.set_dither(Dither::Ordered(1.0));
+
+        let gray = RGBAf32Color::new(0.5, 0.5, 0.5, 1.0);
+
+        for index in 0..16 {
+            unsafe { framebuffer.set_pixel_unchecked(index, gray); }
+        }
+
+        // Mid gray dithers to half black and half white
+        assert_eq!(framebuffer.indices().iter().filter(|&&i| i == 1).count(), 8);
+    }
+}
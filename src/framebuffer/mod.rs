@@ -4,9 +4,15 @@ pub mod accessor;
 pub mod nullbuffer;
 pub mod renderbuffer;
 pub mod texturebuffer;
+pub mod borrowed;
+pub mod indexed;
+pub mod monochrome;
 
 pub use self::attachments::Attachments;
-pub use self::renderbuffer::RenderBuffer;
+pub use self::renderbuffer::{RenderBuffer, RenderBufferSnapshot};
+pub use self::borrowed::BorrowedFramebuffer;
+pub use self::indexed::{IndexedFramebuffer, Palette, Dither};
+pub use self::monochrome::{MonochromeBuffer, MonochromeDither};
 
 use ::error::{RenderResult, RenderError};
 
@@ -17,7 +23,10 @@ use self::types::{ColorAttachment, DepthAttachment, StencilAttachment};
 use self::accessor::{FramebufferAccessor, FramebufferAccessorMut};
 
 /// Framebuffer base trait defining any attachments and prerequisite traits
-pub trait FramebufferBase: Sized + Clone + HasDimensions + PixelWrite + 'static {
+///
+/// Framebuffers don't have to be `Clone` or `'static`, so they can borrow memory owned by someone else,
+/// like `BorrowedFramebuffer` does.
+pub trait FramebufferBase: Sized + HasDimensions + PixelWrite {
     /// Associated type for the framebuffer attachments
     type Attachments: Attachments;
 }
@@ -40,7 +49,7 @@ pub trait Framebuffer: UnsafeFramebuffer {
         let dim = self.dimensions();
 
         if dim.in_bounds(coord) {
-            Ok(FramebufferAccessor::new(coord.into_index(dim), self))
+            Ok(FramebufferAccessor::new(self.index_of(coord), self))
         } else {
             throw!(RenderError::InvalidPixelCoordinate);
         }
@@ -50,7 +59,7 @@ pub trait Framebuffer: UnsafeFramebuffer {
         let dim = self.dimensions();
 
         if dim.in_bounds(coord) {
-            Ok(FramebufferAccessorMut::new(coord.into_index(dim), self))
+            Ok(FramebufferAccessorMut::new(self.index_of(coord), self))
         } else {
             throw!(RenderError::InvalidPixelCoordinate);
         }
@@ -0,0 +1,223 @@
+//! 1-bit monochrome buffer
+//!
+//! Packs one bit per pixel in the layout used by most e-ink and 1-bit LCD controllers: rows padded to whole bytes,
+//! with the leftmost pixel in the most significant bit. Rendering happens into a regular luma buffer,
+//! which is then reduced to black and white with `MonochromeBuffer::from_luma`.
+
+use ::color::ToChannels;
+use ::color::predefined::formats::Ru8Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::indexed::BAYER_4X4;
+
+/// How brightness values are reduced to black and white
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MonochromeDither {
+    /// Pixels brighter than the given threshold are white
+    Threshold(f32),
+    /// Thresholds from a 4x4 Bayer matrix, which gives a regular pattern that stays stable between frames
+    Ordered,
+    /// Floyd–Steinberg error diffusion, which looks best for still images
+    FloydSteinberg,
+}
+
+impl Default for MonochromeDither {
+    fn default() -> MonochromeDither { MonochromeDither::Threshold(0.5) }
+}
+
+/// Bit-packed black and white pixels, where set bits are white
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonochromeBuffer {
+    dimensions: Dimensions,
+    row_bytes: usize,
+    bits: Vec<u8>,
+}
+
+impl MonochromeBuffer {
+    /// Creates an all black buffer
+    pub fn new(dimensions: Dimensions) -> MonochromeBuffer {
+        let row_bytes = (dimensions.width as usize + 7) / 8;
+
+        MonochromeBuffer {
+            dimensions,
+            row_bytes,
+            bits: vec![0; row_bytes * dimensions.height as usize],
+        }
+    }
+
+    /// Converts a luma buffer, taking the first channel of each color as its brightness in `0..1`.
+    ///
+    /// Buffers with more channels should be converted to luma first, otherwise only the red channel is used.
+    pub fn from_luma<P>(buffer: &P, dither: MonochromeDither) -> MonochromeBuffer where P: PixelRead, P::Color: ToChannels {
+        let dimensions = buffer.dimensions();
+        let (width, height) = (dimensions.width as usize, dimensions.height as usize);
+
+        let mut out = MonochromeBuffer::new(dimensions);
+
+        let mut luma = Vec::with_capacity(dimensions.area());
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                let c = unsafe { buffer.get_pixel_unchecked(buffer.index_of(Coordinate::new(x, y))) }.to_channels();
+
+                luma.push(c[0]);
+            }
+        }
+
+        for y in 0..height {
+            for x in 0..width {
+                let l = luma[y * width + x];
+
+                let white = match dither {
+                    MonochromeDither::Threshold(threshold) => l > threshold,
+                    MonochromeDither::Ordered => l > (BAYER_4X4[y % 4][x % 4] as f32 + 0.5) / 16.0,
+                    MonochromeDither::FloydSteinberg => {
+                        let white = l > 0.5;
+
+                        let error = l - if white { 1.0 } else { 0.0 };
+
+                        let mut diffuse = |dx: isize, dy: usize, weight: f32| {
+                            let nx = x as isize + dx;
+
+                            if nx >= 0 && (nx as usize) < width && y + dy < height {
+                                luma[(y + dy) * width + nx as usize] += error * weight;
+                            }
+                        };
+
+                        diffuse(1, 0, 7.0 / 16.0);
+                        diffuse(-1, 1, 3.0 / 16.0);
+                        diffuse(0, 1, 5.0 / 16.0);
+                        diffuse(1, 1, 1.0 / 16.0);
+
+                        white
+                    }
+                };
+
+                out.set(Coordinate::new(x as u32, y as u32), white);
+            }
+        }
+
+        out
+    }
+
+    /// Number of bytes in each row of packed pixels
+    #[inline]
+    pub fn row_bytes(&self) -> usize { self.row_bytes }
+
+    /// The packed pixels, ready to send to a display controller
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] { &self.bits }
+
+    #[inline]
+    fn bit(&self, coord: Coordinate) -> (usize, u8) {
+        (coord.y as usize * self.row_bytes + coord.x as usize / 8, 0x80 >> (coord.x % 8))
+    }
+
+    /// Whether the pixel at the given coordinate is white. Panics if the coordinate is out of bounds.
+    #[inline]
+    pub fn get(&self, coord: Coordinate) -> bool {
+        assert!(self.dimensions.in_bounds(coord), "Coordinate out of bounds");
+
+        let (byte, mask) = self.bit(coord);
+
+        self.bits[byte] & mask != 0
+    }
+
+    /// Sets the pixel at the given coordinate to white or black. Panics if the coordinate is out of bounds.
+    #[inline]
+    pub fn set(&mut self, coord: Coordinate, white: bool) {
+        assert!(self.dimensions.in_bounds(coord), "Coordinate out of bounds");
+
+        let (byte, mask) = self.bit(coord);
+
+        if white {
+            self.bits[byte] |= mask;
+        } else {
+            self.bits[byte] &= !mask;
+        }
+    }
+
+    /// Swaps black and white, for displays where set bits are black
+    pub fn invert(&mut self) {
+        for byte in &mut self.bits {
+            *byte = !*byte;
+        }
+    }
+}
+
+impl HasDimensions for MonochromeBuffer {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl PixelBuffer for MonochromeBuffer {
+    type Color = Ru8Color;
+}
+
+impl PixelRead for MonochromeBuffer {
+    /// Reads pixels as `0` for black and `255` for white
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> Ru8Color {
+        let white = self.get(Coordinate::from_index(index, self.dimensions));
+
+        Ru8Color::new(if white { 255 } else { 0 })
+    }
+}
+
+impl PixelWrite for MonochromeBuffer {
+    /// Writes pixels of at least `128` as white, without dithering
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: Ru8Color) {
+        let coord = Coordinate::from_index(index, self.dimensions);
+
+        self.set(coord, color.x >= 128);
+    }
+}
+
+impl MemoryUsage for MonochromeBuffer {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("bits", &self.bits);
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::Rf32Color;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_packing() {
+        let mut buffer = MonochromeBuffer::new(Dimensions::new(10, 2));
+
+        buffer.set(Coordinate::new(0, 0), true);
+        buffer.set(Coordinate::new(9, 1), true);
+
+        assert_eq!(buffer.row_bytes(), 2);
+        assert_eq!(buffer.as_bytes(), &[0x80, 0x00, 0x00, 0x40]);
+    }
+
+    #[test]
+    fn test_dithering() {
+        let gray = ColorBuffer::filled(Dimensions::new(8, 8), Rf32Color::new(0.5));
+
+        let count = |buffer: &MonochromeBuffer| buffer.as_bytes().iter().map(|b| b.count_ones()).sum::<u32>();
+
+        assert_eq!(count(&MonochromeBuffer::from_luma(&gray, MonochromeDither::Ordered)), 32);
+
+        let diffused = count(&MonochromeBuffer::from_luma(&gray, MonochromeDither::FloydSteinberg));
+
+        assert!(diffused >= 28 && diffused <= 36);
+
+        let buffer = MonochromeBuffer::from_luma(&gray, MonochromeDither::Threshold(0.25));
+
+        assert!(buffer.as_bytes().iter().all(|&b| b == 0xFF));
+    }
+}
@@ -2,6 +2,7 @@
 
 use ::geometry::{Dimensions, HasDimensions};
 use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::memory::{MemoryReport, MemoryUsage};
 
 use super::{FramebufferBase, UnsafeFramebuffer, Framebuffer, attachments};
 
@@ -48,6 +49,10 @@ impl UnsafeFramebuffer for NullFramebuffer {
     unsafe fn set_stencil_unchecked(&mut self, _: usize, _: ()) {}
 }
 
+impl MemoryUsage for NullFramebuffer {
+    fn memory_report(&self) -> MemoryReport { MemoryReport::new() }
+}
+
 impl Framebuffer for NullFramebuffer {
     #[inline(always)]
     fn clear(&mut self, _: ()) {}
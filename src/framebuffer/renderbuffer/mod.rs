@@ -1,6 +1,24 @@
 //! An efficient framebuffer implementation
+//!
+//! The pixels of a `RenderBuffer` are reference counted, so `snapshot` can hand out an immutable copy of the
+//! current frame without copying anything. The next write to the framebuffer copies the pixels if any snapshot
+//! is still alive, so a UI or encoder thread can keep reading the last completed frame while the next one is rendered:
+//!
+//! ```ignore
+//! let frame = pipeline.framebuffer().snapshot();
+//!
+//! sender.send(frame).unwrap();
+//!
+//! pipeline.framebuffer_mut().clear(RGBAf32Color::default());
+//! ```
+
+use std::mem::size_of;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use ::geometry::{Dimensions, HasDimensions};
+use ::memory::{MemoryReport, MemoryUsage};
+use ::parallel::TrustedThreadSafe;
 use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
 
 use super::{FramebufferBase, UnsafeFramebuffer, Framebuffer, Attachments};
@@ -8,8 +26,15 @@ use super::attachments::{Color, Depth, Stencil};
 use super::types::{ColorAttachment, DepthAttachment, StencilAttachment};
 
 pub mod iterator;
+pub mod snapshot;
 
 pub use self::iterator::{RenderBufferIter, RenderBufferIterMut};
+pub use self::snapshot::RenderBufferSnapshot;
+
+/// Pixel storage shared between a `RenderBuffer` and its snapshots
+///
+/// It is only ever written to while the `RenderBuffer` holds the only reference to it.
+pub ( in ::framebuffer::renderbuffer) type SharedPixels<A> = Arc<TrustedThreadSafe<Vec<RenderBufferAttachments<A>>>>;
 
 /// Interlaced framebuffer for more cache-friendly access
 #[derive(Debug, Clone, Copy)]
@@ -33,14 +58,20 @@ impl<A: Attachments> Default for RenderBufferAttachments<A> {
 /// it cannot be re-used later as a texture without copying the attachments out.
 pub struct RenderBuffer<A: Attachments> {
     dimensions: Dimensions,
-    buffer: Vec<RenderBufferAttachments<A>>,
+    buffer: SharedPixels<A>,
+    /// Set whenever the pixels are handed out to a snapshot or clone, and cleared once they are unique again
+    shared: AtomicBool,
 }
 
+/// Clones share the pixels until either of them is written to
 impl<A: Attachments> Clone for RenderBuffer<A> {
     fn clone(&self) -> RenderBuffer<A> {
+        self.shared.store(true, Ordering::Relaxed);
+
         RenderBuffer {
+            dimensions: self.dimensions,
             buffer: self.buffer.clone(),
-            ..*self
+            shared: AtomicBool::new(true),
         }
     }
 }
@@ -48,28 +79,82 @@ impl<A: Attachments> Clone for RenderBuffer<A> {
 impl<A: Attachments> RenderBuffer<A> {
     /// Create a new empty `RenderBuffer` with no allocated pixels.
     pub fn new() -> RenderBuffer<A> {
-        RenderBuffer {
-            dimensions: Dimensions::new(0, 0),
-            buffer: Vec::new()
-        }
+        RenderBuffer::from_pixels(Dimensions::new(0, 0), Vec::new())
     }
 
     /// Create a new empty `Renderbuffer` with the given number of pixels allocated.
     pub fn with_dimensions(dimensions: Dimensions) -> RenderBuffer<A> {
+        RenderBuffer::from_pixels(dimensions, vec![RenderBufferAttachments::default(); dimensions.area()])
+    }
+
+    fn from_pixels(dimensions: Dimensions, pixels: Vec<RenderBufferAttachments<A>>) -> RenderBuffer<A> {
         RenderBuffer {
             dimensions,
-            buffer: vec![RenderBufferAttachments::default(); dimensions.area()]
+            buffer: Arc::new(TrustedThreadSafe::new(pixels)),
+            shared: AtomicBool::new(false),
         }
     }
 
     /// Return an efficient iterator for `RenderBuffer` pixels
     pub fn iter<'a>(&'a self) -> RenderBufferIter<'a, A> {
-        RenderBufferIter { iter: self.buffer.iter() }
+        RenderBufferIter { iter: self.pixels().iter() }
     }
 
     /// Return an efficient iterator for mutating `RenderBuffer` pixels
     pub fn iter_mut<'a>(&'a mut self) -> RenderBufferIterMut<'a, A> {
-        RenderBufferIterMut { iter: self.buffer.iter_mut() }
+        RenderBufferIterMut { iter: self.pixels_mut().iter_mut() }
+    }
+
+    /// Returns an immutable view of the current pixels, which stays unchanged no matter what is drawn afterwards.
+    ///
+    /// Taking a snapshot is cheap, but the next write to the framebuffer copies all pixels
+    /// if the snapshot is still alive by then.
+    pub fn snapshot(&self) -> RenderBufferSnapshot<A> {
+        self.shared.store(true, Ordering::Relaxed);
+
+        RenderBufferSnapshot {
+            dimensions: self.dimensions,
+            buffer: self.buffer.clone(),
+        }
+    }
+
+    /// Returns true if the pixels are still shared with a snapshot or clone, so the next write will copy them
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.buffer) > 1
+    }
+
+    /// Makes sure no snapshot or clone shares the pixels, copying them if needed
+    fn make_unique(&mut self) {
+        if Arc::get_mut(&mut self.buffer).is_none() {
+            let pixels = self.pixels().clone();
+
+            self.buffer = Arc::new(TrustedThreadSafe::new(pixels));
+        }
+
+        self.shared.store(false, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn pixels(&self) -> &Vec<RenderBufferAttachments<A>> {
+        TrustedThreadSafe::as_ref(&self.buffer)
+    }
+
+    /// Pixels for writing, copied first if they may be shared
+    #[inline]
+    fn pixels_mut(&mut self) -> &mut Vec<RenderBufferAttachments<A>> {
+        self.prepare_write();
+
+        self.pixels_unchecked_mut()
+    }
+
+    /// Pixels for writing without copying them, for the unchecked setters.
+    ///
+    /// `prepare_write` must have been called since the pixels were last shared, which is all it takes,
+    /// since nothing can share the pixels while the framebuffer is borrowed mutably.
+    /// The parallel rasterizer calls it once up front and then writes through this from every thread.
+    #[inline(always)]
+    fn pixels_unchecked_mut(&mut self) -> &mut Vec<RenderBufferAttachments<A>> {
+        TrustedThreadSafe::as_mut(&self.buffer)
     }
 }
 
@@ -85,14 +170,20 @@ impl<A: Attachments> PixelBuffer for RenderBuffer<A> {
 impl<A: Attachments> PixelRead for RenderBuffer<A> {
     #[inline]
     unsafe fn get_pixel_unchecked(&self, index: usize) -> Self::Color {
-        self.buffer.get_unchecked(index).color
+        self.pixels().get_unchecked(index).color
     }
 }
 
 impl<A: Attachments> PixelWrite for RenderBuffer<A> {
     #[inline]
     unsafe fn set_pixel_unchecked(&mut self, index: usize, color: Self::Color) {
-        self.buffer.get_unchecked_mut(index).color = color;
+        self.pixels_unchecked_mut().get_unchecked_mut(index).color = color;
+    }
+
+    fn prepare_write(&mut self) {
+        if self.shared.load(Ordering::Relaxed) {
+            self.make_unique();
+        }
     }
 }
 
@@ -103,32 +194,63 @@ impl<A: Attachments> FramebufferBase for RenderBuffer<A> {
 impl<A: Attachments> UnsafeFramebuffer for RenderBuffer<A> {
     #[inline]
     unsafe fn get_depth_unchecked(&self, index: usize) -> DepthAttachment<Self> {
-        self.buffer.get_unchecked(index).depth
+        self.pixels().get_unchecked(index).depth
     }
 
     #[inline]
     unsafe fn set_depth_unchecked(&mut self, index: usize, depth: DepthAttachment<Self>) {
-        self.buffer.get_unchecked_mut(index).depth = depth;
+        self.pixels_unchecked_mut().get_unchecked_mut(index).depth = depth;
     }
 
     #[inline]
     unsafe fn get_stencil_unchecked(&self, index: usize) -> StencilAttachment<Self> {
-        self.buffer.get_unchecked(index).stencil
+        self.pixels().get_unchecked(index).stencil
     }
 
     #[inline]
     unsafe fn set_stencil_unchecked(&mut self, index: usize, stencil: StencilAttachment<Self>) {
-        self.buffer.get_unchecked_mut(index).stencil = stencil;
+        self.pixels_unchecked_mut().get_unchecked_mut(index).stencil = stencil;
+    }
+}
+
+impl<A: Attachments> MemoryUsage for RenderBuffer<A> {
+    fn memory_report(&self) -> MemoryReport {
+        let capacity = self.pixels().capacity();
+
+        let color = capacity * size_of::<A::Color>();
+        let depth = capacity * size_of::<A::Depth>();
+        let stencil = capacity * size_of::<A::Stencil>();
+
+        let mut report = MemoryReport::new();
+
+        report.add("color", color);
+        report.add("depth", depth);
+        report.add("stencil", stencil);
+
+        // Attachments are interleaved, so alignment may add padding between them
+        report.add("padding", capacity * size_of::<RenderBufferAttachments<A>>() - color - depth - stencil);
+
+        report
     }
 }
 
 impl<A: Attachments> Framebuffer for RenderBuffer<A> {
     fn clear(&mut self, color: ColorAttachment<Self>) {
-        for mut a in &mut self.buffer {
-            *a = RenderBufferAttachments {
-                color,
-                ..RenderBufferAttachments::default()
-            };
+        trace_event!(pixels = self.pixels().len(), "buffer cleared");
+
+        let cleared = RenderBufferAttachments {
+            color,
+            ..RenderBufferAttachments::default()
+        };
+
+        // Every pixel is overwritten anyway, so don't copy pixels still shared with a snapshot
+        if self.is_shared() {
+            *self = RenderBuffer::from_pixels(self.dimensions, vec![cleared; self.dimensions.area()]);
+            return;
+        }
+
+        for a in self.pixels_mut() {
+            *a = cleared;
         }
     }
 }
\ No newline at end of file
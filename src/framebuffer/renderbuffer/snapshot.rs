@@ -0,0 +1,136 @@
+//! Immutable snapshots of `RenderBuffer` pixels
+
+use std::sync::Arc;
+
+use ::attachments::Attachments;
+use ::geometry::{Coordinate, Dimensions, HasDimensions};
+use ::parallel::TrustedThreadSafe;
+use ::pixels::{PixelBuffer, PixelRead};
+
+use super::{RenderBuffer, RenderBufferAttachments, SharedPixels};
+use super::iterator::RenderBufferIter;
+
+/// Immutable view of the pixels of a `RenderBuffer` at the time `RenderBuffer::snapshot` was called.
+///
+/// Snapshots are cheap to clone and can be sent to other threads. The `RenderBuffer` copies its pixels
+/// before writing to them while any snapshot is alive, so dropping snapshots early avoids that copy.
+pub struct RenderBufferSnapshot<A: Attachments> {
+    pub ( in ::framebuffer::renderbuffer) dimensions: Dimensions,
+    pub ( in ::framebuffer::renderbuffer) buffer: SharedPixels<A>,
+}
+
+impl<A: Attachments> Clone for RenderBufferSnapshot<A> {
+    fn clone(&self) -> RenderBufferSnapshot<A> {
+        RenderBufferSnapshot {
+            dimensions: self.dimensions,
+            buffer: self.buffer.clone(),
+        }
+    }
+}
+
+impl<A: Attachments> RenderBufferSnapshot<A> {
+    #[inline]
+    fn pixels(&self) -> &Vec<RenderBufferAttachments<A>> {
+        TrustedThreadSafe::as_ref(&self.buffer)
+    }
+
+    /// Return an efficient iterator for the snapshot pixels
+    pub fn iter<'a>(&'a self) -> RenderBufferIter<'a, A> {
+        RenderBufferIter { iter: self.pixels().iter() }
+    }
+
+    /// Depth value at the given coordinate, or `None` if it is out of bounds
+    pub fn depth(&self, coord: Coordinate) -> Option<A::Depth> {
+        if self.dimensions.in_bounds(coord) {
+            Some(self.pixels()[self.index_of(coord)].depth)
+        } else {
+            None
+        }
+    }
+
+    /// Stencil value at the given coordinate, or `None` if it is out of bounds
+    pub fn stencil(&self, coord: Coordinate) -> Option<A::Stencil> {
+        if self.dimensions.in_bounds(coord) {
+            Some(self.pixels()[self.index_of(coord)].stencil)
+        } else {
+            None
+        }
+    }
+
+    /// Turns the snapshot back into a `RenderBuffer`, which only copies the pixels if other snapshots of them remain
+    pub fn into_renderbuffer(self) -> RenderBuffer<A> {
+        let RenderBufferSnapshot { dimensions, buffer } = self;
+
+        match Arc::try_unwrap(buffer) {
+            Ok(pixels) => RenderBuffer::from_pixels(dimensions, pixels.into_inner()),
+            Err(buffer) => RenderBuffer::from_pixels(dimensions, TrustedThreadSafe::as_ref(&buffer).clone()),
+        }
+    }
+}
+
+impl<A: Attachments> HasDimensions for RenderBufferSnapshot<A> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<A: Attachments> PixelBuffer for RenderBufferSnapshot<A> {
+    type Color = <A as Attachments>::Color;
+}
+
+impl<A: Attachments> PixelRead for RenderBufferSnapshot<A> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> Self::Color {
+        self.pixels().get_unchecked(index).color
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::attachments::predefined::ColorDepthAttachments;
+    use ::color::predefined::formats::RGBAf32Color;
+    use ::framebuffer::Framebuffer;
+    use ::pixels::PixelWrite;
+
+    use super::*;
+
+    #[test]
+    fn test_snapshot_copy_on_write() {
+        let red = RGBAf32Color::new(1.0, 0.0, 0.0, 1.0);
+        let blue = RGBAf32Color::new(0.0, 0.0, 1.0, 1.0);
+
+        let mut framebuffer = RenderBuffer::<ColorDepthAttachments<RGBAf32Color, f32>>::with_dimensions(Dimensions::new(4, 4));
+
+        framebuffer.clear(red);
+
+        let snapshot = framebuffer.snapshot();
+
+        assert!(framebuffer.is_shared());
+
+        framebuffer.pixel_mut(Coordinate::new(1, 1)).unwrap().set(blue);
+
+        // The write copied the pixels, leaving the snapshot alone
+        assert!(!framebuffer.is_shared());
+        assert_eq!(snapshot.pixel_ref(Coordinate::new(1, 1)).unwrap().get(), red);
+        assert_eq!(framebuffer.pixel_ref(Coordinate::new(1, 1)).unwrap().get(), blue);
+
+        // Clearing while shared replaces the pixels instead of copying them
+        let second = framebuffer.snapshot();
+
+        framebuffer.clear(red);
+
+        assert_eq!(second.pixel_ref(Coordinate::new(1, 1)).unwrap().get(), blue);
+        assert_eq!(framebuffer.pixel_ref(Coordinate::new(1, 1)).unwrap().get(), red);
+
+        drop(snapshot);
+
+        // Dropped snapshots no longer share the pixels
+        let third = framebuffer.snapshot();
+        drop(third);
+
+        assert!(!framebuffer.is_shared());
+
+        framebuffer.pixel_mut(Coordinate::new(0, 0)).unwrap().set(blue);
+
+        assert_eq!(second.into_renderbuffer().pixel_ref(Coordinate::new(1, 1)).unwrap().get(), blue);
+    }
+}
@@ -68,6 +68,24 @@ impl<'a, F: Framebuffer, C: Color> PixelRead for TextureBufferRef<'a, F, C> {
 ///
 /// Because the inner buffers are an implementation detail,
 /// the attributes given to the fields are placed on their accessor functions.
+///
+/// Fragment shaders for texture buffers return a tuple of all colors. To write only some of them,
+/// a unit struct can be declared after the texture buffer, which becomes an output struct with an `Option`
+/// for each color by name. Colors left as `None` are discarded, keeping the existing value of that attachment:
+///
+/// ```ignore
+/// declare_texture_buffer! {
+///     pub struct GBuffer {
+///         pub albedo: RGBAf32Color,
+///         pub normal: RGBAf32Color,
+///     }
+///
+///     /// Fragment shader output for `GBuffer`
+///     pub struct GBufferOutput;
+/// }
+///
+/// GBufferOutput { albedo: Some(albedo), ..GBufferOutput::discard() }.into_fragment()
+/// ```
 #[macro_export]
 macro_rules! declare_texture_buffer {
     (
@@ -125,6 +143,19 @@ macro_rules! declare_texture_buffer {
             }
         }
 
+        impl<A: $crate::attachments::Attachments> $crate::memory::MemoryUsage for $buffer_name<A>
+            where <A as $crate::attachments::Attachments>::Color: $crate::attachments::EmptyAttachment {
+            fn memory_report(&self) -> $crate::memory::MemoryReport {
+                let mut report = $crate::memory::MemoryReport::new();
+
+                $(report.add_vec(stringify!($color_name), &self.$color_name);)+
+
+                report.add_vec("depth_stencil", &self.__internal_buffer);
+
+                report
+            }
+        }
+
         impl<A: $crate::attachments::Attachments> $crate::pixels::PixelBuffer for $buffer_name<A>
             where <A as $crate::attachments::Attachments>::Color: $crate::attachments::EmptyAttachment {
             /// All texture buffer colors as a tuple
@@ -194,11 +225,72 @@ macro_rules! declare_texture_buffer {
                 }
             }
         }
-    }
+    };
+
+    (
+        $(#[$($struct_attrs:tt)*])*
+        pub struct $buffer_name:ident {
+            $(
+                $(#[$($field_attrs:tt)*])*
+                pub $color_name:ident: $color_ty:ty,
+            )+
+        }
+
+        $(#[$($output_attrs:tt)*])*
+        pub struct $output_name:ident;
+    ) => {
+        declare_texture_buffer! {
+            $(#[$($struct_attrs)*])*
+            pub struct $buffer_name {
+                $(
+                    $(#[$($field_attrs)*])*
+                    pub $color_name: $color_ty,
+                )+
+            }
+        }
+
+        $(#[$($output_attrs)*])*
+        #[derive(Clone, Copy)]
+        pub struct $output_name {
+            $(
+                $(#[$($field_attrs)*])*
+                pub $color_name: Option<$color_ty>,
+            )+
+        }
+
+        impl $output_name {
+            /// Creates an output with every color discarded
+            pub fn discard() -> $output_name {
+                $output_name { $($color_name: None,)+ }
+            }
+
+            /// Converts into a fragment writing only the colors that are `Some`,
+            /// or discarding the fragment entirely if none are.
+            pub fn into_fragment(self) -> $crate::pipeline::stages::fragment::Fragment<($($color_ty,)+)> {
+                let written = [$(self.$color_name.is_some(),)+];
+
+                let mask = written.iter().enumerate().fold(0u32, |mask, (i, &w)| if w { mask | (1 << i) } else { mask });
+
+                let colors = ($(self.$color_name.unwrap_or_else(<$color_ty as $crate::attachments::Color>::empty),)+);
+
+                if mask == 0 {
+                    $crate::pipeline::stages::fragment::Fragment::Discard
+                } else {
+                    $crate::pipeline::stages::fragment::Fragment::Targets($crate::pipeline::stages::fragment::TargetColors::__from_mask(colors, mask))
+                }
+            }
+        }
+
+        impl From<$output_name> for $crate::pipeline::stages::fragment::Fragment<($($color_ty,)+)> {
+            fn from(output: $output_name) -> $crate::pipeline::stages::fragment::Fragment<($($color_ty,)+)> {
+                output.into_fragment()
+            }
+        }
+    };
 }
 
 pub mod predefined {
-    use ::attachments::color::predefined::formats::RGBAf32Color;
+    use ::attachments::color::predefined::formats::{RGBAf32Color, RGf32Color};
 
     declare_texture_buffer! {
         /// Texture Buffer with a single RGBA 32-bit Floating Point color.
@@ -211,6 +303,21 @@ pub mod predefined {
         }
     }
 
+    declare_texture_buffer! {
+        /// Texture Buffer with an RGBA 32-bit Floating Point color and per-pixel screen-space motion vectors.
+        ///
+        /// See `post::motion` for writing motion vectors and applying motion blur.
+        pub struct RGBAf32MotionTextureBuffer {
+            /// Primary color buffer
+            pub color: RGBAf32Color,
+            /// Screen-space motion in pixels since the previous frame
+            pub motion: RGf32Color,
+        }
+
+        /// Fragment shader output for `RGBAf32MotionTextureBuffer`, so either attachment can be left unchanged
+        pub struct RGBAf32MotionOutput;
+    }
+
     #[cfg(test)]
     mod test {
         use super::*;
@@ -228,5 +335,29 @@ pub mod predefined {
 
             assert_texture(color)
         }
+
+        #[test]
+        fn test_output_targets() {
+            use ::attachments::Color;
+            use ::pipeline::stages::fragment::{Fragment, TargetColors};
+
+            let motion = RGf32Color::new(1.0, 2.0);
+
+            match (RGBAf32MotionOutput { motion: Some(motion), ..RGBAf32MotionOutput::discard() }).into_fragment() {
+                Fragment::Targets(TargetColors { colors, mask }) => {
+                    assert_eq!(mask, 0b10);
+
+                    let existing = (RGBAf32Color::new(0.5, 0.5, 0.5, 1.0), RGf32Color::new(0.0, 0.0));
+
+                    assert_eq!(colors.select_targets(existing, mask), (existing.0, motion));
+                }
+                _ => panic!("Expected a partial fragment"),
+            }
+
+            match RGBAf32MotionOutput::discard().into_fragment() {
+                Fragment::Discard => (),
+                _ => panic!("Expected a discarded fragment"),
+            }
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,365 @@
+//! Fuzzing helpers
+//!
+//! Builds arbitrary draws from raw bytes, for fuzzing the pipeline with `cargo fuzz`.
+//! Meshes built this way are always valid, with every index in range and whole primitives only,
+//! but their vertex data and the render state are arbitrary, including NaN, infinities and huge coordinates.
+//!
+//! The fuzz targets themselves live in the `fuzz` directory at the root of the repository:
+//!
+//! ```text
+//! cargo +nightly fuzz run rasterize
+//! cargo +nightly fuzz run tiles
+//! ```
+//!
+//! Every draw has to finish without panicking, and must not touch any pixel outside of its scissor rectangle,
+//! dirty regions and subsample field.
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use ::geometry::{Dimensions, Coordinate, HasDimensions, ClipVertex, Viewport, FaceWinding, PixelCenter};
+use ::primitive::{Primitive, Point, Line, Triangle};
+use ::mesh::{Mesh, SimpleVertex};
+use ::pixels::PixelRead;
+use ::framebuffer::{Framebuffer, RenderBuffer};
+use ::attachments::predefined::ColorDepthAttachments;
+use ::attachments::depth::DepthTest;
+use ::color::ColorMask;
+use ::color::blend::BlendPreset;
+use ::color::predefined::formats::RGBAf32Color;
+use ::pipeline::{Pipeline, PipelineObject, RenderState, RasterBackend};
+use ::pipeline::stages::fragment::Fragment;
+use ::pipeline::stages::rasterization::{Tile, generate_tiles, scissor_tiles};
+use ::pipeline::subsample::Subsample;
+
+/// Pipeline fuzzed draws render into
+pub type FuzzPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+/// Largest framebuffer width or height of a fuzzed draw
+pub const MAX_FUZZ_DIMENSION: u32 = 64;
+
+/// Reads values from fuzzer input, producing zeros once it runs out, so any input is a valid draw
+#[derive(Debug, Clone)]
+pub struct ByteSource<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ByteSource<'a> {
+    pub fn new(data: &'a [u8]) -> ByteSource<'a> {
+        ByteSource { data }
+    }
+
+    /// Returns true if all input has been consumed
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.data.is_empty() }
+
+    pub fn u8(&mut self) -> u8 {
+        match self.data.split_first() {
+            Some((&byte, rest)) => {
+                self.data = rest;
+                byte
+            }
+            None => 0,
+        }
+    }
+
+    pub fn u16(&mut self) -> u16 {
+        self.u8() as u16 | (self.u8() as u16) << 8
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        self.u16() as u32 | (self.u16() as u32) << 16
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.u8() & 1 == 1
+    }
+
+    /// Value from `low` to `high` inclusive, where the range must be smaller than `u16::MAX`
+    pub fn range(&mut self, low: u32, high: u32) -> u32 {
+        low + self.u16() as u32 % (high - low + 1)
+    }
+
+    /// One of the given choices, which must not be empty
+    pub fn choose<T: Copy>(&mut self, choices: &[T]) -> T {
+        choices[self.u8() as usize % choices.len()]
+    }
+
+    /// Either a special value like NaN, an infinity, the largest finite values or negative zero,
+    /// arbitrary bits, or a value from `-4` to `4` in steps of `1/8192`, which mostly lands on screen.
+    pub fn f32(&mut self) -> f32 {
+        match self.u8() % 16 {
+            0 => ::std::f32::NAN,
+            1 => ::std::f32::INFINITY,
+            2 => ::std::f32::NEG_INFINITY,
+            3 => ::std::f32::MAX,
+            4 => ::std::f32::MIN,
+            5 => -0.0,
+            6 => ::std::f32::MIN_POSITIVE,
+            7 => if self.bool() { 1e30 } else { -1e30 },
+            8 | 9 => f32::from_bits(self.u32()),
+            _ => self.u16() as i16 as f32 / 8192.0,
+        }
+    }
+
+    /// Rectangle reaching a bit beyond a framebuffer of the largest fuzzed size, with its corners in any order
+    pub fn tile(&mut self) -> Tile {
+        let mut coordinate = || Coordinate::new(self.range(0, MAX_FUZZ_DIMENSION + 16), self.range(0, MAX_FUZZ_DIMENSION + 16));
+
+        (coordinate(), coordinate())
+    }
+}
+
+/// Kind of primitive drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuzzPrimitive {
+    Points,
+    Lines,
+    Triangles,
+}
+
+impl FuzzPrimitive {
+    /// Number of vertices in each primitive
+    pub fn num_vertices(self) -> usize {
+        match self {
+            FuzzPrimitive::Points => 1,
+            FuzzPrimitive::Lines => 2,
+            FuzzPrimitive::Triangles => 3,
+        }
+    }
+}
+
+/// A single draw built from fuzzer input.
+///
+/// Vertex positions are used as clip-space `x`, `y` and `z` directly, with the vertex data as `w`.
+#[derive(Clone)]
+pub struct FuzzDraw {
+    pub dimensions: Dimensions,
+    pub threads: u32,
+    pub state: RenderState,
+    pub primitive: FuzzPrimitive,
+    pub mesh: Arc<Mesh<SimpleVertex<f32, f32>>>,
+    pub raster_backend: RasterBackend,
+    pub line_width: f64,
+    /// Whether primitives are clipped in the geometry stage before rasterization
+    pub clip: bool,
+    /// Guard band primitives are clipped against instead of the viewport, if any
+    pub guard_band: Option<f32>,
+    /// Region the draw is restricted to with `Pipeline::mark_dirty`, if any
+    pub dirty: Option<Tile>,
+}
+
+impl FuzzDraw {
+    pub fn from_bytes(data: &[u8]) -> FuzzDraw {
+        let mut bytes = ByteSource::new(data);
+
+        let dimensions = Dimensions::new(bytes.range(1, MAX_FUZZ_DIMENSION), bytes.range(1, MAX_FUZZ_DIMENSION));
+
+        let threads = bytes.range(1, 4);
+
+        let mut state = RenderState::default();
+
+        state.desc.cull_faces = bytes.choose(&[None, Some(FaceWinding::Clockwise), Some(FaceWinding::CounterClockwise)]);
+
+        state.desc.blend = bytes.choose(&[BlendPreset::Replace, BlendPreset::AlphaOver, BlendPreset::Additive,
+                                          BlendPreset::Multiply, BlendPreset::PremultipliedOver]);
+
+        state.desc.depth_test = bytes.choose(&[DepthTest::Always, DepthTest::Never, DepthTest::LessThan,
+                                               DepthTest::GreaterThan, DepthTest::LessThanEq,
+                                               DepthTest::GreaterThanEq, DepthTest::Equal]);
+
+        state.desc.tile_size = Some(Dimensions::new(bytes.range(1, MAX_FUZZ_DIMENSION), bytes.range(1, MAX_FUZZ_DIMENSION)));
+        state.desc.antialiased_lines = bytes.bool();
+        state.desc.color_mask = ColorMask { r: bytes.bool(), g: bytes.bool(), b: bytes.bool(), a: bytes.bool() };
+
+        state.scissor = if bytes.bool() { Some(bytes.tile()) } else { None };
+
+        state.viewport = if bytes.bool() {
+            Some(Viewport {
+                x: bytes.f32() as f64 * 16.0,
+                y: bytes.f32() as f64 * 16.0,
+                width: bytes.f32() as f64 * 16.0,
+                height: bytes.f32() as f64 * 16.0,
+                near: bytes.f32() as f64,
+                far: bytes.f32() as f64,
+            })
+        } else {
+            None
+        };
+
+        state.subsample = bytes.choose(&[Subsample::Off, Subsample::Interlaced, Subsample::Checkerboard]);
+        state.pixel_center = bytes.choose(&[PixelCenter::HalfInteger, PixelCenter::Integer]);
+
+        let primitive = bytes.choose(&[FuzzPrimitive::Points, FuzzPrimitive::Lines, FuzzPrimitive::Triangles]);
+        let raster_backend = bytes.choose(&[RasterBackend::EdgeFunction, RasterBackend::Scanline, RasterBackend::Hierarchical]);
+
+        let line_width = if bytes.bool() { 1.0 } else { bytes.f32() as f64 * 8.0 };
+
+        let clip = bytes.bool();
+        let guard_band = if bytes.bool() { Some(bytes.f32()) } else { None };
+        let dirty = if bytes.bool() { Some(bytes.tile()) } else { None };
+
+        let vertex_count = bytes.range(1, 32) as usize;
+        let primitive_count = bytes.range(0, 32) as usize;
+
+        let indices = (0..primitive_count * primitive.num_vertices()).map(|_| bytes.u8() as usize % vertex_count).collect();
+
+        // Vertices go last, so any remaining input ends up as vertex data
+        let vertices = (0..vertex_count).map(|_| SimpleVertex {
+            position: Point3::new(bytes.f32(), bytes.f32(), bytes.f32()),
+            data: bytes.f32(),
+        }).collect();
+
+        FuzzDraw {
+            dimensions,
+            threads,
+            state,
+            primitive,
+            mesh: Arc::new(Mesh { indices, vertices }),
+            raster_backend,
+            line_width,
+            clip,
+            guard_band,
+            dirty,
+        }
+    }
+
+    /// Creates a pipeline with the framebuffer size, thread count and render state of the draw
+    pub fn pipeline(&self) -> FuzzPipeline {
+        Pipeline::builder().framebuffer(RenderBuffer::with_dimensions(self.dimensions))
+                           .uniforms(())
+                           .threads(self.threads)
+                           .render_state(self.state)
+                           .build()
+                           .expect("Fuzzed pipeline configuration is invalid")
+    }
+
+    /// Clears the framebuffer to `fuzz_background()` and draws the mesh in white
+    pub fn draw(&self, pipeline: &mut FuzzPipeline) {
+        pipeline.framebuffer_mut().clear(fuzz_background());
+
+        pipeline.mark_all_dirty();
+
+        if let Some(region) = self.dirty {
+            pipeline.mark_dirty(region);
+        }
+
+        match self.primitive {
+            FuzzPrimitive::Points => self.draw_primitive(pipeline, Point),
+            FuzzPrimitive::Lines => self.draw_primitive(pipeline, Line),
+            FuzzPrimitive::Triangles => self.draw_primitive(pipeline, Triangle),
+        }
+    }
+
+    fn draw_primitive<T: Primitive>(&self, pipeline: &mut FuzzPipeline, primitive: T) {
+        let vertex_shader = pipeline.render_mesh(primitive, self.mesh.clone(), None);
+
+        let geometry_shader = vertex_shader.run(|vertex: &SimpleVertex<f32, f32>, _: &()| -> ClipVertex<f32, ()> {
+            ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, vertex.data), ())
+        });
+
+        let geometry_shader = match (self.clip, self.guard_band) {
+            (true, Some(guard_band)) => geometry_shader.clip_primitives_guard_band(guard_band),
+            (true, None) => geometry_shader.clip_primitives(),
+            (false, _) => geometry_shader,
+        };
+
+        geometry_shader.finish_default()
+                       .with_state_blend()
+                       .with_raster_backend(self.raster_backend)
+                       .with_line_width(self.line_width)
+                       .run(|_, _| Fragment::Color(RGBAf32Color::new(1.0, 1.0, 1.0, 1.0)));
+    }
+
+    /// Returns true if the draw may write to the given pixel
+    pub fn may_write(&self, pipeline: &FuzzPipeline, coord: Coordinate) -> bool {
+        let inside = |(start, end): Tile| start.x <= coord.x && coord.x <= end.x && start.y <= coord.y && coord.y <= end.y;
+
+        let in_dirty = match pipeline.dirty_regions() {
+            Some(dirty) => dirty.regions().iter().any(|&region| inside(region)),
+            None => true,
+        };
+
+        self.state.scissor.map_or(true, |scissor| inside(scissor)) && in_dirty &&
+            pipeline.subsample_field().covers(coord.x, coord.y)
+    }
+
+    /// Panics if the draw wrote to any pixel it may not write to
+    pub fn assert_within_bounds(&self, pipeline: &FuzzPipeline) {
+        let dimensions = pipeline.framebuffer().dimensions();
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                let coord = Coordinate::new(x, y);
+
+                if !self.may_write(pipeline, coord) {
+                    assert_eq!(pipeline.framebuffer().pixel_ref(coord).unwrap().get(), fuzz_background(),
+                               "Draw wrote to {:?} outside of the scissor, dirty regions or subsample field", coord);
+                }
+            }
+        }
+    }
+}
+
+/// Color the framebuffer is cleared to before fuzzed draws, which differs from anything a draw writes
+pub fn fuzz_background() -> RGBAf32Color {
+    RGBAf32Color::new(0.25, 0.5, 0.75, 0.125)
+}
+
+/// Builds a draw from fuzzer input, renders it, and checks that it stayed within its bounds
+pub fn fuzz_rasterize(data: &[u8]) {
+    let draw = FuzzDraw::from_bytes(data);
+
+    let mut pipeline = draw.pipeline();
+
+    draw.draw(&mut pipeline);
+    draw.assert_within_bounds(&pipeline);
+}
+
+/// Generates tiles for arbitrary framebuffer and tile sizes, including zero, and checks that they
+/// stay within the framebuffer and the scissor rectangle
+pub fn fuzz_tiles(data: &[u8]) {
+    let mut bytes = ByteSource::new(data);
+
+    let dimensions = Dimensions::new(bytes.range(0, 512), bytes.range(0, 512));
+    let tile_size = Dimensions::new(bytes.range(0, 64), bytes.range(0, 64));
+
+    let tiles = generate_tiles(dimensions, tile_size);
+
+    for &(start, end) in &tiles {
+        assert!(start.x <= end.x && start.y <= end.y, "Tile {:?} is inverted", (start, end));
+        assert!(end.x < dimensions.width && end.y < dimensions.height, "Tile {:?} is outside of {:?}", (start, end), dimensions);
+    }
+
+    let scissor = bytes.tile();
+
+    for &(start, end) in &scissor_tiles(tiles, scissor) {
+        assert!(start.x >= scissor.0.x && start.y >= scissor.0.y && end.x <= scissor.1.x && end.y <= scissor.1.y,
+                "Tile {:?} is outside of the scissor {:?}", (start, end), scissor);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::sampling::SampleRng;
+
+    use super::*;
+
+    #[test]
+    fn test_fuzz_inputs() {
+        fuzz_rasterize(&[]);
+        fuzz_rasterize(&[0xFF; 512]);
+        fuzz_tiles(&[]);
+        fuzz_tiles(&[0xFF; 32]);
+
+        let mut rng = SampleRng::new(1960);
+
+        for _ in 0..200 {
+            let data: Vec<u8> = (0..256).map(|_| rng.next_u32() as u8).collect();
+
+            fuzz_rasterize(&data);
+            fuzz_tiles(&data);
+        }
+    }
+}
@@ -0,0 +1,142 @@
+//! Camera-facing quads
+//!
+//! Billboards turn points into textured quads that always face the camera, for particles, sprites and
+//! distant vegetation. They can be built in object-space with `billboard_mesh`, which also supports
+//! cylindrical billboards that only rotate around an axis, like trees, or expanded from points in
+//! the geometry stage with `PrimitiveStorage::emit_billboard`.
+//!
+//! Texture coordinates go from `(0, 0)` at the top-left corner of the quad to `(1, 1)` at the bottom-right.
+
+use alga::general::Real;
+
+use nalgebra::{Point3, Vector2, Vector3};
+
+use num_traits::cast;
+
+use ::behavior::ThreadSafeCopyable;
+use ::numeric::FloatScalar;
+use ::mesh::{SimpleVertex, Mesh};
+
+/// Offsets of the corners of a billboard from its center, counter-clockwise from the bottom-left,
+/// with the texture coordinates of each corner
+pub const BILLBOARD_CORNERS: [([f32; 2], [f32; 2]); 4] = [
+    ([-1.0, -1.0], [0.0, 1.0]),
+    ([1.0, -1.0], [1.0, 1.0]),
+    ([1.0, 1.0], [1.0, 0.0]),
+    ([-1.0, 1.0], [0.0, 0.0]),
+];
+
+/// Indices of the two triangles of a billboard into `BILLBOARD_CORNERS`
+pub const BILLBOARD_INDICES: [usize; 6] = [0, 1, 2, 0, 2, 3];
+
+/// How billboards turn to face the camera
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BillboardMode<N: FloatScalar> {
+    /// Face the camera from every direction, keeping the given up direction as upright as possible
+    Spherical(Vector3<N>),
+    /// Only rotate around the given axis, which stays the up direction of the quad
+    Cylindrical(Vector3<N>),
+}
+
+/// A single billboard to build with `billboard_mesh`
+#[derive(Debug, Clone)]
+pub struct Billboard<N: FloatScalar, D> {
+    /// Center of the quad
+    pub position: Point3<N>,
+    /// Half of the width and height of the quad
+    pub half_size: Vector2<N>,
+    /// Any data to copy to all four vertices, such as color or a sprite index
+    pub data: D,
+}
+
+/// Vertex data of billboard meshes
+#[derive(Debug, Clone)]
+pub struct BillboardData<N: FloatScalar, D> {
+    pub uv: Vector2<N>,
+    pub data: D,
+}
+
+/// Right and up directions of a billboard at `center` seen from `camera`
+pub fn billboard_axes<N>(center: Point3<N>, camera: Point3<N>, mode: BillboardMode<N>) -> (Vector3<N>, Vector3<N>)
+    where N: Real + FloatScalar {
+    let to_camera = camera - center;
+
+    match mode {
+        BillboardMode::Spherical(up) => {
+            let forward = to_camera.normalize();
+            let right = up.cross(&forward).normalize();
+
+            (right, forward.cross(&right))
+        }
+        BillboardMode::Cylindrical(axis) => {
+            let axis = axis.normalize();
+
+            (axis.cross(&to_camera).normalize(), axis)
+        }
+    }
+}
+
+/// Builds a triangle mesh with one quad for every billboard, facing the camera at `camera`.
+///
+/// The mesh needs to be rebuilt whenever the camera moves, which is cheap compared to rendering it.
+pub fn billboard_mesh<N, D>(billboards: &[Billboard<N, D>], camera: Point3<N>, mode: BillboardMode<N>) -> Mesh<SimpleVertex<N, BillboardData<N, D>>>
+    where N: Real + FloatScalar + ThreadSafeCopyable,
+          D: Clone + Send + Sync {
+    let mut vertices = Vec::with_capacity(billboards.len() * 4);
+    let mut indices = Vec::with_capacity(billboards.len() * 6);
+
+    for billboard in billboards {
+        let (right, up) = billboard_axes(billboard.position, camera, mode);
+
+        let base = vertices.len();
+
+        for &(offset, uv) in &BILLBOARD_CORNERS {
+            let (dx, dy): (N, N) = (cast(offset[0]).unwrap(), cast(offset[1]).unwrap());
+
+            vertices.push(SimpleVertex {
+                position: billboard.position + right * (dx * billboard.half_size.x) + up * (dy * billboard.half_size.y),
+                data: BillboardData {
+                    uv: Vector2::new(cast(uv[0]).unwrap(), cast(uv[1]).unwrap()),
+                    data: billboard.data.clone(),
+                },
+            });
+        }
+
+        indices.extend(BILLBOARD_INDICES.iter().map(|&i| base + i));
+    }
+
+    Mesh { indices, vertices }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_billboard_axes() {
+        let center = Point3::new(0.0f32, 0.0, 0.0);
+        let camera = Point3::new(0.0f32, 5.0, 5.0);
+
+        let (right, up) = billboard_axes(center, camera, BillboardMode::Cylindrical(Vector3::y()));
+
+        assert_eq!(up, Vector3::y());
+        assert!((right - Vector3::x()).norm() < 1e-6);
+
+        let (right, up) = billboard_axes(center, camera, BillboardMode::Spherical(Vector3::y()));
+
+        assert!((right - Vector3::x()).norm() < 1e-6);
+        assert!((up - Vector3::new(0.0, 1.0, -1.0).normalize()).norm() < 1e-6);
+    }
+
+    #[test]
+    fn test_billboard_mesh() {
+        let billboards = [Billboard { position: Point3::new(1.0f32, 0.0, 0.0), half_size: Vector2::new(1.0, 2.0), data: () }];
+
+        let mesh = billboard_mesh(&billboards, Point3::new(1.0, 0.0, 10.0), BillboardMode::Spherical(Vector3::y()));
+
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.vertices[0].position, Point3::new(0.0, -2.0, 0.0));
+        assert_eq!(mesh.vertices[2].position, Point3::new(2.0, 2.0, 0.0));
+        assert_eq!(mesh.vertices[3].data.uv, Vector2::new(0.0, 0.0));
+    }
+}
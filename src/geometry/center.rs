@@ -0,0 +1,65 @@
+//! Pixel center conventions
+
+use num_traits::NumCast;
+
+use ::numeric::FloatScalar;
+
+/// Where the center of a pixel lies relative to its integer coordinates.
+///
+/// OpenGL and Direct3D 10 and later sample pixels at their centers, half a pixel away from the integer coordinates,
+/// while Direct3D 9 samples them at the integer coordinates themselves. Comparing against reference images
+/// rendered with the other convention shows everything shifted by half a pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum PixelCenter {
+    /// Pixel `(x, y)` is sampled at `(x + 0.5, y + 0.5)`, like OpenGL and Direct3D 10 and later
+    HalfInteger,
+    /// Pixel `(x, y)` is sampled at `(x, y)`, like Direct3D 9
+    Integer,
+}
+
+impl Default for PixelCenter {
+    fn default() -> PixelCenter { PixelCenter::HalfInteger }
+}
+
+impl PixelCenter {
+    /// Offset from the integer coordinates of a pixel to where it is sampled
+    #[inline]
+    pub fn offset<N: FloatScalar>(self) -> N {
+        match self {
+            PixelCenter::HalfInteger => <N as NumCast>::from(0.5).unwrap(),
+            PixelCenter::Integer => N::zero(),
+        }
+    }
+
+    /// Converts a pixel-space coordinate in this convention into one where pixel centers lie at half-integer coordinates
+    #[inline]
+    pub fn to_half_integer<N: FloatScalar>(self, coordinate: N) -> N {
+        coordinate + PixelCenter::HalfInteger.offset::<N>() - self.offset::<N>()
+    }
+
+    /// Converts a pixel-space coordinate where pixel centers lie at half-integer coordinates into this convention
+    #[inline]
+    pub fn from_half_integer<N: FloatScalar>(self, coordinate: N) -> N {
+        coordinate - PixelCenter::HalfInteger.offset::<N>() + self.offset::<N>()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pixel_center() {
+        assert_eq!(PixelCenter::default().offset::<f32>(), 0.5);
+        assert_eq!(PixelCenter::Integer.offset::<f64>(), 0.0);
+
+        assert_eq!(PixelCenter::Integer.to_half_integer(3.0f32), 3.5);
+        assert_eq!(PixelCenter::Integer.from_half_integer(3.5f32), 3.0);
+        assert_eq!(PixelCenter::HalfInteger.to_half_integer(3.25f32), 3.25);
+
+        for &center in &[PixelCenter::HalfInteger, PixelCenter::Integer] {
+            assert_eq!(center.from_half_integer(center.to_half_integer(7.75f64)), 7.75);
+        }
+    }
+}
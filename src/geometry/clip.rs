@@ -1,6 +1,6 @@
 //! Clipping planes
 
-use num_traits::Zero;
+use num_traits::{Zero, One};
 
 use nalgebra::coordinates::XYZW;
 
@@ -9,6 +9,7 @@ use ::geometry::ClipVertex;
 use ::interpolate::Interpolate;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub enum ClippingPlane {
     Left,
     Right,
@@ -30,35 +31,70 @@ pub const ALL_CLIPPING_PLANES: [ClippingPlane; 6] = [
 
 impl ClippingPlane {
     /// Check if the clipping plane has the given clip-space point inside of it
+    #[inline]
     pub fn has_inside<N: FloatScalar, K>(self, v: &ClipVertex<N, K>) -> bool {
-        let XYZW { x, y, z, w } = *v.position;
-
-        match self {
-            ClippingPlane::Left => { x >= -w }
-            ClippingPlane::Right => { x <= w }
-            ClippingPlane::Top => { y >= -w }
-            ClippingPlane::Bottom => { y <= w }
-            ClippingPlane::Near => { z >= Zero::zero() }
-            ClippingPlane::Far => { z <= w }
-        }
+        self.has_inside_scaled(v, One::one())
     }
 
     /// Find the intersection of a line and the clipping plane
+    #[inline]
     pub fn intersect<N: FloatScalar, K>(self, v1: &ClipVertex<N, K>, v2: &ClipVertex<N, K>) -> ClipVertex<N, K> where K: Interpolate {
-        let XYZW { x: x1, y: y1, z: z1, w: w1 } = *v1.position;
-        let XYZW { x: x2, y: y2, z: z2, w: w2 } = *v2.position;
-
-        let (a, b) = match self {
-            ClippingPlane::Left => (w1 + x1, w2 + x2),
-            ClippingPlane::Right => (w1 - x1, w2 - x2),
-            ClippingPlane::Top => (w1 + y1, w2 + y2),
-            ClippingPlane::Bottom => (w1 - y1, w2 - y2),
-            ClippingPlane::Near => (z1, z2),
-            ClippingPlane::Far => (w1 - z1, w2 - z2),
-        };
+        self.intersect_scaled(v1, v2, One::one())
+    }
+
+    /// Same as `has_inside`, but with the left, right, top and bottom planes moved out to `scale` times
+    /// their distance from the center of the viewport, like the edges of a guard band.
+    pub fn has_inside_scaled<N: FloatScalar, K>(self, v: &ClipVertex<N, K>, scale: N) -> bool {
+        self.distance(v, scale) >= Zero::zero()
+    }
+
+    /// Same as `intersect`, but with the left, right, top and bottom planes moved out to `scale` times
+    /// their distance from the center of the viewport, like the edges of a guard band.
+    pub fn intersect_scaled<N: FloatScalar, K>(self, v1: &ClipVertex<N, K>, v2: &ClipVertex<N, K>, scale: N) -> ClipVertex<N, K> where K: Interpolate {
+        let (a, b) = (self.distance(v1, scale), self.distance(v2, scale));
 
         let t = a / (a - b);
 
         Interpolate::linear_interpolate(t, &v1, &v2)
     }
-}
\ No newline at end of file
+
+    /// Signed distance of a clip-space point to the plane, which is positive or zero inside of it
+    #[inline]
+    fn distance<N: FloatScalar, K>(self, v: &ClipVertex<N, K>, scale: N) -> N {
+        let XYZW { x, y, z, w } = *v.position;
+
+        match self {
+            ClippingPlane::Left => { w * scale + x }
+            ClippingPlane::Right => { w * scale - x }
+            ClippingPlane::Top => { w * scale + y }
+            ClippingPlane::Bottom => { w * scale - y }
+            ClippingPlane::Near => { z }
+            ClippingPlane::Far => { w - z }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use super::*;
+
+    #[test]
+    fn test_scaled_clipping_planes() {
+        let inside = ClipVertex::new(Vector4::new(1.5f32, -0.5, 0.5, 1.0), ());
+        let outside = ClipVertex::new(Vector4::new(3.0f32, 0.0, 0.5, 1.0), ());
+
+        assert!(!ClippingPlane::Right.has_inside(&inside));
+        assert!(ClippingPlane::Right.has_inside_scaled(&inside, 2.0));
+        assert!(!ClippingPlane::Right.has_inside_scaled(&outside, 2.0));
+
+        // Depth planes don't move
+        assert!(ClippingPlane::Near.has_inside_scaled(&inside, 2.0) && ClippingPlane::Far.has_inside_scaled(&inside, 2.0));
+
+        let intersection = ClippingPlane::Right.intersect_scaled(&inside, &outside, 2.0);
+
+        assert!((intersection.position.x - 2.0).abs() < 1e-6);
+        assert!((intersection.position.y - -1.0 / 3.0).abs() < 1e-6);
+    }
+}
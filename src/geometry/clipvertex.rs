@@ -1,6 +1,6 @@
 use num_traits::Float;
 
-use nalgebra::{Vector4, Matrix4};
+use nalgebra::{Vector3, Vector4, Matrix4};
 use nalgebra::core::coordinates::XYZW;
 
 use ::numeric::FloatScalar;
@@ -36,7 +36,8 @@ impl<N, K> Interpolate for ClipVertex<N, K> where N: FloatScalar, K: Interpolate
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub struct Viewport<N> where N: FloatScalar {
     pub x: N,
     pub y: N,
@@ -61,6 +62,35 @@ impl<N> Viewport<N> where N: FloatScalar {
     pub fn aspect_ratio(&self) -> N {
         self.width / self.height
     }
+
+    /// Converts a screen-space position back into normalized device coordinates,
+    /// undoing the viewport transform applied by `ClipVertex::normalize`.
+    ///
+    /// Combined with the inverse view-projection matrix, this reconstructs positions from the depth attachment.
+    pub fn screen_to_ndc(&self, x: N, y: N, z: N) -> Vector3<N> {
+        let two = N::one() + N::one();
+
+        let (left, right) = (self.x, self.x + self.width);
+        let (bottom, top) = (self.y, self.y + self.height);
+
+        Vector3::new(
+            (two * x - (right + left)) / (right - left),
+            ((top + bottom) - two * y) / (top - bottom),
+            -(two * z + (self.far + self.near)) / (self.far - self.near),
+        )
+    }
+
+    /// Converts the viewport to another scalar type
+    pub fn cast<M: FloatScalar>(&self) -> Viewport<M> {
+        Viewport {
+            x: M::from(self.x).unwrap(),
+            y: M::from(self.y).unwrap(),
+            width: M::from(self.width).unwrap(),
+            height: M::from(self.height).unwrap(),
+            near: M::from(self.near).unwrap(),
+            far: M::from(self.far).unwrap(),
+        }
+    }
 }
 
 impl<N, K> ClipVertex<N, K> where N: FloatScalar,
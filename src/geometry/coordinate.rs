@@ -7,6 +7,7 @@ use super::Dimensions;
 
 /// Simple 2D Coordinate structure. Easily converts to/from nalgebra's `Vector2D<u32>` for more complex operations.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub struct Coordinate {
     /// x-coordinate
     pub x: u32,
@@ -51,6 +52,20 @@ impl Coordinate {
         self.x as usize + self.y as usize * dimensions.width as usize
     }
 
+    /// Convert a 2D coordinate into a 1D array index, where rows start `stride` elements apart
+    #[inline]
+    pub fn into_strided_index(self, stride: usize) -> usize {
+        debug_assert!((self.x as usize) < stride);
+
+        self.x as usize + self.y as usize * stride
+    }
+
+    /// Convert a 1D array index into a 2D coordinate, where rows start `stride` elements apart
+    #[inline]
+    pub fn from_strided_index(index: usize, stride: usize) -> Coordinate {
+        Coordinate { x: (index % stride) as u32, y: (index / stride) as u32 }
+    }
+
     /// Convert a 1D array index into a 2D coordinate using the given `Dimensions`
     #[inline]
     pub fn from_index(index: usize, dimensions: Dimensions) -> Coordinate {
@@ -84,6 +99,14 @@ mod test {
             }
         }
     }
+
+    #[test]
+    fn coordinate_strided_index() {
+        let coord = Coordinate::new(3, 2);
+
+        assert_eq!(coord.into_strided_index(16), 35);
+        assert_eq!(Coordinate::from_strided_index(35, 16), coord);
+    }
 }
 
 impl From<Vector2<u32>> for Coordinate {
@@ -5,6 +5,21 @@ pub trait HasDimensions {
     /// Returns the dimensions of the object
     fn dimensions(&self) -> Dimensions;
 
+    /// Returns the number of indices between the start of one row and the next.
+    ///
+    /// This is the width for tightly packed buffers, but can be larger for buffers with padded rows,
+    /// such as surfaces provided by the operating system.
+    #[inline]
+    fn stride(&self) -> usize {
+        self.dimensions().width as usize
+    }
+
+    /// Converts a coordinate into an index for this object, taking the stride into account
+    #[inline]
+    fn index_of(&self, coord: Coordinate) -> usize {
+        coord.into_strided_index(self.stride())
+    }
+
     /// Checks if the given coordinate is within the dimension bounds of the current object
     #[inline]
     fn in_bounds(&self, coord: Coordinate) -> bool {
@@ -13,6 +28,7 @@ pub trait HasDimensions {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub struct Dimensions {
     pub width: u32,
     pub height: u32,
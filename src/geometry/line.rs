@@ -2,12 +2,27 @@ use num_traits::{One, Zero};
 
 use ::numeric::FloatScalar;
 
+/// Clips the line from `start` to `end` against the inclusive `bounds` rectangle,
+/// returning the clipped endpoints, or `None` if the line is entirely outside of the bounds.
 #[inline]
 pub fn liang_barsky_iterative<T: FloatScalar>(start: (T, T), end: (T, T), bounds: ((T, T), (T, T))) -> Option<((T, T), (T, T))> {
+    liang_barsky_parametric(start, end, bounds).map(|(t0, t1)| {
+        let (x1, y1) = start;
+        let (x2, y2) = end;
+
+        let (dx, dy) = (x2 - x1, y2 - y1);
+
+        ((x1 + t0 * dx, y1 + t0 * dy), (x1 + t1 * dx, y1 + t1 * dy))
+    })
+}
+
+/// Same as `liang_barsky_iterative`, but returns the parameters `(t0, t1)` of the clipped endpoints along the line,
+/// which can be used to interpolate anything else attached to the endpoints.
+pub fn liang_barsky_parametric<T: FloatScalar>(start: (T, T), end: (T, T), bounds: ((T, T), (T, T))) -> Option<(T, T)> {
     let ((xmin, ymin), (xmax, ymax)) = bounds;
 
-    let (mut x1, mut y1) = start;
-    let (mut x2, mut y2) = end;
+    let (x1, y1) = start;
+    let (x2, y2) = end;
 
     let mut t0 = Zero::zero();
     let mut t1 = One::one();
@@ -45,10 +60,21 @@ pub fn liang_barsky_iterative<T: FloatScalar>(start: (T, T), end: (T, T), bounds
         }
     }
 
-    let x1clip = x1 + t0 * dx;
-    let y1clip = y1 + t0 * dy;
-    let x2clip = x1 + t1 * dx;
-    let y2clip = y1 + t1 * dy;
+    Some((t0, t1))
+}
 
-    Some(((x1clip, y1clip), (x2clip, y2clip)))
+#[cfg(test)]
+mod test {
+    use super::{liang_barsky_iterative, liang_barsky_parametric};
+
+    #[test]
+    fn test_clip_line() {
+        let bounds = ((0.0, 0.0), (10.0, 10.0));
+
+        assert_eq!(liang_barsky_parametric((-10.0, 5.0), (10.0, 5.0), bounds), Some((0.5, 1.0)));
+        assert_eq!(liang_barsky_iterative((-10.0, 5.0), (10.0, 5.0), bounds), Some(((0.0, 5.0), (10.0, 5.0))));
+
+        assert_eq!(liang_barsky_parametric((2.0, 2.0), (8.0, 8.0), bounds), Some((0.0, 1.0)));
+        assert_eq!(liang_barsky_parametric((-5.0, 20.0), (20.0, 15.0), bounds), None);
+    }
 }
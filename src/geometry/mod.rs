@@ -7,10 +7,14 @@ pub mod clipvertex;
 pub mod screenvertex;
 pub mod clip;
 pub mod line;
+pub mod billboard;
+pub mod center;
 
 pub use self::dimension::{Dimensions, HasDimensions};
 pub use self::coordinate::Coordinate;
 pub use self::winding::FaceWinding;
 pub use self::clipvertex::{ClipVertex, Viewport};
 pub use self::screenvertex::ScreenVertex;
-pub use self::clip::{ClippingPlane, ALL_CLIPPING_PLANES};
\ No newline at end of file
+pub use self::clip::{ClippingPlane, ALL_CLIPPING_PLANES};
+pub use self::billboard::{Billboard, BillboardMode, billboard_mesh};
+pub use self::center::PixelCenter;
@@ -8,6 +8,7 @@
 /// will have the opposite winding order, since they are viewed from the back. This is known
 /// as backface culling.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub enum FaceWinding {
     /// Clockwise face winding, where the vertices are like so:
     ///
@@ -41,4 +42,13 @@ pub enum FaceWinding {
     ///       ------->
     /// ```
     CounterClockwise
-}
\ No newline at end of file
+}
+impl FaceWinding {
+    /// Returns the opposite winding, which is what triangles end up with after being mirrored
+    pub fn reversed(self) -> FaceWinding {
+        match self {
+            FaceWinding::Clockwise => FaceWinding::CounterClockwise,
+            FaceWinding::CounterClockwise => FaceWinding::Clockwise,
+        }
+    }
+}
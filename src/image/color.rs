@@ -5,7 +5,7 @@ use num_traits::{Float, PrimInt};
 use ::behavior::ThreadSafeCopyable;
 use ::geometry::{Coordinate, Dimensions, HasDimensions};
 use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
-use ::color::{Color, ColorAlpha, AlphaMultiply};
+use ::color::{Color, ColorAlpha, ColorMask, AlphaMultiply};
 
 impl<T: Primitive> Color for Rgb<T> where T: ColorAlpha {
     type Alpha = ();
@@ -23,6 +23,17 @@ impl<T: Primitive> Color for Rgb<T> where T: ColorAlpha {
 
     #[inline]
     fn get_alpha(&self) -> () { () }
+
+    #[inline]
+    fn mask_channels(self, other: Self, mask: ColorMask) -> Self {
+        let mut out = other;
+
+        for i in 0..3 {
+            if mask.channel(i) { out.data[i] = self.data[i]; }
+        }
+
+        out
+    }
 }
 
 impl<T: Primitive> Color for Luma<T> where T: ColorAlpha {
@@ -41,6 +52,11 @@ impl<T: Primitive> Color for Luma<T> where T: ColorAlpha {
 
     #[inline]
     fn get_alpha(&self) -> () { () }
+
+    #[inline]
+    fn mask_channels(self, other: Self, mask: ColorMask) -> Self {
+        if mask.r { self } else { other }
+    }
 }
 
 impl<T: Primitive> Color for Rgba<T> where T: AlphaMultiply + ColorAlpha {
@@ -77,6 +93,17 @@ impl<T: Primitive> Color for Rgba<T> where T: AlphaMultiply + ColorAlpha {
     fn get_alpha(&self) -> T {
         self.data[3]
     }
+
+    #[inline]
+    fn mask_channels(self, other: Self, mask: ColorMask) -> Self {
+        let mut out = other;
+
+        for i in 0..4 {
+            if mask.channel(i) { out.data[i] = self.data[i]; }
+        }
+
+        out
+    }
 }
 
 impl<T: Primitive> Color for LumaA<T> where T: AlphaMultiply + ColorAlpha {
@@ -104,4 +131,45 @@ impl<T: Primitive> Color for LumaA<T> where T: AlphaMultiply + ColorAlpha {
     fn get_alpha(&self) -> T {
         self.data[1]
     }
-}
\ No newline at end of file
+
+    #[inline]
+    fn mask_channels(self, other: Self, mask: ColorMask) -> Self {
+        LumaA {
+            data: [
+                if mask.r { self.data[0] } else { other.data[0] },
+                if mask.a { self.data[1] } else { other.data[1] },
+            ]
+        }
+    }
+}
+impl ::color::ToChannels for Rgba<u8> {
+    #[inline]
+    fn to_channels(&self) -> [f32; 4] {
+        let d = self.data;
+        [d[0] as f32 / 255.0, d[1] as f32 / 255.0, d[2] as f32 / 255.0, d[3] as f32 / 255.0]
+    }
+}
+
+impl ::color::FromChannels for Rgba<u8> {
+    #[inline]
+    fn from_channels(c: [f32; 4]) -> Rgba<u8> {
+        let to_u8 = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+        Rgba { data: [to_u8(c[0]), to_u8(c[1]), to_u8(c[2]), to_u8(c[3])] }
+    }
+}
+
+impl ::color::ToChannels for Rgb<u8> {
+    #[inline]
+    fn to_channels(&self) -> [f32; 4] {
+        let d = self.data;
+        [d[0] as f32 / 255.0, d[1] as f32 / 255.0, d[2] as f32 / 255.0, 1.0]
+    }
+}
+
+impl ::color::FromChannels for Rgb<u8> {
+    #[inline]
+    fn from_channels(c: [f32; 4]) -> Rgb<u8> {
+        let to_u8 = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+        Rgb { data: [to_u8(c[0]), to_u8(c[1]), to_u8(c[2])] }
+    }
+}
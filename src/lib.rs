@@ -22,6 +22,11 @@
 //! * Simple yet flexible Mesh representation.
 //! * Define your own vertex attributes.
 //! * Built-in compatibility with the `image` crate, using the `image_compat` cargo feature.
+//! * Drawing to and from `embedded-graphics` targets, using the `embedded_graphics_compat` cargo feature.
+//! * Recording animated GIFs, APNGs and PNG sequences, using the `recorder_compat` cargo feature.
+//! * Serialization of render settings with `serde`, using the `serde_compat` cargo feature.
+//! * Scripted shaders for live editing with `rhai`, using the `script_compat` cargo feature.
+//! * Pinning render threads to CPU cores, using the `affinity_compat` cargo feature.
 //!
 //! ### Planned Features:
 //!
@@ -38,6 +43,14 @@
 //! Geometry at the edge of the screen is totally messed up, and I don't know how to fix it as of writing this.
 //! Any help would be greatly appreciated. I really want to fix it but have no idea how yet.
 //!
+//! #### No `no_std` support.
+//!
+//! The crate requires the standard library, so it can't drive embedded framebuffers directly.
+//! `nalgebra` 0.12 and `alga` require `std`, so the core math, rasterization and framebuffer code can't be split out
+//! into a `no_std` + `alloc` build until the crate moves to a `nalgebra` release without that requirement.
+//! After that, the `scoped_threadpool` and `parking_lot` based stages and the `std::time` based profiler would go
+//! behind a `std` feature, with a single-threaded fallback for the vertex, geometry and fragment stages.
+//!
 //! #### Multi-mesh performance.
 //!
 //! Although this can chew through millions of triangles per second easy in a single mesh,
@@ -64,10 +77,70 @@ extern crate parking_lot;
 #[macro_use]
 extern crate trace_error;
 
+#[cfg(feature = "serde_compat")]
+extern crate serde;
+
+#[cfg(feature = "serde_compat")]
+#[macro_use]
+extern crate serde_derive;
+
+#[cfg(feature = "ron_compat")]
+extern crate ron;
+
+#[cfg(feature = "toml_compat")]
+extern crate toml;
+
+#[cfg(feature = "script_compat")]
+extern crate rhai;
+
+#[cfg(feature = "tracing_compat")]
+extern crate tracing;
+
+#[cfg(feature = "affinity_compat")]
+extern crate core_affinity;
+
+// Records a profiler span until the end of the enclosing block, or does nothing without the `profile` feature
+#[cfg(feature = "profile")]
+macro_rules! profile_scope {
+    ($name:expr) => { let _profile_scope = ::profile::Scope::new($name); }
+}
+
+#[cfg(not(feature = "profile"))]
+macro_rules! profile_scope {
+    ($name:expr) => {}
+}
+
+// Emits a `tracing` event at the debug level, or does nothing without the `tracing_compat` feature
+#[cfg(feature = "tracing_compat")]
+macro_rules! trace_event {
+    ($($arg:tt)*) => { ::tracing::debug!($($arg)*); }
+}
+
+#[cfg(not(feature = "tracing_compat"))]
+macro_rules! trace_event {
+    ($($arg:tt)*) => {}
+}
+
+// Enters a `tracing` span at the debug level until the end of the enclosing block, or does nothing without the `tracing_compat` feature
+#[cfg(feature = "tracing_compat")]
+macro_rules! trace_span {
+    ($($arg:tt)*) => {
+        let _trace_span = ::tracing::debug_span!($($arg)*);
+        let _trace_enter = _trace_span.enter();
+    }
+}
+
+#[cfg(not(feature = "tracing_compat"))]
+macro_rules! trace_span {
+    ($($arg:tt)*) => {}
+}
+
 // Low-level and very unsafe multithreading code
 pub ( crate ) mod parallel;
 
 pub mod error;
+pub mod validation;
+pub mod memory;
 pub mod numeric;
 pub mod behavior;
 pub mod color;
@@ -78,26 +151,58 @@ pub mod framebuffer;
 pub mod primitive;
 pub mod geometry;
 pub mod texture;
+pub mod noise;
+pub mod particles;
+pub mod sampling;
+pub mod shading;
+pub mod raytrace;
+pub mod sdf;
+pub mod path;
 pub mod pipeline;
+pub mod post;
+pub mod analysis;
+pub mod debug;
+pub mod testing;
+pub mod terminal;
+pub mod video;
 
 #[cfg(feature = "image_compat")]
 pub mod image;
 
+#[cfg(feature = "embedded_graphics_compat")]
+pub mod embedded;
+
+#[cfg(feature = "recorder_compat")]
+pub mod recorder;
+
+#[cfg(feature = "script_compat")]
+pub mod script;
+
+#[cfg(feature = "profile")]
+pub mod profile;
+
+#[cfg(feature = "font_compat")]
+pub mod font;
+
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
+
 pub use numeric::interpolate;
 pub use framebuffer::attachments;
 
 pub mod prelude {
-    pub use ::color::blend::{Blend, GenericBlend, BoxedGenericBlend};
+    pub use ::color::blend::{Blend, BlendPreset, GenericBlend, BoxedGenericBlend};
     pub use ::geometry::{Dimensions, HasDimensions, Coordinate, ClipVertex,
                          Viewport, ScreenVertex, FaceWinding};
     pub use ::primitive::{Primitive, Point, Line, Triangle, PrimitiveRef, PrimitiveMut};
     pub use ::mesh::{Vertex, SimpleVertex, Mesh};
     pub use ::pixels::{PixelBuffer, PixelRead, PixelWrite, PartialPixelBuffer};
     pub use ::framebuffer::{Framebuffer, RenderBuffer, Attachments};
-    pub use ::interpolate::Interpolate;
+    pub use ::interpolate::{Interpolate, Flat};
     pub use ::pipeline::{Pipeline, PipelineObject,
                          VertexShader, GeometryShader, FragmentShader,
-                         PrimitiveStorage};
+                         PrimitiveStorage, RenderStateDesc, ShaderSlot,
+                         PipelineBuilder};
     pub use ::pipeline::stages::fragment::Fragment;
 }
 
@@ -37,6 +37,9 @@
 /// note that the `u` and `v` in the `Interpolate::barycentric_interpolate` arguments are mostly unrelated to the `uv` normal. They're both Interpolate coordinates,
 /// but for different things.
 ///
+/// Members that shouldn't be interpolated, like textures or texture handles, can be wrapped in
+/// [`Flat`](interpolate/struct.Flat.html) to keep the value of the first vertex.
+///
 /// For now, the struct itself must be `pub` and all the members must be `pub`, but hopefully that can change in the future.
 #[macro_export]
 macro_rules! declare_uniforms {
@@ -0,0 +1,106 @@
+//! Memory usage accounting
+//!
+//! Framebuffers, texture buffers, meshes and cubemaps implement `MemoryUsage`, returning a `MemoryReport`
+//! with the bytes allocated for each of their attachments or attributes, so applications on constrained devices
+//! can budget and log their memory use.
+//!
+//! Reports count the heap memory allocated by the buffers themselves, including any spare capacity,
+//! but not memory owned by the elements, such as vertices holding their own `Vec`s.
+
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::mem::size_of;
+
+/// Bytes allocated for a single named attachment or attribute
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MemoryEntry {
+    pub name: String,
+    pub bytes: usize,
+}
+
+/// Breakdown of the memory allocated by an object
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    pub entries: Vec<MemoryEntry>,
+}
+
+impl MemoryReport {
+    pub fn new() -> MemoryReport { MemoryReport::default() }
+
+    /// Adds an entry to the report
+    pub fn add<S: Into<String>>(&mut self, name: S, bytes: usize) {
+        self.entries.push(MemoryEntry { name: name.into(), bytes });
+    }
+
+    /// Adds an entry for the allocation of a `Vec`
+    pub fn add_vec<S: Into<String>, T>(&mut self, name: S, vec: &Vec<T>) {
+        self.add(name, vec.capacity() * size_of::<T>());
+    }
+
+    /// Adds all entries of another report, with their names prefixed by `prefix` and a period
+    pub fn merge(&mut self, prefix: &str, other: MemoryReport) {
+        for entry in other.entries {
+            self.add(format!("{}.{}", prefix, entry.name), entry.bytes);
+        }
+    }
+
+    /// Bytes allocated for the entry with the given name, if any
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.entries.iter().find(|entry| entry.name == name).map(|entry| entry.bytes)
+    }
+
+    /// Total bytes allocated
+    pub fn total(&self) -> usize {
+        self.entries.iter().map(|entry| entry.bytes).sum()
+    }
+}
+
+impl Display for MemoryReport {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        for entry in &self.entries {
+            writeln!(f, "{}: {} bytes", entry.name, entry.bytes)?;
+        }
+
+        write!(f, "total: {} bytes", self.total())
+    }
+}
+
+/// Objects that can report the memory they have allocated
+pub trait MemoryUsage {
+    /// Returns the memory allocated for each attachment or attribute
+    fn memory_report(&self) -> MemoryReport;
+
+    /// Returns the total bytes allocated
+    fn memory_usage(&self) -> usize {
+        self.memory_report().total()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::framebuffer::RenderBuffer;
+    use ::framebuffer::texturebuffer::predefined::RGBAf32MotionTextureBuffer;
+    use ::attachments::predefined::{ColorDepthAttachments, EmptyAttachments};
+    use ::color::predefined::formats::RGBAf32Color;
+
+    use super::MemoryUsage;
+
+    #[test]
+    fn test_framebuffer_memory() {
+        let dimensions = Dimensions::new(16, 8);
+
+        let renderbuffer = RenderBuffer::<ColorDepthAttachments<RGBAf32Color, f32>>::with_dimensions(dimensions);
+
+        let report = renderbuffer.memory_report();
+
+        assert!(report.get("color").unwrap() >= 16 * 8 * 16);
+        assert!(report.total() >= report.get("color").unwrap() + report.get("depth").unwrap());
+
+        let texturebuffer = RGBAf32MotionTextureBuffer::<EmptyAttachments>::with_dimensions(dimensions);
+
+        let report = texturebuffer.memory_report();
+
+        assert_eq!(report.get("color"), Some(16 * 8 * 16));
+        assert_eq!(report.get("motion"), Some(16 * 8 * 8));
+    }
+}
@@ -0,0 +1,54 @@
+//! Mesh index types
+
+use std::fmt::Debug;
+
+/// Integer types usable as mesh vertex indices.
+///
+/// `u16` indices take a quarter of the memory of `usize` indices on 64-bit platforms,
+/// and match the index buffers of many model formats such as glTF, so they can be used without conversion.
+pub trait MeshIndex: Debug + Copy + Eq + Ord + Send + Sync + 'static {
+    /// Largest number of vertices that can be indexed, one more than the largest index,
+    /// except for `usize` where it saturates at `usize::max_value()`
+    const MAX_VERTICES: usize;
+
+    /// Converts the index into a `usize` for indexing vertices
+    fn to_usize(self) -> usize;
+
+    /// Converts a `usize` into an index, returning `None` if it's out of range
+    fn from_usize(index: usize) -> Option<Self>;
+}
+
+macro_rules! impl_mesh_index {
+    ($($t:ty),+) => {
+        $(
+            impl MeshIndex for $t {
+                const MAX_VERTICES: usize = (<$t>::max_value() as usize).saturating_add(1);
+
+                #[inline(always)]
+                fn to_usize(self) -> usize { self as usize }
+
+                #[inline]
+                fn from_usize(index: usize) -> Option<$t> {
+                    if index <= <$t>::max_value() as usize { Some(index as $t) } else { None }
+                }
+            }
+        )+
+    }
+}
+
+impl_mesh_index!(u16, u32, usize);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_max_vertices() {
+        assert_eq!(<u16 as MeshIndex>::MAX_VERTICES, 65536);
+        assert_eq!(<usize as MeshIndex>::MAX_VERTICES, usize::max_value());
+
+        // Every vertex below the maximum can be indexed
+        assert_eq!(u16::from_usize(<u16 as MeshIndex>::MAX_VERTICES - 1), Some(65535));
+        assert_eq!(u16::from_usize(<u16 as MeshIndex>::MAX_VERTICES), None);
+    }
+}
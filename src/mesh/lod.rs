@@ -0,0 +1,89 @@
+//! Levels of detail
+//!
+//! A `MeshLod` holds a mesh simplified to several levels of detail, and selects the coarsest level
+//! that still looks the same as the original at the size the mesh is drawn on screen.
+
+use std::sync::Arc;
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::mesh::simplify::{simplify_with_error, bounding_radius};
+
+/// A single level of detail
+#[derive(Debug, Clone)]
+pub struct LodLevel<V: Vertex, I: MeshIndex = usize> {
+    /// Mesh for this level, ready to be passed to `Pipeline::render_mesh`
+    pub mesh: Arc<Mesh<V, I>>,
+    /// Largest geometric error of this level, relative to the bounding radius of the original mesh
+    pub error: f32,
+}
+
+/// A mesh with multiple levels of detail, from finest to coarsest
+#[derive(Debug, Clone)]
+pub struct MeshLod<V: Vertex, I: MeshIndex = usize> {
+    levels: Vec<LodLevel<V, I>>,
+    radius: f32,
+}
+
+impl<V, I> MeshLod<V, I> where V: Vertex + Clone, I: MeshIndex {
+    /// Generates levels of detail for a triangle mesh.
+    ///
+    /// The original mesh is the first level, followed by one level for each ratio of triangles to keep,
+    /// which should be in decreasing order, such as `&[0.5, 0.25, 0.125]`.
+    pub fn new(mesh: Mesh<V, I>, ratios: &[f32]) -> MeshLod<V, I> {
+        let radius = bounding_radius(&mesh) as f32;
+
+        let mut levels = Vec::with_capacity(ratios.len() + 1);
+
+        for &ratio in ratios {
+            let (indices, error) = simplify_with_error(&mesh, ratio);
+
+            levels.push(LodLevel {
+                mesh: Arc::new(Mesh { indices, vertices: mesh.vertices.clone() }),
+                error,
+            });
+        }
+
+        levels.insert(0, LodLevel { mesh: Arc::new(mesh), error: 0.0 });
+
+        MeshLod { levels, radius }
+    }
+}
+
+impl<V, I> MeshLod<V, I> where V: Vertex, I: MeshIndex {
+    /// Wraps levels generated elsewhere, from finest to coarsest, with errors relative to `radius`
+    pub fn from_levels(levels: Vec<LodLevel<V, I>>, radius: f32) -> MeshLod<V, I> {
+        assert!(!levels.is_empty(), "Meshes need at least one level of detail");
+
+        MeshLod { levels, radius }
+    }
+
+    /// All levels, from finest to coarsest
+    #[inline]
+    pub fn levels(&self) -> &[LodLevel<V, I>] { &self.levels }
+
+    /// Radius of the bounding sphere of the original mesh, in object-space
+    #[inline]
+    pub fn radius(&self) -> f32 { self.radius }
+
+    /// Selects the coarsest level whose error, when drawn with the given projected radius in pixels,
+    /// is no larger than `pixel_error` pixels.
+    ///
+    /// Use `projected_radius` to compute the projected radius of the mesh.
+    pub fn select(&self, projected_radius: f32, pixel_error: f32) -> &LodLevel<V, I> {
+        self.levels.iter()
+            .rev()
+            .find(|level| level.error * projected_radius <= pixel_error)
+            .unwrap_or(&self.levels[0])
+    }
+}
+
+/// Approximate radius in pixels of a bounding sphere of the given radius
+/// at `distance` from a perspective camera with vertical field of view `fov_y`, in radians.
+pub fn projected_radius(radius: f32, distance: f32, fov_y: f32, viewport_height: u32) -> f32 {
+    if distance <= radius {
+        // The camera is inside the sphere, so the mesh covers the entire screen
+        return ::std::f32::INFINITY;
+    }
+
+    radius / (distance * (fov_y / 2.0).tan()) * (viewport_height as f32 / 2.0)
+}
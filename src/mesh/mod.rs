@@ -6,6 +6,25 @@ use nalgebra::Point3;
 
 use ::behavior::ThreadSafeCopyable;
 use ::numeric::FloatScalar;
+use ::memory::{MemoryReport, MemoryUsage};
+
+pub mod index;
+pub mod validate;
+pub mod simplify;
+pub mod lod;
+pub mod optimize;
+pub mod polygon;
+pub mod terrain;
+pub mod voxelize;
+
+pub use self::index::MeshIndex;
+pub use self::validate::{ValidationOptions, MeshReport};
+pub use self::simplify::{simplify, simplify_with_error};
+pub use self::lod::{MeshLod, LodLevel, projected_radius};
+pub use self::optimize::{optimize_vertex_order, average_cache_miss_ratio};
+pub use self::polygon::triangulate_polygon;
+pub use self::terrain::{TerrainOptions, TerrainVertex, terrain_mesh, terrain_chunks};
+pub use self::voxelize::{VoxelGrid, voxelize};
 
 /// A single vertex with a required position vector and any other vertex data
 #[derive(Debug, Clone)]
@@ -34,8 +53,10 @@ impl<N, D> Vertex for SimpleVertex<N, D> where N: ThreadSafeCopyable + FloatScal
 }
 
 /// Mesh structure with indexed vertices.
+///
+/// Indices are `usize` by default, but any `MeshIndex` type can be used to save memory.
 #[derive(Clone)]
-pub struct Mesh<V: Vertex> {
+pub struct Mesh<V: Vertex, I: MeshIndex = usize> {
     /// Vertex indices
     ///
     /// If you are unfamiliar with vertex indices, it's a way of re-using vertices for multiple primitives.
@@ -58,13 +79,36 @@ pub struct Mesh<V: Vertex> {
     /// ```
     ///
     /// Note that both of those triangles go in a clockwise direction from vertex to vertex.
-    pub indices: Vec<usize>,
+    pub indices: Vec<I>,
     /// Vertices with their vertex data
     pub vertices: Vec<V>,
 }
 
-impl<V> Debug for Mesh<V> where V: Vertex {
+impl<V, I> Debug for Mesh<V, I> where V: Vertex, I: MeshIndex {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(f, "Mesh {{ vertices: {} }}", self.vertices.len())
     }
-}
\ No newline at end of file
+}
+
+impl<V, I> MemoryUsage for Mesh<V, I> where V: Vertex, I: MeshIndex {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("indices", &self.indices);
+        report.add_vec("vertices", &self.vertices);
+
+        report
+    }
+}
+
+impl<V, I> Mesh<V, I> where V: Vertex, I: MeshIndex {
+    /// Converts the mesh to use another index type,
+    /// returning `None` if any index doesn't fit in the new type.
+    pub fn convert_indices<J>(self) -> Option<Mesh<V, J>> where J: MeshIndex {
+        let Mesh { indices, vertices } = self;
+
+        let indices = indices.into_iter().map(|index| J::from_usize(index.to_usize())).collect::<Option<Vec<J>>>()?;
+
+        Some(Mesh { indices, vertices })
+    }
+}
@@ -0,0 +1,222 @@
+//! Vertex order optimization
+//!
+//! Triangles are reordered with the Tipsify algorithm, from "Fast Triangle Reordering for Vertex Locality
+//! and Reduced Overdraw" by Sander, Nehab and Barczak, so that triangles sharing vertices are drawn close together.
+//! Vertices are then renumbered in the order they are first used, so they are also fetched in order.
+//!
+//! This benefits any cache of transformed vertices, and memory locality in general.
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+
+/// Common post-transform vertex cache size to optimize for
+pub const DEFAULT_CACHE_SIZE: usize = 16;
+
+/// Computes the average cache miss ratio (ACMR) of a triangle index buffer for a FIFO vertex cache of the given size.
+///
+/// This is the average number of vertices transformed per triangle, which is `3.0` for no reuse at all,
+/// and around `0.5` at best for large regular meshes.
+pub fn average_cache_miss_ratio<I: MeshIndex>(indices: &[I], cache_size: usize) -> f32 {
+    let triangles = indices.len() / 3;
+
+    if triangles == 0 {
+        return 0.0;
+    }
+
+    let mut cache: Vec<I> = Vec::with_capacity(cache_size);
+    let mut head = 0;
+    let mut misses = 0;
+
+    for &index in &indices[..triangles * 3] {
+        if !cache.contains(&index) {
+            misses += 1;
+
+            if cache.len() < cache_size {
+                cache.push(index);
+            } else if cache_size > 0 {
+                cache[head] = index;
+                head = (head + 1) % cache_size;
+            }
+        }
+    }
+
+    misses as f32 / triangles as f32
+}
+
+/// Reorders the triangles of `indices` for a vertex cache of the given size, returning the new index buffer.
+///
+/// Any trailing indices that don't form a whole triangle are dropped.
+pub fn optimize_triangle_order<I: MeshIndex>(indices: &[I], num_vertices: usize, cache_size: usize) -> Vec<I> {
+    let triangles: Vec<&[I]> = indices.chunks(3).filter(|triangle| triangle.len() == 3).collect();
+
+    let mut adjacency = vec![Vec::new(); num_vertices];
+    let mut live = vec![0usize; num_vertices];
+
+    for (t, triangle) in triangles.iter().enumerate() {
+        for index in triangle.iter() {
+            adjacency[index.to_usize()].push(t);
+            live[index.to_usize()] += 1;
+        }
+    }
+
+    let mut timestamps = vec![0usize; num_vertices];
+    let mut emitted = vec![false; triangles.len()];
+    let mut dead_end: Vec<usize> = Vec::new();
+    let mut output = Vec::with_capacity(triangles.len() * 3);
+
+    let mut time = cache_size + 1;
+    let mut cursor = 0;
+    let mut fanning = if num_vertices > 0 { Some(0) } else { None };
+
+    while let Some(vertex) = fanning {
+        let mut candidates = Vec::new();
+
+        for &t in &adjacency[vertex] {
+            if emitted[t] {
+                continue;
+            }
+
+            for index in triangles[t].iter() {
+                let v = index.to_usize();
+
+                output.push(*index);
+                dead_end.push(v);
+                candidates.push(v);
+
+                live[v] -= 1;
+
+                if time - timestamps[v] > cache_size {
+                    timestamps[v] = time;
+                    time += 1;
+                }
+            }
+
+            emitted[t] = true;
+        }
+
+        // Prefer the candidate that will still be in the cache after emitting all its remaining triangles,
+        // and has been in the cache the longest
+        let mut best = None;
+        let mut best_priority = None;
+
+        for &v in &candidates {
+            if live[v] > 0 {
+                let priority = if time - timestamps[v] + 2 * live[v] <= cache_size { time - timestamps[v] } else { 0 };
+
+                if best_priority.map_or(true, |p| priority > p) {
+                    best_priority = Some(priority);
+                    best = Some(v);
+                }
+            }
+        }
+
+        fanning = best.or_else(|| {
+            // Dead end, so try recently used vertices first, then any vertex with triangles left
+            while let Some(v) = dead_end.pop() {
+                if live[v] > 0 {
+                    return Some(v);
+                }
+            }
+
+            while cursor < num_vertices {
+                if live[cursor] > 0 {
+                    return Some(cursor);
+                }
+
+                cursor += 1;
+            }
+
+            None
+        });
+    }
+
+    output
+}
+
+/// Reorders the triangles and vertices of a triangle mesh for vertex cache locality.
+///
+/// Vertices are renumbered in the order they are first referenced, and any unreferenced vertices are moved to the end.
+pub fn optimize_vertex_order<V, I>(mesh: &mut Mesh<V, I>, cache_size: usize) where V: Vertex, I: MeshIndex {
+    let num_vertices = mesh.vertices.len();
+
+    let mut indices = optimize_triangle_order(&mesh.indices, num_vertices, cache_size);
+
+    let mut remap: Vec<Option<usize>> = vec![None; num_vertices];
+    let mut order = Vec::with_capacity(num_vertices);
+
+    for index in &mut indices {
+        let old = index.to_usize();
+
+        let new = match remap[old] {
+            Some(new) => new,
+            None => {
+                let new = order.len();
+                remap[old] = Some(new);
+                order.push(old);
+                new
+            }
+        };
+
+        *index = I::from_usize(new).expect("Index out of range");
+    }
+
+    for old in 0..num_vertices {
+        if remap[old].is_none() {
+            order.push(old);
+        }
+    }
+
+    let mut vertices: Vec<Option<V>> = mesh.vertices.drain(..).map(Some).collect();
+
+    mesh.vertices = order.into_iter().map(|old| vertices[old].take().unwrap()).collect();
+    mesh.indices = indices;
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Point3;
+
+    use ::mesh::{Mesh, SimpleVertex};
+
+    use super::{optimize_vertex_order, average_cache_miss_ratio, DEFAULT_CACHE_SIZE};
+
+    #[test]
+    fn test_optimize_grid() {
+        let size = 16;
+
+        let mut mesh = Mesh { indices: Vec::new(), vertices: Vec::new() };
+
+        for y in 0..size {
+            for x in 0..size {
+                mesh.vertices.push(SimpleVertex { position: Point3::new(x as f32, y as f32, 0.0), data: () });
+            }
+        }
+
+        let mut triangles = Vec::new();
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let i = y * size + x;
+
+                triangles.push([i, i + 1, i + size]);
+                triangles.push([i + 1, i + size + 1, i + size]);
+            }
+        }
+
+        // Scramble the triangles, which is the worst case for a vertex cache
+        for t in 0..triangles.len() {
+            mesh.indices.extend_from_slice(&triangles[(t * 97) % triangles.len()]);
+        }
+
+        let before = average_cache_miss_ratio(&mesh.indices, DEFAULT_CACHE_SIZE);
+
+        let num_indices = mesh.indices.len();
+
+        optimize_vertex_order(&mut mesh, DEFAULT_CACHE_SIZE);
+
+        let after = average_cache_miss_ratio(&mesh.indices, DEFAULT_CACHE_SIZE);
+
+        assert!(after < before);
+        assert_eq!(mesh.indices.len(), num_indices);
+        assert_eq!(mesh.vertices.len(), size * size);
+    }
+}
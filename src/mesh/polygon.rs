@@ -0,0 +1,209 @@
+//! Polygon triangulation
+//!
+//! The pipeline only rasterizes points, lines and triangles, so polygons with any number of vertices,
+//! as found in CAD and SVG-style data, are triangulated by ear clipping before being added to a `Mesh`.
+//!
+//! Polygons are projected onto the plane they mostly lie in, so both planar 3D polygons and 2D outlines work.
+//! They can be convex or concave, but must not intersect themselves or contain holes.
+
+use num_traits::cast;
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+
+/// Projects the polygon onto the coordinate plane most perpendicular to its normal
+fn project<V: Vertex, I: MeshIndex>(vertices: &[V], polygon: &[I]) -> Vec<[f64; 2]> {
+    let positions: Vec<[f64; 3]> = polygon.iter().map(|index| {
+        let p = vertices[index.to_usize()].position();
+
+        [cast(p.x).unwrap_or(0.0), cast(p.y).unwrap_or(0.0), cast(p.z).unwrap_or(0.0)]
+    }).collect();
+
+    // Newell's method, which gives a sensible normal even for concave polygons
+    let mut normal = [0.0f64; 3];
+
+    for i in 0..positions.len() {
+        let a = &positions[i];
+        let b = &positions[(i + 1) % positions.len()];
+
+        normal[0] += (a[1] - b[1]) * (a[2] + b[2]);
+        normal[1] += (a[2] - b[2]) * (a[0] + b[0]);
+        normal[2] += (a[0] - b[0]) * (a[1] + b[1]);
+    }
+
+    let (u, v) = if normal[0].abs() > normal[1].abs() && normal[0].abs() > normal[2].abs() {
+        (1, 2)
+    } else if normal[1].abs() > normal[2].abs() {
+        (2, 0)
+    } else {
+        (0, 1)
+    };
+
+    positions.iter().map(|p| [p[u], p[v]]).collect()
+}
+
+/// Twice the signed area of the triangle `abc`, positive if counter-clockwise
+#[inline]
+fn cross(a: &[f64; 2], b: &[f64; 2], c: &[f64; 2]) -> f64 {
+    (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+}
+
+/// Triangulates a single simple polygon given as indices into `vertices`, returning triangle indices.
+///
+/// Triangles keep the winding order of the polygon. Collinear and repeated vertices don't produce zero-area triangles,
+/// and polygons with fewer than three distinct vertices produce no triangles at all.
+pub fn triangulate_polygon<V, I>(vertices: &[V], polygon: &[I]) -> Vec<I> where V: Vertex, I: MeshIndex {
+    let mut triangles = Vec::new();
+
+    if polygon.len() < 3 {
+        return triangles;
+    }
+
+    let points = project(vertices, polygon);
+
+    let area: f64 = (0..points.len()).map(|i| cross(&[0.0, 0.0], &points[i], &points[(i + 1) % points.len()])).sum();
+
+    if area == 0.0 {
+        return triangles;
+    }
+
+    // Orientation of the polygon in the projected plane, so convex corners always have a positive cross product
+    let orientation = area.signum();
+
+    // Tolerance for collinear points, relative to the size of the polygon
+    let epsilon = area.abs() * 1e-12;
+
+    let corner = |a: usize, b: usize, c: usize| orientation * cross(&points[a], &points[b], &points[c]);
+
+    let mut remaining: Vec<usize> = (0..points.len()).collect();
+
+    // Drop repeated vertices
+    remaining.dedup_by(|a, b| points[*a] == points[*b]);
+
+    while remaining.len() > 1 && points[remaining[0]] == points[remaining[remaining.len() - 1]] {
+        remaining.pop();
+    }
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+
+        let mut clipped = false;
+
+        for i in 0..n {
+            let (a, b, c) = (remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+
+            let turn = corner(a, b, c);
+
+            if turn.abs() <= epsilon {
+                // Collinear vertices add nothing to the shape, so remove them without emitting a triangle
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+
+            if turn < 0.0 {
+                continue;
+            }
+
+            // An ear may not contain any other vertex of the polygon
+            let contains_vertex = remaining.iter().any(|&p| {
+                p != a && p != b && p != c &&
+                    points[p] != points[a] && points[p] != points[b] && points[p] != points[c] &&
+                    corner(a, b, p) >= 0.0 && corner(b, c, p) >= 0.0 && corner(c, a, p) >= 0.0
+            });
+
+            if !contains_vertex {
+                triangles.extend_from_slice(&[polygon[a], polygon[b], polygon[c]]);
+                remaining.remove(i);
+                clipped = true;
+                break;
+            }
+        }
+
+        if !clipped {
+            // No ears left, which only happens for self-intersecting polygons or from rounding errors,
+            // so fall back to clipping the most convex corner
+            let i = (0..n).max_by(|&i, &j| {
+                let ci = corner(remaining[(i + n - 1) % n], remaining[i], remaining[(i + 1) % n]);
+                let cj = corner(remaining[(j + n - 1) % n], remaining[j], remaining[(j + 1) % n]);
+
+                ci.partial_cmp(&cj).unwrap_or(::std::cmp::Ordering::Equal)
+            }).unwrap();
+
+            triangles.extend_from_slice(&[polygon[remaining[(i + n - 1) % n]], polygon[remaining[i]], polygon[remaining[(i + 1) % n]]]);
+            remaining.remove(i);
+        }
+    }
+
+    if remaining.len() == 3 && corner(remaining[0], remaining[1], remaining[2]).abs() > epsilon {
+        triangles.extend_from_slice(&[polygon[remaining[0]], polygon[remaining[1]], polygon[remaining[2]]]);
+    }
+
+    triangles
+}
+
+impl<V, I> Mesh<V, I> where V: Vertex, I: MeshIndex {
+    /// Creates a triangle mesh from polygons with any number of vertices, each given as indices into `vertices`.
+    ///
+    /// See `triangulate_polygon` for details.
+    pub fn from_polygons<P>(vertices: Vec<V>, polygons: &[P]) -> Mesh<V, I> where P: AsRef<[I]> {
+        let mut indices = Vec::new();
+
+        for polygon in polygons {
+            indices.extend(triangulate_polygon(&vertices, polygon.as_ref()));
+        }
+
+        Mesh { indices, vertices }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Point3;
+
+    use ::mesh::{Mesh, SimpleVertex};
+
+    use super::cross;
+
+    fn vertices(points: &[(f32, f32)]) -> Vec<SimpleVertex<f32, ()>> {
+        points.iter().map(|&(x, y)| SimpleVertex { position: Point3::new(x, y, 0.0), data: () }).collect()
+    }
+
+    fn area(mesh: &Mesh<SimpleVertex<f32, ()>>) -> f64 {
+        mesh.indices.chunks(3).map(|triangle| {
+            let p: Vec<[f64; 2]> = triangle.iter().map(|&i| {
+                let p = mesh.vertices[i].position;
+                [p.x as f64, p.y as f64]
+            }).collect();
+
+            cross(&p[0], &p[1], &p[2]) / 2.0
+        }).sum()
+    }
+
+    #[test]
+    fn test_triangulate_concave() {
+        // L-shape with an area of 3
+        let mesh: Mesh<_> = Mesh::from_polygons(vertices(&[(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)]),
+                                                &[vec![0, 1, 2, 3, 4, 5]]);
+
+        assert_eq!(mesh.indices.len(), 4 * 3);
+        assert!((area(&mesh) - 3.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_triangulate_collinear() {
+        // Square with extra vertices along its edges, wound clockwise
+        let mesh: Mesh<_> = Mesh::from_polygons(vertices(&[(0.0, 0.0), (0.0, 1.0), (0.0, 2.0), (2.0, 2.0), (2.0, 1.0), (2.0, 0.0), (1.0, 0.0)]),
+                                                &[vec![0, 1, 2, 3, 4, 5, 6]]);
+
+        assert!((area(&mesh) + 4.0).abs() < 1e-6);
+
+        for triangle in mesh.indices.chunks(3) {
+            let p: Vec<[f64; 2]> = triangle.iter().map(|&i| {
+                let p = mesh.vertices[i].position;
+                [p.x as f64, p.y as f64]
+            }).collect();
+
+            assert!(cross(&p[0], &p[1], &p[2]).abs() > 1e-6);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+//! Mesh simplification using quadric error metrics
+//!
+//! Simplification repeatedly collapses the edge that changes the shape of the mesh the least,
+//! as measured by the sum of squared distances to the planes of the original triangles around each vertex.
+//! See "Surface Simplification Using Quadric Error Metrics" by Garland and Heckbert.
+//!
+//! Edges are collapsed onto one of their existing vertices, so simplified index buffers still refer to the
+//! original vertices, and every level of detail of a mesh can share the same vertex buffer.
+//! Vertices on open boundaries are never moved, so holes and mesh borders keep their shape.
+
+use std::collections::HashMap;
+
+use num_traits::cast;
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+
+/// Symmetric 4x4 quadric matrix, storing only the upper triangle
+#[derive(Debug, Clone, Copy, Default)]
+struct Quadric([f64; 10]);
+
+impl Quadric {
+    /// Quadric of the plane `ax + by + cz + d = 0`, scaled by `weight`
+    fn plane(a: f64, b: f64, c: f64, d: f64, weight: f64) -> Quadric {
+        Quadric([a * a * weight, a * b * weight, a * c * weight, a * d * weight,
+                 b * b * weight, b * c * weight, b * d * weight,
+                 c * c * weight, c * d * weight,
+                 d * d * weight])
+    }
+
+    fn add(&mut self, other: &Quadric) {
+        for i in 0..10 {
+            self.0[i] += other.0[i];
+        }
+    }
+
+    /// Sum of squared distances from `p` to all planes in the quadric
+    fn error(&self, p: &[f64; 3]) -> f64 {
+        let q = &self.0;
+        let (x, y, z) = (p[0], p[1], p[2]);
+
+        q[0] * x * x + 2.0 * q[1] * x * y + 2.0 * q[2] * x * z + 2.0 * q[3] * x +
+            q[4] * y * y + 2.0 * q[5] * y * z + 2.0 * q[6] * y +
+            q[7] * z * z + 2.0 * q[8] * z +
+            q[9]
+    }
+}
+
+fn sub(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1],
+     a[2] * b[0] - a[0] * b[2],
+     a[0] * b[1] - a[1] * b[0]]
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn positions<V: Vertex, I: MeshIndex>(mesh: &Mesh<V, I>) -> Vec<[f64; 3]> {
+    mesh.vertices.iter().map(|vertex| {
+        let p = vertex.position();
+
+        [cast(p.x).unwrap_or(0.0), cast(p.y).unwrap_or(0.0), cast(p.z).unwrap_or(0.0)]
+    }).collect()
+}
+
+/// Radius of the bounding sphere around the center of the mesh bounding box
+pub ( in ::mesh ) fn bounding_radius<V: Vertex, I: MeshIndex>(mesh: &Mesh<V, I>) -> f64 {
+    let positions = positions(mesh);
+
+    if positions.is_empty() {
+        return 0.0;
+    }
+
+    let mut min = positions[0];
+    let mut max = positions[0];
+
+    for p in &positions {
+        for i in 0..3 {
+            if p[i] < min[i] { min[i] = p[i]; }
+            if p[i] > max[i] { max[i] = p[i]; }
+        }
+    }
+
+    let extent = sub(&max, &min);
+
+    dot(&extent, &extent).sqrt() / 2.0
+}
+
+/// Returns true if moving vertex `from` onto vertex `to` would flip any of the remaining triangles around `from`
+fn flips(from: usize, to: usize, positions: &[[f64; 3]], indices: &[usize], triangles: &[usize]) -> bool {
+    for &t in triangles {
+        let triangle = &indices[t * 3..t * 3 + 3];
+
+        if triangle.contains(&to) {
+            continue;
+        }
+
+        let corners = [positions[triangle[0]], positions[triangle[1]], positions[triangle[2]]];
+        let mut moved = corners;
+
+        for i in 0..3 {
+            if triangle[i] == from {
+                moved[i] = positions[to];
+            }
+        }
+
+        let before = cross(&sub(&corners[1], &corners[0]), &sub(&corners[2], &corners[0]));
+        let after = cross(&sub(&moved[1], &moved[0]), &sub(&moved[2], &moved[0]));
+
+        if dot(&before, &after) <= 0.0 {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Simplifies a triangle mesh down to around `target_ratio` of its triangles,
+/// returning a new index buffer for the original vertices.
+///
+/// The result may have more triangles than requested if the mesh can't be simplified further
+/// without moving boundary vertices or flipping triangles.
+pub fn simplify<V, I>(mesh: &Mesh<V, I>, target_ratio: f32) -> Vec<I> where V: Vertex, I: MeshIndex {
+    simplify_with_error(mesh, target_ratio).0
+}
+
+/// Same as `simplify`, but also returns the largest error introduced,
+/// as a distance relative to the bounding radius of the mesh.
+pub fn simplify_with_error<V, I>(mesh: &Mesh<V, I>, target_ratio: f32) -> (Vec<I>, f32) where V: Vertex, I: MeshIndex {
+    assert!(target_ratio >= 0.0 && target_ratio <= 1.0, "Target ratio must be between zero and one");
+
+    let positions = positions(mesh);
+    let num_vertices = positions.len();
+
+    let mut indices: Vec<usize> = mesh.indices.chunks(3)
+                                      .filter(|triangle| triangle.len() == 3)
+                                      .flat_map(|triangle| triangle.iter().map(|index| index.to_usize()))
+                                      .collect();
+
+    let target = (((indices.len() / 3) as f32 * target_ratio).round() as usize) * 3;
+
+    let mut quadrics = vec![Quadric::default(); num_vertices];
+    let mut edges = HashMap::new();
+
+    for triangle in indices.chunks(3) {
+        let (a, b, c) = (positions[triangle[0]], positions[triangle[1]], positions[triangle[2]]);
+
+        let normal = cross(&sub(&b, &a), &sub(&c, &a));
+        let length = dot(&normal, &normal).sqrt();
+
+        if length > 0.0 {
+            let n = [normal[0] / length, normal[1] / length, normal[2] / length];
+
+            // Weighted by area, so large triangles have more influence than slivers
+            let quadric = Quadric::plane(n[0], n[1], n[2], -dot(&n, &a), length / 2.0);
+
+            for &vertex in triangle {
+                quadrics[vertex].add(&quadric);
+            }
+        }
+
+        for &(start, end) in &[(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+            let edge = if start < end { (start, end) } else { (end, start) };
+
+            *edges.entry(edge).or_insert(0usize) += 1;
+        }
+    }
+
+    // Vertices on edges used by a single triangle are on a boundary
+    let mut locked = vec![false; num_vertices];
+
+    for (&(start, end), &count) in &edges {
+        if count == 1 {
+            locked[start] = true;
+            locked[end] = true;
+        }
+    }
+
+    let mut max_error: f64 = 0.0;
+
+    while indices.len() > target {
+        let mut adjacency = vec![Vec::new(); num_vertices];
+
+        for (t, triangle) in indices.chunks(3).enumerate() {
+            for &vertex in triangle {
+                adjacency[vertex].push(t);
+            }
+        }
+
+        let mut candidates = Vec::with_capacity(indices.len() * 2);
+
+        for triangle in indices.chunks(3) {
+            for &(from, to) in &[(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0]),
+                                 (triangle[1], triangle[0]), (triangle[2], triangle[1]), (triangle[0], triangle[2])] {
+                if !locked[from] {
+                    let mut quadric = quadrics[from];
+                    quadric.add(&quadrics[to]);
+
+                    candidates.push((quadric.error(&positions[to]).max(0.0), from, to));
+                }
+            }
+        }
+
+        candidates.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+
+        let mut remap: Vec<usize> = (0..num_vertices).collect();
+        let mut touched = vec![false; num_vertices];
+        let mut remaining = indices.len();
+        let mut collapsed = false;
+
+        for (error, from, to) in candidates {
+            if remaining <= target {
+                break;
+            }
+
+            if touched[from] || touched[to] || flips(from, to, &positions, &indices, &adjacency[from]) {
+                continue;
+            }
+
+            remap[from] = to;
+
+            let quadric = quadrics[from];
+            quadrics[to].add(&quadric);
+
+            // Don't touch the neighborhood again this pass, since the adjacency is now out of date
+            for &t in &adjacency[from] {
+                let triangle = &indices[t * 3..t * 3 + 3];
+
+                if triangle.contains(&to) {
+                    remaining -= 3;
+                }
+
+                for &vertex in triangle {
+                    touched[vertex] = true;
+                }
+            }
+
+            max_error = max_error.max(error);
+            collapsed = true;
+        }
+
+        if !collapsed {
+            break;
+        }
+
+        let mut simplified = Vec::with_capacity(remaining);
+
+        for triangle in indices.chunks(3) {
+            let (a, b, c) = (remap[triangle[0]], remap[triangle[1]], remap[triangle[2]]);
+
+            if a != b && b != c && a != c {
+                simplified.push(a);
+                simplified.push(b);
+                simplified.push(c);
+            }
+        }
+
+        indices = simplified;
+    }
+
+    let radius = bounding_radius(mesh);
+
+    let error = if radius > 0.0 { max_error.sqrt() / radius } else { 0.0 };
+
+    let indices = indices.into_iter().map(|index| I::from_usize(index).expect("Index out of range")).collect();
+
+    (indices, error as f32)
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Point3;
+
+    use ::mesh::{Mesh, SimpleVertex};
+
+    use super::simplify_with_error;
+
+    #[test]
+    fn test_simplify_plane() {
+        let size = 5;
+
+        let mut mesh = Mesh { indices: Vec::new(), vertices: Vec::new() };
+
+        for y in 0..size {
+            for x in 0..size {
+                mesh.vertices.push(SimpleVertex { position: Point3::new(x as f32, y as f32, 0.0), data: () });
+            }
+        }
+
+        for y in 0..size - 1 {
+            for x in 0..size - 1 {
+                let i = y * size + x;
+
+                mesh.indices.extend_from_slice(&[i, i + 1, i + size, i + 1, i + size + 1, i + size]);
+            }
+        }
+
+        let (indices, error) = simplify_with_error(&mesh, 0.5);
+
+        let triangles = indices.len() / 3;
+
+        assert!(triangles < 32 && triangles >= 16);
+        assert!(error < 1e-6);
+        assert!(indices.iter().all(|&index| index < mesh.vertices.len()));
+    }
+}
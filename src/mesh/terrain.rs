@@ -0,0 +1,336 @@
+//! Heightmap terrain
+//!
+//! Converts a heightmap into a regular grid mesh with normals and texture coordinates, optionally split into chunks
+//! with several levels of detail each. Chunks can be drawn at different levels, and the skirts hanging down from
+//! their edges hide the small cracks that appear where levels meet.
+//!
+//! Rows of the heightmap run along the z axis and columns along the x axis, with heights along y.
+//! Triangles wind counter-clockwise when seen from above.
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector2, Vector3};
+
+use ::color::ToChannels;
+use ::geometry::{Coordinate, Dimensions};
+use ::pixels::PixelRead;
+use ::mesh::{SimpleVertex, Mesh};
+use ::mesh::lod::{MeshLod, LodLevel};
+use ::mesh::simplify::bounding_radius;
+
+/// Vertex data of terrain meshes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainData {
+    /// Surface normal, computed from the full resolution heightmap so it matches across chunks and levels
+    pub normal: Vector3<f32>,
+    /// Texture coordinates across the whole heightmap, from `(0, 0)` at the first pixel to `(1, 1)` at the last
+    pub uv: Vector2<f32>,
+}
+
+pub type TerrainVertex = SimpleVertex<f32, TerrainData>;
+
+/// Options for converting heightmaps to meshes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TerrainOptions {
+    /// Distance between neighboring heightmap pixels
+    pub spacing: f32,
+    /// Height of a full intensity pixel
+    pub height_scale: f32,
+    /// Depth of the skirts below the edges of the mesh, or `None` for no skirts
+    pub skirt: Option<f32>,
+}
+
+impl Default for TerrainOptions {
+    fn default() -> TerrainOptions {
+        TerrainOptions {
+            spacing: 1.0,
+            height_scale: 1.0,
+            skirt: None,
+        }
+    }
+}
+
+/// Heights of the whole heightmap, read once from the first channel of every pixel
+struct Heights {
+    dimensions: Dimensions,
+    heights: Vec<f32>,
+}
+
+impl Heights {
+    fn new<P>(heightmap: &P, options: &TerrainOptions) -> Heights where P: PixelRead, P::Color: ToChannels {
+        let dimensions = heightmap.dimensions();
+
+        assert!(dimensions.width > 1 && dimensions.height > 1, "Heightmaps must be at least 2x2 pixels");
+
+        let mut heights = Vec::with_capacity(dimensions.area());
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                let c = unsafe { heightmap.get_pixel_unchecked(heightmap.index_of(Coordinate::new(x, y))) }.to_channels();
+
+                heights.push(c[0] * options.height_scale);
+            }
+        }
+
+        Heights { dimensions, heights }
+    }
+
+    #[inline]
+    fn get(&self, x: u32, y: u32) -> f32 {
+        self.heights[y as usize * self.dimensions.width as usize + x as usize]
+    }
+
+    fn normal(&self, x: u32, y: u32, spacing: f32) -> Vector3<f32> {
+        let (w, h) = (self.dimensions.width - 1, self.dimensions.height - 1);
+
+        let (x0, x1) = (x.saturating_sub(1), (x + 1).min(w));
+        let (y0, y1) = (y.saturating_sub(1), (y + 1).min(h));
+
+        let dx = (self.get(x1, y) - self.get(x0, y)) / ((x1 - x0) as f32 * spacing);
+        let dz = (self.get(x, y1) - self.get(x, y0)) / ((y1 - y0) as f32 * spacing);
+
+        Vector3::new(-dx, 1.0, -dz).normalize()
+    }
+
+    /// Height interpolated between the samples of a coarser grid, used to measure the error of a level of detail
+    fn coarse(&self, x: u32, y: u32, columns: &[u32], rows: &[u32]) -> f32 {
+        let cell = |samples: &[u32], v: u32| {
+            let i = samples.iter().position(|&s| s >= v).unwrap_or(samples.len() - 1).max(1);
+
+            let (a, b) = (samples[i - 1], samples[i]);
+
+            (a, b, (v - a) as f32 / (b - a) as f32)
+        };
+
+        let (x0, x1, fx) = cell(columns, x);
+        let (y0, y1, fy) = cell(rows, y);
+
+        let top = self.get(x0, y0) + (self.get(x1, y0) - self.get(x0, y0)) * fx;
+        let bottom = self.get(x0, y1) + (self.get(x1, y1) - self.get(x0, y1)) * fx;
+
+        top + (bottom - top) * fy
+    }
+}
+
+/// Samples from `start` to `end` inclusive, every `step` pixels
+fn samples(start: u32, end: u32, step: u32) -> Vec<u32> {
+    let mut samples: Vec<u32> = (start..end).filter(|v| (v - start) % step == 0).collect();
+
+    samples.push(end);
+
+    samples
+}
+
+/// Builds the grid mesh over the given columns and rows of the heightmap
+fn grid_mesh(heights: &Heights, options: &TerrainOptions, columns: &[u32], rows: &[u32]) -> Mesh<TerrainVertex, u32> {
+    let (w, h) = (heights.dimensions.width - 1, heights.dimensions.height - 1);
+
+    let mut vertices = Vec::with_capacity(columns.len() * rows.len());
+
+    for &y in rows {
+        for &x in columns {
+            vertices.push(SimpleVertex {
+                position: Point3::new(x as f32 * options.spacing, heights.get(x, y), y as f32 * options.spacing),
+                data: TerrainData {
+                    normal: heights.normal(x, y, options.spacing),
+                    uv: Vector2::new(x as f32 / w as f32, y as f32 / h as f32),
+                },
+            });
+        }
+    }
+
+    let stride = columns.len() as u32;
+
+    let mut indices = Vec::with_capacity((columns.len() - 1) * (rows.len() - 1) * 6);
+
+    for r in 0..rows.len() as u32 - 1 {
+        for c in 0..stride - 1 {
+            let a = r * stride + c;
+            let (b, cc, d) = (a + 1, a + stride, a + stride + 1);
+
+            indices.extend_from_slice(&[a, cc, b, b, cc, d]);
+        }
+    }
+
+    if let Some(depth) = options.skirt {
+        add_skirt(&mut vertices, &mut indices, columns.len(), rows.len(), depth);
+    }
+
+    Mesh { indices, vertices }
+}
+
+/// Hangs a strip of triangles down from every edge on the border of a grid, facing outwards
+fn add_skirt(vertices: &mut Vec<TerrainVertex>, indices: &mut Vec<u32>, columns: usize, rows: usize, depth: f32) {
+    let on_border = |i: u32, j: u32| {
+        let (ci, ri) = (i as usize % columns, i as usize / columns);
+        let (cj, rj) = (j as usize % columns, j as usize / columns);
+
+        (ci == cj && (ci == 0 || ci == columns - 1)) || (ri == rj && (ri == 0 || ri == rows - 1))
+    };
+
+    let mut lowered: Vec<Option<u32>> = vec![None; vertices.len()];
+
+    let mut lower = |vertices: &mut Vec<TerrainVertex>, i: u32| {
+        *lowered[i as usize].get_or_insert_with(|| {
+            let mut vertex = vertices[i as usize].clone();
+
+            vertex.position.y -= depth;
+
+            vertices.push(vertex);
+
+            vertices.len() as u32 - 1
+        })
+    };
+
+    let mut skirt = Vec::new();
+
+    for triangle in indices.chunks(3) {
+        for e in 0..3 {
+            let (p, q) = (triangle[e], triangle[(e + 1) % 3]);
+
+            if on_border(p, q) {
+                let (lp, lq) = (lower(vertices, p), lower(vertices, q));
+
+                skirt.extend_from_slice(&[q, p, lp, q, lp, lq]);
+            }
+        }
+    }
+
+    indices.extend(skirt);
+}
+
+/// Converts a whole heightmap into a single grid mesh, with one vertex per pixel.
+///
+/// Heights are taken from the first channel of each pixel.
+pub fn terrain_mesh<P>(heightmap: &P, options: &TerrainOptions) -> Mesh<TerrainVertex, u32> where P: PixelRead, P::Color: ToChannels {
+    let heights = Heights::new(heightmap, options);
+
+    let (w, h) = (heights.dimensions.width - 1, heights.dimensions.height - 1);
+
+    grid_mesh(&heights, options, &samples(0, w, 1), &samples(0, h, 1))
+}
+
+/// A square piece of terrain with its own levels of detail
+#[derive(Debug, Clone)]
+pub struct TerrainChunk {
+    /// Center of the bounding box of the chunk
+    pub center: Point3<f32>,
+    /// Levels of detail, where each level skips twice as many pixels as the previous one
+    pub lod: MeshLod<TerrainVertex, u32>,
+}
+
+/// Splits a heightmap into chunks of `chunk_size` by `chunk_size` pixel cells, each with `levels` levels of detail.
+///
+/// Neighboring chunks share their edge vertices, so they line up exactly when drawn at the same level.
+/// The error of each level is the largest height difference from the full resolution heightmap,
+/// so `MeshLod::select` picks levels as it does for simplified meshes.
+pub fn terrain_chunks<P>(heightmap: &P, options: &TerrainOptions, chunk_size: u32, levels: usize) -> Vec<TerrainChunk>
+    where P: PixelRead, P::Color: ToChannels {
+    assert!(chunk_size > 0 && levels > 0, "Chunks need at least one cell and one level");
+
+    let heights = Heights::new(heightmap, options);
+
+    let (w, h) = (heights.dimensions.width - 1, heights.dimensions.height - 1);
+
+    let mut chunks = Vec::new();
+
+    for y0 in (0..h).filter(|y| y % chunk_size == 0) {
+        for x0 in (0..w).filter(|x| x % chunk_size == 0) {
+            let (x1, y1) = ((x0 + chunk_size).min(w), (y0 + chunk_size).min(h));
+
+            let mut lod_levels: Vec<LodLevel<TerrainVertex, u32>> = Vec::with_capacity(levels);
+
+            let mut radius = 0.0;
+            let mut center = Point3::origin();
+
+            for level in 0..levels {
+                let step = 1 << level;
+
+                let (columns, rows) = (samples(x0, x1, step), samples(y0, y1, step));
+
+                let mesh = grid_mesh(&heights, options, &columns, &rows);
+
+                let error = if level == 0 {
+                    radius = bounding_radius(&mesh) as f32;
+
+                    let (min, max) = mesh.vertices.iter().fold((mesh.vertices[0].position, mesh.vertices[0].position), |(min, max), v| {
+                        let p = v.position;
+
+                        (Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+                         Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)))
+                    });
+
+                    center = Point3::from_coordinates((min.coords + max.coords) * 0.5);
+
+                    0.0
+                } else {
+                    let mut error = 0.0f32;
+
+                    for y in y0..y1 + 1 {
+                        for x in x0..x1 + 1 {
+                            error = error.max((heights.get(x, y) - heights.coarse(x, y, &columns, &rows)).abs());
+                        }
+                    }
+
+                    if radius > 0.0 { error / radius } else { 0.0 }
+                };
+
+                lod_levels.push(LodLevel { mesh: Arc::new(mesh), error });
+
+                // Further levels would not remove any more vertices
+                if columns.len() == 2 && rows.len() == 2 {
+                    break;
+                }
+            }
+
+            chunks.push(TerrainChunk { center, lod: MeshLod::from_levels(lod_levels, radius) });
+        }
+    }
+
+    chunks
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::Rf32Color;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_terrain_mesh() {
+        let heightmap = ColorBuffer::from_fn(Dimensions::new(3, 3), |coord| Rf32Color::new(coord.x as f32));
+
+        let mesh = terrain_mesh(&heightmap, &TerrainOptions::default());
+
+        assert_eq!(mesh.vertices.len(), 9);
+        assert_eq!(mesh.indices.len(), 4 * 6);
+
+        // Slope of 1 along x
+        let normal = mesh.vertices[4].data.normal;
+
+        assert!((normal - Vector3::new(-1.0, 1.0, 0.0).normalize()).norm() < 1e-6);
+
+        let skirted = terrain_mesh(&heightmap, &TerrainOptions { skirt: Some(1.0), ..TerrainOptions::default() });
+
+        // Eight border edges with two triangles each
+        assert_eq!(skirted.vertices.len(), 9 + 8);
+        assert_eq!(skirted.indices.len(), 4 * 6 + 8 * 6);
+    }
+
+    #[test]
+    fn test_terrain_chunks() {
+        let heightmap = ColorBuffer::from_fn(Dimensions::new(9, 5), |coord| Rf32Color::new(((coord.x * coord.y) % 3) as f32));
+
+        let chunks = terrain_chunks(&heightmap, &TerrainOptions::default(), 4, 3);
+
+        assert_eq!(chunks.len(), 2);
+
+        let levels = chunks[0].lod.levels();
+
+        assert_eq!(levels.len(), 3);
+        assert_eq!(levels[0].mesh.vertices.len(), 25);
+        assert_eq!(levels[2].mesh.vertices.len(), 4);
+        assert!(levels[2].error > 0.0);
+    }
+}
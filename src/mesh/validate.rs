@@ -0,0 +1,201 @@
+//! Mesh validation
+//!
+//! Most "black screen" bugs come from broken mesh data rather than the shaders,
+//! so validating meshes after loading them catches those problems up front.
+
+use std::collections::HashMap;
+use std::f64::NAN;
+
+use num_traits::cast;
+
+use ::primitive::Primitive;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+
+/// Options controlling which checks `Mesh::validate_with` performs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ValidationOptions {
+    /// Number of indices per primitive, three for triangles by default.
+    ///
+    /// Degenerate triangle and manifold checks are only performed for triangles.
+    pub vertices_per_primitive: usize,
+    /// Triangles with an area at or below this are considered degenerate
+    pub degenerate_area: f64,
+    /// Whether to check for non-manifold edges, which is slower than the other checks
+    pub check_manifold: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> ValidationOptions {
+        ValidationOptions {
+            vertices_per_primitive: 3,
+            degenerate_area: 0.0,
+            check_manifold: false,
+        }
+    }
+}
+
+impl ValidationOptions {
+    /// Default options for meshes rendered as the given primitive type
+    pub fn for_primitive<T: Primitive>() -> ValidationOptions {
+        ValidationOptions {
+            vertices_per_primitive: T::num_vertices(),
+            ..ValidationOptions::default()
+        }
+    }
+}
+
+/// Problems found by mesh validation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MeshReport {
+    /// Number of trailing indices that don't form a whole primitive
+    pub trailing_indices: usize,
+    /// Positions in the index list of indices referring to vertices that don't exist
+    pub out_of_range_indices: Vec<usize>,
+    /// Vertices with NaN or infinite positions
+    pub non_finite_vertices: Vec<usize>,
+    /// Triangles, by primitive number, that have repeated vertices or zero area
+    pub degenerate_triangles: Vec<usize>,
+    /// Edges, as sorted vertex index pairs, shared by more than two triangles
+    pub non_manifold_edges: Vec<(usize, usize)>,
+}
+
+impl MeshReport {
+    /// Returns true if no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.trailing_indices == 0 &&
+            self.out_of_range_indices.is_empty() &&
+            self.non_finite_vertices.is_empty() &&
+            self.degenerate_triangles.is_empty() &&
+            self.non_manifold_edges.is_empty()
+    }
+}
+
+/// Area of a triangle, which is NaN for non-finite vertices
+fn triangle_area<V: Vertex, I: MeshIndex>(mesh: &Mesh<V, I>, a: usize, b: usize, c: usize) -> f64 {
+    let position = |index: usize| -> [f64; 3] {
+        let p = mesh.vertices[index].position();
+
+        [cast(p.x).unwrap_or(NAN), cast(p.y).unwrap_or(NAN), cast(p.z).unwrap_or(NAN)]
+    };
+
+    let (pa, pb, pc) = (position(a), position(b), position(c));
+
+    let u = [pb[0] - pa[0], pb[1] - pa[1], pb[2] - pa[2]];
+    let v = [pc[0] - pa[0], pc[1] - pa[1], pc[2] - pa[2]];
+
+    let cross = [u[1] * v[2] - u[2] * v[1],
+                 u[2] * v[0] - u[0] * v[2],
+                 u[0] * v[1] - u[1] * v[0]];
+
+    (cross[0] * cross[0] + cross[1] * cross[1] + cross[2] * cross[2]).sqrt() / 2.0
+}
+
+impl<V, I> Mesh<V, I> where V: Vertex, I: MeshIndex {
+    /// Validates the mesh as a triangle mesh with the default options
+    pub fn validate(&self) -> MeshReport {
+        self.validate_with(&ValidationOptions::default())
+    }
+
+    /// Validates the mesh with the given options
+    pub fn validate_with(&self, options: &ValidationOptions) -> MeshReport {
+        assert!(options.vertices_per_primitive > 0, "Primitives must have at least one vertex");
+
+        let mut report = MeshReport::default();
+
+        let num_vertices = self.vertices.len();
+
+        report.trailing_indices = self.indices.len() % options.vertices_per_primitive;
+
+        report.out_of_range_indices = self.indices.iter().enumerate()
+                                          .filter(|&(_, index)| index.to_usize() >= num_vertices)
+                                          .map(|(i, _)| i)
+                                          .collect();
+
+        report.non_finite_vertices = self.vertices.iter().enumerate()
+                                         .filter(|&(_, vertex)| {
+                                             let p = vertex.position();
+                                             !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite())
+                                         })
+                                         .map(|(i, _)| i)
+                                         .collect();
+
+        if options.vertices_per_primitive != 3 {
+            return report;
+        }
+
+        let mut edges = HashMap::new();
+
+        for (primitive, triangle) in self.indices.chunks(3).enumerate() {
+            if triangle.len() != 3 || triangle.iter().any(|index| index.to_usize() >= num_vertices) {
+                continue;
+            }
+
+            let (a, b, c) = (triangle[0].to_usize(), triangle[1].to_usize(), triangle[2].to_usize());
+
+            let repeated = a == b || b == c || a == c;
+
+            if repeated || triangle_area(self, a, b, c) <= options.degenerate_area {
+                report.degenerate_triangles.push(primitive);
+            }
+
+            // Edges collapsed by repeated vertices aren't real edges
+            if options.check_manifold && !repeated {
+                for &(start, end) in &[(a, b), (b, c), (c, a)] {
+                    let edge = if start < end { (start, end) } else { (end, start) };
+
+                    *edges.entry(edge).or_insert(0usize) += 1;
+                }
+            }
+        }
+
+        report.non_manifold_edges = edges.into_iter()
+                                         .filter(|&(_, count)| count > 2)
+                                         .map(|(edge, _)| edge)
+                                         .collect();
+
+        report.non_manifold_edges.sort();
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Point3;
+
+    use ::mesh::{Mesh, SimpleVertex};
+
+    use super::ValidationOptions;
+
+    fn vertex(x: f32, y: f32, z: f32) -> SimpleVertex<f32, ()> {
+        SimpleVertex { position: Point3::new(x, y, z), data: () }
+    }
+
+    #[test]
+    fn test_validate() {
+        let mesh = Mesh {
+            indices: vec![0, 1, 2,
+                          0, 1, 3,
+                          0, 1, 4,
+                          0, 0, 2,
+                          0, 1, 9,
+                          2],
+            vertices: vec![
+                vertex(0.0, 0.0, 0.0),
+                vertex(1.0, 0.0, 0.0),
+                vertex(0.0, 1.0, 0.0),
+                vertex(2.0, 0.0, 0.0),
+                vertex(0.0, 0.0, ::std::f32::NAN),
+            ],
+        };
+
+        let report = mesh.validate_with(&ValidationOptions { check_manifold: true, ..ValidationOptions::default() });
+
+        assert!(!report.is_valid());
+        assert_eq!(report.trailing_indices, 1);
+        assert_eq!(report.out_of_range_indices, vec![14]);
+        assert_eq!(report.non_finite_vertices, vec![4]);
+        assert_eq!(report.degenerate_triangles, vec![1, 3]);
+        assert_eq!(report.non_manifold_edges, vec![(0, 1)]);
+    }
+}
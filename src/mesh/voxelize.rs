@@ -0,0 +1,286 @@
+//! Mesh voxelization
+//!
+//! Triangles are rasterized conservatively onto the grid plane facing their dominant axis,
+//! marking every voxel the triangle touches rather than only those containing its samples,
+//! so thin and steep triangles never leave holes in the resulting surface.
+//!
+//! The resulting `VoxelGrid` can be uploaded as a `Texture3D` for cone-traced ambient occlusion,
+//! used as fluid or particle collision bounds, or inspected slice by slice when debugging.
+
+use nalgebra::{Point3, Vector3};
+
+use num_traits::cast;
+
+use ::color::Color;
+use ::geometry::{Dimensions, Coordinate};
+use ::texture::Texture3D;
+use ::memory::{MemoryReport, MemoryUsage};
+use ::pipeline::stages::rasterization::hierarchical::{barycentric_edges, classify_block, BlockCoverage};
+
+use super::{Vertex, Mesh, MeshIndex};
+
+/// Occupancy of a regular grid of cubic voxels
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoxelGrid {
+    origin: Point3<f32>,
+    voxel_size: f32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    voxels: Vec<bool>,
+}
+
+impl MemoryUsage for VoxelGrid {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("voxels", &self.voxels);
+
+        report
+    }
+}
+
+impl VoxelGrid {
+    /// Creates an empty grid of `width` by `height` by `depth` voxels, with the minimum corner of the first voxel at `origin`
+    pub fn new(origin: Point3<f32>, voxel_size: f32, width: u32, height: u32, depth: u32) -> VoxelGrid {
+        VoxelGrid {
+            origin,
+            voxel_size,
+            width,
+            height,
+            depth,
+            voxels: vec![false; width as usize * height as usize * depth as usize],
+        }
+    }
+
+    /// Minimum corner of the grid
+    #[inline]
+    pub fn origin(&self) -> Point3<f32> { self.origin }
+
+    /// Length of the edges of each voxel
+    #[inline]
+    pub fn voxel_size(&self) -> f32 { self.voxel_size }
+
+    /// Number of voxels along the x axis
+    #[inline]
+    pub fn width(&self) -> u32 { self.width }
+
+    /// Number of voxels along the y axis
+    #[inline]
+    pub fn height(&self) -> u32 { self.height }
+
+    /// Number of voxels along the z axis
+    #[inline]
+    pub fn depth(&self) -> u32 { self.depth }
+
+    #[inline]
+    fn index(&self, x: u32, y: u32, z: u32) -> usize {
+        (z as usize * self.height as usize + y as usize) * self.width as usize + x as usize
+    }
+
+    /// Returns true if the voxel is filled, or false if it's empty or out of bounds
+    pub fn get(&self, x: u32, y: u32, z: u32) -> bool {
+        x < self.width && y < self.height && z < self.depth && self.voxels[self.index(x, y, z)]
+    }
+
+    /// Fills or clears a voxel, returning false if out of bounds
+    pub fn set(&mut self, x: u32, y: u32, z: u32, filled: bool) -> bool {
+        if x < self.width && y < self.height && z < self.depth {
+            let index = self.index(x, y, z);
+
+            self.voxels[index] = filled;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Number of filled voxels
+    pub fn count(&self) -> usize {
+        self.voxels.iter().filter(|&&filled| filled).count()
+    }
+
+    /// Voxel containing a world-space position, or `None` if it's outside the grid
+    pub fn voxel_at(&self, position: Point3<f32>) -> Option<(u32, u32, u32)> {
+        let p = (position - self.origin) / self.voxel_size;
+
+        if p.x >= 0.0 && p.y >= 0.0 && p.z >= 0.0 &&
+            p.x < self.width as f32 && p.y < self.height as f32 && p.z < self.depth as f32 {
+            Some((p.x as u32, p.y as u32, p.z as u32))
+        } else {
+            None
+        }
+    }
+
+    /// World-space center of a voxel
+    pub fn voxel_center(&self, x: u32, y: u32, z: u32) -> Point3<f32> {
+        self.origin + Vector3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5) * self.voxel_size
+    }
+
+    /// Converts the grid into a 3D texture, with grid x and y as texel coordinates and grid z as the slice,
+    /// so `voxel_center` positions map to normalized texture coordinates by `(position - origin) / (voxel_size * size)`.
+    pub fn to_texture<C: Color>(&self, filled: C, empty: C) -> Texture3D<C> {
+        Texture3D::from_fn(Dimensions::new(self.width, self.height), self.depth, |coord: Coordinate, z| {
+            if self.voxels[self.index(coord.x, coord.y, z)] { filled } else { empty }
+        })
+    }
+
+    /// Conservatively marks every voxel touched by a triangle given in grid space, where voxels are unit cubes
+    fn fill_triangle(&mut self, p: [Vector3<f32>; 3]) {
+        let normal = (p[1] - p[0]).cross(&(p[2] - p[0]));
+
+        let abs = Vector3::new(normal.x.abs(), normal.y.abs(), normal.z.abs());
+
+        // Project along the axis the triangle faces the most, and rasterize onto the other two
+        let (a, b, d) = if abs.x >= abs.y && abs.x >= abs.z {
+            (1, 2, 0)
+        } else if abs.y >= abs.z {
+            (2, 0, 1)
+        } else {
+            (0, 1, 2)
+        };
+
+        let edges = match barycentric_edges((p[0][a], p[0][b]), (p[1][a], p[1][b]), (p[2][a], p[2][b])) {
+            Some(edges) => edges,
+            None => return,
+        };
+
+        let size = [self.width, self.height, self.depth];
+
+        let range = |axis: usize, lo: f32, hi: f32| -> Option<(u32, u32)> {
+            let max = size[axis] as f32;
+
+            if hi < 0.0 || lo >= max {
+                None
+            } else {
+                Some((lo.max(0.0) as u32, (hi.max(0.0) as u32).min(size[axis] - 1)))
+            }
+        };
+
+        let min3 = |axis: usize| p[0][axis].min(p[1][axis]).min(p[2][axis]);
+        let max3 = |axis: usize| p[0][axis].max(p[1][axis]).max(p[2][axis]);
+
+        let (range_a, range_b) = match (range(a, min3(a), max3(a)), range(b, min3(b), max3(b))) {
+            (Some(range_a), Some(range_b)) => (range_a, range_b),
+            _ => return,
+        };
+
+        let (depth_min, depth_max) = (min3(d), max3(d));
+
+        // Depth of the triangle's plane at a point on the projection plane
+        let plane_depth = |x: f32, y: f32| p[0][d] - (normal[a] * (x - p[0][a]) + normal[b] * (y - p[0][b])) / normal[d];
+
+        for j in range_b.0..range_b.1 + 1 {
+            for i in range_a.0..range_a.1 + 1 {
+                let (x0, y0) = (i as f32, j as f32);
+                let (x1, y1) = (x0 + 1.0, y0 + 1.0);
+
+                if classify_block(&edges, (x0, y0), (x1, y1)) == BlockCoverage::Outside {
+                    continue;
+                }
+
+                let corners = [plane_depth(x0, y0), plane_depth(x1, y0), plane_depth(x0, y1), plane_depth(x1, y1)];
+
+                let lo = corners.iter().cloned().fold(::std::f32::INFINITY, f32::min).max(depth_min);
+                let hi = corners.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max).min(depth_max);
+
+                if let Some((k0, k1)) = range(d, lo, hi) {
+                    for k in k0..k1 + 1 {
+                        let mut voxel = [0; 3];
+
+                        voxel[a] = i;
+                        voxel[b] = j;
+                        voxel[d] = k;
+
+                        let index = self.index(voxel[0], voxel[1], voxel[2]);
+
+                        self.voxels[index] = true;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Voxelizes the surface of a mesh into a grid fitted around it, with `resolution` cubic voxels along its longest side.
+///
+/// Every voxel touched by a triangle is filled, but the interior of closed meshes is left empty.
+pub fn voxelize<V, I>(mesh: &Mesh<V, I>, resolution: u32) -> VoxelGrid where V: Vertex, I: MeshIndex {
+    let positions: Vec<Point3<f32>> = mesh.vertices.iter().map(|vertex| {
+        let p = vertex.position();
+
+        Point3::new(cast(p.x).unwrap(), cast(p.y).unwrap(), cast(p.z).unwrap())
+    }).collect();
+
+    let resolution = resolution.max(1);
+
+    let (mut min, mut max) = match positions.first() {
+        Some(&first) => (first, first),
+        None => return VoxelGrid::new(Point3::origin(), 1.0, 0, 0, 0),
+    };
+
+    for p in &positions {
+        for i in 0..3 {
+            min[i] = min[i].min(p[i]);
+            max[i] = max[i].max(p[i]);
+        }
+    }
+
+    let extent = max - min;
+
+    let longest = extent.x.max(extent.y).max(extent.z);
+
+    let voxel_size = if longest > 0.0 { longest / resolution as f32 } else { 1.0 };
+
+    let count = |length: f32| ((length / voxel_size).ceil() as u32).max(1).min(resolution);
+
+    let mut grid = VoxelGrid::new(min, voxel_size, count(extent.x), count(extent.y), count(extent.z));
+
+    for triangle in mesh.indices.chunks(3).filter(|triangle| triangle.len() == 3) {
+        let to_grid = |index: I| (positions[index.to_usize()] - min) / voxel_size;
+
+        grid.fill_triangle([to_grid(triangle[0]), to_grid(triangle[1]), to_grid(triangle[2])]);
+    }
+
+    grid
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::Rf32Color;
+    use ::mesh::SimpleVertex;
+
+    use super::*;
+
+    #[test]
+    fn test_voxelize_slanted_quad() {
+        let vertex = |x: f32, y: f32, z: f32| SimpleVertex { position: Point3::new(x, y, z), data: () };
+
+        // Quad from the bottom front edge to the top back edge of a cube
+        let mesh: Mesh<SimpleVertex<f32, ()>> = Mesh {
+            indices: vec![0, 1, 2, 1, 3, 2],
+            vertices: vec![vertex(0.0, 0.0, 0.0), vertex(8.0, 0.0, 0.0), vertex(0.0, 8.0, 8.0), vertex(8.0, 8.0, 8.0)],
+        };
+
+        let grid = voxelize(&mesh, 8);
+
+        assert_eq!((grid.width(), grid.height(), grid.depth()), (8, 8, 8));
+        assert_eq!(grid.voxel_size(), 1.0);
+
+        // The diagonal is filled without gaps, and corners far from it are empty
+        for i in 0..8 {
+            assert!(grid.get(3, i, i));
+        }
+
+        assert!(!grid.get(0, 7, 0));
+        assert!(!grid.get(0, 0, 7));
+
+        assert_eq!(grid.voxel_at(Point3::new(2.5, 3.5, 3.5)), Some((2, 3, 3)));
+        assert_eq!(grid.voxel_at(Point3::new(-0.5, 0.0, 0.0)), None);
+
+        let texture = grid.to_texture(Rf32Color::new(1.0), Rf32Color::new(0.0));
+
+        assert_eq!(texture.get(Coordinate::new(3, 5), 5), Some(Rf32Color::new(1.0)));
+    }
+}
@@ -0,0 +1,368 @@
+//! Procedural noise
+//!
+//! Gradient (Perlin and simplex) and cellular (Worley) noise in two and three dimensions, plus fractal sums of octaves,
+//! generic over `FloatScalar` so they can be called with the same float type as the rest of a shader.
+//! A `Noise` is cheap to share between threads, so it can live in the global uniforms of a pipeline,
+//! or be used with `ColorBuffer::from_fn` to generate textures ahead of time.
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+
+#[inline(always)]
+fn n<N: FloatScalar>(v: f64) -> N { cast(v).unwrap() }
+
+#[inline(always)]
+fn floor_i<N: FloatScalar>(v: N) -> i32 { cast::<N, i64>(v.floor()).unwrap() as i32 }
+
+/// Perlin's fade curve, `6t^5 - 15t^4 + 10t^3`
+#[inline]
+fn fade<N: FloatScalar>(t: N) -> N {
+    t * t * t * (t * (t * n(6.0) - n(15.0)) + n(10.0))
+}
+
+#[inline]
+fn lerp<N: FloatScalar>(a: N, b: N, t: N) -> N {
+    a + (b - a) * t
+}
+
+#[inline]
+fn grad2<N: FloatScalar>(hash: u8, x: N, y: N) -> N {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+#[inline]
+fn grad3<N: FloatScalar>(hash: u8, x: N, y: N, z: N) -> N {
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+/// Parameters for summing octaves of noise, also known as fractal Brownian motion
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct Fractal {
+    /// Number of layers of noise
+    pub octaves: u32,
+    /// Frequency multiplier between octaves
+    pub lacunarity: f64,
+    /// Amplitude multiplier between octaves
+    pub gain: f64,
+}
+
+impl Default for Fractal {
+    fn default() -> Fractal {
+        Fractal { octaves: 5, lacunarity: 2.0, gain: 0.5 }
+    }
+}
+
+impl Fractal {
+    /// Sums octaves of `f`, called with the frequency of each octave, normalized back to the range of a single octave
+    pub fn sum<N, F>(&self, mut f: F) -> N where N: FloatScalar, F: FnMut(N) -> N {
+        let (mut total, mut norm) = (N::zero(), N::zero());
+        let (mut frequency, mut amplitude) = (N::one(), N::one());
+
+        for _ in 0..self.octaves {
+            total = total + f(frequency) * amplitude;
+            norm = norm + amplitude;
+
+            frequency = frequency * n(self.lacunarity);
+            amplitude = amplitude * n(self.gain);
+        }
+
+        if norm > N::zero() { total / norm } else { N::zero() }
+    }
+}
+
+/// Seeded noise generator
+///
+/// Gradient noise is roughly in `[-1, 1]` and zero at integer coordinates,
+/// while Worley noise is the distance to the nearest feature point, roughly in `[0, 1]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Noise {
+    /// Permutation of `0..256`, repeated twice to avoid wrapping indices
+    perm: Vec<u8>,
+}
+
+impl Default for Noise {
+    fn default() -> Noise { Noise::new(0) }
+}
+
+impl Noise {
+    /// Creates a generator with a permutation table shuffled by `seed`
+    pub fn new(seed: u64) -> Noise {
+        let mut table: Vec<u8> = (0..256).map(|i| i as u8).collect();
+
+        // xorshift64*, which must not start at zero
+        let mut state = seed ^ 0x9E37_79B9_7F4A_7C15;
+
+        for i in (1..256).rev() {
+            state ^= state >> 12;
+            state ^= state << 25;
+            state ^= state >> 27;
+
+            let j = (state.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32) as usize % (i + 1);
+
+            table.swap(i, j);
+        }
+
+        let mut perm = table.clone();
+
+        perm.extend_from_slice(&table);
+
+        Noise { perm }
+    }
+
+    #[inline]
+    fn hash2(&self, x: i32, y: i32) -> u8 {
+        self.perm[self.perm[(x & 255) as usize] as usize + (y & 255) as usize]
+    }
+
+    #[inline]
+    fn hash3(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.perm[self.hash2(x, y) as usize + (z & 255) as usize]
+    }
+
+    /// 2D Perlin noise
+    pub fn perlin2<N: FloatScalar>(&self, x: N, y: N) -> N {
+        let (xi, yi) = (floor_i(x), floor_i(y));
+        let (xf, yf) = (x - x.floor(), y - y.floor());
+        let (u, v) = (fade(xf), fade(yf));
+
+        let one = N::one();
+
+        let a = lerp(grad2(self.hash2(xi, yi), xf, yf), grad2(self.hash2(xi + 1, yi), xf - one, yf), u);
+        let b = lerp(grad2(self.hash2(xi, yi + 1), xf, yf - one), grad2(self.hash2(xi + 1, yi + 1), xf - one, yf - one), u);
+
+        lerp(a, b, v)
+    }
+
+    /// 3D Perlin noise
+    pub fn perlin3<N: FloatScalar>(&self, x: N, y: N, z: N) -> N {
+        let (xi, yi, zi) = (floor_i(x), floor_i(y), floor_i(z));
+        let (xf, yf, zf) = (x - x.floor(), y - y.floor(), z - z.floor());
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            grad3(self.hash3(xi + dx, yi + dy, zi + dz),
+                  xf - n(dx as f64), yf - n(dy as f64), zf - n(dz as f64))
+        };
+
+        let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+        let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+        let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+        let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+
+        lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+    }
+
+    /// 2D simplex noise, which has fewer directional artifacts than Perlin noise
+    pub fn simplex2<N: FloatScalar>(&self, x: N, y: N) -> N {
+        let f2: N = n(0.5 * (3.0f64.sqrt() - 1.0));
+        let g2: N = n((3.0 - 3.0f64.sqrt()) / 6.0);
+
+        // Skew into the simplex grid to find the containing cell
+        let s = (x + y) * f2;
+        let (i, j) = (floor_i(x + s), floor_i(y + s));
+
+        let t = n::<N>((i + j) as f64) * g2;
+        let x0 = x - (n::<N>(i as f64) - t);
+        let y0 = y - (n::<N>(j as f64) - t);
+
+        // Which of the two triangles of the cell
+        let (i1, j1) = if x0 > y0 { (1, 0) } else { (0, 1) };
+
+        let x1 = x0 - n(i1 as f64) + g2;
+        let y1 = y0 - n(j1 as f64) + g2;
+        let x2 = x0 - N::one() + g2 * n(2.0);
+        let y2 = y0 - N::one() + g2 * n(2.0);
+
+        let contribution = |hash: u8, x: N, y: N| {
+            let t = n::<N>(0.5) - x * x - y * y;
+
+            if t < N::zero() { N::zero() } else { t * t * t * t * grad2(hash, x, y) }
+        };
+
+        let total = contribution(self.hash2(i, j), x0, y0)
+                  + contribution(self.hash2(i + i1, j + j1), x1, y1)
+                  + contribution(self.hash2(i + 1, j + 1), x2, y2);
+
+        total * n(70.0)
+    }
+
+    /// 3D simplex noise, which has fewer directional artifacts than Perlin noise
+    pub fn simplex3<N: FloatScalar>(&self, x: N, y: N, z: N) -> N {
+        let f3: N = n(1.0 / 3.0);
+        let g3: N = n(1.0 / 6.0);
+
+        let s = (x + y + z) * f3;
+        let (i, j, k) = (floor_i(x + s), floor_i(y + s), floor_i(z + s));
+
+        let t = n::<N>((i + j + k) as f64) * g3;
+        let x0 = x - (n::<N>(i as f64) - t);
+        let y0 = y - (n::<N>(j as f64) - t);
+        let z0 = z - (n::<N>(k as f64) - t);
+
+        // Which of the six tetrahedra of the cell, by the order of the offsets
+        let ((i1, j1, k1), (i2, j2, k2)) = if x0 >= y0 {
+            if y0 >= z0 {
+                ((1, 0, 0), (1, 1, 0))
+            } else if x0 >= z0 {
+                ((1, 0, 0), (1, 0, 1))
+            } else {
+                ((0, 0, 1), (1, 0, 1))
+            }
+        } else {
+            if y0 < z0 {
+                ((0, 0, 1), (0, 1, 1))
+            } else if x0 < z0 {
+                ((0, 1, 0), (0, 1, 1))
+            } else {
+                ((0, 1, 0), (1, 1, 0))
+            }
+        };
+
+        let contribution = |hash: u8, x: N, y: N, z: N| {
+            let t = n::<N>(0.6) - x * x - y * y - z * z;
+
+            if t < N::zero() { N::zero() } else { t * t * t * t * grad3(hash, x, y, z) }
+        };
+
+        let corner = |di: i32, dj: i32, dk: i32, offset: f64| {
+            let o: N = n(offset);
+
+            contribution(self.hash3(i + di, j + dj, k + dk),
+                         x0 - n(di as f64) + o, y0 - n(dj as f64) + o, z0 - n(dk as f64) + o)
+        };
+
+        let total = corner(0, 0, 0, 0.0)
+                  + corner(i1, j1, k1, 1.0 / 6.0)
+                  + corner(i2, j2, k2, 2.0 / 6.0)
+                  + corner(1, 1, 1, 3.0 / 6.0);
+
+        total * n(32.0)
+    }
+
+    /// Offset of the feature point within a cell, in `[0, 1)`
+    #[inline]
+    fn feature<N: FloatScalar>(&self, hash: u8) -> N {
+        n::<N>(self.perm[hash as usize + 101] as f64) / n(256.0)
+    }
+
+    /// 2D Worley noise, the distance to the nearest of one random feature point per unit cell
+    pub fn worley2<N: FloatScalar>(&self, x: N, y: N) -> N {
+        let (xi, yi) = (floor_i(x), floor_i(y));
+
+        let mut nearest = n::<N>(8.0);
+
+        for dy in -1..2 {
+            for dx in -1..2 {
+                let (cx, cy) = (xi + dx, yi + dy);
+
+                let hash = self.hash2(cx, cy);
+
+                let px = n::<N>(cx as f64) + self.feature(hash);
+                let py = n::<N>(cy as f64) + self.feature(hash.wrapping_add(53));
+
+                let (ox, oy) = (px - x, py - y);
+
+                nearest = nearest.min(ox * ox + oy * oy);
+            }
+        }
+
+        nearest.sqrt()
+    }
+
+    /// 3D Worley noise, the distance to the nearest of one random feature point per unit cell
+    pub fn worley3<N: FloatScalar>(&self, x: N, y: N, z: N) -> N {
+        let (xi, yi, zi) = (floor_i(x), floor_i(y), floor_i(z));
+
+        let mut nearest = n::<N>(8.0);
+
+        for dz in -1..2 {
+            for dy in -1..2 {
+                for dx in -1..2 {
+                    let (cx, cy, cz) = (xi + dx, yi + dy, zi + dz);
+
+                    let hash = self.hash3(cx, cy, cz);
+
+                    let px = n::<N>(cx as f64) + self.feature(hash);
+                    let py = n::<N>(cy as f64) + self.feature(hash.wrapping_add(53));
+                    let pz = n::<N>(cz as f64) + self.feature(hash.wrapping_add(131));
+
+                    let (ox, oy, oz) = (px - x, py - y, pz - z);
+
+                    nearest = nearest.min(ox * ox + oy * oy + oz * oz);
+                }
+            }
+        }
+
+        nearest.sqrt()
+    }
+
+    /// Fractal sum of 2D Perlin noise
+    pub fn fbm2<N: FloatScalar>(&self, x: N, y: N, fractal: Fractal) -> N {
+        fractal.sum(|frequency| self.perlin2(x * frequency, y * frequency))
+    }
+
+    /// Fractal sum of 3D Perlin noise
+    pub fn fbm3<N: FloatScalar>(&self, x: N, y: N, z: N, fractal: Fractal) -> N {
+        fractal.sum(|frequency| self.perlin3(x * frequency, y * frequency, z * frequency))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gradient_noise_range() {
+        let noise = Noise::new(42);
+
+        assert_eq!(noise.perlin2(3.0f32, -7.0), 0.0);
+        assert_eq!(noise.perlin3(1.0f64, 2.0, 3.0), 0.0);
+
+        for i in 0..500 {
+            let (x, y, z) = (i as f64 * 0.173, i as f64 * 0.311 - 20.0, i as f64 * 0.057);
+
+            for &v in &[noise.perlin2(x, y), noise.perlin3(x, y, z), noise.simplex2(x, y),
+                        noise.simplex3(x, y, z), noise.fbm2(x, y, Fractal::default())] {
+                assert!(v >= -1.1 && v <= 1.1, "Noise out of range: {}", v);
+            }
+
+            let w = noise.worley2(x, y);
+
+            assert!(w >= 0.0 && w <= 1.5);
+        }
+    }
+
+    #[test]
+    fn test_seeds() {
+        assert_eq!(Noise::new(1), Noise::new(1));
+        assert!(Noise::new(1) != Noise::new(2));
+
+        let noise = Noise::default();
+
+        assert_eq!(noise.simplex2(0.3f32, 0.7), noise.simplex2(0.3f32, 0.7));
+    }
+}
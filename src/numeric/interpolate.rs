@@ -1,6 +1,6 @@
 //! Interpolation utilities
 
-use std::ops::{Add, Mul};
+use std::ops::{Add, Mul, Deref, DerefMut};
 
 use num_traits::{Float, NumCast};
 
@@ -40,6 +40,58 @@ impl Interpolate for () {
     fn linear_interpolate<R: Float>(_: R, _: &Self, _: &Self) -> Self { () }
 }
 
+/// Wrapper for intermediate uniforms that shouldn't be interpolated,
+/// like textures, samplers or texture handles chosen per mesh.
+///
+/// Interpolating a `Flat` value returns the value of the first vertex, like the `flat` qualifier in GLSL,
+/// so it can be placed in a [`declare_uniforms!`](../../macro.declare_uniforms.html) struct with any type that is `Clone`:
+///
+/// ```ignore
+/// declare_uniforms!(
+///     pub struct MyUniforms {
+///         pub uv: Vector2<f32>,
+///         pub albedo: Flat<Arc<Texture>>,
+///     }
+/// );
+/// ```
+///
+/// The value is cloned for every vertex and fragment, so large values should be shared with `Arc` or referenced by handle.
+/// Values that are the same for the whole draw are better kept in the pipeline uniforms,
+/// which are never interpolated and only need to be `Send + Sync`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Flat<T>(pub T);
+
+impl<T> Flat<T> {
+    /// Returns the wrapped value
+    #[inline]
+    pub fn into_inner(self) -> T { self.0 }
+}
+
+impl<T> From<T> for Flat<T> {
+    #[inline]
+    fn from(value: T) -> Flat<T> { Flat(value) }
+}
+
+impl<T> Deref for Flat<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T { &self.0 }
+}
+
+impl<T> DerefMut for Flat<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T { &mut self.0 }
+}
+
+impl<T> Interpolate for Flat<T> where T: Clone {
+    #[inline(always)]
+    fn barycentric_interpolate<R: Float>(_: R, ux: &Self, _: R, _: &Self, _: R, _: &Self) -> Self { ux.clone() }
+
+    #[inline(always)]
+    fn linear_interpolate<R: Float>(_: R, x1: &Self, _: &Self) -> Self { x1.clone() }
+}
+
 macro_rules! impl_primitive_interpolate {
     ($($t:ty),+) => {
         $(
@@ -3,4 +3,9 @@
 /// Find minimum of two values
 pub fn min<T>(a: T, b: T) -> T where T: PartialOrd {
     if a < b { a } else { b }
-}
\ No newline at end of file
+}
+
+/// Find maximum of two values
+pub fn max<T>(a: T, b: T) -> T where T: PartialOrd {
+    if a > b { a } else { b }
+}
@@ -1,10 +1,19 @@
 use std::cell::UnsafeCell;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cmp::max;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::{ptr, mem};
 
+use scoped_threadpool::Pool;
+
 // Common x86-64 cache line size
 pub const CACHE_LINE_SIZE: usize = 64;
 
+/// Smallest number of items mapped by a single job in `map_chunks`, so small inputs aren't split up needlessly
+pub const MIN_CHUNK_SIZE: usize = 256;
+
+/// Number of chunks created per thread in `map_chunks`, so threads finishing early can pick up remaining work
+pub const CHUNKS_PER_THREAD: usize = 4;
+
 pub struct TrustedThreadSafe<T> {
     inner: UnsafeCell<T>,
 }
@@ -109,4 +118,119 @@ impl<T> Mapper<T> {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+/// Maps every item of `data` on the thread pool, splitting it into contiguous chunks that each
+/// write to their own output, then gathering the outputs back together in order.
+///
+/// Unlike `Mapper`, the number of chunks depends on the number of threads rather than the size of the items,
+/// so large items don't leave most threads without any work.
+pub fn map_chunks<T, U, F>(pool: &mut Pool, data: &[U], mapper: F) -> Vec<T> where T: Send, U: Sync, F: Fn(&U) -> T + Sync {
+    map_chunks_until(pool, data, &AtomicBool::new(false), mapper).unwrap()
+}
+
+/// Same as `map_chunks`, but skips any chunks not started before `cancelled` is set,
+/// returning `None` if any were skipped.
+pub fn map_chunks_until<T, U, F>(pool: &mut Pool, data: &[U], cancelled: &AtomicBool, mapper: F) -> Option<Vec<T>>
+    where T: Send, U: Sync, F: Fn(&U) -> T + Sync {
+    let thread_count = pool.thread_count() as usize;
+
+    if cancelled.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    if thread_count <= 1 || data.len() <= MIN_CHUNK_SIZE {
+        let mut target = Vec::with_capacity(data.len());
+
+        // Still map in chunks, so cancellation is noticed partway through
+        for chunk in data.chunks(MIN_CHUNK_SIZE) {
+            if cancelled.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            target.extend(chunk.iter().map(&mapper));
+        }
+
+        return Some(target);
+    }
+
+    let chunks = thread_count * CHUNKS_PER_THREAD;
+
+    let chunk_size = max(MIN_CHUNK_SIZE, (data.len() + chunks - 1) / chunks);
+
+    let mut outputs: Vec<Option<Vec<T>>> = data.chunks(chunk_size).map(|_| None).collect();
+
+    {
+        let mapper = &mapper;
+
+        pool.scoped(|scope| {
+            for (chunk, output) in data.chunks(chunk_size).zip(outputs.iter_mut()) {
+                scope.execute(move || {
+                    if !cancelled.load(Ordering::Relaxed) {
+                        *output = Some(chunk.iter().map(mapper).collect());
+                    }
+                });
+            }
+        });
+    }
+
+    let mut target = Vec::with_capacity(data.len());
+
+    for output in outputs {
+        match output {
+            Some(output) => target.extend(output),
+            None => return None,
+        }
+    }
+
+    Some(target)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_map_chunks_order() {
+        let mut pool = Pool::new(4);
+
+        let data: Vec<usize> = (0..10_000).collect();
+
+        let mapped = map_chunks(&mut pool, &data, |&x| x * 2);
+
+        assert_eq!(mapped.len(), data.len());
+        assert!(mapped.iter().enumerate().all(|(i, &x)| x == i * 2));
+
+        // Small inputs are mapped on the calling thread
+        assert_eq!(map_chunks(&mut pool, &data[..10], |&x| x + 1), (1..11).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_map_chunks_cancelled() {
+        let data: Vec<usize> = (0..10_000).collect();
+
+        // Both the serial and the parallel paths
+        for &threads in &[1, 2] {
+            let mut pool = Pool::new(threads);
+
+            let cancelled = AtomicBool::new(false);
+
+            // The first chunk cancels the map, and any other chunk already running waits for it,
+            // so no chunk can finish before the rest are skipped. Jobs start in order, so the first chunk never waits itself.
+            let mapped = map_chunks_until(&mut pool, &data, &cancelled, |&x| {
+                if x == 0 {
+                    cancelled.store(true, Ordering::Relaxed);
+                }
+
+                while !cancelled.load(Ordering::Relaxed) {
+                    ::std::thread::yield_now();
+                }
+
+                x
+            });
+
+            assert!(mapped.is_none(), "{} threads", threads);
+            assert!(map_chunks_until(&mut pool, &data, &cancelled, |&x| x).is_none());
+        }
+    }
+}
@@ -0,0 +1,330 @@
+//! CPU particle systems
+//!
+//! A `ParticleSystem` emits particles with a random lifetime and velocity, moves them under gravity and drag,
+//! and fades their color and size over their lifetime. Particles are stored as a structure of arrays
+//! and updated in parallel on the thread pool of a pipeline.
+//!
+//! Particles are drawn as meshes sorted from back to front, so alpha blending composites them correctly:
+//!
+//! * `point_mesh` gives one point per particle, to be drawn with the `Point` primitive and expanded into
+//!   sprites in the geometry stage with `PrimitiveStorage::emit_billboard`.
+//! * `quad_mesh` gives one camera-facing quad per particle, to be drawn with the `Triangle` primitive.
+
+use nalgebra::{Point3, Vector2, Vector3};
+
+use scoped_threadpool::Pool;
+
+use ::color::predefined::formats::RGBAf32Color;
+use ::geometry::billboard::{Billboard, BillboardData, BillboardMode, billboard_mesh};
+use ::mesh::{SimpleVertex, Mesh};
+use ::memory::{MemoryReport, MemoryUsage};
+
+/// Parameters of a particle emitter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EmitterParams {
+    /// Where new particles appear
+    pub origin: Point3<f32>,
+    /// New particles appear anywhere within this distance of the origin
+    pub radius: f32,
+    /// Particles emitted per second by `update`
+    pub rate: f32,
+    /// Maximum number of live particles, beyond which no more are emitted
+    pub max_particles: usize,
+    /// Shortest and longest lifetime, in seconds
+    pub lifetime: (f32, f32),
+    /// Average initial velocity
+    pub velocity: Vector3<f32>,
+    /// Largest random deviation from the average velocity in each direction
+    pub spread: f32,
+    /// Acceleration applied to every particle
+    pub gravity: Vector3<f32>,
+    /// Fraction of velocity lost per second
+    pub drag: f32,
+    /// Color at the start and end of the lifetime of each particle
+    pub color: (RGBAf32Color, RGBAf32Color),
+    /// Size at the start and end of the lifetime of each particle
+    pub size: (f32, f32),
+}
+
+impl Default for EmitterParams {
+    fn default() -> EmitterParams {
+        EmitterParams {
+            origin: Point3::origin(),
+            radius: 0.0,
+            rate: 100.0,
+            max_particles: 10_000,
+            lifetime: (1.0, 2.0),
+            velocity: Vector3::new(0.0, 1.0, 0.0),
+            spread: 0.5,
+            gravity: Vector3::new(0.0, -9.81, 0.0),
+            drag: 0.0,
+            color: (RGBAf32Color::new(1.0, 1.0, 1.0, 1.0), RGBAf32Color::new(1.0, 1.0, 1.0, 0.0)),
+            size: (0.1, 0.1),
+        }
+    }
+}
+
+/// Vertex data of particle meshes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParticleData {
+    pub color: RGBAf32Color,
+    /// Half of the width and height of the particle
+    pub size: f32,
+}
+
+/// Particles and the emitter creating them
+#[derive(Debug, Clone)]
+pub struct ParticleSystem {
+    params: EmitterParams,
+    positions: Vec<Point3<f32>>,
+    velocities: Vec<Vector3<f32>>,
+    ages: Vec<f32>,
+    lifetimes: Vec<f32>,
+    /// Fractional particles left over from the emission rate
+    pending: f32,
+    /// State of the xorshift random number generator
+    rng: u64,
+}
+
+impl ParticleSystem {
+    pub fn new(params: EmitterParams) -> ParticleSystem {
+        ParticleSystem::with_seed(params, 0)
+    }
+
+    /// Creates a particle system with a specific random seed, for reproducible effects
+    pub fn with_seed(params: EmitterParams, seed: u64) -> ParticleSystem {
+        ParticleSystem {
+            params,
+            positions: Vec::new(),
+            velocities: Vec::new(),
+            ages: Vec::new(),
+            lifetimes: Vec::new(),
+            pending: 0.0,
+            rng: seed ^ 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    #[inline]
+    pub fn params(&self) -> &EmitterParams { &self.params }
+
+    /// Emitter parameters, which can be changed at any time, such as to move the origin
+    #[inline]
+    pub fn params_mut(&mut self) -> &mut EmitterParams { &mut self.params }
+
+    /// Number of live particles
+    #[inline]
+    pub fn len(&self) -> usize { self.positions.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.positions.is_empty() }
+
+    #[inline]
+    pub fn positions(&self) -> &[Point3<f32>] { &self.positions }
+
+    #[inline]
+    pub fn velocities(&self) -> &[Vector3<f32>] { &self.velocities }
+
+    /// Removes every particle
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        self.velocities.clear();
+        self.ages.clear();
+        self.lifetimes.clear();
+        self.pending = 0.0;
+    }
+
+    /// Random number in `[-1, 1]`
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng >> 12;
+        self.rng ^= self.rng << 25;
+        self.rng ^= self.rng >> 27;
+
+        let bits = (self.rng.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 40) as f32;
+
+        bits / (1u64 << 23) as f32 - 1.0
+    }
+
+    fn random_vector(&mut self) -> Vector3<f32> {
+        Vector3::new(self.random(), self.random(), self.random())
+    }
+
+    /// Emits up to `count` particles immediately, without exceeding `max_particles`
+    pub fn emit(&mut self, count: usize) {
+        let count = count.min(self.params.max_particles.saturating_sub(self.len()));
+
+        for _ in 0..count {
+            let EmitterParams { origin, radius, velocity, spread, lifetime: (shortest, longest), .. } = self.params;
+
+            let offset = self.random_vector() * radius;
+            let velocity = velocity + self.random_vector() * spread;
+            let lifetime = shortest + (longest - shortest) * (self.random() * 0.5 + 0.5);
+
+            self.positions.push(origin + offset);
+            self.velocities.push(velocity);
+            self.ages.push(0.0);
+            self.lifetimes.push(lifetime);
+        }
+    }
+
+    /// Advances the simulation by `dt` seconds: moves and ages every particle in parallel on `pool`,
+    /// removes expired particles, and emits new ones at the emission rate.
+    ///
+    /// Use `Pipeline::threadpool_mut` to share the thread pool of a pipeline.
+    pub fn update(&mut self, pool: &mut Pool, dt: f32) {
+        let EmitterParams { gravity, drag, .. } = self.params;
+
+        let len = self.len();
+
+        if len > 0 {
+            let threads = pool.thread_count() as usize;
+            let chunk = (len + threads - 1) / threads.max(1);
+
+            let damping = (1.0 - drag * dt).max(0.0);
+
+            pool.scoped(|scope| {
+                let chunks = self.positions.chunks_mut(chunk)
+                    .zip(self.velocities.chunks_mut(chunk))
+                    .zip(self.ages.chunks_mut(chunk));
+
+                for ((positions, velocities), ages) in chunks {
+                    scope.execute(move || {
+                        for i in 0..positions.len() {
+                            velocities[i] = (velocities[i] + gravity * dt) * damping;
+                            positions[i] += velocities[i] * dt;
+                            ages[i] += dt;
+                        }
+                    });
+                }
+            });
+
+            // Remove expired particles, swapping in the last particle to keep the arrays packed
+            let mut i = 0;
+
+            while i < self.ages.len() {
+                if self.ages[i] >= self.lifetimes[i] {
+                    self.positions.swap_remove(i);
+                    self.velocities.swap_remove(i);
+                    self.ages.swap_remove(i);
+                    self.lifetimes.swap_remove(i);
+                } else {
+                    i += 1;
+                }
+            }
+        }
+
+        self.pending += self.params.rate * dt;
+
+        let count = self.pending.floor();
+
+        self.pending -= count;
+
+        self.emit(count as usize);
+    }
+
+    /// Color and size of the particle at the given index, interpolated over its lifetime
+    fn appearance(&self, i: usize) -> ParticleData {
+        let t = (self.ages[i] / self.lifetimes[i]).min(1.0);
+
+        let EmitterParams { color: (start_color, end_color), size: (start_size, end_size), .. } = self.params;
+
+        ParticleData {
+            color: start_color + (end_color - start_color) * t,
+            size: start_size + (end_size - start_size) * t,
+        }
+    }
+
+    /// Indices of all particles sorted by decreasing distance from the camera
+    pub fn back_to_front(&self, camera: Point3<f32>) -> Vec<usize> {
+        let distances: Vec<f32> = self.positions.iter().map(|p| (p - camera).norm_squared()).collect();
+
+        let mut order: Vec<usize> = (0..self.len()).collect();
+
+        order.sort_by(|&a, &b| distances[b].partial_cmp(&distances[a]).unwrap_or(::std::cmp::Ordering::Equal));
+
+        order
+    }
+
+    /// One point per particle, sorted from back to front
+    pub fn point_mesh(&self, camera: Point3<f32>) -> Mesh<SimpleVertex<f32, ParticleData>> {
+        let order = self.back_to_front(camera);
+
+        Mesh {
+            indices: (0..order.len()).collect(),
+            vertices: order.iter().map(|&i| SimpleVertex { position: self.positions[i], data: self.appearance(i) }).collect(),
+        }
+    }
+
+    /// One camera-facing quad per particle, sorted from back to front
+    pub fn quad_mesh(&self, camera: Point3<f32>, mode: BillboardMode<f32>) -> Mesh<SimpleVertex<f32, BillboardData<f32, ParticleData>>> {
+        let billboards: Vec<_> = self.back_to_front(camera).into_iter().map(|i| {
+            let data = self.appearance(i);
+
+            Billboard { position: self.positions[i], half_size: Vector2::new(data.size, data.size), data }
+        }).collect();
+
+        billboard_mesh(&billboards, camera, mode)
+    }
+}
+
+impl MemoryUsage for ParticleSystem {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("positions", &self.positions);
+        report.add_vec("velocities", &self.velocities);
+        report.add_vec("ages", &self.ages);
+        report.add_vec("lifetimes", &self.lifetimes);
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_particle_lifetime() {
+        let mut pool = Pool::new(2);
+
+        let mut system = ParticleSystem::new(EmitterParams {
+            rate: 10.0,
+            lifetime: (1.0, 1.0),
+            spread: 0.0,
+            velocity: Vector3::zeros(),
+            gravity: Vector3::new(0.0, -1.0, 0.0),
+            ..EmitterParams::default()
+        });
+
+        system.update(&mut pool, 0.5);
+
+        assert_eq!(system.len(), 5);
+
+        system.params_mut().rate = 0.0;
+        system.update(&mut pool, 0.25);
+
+        // Falling under gravity
+        assert!(system.positions()[0].y < 0.0);
+        assert!(system.velocities()[0].y < 0.0);
+        assert_eq!(system.len(), 5);
+
+        // Every particle expires after a second
+        system.update(&mut pool, 1.0);
+
+        assert!(system.is_empty());
+    }
+
+    #[test]
+    fn test_back_to_front() {
+        let mut system = ParticleSystem::new(EmitterParams { radius: 10.0, ..EmitterParams::default() });
+
+        system.emit(20);
+
+        let camera = Point3::new(0.0, 0.0, 50.0);
+        let mesh = system.point_mesh(camera);
+
+        let distances: Vec<f32> = mesh.vertices.iter().map(|v| (v.position - camera).norm()).collect();
+
+        assert!(distances.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
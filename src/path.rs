@@ -0,0 +1,332 @@
+//! Anti-aliased 2D path filling
+//!
+//! Paths are built from lines and quadratic or cubic Bézier curves in pixel coordinates,
+//! with `y` pointing down, then filled with exact area coverage for anti-aliasing:
+//!
+//! ```ignore
+//! let mut path = Path::new();
+//!
+//! path.move_to(10.0, 10.0);
+//! path.quad_to(40.0, 0.0, 70.0, 10.0);
+//! path.line_to(40.0, 60.0);
+//! path.close();
+//!
+//! fill_path(&mut framebuffer.color, &path, [1.0, 0.5, 0.0, 1.0]);
+//! ```
+//!
+//! Overlapping contours are combined with the non-zero rule, clamping accumulated coverage to one.
+
+use nalgebra::Point2;
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::PixelWrite;
+
+/// Larger values flatten curves into more line segments
+const FLATTEN_TOLERANCE: f32 = 3.0;
+
+/// Outline made of closed contours of line segments
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Path {
+    segments: Vec<(Point2<f32>, Point2<f32>)>,
+    start: Point2<f32>,
+    current: Point2<f32>,
+}
+
+impl Path {
+    pub fn new() -> Path {
+        Path::default()
+    }
+
+    /// Starts a new contour, closing the previous one
+    pub fn move_to(&mut self, x: f32, y: f32) {
+        self.close();
+
+        self.start = Point2::new(x, y);
+        self.current = self.start;
+    }
+
+    pub fn line_to(&mut self, x: f32, y: f32) {
+        let to = Point2::new(x, y);
+
+        if to != self.current {
+            self.segments.push((self.current, to));
+        }
+
+        self.current = to;
+    }
+
+    /// Quadratic Bézier curve with control point `(x1, y1)`
+    pub fn quad_to(&mut self, x1: f32, y1: f32, x: f32, y: f32) {
+        let (p0, p1, p2) = (self.current, Point2::new(x1, y1), Point2::new(x, y));
+
+        let deviation = (p0.coords - p1.coords * 2.0 + p2.coords).norm_squared();
+
+        let steps = 1 + (FLATTEN_TOLERANCE * deviation).sqrt().sqrt() as u32;
+
+        for i in 1..steps + 1 {
+            let t = i as f32 / steps as f32;
+            let s = 1.0 - t;
+
+            let p = p0.coords * (s * s) + p1.coords * (2.0 * s * t) + p2.coords * (t * t);
+
+            self.line_to(p.x, p.y);
+        }
+    }
+
+    /// Cubic Bézier curve with control points `(x1, y1)` and `(x2, y2)`
+    pub fn cubic_to(&mut self, x1: f32, y1: f32, x2: f32, y2: f32, x: f32, y: f32) {
+        let (p0, p1, p2, p3) = (self.current, Point2::new(x1, y1), Point2::new(x2, y2), Point2::new(x, y));
+
+        let deviation = (p0.coords - p1.coords * 2.0 + p2.coords).norm_squared()
+            .max((p1.coords - p2.coords * 2.0 + p3.coords).norm_squared());
+
+        let steps = 1 + (FLATTEN_TOLERANCE * deviation * 2.0).sqrt().sqrt() as u32;
+
+        for i in 1..steps + 1 {
+            let t = i as f32 / steps as f32;
+            let s = 1.0 - t;
+
+            let p = p0.coords * (s * s * s) + p1.coords * (3.0 * s * s * t) + p2.coords * (3.0 * s * t * t) + p3.coords * (t * t * t);
+
+            self.line_to(p.x, p.y);
+        }
+    }
+
+    /// Closes the current contour with a line back to its start
+    pub fn close(&mut self) {
+        let start = self.start;
+
+        self.line_to(start.x, start.y);
+    }
+
+    /// Line segments of the path so far, excluding the line closing the current contour
+    #[inline]
+    pub fn segments(&self) -> &[(Point2<f32>, Point2<f32>)] { &self.segments }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.segments.is_empty() }
+
+    /// Minimum and maximum corners of the path, or `None` if it's empty
+    pub fn bounds(&self) -> Option<(Point2<f32>, Point2<f32>)> {
+        let first = self.segments.first()?.0;
+
+        Some(self.segments.iter().fold((first, first), |(min, max), &(a, b)| {
+            (Point2::new(min.x.min(a.x).min(b.x), min.y.min(a.y).min(b.y)),
+             Point2::new(max.x.max(a.x).max(b.x), max.y.max(a.y).max(b.y)))
+        }))
+    }
+
+    /// Coverage of each pixel of a buffer with the given dimensions by the path, from `0.0` to `1.0`, row by row.
+    ///
+    /// The current contour is closed implicitly.
+    pub fn coverage(&self, dimensions: Dimensions) -> Vec<f32> {
+        let (width, height) = (dimensions.width as usize, dimensions.height as usize);
+
+        // Two extra columns per row take the area spilling past the right edge
+        let stride = width + 2;
+
+        let mut accumulation = vec![0.0f32; stride * height];
+
+        let closing = if self.current != self.start { Some((self.current, self.start)) } else { None };
+
+        for &(a, b) in self.segments.iter().chain(closing.iter()) {
+            accumulate_line(&mut accumulation, stride, width, height, a, b);
+        }
+
+        let mut coverage = Vec::with_capacity(width * height);
+
+        for row in accumulation.chunks(stride) {
+            let mut sum = 0.0;
+
+            for &area in &row[..width] {
+                sum += area;
+
+                coverage.push(sum.abs().min(1.0));
+            }
+        }
+
+        coverage
+    }
+}
+
+/// Adds the signed area covered to the right of a line to the accumulation buffer, one row at a time
+fn accumulate_line(accumulation: &mut [f32], stride: usize, width: usize, height: usize, a: Point2<f32>, b: Point2<f32>) {
+    if a.y == b.y {
+        return;
+    }
+
+    let (direction, p0, p1) = if a.y < b.y { (1.0, a, b) } else { (-1.0, b, a) };
+
+    let dxdy = (p1.x - p0.x) / (p1.y - p0.y);
+
+    let first_row = p0.y.max(0.0) as usize;
+    let last_row = (p1.y.ceil().max(0.0) as usize).min(height);
+
+    let max_x = width as f32;
+
+    for row in first_row..last_row {
+        let top = (row as f32).max(p0.y);
+        let bottom = ((row + 1) as f32).min(p1.y);
+
+        if bottom <= top {
+            continue;
+        }
+
+        // Area left of the buffer collapses onto the first column, and right of it onto the spare columns
+        let x_top = (p0.x + (top - p0.y) * dxdy).max(0.0).min(max_x);
+        let x_bottom = (p0.x + (bottom - p0.y) * dxdy).max(0.0).min(max_x);
+
+        let d = (bottom - top) * direction;
+
+        let (x0, x1) = if x_top < x_bottom { (x_top, x_bottom) } else { (x_bottom, x_top) };
+
+        let start = row * stride;
+
+        let x0_floor = x0.floor();
+        let x1_ceil = x1.ceil();
+
+        let (x0i, x1i) = (x0_floor as usize, x1_ceil as usize);
+
+        if x1i <= x0i + 1 {
+            // Within a single pixel, split by the average position of the line
+            let middle = 0.5 * (x0 + x1) - x0_floor;
+
+            accumulation[start + x0i] += d * (1.0 - middle);
+            accumulation[start + x0i + 1] += d * middle;
+        } else {
+            let s = 1.0 / (x1 - x0);
+
+            let x0f = x0 - x0_floor;
+            let a0 = 0.5 * s * (1.0 - x0f) * (1.0 - x0f);
+
+            let x1f = x1 - x1_ceil + 1.0;
+            let am = 0.5 * s * x1f * x1f;
+
+            accumulation[start + x0i] += d * a0;
+
+            if x1i == x0i + 2 {
+                accumulation[start + x0i + 1] += d * (1.0 - a0 - am);
+            } else {
+                let a1 = s * (1.5 - x0f);
+
+                accumulation[start + x0i + 1] += d * (a1 - a0);
+
+                for x in x0i + 2..x1i - 1 {
+                    accumulation[start + x] += d * s;
+                }
+
+                let a2 = a1 + (x1i - x0i - 3) as f32 * s;
+
+                accumulation[start + x1i - 1] += d * (1.0 - a2 - am);
+            }
+
+            accumulation[start + x1i] += d * am;
+        }
+    }
+}
+
+/// Fills a path with a color, blending it over `target` by coverage and alpha
+pub fn fill_path<P>(target: &mut P, path: &Path, color: [f32; 4]) where P: PixelWrite,
+                                                                       P::Color: ToChannels + FromChannels {
+    let dimensions = target.dimensions();
+
+    let coverage = path.coverage(dimensions);
+
+    target.prepare_write();
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            let alpha = coverage[(y * dimensions.width + x) as usize] * color[3];
+
+            if alpha <= 0.0 {
+                continue;
+            }
+
+            let index = target.index_of(Coordinate::new(x, y));
+
+            let existing = unsafe { target.get_pixel_unchecked(index) }.to_channels();
+
+            let mut blended = [0.0; 4];
+
+            for i in 0..3 {
+                blended[i] = color[i] * alpha + existing[i] * (1.0 - alpha);
+            }
+
+            blended[3] = alpha + existing[3] * (1.0 - alpha);
+
+            unsafe { target.set_pixel_unchecked(index, P::Color::from_channels(blended)); }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rectangle(x0: f32, y0: f32, x1: f32, y1: f32) -> Path {
+        let mut path = Path::new();
+
+        path.move_to(x0, y0);
+        path.line_to(x1, y0);
+        path.line_to(x1, y1);
+        path.line_to(x0, y1);
+        path.close();
+
+        path
+    }
+
+    #[test]
+    fn test_rectangle_coverage() {
+        let dimensions = Dimensions::new(8, 8);
+
+        let coverage = rectangle(2.0, 2.5, 5.5, 6.0).coverage(dimensions);
+
+        let at = |x: usize, y: usize| coverage[y * 8 + x];
+
+        assert_eq!(at(3, 3), 1.0);
+        assert_eq!(at(1, 3), 0.0);
+        assert_eq!(at(6, 3), 0.0);
+        assert_eq!(at(5, 3), 0.5);
+        assert_eq!(at(3, 2), 0.5);
+        assert_eq!(at(5, 2), 0.25);
+
+        // Parts outside of the buffer are clipped, and opposite windings cancel out
+        let clipped = rectangle(-4.0, -4.0, 12.0, 4.0).coverage(dimensions);
+
+        assert!(clipped[..32].iter().all(|&c| c == 1.0));
+        assert!(clipped[32..].iter().all(|&c| c == 0.0));
+
+        let mut hole = rectangle(0.0, 0.0, 8.0, 8.0);
+
+        hole.move_to(2.0, 2.0);
+        hole.line_to(2.0, 6.0);
+        hole.line_to(6.0, 6.0);
+        hole.line_to(6.0, 2.0);
+
+        let coverage = hole.coverage(dimensions);
+
+        assert_eq!(coverage[0], 1.0);
+        assert_eq!(coverage[3 * 8 + 3], 0.0);
+    }
+
+    #[test]
+    fn test_curve_area() {
+        // Circle of radius 20 from four cubic curves
+        let k = 0.5523 * 20.0;
+
+        let mut path = Path::new();
+
+        path.move_to(50.0, 30.0);
+        path.cubic_to(50.0, 30.0 + k, 30.0 + k, 50.0, 30.0, 50.0);
+        path.cubic_to(30.0 - k, 50.0, 10.0, 30.0 + k, 10.0, 30.0);
+        path.cubic_to(10.0, 30.0 - k, 30.0 - k, 10.0, 30.0, 10.0);
+        path.cubic_to(30.0 + k, 10.0, 50.0, 30.0 - k, 50.0, 30.0);
+
+        let area: f32 = path.coverage(Dimensions::new(60, 60)).iter().sum();
+
+        let expected = ::std::f32::consts::PI * 400.0;
+
+        assert!((area - expected).abs() / expected < 0.01);
+    }
+}
@@ -0,0 +1,176 @@
+//! Reusable storage for per-frame transient data
+//!
+//! Every draw used to allocate its tile list from scratch, and every geometry shader pass grew its primitive storage
+//! from empty, reallocating many times for large meshes. The `FrameArena` owned by the pipeline keeps the tile list
+//! around between draws, and remembers how many primitives were generated so new storage is allocated at the right
+//! size up front.
+//!
+//! It also holds a `BufferPool` for recycling temporary pixel buffers between passes and frames,
+//! such as blur ping-pong targets, through `FrameArena::buffers_mut`, and the dirty regions draws are restricted to
+//! for the current frame, which are folded into the cached tile list.
+//!
+//! Call `Pipeline::begin_frame` at the start of each frame, so the remembered sizes follow the scene as it changes.
+
+use std::mem;
+
+use ::geometry::Dimensions;
+use ::pixels::BufferPool;
+use ::pipeline::dirty::DirtyRegions;
+use ::pipeline::stages::rasterization::{Tile, generate_tiles, generate_tiles_into, scissor_tiles_in_place};
+
+/// Number of vertices used for each kind of generated primitive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrimitiveCapacity {
+    pub points: usize,
+    pub lines: usize,
+    pub tris: usize,
+}
+
+impl PrimitiveCapacity {
+    fn max(self, other: PrimitiveCapacity) -> PrimitiveCapacity {
+        PrimitiveCapacity {
+            points: self.points.max(other.points),
+            lines: self.lines.max(other.lines),
+            tris: self.tris.max(other.tris),
+        }
+    }
+
+    /// Splits the capacity evenly between `parts`
+    pub fn split(self, parts: usize) -> PrimitiveCapacity {
+        let parts = parts.max(1);
+
+        PrimitiveCapacity {
+            points: (self.points + parts - 1) / parts,
+            lines: (self.lines + parts - 1) / parts,
+            tris: (self.tris + parts - 1) / parts,
+        }
+    }
+}
+
+/// Per-frame transient storage owned by a pipeline
+#[derive(Debug, Clone, Default)]
+pub struct FrameArena {
+    frame: u64,
+    tiles: Vec<Tile>,
+    tiles_key: Option<(Dimensions, Dimensions, Option<Tile>, u64)>,
+    previous_capacity: PrimitiveCapacity,
+    current_capacity: PrimitiveCapacity,
+    buffers: BufferPool,
+    dirty: Option<DirtyRegions>,
+    /// Incremented whenever the dirty regions change, so the cached tile list is regenerated
+    dirty_generation: u64,
+}
+
+impl FrameArena {
+    pub fn new() -> FrameArena { FrameArena::default() }
+
+    /// Starts a new frame.
+    ///
+    /// Retained storage keeps its allocation, but primitive capacity is based on the previous and current frame only,
+    /// so a single huge frame doesn't keep memory reserved forever. Dirty regions are forgotten, so draws cover the
+    /// whole framebuffer again until new ones are marked.
+    pub fn reset(&mut self) {
+        self.frame += 1;
+        self.previous_capacity = mem::replace(&mut self.current_capacity, PrimitiveCapacity::default());
+        self.buffers.next_frame();
+        self.set_dirty_regions(None);
+    }
+
+    /// Number of frames started with `reset`
+    #[inline]
+    pub fn frame(&self) -> u64 { self.frame }
+
+    /// Largest number of primitive vertices generated by a single geometry shader pass over the last two frames
+    #[inline]
+    pub fn primitive_capacity(&self) -> PrimitiveCapacity {
+        self.previous_capacity.max(self.current_capacity)
+    }
+
+    /// Bytes currently retained for tile lists
+    pub fn retained_bytes(&self) -> usize {
+        self.tiles.capacity() * mem::size_of::<Tile>()
+    }
+
+    /// Pool of temporary pixel buffers
+    #[inline]
+    pub fn buffers(&self) -> &BufferPool { &self.buffers }
+
+    #[inline]
+    pub fn buffers_mut(&mut self) -> &mut BufferPool { &mut self.buffers }
+
+    /// Dirty regions draws are restricted to, or `None` if they draw to the whole framebuffer
+    #[inline]
+    pub fn dirty_regions(&self) -> Option<&DirtyRegions> { self.dirty.as_ref() }
+
+    /// Replaces the dirty regions, where `None` draws to the whole framebuffer
+    pub fn set_dirty_regions(&mut self, dirty: Option<DirtyRegions>) {
+        if self.dirty.is_none() && dirty.is_none() {
+            return;
+        }
+
+        self.dirty = dirty;
+        self.dirty_generation += 1;
+    }
+
+    /// Adds a dirty region, starting to restrict draws to dirty regions if they weren't already
+    pub fn mark_dirty(&mut self, region: Tile) {
+        self.dirty.get_or_insert_with(DirtyRegions::new).add(region);
+        self.dirty_generation += 1;
+    }
+
+    /// Frees all retained storage, including idle pooled buffers
+    pub fn shrink(&mut self) {
+        self.tiles = Vec::new();
+        self.tiles_key = None;
+        self.buffers.purge();
+    }
+
+    pub ( in ::pipeline ) fn record_primitives(&mut self, capacity: PrimitiveCapacity) {
+        self.current_capacity = self.current_capacity.max(capacity);
+    }
+
+    /// Takes the tile list for a draw, which is only regenerated if the framebuffer size, tile size, scissor or dirty regions changed.
+    ///
+    /// Give it back with `return_tiles` after the draw, or the next draw will have to allocate a new list.
+    pub ( in ::pipeline ) fn take_tiles(&mut self, dimensions: Dimensions, tile_size: Dimensions, scissor: Option<Tile>) -> Vec<Tile> {
+        let mut tiles = mem::replace(&mut self.tiles, Vec::new());
+
+        let key = Some((dimensions, tile_size, scissor, self.dirty_generation));
+
+        if self.tiles_key != key || tiles.is_empty() {
+            generate_tiles_into(&mut tiles, dimensions, tile_size);
+
+            if let Some(scissor) = scissor {
+                scissor_tiles_in_place(&mut tiles, scissor);
+            }
+
+            if let Some(ref dirty) = self.dirty {
+                dirty.restrict_tiles_in_place(&mut tiles);
+            }
+
+            self.tiles_key = key;
+        }
+
+        tiles
+    }
+
+    /// Appends the tiles within another scissor rectangle to a list from `take_tiles`,
+    /// which has to be truncated back to its original length before it's returned.
+    pub ( in ::pipeline ) fn append_tiles(&self, tiles: &mut Vec<Tile>, dimensions: Dimensions, tile_size: Dimensions, scissor: Option<Tile>) {
+        let mut appended = generate_tiles(dimensions, tile_size);
+
+        if let Some(scissor) = scissor {
+            scissor_tiles_in_place(&mut appended, scissor);
+        }
+
+        if let Some(ref dirty) = self.dirty {
+            dirty.restrict_tiles_in_place(&mut appended);
+        }
+
+        tiles.extend(appended);
+    }
+
+    pub ( in ::pipeline ) fn return_tiles(&mut self, tiles: Vec<Tile>) {
+        self.tiles = tiles;
+    }
+}
@@ -0,0 +1,44 @@
+//! Time budgets for interactive rendering
+//!
+//! `FragmentShader::run_with_budget` renders tiles until a deadline passes, then skips the rest of them,
+//! so interactive applications can keep their frame rate when a scene is too heavy to render in time.
+//! The skipped tiles keep whatever was in the framebuffer before, such as the previous frame:
+//!
+//! ```ignore
+//! let report = geometry_shader.finish_default()
+//!     .run_with_budget(Duration::from_millis(12), fragment_shader);
+//!
+//! if !report.is_complete() {
+//!     // Lower the resolution or level of detail for the next frame
+//! }
+//! ```
+//!
+//! The deadline is checked before starting each tile, so tiles that are already being rendered when it passes
+//! are still finished. Tiles are only started after that check, so the completed tiles always come before
+//! the skipped ones. Smaller tiles give finer control over the time taken.
+
+use ::pipeline::stages::rasterization::Tile;
+
+/// Tiles rendered by a draw with a time budget
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BudgetReport {
+    /// Tiles rendered before the deadline, in the order they were generated
+    pub completed: Vec<Tile>,
+    /// Tiles skipped after the deadline passed, in the order they were generated
+    pub skipped: Vec<Tile>,
+}
+
+impl BudgetReport {
+    /// Returns true if every tile was rendered
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.skipped.is_empty()
+    }
+
+    /// Fraction of tiles rendered, from `0.0` to `1.0`
+    pub fn completed_fraction(&self) -> f32 {
+        let total = self.completed.len() + self.skipped.len();
+
+        if total == 0 { 1.0 } else { self.completed.len() as f32 / total as f32 }
+    }
+}
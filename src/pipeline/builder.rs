@@ -0,0 +1,193 @@
+//! Pipeline construction
+//!
+//! `PipelineBuilder` collects the framebuffer, uniforms and initial state of a pipeline,
+//! and validates them all at once when the pipeline is built.
+//!
+//! ```ignore
+//! let mut pipeline = Pipeline::builder()
+//!     .framebuffer(framebuffer)
+//!     .uniforms(global_uniforms)
+//!     .threads(4)
+//!     .tile_size(Dimensions::new(64, 64))
+//!     .build()?;
+//! ```
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use scoped_threadpool::Pool;
+use num_cpus::get as num_cpus;
+
+use ::geometry::{Dimensions, HasDimensions};
+use ::stencil::StencilConfig;
+use ::framebuffer::Framebuffer;
+use ::framebuffer::nullbuffer::NullFramebuffer;
+
+use ::pipeline::{Pipeline, FrameArena};
+use ::pipeline::state::RenderState;
+
+/// Errors that may occur when building a pipeline
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineBuildError {
+    /// No framebuffer was given
+    MissingFramebuffer,
+    /// No uniforms were given
+    MissingUniforms,
+    /// The framebuffer has a zero width or height
+    ZeroSizedFramebuffer(Dimensions),
+    /// The tile size has a zero width or height
+    ZeroSizedTile(Dimensions),
+    /// The thread pool was given zero threads
+    ZeroThreads,
+}
+
+impl Display for PipelineBuildError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            PipelineBuildError::ZeroSizedFramebuffer(Dimensions { width, height }) => {
+                write!(f, "{}: {}x{}", self.description(), width, height)
+            }
+            PipelineBuildError::ZeroSizedTile(Dimensions { width, height }) => {
+                write!(f, "{}: {}x{}", self.description(), width, height)
+            }
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for PipelineBuildError {
+    fn description(&self) -> &str {
+        match *self {
+            PipelineBuildError::MissingFramebuffer => "Missing Framebuffer",
+            PipelineBuildError::MissingUniforms => "Missing Uniforms",
+            PipelineBuildError::ZeroSizedFramebuffer(_) => "Zero-sized Framebuffer",
+            PipelineBuildError::ZeroSizedTile(_) => "Zero-sized Tile",
+            PipelineBuildError::ZeroThreads => "Zero Threads",
+        }
+    }
+}
+
+/// Builder for `Pipeline` instances.
+///
+/// The framebuffer and uniforms are required, everything else has a default:
+///
+/// * Threads: one per logical CPU
+/// * Tile size: the render state tile size, or `DEFAULT_TILE_SIZE`
+/// * Render state: `RenderState::default()`
+/// * Stencil configuration: `()`, no stencil testing
+pub struct PipelineBuilder<U, F, S = ()> {
+    framebuffer: Option<F>,
+    uniforms: Option<U>,
+    stencil_config: S,
+    threads: Option<u32>,
+    tile_size: Option<Dimensions>,
+    render_state: RenderState,
+}
+
+impl Default for PipelineBuilder<(), NullFramebuffer, ()> {
+    fn default() -> PipelineBuilder<(), NullFramebuffer, ()> {
+        PipelineBuilder {
+            framebuffer: None,
+            uniforms: None,
+            stencil_config: (),
+            threads: None,
+            tile_size: None,
+            render_state: RenderState::default(),
+        }
+    }
+}
+
+impl PipelineBuilder<(), NullFramebuffer, ()> {
+    /// Create a new builder with nothing set
+    pub fn new() -> PipelineBuilder<(), NullFramebuffer, ()> {
+        PipelineBuilder::default()
+    }
+}
+
+impl Pipeline<(), NullFramebuffer, ()> {
+    /// Create a new `PipelineBuilder`
+    pub fn builder() -> PipelineBuilder<(), NullFramebuffer, ()> {
+        PipelineBuilder::new()
+    }
+}
+
+impl<U, F, S> PipelineBuilder<U, F, S> {
+    /// Sets the framebuffer to render into
+    pub fn framebuffer<G>(self, framebuffer: G) -> PipelineBuilder<U, G, S> where G: Framebuffer {
+        let PipelineBuilder { uniforms, stencil_config, threads, tile_size, render_state, .. } = self;
+
+        PipelineBuilder { framebuffer: Some(framebuffer), uniforms, stencil_config, threads, tile_size, render_state }
+    }
+
+    /// Sets the global uniforms available to all shaders
+    pub fn uniforms<V>(self, uniforms: V) -> PipelineBuilder<V, F, S> where V: Send + Sync {
+        let PipelineBuilder { framebuffer, stencil_config, threads, tile_size, render_state, .. } = self;
+
+        PipelineBuilder { framebuffer, uniforms: Some(uniforms), stencil_config, threads, tile_size, render_state }
+    }
+
+    /// Sets the stencil configuration of the pipeline
+    pub fn stencil_config<T>(self, stencil_config: T) -> PipelineBuilder<U, F, T> where T: StencilConfig {
+        let PipelineBuilder { framebuffer, uniforms, threads, tile_size, render_state, .. } = self;
+
+        PipelineBuilder { framebuffer, uniforms, stencil_config, threads, tile_size, render_state }
+    }
+
+    /// Sets the number of threads used for rendering
+    pub fn threads(self, threads: u32) -> Self {
+        PipelineBuilder { threads: Some(threads), ..self }
+    }
+
+    /// Sets the tile size used for rasterization,
+    /// overriding any tile size in the default render state
+    pub fn tile_size(self, tile_size: Dimensions) -> Self {
+        PipelineBuilder { tile_size: Some(tile_size), ..self }
+    }
+
+    /// Sets the render state every draw starts out with
+    pub fn render_state(self, render_state: RenderState) -> Self {
+        PipelineBuilder { render_state, ..self }
+    }
+
+    /// Validates the configuration and creates the pipeline
+    pub fn build(self) -> Result<Pipeline<U, F, S>, PipelineBuildError> where U: Send + Sync,
+                                                                           F: Framebuffer,
+                                                                           S: StencilConfig {
+        let PipelineBuilder { framebuffer, uniforms, stencil_config, threads, tile_size, mut render_state } = self;
+
+        let framebuffer = framebuffer.ok_or(PipelineBuildError::MissingFramebuffer)?;
+        let uniforms = uniforms.ok_or(PipelineBuildError::MissingUniforms)?;
+
+        let dimensions = framebuffer.dimensions();
+
+        if dimensions.width == 0 || dimensions.height == 0 {
+            return Err(PipelineBuildError::ZeroSizedFramebuffer(dimensions));
+        }
+
+        if let Some(tile_size) = tile_size {
+            render_state.desc.tile_size = Some(tile_size);
+        }
+
+        if let Some(tile_size) = render_state.desc.tile_size {
+            if tile_size.width == 0 || tile_size.height == 0 {
+                return Err(PipelineBuildError::ZeroSizedTile(tile_size));
+            }
+        }
+
+        let threads = threads.unwrap_or(num_cpus() as u32);
+
+        if threads == 0 {
+            return Err(PipelineBuildError::ZeroThreads);
+        }
+
+        Ok(Pipeline {
+            framebuffer,
+            uniforms,
+            stencil_config,
+            threadpool: Pool::new(threads),
+            render_state,
+            state_stack: Vec::new(),
+            arena: FrameArena::new(),
+        })
+    }
+}
@@ -0,0 +1,91 @@
+//! Cancelling long renders
+//!
+//! A `CancellationToken` is a shared flag that can be set from any thread, such as when the user closes the window
+//! or changes the scene, to abort a render in progress:
+//!
+//! ```ignore
+//! let token = CancellationToken::new();
+//!
+//! ui.on_close({ let token = token.clone(); move || token.cancel() });
+//!
+//! pipeline.render_mesh(Triangle, mesh, None)
+//!     .with_cancellation(Some(token.clone()))
+//!     .run(vertex_shader)
+//!     .finish_default()
+//!     .with_cancellation(Some(token.clone()))
+//!     .run(fragment_shader);
+//! ```
+//!
+//! The vertex stage checks the token before shading each chunk of vertices. If it was cancelled,
+//! the remaining stages receive no geometry at all. The fragment stage checks it before rendering each tile,
+//! leaving the remaining tiles untouched.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Shared flag for cancelling renders
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    flag: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Creates a token that hasn't been cancelled
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels every render using this token or a clone of it
+    #[inline]
+    pub fn cancel(&self) {
+        self.flag.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns true if the token has been cancelled
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.flag.load(Ordering::Relaxed)
+    }
+
+    /// Clears the cancellation, so the token can be used for the next render
+    #[inline]
+    pub fn reset(&self) {
+        self.flag.store(false, Ordering::Relaxed);
+    }
+
+    /// The underlying flag
+    #[inline]
+    pub fn flag(&self) -> &AtomicBool {
+        &self.flag
+    }
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+    fn from(flag: Arc<AtomicBool>) -> CancellationToken {
+        CancellationToken { flag }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shared_cancellation() {
+        let flag = Arc::new(AtomicBool::new(false));
+
+        let token = CancellationToken::from(flag.clone());
+        let clone = token.clone();
+
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+
+        assert!(token.is_cancelled());
+        assert!(flag.load(Ordering::Relaxed));
+
+        token.reset();
+
+        assert!(!clone.is_cancelled());
+    }
+}
@@ -0,0 +1,322 @@
+//! Cascaded shadow maps
+//!
+//! A single shadow map covering the whole view frustum has too few texels near the camera, where shadows matter most.
+//! Cascaded shadow maps split the frustum into several ranges of distance from the camera, each with its own shadow map
+//! fitted tightly around it, so nearby shadows get most of the resolution:
+//!
+//! ```ignore
+//! let mut cascades = CascadedShadows::new(4, 0.1, 200.0, light_direction);
+//!
+//! // Every frame, fit the cascades to the camera and render the shadow maps with a depth-only pipeline
+//! cascades.update(&view, fovy, aspect);
+//!
+//! let maps = shadow_pipeline.render_shadow_cascades(&cascades, Triangle, scene.clone(),
+//!     |v, light_view_projection, _| ClipVertex::new(light_view_projection * v.position.to_homogeneous(), ()));
+//!
+//! let shadows = CascadeSampler::new(&cascades, maps);
+//!
+//! // In the fragment shader, given the world-space position and its distance in front of the camera
+//! let visibility = shadows.sample(v.uniforms.world_position, v.uniforms.view_depth);
+//! ```
+//!
+//! Cascades are fitted with bounding spheres and snapped to whole texels, so shadows don't shimmer
+//! as the camera moves and rotates, at the cost of some wasted resolution.
+
+use std::sync::Arc;
+
+use alga::general::Real;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4, Isometry3, Orthographic3};
+
+use num_traits::{Float, ToPrimitive, cast};
+
+use ::numeric::FloatScalar;
+use ::color::{Color, ColorMask};
+use ::framebuffer::Framebuffer;
+use ::framebuffer::types::DepthAttachment;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::primitive::Primitive;
+use ::geometry::{ClipVertex, HasDimensions};
+use ::texture::{ShadowMap, ShadowSampler};
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::PipelineUniforms;
+
+/// Shadow map cascades fitted to the view frustum of a camera, for a directional light
+#[derive(Debug, Clone)]
+pub struct CascadedShadows<N: FloatScalar> {
+    /// Direction the light travels in
+    pub light_direction: Vector3<N>,
+    /// Distance of the near plane of the camera
+    pub near: N,
+    /// Farthest distance from the camera that receives shadows
+    pub far: N,
+    /// Blend between uniform splits at `0` and logarithmic splits at `1`, which put more cascades close to the camera
+    pub lambda: N,
+    /// Extra distance towards the light to include shadow casters outside of the view frustum
+    pub caster_distance: N,
+    /// Width and height of each shadow map in texels, used to snap cascades to whole texels
+    pub resolution: u32,
+    splits: Vec<N>,
+    view_projections: Vec<Matrix4<N>>,
+}
+
+impl<N> CascadedShadows<N> where N: Real + FloatScalar {
+    /// Creates `count` cascades covering distances from `near` to `far` in front of the camera,
+    /// with 1024 by 1024 texel shadow maps.
+    ///
+    /// The cascades have to be fitted to a camera with `update` before rendering.
+    pub fn new(count: usize, near: N, far: N, light_direction: Vector3<N>) -> CascadedShadows<N> {
+        let count = count.max(1);
+
+        let mut cascades = CascadedShadows {
+            light_direction,
+            near,
+            far,
+            lambda: cast(0.75).unwrap(),
+            caster_distance: far,
+            resolution: 1024,
+            splits: Vec::with_capacity(count),
+            view_projections: vec![Matrix4::identity(); count],
+        };
+
+        cascades.update_splits(count);
+
+        cascades
+    }
+
+    /// Number of cascades
+    #[inline]
+    pub fn count(&self) -> usize { self.splits.len() }
+
+    /// Far distance of each cascade from the camera, with the near distance of each being the far distance of the one before
+    #[inline]
+    pub fn splits(&self) -> &[N] { &self.splits }
+
+    /// Near and far distance of a cascade from the camera
+    pub fn range(&self, cascade: usize) -> (N, N) {
+        (if cascade == 0 { self.near } else { self.splits[cascade - 1] }, self.splits[cascade])
+    }
+
+    /// View-projection matrix of the light for a cascade
+    #[inline]
+    pub fn view_projection(&self, cascade: usize) -> &Matrix4<N> { &self.view_projections[cascade] }
+
+    /// View-projection matrices of the light for every cascade
+    #[inline]
+    pub fn view_projections(&self) -> &[Matrix4<N>] { &self.view_projections }
+
+    fn update_splits(&mut self, count: usize) {
+        self.splits.clear();
+
+        let ratio = self.far / self.near;
+
+        for i in 1..count + 1 {
+            let t: N = cast::<f64, N>(i as f64 / count as f64).unwrap();
+
+            let logarithmic = self.near * Float::powf(ratio, t);
+            let uniform = self.near + (self.far - self.near) * t;
+
+            self.splits.push(self.lambda * logarithmic + (N::one() - self.lambda) * uniform);
+        }
+    }
+
+    /// Fits the cascades to a camera with the given view matrix, vertical field of view in radians and aspect ratio
+    pub fn update(&mut self, view: &Matrix4<N>, fovy: N, aspect: N) {
+        let count = self.count();
+
+        self.update_splits(count);
+
+        let inverse_view = view.try_inverse().unwrap_or_else(Matrix4::identity);
+
+        let two: N = cast(2.0).unwrap();
+        let tan = Float::tan(fovy / two);
+
+        let direction = self.light_direction.normalize();
+
+        let up = if Float::abs(direction.y) > cast(0.99).unwrap() { Vector3::x() } else { Vector3::y() };
+
+        let resolution: N = cast(self.resolution.max(1)).unwrap();
+
+        for cascade in 0..count {
+            let (start, end) = self.range(cascade);
+
+            let mut corners = Vec::with_capacity(8);
+
+            for &d in &[start, end] {
+                let (hh, hw) = (d * tan, d * tan * aspect);
+
+                for &(sx, sy) in &[(-N::one(), -N::one()), (N::one(), -N::one()), (-N::one(), N::one()), (N::one(), N::one())] {
+                    let corner = inverse_view * Vector4::new(hw * sx, hh * sy, -d, N::one());
+
+                    corners.push(Vector3::new(corner.x, corner.y, corner.z));
+                }
+            }
+
+            let center = corners.iter().fold(Vector3::new(N::zero(), N::zero(), N::zero()), |sum, &corner| sum + corner) / cast::<f64, N>(8.0).unwrap();
+
+            let radius = corners.iter().fold(N::zero(), |radius, &corner| Float::max(radius, (corner - center).norm()));
+
+            // Round the radius up, so the size of the cascade doesn't change with rounding errors as the camera rotates
+            let sixteen: N = cast(16.0).unwrap();
+            let radius = Float::ceil(radius * sixteen) / sixteen;
+
+            let eye = Point3::from_coordinates(center - direction * (radius + self.caster_distance));
+
+            let light_view = Isometry3::look_at_rh(&eye, &Point3::from_coordinates(center), &up).to_homogeneous();
+
+            let mut projection = Orthographic3::new(-radius, radius, -radius, radius,
+                                                    N::zero(), two * radius + self.caster_distance).to_homogeneous();
+
+            // Snap the origin to whole texels, so shadows don't shimmer as the camera moves
+            let origin = projection * light_view * Vector4::new(N::zero(), N::zero(), N::zero(), N::one());
+
+            let (ox, oy) = (origin.x * resolution / two, origin.y * resolution / two);
+
+            projection[(0, 3)] += (Float::round(ox) - ox) * two / resolution;
+            projection[(1, 3)] += (Float::round(oy) - oy) * two / resolution;
+
+            self.view_projections[cascade] = projection * light_view;
+        }
+    }
+}
+
+/// Samples the cascade covering each fragment, blending between cascades near their edges
+#[derive(Debug, Clone)]
+pub struct CascadeSampler<N: FloatScalar> {
+    samplers: Vec<ShadowSampler>,
+    view_projections: Vec<Matrix4<N>>,
+    ranges: Vec<(N, N)>,
+    /// Fraction at the far end of each cascade blended with the next, to hide the switch between them
+    pub blend: N,
+}
+
+impl<N> CascadeSampler<N> where N: Real + FloatScalar {
+    /// Creates a sampler for the shadow maps of each cascade, as rendered by `Pipeline::render_shadow_cascades`
+    pub fn new(cascades: &CascadedShadows<N>, maps: Vec<ShadowMap>) -> CascadeSampler<N> {
+        assert_eq!(maps.len(), cascades.count(), "Every cascade needs a shadow map");
+
+        CascadeSampler {
+            samplers: maps.into_iter().map(ShadowSampler::new).collect(),
+            view_projections: cascades.view_projections.clone(),
+            ranges: (0..cascades.count()).map(|cascade| cascades.range(cascade)).collect(),
+            blend: cast(0.1).unwrap(),
+        }
+    }
+
+    /// Shadow samplers of each cascade, to change their comparison or bias
+    #[inline]
+    pub fn samplers_mut(&mut self) -> &mut [ShadowSampler] { &mut self.samplers }
+
+    /// Index of the cascade covering the given distance in front of the camera, if any
+    pub fn select(&self, view_depth: N) -> Option<usize> {
+        self.ranges.iter().position(|&(_, end)| view_depth <= end)
+    }
+
+    /// Returns the visibility of a world-space position at the given distance in front of the camera,
+    /// from `0.0` in shadow to `1.0` lit.
+    ///
+    /// Positions beyond the last cascade are always lit.
+    pub fn sample(&self, position: Point3<N>, view_depth: N) -> f32 {
+        let cascade = match self.select(view_depth) {
+            Some(cascade) => cascade,
+            None => return 1.0,
+        };
+
+        let position = position.to_homogeneous();
+
+        let visibility = self.samplers[cascade].sample_clip(self.view_projections[cascade] * position);
+
+        let (start, end) = self.ranges[cascade];
+
+        let blend_start = end - (end - start) * self.blend;
+
+        if cascade + 1 < self.samplers.len() && view_depth > blend_start {
+            let t: f32 = cast((view_depth - blend_start) / (end - blend_start)).unwrap_or(0.0);
+
+            let next = self.samplers[cascade + 1].sample_clip(self.view_projections[cascade + 1] * position);
+
+            visibility + (next - visibility) * t
+        } else {
+            visibility
+        }
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Renders the depth of a mesh into a shadow map for each cascade, clearing the framebuffer before each one.
+    ///
+    /// The vertex shader is given the view-projection matrix of the light for the cascade being rendered.
+    /// No color is written, so the framebuffer only needs a depth attachment the size of `cascades.resolution`.
+    pub fn render_shadow_cascades<T, V, I, VS>(&mut self, cascades: &CascadedShadows<V::Scalar>,
+                                               primitive: T, mesh: Arc<Mesh<V, I>>, vertex_shader: VS) -> Vec<ShadowMap>
+        where T: Primitive + Copy,
+              V: Vertex,
+              V::Scalar: Real,
+              I: MeshIndex,
+              VS: Fn(&V, &Matrix4<V::Scalar>, &PipelineUniforms<Self>) -> ClipVertex<V::Scalar, ()> + Send + Sync,
+              DepthAttachment<<Self as PipelineObject>::Framebuffer>: ToPrimitive {
+        let mut maps = Vec::with_capacity(cascades.count());
+
+        for view_projection in cascades.view_projections() {
+            self.framebuffer_mut().clear(Color::empty());
+
+            self.render_mesh(primitive, mesh.clone(), None)
+                .run(|vertex, uniforms| vertex_shader(vertex, view_projection, uniforms))
+                .clip_primitives()
+                .finish_default()
+                .with_color_mask(ColorMask::none())
+                .run(|_, _| Fragment::Color(Color::empty()));
+
+            let mut map = ShadowMap::from_framebuffer(self.framebuffer());
+
+            map.set_viewport(self.render_state().viewport_or_full(self.framebuffer().dimensions()));
+
+            maps.push(map);
+        }
+
+        maps
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cascade_splits() {
+        let mut cascades = CascadedShadows::new(4, 1.0f32, 100.0, Vector3::new(0.0, -1.0, 0.0));
+
+        assert_eq!(cascades.count(), 4);
+        assert!((cascades.splits()[3] - 100.0).abs() < 1e-3);
+        assert!(cascades.splits().windows(2).all(|pair| pair[0] < pair[1]));
+
+        cascades.lambda = 0.0;
+        cascades.update(&Matrix4::identity(), 1.0, 1.0);
+
+        assert!((cascades.splits()[0] - 25.75).abs() < 1e-3);
+    }
+
+    #[test]
+    fn test_cascades_contain_frustum() {
+        let mut cascades = CascadedShadows::new(3, 0.5f32, 50.0, Vector3::new(1.0, -2.0, 0.5));
+
+        let view = Isometry3::look_at_rh(&Point3::new(3.0, 2.0, 5.0), &Point3::origin(), &Vector3::y()).to_homogeneous();
+
+        cascades.update(&view, 1.0, 1.5);
+
+        let inverse_view = view.try_inverse().unwrap();
+
+        for cascade in 0..cascades.count() {
+            let (_, end) = cascades.range(cascade);
+
+            // Center of the far end of the cascade, in world-space
+            let point = inverse_view * Vector4::new(0.0, 0.0, -end, 1.0);
+
+            let clip = cascades.view_projection(cascade) * point;
+
+            assert!(clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0 && clip.z.abs() <= 1.0);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! Immediate-mode debug drawing
+//!
+//! `DebugDraw` collects world-space lines, boxes, spheres, frusta and axes from anywhere in an application
+//! during a frame, then draws them all in one batched draw after the main scene:
+//!
+//! ```ignore
+//! debug.aabb(min, max, [0.0, 1.0, 0.0, 1.0]);
+//! debug.frustum(&light_view_projection, [1.0, 1.0, 0.0, 1.0]);
+//!
+//! // Drawn on top of everything
+//! debug.set_depth(DebugDepth::Overlay);
+//! debug.axes(&model, 1.0);
+//!
+//! pipeline.draw_debug(&debug, &view_projection);
+//! debug.clear();
+//! ```
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4};
+
+use ::color::FromChannels;
+use ::attachments::depth::DepthTest;
+use ::mesh::{Vertex, Mesh};
+use ::primitive::Line;
+use ::geometry::ClipVertex;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::Pixel;
+
+/// Segments used for circles and spheres
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// Whether debug shapes are hidden behind the scene
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugDepth {
+    /// Shapes are depth-tested against the scene
+    Tested,
+    /// Shapes are drawn over the scene
+    Overlay,
+}
+
+impl Default for DebugDepth {
+    fn default() -> DebugDepth { DebugDepth::Tested }
+}
+
+/// Line endpoint of a debug shape
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DebugVertex {
+    pub position: Point3<f32>,
+    pub color: [f32; 4],
+}
+
+impl Vertex for DebugVertex {
+    type Scalar = f32;
+
+    #[inline(always)]
+    fn position(&self) -> Point3<f32> { self.position }
+}
+
+/// List of debug shapes for a frame, drawn with `Pipeline::draw_debug`
+#[derive(Debug, Clone, Default)]
+pub struct DebugDraw {
+    depth: DebugDepth,
+    tested: Vec<DebugVertex>,
+    overlay: Vec<DebugVertex>,
+}
+
+impl DebugDraw {
+    pub fn new() -> DebugDraw {
+        DebugDraw::default()
+    }
+
+    /// Sets whether the shapes added after this are depth-tested or drawn over the scene
+    #[inline]
+    pub fn set_depth(&mut self, depth: DebugDepth) {
+        self.depth = depth;
+    }
+
+    #[inline]
+    pub fn depth(&self) -> DebugDepth { self.depth }
+
+    /// Removes every shape, typically after drawing each frame
+    pub fn clear(&mut self) {
+        self.tested.clear();
+        self.overlay.clear();
+    }
+
+    /// Number of lines making up all the shapes
+    #[inline]
+    pub fn line_count(&self) -> usize {
+        (self.tested.len() + self.overlay.len()) / 2
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.tested.is_empty() && self.overlay.is_empty()
+    }
+
+    /// Line endpoints drawn with the given depth mode, in pairs
+    pub fn vertices(&self, depth: DebugDepth) -> &[DebugVertex] {
+        match depth {
+            DebugDepth::Tested => &self.tested,
+            DebugDepth::Overlay => &self.overlay,
+        }
+    }
+
+    pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 4]) {
+        let vertices = match self.depth {
+            DebugDepth::Tested => &mut self.tested,
+            DebugDepth::Overlay => &mut self.overlay,
+        };
+
+        vertices.push(DebugVertex { position: a, color });
+        vertices.push(DebugVertex { position: b, color });
+    }
+
+    /// Axis-aligned box between two corners
+    pub fn aabb(&mut self, min: Point3<f32>, max: Point3<f32>, color: [f32; 4]) {
+        let corner = |i: usize| Point3::new(if i & 1 == 0 { min.x } else { max.x },
+                                            if i & 2 == 0 { min.y } else { max.y },
+                                            if i & 4 == 0 { min.z } else { max.z });
+
+        self.box_edges(&[corner(0), corner(1), corner(2), corner(3), corner(4), corner(5), corner(6), corner(7)], color);
+    }
+
+    /// Box transformed by a model matrix, covering `-half_extents` to `half_extents` in model-space
+    pub fn oriented_box(&mut self, transform: &Matrix4<f32>, half_extents: Vector3<f32>, color: [f32; 4]) {
+        let corner = |i: usize| {
+            let sign = |bit: usize, extent: f32| if i & bit == 0 { -extent } else { extent };
+
+            transform_point(transform, Point3::new(sign(1, half_extents.x), sign(2, half_extents.y), sign(4, half_extents.z)))
+        };
+
+        self.box_edges(&[corner(0), corner(1), corner(2), corner(3), corner(4), corner(5), corner(6), corner(7)], color);
+    }
+
+    /// Edges between the eight corners of a box, with corner `i` on the positive side of x, y and z for bits 1, 2 and 4 of `i`
+    fn box_edges(&mut self, corners: &[Point3<f32>; 8], color: [f32; 4]) {
+        for i in 0..8 {
+            for &bit in &[1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    /// Circle around a normal
+    pub fn circle(&mut self, center: Point3<f32>, normal: Vector3<f32>, radius: f32, color: [f32; 4]) {
+        let normal = normal.normalize();
+
+        let tangent = if normal.x.abs() < 0.9 { normal.cross(&Vector3::x()) } else { normal.cross(&Vector3::y()) }.normalize();
+        let bitangent = normal.cross(&tangent);
+
+        let point = |i: usize| {
+            let angle = i as f32 / CIRCLE_SEGMENTS as f32 * 2.0 * ::std::f32::consts::PI;
+
+            center + (tangent * angle.cos() + bitangent * angle.sin()) * radius
+        };
+
+        for i in 0..CIRCLE_SEGMENTS {
+            self.line(point(i), point(i + 1), color);
+        }
+    }
+
+    /// Sphere drawn as three circles around the axes
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: [f32; 4]) {
+        self.circle(center, Vector3::x(), radius, color);
+        self.circle(center, Vector3::y(), radius, color);
+        self.circle(center, Vector3::z(), radius, color);
+    }
+
+    /// Edges of the volume visible through a view-projection matrix, such as the frustum of a camera or light
+    pub fn frustum(&mut self, view_projection: &Matrix4<f32>, color: [f32; 4]) {
+        let inverse = match view_projection.try_inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+
+            transform_point(&inverse, Point3::new(sign(1), sign(2), sign(4)))
+        };
+
+        self.box_edges(&[corner(0), corner(1), corner(2), corner(3), corner(4), corner(5), corner(6), corner(7)], color);
+    }
+
+    /// Red, green and blue lines along the x, y and z axes of a model matrix, `size` units long before scaling
+    pub fn axes(&mut self, transform: &Matrix4<f32>, size: f32) {
+        let origin = transform_point(transform, Point3::origin());
+
+        self.line(origin, transform_point(transform, Point3::new(size, 0.0, 0.0)), [1.0, 0.0, 0.0, 1.0]);
+        self.line(origin, transform_point(transform, Point3::new(0.0, size, 0.0)), [0.0, 1.0, 0.0, 1.0]);
+        self.line(origin, transform_point(transform, Point3::new(0.0, 0.0, size)), [0.0, 0.0, 1.0, 1.0]);
+    }
+
+    /// Mesh of the lines drawn with the given depth mode, for drawing with `Line` primitives
+    pub fn mesh(&self, depth: DebugDepth) -> Mesh<DebugVertex, u32> {
+        let vertices = self.vertices(depth).to_vec();
+
+        Mesh { indices: (0..vertices.len() as u32).collect(), vertices }
+    }
+}
+
+#[inline]
+fn transform_point(transform: &Matrix4<f32>, point: Point3<f32>) -> Point3<f32> {
+    let p = transform * point.to_homogeneous();
+
+    Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Draws all the shapes in `debug` into the framebuffer, as seen through `view_projection`.
+    ///
+    /// Call this after rendering the scene, so depth-tested shapes are hidden behind it.
+    /// Overlay shapes still write depth, so draw them last.
+    pub fn draw_debug(&mut self, debug: &DebugDraw, view_projection: &Matrix4<f32>) where Pixel<Self>: FromChannels {
+        for &(depth, depth_test) in &[(DebugDepth::Tested, DepthTest::default()), (DebugDepth::Overlay, DepthTest::Always)] {
+            if debug.vertices(depth).is_empty() {
+                continue;
+            }
+
+            self.render_mesh(Line, Arc::new(debug.mesh(depth)), None)
+                .run(|vertex, _| {
+                    let color = vertex.color;
+
+                    ClipVertex::new(view_projection * vertex.position.to_homogeneous(), Vector4::new(color[0], color[1], color[2], color[3]))
+                })
+                .clip_primitives()
+                .finish_default()
+                .with_depth_test(depth_test)
+                .run(|v, _| {
+                    let color = v.uniforms;
+
+                    Fragment::Color(<Pixel<Self> as FromChannels>::from_channels([color.x, color.y, color.z, color.w]))
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_debug_shapes() {
+        let mut debug = DebugDraw::new();
+
+        debug.aabb(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 2.0, 3.0), [1.0; 4]);
+
+        assert_eq!(debug.line_count(), 12);
+
+        // Every edge of a box is parallel to one axis
+        for pair in debug.vertices(DebugDepth::Tested).chunks(2) {
+            let d = pair[1].position - pair[0].position;
+
+            assert_eq!([d.x != 0.0, d.y != 0.0, d.z != 0.0].iter().filter(|&&moved| moved).count(), 1);
+        }
+
+        debug.set_depth(DebugDepth::Overlay);
+        debug.axes(&Matrix4::new_translation(&Vector3::new(1.0, 0.0, 0.0)), 2.0);
+        debug.frustum(&Matrix4::identity(), [1.0; 4]);
+
+        let overlay = debug.vertices(DebugDepth::Overlay);
+
+        assert_eq!(overlay.len(), 2 * (3 + 12));
+        assert_eq!(overlay[1].position, Point3::new(3.0, 0.0, 0.0));
+        assert!(overlay[6..].iter().all(|v| v.position.x.abs() == 1.0 && v.position.z.abs() == 1.0));
+
+        assert_eq!(debug.mesh(DebugDepth::Tested).indices.len(), 24);
+
+        debug.clear();
+
+        assert!(debug.is_empty());
+    }
+}
@@ -0,0 +1,220 @@
+//! Dirty-region rendering
+//!
+//! User interfaces and 2D scenes often change only a few small areas per frame, yet every draw still
+//! rasterizes the whole framebuffer. Marking the changed areas as dirty restricts every following draw
+//! to the tiles intersecting them, leaving all other pixels exactly as the previous frame left them:
+//!
+//! ```ignore
+//! pipeline.begin_frame();
+//!
+//! pipeline.mark_dirty((Coordinate::new(10, 10), Coordinate::new(90, 40)));
+//! pipeline.clear_dirty(background);
+//!
+//! // Redraw anything overlapping the dirty region, everything else is skipped
+//! draw_widgets(&mut pipeline);
+//! ```
+//!
+//! `Pipeline::begin_frame` goes back to rendering everything, so regions have to be marked again every frame.
+
+use ::numeric::utils::{min, max};
+use ::geometry::{Coordinate, HasDimensions};
+use ::framebuffer::{Framebuffer, UnsafeFramebuffer};
+use ::framebuffer::attachments::Depth;
+use ::framebuffer::types::ColorAttachment;
+use ::pixels::PixelWrite;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::rasterization::Tile;
+
+/// Set of non-overlapping rectangles that need to be redrawn, given as inclusive tiles like the scissor
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct DirtyRegions {
+    regions: Vec<Tile>,
+}
+
+#[inline]
+fn overlaps(a: Tile, b: Tile) -> bool {
+    a.0.x <= b.1.x && b.0.x <= a.1.x && a.0.y <= b.1.y && b.0.y <= a.1.y
+}
+
+#[inline]
+fn union(a: Tile, b: Tile) -> Tile {
+    (Coordinate::new(min(a.0.x, b.0.x), min(a.0.y, b.0.y)),
+     Coordinate::new(max(a.1.x, b.1.x), max(a.1.y, b.1.y)))
+}
+
+impl DirtyRegions {
+    pub fn new() -> DirtyRegions { DirtyRegions::default() }
+
+    /// Adds a region that needs to be redrawn.
+    ///
+    /// Regions overlapping existing ones are merged into their bounding rectangle,
+    /// so blending never touches a pixel twice in one draw.
+    pub fn add(&mut self, region: Tile) {
+        let mut region = (Coordinate::new(min(region.0.x, region.1.x), min(region.0.y, region.1.y)),
+                          Coordinate::new(max(region.0.x, region.1.x), max(region.0.y, region.1.y)));
+
+        // Merging can make the region overlap others it didn't before, so repeat until nothing overlaps
+        loop {
+            let before = self.regions.len();
+
+            self.regions.retain(|&other| {
+                if overlaps(region, other) {
+                    region = union(region, other);
+                    false
+                } else {
+                    true
+                }
+            });
+
+            if self.regions.len() == before {
+                break;
+            }
+        }
+
+        self.regions.push(region);
+    }
+
+    /// Removes all regions
+    pub fn clear(&mut self) {
+        self.regions.clear();
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.regions.is_empty() }
+
+    /// The non-overlapping dirty rectangles
+    #[inline]
+    pub fn regions(&self) -> &[Tile] { &self.regions }
+
+    /// Bounding rectangle of all regions, if there are any
+    pub fn bounds(&self) -> Option<Tile> {
+        self.regions.iter().fold(None, |bounds, &region| {
+            Some(match bounds {
+                Some(bounds) => union(bounds, region),
+                None => region,
+            })
+        })
+    }
+
+    /// Returns true if any region overlaps the given tile
+    pub fn intersects(&self, tile: Tile) -> bool {
+        self.regions.iter().any(|&region| overlaps(region, tile))
+    }
+
+    /// Replaces every tile with its intersections with the dirty regions, dropping tiles outside all of them
+    pub ( in ::pipeline ) fn restrict_tiles_in_place(&self, tiles: &mut Vec<Tile>) {
+        let count = tiles.len();
+
+        for i in 0..count {
+            let tile = tiles[i];
+
+            for &region in &self.regions {
+                let start = Coordinate::new(max(tile.0.x, region.0.x), max(tile.0.y, region.0.y));
+                let end = Coordinate::new(min(tile.1.x, region.1.x), min(tile.1.y, region.1.y));
+
+                if start.x <= end.x && start.y <= end.y {
+                    tiles.push((start, end));
+                }
+            }
+        }
+
+        tiles.drain(..count);
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Marks a region of the framebuffer as changed, restricting draws to the dirty regions for the rest of the frame
+    pub fn mark_dirty(&mut self, region: Tile) {
+        self.arena_mut().mark_dirty(region);
+    }
+
+    /// Goes back to drawing to the whole framebuffer, which is also what `begin_frame` does
+    pub fn mark_all_dirty(&mut self) {
+        self.arena_mut().set_dirty_regions(None);
+    }
+
+    /// Dirty regions draws are restricted to, or `None` if they draw to the whole framebuffer
+    pub fn dirty_regions(&self) -> Option<&DirtyRegions> {
+        self.arena().dirty_regions()
+    }
+
+    /// Clears color, depth and stencil within the dirty regions only, or the whole framebuffer if none are marked.
+    ///
+    /// Use this in place of `Framebuffer::clear` when rendering dirty regions, so the rest of the frame is preserved.
+    pub fn clear_dirty(&mut self, color: ColorAttachment<<Self as PipelineObject>::Framebuffer>) {
+        let regions = match self.arena().dirty_regions() {
+            Some(dirty) => dirty.regions().to_vec(),
+            None => return self.framebuffer_mut().clear(color),
+        };
+
+        let framebuffer = self.framebuffer_mut();
+
+        let dimensions = framebuffer.dimensions();
+
+        if dimensions.width == 0 || dimensions.height == 0 {
+            return;
+        }
+
+        let last = Coordinate::new(dimensions.width - 1, dimensions.height - 1);
+
+        framebuffer.prepare_write();
+
+        for (start, end) in regions {
+            let end = Coordinate::new(min(end.x, last.x), min(end.y, last.y));
+
+            for y in start.y..end.y + 1 {
+                for x in start.x..end.x + 1 {
+                    let index = framebuffer.index_of(Coordinate::new(x, y));
+
+                    unsafe {
+                        framebuffer.set_pixel_unchecked(index, color);
+                        framebuffer.set_depth_unchecked(index, Depth::far());
+                        framebuffer.set_stencil_unchecked(index, Default::default());
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::pipeline::stages::rasterization::generate_tiles;
+
+    use super::*;
+
+    fn tile(x0: u32, y0: u32, x1: u32, y1: u32) -> Tile {
+        (Coordinate::new(x0, y0), Coordinate::new(x1, y1))
+    }
+
+    #[test]
+    fn test_dirty_regions() {
+        let mut dirty = DirtyRegions::new();
+
+        dirty.add(tile(10, 10, 20, 20));
+        dirty.add(tile(40, 40, 50, 50));
+
+        assert_eq!(dirty.regions().len(), 2);
+
+        // Bridging both regions merges all three
+        dirty.add(tile(45, 15, 15, 45));
+
+        assert_eq!(dirty.regions(), &[tile(10, 10, 50, 50)]);
+
+        dirty.clear();
+        dirty.add(tile(5, 5, 9, 9));
+        dirty.add(tile(60, 0, 61, 1));
+
+        let mut tiles = generate_tiles(Dimensions::new(64, 64), Dimensions::new(16, 16));
+
+        dirty.restrict_tiles_in_place(&mut tiles);
+
+        assert_eq!(tiles, vec![tile(5, 5, 9, 9), tile(60, 0, 61, 1)]);
+
+        assert!(dirty.intersects(tile(0, 0, 5, 5)));
+        assert!(!dirty.intersects(tile(20, 20, 30, 30)));
+        assert_eq!(dirty.bounds(), Some(tile(5, 0, 61, 9)));
+    }
+}
@@ -0,0 +1,68 @@
+//! Transform feedback
+//!
+//! Similar to transform feedback in OpenGL, the primitive stream coming out of the vertex and geometry stages
+//! can be captured into a `TransformFeedback` with `GeometryShader::capture`, and fed back into the pipeline later
+//! with `Pipeline::render_feedback`, skipping vertex shading entirely.
+//!
+//! This is useful for CPU-side processing of transformed geometry, like picking or collision,
+//! and for caching static geometry across frames while the camera doesn't move.
+
+use ::numeric::FloatScalar;
+use ::geometry::ClipVertex;
+use ::pipeline::storage::SeparablePrimitiveStorage;
+
+/// Captured clip-space primitives, with their positions and varyings.
+///
+/// Lines are stored as consecutive pairs of vertices, and triangles as consecutive triples.
+#[derive(Debug, Clone)]
+pub struct TransformFeedback<N: FloatScalar, K> {
+    pub points: Vec<ClipVertex<N, K>>,
+    pub lines: Vec<ClipVertex<N, K>>,
+    pub tris: Vec<ClipVertex<N, K>>,
+}
+
+impl<N, K> Default for TransformFeedback<N, K> where N: FloatScalar {
+    fn default() -> TransformFeedback<N, K> {
+        TransformFeedback {
+            points: Vec::new(),
+            lines: Vec::new(),
+            tris: Vec::new(),
+        }
+    }
+}
+
+impl<N, K> TransformFeedback<N, K> where N: FloatScalar {
+    #[inline]
+    pub fn num_points(&self) -> usize { self.points.len() }
+
+    #[inline]
+    pub fn num_lines(&self) -> usize { self.lines.len() / 2 }
+
+    #[inline]
+    pub fn num_triangles(&self) -> usize { self.tris.len() / 3 }
+
+    /// Returns true if no primitives were captured
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty() && self.lines.is_empty() && self.tris.is_empty()
+    }
+
+    /// Iterates over the start and end vertices of every line
+    pub fn lines(&self) -> ::std::slice::Chunks<ClipVertex<N, K>> { self.lines.chunks(2) }
+
+    /// Iterates over the three vertices of every triangle
+    pub fn triangles(&self) -> ::std::slice::Chunks<ClipVertex<N, K>> { self.tris.chunks(3) }
+
+    /// Any incomplete line or triangle left at the end of the lists is dropped,
+    /// since the geometry stage only deals with whole primitives.
+    pub ( in ::pipeline ) fn into_storage(self) -> SeparablePrimitiveStorage<N, K> {
+        let TransformFeedback { points, mut lines, mut tris } = self;
+
+        let (num_lines, num_tris) = (lines.len() / 2, tris.len() / 3);
+
+        lines.truncate(num_lines * 2);
+        tris.truncate(num_tris * 3);
+
+        SeparablePrimitiveStorage::from_unindexed(points, lines, tris)
+    }
+}
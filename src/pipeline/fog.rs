@@ -0,0 +1,78 @@
+//! Distance fog
+//!
+//! Fog is applied to every shaded fragment after the fragment shader and before blending,
+//! mixing the fragment color towards the fog color based on the fragment's distance from the camera.
+//!
+//! The distance is taken from the `w` component of the clip-space position, which is the view-space depth
+//! for perspective projections. Orthographic projections have a constant `w`, so fog has no visible effect on them.
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::color::Color;
+use ::interpolate::Interpolate;
+use ::geometry::ScreenVertex;
+
+/// Falloff of fog with distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum FogMode {
+    /// Fog increases linearly from nothing at `start` to full at `end`
+    Linear { start: f32, end: f32 },
+    /// Fog increases exponentially with distance, `1 - e^(-density * distance)`
+    Exponential { density: f32 },
+    /// Fog increases with the square of distance, `1 - e^(-(density * distance)^2)`
+    ExponentialSquared { density: f32 },
+}
+
+impl FogMode {
+    /// Returns the amount of fog at the given distance, from `0.0` for none to `1.0` for fully fogged
+    pub fn amount(&self, distance: f32) -> f32 {
+        let visibility = match *self {
+            FogMode::Linear { start, end } => {
+                if end > start { (end - distance) / (end - start) } else if distance < start { 1.0 } else { 0.0 }
+            }
+            FogMode::Exponential { density } => (-density * distance).exp(),
+            FogMode::ExponentialSquared { density } => {
+                let d = density * distance;
+                (-d * d).exp()
+            }
+        };
+
+        1.0 - visibility.max(0.0).min(1.0)
+    }
+}
+
+/// Fog state for a draw
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct Fog<C> {
+    pub mode: FogMode,
+    /// Color of the fog. The alpha of fogged fragments is left unchanged.
+    pub color: C,
+}
+
+impl<C> Fog<C> where C: Color + Interpolate {
+    pub fn new(mode: FogMode, color: C) -> Fog<C> {
+        Fog { mode, color }
+    }
+
+    /// Mixes the color towards the fog color based on distance
+    pub fn apply(&self, color: C, distance: f32) -> C {
+        let amount = self.mode.amount(distance);
+
+        if amount <= 0.0 {
+            color
+        } else {
+            Interpolate::linear_interpolate(amount, &color, &self.color).with_alpha(color.get_alpha())
+        }
+    }
+
+    /// Applies fog to a color shaded at the given screen-space vertex
+    pub fn apply_at<N: FloatScalar, K>(&self, color: C, vertex: &ScreenVertex<N, K>) -> C {
+        // Screen-space w holds the reciprocal of clip-space w
+        let distance: f32 = cast(N::one() / vertex.position.w).unwrap_or(::std::f32::INFINITY);
+
+        self.apply(color, distance)
+    }
+}
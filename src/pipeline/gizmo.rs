@@ -0,0 +1,432 @@
+//! Manipulation gizmos for editor-like tools
+//!
+//! A `Gizmo` is the familiar set of translate arrows, rotation rings or scale handles drawn at an object,
+//! kept at a constant size on screen regardless of distance, with hit-testing against the handles for picking:
+//!
+//! ```ignore
+//! let mut gizmo = Gizmo::new(GizmoMode::Translate, selected.position);
+//!
+//! let scale = gizmo.world_scale(&view, &projection, &viewport);
+//!
+//! let ray = Ray::from_screen(&inverse_view_projection, &viewport, mouse_x, mouse_y);
+//!
+//! gizmo.set_highlight(gizmo.hit_test(&ray, scale));
+//!
+//! // After the scene, so the gizmo is drawn over it
+//! pipeline.draw_gizmo(&gizmo, &(projection * view), scale);
+//! ```
+//!
+//! Handles are built in gizmo space, where they are one unit long, then positioned, oriented and scaled with `Gizmo::transform`.
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix3, Matrix4};
+
+use ::color::FromChannels;
+use ::geometry::{ClipVertex, Viewport};
+use ::mesh::{Vertex, Mesh};
+use ::primitive::Triangle;
+use ::raytrace::Ray;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::Pixel;
+
+/// Fraction of the depth range in front of the near plane the gizmo is squeezed into, so it's never hidden by the scene
+const DEPTH_RANGE: f32 = 0.01;
+
+/// Handles are made thicker by this factor when hit-testing, so thin shafts and rings are easy to grab
+const PICK_THICKNESS: f32 = 4.0;
+
+const SHAFT_RADIUS: f32 = 0.015;
+const CONE_LENGTH: f32 = 0.2;
+const CONE_RADIUS: f32 = 0.06;
+const CUBE_SIZE: f32 = 0.1;
+const RING_SEGMENTS: usize = 48;
+const SIDES: usize = 8;
+
+/// Highlight color of the hovered or dragged handle
+const HIGHLIGHT_COLOR: [f32; 4] = [1.0, 0.85, 0.0, 1.0];
+
+/// Kind of transformation a gizmo manipulates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GizmoMode {
+    /// Arrows along each axis
+    Translate,
+    /// Rings around each axis
+    Rotate,
+    /// Shafts ending in cubes along each axis
+    Scale,
+}
+
+/// Axis of a gizmo handle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GizmoAxis {
+    X,
+    Y,
+    Z,
+}
+
+impl GizmoAxis {
+    /// Unit vector along the axis in gizmo space
+    pub fn direction(self) -> Vector3<f32> {
+        match self {
+            GizmoAxis::X => Vector3::x(),
+            GizmoAxis::Y => Vector3::y(),
+            GizmoAxis::Z => Vector3::z(),
+        }
+    }
+
+    /// Red, green or blue for the x, y or z axis
+    pub fn color(self) -> [f32; 4] {
+        match self {
+            GizmoAxis::X => [0.9, 0.15, 0.15, 1.0],
+            GizmoAxis::Y => [0.15, 0.8, 0.15, 1.0],
+            GizmoAxis::Z => [0.15, 0.3, 0.95, 1.0],
+        }
+    }
+
+    /// Direction of the axis and two perpendicular directions `t` and `b`, with `t × b` along the axis
+    fn frame(self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        match self {
+            GizmoAxis::X => (Vector3::x(), Vector3::y(), Vector3::z()),
+            GizmoAxis::Y => (Vector3::y(), Vector3::z(), Vector3::x()),
+            GizmoAxis::Z => (Vector3::z(), Vector3::x(), Vector3::y()),
+        }
+    }
+}
+
+/// Vertex of a gizmo handle in gizmo space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GizmoVertex {
+    pub position: Point3<f32>,
+    /// Handle the vertex belongs to
+    pub axis: GizmoAxis,
+}
+
+impl Vertex for GizmoVertex {
+    type Scalar = f32;
+
+    #[inline(always)]
+    fn position(&self) -> Point3<f32> { self.position }
+}
+
+/// Translate, rotate or scale handles at a position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gizmo {
+    mode: GizmoMode,
+    position: Point3<f32>,
+    orientation: Matrix3<f32>,
+    size: f32,
+    highlight: Option<GizmoAxis>,
+}
+
+impl Gizmo {
+    /// Creates a gizmo aligned to the world axes, with handles 100 pixels long
+    pub fn new(mode: GizmoMode, position: Point3<f32>) -> Gizmo {
+        Gizmo {
+            mode,
+            position,
+            orientation: Matrix3::identity(),
+            size: 100.0,
+            highlight: None,
+        }
+    }
+
+    #[inline]
+    pub fn mode(&self) -> GizmoMode { self.mode }
+
+    pub fn set_mode(&mut self, mode: GizmoMode) {
+        self.mode = mode;
+    }
+
+    pub fn with_mode(mut self, mode: GizmoMode) -> Self {
+        self.set_mode(mode);
+        self
+    }
+
+    /// World-space position of the center of the gizmo
+    #[inline]
+    pub fn position(&self) -> Point3<f32> { self.position }
+
+    pub fn set_position(&mut self, position: Point3<f32>) {
+        self.position = position;
+    }
+
+    pub fn with_position(mut self, position: Point3<f32>) -> Self {
+        self.set_position(position);
+        self
+    }
+
+    /// Rotation from gizmo space to world space, with the world-space x, y and z handle directions as its columns
+    #[inline]
+    pub fn orientation(&self) -> &Matrix3<f32> { &self.orientation }
+
+    /// Sets the rotation of the handles, such as the rotation of the selected object for local-space manipulation.
+    ///
+    /// The matrix must not include scaling.
+    pub fn set_orientation(&mut self, orientation: Matrix3<f32>) {
+        self.orientation = orientation;
+    }
+
+    pub fn with_orientation(mut self, orientation: Matrix3<f32>) -> Self {
+        self.set_orientation(orientation);
+        self
+    }
+
+    /// Length of the handles on screen, in pixels
+    #[inline]
+    pub fn size(&self) -> f32 { self.size }
+
+    pub fn set_size(&mut self, size: f32) {
+        self.size = size;
+    }
+
+    pub fn with_size(mut self, size: f32) -> Self {
+        self.set_size(size);
+        self
+    }
+
+    /// Handle drawn in the highlight color, usually the one under the cursor or being dragged
+    #[inline]
+    pub fn highlight(&self) -> Option<GizmoAxis> { self.highlight }
+
+    pub fn set_highlight(&mut self, highlight: Option<GizmoAxis>) {
+        self.highlight = highlight;
+    }
+
+    pub fn with_highlight(mut self, highlight: Option<GizmoAxis>) -> Self {
+        self.set_highlight(highlight);
+        self
+    }
+
+    /// World-space length of the handles that makes them `size` pixels long on screen,
+    /// given the view and projection matrices and viewport of the camera.
+    ///
+    /// This changes whenever the camera or gizmo moves, so compute it once per frame and
+    /// pass it to both `hit_test` and `Pipeline::draw_gizmo`.
+    pub fn world_scale(&self, view: &Matrix4<f32>, projection: &Matrix4<f32>, viewport: &Viewport<f32>) -> f32 {
+        let clip = projection * view * self.position.to_homogeneous();
+
+        // World-space height of a pixel at the depth of the gizmo, for both perspective and orthographic projections
+        let pixel = 2.0 * clip.w.abs() / (projection[(1, 1)].abs() * viewport.height);
+
+        self.size * pixel
+    }
+
+    /// Transform from gizmo space to world space
+    pub fn transform(&self, scale: f32) -> Matrix4<f32> {
+        let rotation = self.orientation * scale;
+
+        Matrix4::new(rotation[(0, 0)], rotation[(0, 1)], rotation[(0, 2)], self.position.x,
+                     rotation[(1, 0)], rotation[(1, 1)], rotation[(1, 2)], self.position.y,
+                     rotation[(2, 0)], rotation[(2, 1)], rotation[(2, 2)], self.position.z,
+                     0.0, 0.0, 0.0, 1.0)
+    }
+
+    /// Triangles of the handles in gizmo space, wound counter-clockwise when seen from outside
+    pub fn mesh(&self) -> Mesh<GizmoVertex, u32> {
+        self.build(1.0)
+    }
+
+    /// Thicker version of the handles used for hit-testing
+    pub fn pick_mesh(&self) -> Mesh<GizmoVertex, u32> {
+        self.build(PICK_THICKNESS)
+    }
+
+    /// Finds the handle hit by a world-space ray, such as one through the mouse cursor from `Ray::from_screen`,
+    /// with `scale` from `world_scale`
+    pub fn hit_test(&self, ray: &Ray, scale: f32) -> Option<GizmoAxis> {
+        let inverse = self.transform(scale).try_inverse()?;
+
+        let origin = inverse * ray.origin.to_homogeneous();
+        let direction = inverse * ray.direction.to_homogeneous();
+
+        // Distances along the ray are unchanged, since the direction is transformed along with the origin
+        let ray = Ray::new(Point3::new(origin.x, origin.y, origin.z), Vector3::new(direction.x, direction.y, direction.z));
+
+        let mesh = self.pick_mesh();
+        let bvh = mesh.build_bvh();
+
+        bvh.ray_intersect(&ray).map(|hit| bvh.vertices(&hit)[0].axis)
+    }
+
+    fn build(&self, thickness: f32) -> Mesh<GizmoVertex, u32> {
+        let mut mesh = Mesh { vertices: Vec::new(), indices: Vec::new() };
+
+        for &axis in &[GizmoAxis::X, GizmoAxis::Y, GizmoAxis::Z] {
+            let direction = axis.direction();
+            let shaft = SHAFT_RADIUS * thickness;
+
+            match self.mode {
+                GizmoMode::Translate => {
+                    let tip = 1.0 - CONE_LENGTH;
+
+                    add_frustum(&mut mesh, axis, Point3::origin(), Point3::from_coordinates(direction * tip), shaft, shaft, SIDES);
+                    add_frustum(&mut mesh, axis, Point3::from_coordinates(direction * tip), Point3::from_coordinates(direction),
+                                CONE_RADIUS * thickness, 0.0, SIDES);
+                }
+                GizmoMode::Scale => {
+                    // Four-sided prism with a square cross-section, forming a cube,
+                    // only grown by the square root of the thickness so it doesn't swallow the other handles
+                    let half = CUBE_SIZE * 0.5 * thickness.sqrt();
+                    let tip = 1.0 - half * 2.0;
+
+                    add_frustum(&mut mesh, axis, Point3::origin(), Point3::from_coordinates(direction * tip), shaft, shaft, SIDES);
+                    add_frustum(&mut mesh, axis, Point3::from_coordinates(direction * tip), Point3::from_coordinates(direction),
+                                half * ::std::f32::consts::SQRT_2, half * ::std::f32::consts::SQRT_2, 4);
+                }
+                GizmoMode::Rotate => {
+                    add_torus(&mut mesh, axis, 1.0, shaft);
+                }
+            }
+        }
+
+        mesh
+    }
+}
+
+#[inline]
+fn push_vertex(mesh: &mut Mesh<GizmoVertex, u32>, axis: GizmoAxis, position: Point3<f32>) -> u32 {
+    mesh.vertices.push(GizmoVertex { position, axis });
+
+    mesh.vertices.len() as u32 - 1
+}
+
+/// Closed cone frustum along an axis from `base` to `tip`, with radius `r0` at the base and `r1` at the tip
+fn add_frustum(mesh: &mut Mesh<GizmoVertex, u32>, axis: GizmoAxis, base: Point3<f32>, tip: Point3<f32>, r0: f32, r1: f32, sides: usize) {
+    let (_, t, b) = axis.frame();
+
+    let ring = |center: Point3<f32>, radius: f32, i: usize| {
+        let angle = (i % sides) as f32 / sides as f32 * 2.0 * ::std::f32::consts::PI;
+
+        center + (t * angle.cos() + b * angle.sin()) * radius
+    };
+
+    let base_center = push_vertex(mesh, axis, base);
+    let tip_center = push_vertex(mesh, axis, tip);
+
+    for i in 0..sides {
+        let b0 = push_vertex(mesh, axis, ring(base, r0, i));
+        let b1 = push_vertex(mesh, axis, ring(base, r0, i + 1));
+        let t0 = push_vertex(mesh, axis, ring(tip, r1, i));
+        let t1 = push_vertex(mesh, axis, ring(tip, r1, i + 1));
+
+        mesh.indices.extend_from_slice(&[b0, b1, t1, b0, t1, t0, base_center, b1, b0]);
+
+        if r1 > 0.0 {
+            mesh.indices.extend_from_slice(&[tip_center, t0, t1]);
+        }
+    }
+}
+
+/// Ring of `radius` around an axis through the origin, with a tube of radius `tube`
+fn add_torus(mesh: &mut Mesh<GizmoVertex, u32>, axis: GizmoAxis, radius: f32, tube: f32) {
+    let (d, t, b) = axis.frame();
+
+    let first = mesh.vertices.len() as u32;
+
+    for i in 0..RING_SEGMENTS {
+        let theta = i as f32 / RING_SEGMENTS as f32 * 2.0 * ::std::f32::consts::PI;
+
+        let radial = t * theta.cos() + b * theta.sin();
+
+        for j in 0..SIDES {
+            let phi = j as f32 / SIDES as f32 * 2.0 * ::std::f32::consts::PI;
+
+            push_vertex(mesh, axis, Point3::from_coordinates(radial * (radius + tube * phi.cos()) + d * (tube * phi.sin())));
+        }
+    }
+
+    let index = |i: usize, j: usize| first + ((i % RING_SEGMENTS) * SIDES + j % SIDES) as u32;
+
+    for i in 0..RING_SEGMENTS {
+        for j in 0..SIDES {
+            let (i0, i1, i2, i3) = (index(i, j), index(i + 1, j), index(i + 1, j + 1), index(i, j + 1));
+
+            mesh.indices.extend_from_slice(&[i0, i1, i2, i0, i2, i3]);
+        }
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Draws the handles of a gizmo into the framebuffer over the scene, as seen through `view_projection`,
+    /// with `scale` from `Gizmo::world_scale`.
+    ///
+    /// The gizmo is depth-tested only against itself, in a sliver of the depth range right at the near plane,
+    /// so draw it after the scene.
+    pub fn draw_gizmo(&mut self, gizmo: &Gizmo, view_projection: &Matrix4<f32>, scale: f32) where Pixel<Self>: FromChannels {
+        let model_view_projection = view_projection * gizmo.transform(scale);
+        let highlight = gizmo.highlight();
+
+        self.render_mesh(Triangle, Arc::new(gizmo.mesh()), None)
+            .run(|vertex, _| {
+                let mut clip = model_view_projection * vertex.position.to_homogeneous();
+
+                // Maps normalized depth from [-1, 1] to [-1, -1 + 2 * DEPTH_RANGE]
+                clip.z = clip.z * DEPTH_RANGE + clip.w * (DEPTH_RANGE - 1.0);
+
+                let color = if highlight == Some(vertex.axis) { HIGHLIGHT_COLOR } else { vertex.axis.color() };
+
+                ClipVertex::new(clip, Vector4::new(color[0], color[1], color[2], color[3]))
+            })
+            .clip_primitives()
+            .finish_default()
+            .run(|v, _| {
+                let color = v.uniforms;
+
+                Fragment::Color(<Pixel<Self> as FromChannels>::from_channels([color.x, color.y, color.z, color.w]))
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Perspective3;
+
+    use ::geometry::{Dimensions, Coordinate};
+
+    use super::*;
+
+    #[test]
+    fn test_gizmo_hit_test() {
+        let mut gizmo = Gizmo::new(GizmoMode::Translate, Point3::new(1.0, 0.0, 0.0));
+
+        let down = |x: f32, y: f32| Ray::new(Point3::new(x, y, 5.0), -Vector3::z());
+
+        assert_eq!(gizmo.hit_test(&down(1.5, 0.0), 1.0), Some(GizmoAxis::X));
+        assert_eq!(gizmo.hit_test(&down(1.0, 0.5), 1.0), Some(GizmoAxis::Y));
+        assert_eq!(gizmo.hit_test(&down(1.5, 0.5), 1.0), None);
+
+        // Scaled up, the handles reach further
+        assert_eq!(gizmo.hit_test(&down(1.0, 1.5), 1.0), None);
+        assert_eq!(gizmo.hit_test(&down(1.0, 1.5), 2.0), Some(GizmoAxis::Y));
+
+        // Looking down the z axis, only the ring around it is in the way
+        gizmo.set_mode(GizmoMode::Rotate);
+
+        let diagonal = ::std::f32::consts::FRAC_1_SQRT_2;
+
+        assert_eq!(gizmo.hit_test(&down(1.0 + diagonal, diagonal), 1.0), Some(GizmoAxis::Z));
+        assert_eq!(gizmo.hit_test(&down(1.3, 0.3), 1.0), None);
+    }
+
+    #[test]
+    fn test_gizmo_world_scale() {
+        let viewport = Viewport::new(Dimensions::new(200, 200), Coordinate::new(0, 0), 0.0f32, 1.0);
+
+        // 90 degree field of view, so the viewport is as tall as twice the distance
+        let projection = Perspective3::new(1.0, ::std::f32::consts::FRAC_PI_2, 0.1, 100.0).to_homogeneous();
+
+        let near = Gizmo::new(GizmoMode::Scale, Point3::new(0.0, 0.0, -2.0)).with_size(50.0);
+        let far = near.clone().with_position(Point3::new(0.0, 0.0, -4.0));
+
+        assert!((near.world_scale(&Matrix4::identity(), &projection, &viewport) - 1.0).abs() < 1e-5);
+        assert!((far.world_scale(&Matrix4::identity(), &projection, &viewport) - 2.0).abs() < 1e-5);
+
+        let mesh = near.mesh();
+
+        assert_eq!(mesh.indices.len() % 3, 0);
+        assert!(mesh.vertices.iter().all(|v| v.position.coords.iter().all(|&c| c.abs() <= 1.0 + 1e-5)));
+    }
+}
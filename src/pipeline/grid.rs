@@ -0,0 +1,382 @@
+//! Anti-aliased world grid on an infinite ground plane
+//!
+//! The grid is shaded per pixel by intersecting the view ray with a horizontal plane,
+//! measuring how much of the plane each pixel covers from the rays through neighbouring pixels,
+//! so lines stay one pixel wide and smooth at any distance. Lines too dense to resolve fade out instead of aliasing,
+//! and the whole grid fades with distance from the camera.
+//!
+//! It can be drawn as a full-screen pass over a finished framebuffer, tested against its depth attachment:
+//!
+//! ```ignore
+//! apply_grid(&mut framebuffer, &Grid::default(), &view_projection, &viewport);
+//! ```
+//!
+//! or as a large quad through the pipeline, which also writes depth where lines are drawn:
+//!
+//! ```ignore
+//! pipeline.draw_grid(&Grid::default(), &view_projection);
+//! ```
+
+use std::sync::Arc;
+
+use num_traits::{NumCast, cast};
+
+use nalgebra::{Point3, Vector2, Vector4, Matrix4};
+
+use ::color::{ToChannels, FromChannels};
+use ::color::blend::{Blend, BlendPreset};
+use ::geometry::{Coordinate, ClipVertex, HasDimensions, Viewport};
+use ::pixels::PixelRead;
+use ::mesh::{Mesh, SimpleVertex};
+use ::primitive::Triangle;
+use ::raytrace::Ray;
+use ::framebuffer::UnsafeFramebuffer;
+use ::framebuffer::types::DepthAttachment;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::Pixel;
+
+/// Grid lines on the horizontal plane `y = height`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct Grid {
+    /// Height of the plane along the y axis
+    pub height: f32,
+    /// Distance between minor lines
+    pub spacing: f32,
+    /// Number of minor lines between major lines, or zero for no major lines
+    pub major: u32,
+    /// Width of minor lines in pixels. Major lines are half again as wide, and axis lines twice as wide.
+    pub line_width: f32,
+    pub minor_color: [f32; 4],
+    pub major_color: [f32; 4],
+    /// Color of the line along the x axis, where `z = 0`
+    pub x_axis_color: [f32; 4],
+    /// Color of the line along the z axis, where `x = 0`
+    pub z_axis_color: [f32; 4],
+    /// Distance from the camera at which the grid has faded out completely, or zero to never fade
+    pub fade_distance: f32,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid {
+            height: 0.0,
+            spacing: 1.0,
+            major: 10,
+            line_width: 1.0,
+            minor_color: [0.5, 0.5, 0.5, 0.4],
+            major_color: [0.6, 0.6, 0.6, 0.8],
+            x_axis_color: [0.9, 0.2, 0.2, 1.0],
+            z_axis_color: [0.2, 0.35, 0.95, 1.0],
+            fade_distance: 100.0,
+        }
+    }
+}
+
+impl Grid {
+    pub fn new() -> Grid {
+        Grid::default()
+    }
+
+    /// Color of the grid at the point `(x, height, z)`, with alpha for its coverage.
+    ///
+    /// `footprint` holds how much `x` and `z` change across a pixel there, like `fwidth` in GLSL,
+    /// and `distance` is the distance of the point from the camera.
+    pub fn color_at(&self, x: f32, z: f32, footprint: Vector2<f32>, distance: f32) -> [f32; 4] {
+        let fade = if self.fade_distance > 0.0 { (1.0 - distance / self.fade_distance).max(0.0) } else { 1.0 };
+
+        if fade <= 0.0 {
+            return [0.0; 4];
+        }
+
+        let minor = self.lines(x, z, self.spacing, footprint, self.line_width);
+
+        let major = if self.major > 0 {
+            self.lines(x, z, self.spacing * self.major as f32, footprint, self.line_width * 1.5)
+        } else {
+            0.0
+        };
+
+        let x_axis = line_coverage(z.abs(), footprint.y, self.line_width * 2.0);
+        let z_axis = line_coverage(x.abs(), footprint.x, self.line_width * 2.0);
+
+        // Each layer replaces the ones below it by its coverage, mixed with premultiplied alpha
+        let mut color = [0.0; 4];
+
+        for &(layer, coverage) in &[(self.minor_color, minor), (self.major_color, major),
+                                    (self.x_axis_color, x_axis), (self.z_axis_color, z_axis)] {
+            for i in 0..3 {
+                color[i] += (layer[i] * layer[3] - color[i]) * coverage;
+            }
+
+            color[3] += (layer[3] - color[3]) * coverage;
+        }
+
+        if color[3] > 0.0 {
+            for i in 0..3 {
+                color[i] /= color[3];
+            }
+        }
+
+        color[3] *= fade;
+
+        color
+    }
+
+    /// Coverage of the nearest line of either direction, for lines `spacing` apart
+    fn lines(&self, x: f32, z: f32, spacing: f32, footprint: Vector2<f32>, width: f32) -> f32 {
+        if spacing <= 0.0 {
+            return 0.0;
+        }
+
+        let along = |coord: f32, footprint: f32| {
+            let offset = (coord / spacing - (coord / spacing).round()).abs() * spacing;
+
+            // Fade out lines closer together on screen than a few pixels, instead of aliasing into moiré patterns
+            let density = ((spacing / footprint - 2.0) / 4.0).max(0.0).min(1.0);
+
+            line_coverage(offset, footprint, width) * density
+        };
+
+        along(x, footprint.x).max(along(z, footprint.y))
+    }
+}
+
+/// Coverage of a pixel `offset` away from the center of a line `width` pixels wide, with `footprint` world units per pixel
+#[inline]
+fn line_coverage(offset: f32, footprint: f32, width: f32) -> f32 {
+    let footprint = footprint.max(1e-6);
+
+    (width * 0.5 - offset / footprint + 0.5).max(0.0).min(1.0)
+}
+
+/// Shades the grid per pixel for a camera
+struct GridShader<'a> {
+    grid: &'a Grid,
+    view_projection: &'a Matrix4<f32>,
+    inverse_view_projection: Matrix4<f32>,
+    viewport: Viewport<f32>,
+}
+
+impl<'a> GridShader<'a> {
+    fn new(grid: &'a Grid, view_projection: &'a Matrix4<f32>, viewport: Viewport<f32>) -> Option<GridShader<'a>> {
+        view_projection.try_inverse().map(|inverse_view_projection| GridShader {
+            grid,
+            view_projection,
+            inverse_view_projection,
+            viewport,
+        })
+    }
+
+    /// Intersection of the ray through a screen-space position with the plane,
+    /// and the distance along the ray from the near plane, where `1.0` reaches the far plane
+    fn intersect(&self, x: f32, y: f32) -> Option<(Point3<f32>, f32)> {
+        let ray = Ray::from_screen(&self.inverse_view_projection, &self.viewport, x, y);
+
+        if ray.direction.y == 0.0 {
+            return None;
+        }
+
+        let t = (self.grid.height - ray.origin.y) / ray.direction.y;
+
+        Some((ray.at(t), t))
+    }
+
+    /// Color of the grid and normalized depth of the plane at a pixel center, or `None` if no grid is visible there
+    fn shade(&self, x: f32, y: f32) -> Option<([f32; 4], f32)> {
+        let (point, t) = self.intersect(x, y)?;
+
+        if t <= 0.0 || t > 1.0 {
+            return None;
+        }
+
+        // How much the plane position changes towards the neighbouring pixels
+        let (right, _) = self.intersect(x + 1.0, y)?;
+        let (below, _) = self.intersect(x, y + 1.0)?;
+
+        let footprint = Vector2::new((right.x - point.x).abs() + (below.x - point.x).abs(),
+                                     (right.z - point.z).abs() + (below.z - point.z).abs());
+
+        let near = Ray::from_screen(&self.inverse_view_projection, &self.viewport, x, y).origin;
+
+        let color = self.grid.color_at(point.x, point.z, footprint, (point - near).norm());
+
+        if color[3] <= 0.0 {
+            return None;
+        }
+
+        let clip = self.view_projection * point.to_homogeneous();
+
+        Some((color, clip.z / clip.w))
+    }
+}
+
+/// Blends the grid over the color attachment of a framebuffer as a full-screen pass,
+/// wherever the plane is in front of the geometry in the depth attachment.
+///
+/// `view_projection` and `viewport` must be the ones the scene was rendered with.
+/// The depth attachment and the alpha of the framebuffer are left unchanged.
+pub fn apply_grid<F>(framebuffer: &mut F, grid: &Grid, view_projection: &Matrix4<f32>, viewport: &Viewport<f32>)
+    where F: UnsafeFramebuffer, F::Color: ToChannels + FromChannels, DepthAttachment<F>: NumCast {
+    let shader = match GridShader::new(grid, view_projection, *viewport) {
+        Some(shader) => shader,
+        None => return,
+    };
+
+    let dimensions = framebuffer.dimensions();
+
+    framebuffer.prepare_write();
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            let (sx, sy) = (x as f32 + 0.5, y as f32 + 0.5);
+
+            let (sample, depth) = match shader.shade(sx, sy) {
+                Some(shaded) => shaded,
+                None => continue,
+            };
+
+            let index = framebuffer.index_of(Coordinate::new(x, y));
+
+            let scene: Option<f32> = cast(unsafe { framebuffer.get_depth_unchecked(index) });
+
+            if let Some(scene) = scene {
+                let scene = viewport.screen_to_ndc(sx, sy, scene).z;
+
+                // Cleared depth lies beyond the far plane, so anything in range is geometry
+                if !scene.is_nan() && scene.abs() <= 1.0 && scene < depth {
+                    continue;
+                }
+            }
+
+            let mut color = unsafe { framebuffer.get_pixel_unchecked(index).to_channels() };
+
+            for i in 0..3 {
+                color[i] += (sample[i] - color[i]) * sample[3];
+            }
+
+            unsafe { framebuffer.set_pixel_unchecked(index, F::Color::from_channels(color)); }
+        }
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Draws the grid as a quad reaching `fade_distance` around the camera, as seen through `view_projection`,
+    /// blended over the framebuffer with the current depth test.
+    ///
+    /// Pixels with grid lines write depth, so draw it after opaque geometry and before transparent geometry.
+    /// With `fade_distance` at zero, the quad reaches as far as the far plane.
+    pub fn draw_grid(&mut self, grid: &Grid, view_projection: &Matrix4<f32>) where Pixel<Self>: FromChannels,
+                                                                                    BlendPreset: Blend<Pixel<Self>> {
+        let viewport = Viewport::new(self.framebuffer().dimensions(), Coordinate::default(), 0.0, 1.0);
+
+        let shader = match GridShader::new(grid, view_projection, viewport) {
+            Some(shader) => shader,
+            None => return,
+        };
+
+        // Center the quad under the camera, found at the middle of the near plane
+        let camera = Point3::from_homogeneous(shader.inverse_view_projection * Vector4::new(0.0, 0.0, -1.0, 1.0))
+            .unwrap_or(Point3::origin());
+
+        let extent = if grid.fade_distance > 0.0 {
+            grid.fade_distance
+        } else {
+            let far = Point3::from_homogeneous(shader.inverse_view_projection * Vector4::new(0.0, 0.0, 1.0, 1.0))
+                .unwrap_or(camera);
+
+            (far - camera).norm()
+        };
+
+        let corner = |dx: f32, dz: f32| SimpleVertex {
+            position: Point3::new(camera.x + dx * extent, grid.height, camera.z + dz * extent),
+            data: (),
+        };
+
+        let quad: Mesh<SimpleVertex<f32, ()>> = Mesh {
+            indices: vec![0, 1, 2, 1, 3, 2],
+            vertices: vec![corner(-1.0, -1.0), corner(1.0, -1.0), corner(-1.0, 1.0), corner(1.0, 1.0)],
+        };
+
+        self.render_mesh(Triangle, Arc::new(quad), None)
+            .run(|vertex, _| ClipVertex::new(view_projection * vertex.position.to_homogeneous(), ()))
+            .clip_primitives()
+            .finish_default()
+            .with_faces_culled(None)
+            .with_blend(BlendPreset::AlphaOver)
+            .run(|v, _| {
+                match shader.shade(v.position.x, v.position.y) {
+                    Some((color, _)) => Fragment::Color(<Pixel<Self> as FromChannels>::from_channels(color)),
+                    None => Fragment::Discard,
+                }
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Orthographic3;
+
+    use ::geometry::Dimensions;
+
+    use super::*;
+
+    fn assert_color(a: [f32; 4], b: [f32; 4]) {
+        assert!(a.iter().zip(b.iter()).all(|(a, b)| (a - b).abs() < 1e-5), "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_grid_color() {
+        let grid = Grid { fade_distance: 0.0, ..Grid::default() };
+
+        let footprint = Vector2::new(0.01, 0.01);
+
+        // Centered on a minor line, between lines, on a major line and on the x axis
+        assert_color(grid.color_at(3.0, 0.5, footprint, 1.0), grid.minor_color);
+        assert_eq!(grid.color_at(3.5, 0.5, footprint, 1.0)[3], 0.0);
+        assert_color(grid.color_at(10.0, 0.5, footprint, 1.0), grid.major_color);
+        assert_color(grid.color_at(3.5, 0.0, footprint, 1.0), grid.x_axis_color);
+
+        // Minor lines a pixel apart are faded out completely, leaving only major lines
+        let far = Vector2::new(1.0, 1.0);
+
+        assert_eq!(grid.color_at(3.0, 4.5, far, 1.0)[3], 0.0);
+        assert!(grid.color_at(10.0, 4.5, far, 1.0)[3] > 0.0);
+
+        let faded = Grid { fade_distance: 10.0, ..grid }.color_at(3.0, 0.5, footprint, 5.0);
+
+        assert!((faded[3] - grid.minor_color[3] * 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_grid_shader_footprint() {
+        let grid = Grid { spacing: 2.0, fade_distance: 0.0, ..Grid::default() };
+
+        // Looking straight down at the plane from y = 10, with 0.2 world units per pixel
+        let view = Matrix4::new(1.0, 0.0, 0.0, 0.0,
+                                0.0, 0.0, -1.0, 0.0,
+                                0.0, 1.0, 0.0, -10.0,
+                                0.0, 0.0, 0.0, 1.0);
+
+        let view_projection = Orthographic3::new(-10.0, 10.0, -10.0, 10.0, 0.1, 100.0).to_homogeneous() * view;
+
+        let viewport = Viewport::new(Dimensions::new(100, 100), Coordinate::new(0, 0), 0.0, 1.0);
+
+        let shader = GridShader::new(&grid, &view_projection, viewport).unwrap();
+
+        // Pixel center half a pixel from the minor line at x = 2
+        let (point, _) = shader.intersect(60.5, 55.5).unwrap();
+
+        assert!((point - Point3::new(2.1, 0.0, 1.1)).norm() < 1e-4);
+
+        let (color, depth) = shader.shade(60.5, 55.5).unwrap();
+
+        assert!((color[3] - grid.minor_color[3] * 0.5).abs() < 1e-3);
+        assert!(depth.abs() < 1.0);
+
+        // Two and a half pixels from any line
+        assert!(shader.shade(62.5, 55.5).is_none());
+    }
+}
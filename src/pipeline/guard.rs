@@ -0,0 +1,155 @@
+//! Guarding the rasterizer against broken primitives
+//!
+//! Primitives with NaN or infinite screen coordinates have nonsensical bounding boxes,
+//! which at best waste time and at worst produce garbage in every tile. Zero-area triangles
+//! can't produce any fragments, but still cost a full trip through the rasterizer.
+//!
+//! With a `PrimitiveGuard` enabled, every primitive is checked once before rasterization
+//! and any broken ones are skipped, optionally counting them in `GuardDiagnostics`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use num_traits::Zero;
+
+use ::numeric::FloatScalar;
+use ::geometry::ScreenVertex;
+use ::mesh::MeshIndex;
+use ::pipeline::storage::{SeparableScreenPrimitiveStorage, primitive_vertices};
+
+/// Counts of primitives rejected by a `PrimitiveGuard`.
+///
+/// Shared between draws with an `Arc`, so the same diagnostics can collect a whole frame.
+#[derive(Debug, Default)]
+pub struct GuardDiagnostics {
+    non_finite: AtomicUsize,
+    degenerate: AtomicUsize,
+}
+
+impl GuardDiagnostics {
+    pub fn new() -> GuardDiagnostics { GuardDiagnostics::default() }
+
+    /// Number of primitives with NaN or infinite screen coordinates
+    #[inline]
+    pub fn non_finite(&self) -> usize { self.non_finite.load(Ordering::Relaxed) }
+
+    /// Number of zero-area triangles
+    #[inline]
+    pub fn degenerate(&self) -> usize { self.degenerate.load(Ordering::Relaxed) }
+
+    /// Total number of rejected primitives
+    #[inline]
+    pub fn total(&self) -> usize { self.non_finite() + self.degenerate() }
+
+    /// Resets all counts to zero
+    pub fn reset(&self) {
+        self.non_finite.store(0, Ordering::Relaxed);
+        self.degenerate.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Determines how the rasterizer handles broken primitives
+#[derive(Debug, Clone)]
+pub enum PrimitiveGuard {
+    /// Don't check primitives at all, which is the default
+    Disabled,
+    /// Silently skip broken primitives
+    Skip,
+    /// Skip broken primitives and count them
+    Record(Arc<GuardDiagnostics>),
+}
+
+impl Default for PrimitiveGuard {
+    fn default() -> PrimitiveGuard { PrimitiveGuard::Disabled }
+}
+
+impl PrimitiveGuard {
+    /// Returns true if primitives will be checked
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        match *self {
+            PrimitiveGuard::Disabled => false,
+            _ => true,
+        }
+    }
+
+    /// Checks a single primitive, recording it if rejected. Returns true if the primitive should be skipped.
+    pub fn reject<N, K>(&self, vertices: &[&ScreenVertex<N, K>]) -> bool where N: FloatScalar {
+        if !self.is_enabled() {
+            return false;
+        }
+
+        let non_finite = vertices.iter().any(|vertex| {
+            let p = &vertex.position;
+            !(p.x.is_finite() && p.y.is_finite() && p.z.is_finite() && p.w.is_finite())
+        });
+
+        let degenerate = !non_finite && vertices.len() == 3 && {
+            let (a, b, c) = (&vertices[0].position, &vertices[1].position, &vertices[2].position);
+
+            ((b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x)).is_zero()
+        };
+
+        if let PrimitiveGuard::Record(ref diagnostics) = *self {
+            if non_finite {
+                diagnostics.non_finite.fetch_add(1, Ordering::Relaxed);
+            } else if degenerate {
+                diagnostics.degenerate.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        non_finite || degenerate
+    }
+}
+
+/// Primitives rejected by the guard for a single draw.
+///
+/// Each list is empty if the guard is disabled, in which case nothing is rejected.
+#[derive(Debug, Default)]
+pub ( in ::pipeline ) struct RejectedPrimitives {
+    pub indexed: Vec<bool>,
+    pub tris: Vec<bool>,
+    pub lines: Vec<bool>,
+    pub points: Vec<bool>,
+}
+
+impl RejectedPrimitives {
+    /// Checks every primitive of a draw once, so tiles don't have to repeat the work or count primitives twice
+    pub fn check<N, K, I>(guard: &PrimitiveGuard,
+                          primitive_size: usize,
+                          indices: &[I],
+                          indexed_vertices: Option<&[ScreenVertex<N, K>]>,
+                          generated: &SeparableScreenPrimitiveStorage<N, K>) -> RejectedPrimitives where N: FloatScalar, I: MeshIndex {
+        if !guard.is_enabled() {
+            return RejectedPrimitives::default();
+        }
+
+        let indexed = match indexed_vertices {
+            Some(vertices) => indices.chunks(primitive_size).map(|primitive| {
+                let (primitive, count) = primitive_vertices(vertices, primitive);
+
+                guard.reject(&primitive[..count])
+            }).collect(),
+            None => Vec::new(),
+        };
+
+        RejectedPrimitives {
+            indexed,
+            tris: (0..generated.num_triangles()).map(|j| guard.reject(&generated.triangle(j))).collect(),
+            lines: generated.lines.chunks(2).map(|l| guard.reject(&[&l[0], &l[1]])).collect(),
+            points: generated.points.iter().map(|p| guard.reject(&[p])).collect(),
+        }
+    }
+
+    /// Total number of rejected primitives
+    pub fn count(&self) -> usize {
+        [&self.indexed, &self.tris, &self.lines, &self.points].iter()
+            .map(|list| list.iter().filter(|&&rejected| rejected).count())
+            .sum()
+    }
+
+    #[inline]
+    pub fn is_rejected(list: &[bool], index: usize) -> bool {
+        list.get(index).cloned().unwrap_or(false)
+    }
+}
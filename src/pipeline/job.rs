@@ -0,0 +1,212 @@
+//! Resumable rendering
+//!
+//! Very large offline renders can take long enough that an application wants to show progress,
+//! display the partially rendered image, or cancel the render altogether. A `RenderJob` splits the fragment stage
+//! into steps of a few tiles each, which can be run from an event loop between handling other events:
+//!
+//! ```ignore
+//! let mut job = geometry_shader.finish_default().into_job(fragment_shader);
+//!
+//! while !job.step(4) {
+//!     progress_bar.set(job.progress());
+//!     preview.update(job.framebuffer());
+//!
+//!     if cancel_button.clicked() {
+//!         break;
+//!     }
+//! }
+//! ```
+//!
+//! Dropping the job cancels it, leaving the remaining tiles unrendered.
+//!
+//! Each step checks and sorts all primitives again before rendering its tiles,
+//! so steps should cover enough tiles to make that negligible.
+
+use std::time::{Duration, Instant};
+
+use ::color::blend::Blend;
+use ::primitive::Primitive;
+use ::mesh::{Vertex, MeshIndex};
+use ::geometry::{HasDimensions, ScreenVertex};
+use ::interpolate::Interpolate;
+use ::pipeline::{PipelineObject, FragmentShader, BudgetReport};
+use ::pipeline::stages::fragment::{Fragment, TileLimit};
+
+use ::pipeline::types::{PipelineUniforms, Pixel};
+
+/// A fragment stage that renders a few tiles at a time
+pub struct RenderJob<'a, P: 'a, V: Vertex, T, K, B, I: MeshIndex, S> where P: PipelineObject {
+    shader: FragmentShader<'a, P, V, T, K, B, I>,
+    fragment_shader: S,
+    /// Which tiles have been rendered, so steps can resume from any unfinished tile
+    finished: Vec<bool>,
+    /// Index of the first unfinished tile
+    next_tile: usize,
+    completed_tiles: usize,
+}
+
+impl<'a, P: 'a, V, T, K, B, I> FragmentShader<'a, P, V, T, K, B, I> where P: PipelineObject,
+                                                                    V: Vertex,
+                                                                    T: Primitive,
+                                                                    K: Send + Sync + Interpolate,
+                                                                    B: Blend<Pixel<P>>,
+                                                                    I: MeshIndex {
+    /// Creates a job rendering the geometry with `fragment_shader` in steps,
+    /// without rendering anything yet. See the [`job`](../job/index.html) module for details.
+    pub fn into_job<S>(self, fragment_shader: S) -> RenderJob<'a, P, V, T, K, B, I, S>
+        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+        let mut shader = self;
+
+        let tile_count = {
+            let dimensions = shader.pipeline.framebuffer().dimensions();
+
+            let tiles = shader.pipeline.arena_mut().take_tiles(dimensions, shader.tile_size, shader.scissor);
+
+            let tile_count = tiles.len();
+
+            shader.pipeline.arena_mut().return_tiles(tiles);
+
+            tile_count
+        };
+
+        RenderJob { shader, fragment_shader, finished: vec![false; tile_count], next_tile: 0, completed_tiles: 0 }
+    }
+}
+
+impl<'a, P: 'a, V, T, K, B, I, S> RenderJob<'a, P, V, T, K, B, I, S> where P: PipelineObject,
+                                                                     V: Vertex,
+                                                                     T: Primitive,
+                                                                     K: Send + Sync + Interpolate,
+                                                                     B: Blend<Pixel<P>>,
+                                                                     I: MeshIndex,
+                                                                     S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+    /// Renders up to `tiles` more tiles, returning true once every tile has been rendered
+    pub fn step(&mut self, tiles: usize) -> bool {
+        if !self.is_finished() && tiles > 0 {
+            let start = self.next_tile;
+            let end = start.saturating_add(tiles);
+
+            self.render(TileLimit { start, end, deadline: None });
+        }
+
+        self.is_finished()
+    }
+
+    /// Renders all remaining tiles
+    pub fn finish(mut self) {
+        let tile_count = self.tile_count();
+
+        self.step(tile_count);
+    }
+
+    /// Renders tiles until `budget` has passed, continuing from the last step,
+    /// and returns which tiles were rendered by this call.
+    pub fn step_with_budget(&mut self, budget: Duration) -> BudgetReport {
+        let limit = TileLimit { start: self.next_tile, end: self.tile_count(), deadline: Some(Instant::now() + budget) };
+
+        self.render(limit)
+    }
+
+    /// Renders the unfinished tiles within `limit`, and marks them as finished
+    fn render(&mut self, limit: TileLimit) -> BudgetReport {
+        let report = self.shader.rasterize(&[&self.fragment_shader], |_| 0, None::<fn(&K) -> Pixel<P>>,
+                                           limit, Some(&mut self.finished[..]));
+
+        self.completed_tiles += report.completed.len();
+
+        // Resume from the first unfinished tile, wherever the last pass stopped
+        while self.next_tile < self.finished.len() && self.finished[self.next_tile] {
+            self.next_tile += 1;
+        }
+
+        report
+    }
+
+    /// Returns true once every tile has been rendered
+    #[inline]
+    pub fn is_finished(&self) -> bool {
+        self.completed_tiles >= self.tile_count()
+    }
+
+    /// Total number of tiles to render
+    #[inline]
+    pub fn tile_count(&self) -> usize { self.finished.len() }
+
+    /// Number of tiles rendered so far
+    #[inline]
+    pub fn completed_tiles(&self) -> usize { self.completed_tiles }
+
+    /// Number of tiles left to render
+    #[inline]
+    pub fn remaining_tiles(&self) -> usize { self.tile_count() - self.completed_tiles }
+
+    /// Fraction of tiles rendered so far, from `0.0` to `1.0`
+    pub fn progress(&self) -> f32 {
+        if self.tile_count() == 0 { 1.0 } else { self.completed_tiles as f32 / self.tile_count() as f32 }
+    }
+
+    /// The framebuffer being rendered into, including all tiles rendered so far
+    #[inline]
+    pub fn framebuffer(&self) -> &P::Framebuffer {
+        self.shader.pipeline.framebuffer()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use nalgebra::{Point3, Vector4};
+
+    use ::prelude::*;
+    use ::color::predefined::formats::RGBAf32Color;
+    use ::framebuffer::attachments::predefined::ColorDepthAttachments;
+
+    #[test]
+    fn test_resume_from_unfinished_tiles() {
+        let mut pipeline: Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>> =
+            Pipeline::from_framebuffer(RenderBuffer::with_dimensions(Dimensions::new(16, 16)), ());
+
+        pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 0.0));
+
+        // Covers the whole screen
+        let mesh = Arc::new(Mesh {
+            indices: vec![0, 1, 2],
+            vertices: vec![
+                SimpleVertex { position: Point3::new(-1.0, -1.0, 0.5), data: () },
+                SimpleVertex { position: Point3::new(3.0, -1.0, 0.5), data: () },
+                SimpleVertex { position: Point3::new(-1.0, 3.0, 0.5), data: () },
+            ],
+        });
+
+        {
+            let fragment_shader = pipeline.render_mesh(Triangle, mesh, None)
+                                          .run(|vertex, _| ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ()))
+                                          .finish_default()
+                                          .with_tile_size(Dimensions::new(8, 8));
+
+            let mut job = fragment_shader.into_job(|_, _| Fragment::Color(RGBAf32Color::new(1.0, 1.0, 1.0, 1.0)));
+
+            assert_eq!(job.tile_count(), 4);
+
+            // As if a pass had finished a later tile before an earlier one
+            job.finished[2] = true;
+            job.completed_tiles = 1;
+
+            assert!(!job.step(1));
+            assert_eq!(job.completed_tiles(), 2);
+            assert_eq!(job.next_tile, 1);
+
+            let report = job.step_with_budget(::std::time::Duration::from_secs(60));
+
+            assert_eq!(report.completed.len(), 2);
+            assert!(report.skipped.is_empty());
+            assert!(job.is_finished());
+        }
+
+        let written = pipeline.framebuffer().pixel_iter().filter(|pixel| pixel.get().x > 0.0).count();
+
+        // The tile marked as finished was never rendered
+        assert_eq!(written, 3 * 8 * 8);
+    }
+}
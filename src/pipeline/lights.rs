@@ -0,0 +1,237 @@
+//! Tiled light culling
+//!
+//! Shading every fragment with every light gets slow quickly, even though most lights only reach
+//! a small part of the screen. A `LightGrid` bins lights into the same screen tiles the fragment stage renders,
+//! by the screen-space bounds of the sphere each light reaches, so fragment shaders only loop over
+//! the few lights that can affect their tile:
+//!
+//! ```ignore
+//! let grid = pipeline.build_light_grid(&lights, &view_projection);
+//!
+//! geometry_shader.finish_default().run(|v, _| {
+//!     let mut color = ambient;
+//!
+//!     for &light in grid.lights_at(v.position.x, v.position.y) {
+//!         color += shade(&lights[light as usize], &v.uniforms);
+//!     }
+//!
+//!     Fragment::Color(color)
+//! });
+//! ```
+//!
+//! Culling is conservative, so some lights in a tile's list might still not reach every fragment in it.
+//! Lights crossing the near plane are added to every tile.
+
+use nalgebra::{Point3, Vector4, Matrix4};
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::geometry::{Dimensions, HasDimensions, Viewport};
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::DEFAULT_TILE_SIZE;
+
+/// Lights with a limited range, for culling
+pub trait LightBounds<N: FloatScalar> {
+    /// World-space center and radius of the sphere outside of which the light has no effect
+    fn bounding_sphere(&self) -> (Point3<N>, N);
+}
+
+impl<N: FloatScalar> LightBounds<N> for (Point3<N>, N) {
+    #[inline]
+    fn bounding_sphere(&self) -> (Point3<N>, N) { *self }
+}
+
+impl<'a, N: FloatScalar, L> LightBounds<N> for &'a L where L: LightBounds<N> {
+    #[inline]
+    fn bounding_sphere(&self) -> (Point3<N>, N) { (**self).bounding_sphere() }
+}
+
+/// Lists of the lights reaching each screen tile
+#[derive(Debug, Clone, Default)]
+pub struct LightGrid {
+    tile_size: Dimensions,
+    columns: u32,
+    rows: u32,
+    /// Start of the lights of each tile in `indices`, with one extra entry for the end of the last tile
+    offsets: Vec<u32>,
+    indices: Vec<u32>,
+}
+
+impl LightGrid {
+    /// Bins lights into tiles of `tile_size` covering `dimensions`, given the view-projection matrix
+    /// and viewport the scene is rendered with.
+    ///
+    /// The lists contain indices into `lights`, in increasing order.
+    pub fn build<N, L>(lights: &[L], view_projection: &Matrix4<N>, viewport: Viewport<N>,
+                       dimensions: Dimensions, tile_size: Dimensions) -> LightGrid where N: FloatScalar, L: LightBounds<N> {
+        let tile_size = Dimensions::new(tile_size.width.max(1), tile_size.height.max(1));
+
+        let columns = (dimensions.width + tile_size.width - 1) / tile_size.width;
+        let rows = (dimensions.height + tile_size.height - 1) / tile_size.height;
+
+        let mut bins: Vec<Vec<u32>> = vec![Vec::new(); (columns * rows) as usize];
+
+        let viewport: Viewport<f32> = viewport.cast();
+
+        for (index, light) in lights.iter().enumerate() {
+            let (x0, y0, x1, y1) = match screen_bounds(light.bounding_sphere(), view_projection, viewport) {
+                Some(bounds) => bounds,
+                None => continue,
+            };
+
+            // Tiles overlapped by the rectangle, skipping lights entirely off the screen
+            if x1 < 0.0 || y1 < 0.0 || x0 >= dimensions.width as f32 || y0 >= dimensions.height as f32 || columns == 0 || rows == 0 {
+                continue;
+            }
+
+            let column_range = (x0.max(0.0) as u32 / tile_size.width, (x1.max(0.0) as u32 / tile_size.width).min(columns - 1));
+            let row_range = (y0.max(0.0) as u32 / tile_size.height, (y1.max(0.0) as u32 / tile_size.height).min(rows - 1));
+
+            for row in row_range.0..row_range.1 + 1 {
+                for column in column_range.0..column_range.1 + 1 {
+                    bins[(row * columns + column) as usize].push(index as u32);
+                }
+            }
+        }
+
+        let mut offsets = Vec::with_capacity(bins.len() + 1);
+        let mut indices = Vec::with_capacity(bins.iter().map(Vec::len).sum());
+
+        for bin in bins {
+            offsets.push(indices.len() as u32);
+            indices.extend(bin);
+        }
+
+        offsets.push(indices.len() as u32);
+
+        LightGrid { tile_size, columns, rows, offsets, indices }
+    }
+
+    /// Size of the tiles
+    #[inline]
+    pub fn tile_size(&self) -> Dimensions { self.tile_size }
+
+    /// Number of tiles across the screen
+    #[inline]
+    pub fn columns(&self) -> u32 { self.columns }
+
+    /// Number of tiles down the screen
+    #[inline]
+    pub fn rows(&self) -> u32 { self.rows }
+
+    /// Lights reaching the tile in the given column and row, or none outside of the grid
+    pub fn tile_lights(&self, column: u32, row: u32) -> &[u32] {
+        if column >= self.columns || row >= self.rows {
+            return &[];
+        }
+
+        let tile = (row * self.columns + column) as usize;
+
+        &self.indices[self.offsets[tile] as usize..self.offsets[tile + 1] as usize]
+    }
+
+    /// Lights reaching the tile containing a screen-space position, such as the position of a fragment
+    pub fn lights_at<N: FloatScalar>(&self, x: N, y: N) -> &[u32] {
+        let x: f32 = cast(x).unwrap_or(-1.0);
+        let y: f32 = cast(y).unwrap_or(-1.0);
+
+        if !(x >= 0.0 && y >= 0.0) {
+            return &[];
+        }
+
+        self.tile_lights(x as u32 / self.tile_size.width, y as u32 / self.tile_size.height)
+    }
+
+    /// Largest number of lights in any tile, useful for spotting overloaded tiles
+    pub fn max_lights_per_tile(&self) -> usize {
+        self.offsets.windows(2).map(|pair| (pair[1] - pair[0]) as usize).max().unwrap_or(0)
+    }
+}
+
+/// Screen-space rectangle `(x0, y0, x1, y1)` covering a sphere, or `None` if it's entirely behind the camera
+fn screen_bounds<N: FloatScalar>(sphere: (Point3<N>, N), view_projection: &Matrix4<N>, viewport: Viewport<f32>) -> Option<(f32, f32, f32, f32)> {
+    let (center, radius) = sphere;
+
+    let mut bounds = (::std::f32::INFINITY, ::std::f32::INFINITY, ::std::f32::NEG_INFINITY, ::std::f32::NEG_INFINITY);
+
+    let mut behind = 0;
+    let mut crossing = false;
+
+    // Project the corners of the cube around the sphere
+    for i in 0..8 {
+        let sign = |bit: usize| if i & bit == 0 { -radius } else { radius };
+
+        let corner = view_projection * Vector4::new(center.x + sign(1), center.y + sign(2), center.z + sign(4), N::one());
+
+        let (x, y, w): (f32, f32, f32) = (cast(corner.x).unwrap_or(0.0), cast(corner.y).unwrap_or(0.0), cast(corner.w).unwrap_or(0.0));
+
+        if w <= 0.0 {
+            behind += 1;
+            crossing = true;
+            continue;
+        }
+
+        let sx = viewport.x + (x / w + 1.0) * 0.5 * viewport.width;
+        let sy = viewport.y + (1.0 - y / w) * 0.5 * viewport.height;
+
+        bounds = (bounds.0.min(sx), bounds.1.min(sy), bounds.2.max(sx), bounds.3.max(sy));
+    }
+
+    if behind == 8 {
+        None
+    } else if crossing {
+        Some((::std::f32::NEG_INFINITY, ::std::f32::NEG_INFINITY, ::std::f32::INFINITY, ::std::f32::INFINITY))
+    } else {
+        Some(bounds)
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Bins lights into the tiles of the framebuffer, using the tile size and viewport of the current render state
+    /// and the view-projection matrix the scene is rendered with.
+    ///
+    /// Draws have to use the same tile size as the render state for the tiles to line up.
+    pub fn build_light_grid<N, L>(&self, lights: &[L], view_projection: &Matrix4<N>) -> LightGrid where N: FloatScalar, L: LightBounds<N> {
+        let dimensions = self.framebuffer().dimensions();
+        let tile_size = self.render_state().desc.tile_size.unwrap_or(DEFAULT_TILE_SIZE);
+
+        LightGrid::build(lights, view_projection, self.render_state().viewport_or_full(dimensions), dimensions, tile_size)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Coordinate;
+
+    use super::*;
+
+    #[test]
+    fn test_light_binning() {
+        let dimensions = Dimensions::new(256, 256);
+        let viewport = Viewport::new(dimensions, Coordinate::default(), 0.0f32, 1.0);
+
+        // Orthographic camera looking down -z, with x and y from -1 to 1 covering the screen
+        let mut view_projection = Matrix4::identity();
+        view_projection[(2, 2)] = -1.0;
+
+        let lights = [
+            (Point3::new(0.5f32, 0.5, -1.0), 0.1),
+            (Point3::new(-0.9f32, -0.9, -1.0), 0.05),
+            (Point3::new(5.0f32, 5.0, -1.0), 0.1),
+        ];
+
+        let grid = LightGrid::build(&lights, &view_projection, viewport, dimensions, Dimensions::new(64, 64));
+
+        assert_eq!((grid.columns(), grid.rows()), (4, 4));
+
+        // The first light is in the top-right, and the second in the bottom-left, since y is flipped
+        assert_eq!(grid.lights_at(192.0f32, 64.0), &[0]);
+        assert_eq!(grid.lights_at(10.0f32, 250.0), &[1]);
+        assert!(grid.lights_at(10.0f32, 10.0).is_empty());
+        assert!(grid.lights_at(-1.0f32, 10.0).is_empty());
+
+        // The off-screen light isn't anywhere
+        assert_eq!(grid.max_lights_per_tile(), 1);
+    }
+}
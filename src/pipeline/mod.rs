@@ -6,8 +6,12 @@ use std::marker::PhantomData;
 use scoped_threadpool::Pool;
 use num_cpus::get as num_cpus;
 
-use ::mesh::{Vertex, Mesh};
-use ::primitive::Primitive;
+use ::error::{RenderError, RenderResult};
+use ::validation::{validate_dimensions, validate_mesh};
+use ::behavior::ThreadSafeCopyable;
+use ::numeric::FloatScalar;
+use ::mesh::{Vertex, SimpleVertex, Mesh, MeshIndex};
+use ::primitive::{Primitive, Triangle};
 use ::geometry::Dimensions;
 use ::stencil::StencilConfig;
 use ::framebuffer::Framebuffer;
@@ -16,9 +20,67 @@ use ::framebuffer::nullbuffer::NullFramebuffer;
 pub mod storage;
 pub mod types;
 pub mod stages;
+pub mod state;
+pub mod slot;
+pub mod split;
+pub mod stereo;
+pub mod fog;
+pub mod sort;
+pub mod retro;
+pub mod reflection;
+pub mod cascades;
+pub mod lights;
+pub mod debug_draw;
+pub mod gizmo;
+pub mod grid;
+pub mod skybox;
+#[cfg(feature = "procedural_sky")]
+pub mod preetham;
+pub mod builder;
+pub mod guard;
+pub mod stats;
+pub mod transformed;
+pub mod feedback;
+pub mod arena;
+pub mod dirty;
+pub mod subsample;
+pub mod resolution;
+pub mod threads;
+pub mod budget;
+pub mod job;
+pub mod cancel;
 
 pub use self::storage::PrimitiveStorage;
 pub use self::stages::{VertexShader, GeometryShader, FragmentShader};
+pub use self::stages::rasterization::RasterBackend;
+pub use self::state::{RenderStateDesc, RenderState};
+pub use self::slot::ShaderSlot;
+pub use self::split::{SplitScreen, Partition};
+pub use self::stereo::{Stereo, Eye};
+pub use self::fog::{Fog, FogMode};
+pub use self::sort::SortMode;
+pub use self::retro::{Retro, Interpolation};
+pub use self::reflection::{PlanarReflection, ReflectionSampler};
+pub use self::cascades::{CascadedShadows, CascadeSampler};
+pub use self::lights::{LightGrid, LightBounds};
+pub use self::debug_draw::{DebugDraw, DebugDepth, DebugVertex};
+pub use self::gizmo::{Gizmo, GizmoMode, GizmoAxis, GizmoVertex};
+pub use self::grid::Grid;
+pub use self::skybox::{Sky, CubemapSky};
+#[cfg(feature = "procedural_sky")]
+pub use self::preetham::PreethamSky;
+pub use self::builder::{PipelineBuilder, PipelineBuildError};
+pub use self::guard::{PrimitiveGuard, GuardDiagnostics};
+pub use self::stats::VertexCacheStats;
+pub use self::transformed::TransformedGeometry;
+pub use self::feedback::TransformFeedback;
+pub use self::arena::{FrameArena, PrimitiveCapacity};
+pub use self::dirty::DirtyRegions;
+pub use self::subsample::{Subsample, SubsampleField, Reconstruction};
+pub use self::resolution::DynamicResolution;
+pub use self::budget::BudgetReport;
+pub use self::job::RenderJob;
+pub use self::cancel::CancellationToken;
 
 use self::types::StencilValue;
 
@@ -27,6 +89,9 @@ pub trait PipelineObject {
     /// The associated framebuffer type for the pipeline
     type Framebuffer: Framebuffer;
     /// The associated global uniforms type for the pipeline
+    ///
+    /// These are passed to every shader without being interpolated,
+    /// so they can hold textures, samplers or a shared `TextureRegistry` directly.
     type Uniforms: Send + Sync;
     /// The associated stencil configuration type for the pipeline
     type StencilConfig: StencilConfig;
@@ -49,6 +114,16 @@ pub trait PipelineObject {
     /// Returns a mutable reference to the framebuffer
     fn framebuffer_mut(&mut self) -> &mut Self::Framebuffer;
 
+    /// Returns a reference to the current render state
+    fn render_state(&self) -> &RenderState;
+    /// Returns a mutable reference to the current render state
+    fn render_state_mut(&mut self) -> &mut RenderState;
+
+    /// Returns a reference to the per-frame transient storage
+    fn arena(&self) -> &FrameArena;
+    /// Returns a mutable reference to the per-frame transient storage
+    fn arena_mut(&mut self) -> &mut FrameArena;
+
     #[inline]
     fn all_mut(&mut self) -> (&Self::Uniforms, &mut Self::Framebuffer, &mut Pool);
 }
@@ -62,6 +137,9 @@ pub struct Pipeline<U, F, S = ()> {
     uniforms: U,
     stencil_config: S,
     threadpool: Pool,
+    render_state: RenderState,
+    state_stack: Vec<RenderState>,
+    arena: FrameArena,
 }
 
 impl<U, F, S> PipelineObject for Pipeline<U, F, S> where U: Send + Sync,
@@ -97,6 +175,16 @@ impl<U, F, S> PipelineObject for Pipeline<U, F, S> where U: Send + Sync,
     #[inline]
     fn framebuffer_mut(&mut self) -> &mut Self::Framebuffer { &mut self.framebuffer }
 
+    #[inline]
+    fn render_state(&self) -> &RenderState { &self.render_state }
+    #[inline]
+    fn render_state_mut(&mut self) -> &mut RenderState { &mut self.render_state }
+
+    #[inline]
+    fn arena(&self) -> &FrameArena { &self.arena }
+    #[inline]
+    fn arena_mut(&mut self) -> &mut FrameArena { &mut self.arena }
+
     #[inline]
     fn all_mut(&mut self) -> (&Self::Uniforms, &mut Self::Framebuffer, &mut Pool) {
         (&self.uniforms, &mut self.framebuffer, &mut self.threadpool)
@@ -106,13 +194,17 @@ impl<U, F, S> PipelineObject for Pipeline<U, F, S> where U: Send + Sync,
 impl<U, S> Pipeline<U, NullFramebuffer, S> where U: Send + Sync, S: StencilConfig {
     /// Create a new rendering pipeline instance with a `NullFramebuffer`.
     ///
-    /// Use `from_framebuffer` or `with_framebuffer` to set the desired framebuffer for rendering.
+    /// Use `from_framebuffer` or `with_framebuffer` to set the desired framebuffer for rendering,
+    /// or `Pipeline::builder` to configure and validate everything at once.
     pub fn new(uniforms: U) -> Pipeline<U, NullFramebuffer, S> {
         Pipeline {
             framebuffer: NullFramebuffer::new(),
             uniforms,
             stencil_config: Default::default(),
-            threadpool: Pool::new(num_cpus() as u32)
+            threadpool: Pool::new(num_cpus() as u32),
+            render_state: RenderState::default(),
+            state_stack: Vec::new(),
+            arena: FrameArena::new(),
         }
     }
 
@@ -121,6 +213,14 @@ impl<U, S> Pipeline<U, NullFramebuffer, S> where U: Send + Sync, S: StencilConfi
         Self::new(uniforms).with_framebuffer(framebuffer)
     }
 
+    /// Like `with_framebuffer`, but runs the validation layer on the framebuffer dimensions first,
+    /// returning an error for zero-sized framebuffers instead of panicking.
+    pub fn try_with_framebuffer<F>(self, framebuffer: F) -> RenderResult<Pipeline<U, F, S>> where F: Framebuffer {
+        validate_dimensions(framebuffer.dimensions())?;
+
+        Ok(self.with_framebuffer(framebuffer))
+    }
+
     /// Convert one pipeline into another with the given framebuffer,
     /// discarding the old framebuffer.
     pub fn with_framebuffer<F>(self, framebuffer: F) -> Pipeline<U, F, S> where F: Framebuffer {
@@ -129,28 +229,91 @@ impl<U, S> Pipeline<U, NullFramebuffer, S> where U: Send + Sync, S: StencilConfi
         assert!(width > 0, "Framebuffer must have a non-zero width");
         assert!(height > 0, "Framebuffer must have a non-zero height");
 
-        let Pipeline { uniforms, threadpool, .. } = self;
+        let Pipeline { uniforms, threadpool, render_state, state_stack, arena, .. } = self;
 
         Pipeline {
             framebuffer,
             uniforms,
             stencil_config: Default::default(),
             threadpool,
+            render_state,
+            state_stack,
+            arena,
         }
     }
 }
 
 impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Starts a new frame, resetting per-frame transient storage while keeping its allocations around
+    pub fn begin_frame(&mut self) {
+        self.arena.reset();
+    }
+
+    /// Saves a copy of the current render state, to be restored by `pop_state`
+    pub fn push_state(&mut self) {
+        self.state_stack.push(self.render_state);
+    }
+
+    /// Restores the render state saved by the last `push_state`, returning the state that was replaced.
+    ///
+    /// Returns `None` and leaves the current state unchanged if there are no saved states.
+    pub fn pop_state(&mut self) -> Option<RenderState> {
+        self.state_stack.pop().map(|state| ::std::mem::replace(&mut self.render_state, state))
+    }
+
+    /// Runs `f` with the render state saved beforehand and restored afterwards
+    pub fn with_state<R, G>(&mut self, f: G) -> R where G: FnOnce(&mut Self) -> R {
+        self.push_state();
+        let result = f(self);
+        self.pop_state();
+        result
+    }
+
     /// Start the shading pipeline for a given mesh, with an optional stencil value for the mesh.
     #[must_use]
-    pub fn render_mesh<T, V>(&mut self, primitive: T, mesh: Arc<Mesh<V>>, stencil: Option<StencilValue<Self>>) -> VertexShader<Self, V, T>
-        where T: Primitive, V: Vertex {
+    pub fn render_mesh<T, V, I>(&mut self, primitive: T, mesh: Arc<Mesh<V, I>>, stencil: Option<StencilValue<Self>>) -> VertexShader<Self, V, T, I>
+        where T: Primitive, V: Vertex, I: MeshIndex {
         assert_eq!(mesh.indices.len() % T::num_vertices(), 0);
 
         // We only needed the type information,
         // so just throw away the empty object passed in
         drop(primitive);
 
-        VertexShader { pipeline: self, mesh, stencil_value: stencil.unwrap_or_default(), indexed_primitive: PhantomData }
+        trace_event!(vertices = mesh.vertices.len(), indices = mesh.indices.len(), "draw submitted");
+
+        VertexShader { pipeline: self, mesh, stencil_value: stencil.unwrap_or_default(), indexed_primitive: PhantomData, cancellation: None }
+    }
+
+    /// Feeds previously captured primitives back into the pipeline, starting at the geometry stage.
+    ///
+    /// See `GeometryShader::capture` for how to capture primitives.
+    /// Incomplete lines or triangles at the end of the feedback are dropped.
+    #[must_use]
+    pub fn render_feedback<N, K>(&mut self, feedback: TransformFeedback<N, K>, stencil: Option<StencilValue<Self>>) -> GeometryShader<Self, SimpleVertex<N, ()>, Triangle, K>
+        where N: ThreadSafeCopyable + FloatScalar {
+        GeometryShader {
+            pipeline: self,
+            mesh: Arc::new(Mesh { indices: Vec::new(), vertices: Vec::new() }),
+            indexed_primitive: PhantomData,
+            stencil_value: stencil.unwrap_or_default(),
+            indexed_vertices: None,
+            generated_primitives: feedback.into_storage(),
+        }
+    }
+
+    /// Like `render_mesh`, but runs the validation layer on the mesh first,
+    /// returning an error for invalid indices or non-finite vertex positions instead of panicking later on.
+    ///
+    /// Validation is skipped in release builds unless the `validation` cargo feature is enabled,
+    /// but the index count is always checked.
+    pub fn try_render_mesh<T, V, I>(&mut self, primitive: T, mesh: Arc<Mesh<V, I>>, stencil: Option<StencilValue<Self>>) -> RenderResult<VertexShader<Self, V, T, I>>
+        where T: Primitive, V: Vertex, I: MeshIndex {
+        if mesh.indices.len() % T::num_vertices() != 0 {
+            throw!(RenderError::IndexOutOfRange);
+        }
+
+        validate_mesh::<T, V, I>(&mesh)?;
+
+        Ok(self.render_mesh(primitive, mesh, stencil))
     }
 }
\ No newline at end of file
@@ -0,0 +1,190 @@
+//! Procedural daylight sky
+//!
+//! Implements the analytic sky model from "A Practical Analytic Model for Daylight" by Preetham, Shirley and Smits,
+//! giving the color of a clear sky for any sun position and amount of haze without any textures:
+//!
+//! ```ignore
+//! let sky = PreethamSky::new(Vector3::new(0.3, 0.4, -1.0), 2.5);
+//!
+//! pipeline.draw_sky(&sky, &view_projection);
+//! ```
+//!
+//! The y axis points towards the zenith. Directions below the horizon repeat the color at the horizon.
+
+use nalgebra::Vector3;
+
+use super::skybox::Sky;
+
+/// Perez distribution coefficients `A` to `E` of luminance and the x and y chromaticities, as `(slope, offset)` in turbidity
+const COEFFICIENTS: [[(f32, f32); 5]; 3] = [
+    [(0.1787, -1.4630), (-0.3554, 0.4275), (-0.0227, 5.3251), (0.1206, -2.5771), (-0.0670, 0.3703)],
+    [(-0.0193, -0.2592), (-0.0665, 0.0008), (-0.0004, 0.2125), (-0.0641, -0.8989), (-0.0033, 0.0452)],
+    [(-0.0167, -0.2608), (-0.0950, 0.0092), (-0.0079, 0.2102), (-0.0441, -1.6537), (-0.0109, 0.0529)],
+];
+
+/// Clear sky lit by the sun, in linear RGB
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct PreethamSky {
+    sun_direction: Vector3<f32>,
+    turbidity: f32,
+    exposure: f32,
+    /// Perez coefficients for luminance and the x and y chromaticities
+    perez: [[f32; 5]; 3],
+    /// Zenith values divided by the Perez function at the zenith, for luminance and the x and y chromaticities
+    zenith: [f32; 3],
+}
+
+impl PreethamSky {
+    /// Creates a sky with the sun in the given direction, and `turbidity` describing the haze in the air,
+    /// from `2.0` for a very clear sky to `10.0` for a hazy one.
+    pub fn new(sun_direction: Vector3<f32>, turbidity: f32) -> PreethamSky {
+        let mut sky = PreethamSky {
+            sun_direction: Vector3::y(),
+            turbidity: 2.0,
+            exposure: 1.0,
+            perez: [[0.0; 5]; 3],
+            zenith: [0.0; 3],
+        };
+
+        sky.set_sun(sun_direction, turbidity);
+        sky
+    }
+
+    /// Moves the sun and changes the turbidity, which must be between `1.7` and `10.0` for the model to hold
+    pub fn set_sun(&mut self, sun_direction: Vector3<f32>, turbidity: f32) {
+        let t = turbidity.max(1.7).min(10.0);
+
+        let sun = sun_direction.normalize();
+
+        // Keep the sun at or above the horizon, where the model holds
+        let theta_s = sun.y.max(0.0).min(1.0).acos();
+
+        for (perez, coefficients) in self.perez.iter_mut().zip(COEFFICIENTS.iter()) {
+            for (value, &(slope, offset)) in perez.iter_mut().zip(coefficients.iter()) {
+                *value = slope * t + offset;
+            }
+        }
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (::std::f32::consts::PI - 2.0 * theta_s);
+
+        // Zenith luminance in kcd/m², and chromaticities
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+
+        let (t2, th, th2, th3) = (t * t, theta_s, theta_s * theta_s, theta_s * theta_s * theta_s);
+
+        let x = t2 * (0.00166 * th3 - 0.00375 * th2 + 0.00209 * th) +
+            t * (-0.02903 * th3 + 0.06377 * th2 - 0.03202 * th + 0.00394) +
+            (0.11693 * th3 - 0.21196 * th2 + 0.06052 * th + 0.25886);
+
+        let y = t2 * (0.00275 * th3 - 0.00610 * th2 + 0.00317 * th) +
+            t * (-0.04214 * th3 + 0.08970 * th2 - 0.04153 * th + 0.00516) +
+            (0.15346 * th3 - 0.26756 * th2 + 0.06670 * th + 0.26688);
+
+        for (i, &value) in [luminance, x, y].iter().enumerate() {
+            self.zenith[i] = value / perez(&self.perez[i], 1.0, theta_s.cos());
+        }
+
+        self.sun_direction = sun;
+        self.turbidity = t;
+    }
+
+    /// Normalized direction towards the sun
+    #[inline]
+    pub fn sun_direction(&self) -> Vector3<f32> { self.sun_direction }
+
+    #[inline]
+    pub fn turbidity(&self) -> f32 { self.turbidity }
+
+    /// Scale applied to the luminance, which is in kcd/m² before scaling
+    #[inline]
+    pub fn exposure(&self) -> f32 { self.exposure }
+
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure;
+    }
+
+    pub fn with_exposure(mut self, exposure: f32) -> Self {
+        self.set_exposure(exposure);
+        self
+    }
+
+    /// Linear RGB color of the sky in a direction, which does not need to be normalized
+    pub fn radiance(&self, direction: Vector3<f32>) -> [f32; 3] {
+        let flattened = Vector3::new(direction.x, direction.y.max(0.0), direction.z);
+
+        let direction = if flattened.norm_squared() > 0.0 { flattened.normalize() } else { Vector3::x() };
+
+        // The Perez function diverges at the horizon
+        let cos_theta = direction.y.max(0.01);
+        let cos_gamma = direction.dot(&self.sun_direction).max(-1.0).min(1.0);
+
+        let value = |i: usize| self.zenith[i] * perez(&self.perez[i], cos_theta, cos_gamma);
+
+        let (luminance, x, y) = (value(0) * self.exposure, value(1), value(2));
+
+        if y <= 0.0 {
+            return [0.0; 3];
+        }
+
+        // xyY to XYZ to linear sRGB
+        let big_x = x / y * luminance;
+        let big_z = (1.0 - x - y) / y * luminance;
+
+        [
+            (3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z).max(0.0),
+            (-0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z).max(0.0),
+            (0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z).max(0.0),
+        ]
+    }
+}
+
+/// Perez sky distribution function, given the cosines of the angles from the zenith and from the sun
+#[inline]
+fn perez(coefficients: &[f32; 5], cos_theta: f32, cos_gamma: f32) -> f32 {
+    let (a, b, c, d, e) = (coefficients[0], coefficients[1], coefficients[2], coefficients[3], coefficients[4]);
+
+    let gamma = cos_gamma.acos();
+
+    (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+impl Sky for PreethamSky {
+    #[inline]
+    fn sample(&self, direction: Vector3<f32>) -> [f32; 4] {
+        let rgb = self.radiance(direction);
+
+        [rgb[0], rgb[1], rgb[2], 1.0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_preetham_sky() {
+        let sky = PreethamSky::new(Vector3::new(1.0, 1.0, 0.0), 2.5).with_exposure(0.1);
+
+        let luminance = |direction: Vector3<f32>| {
+            let rgb = sky.radiance(direction);
+
+            0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2]
+        };
+
+        let zenith = sky.radiance(Vector3::y());
+
+        // Zenith luminance from the model, scaled by the exposure
+        assert!(luminance(Vector3::y()) > 0.0);
+        assert!(zenith.iter().all(|c| c.is_finite()));
+
+        // A clear sky is blue overhead
+        assert!(zenith[2] > zenith[0]);
+
+        // Brighter around the sun than away from it
+        assert!(luminance(Vector3::new(1.0, 0.9, 0.0)) > luminance(Vector3::new(-1.0, 0.9, 0.0)));
+
+        // Below the horizon repeats the horizon
+        assert_eq!(sky.radiance(Vector3::new(0.0, -1.0, 1.0)), sky.radiance(Vector3::new(0.0, -0.5, 1.0)));
+    }
+}
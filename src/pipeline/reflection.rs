@@ -0,0 +1,222 @@
+//! Planar reflections
+//!
+//! Flat mirrors and calm water reflect the scene as seen by a camera mirrored about their plane.
+//! A `PlanarReflection` renders the scene with that mirrored camera into its own framebuffer, which is then
+//! sampled by the fragment shader of the reflective surface with a `ReflectionSampler`:
+//!
+//! ```ignore
+//! let reflection = PlanarReflection::new(Point3::origin(), Vector3::y());
+//!
+//! reflection_pipeline.framebuffer_mut().clear(sky);
+//! reflection_pipeline.render_reflection::<_, _, _, _, AlphaOver, _, _>(&reflection, &view, &projection, Triangle, scene.clone(),
+//!     |v, view_projection, _| ClipVertex::new(view_projection * v.position.to_homogeneous(), ()),
+//!     |_, _| Fragment::Color(shade()));
+//!
+//! let sampler = ReflectionSampler::new(reflection_pipeline.framebuffer(), Filter::Bilinear);
+//!
+//! // In the fragment shader of the mirror, given its clip-space position from the main camera
+//! let reflected = sampler.sample(v.uniforms.clip_position);
+//! ```
+//!
+//! Anything behind the mirror would show up in the reflection, so the near plane of the mirrored projection
+//! is replaced by the plane of the mirror, using Eric Lengyel's oblique near-plane clipping.
+//! That clips the geometry behind the mirror away for free, at the cost of some depth precision.
+//!
+//! Mirroring reverses the winding of every triangle, so the face culling of the current render state
+//! is reversed while rendering the reflection.
+
+use std::sync::Arc;
+
+use alga::general::Real;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4};
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::color::ToChannels;
+use ::color::blend::Blend;
+use ::pixels::PixelRead;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::primitive::Primitive;
+use ::geometry::{ClipVertex, ScreenVertex};
+use ::interpolate::Interpolate;
+use ::texture::{Filter, sample_channels};
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::{PipelineUniforms, Pixel};
+
+/// A reflective plane
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlanarReflection<N: FloatScalar> {
+    /// Plane equation `ax + by + cz + d = 0`, with the unit normal pointing towards the reflected side
+    plane: Vector4<N>,
+}
+
+impl<N> PlanarReflection<N> where N: Real + FloatScalar {
+    /// Creates a reflection about the plane through `point`, reflecting everything on the side `normal` points to
+    pub fn new(point: Point3<N>, normal: Vector3<N>) -> PlanarReflection<N> {
+        let normal = normal.normalize();
+
+        PlanarReflection {
+            plane: Vector4::new(normal.x, normal.y, normal.z, -normal.dot(&point.coords)),
+        }
+    }
+
+    /// Plane equation of the mirror in world-space
+    #[inline]
+    pub fn plane(&self) -> Vector4<N> { self.plane }
+
+    /// Mirrors world-space positions about the plane
+    pub fn mirror_matrix(&self) -> Matrix4<N> {
+        let (a, b, c, d) = (self.plane.x, self.plane.y, self.plane.z, self.plane.w);
+
+        let one = N::one();
+        let two = one + one;
+
+        Matrix4::new(
+            one - two * a * a, -two * a * b, -two * a * c, -two * a * d,
+            -two * a * b, one - two * b * b, -two * b * c, -two * b * d,
+            -two * a * c, -two * b * c, one - two * c * c, -two * c * d,
+            N::zero(), N::zero(), N::zero(), one,
+        )
+    }
+
+    /// View matrix of the mirrored camera, given the view matrix of the main camera
+    pub fn view_matrix(&self, view: &Matrix4<N>) -> Matrix4<N> {
+        view * self.mirror_matrix()
+    }
+
+    /// Replaces the near plane of `projection` with the mirror plane, as seen from `mirrored_view`
+    pub fn oblique_projection(&self, mirrored_view: &Matrix4<N>, projection: &Matrix4<N>) -> Matrix4<N> {
+        let (inverse_view, inverse_projection) = match (mirrored_view.try_inverse(), projection.try_inverse()) {
+            (Some(inverse_view), Some(inverse_projection)) => (inverse_view, inverse_projection),
+            _ => return *projection,
+        };
+
+        // Planes transform with the inverse transpose
+        let clip_plane = inverse_view.transpose() * self.plane;
+
+        let sign = |x: N| if x > N::zero() { N::one() } else if x < N::zero() { -N::one() } else { N::zero() };
+
+        // Corner of the view frustum opposite the clip plane
+        let corner = inverse_projection * Vector4::new(sign(clip_plane.x), sign(clip_plane.y), N::one(), N::one());
+
+        let scaled = clip_plane * (cast::<f64, N>(2.0).unwrap() / clip_plane.dot(&corner));
+
+        let mut oblique = *projection;
+
+        for i in 0..4 {
+            oblique[(2, i)] = scaled[i] - projection[(3, i)];
+        }
+
+        oblique
+    }
+
+    /// View-projection matrix of the mirrored camera, with the mirror as the near plane
+    pub fn view_projection(&self, view: &Matrix4<N>, projection: &Matrix4<N>) -> Matrix4<N> {
+        let mirrored_view = self.view_matrix(view);
+
+        self.oblique_projection(&mirrored_view, projection) * mirrored_view
+    }
+}
+
+/// Samples a rendered reflection at the screen position of the reflective surface
+#[derive(Debug, Clone, Copy)]
+pub struct ReflectionSampler<'a, T: 'a> {
+    texture: &'a T,
+    filter: Filter,
+}
+
+impl<'a, T> ReflectionSampler<'a, T> where T: PixelRead, T::Color: ToChannels {
+    /// Creates a sampler for a reflection rendered with the same dimensions and projection as the main view
+    pub fn new(texture: &'a T, filter: Filter) -> ReflectionSampler<'a, T> {
+        ReflectionSampler { texture, filter }
+    }
+
+    /// Samples the reflection given the clip-space position of the reflective surface in the main view
+    pub fn sample<N: FloatScalar>(&self, clip_position: Vector4<N>) -> [f32; 4] {
+        self.sample_offset(clip_position, 0.0, 0.0)
+    }
+
+    /// Samples the reflection with an additional offset in pixels, for ripples and other distortions
+    pub fn sample_offset<N: FloatScalar>(&self, clip_position: Vector4<N>, dx: f32, dy: f32) -> [f32; 4] {
+        let dimensions = self.texture.dimensions();
+
+        let x: f32 = cast(clip_position.x / clip_position.w).unwrap_or(0.0);
+        let y: f32 = cast(clip_position.y / clip_position.w).unwrap_or(0.0);
+
+        // NDC spans two units across the screen, and the y-axis is flipped in screen-space
+        let sx = (x + 1.0) * 0.5 * dimensions.width as f32 + dx;
+        let sy = (1.0 - y) * 0.5 * dimensions.height as f32 + dy;
+
+        sample_channels(self.texture, sx, sy, self.filter)
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Renders a mesh into the framebuffer as seen in the mirror of `reflection`,
+    /// from the main camera described by `view` and `projection`.
+    ///
+    /// The vertex shader is given the view-projection matrix of the mirrored camera to transform vertices with.
+    pub fn render_reflection<T, V, I, K, B, VS, FS>(&mut self, reflection: &PlanarReflection<V::Scalar>,
+                                                    view: &Matrix4<V::Scalar>, projection: &Matrix4<V::Scalar>,
+                                                    primitive: T, mesh: Arc<Mesh<V, I>>,
+                                                    vertex_shader: VS, fragment_shader: FS)
+        where T: Primitive,
+              V: Vertex,
+              V::Scalar: Real,
+              I: MeshIndex,
+              K: Send + Sync + Clone + Interpolate,
+              B: Blend<Pixel<Self>> + Default,
+              VS: Fn(&V, &Matrix4<V::Scalar>, &PipelineUniforms<Self>) -> ClipVertex<V::Scalar, K> + Send + Sync,
+              FS: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<Self>) -> Fragment<Pixel<Self>> + Send + Sync {
+        let view_projection = reflection.view_projection(view, projection);
+
+        let cull_faces = self.render_state().desc.cull_faces.map(|winding| winding.reversed());
+
+        self.render_mesh(primitive, mesh, None)
+            .run(|vertex, uniforms| vertex_shader(vertex, &view_projection, uniforms))
+            .clip_primitives()
+            .finish_default()
+            .with_faces_culled(cull_faces)
+            .with_default_blend::<B>()
+            .run(fragment_shader);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Isometry3, Perspective3};
+
+    use super::*;
+
+    #[test]
+    fn test_mirror_matrix() {
+        let reflection = PlanarReflection::new(Point3::new(0.0f32, 1.0, 0.0), Vector3::y());
+
+        let mirrored = Point3::from_homogeneous(reflection.mirror_matrix() * Vector4::new(2.0, 3.0, -1.0, 1.0)).unwrap();
+
+        assert!((mirrored - Point3::new(2.0, -1.0, -1.0)).norm() < 1e-6);
+    }
+
+    #[test]
+    fn test_oblique_near_plane() {
+        let reflection = PlanarReflection::new(Point3::origin(), Vector3::y());
+
+        let view = Isometry3::look_at_rh(&Point3::new(0.0f32, 2.0, 5.0), &Point3::origin(), &Vector3::y()).to_homogeneous();
+        let projection = Perspective3::new(1.0f32, 1.0, 0.1, 100.0).to_homogeneous();
+
+        let view_projection = reflection.view_projection(&view, &projection);
+
+        // Points on the mirror plane land on the near plane, and points above it are kept
+        let on_plane = view_projection * Vector4::new(0.5, 0.0, -1.0, 1.0);
+        let above = view_projection * Vector4::new(0.0, 1.0, 0.0, 1.0);
+        let below = view_projection * Vector4::new(0.0, -1.0, 0.0, 1.0);
+
+        assert!((on_plane.z / on_plane.w + 1.0).abs() < 1e-3);
+        assert!(above.z > -above.w);
+        assert!(below.z < -below.w);
+    }
+}
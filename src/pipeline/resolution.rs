@@ -0,0 +1,211 @@
+//! Dynamic resolution scaling
+//!
+//! Keeps interactive applications at their target framerate by rendering to a smaller part of the framebuffer
+//! when frames take too long, and scaling the result up to the presentation buffer afterwards.
+//! The framebuffer keeps its full size, so nothing is reallocated when the resolution changes:
+//!
+//! ```ignore
+//! let mut resolution = DynamicResolution::new(Duration::from_millis(16));
+//!
+//! loop {
+//!     let start = Instant::now();
+//!
+//!     pipeline.apply_resolution(&resolution);
+//!     draw_scene(&mut pipeline);
+//!     pipeline.present_scaled(&resolution, &mut window_buffer, UpscaleFilter::Fsr { sharpness: 0.5 });
+//!
+//!     resolution.update(start.elapsed());
+//! }
+//! ```
+//!
+//! `apply_resolution` sets the viewport and scissor of the render state, so draws that set their own
+//! viewport or scissor, and passes that reconstruct rays from the full framebuffer size, need to use
+//! `DynamicResolution::render_dimensions` themselves.
+
+use std::time::Duration;
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Coordinate, Dimensions, HasDimensions, Viewport};
+use ::pixels::{PixelBuffer, PixelWrite};
+use ::post::upscale::{UpscaleFilter, upscale};
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::types::Pixel;
+
+/// Chooses the fraction of the framebuffer to render to from recent frame times
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct DynamicResolution {
+    target: Duration,
+    min_scale: f32,
+    max_scale: f32,
+    smoothing: f32,
+    step: f32,
+    scale: f32,
+    /// Exponential moving average of frame times in seconds
+    average: Option<f32>,
+}
+
+#[inline]
+fn seconds(duration: Duration) -> f32 {
+    duration.as_secs() as f32 + duration.subsec_nanos() as f32 * 1e-9
+}
+
+impl DynamicResolution {
+    /// Creates a controller aiming for frames taking `target`, starting at full resolution
+    pub fn new(target: Duration) -> DynamicResolution {
+        DynamicResolution {
+            target,
+            min_scale: 0.5,
+            max_scale: 1.0,
+            smoothing: 0.25,
+            step: 0.05,
+            scale: 1.0,
+            average: None,
+        }
+    }
+
+    #[inline]
+    pub fn target(&self) -> Duration { self.target }
+
+    pub fn set_target(&mut self, target: Duration) {
+        self.target = target;
+    }
+
+    pub fn with_target(mut self, target: Duration) -> Self {
+        self.set_target(target);
+        self
+    }
+
+    /// Smallest and largest scale of each axis, defaulting to `0.5` and `1.0`
+    #[inline]
+    pub fn scale_range(&self) -> (f32, f32) { (self.min_scale, self.max_scale) }
+
+    pub fn set_scale_range(&mut self, min_scale: f32, max_scale: f32) {
+        self.max_scale = max_scale.max(1e-3);
+        self.min_scale = min_scale.max(1e-3).min(self.max_scale);
+        self.scale = self.scale.max(self.min_scale).min(self.max_scale);
+    }
+
+    pub fn with_scale_range(mut self, min_scale: f32, max_scale: f32) -> Self {
+        self.set_scale_range(min_scale, max_scale);
+        self
+    }
+
+    /// Weight of the newest frame time in the running average, from `0.0` to `1.0`, defaulting to `0.25`.
+    ///
+    /// Lower values react slower but ignore single slow frames.
+    #[inline]
+    pub fn smoothing(&self) -> f32 { self.smoothing }
+
+    pub fn set_smoothing(&mut self, smoothing: f32) {
+        self.smoothing = smoothing.max(0.0).min(1.0);
+    }
+
+    pub fn with_smoothing(mut self, smoothing: f32) -> Self {
+        self.set_smoothing(smoothing);
+        self
+    }
+
+    /// Smallest change of the scale that is applied, defaulting to `0.05`,
+    /// so the resolution doesn't change every frame from small variations in frame time
+    #[inline]
+    pub fn step(&self) -> f32 { self.step }
+
+    pub fn set_step(&mut self, step: f32) {
+        self.step = step.max(0.0);
+    }
+
+    pub fn with_step(mut self, step: f32) -> Self {
+        self.set_step(step);
+        self
+    }
+
+    /// Current scale of each axis
+    #[inline]
+    pub fn scale(&self) -> f32 { self.scale }
+
+    /// Records the time the last frame took, and returns the scale for the next frame.
+    pub fn update(&mut self, frame_time: Duration) -> f32 {
+        let time = seconds(frame_time);
+
+        let average = match self.average {
+            Some(average) => average + (time - average) * self.smoothing,
+            None => time,
+        };
+
+        self.average = Some(average);
+
+        if average > 0.0 {
+            // Frame time is roughly proportional to the number of pixels, so the square of the scale
+            let ideal = self.scale * (seconds(self.target) / average).sqrt();
+            let ideal = ideal.max(self.min_scale).min(self.max_scale);
+
+            // Always allow reaching the limits, even when they are closer than a step away
+            if (ideal - self.scale).abs() >= self.step || ideal == self.min_scale || ideal == self.max_scale {
+                self.scale = ideal;
+            }
+        }
+
+        self.scale
+    }
+
+    /// Size of the part of a framebuffer of size `full` rendered to at the current scale, at least one pixel
+    pub fn render_dimensions(&self, full: Dimensions) -> Dimensions {
+        let scaled = |size: u32| ((size as f32 * self.scale).round() as u32).max(1).min(size);
+
+        Dimensions::new(scaled(full.width), scaled(full.height))
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Restricts the viewport and scissor of the render state to the top-left part of the framebuffer
+    /// given by the current scale of `resolution`
+    pub fn apply_resolution(&mut self, resolution: &DynamicResolution) {
+        let dimensions = resolution.render_dimensions(self.framebuffer().dimensions());
+
+        let state = self.render_state_mut();
+
+        state.viewport = Some(Viewport::new(dimensions, Coordinate::default(), 0.0, 1.0));
+        state.scissor = Some((Coordinate::new(0, 0), Coordinate::new(dimensions.width - 1, dimensions.height - 1)));
+    }
+
+    /// Scales the part of the framebuffer rendered at the current scale of `resolution` up to the size of `out`
+    pub fn present_scaled<O>(&self, resolution: &DynamicResolution, out: &mut O, filter: UpscaleFilter)
+        where O: PixelWrite, O::Color: FromChannels, Pixel<Self>: ToChannels {
+        let framebuffer = self.framebuffer();
+
+        let dimensions = resolution.render_dimensions(framebuffer.dimensions());
+
+        let end = Coordinate::new(dimensions.width, dimensions.height);
+
+        if let Ok(rendered) = framebuffer.partial_ref(Coordinate::new(0, 0), end) {
+            upscale(&rendered, out, filter);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_resolution() {
+        let mut resolution = DynamicResolution::new(Duration::from_millis(10)).with_smoothing(1.0);
+
+        // Twice the target time needs half the pixels
+        let scale = resolution.update(Duration::from_millis(20));
+
+        assert!((scale - 0.5f32.sqrt()).abs() < 1e-4);
+        assert_eq!(resolution.render_dimensions(Dimensions::new(100, 50)), Dimensions::new(71, 35));
+
+        // Small changes are ignored
+        assert_eq!(resolution.update(Duration::from_millis(10)), scale);
+
+        // Far too slow clamps to the minimum scale
+        assert_eq!(resolution.update(Duration::from_millis(100)), 0.5);
+
+        // Fast frames go back up to full resolution
+        assert_eq!(resolution.update(Duration::from_millis(1)), 1.0);
+        assert_eq!(resolution.render_dimensions(Dimensions::new(100, 50)), Dimensions::new(100, 50));
+    }
+}
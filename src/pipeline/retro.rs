@@ -0,0 +1,91 @@
+//! Interpolation modes and retro rendering
+//!
+//! Uniforms are interpolated across triangles linearly in screen-space by default, as they always have been.
+//! `Interpolation::Perspective` corrects for perspective instead, so textures stay straight on surfaces
+//! receding into the distance, and can be enabled for a draw with `FragmentShader::with_interpolation`.
+//!
+//! Early 3D consoles like the PlayStation had no depth information per pixel, so they interpolated texture
+//! coordinates linearly in screen-space too, which makes textures swim and warp as the camera moves.
+//! Combined with vertices snapped to whole pixels and unfiltered textures, that gives the characteristic
+//! wobbly look of the era.
+//!
+//! A `Retro` style enables all of these for a draw with `FragmentShader::with_retro`:
+//!
+//! ```ignore
+//! let retro = Retro::ps1();
+//!
+//! geometry_shader.finish_default()
+//!     .with_retro(retro)
+//!     .run(|v, u| Fragment::Color(texture(&u.texture, v.uniforms.uv, retro.filter, Edge::Wrap).unwrap()));
+//! ```
+//!
+//! Affine interpolation is also slightly cheaper, as it skips a division for every pixel.
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::texture::Filter;
+
+/// How uniforms are interpolated across triangles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Interpolation {
+    /// Perspective-correct interpolation
+    Perspective,
+    /// Linear interpolation in screen-space, ignoring perspective, which is the default
+    Affine,
+}
+
+impl Default for Interpolation {
+    fn default() -> Interpolation { Interpolation::Affine }
+}
+
+/// Settings for retro-styled rendering
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct Retro {
+    /// Interpolation of uniforms across triangles
+    pub interpolation: Interpolation,
+    /// Number of subpixel positions vertices are snapped to in each direction, or `None` to not snap vertices.
+    ///
+    /// `Some(1)` snaps vertices to whole pixels.
+    pub vertex_snap: Option<u32>,
+    /// Filter to sample textures with in the fragment shader
+    pub filter: Filter,
+}
+
+impl Retro {
+    /// Affine texture mapping, vertices snapped to whole pixels and nearest sampling, like the original PlayStation
+    pub fn ps1() -> Retro {
+        Retro {
+            interpolation: Interpolation::Affine,
+            vertex_snap: Some(1),
+            filter: Filter::Nearest,
+        }
+    }
+}
+
+impl Default for Retro {
+    fn default() -> Retro { Retro::ps1() }
+}
+
+/// Snaps a screen-space coordinate to the nearest of `subpixels` positions per pixel
+#[inline]
+pub fn snap_to_grid<N: FloatScalar>(x: N, subpixels: u32) -> N {
+    let subpixels: N = cast(subpixels.max(1)).unwrap();
+
+    (x * subpixels).round() / subpixels
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_snap_to_grid() {
+        assert_eq!(snap_to_grid(10.4f32, 1), 10.0);
+        assert_eq!(snap_to_grid(10.6f32, 1), 11.0);
+        assert_eq!(snap_to_grid(10.3f32, 4), 10.25);
+        assert_eq!(snap_to_grid(10.3f32, 0), 10.0);
+    }
+}
@@ -0,0 +1,142 @@
+//! Sky backgrounds
+//!
+//! The sky is drawn as a single full-screen triangle pinned just inside the far plane,
+//! with the view direction of every pixel reconstructed from the inverse view-projection matrix,
+//! so it needs no cube geometry and fills whatever the scene left uncovered, whether drawn before or after it:
+//!
+//! ```ignore
+//! let sky = CubemapSky::new(&cubemap, CubeSampler::seamless(Filter::Bilinear));
+//!
+//! pipeline.draw_sky(&sky, &view_projection);
+//! ```
+//!
+//! Anything implementing `Sky` can be drawn, including closures from directions to colors for simple gradients,
+//! and the procedural `PreethamSky` with the `procedural_sky` feature.
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4};
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Coordinate, ClipVertex, HasDimensions, Viewport};
+use ::pixels::PixelRead;
+use ::mesh::{Mesh, SimpleVertex};
+use ::primitive::Triangle;
+use ::raytrace::Ray;
+use ::texture::cubemap::{Cubemap, CubeSampler};
+use ::attachments::depth::DepthTest;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::Pixel;
+
+/// How far in front of the far plane the sky is drawn, so it isn't clipped away by it
+const FAR_PLANE_OFFSET: f32 = 1e-5;
+
+/// Color of the sky in every direction
+pub trait Sky: Sync {
+    /// Color of the sky looking in a world-space direction, which does not need to be normalized
+    fn sample(&self, direction: Vector3<f32>) -> [f32; 4];
+}
+
+impl<F> Sky for F where F: Fn(Vector3<f32>) -> [f32; 4] + Sync {
+    #[inline]
+    fn sample(&self, direction: Vector3<f32>) -> [f32; 4] {
+        self(direction)
+    }
+}
+
+/// Sky from the faces of a cubemap, such as one loaded from six images or rendered from each `CubeFace`
+#[derive(Debug, Clone, Copy)]
+pub struct CubemapSky<'a, T: 'a> {
+    cubemap: &'a Cubemap<T>,
+    sampler: CubeSampler,
+}
+
+impl<'a, T> CubemapSky<'a, T> where T: PixelRead + Sync, T::Color: ToChannels {
+    pub fn new(cubemap: &'a Cubemap<T>, sampler: CubeSampler) -> CubemapSky<'a, T> {
+        CubemapSky { cubemap, sampler }
+    }
+}
+
+impl<'a, T> Sky for CubemapSky<'a, T> where T: PixelRead + Sync, T::Color: ToChannels {
+    #[inline]
+    fn sample(&self, direction: Vector3<f32>) -> [f32; 4] {
+        self.cubemap.sample_with(direction, &self.sampler)
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Fills the background of the framebuffer with the sky, as seen through `view_projection`.
+    ///
+    /// The sky is drawn at the far plane with a `GreaterThanEq` depth test, regardless of the current depth test,
+    /// so it only covers pixels no geometry has been drawn to. Drawing it after opaque geometry
+    /// avoids shading the sky for pixels that end up covered.
+    pub fn draw_sky<K>(&mut self, sky: &K, view_projection: &Matrix4<f32>) where K: Sky, Pixel<Self>: FromChannels {
+        let inverse_view_projection = match view_projection.try_inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+
+        let viewport = Viewport::new(self.framebuffer().dimensions(), Coordinate::default(), 0.0, 1.0);
+
+        // Triangle covering all of normalized device coordinates, given directly in clip-space
+        let vertex = |x: f32, y: f32| SimpleVertex { position: Point3::new(x, y, 0.0), data: () };
+
+        let triangle: Mesh<SimpleVertex<f32, ()>> = Mesh {
+            indices: vec![0, 1, 2],
+            vertices: vec![vertex(-1.0, -1.0), vertex(3.0, -1.0), vertex(-1.0, 3.0)],
+        };
+
+        self.render_mesh(Triangle, Arc::new(triangle), None)
+            .run(|vertex, _| {
+                let p = vertex.position;
+
+                ClipVertex::new(Vector4::new(p.x, p.y, 1.0 - FAR_PLANE_OFFSET, 1.0), ())
+            })
+            .clip_primitives()
+            .finish_default()
+            .with_faces_culled(None)
+            // Stored depth increases towards the camera, so this passes wherever only the cleared depth remains
+            .with_depth_test(DepthTest::GreaterThanEq)
+            .run(|v, _| {
+                let ray = Ray::from_screen(&inverse_view_projection, &viewport, v.position.x, v.position.y);
+
+                Fragment::Color(<Pixel<Self> as FromChannels>::from_channels(sky.sample(ray.direction)))
+            });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+    use ::texture::Filter;
+    use ::texture::cubemap::CubeFace;
+    use ::color::predefined::formats::RGBAf32Color;
+
+    use super::*;
+
+    #[test]
+    fn test_cubemap_sky() {
+        let cubemap = Cubemap::from_fn(|face| {
+            let v = face.index() as f32 / 5.0;
+
+            ColorBuffer::from_fn(Dimensions::new(4, 4), |_| RGBAf32Color::new(v, v, v, 1.0))
+        });
+
+        let sky = CubemapSky::new(&cubemap, CubeSampler::seamless(Filter::Bilinear));
+
+        let up = sky.sample(Vector3::new(0.1, 2.0, -0.1));
+
+        assert!((up[0] - CubeFace::PositiveY.index() as f32 / 5.0).abs() < 1e-6);
+
+        let gradient = |direction: Vector3<f32>| {
+            let t = direction.normalize().y.max(0.0);
+
+            [t, t, 1.0, 1.0]
+        };
+
+        assert_eq!(Sky::sample(&gradient, Vector3::y()), [1.0, 1.0, 1.0, 1.0]);
+    }
+}
@@ -0,0 +1,119 @@
+//! Hot-reloadable shader slots
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use parking_lot::RwLock;
+
+/// A shared, swappable shader.
+///
+/// The pipeline loads the current shader from the slot once at the start of each draw,
+/// so another thread can replace it at any time, for example after recompiling a shader library,
+/// without rebuilding any pipeline state. Draws already in progress keep using the shader they started with.
+///
+/// Slots are usually used with trait objects, so shaders of different closure types can be swapped in:
+///
+/// ```ignore
+/// type Shader = Fn(&ScreenVertex<f32, Uniforms>, &GlobalUniforms) -> Fragment<RGBAf32Color> + Send + Sync;
+///
+/// let slot: ShaderSlot<Shader> = ShaderSlot::new(Arc::new(|_, _| Fragment::Discard));
+///
+/// let reloader = slot.clone();
+///
+/// thread::spawn(move || {
+///     reloader.store(Arc::new(|_, _| Fragment::Color(RGBAf32Color::white())));
+/// });
+///
+/// fragment_shader.run_slot(&slot);
+/// ```
+pub struct ShaderSlot<T: ?Sized> {
+    inner: Arc<SlotInner<T>>,
+}
+
+struct SlotInner<T: ?Sized> {
+    shader: RwLock<Arc<T>>,
+    generation: AtomicUsize,
+}
+
+impl<T: ?Sized> Clone for ShaderSlot<T> {
+    fn clone(&self) -> ShaderSlot<T> {
+        ShaderSlot { inner: self.inner.clone() }
+    }
+}
+
+impl<T: ?Sized> ShaderSlot<T> {
+    /// Create a new slot holding the given shader
+    pub fn new(shader: Arc<T>) -> ShaderSlot<T> {
+        ShaderSlot {
+            inner: Arc::new(SlotInner {
+                shader: RwLock::new(shader),
+                generation: AtomicUsize::new(0),
+            })
+        }
+    }
+
+    /// Returns the current shader
+    #[inline]
+    pub fn load(&self) -> Arc<T> {
+        self.inner.shader.read().clone()
+    }
+
+    /// Replaces the shader for all future draws, returning the previous one
+    pub fn store(&self, shader: Arc<T>) -> Arc<T> {
+        let previous = ::std::mem::replace(&mut *self.inner.shader.write(), shader);
+
+        self.inner.generation.fetch_add(1, Ordering::Release);
+
+        previous
+    }
+
+    /// Returns the number of times the shader has been replaced.
+    ///
+    /// Useful for invalidating anything cached from a previous shader.
+    #[inline]
+    pub fn generation(&self) -> usize {
+        self.inner.generation.load(Ordering::Acquire)
+    }
+
+    /// Returns true if both slots share the same shader storage
+    #[inline]
+    pub fn ptr_eq(&self, other: &ShaderSlot<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::thread;
+
+    type Shader = Fn(u32) -> u32 + Send + Sync;
+
+    #[test]
+    fn test_swap_from_another_thread() {
+        let slot: ShaderSlot<Shader> = ShaderSlot::new(Arc::new(|x| x + 1));
+
+        // Stands in for a draw that started before the swap
+        let in_progress = slot.load();
+
+        assert_eq!(slot.generation(), 0);
+
+        let reloader = slot.clone();
+
+        assert!(reloader.ptr_eq(&slot));
+
+        let previous = thread::spawn(move || {
+            let previous = reloader.store(Arc::new(|x| x * 2));
+
+            previous(10)
+        }).join().unwrap();
+
+        assert_eq!(previous, 11);
+
+        assert_eq!(slot.load()(10), 20);
+        assert_eq!(slot.generation(), 1);
+
+        assert_eq!(in_progress(10), 11);
+    }
+}
@@ -0,0 +1,182 @@
+//! Primitive sorting by depth
+//!
+//! Triangles and points can be sorted by the view-space depth of their centroid right before rasterization,
+//! across both the mesh and any primitives generated by a geometry shader.
+//!
+//! Alpha blending is order-dependent, so transparent geometry only composites correctly when drawn
+//! from the farthest primitive to the nearest, with `SortMode::BackToFront`.
+//!
+//! Opaque geometry is the opposite. The depth test runs before the fragment shader, so drawing from
+//! the nearest primitive to the farthest with `SortMode::FrontToBack` lets hidden fragments fail the test
+//! without ever being shaded. This reduces overdraw for scenes with expensive fragment shaders and lots of
+//! occlusion, at the cost of sorting every draw. The `overdraw` example compares both orders.
+//!
+//! The view-space depth is taken from the `w` component of the clip-space position, like fog.
+//! Orthographic projections have a constant `w`, so primitives are sorted by their screen-space depth instead.
+//!
+//! Sorting is per primitive, so intersecting or overlapping primitives with the same centroid depth
+//! can still be drawn in the wrong order. Lines are never sorted.
+
+use std::cmp::Ordering;
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::geometry::ScreenVertex;
+use ::mesh::MeshIndex;
+use ::pipeline::storage::{SeparableScreenPrimitiveStorage, primitive_vertices};
+
+/// Order in which primitives of a draw are rasterized
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum SortMode {
+    /// Primitives are drawn in the order they are given, with mesh primitives before generated ones
+    None,
+    /// Triangles and points are drawn from the farthest to the nearest, for alpha blended geometry
+    BackToFront,
+    /// Triangles and points are drawn from the nearest to the farthest, for opaque geometry
+    FrontToBack,
+}
+
+impl Default for SortMode {
+    fn default() -> SortMode { SortMode::None }
+}
+
+/// A primitive of a draw, either from the mesh or generated by a geometry shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub ( in ::pipeline ) enum PrimitiveSource {
+    Indexed(usize),
+    Generated(usize),
+}
+
+/// Sorted triangles and points of a single draw
+#[derive(Debug, Default)]
+pub ( in ::pipeline ) struct DrawOrder {
+    pub tris: Vec<PrimitiveSource>,
+    pub points: Vec<PrimitiveSource>,
+}
+
+/// Sort key of a primitive, from the average view-space and screen-space depth of its vertices
+fn centroid_depth<N, K>(vertices: &[&ScreenVertex<N, K>]) -> (f64, f64) where N: FloatScalar {
+    let mut view_depth = 0.0;
+    let mut screen_depth = 0.0;
+
+    for vertex in vertices {
+        // Screen-space w holds the reciprocal of clip-space w
+        let w: f64 = cast(vertex.position.w).unwrap_or(0.0);
+        let z: f64 = cast(vertex.position.z).unwrap_or(0.0);
+
+        view_depth += 1.0 / w;
+        screen_depth += z;
+    }
+
+    let count = vertices.len() as f64;
+
+    (view_depth / count, screen_depth / count)
+}
+
+fn sort_by_depth(keyed: &mut Vec<((f64, f64), PrimitiveSource)>, mode: SortMode) {
+    // Farther primitives have a larger view depth and a smaller screen depth
+    keyed.sort_by(|&((a_view, a_screen), _), &((b_view, b_screen), _)| {
+        let back_to_front = b_view.partial_cmp(&a_view).unwrap_or(Ordering::Equal)
+            .then(a_screen.partial_cmp(&b_screen).unwrap_or(Ordering::Equal));
+
+        if mode == SortMode::FrontToBack { back_to_front.reverse() } else { back_to_front }
+    });
+}
+
+impl DrawOrder {
+    /// Sorts the triangles and points of a draw, or returns `None` if they are drawn in the given order
+    pub fn sort<N, K, I>(mode: SortMode,
+                         primitive_size: usize,
+                         indices: &[I],
+                         indexed_vertices: Option<&[ScreenVertex<N, K>]>,
+                         generated: &SeparableScreenPrimitiveStorage<N, K>) -> Option<DrawOrder> where N: FloatScalar, I: MeshIndex {
+        if mode == SortMode::None {
+            return None;
+        }
+
+        let mut tris = Vec::with_capacity(generated.num_triangles());
+        let mut points = Vec::with_capacity(generated.points.len());
+
+        if let Some(vertices) = indexed_vertices {
+            let target = match primitive_size {
+                3 => Some(&mut tris),
+                1 => Some(&mut points),
+                _ => None,
+            };
+
+            if let Some(target) = target {
+                for (j, primitive) in indices.chunks(primitive_size).enumerate() {
+                    let (primitive, count) = primitive_vertices(vertices, primitive);
+
+                    target.push((centroid_depth(&primitive[..count]), PrimitiveSource::Indexed(j)));
+                }
+            }
+        }
+
+        for j in 0..generated.num_triangles() {
+            tris.push((centroid_depth(&generated.triangle(j)), PrimitiveSource::Generated(j)));
+        }
+
+        for (j, p) in generated.points.iter().enumerate() {
+            points.push((centroid_depth(&[p]), PrimitiveSource::Generated(j)));
+        }
+
+        sort_by_depth(&mut tris, mode);
+        sort_by_depth(&mut points, mode);
+
+        Some(DrawOrder {
+            tris: tris.into_iter().map(|(_, source)| source).collect(),
+            points: points.into_iter().map(|(_, source)| source).collect(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use super::*;
+
+    fn vertex(z: f32, w: f32) -> ScreenVertex<f32, ()> {
+        ScreenVertex { position: Vector4::new(0.0, 0.0, z, w), uniforms: () }
+    }
+
+    #[test]
+    fn test_sort_points() {
+        let generated = SeparableScreenPrimitiveStorage {
+            points: vec![vertex(0.9, 1.0 / 2.0), vertex(0.1, 1.0 / 10.0)],
+            lines: Vec::new(),
+            tris: Vec::new(),
+            tri_indices: Vec::new(),
+        };
+
+        let indexed = [vertex(0.5, 1.0 / 5.0)];
+
+        let order = DrawOrder::sort(SortMode::BackToFront, 1, &[0usize], Some(&indexed), &generated).unwrap();
+
+        assert_eq!(order.points, vec![PrimitiveSource::Generated(1), PrimitiveSource::Indexed(0), PrimitiveSource::Generated(0)]);
+
+        let order = DrawOrder::sort(SortMode::FrontToBack, 1, &[0usize], Some(&indexed), &generated).unwrap();
+
+        assert_eq!(order.points, vec![PrimitiveSource::Generated(0), PrimitiveSource::Indexed(0), PrimitiveSource::Generated(1)]);
+
+        assert!(DrawOrder::sort(SortMode::None, 1, &[0usize], Some(&indexed), &generated).is_none());
+    }
+
+    #[test]
+    fn test_orthographic_fallback() {
+        let generated = SeparableScreenPrimitiveStorage {
+            points: Vec::new(),
+            lines: Vec::new(),
+            tris: vec![vertex(0.8, 1.0), vertex(0.8, 1.0), vertex(0.8, 1.0),
+                       vertex(0.2, 1.0), vertex(0.2, 1.0), vertex(0.2, 1.0)],
+            tri_indices: (0..6).collect(),
+        };
+
+        let order = DrawOrder::sort::<f32, (), usize>(SortMode::BackToFront, 3, &[], None, &generated).unwrap();
+
+        assert_eq!(order.tris, vec![PrimitiveSource::Generated(1), PrimitiveSource::Generated(0)]);
+    }
+}
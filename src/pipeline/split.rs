@@ -0,0 +1,162 @@
+//! Split-screen rendering
+//!
+//! A `SplitScreen` partitions a single framebuffer into independent rectangular regions,
+//! each with its own viewport and scissor rectangle, so several cameras can share one framebuffer.
+
+use std::sync::Arc;
+
+use ::numeric::FloatScalar;
+use ::numeric::utils::min;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::primitive::Primitive;
+use ::color::blend::Blend;
+use ::geometry::{Dimensions, Coordinate, ClipVertex, ScreenVertex, Viewport};
+use ::interpolate::Interpolate;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::stages::fragment::{Fragment, TileLimit};
+use ::pipeline::stages::rasterization::Tile;
+
+use ::pipeline::types::{PipelineUniforms, Pixel};
+
+/// A single region of a split framebuffer
+#[derive(Debug, Clone, Copy)]
+pub struct Partition<N> where N: FloatScalar {
+    /// Index of the partition within its `SplitScreen`
+    pub index: usize,
+    /// Viewport mapping clip-space into the partition
+    pub viewport: Viewport<N>,
+    /// Inclusive framebuffer rectangle covered by the partition
+    pub scissor: Tile,
+}
+
+impl<N> Partition<N> where N: FloatScalar {
+    /// Creates a partition covering the given region of the framebuffer
+    pub fn new(index: usize, offset: Coordinate, dimensions: Dimensions, near: N, far: N) -> Partition<N> {
+        Partition {
+            index,
+            viewport: Viewport::new(dimensions, offset, near, far),
+            scissor: (offset, Coordinate::new(offset.x + dimensions.width - 1, offset.y + dimensions.height - 1)),
+        }
+    }
+
+    /// Dimensions of the partition, useful for computing per-partition projection matrices
+    pub fn dimensions(&self) -> Dimensions {
+        let (start, end) = self.scissor;
+
+        Dimensions::new(end.x - start.x + 1, end.y - start.y + 1)
+    }
+}
+
+/// A framebuffer partitioned into independent regions
+#[derive(Debug, Clone)]
+pub struct SplitScreen<N> where N: FloatScalar {
+    partitions: Vec<Partition<N>>,
+}
+
+impl<N> SplitScreen<N> where N: FloatScalar {
+    /// Creates a split screen from arbitrary partitions, which should not overlap.
+    pub fn new(partitions: Vec<Partition<N>>) -> SplitScreen<N> {
+        SplitScreen { partitions }
+    }
+
+    /// Splits the framebuffer into a grid of `columns` by `rows` partitions,
+    /// ordered left to right, then top to bottom.
+    ///
+    /// Any remainder pixels go to the last column and row.
+    pub fn grid(dimensions: Dimensions, columns: u32, rows: u32, near: N, far: N) -> SplitScreen<N> {
+        assert!(columns > 0 && rows > 0, "Split screen must have at least one column and row");
+
+        let width = dimensions.width / columns;
+        let height = dimensions.height / rows;
+
+        assert!(width > 0 && height > 0, "Split screen partitions must have non-zero dimensions");
+
+        let mut partitions = Vec::with_capacity((columns * rows) as usize);
+
+        for row in 0..rows {
+            for column in 0..columns {
+                let offset = Coordinate::new(column * width, row * height);
+
+                let size = Dimensions::new(
+                    if column + 1 == columns { dimensions.width - offset.x } else { width },
+                    if row + 1 == rows { dimensions.height - offset.y } else { height },
+                );
+
+                let index = partitions.len();
+
+                partitions.push(Partition::new(index, offset, size, near, far));
+            }
+        }
+
+        SplitScreen { partitions }
+    }
+
+    /// Splits the framebuffer into `count` side-by-side partitions
+    pub fn horizontal(dimensions: Dimensions, count: u32, near: N, far: N) -> SplitScreen<N> {
+        SplitScreen::grid(dimensions, count, 1, near, far)
+    }
+
+    /// Splits the framebuffer into `count` partitions stacked on top of each other
+    pub fn vertical(dimensions: Dimensions, count: u32, near: N, far: N) -> SplitScreen<N> {
+        SplitScreen::grid(dimensions, 1, count, near, far)
+    }
+
+    /// Number of partitions
+    #[inline]
+    pub fn len(&self) -> usize { self.partitions.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.partitions.is_empty() }
+
+    /// Returns all partitions
+    #[inline]
+    pub fn partitions(&self) -> &[Partition<N>] { &self.partitions }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Renders meshes into every partition of a split screen.
+    ///
+    /// `meshes` holds either a single mesh shared by all partitions, or one mesh per partition.
+    /// The vertex and fragment shaders are given the partition being rendered,
+    /// which can be used to select per-partition camera uniforms.
+    ///
+    /// Every partition is transformed first, each spreading its vertices across the thread pool.
+    /// Then the tiles of all partitions, each clipped to its scissor rectangle, are rasterized together
+    /// in a single pass, so threads aren't left idle by small partitions or partitions with little geometry.
+    pub fn render_split<T, V, I, K, B, VS, FS>(&mut self, split: &SplitScreen<V::Scalar>, primitive: T, meshes: &[Arc<Mesh<V, I>>],
+                                            vertex_shader: VS, fragment_shader: FS)
+        where T: Primitive + Copy,
+              V: Vertex,
+              I: MeshIndex,
+              K: Send + Sync + Interpolate,
+              B: Blend<Pixel<Self>> + Default,
+              VS: Fn(&V, &PipelineUniforms<Self>, &Partition<V::Scalar>) -> ClipVertex<V::Scalar, K> + Send + Sync,
+              FS: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<Self>, &Partition<V::Scalar>) -> Fragment<Pixel<Self>> + Send + Sync {
+        assert!(meshes.len() == 1 || meshes.len() == split.len(), "Expected one mesh, or one mesh per partition");
+
+        let fragment_shader = &fragment_shader;
+
+        // One shader per partition, all of the same type
+        let shaders: Vec<_> = split.partitions().iter().map(|partition| {
+            move |vertex: &ScreenVertex<V::Scalar, K>, uniforms: &PipelineUniforms<Self>| fragment_shader(vertex, uniforms, partition)
+        }).collect();
+
+        let mut parts = Vec::with_capacity(split.len());
+
+        for (i, partition) in split.partitions().iter().enumerate() {
+            let mesh = meshes[min(partition.index, meshes.len() - 1)].clone();
+
+            let mut stage = self.render_mesh(primitive, mesh, None)
+                                .run_to_fragment(partition.viewport, |vertex, uniforms| vertex_shader(vertex, uniforms, partition))
+                                .with_scissor(Some(partition.scissor))
+                                .with_default_blend::<B>();
+
+            parts.push(stage.raster_part(&shaders[i..i + 1]));
+
+            // The render state is the same for every partition, so the last stage rasterizes all of them
+            if parts.len() == split.len() {
+                stage.rasterize_parts(&parts, |_| 0, None::<fn(&K) -> Pixel<Self>>, TileLimit::all(), None);
+            }
+        }
+    }
+}
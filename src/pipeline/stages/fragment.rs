@@ -1,33 +1,77 @@
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
+use std::sync::mpsc::Sender;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
 use num_traits::{Float, One, Zero, NumCast, cast};
 use nalgebra::coordinates::XYZW;
+use parking_lot::Mutex;
 
 use ::error::RenderResult;
 
 use ::numeric::utils::min;
-use ::color::{Color, ColorAlpha};
-use ::color::blend::Blend;
+use ::color::{Color, ColorAlpha, ColorMask};
+use ::color::blend::{Blend, BlendPreset};
 use ::pixels::{PixelRead, PixelWrite};
 use ::framebuffer::{UnsafeFramebuffer, Framebuffer};
-use ::attachments::depth::Depth;
-use ::stencil::StencilConfig;
+use ::attachments::depth::{Depth, DepthTest};
+use ::stencil::{StencilConfig, GenericStencilConfig};
 use ::primitive::Primitive;
-use ::mesh::{Vertex, Mesh};
+use ::mesh::{Vertex, Mesh, MeshIndex};
 use ::geometry::{Dimensions, HasDimensions, Coordinate, ScreenVertex, FaceWinding};
 use ::interpolate::Interpolate;
 use ::pipeline::storage::SeparableScreenPrimitiveStorage;
+use ::pipeline::stages::rasterization::{Tile, RasterBackend, generate_tiles, scissor_tiles, tile_pixel_end};
+use ::debug::TileBinning;
 
 use ::pipeline::PipelineObject;
+use ::pipeline::state::RenderStateDesc;
+use ::pipeline::slot::ShaderSlot;
+use ::pipeline::fog::Fog;
+use ::pipeline::sort::{SortMode, DrawOrder, PrimitiveSource};
+use ::pipeline::retro::{Retro, Interpolation};
+use ::pipeline::guard::{PrimitiveGuard, RejectedPrimitives};
+use ::pipeline::stats::{VertexCacheStats, indexed_stats};
+use ::pipeline::transformed::TransformedGeometry;
+use ::pipeline::budget::BudgetReport;
+use ::pipeline::cancel::CancellationToken;
+use ::pipeline::subsample::SubsampleField;
 
 use ::framebuffer::types::DepthAttachment;
 use ::pipeline::types::{PipelineUniforms, Pixel, StencilValue};
 
 pub const DEFAULT_TILE_SIZE: Dimensions = Dimensions { width: 128, height: 128 };
 
+/// Tiles a single pass of the rasterizer may render
+#[derive(Debug, Clone, Copy)]
+pub ( in ::pipeline) struct TileLimit {
+    /// Index of the first tile to render
+    pub start: usize,
+    /// Index past the last tile to render
+    pub end: usize,
+    /// No more tiles are started once this has passed
+    pub deadline: Option<Instant>,
+}
+
+impl TileLimit {
+    /// Every tile, without a deadline
+    pub fn all() -> TileLimit {
+        TileLimit { start: 0, end: usize::max_value(), deadline: None }
+    }
+}
+
+/// Geometry rasterized within its own scissor rectangle and with its own shaders,
+/// so several draws can share a single pass over the thread pool.
+pub ( in ::pipeline) struct RasterPart<'s, V: Vertex, K, I: MeshIndex, S: 's> {
+    pub mesh: Arc<Mesh<V, I>>,
+    pub indexed_vertices: Arc<Option<Vec<ScreenVertex<V::Scalar, K>>>>,
+    pub generated_primitives: Arc<SeparableScreenPrimitiveStorage<V::Scalar, K>>,
+    pub scissor: Option<Tile>,
+    pub shaders: &'s [S],
+}
+
 /// Fragment shader stage.
 ///
 /// The fragment shader is responsible for determining the color of pixels where the underlying geometry has been projected onto.
@@ -42,9 +86,9 @@ pub const DEFAULT_TILE_SIZE: Dimensions = Dimensions { width: 128, height: 128 }
 /// Uniforms passed from the vertex shader are interpolating inside the triangles using Interpolate interpolation,
 /// which is why it must satisfy the [`Interpolate`](../uniform/trait.Interpolate.html) trait, which can be automatically implemented for many types using the
 /// `declare_uniforms!` macro. See the documentation on that for more information on how to use it.
-pub struct FragmentShader<'a, P: 'a, V: Vertex, T, K, B> where P: PipelineObject {
+pub struct FragmentShader<'a, P: 'a, V: Vertex, T, K, B, I: MeshIndex = usize> where P: PipelineObject {
     pub ( in ::pipeline) pipeline: &'a mut P,
-    pub ( in ::pipeline) mesh: Arc<Mesh<V>>,
+    pub ( in ::pipeline) mesh: Arc<Mesh<V, I>>,
     pub ( in ::pipeline) indexed_primitive: PhantomData<T>,
     pub ( in ::pipeline) stencil_value: StencilValue<P>,
     pub ( in ::pipeline) indexed_vertices: Arc<Option<Vec<ScreenVertex<V::Scalar, K>>>>,
@@ -52,9 +96,29 @@ pub struct FragmentShader<'a, P: 'a, V: Vertex, T, K, B> where P: PipelineObject
     pub ( in ::pipeline) cull_faces: Option<FaceWinding>,
     pub ( in ::pipeline) blend: B,
     pub ( in ::pipeline) antialiased_lines: bool,
+    pub ( in ::pipeline) line_width: f64,
+    pub ( in ::pipeline) color_mask: ColorMask,
     pub ( in ::pipeline) tile_size: Dimensions,
+    pub ( in ::pipeline) depth_test: DepthTest,
+    pub ( in ::pipeline) stencil_config: Option<GenericStencilConfig>,
+    pub ( in ::pipeline) scissor: Option<Tile>,
+    pub ( in ::pipeline) fog: Option<FogFunction<P>>,
+    pub ( in ::pipeline) guard: PrimitiveGuard,
+    pub ( in ::pipeline) sort_mode: SortMode,
+    pub ( in ::pipeline) interpolation: Interpolation,
+    pub ( in ::pipeline) vertex_snap: Option<u32>,
+    pub ( in ::pipeline) raster_backend: RasterBackend,
+    pub ( in ::pipeline) progress: Option<ProgressFunction>,
+    pub ( in ::pipeline) cancellation: Option<CancellationToken>,
+    pub ( in ::pipeline) subsample: SubsampleField,
 }
 
+/// Type-erased fog, so the color bounds needed for fog are only required when fog is enabled
+pub ( in ::pipeline) type FogFunction<P> = Arc<Fn(Pixel<P>, f32) -> Pixel<P> + Send + Sync>;
+
+/// Type-erased progress callback, given the number of tiles rendered so far and the total number of tiles
+pub ( in ::pipeline) type ProgressFunction = Arc<Fn(usize, usize) + Send + Sync>;
+
 /// Fragment returned by the fragment shader, which can either be a color
 /// value for the pixel or a discard flag to skip that fragment altogether.
 #[derive(Debug, Clone, Copy)]
@@ -62,21 +126,88 @@ pub enum Fragment<C> where C: Color {
     /// Discard the fragment altogether, as if it was never there.
     Discard,
     /// Desired color for the pixel
-    Color(C)
+    Color(C),
+    /// Desired colors for only some of the render targets, leaving the others unchanged.
+    ///
+    /// Texture buffers declared with an output struct in `declare_texture_buffer!` build these by name.
+    ///
+    /// This variant was added after the others, so exhaustive matches on `Fragment` need an arm for it.
+    Targets(TargetColors<C>),
+}
+
+/// Colors for a subset of the render targets of a texture buffer, created by converting
+/// an output struct declared with `declare_texture_buffer!` into a `Fragment`.
+///
+/// Which targets are written is decided by the named fields of the output struct, and is only seen by the rasterizer.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetColors<C> where C: Color {
+    pub ( crate ) colors: C,
+    pub ( crate ) mask: u32,
+}
+
+impl<C> TargetColors<C> where C: Color {
+    #[doc(hidden)]
+    pub fn __from_mask(colors: C, mask: u32) -> TargetColors<C> {
+        TargetColors { colors, mask }
+    }
+
+    /// Colors of every target, where those not written hold empty colors
+    #[inline]
+    pub fn colors(&self) -> &C { &self.colors }
 }
 
-impl<'a, P: 'a, V, T, K, B> Deref for FragmentShader<'a, P, V, T, K, B>
-    where P: PipelineObject, V: Vertex, B: Blend<Pixel<P>> {
+impl<'a, P: 'a, V, T, K, B, I> Deref for FragmentShader<'a, P, V, T, K, B, I>
+    where P: PipelineObject, V: Vertex, B: Blend<Pixel<P>>, I: MeshIndex {
     type Target = B;
     fn deref(&self) -> &B { &self.blend }
 }
 
-impl<'a, P: 'a, V, T, K, B> DerefMut for FragmentShader<'a, P, V, T, K, B>
-    where P: PipelineObject, V: Vertex, B: Blend<Pixel<P>> {
+impl<'a, P: 'a, V, T, K, B, I> DerefMut for FragmentShader<'a, P, V, T, K, B, I>
+    where P: PipelineObject, V: Vertex, B: Blend<Pixel<P>>, I: MeshIndex {
     fn deref_mut(&mut self) -> &mut B { &mut self.blend }
 }
 
-impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineObject, V: Vertex {
+impl<'a, P: 'a, V, T, K, I> FragmentShader<'a, P, V, T, K, (), I> where P: PipelineObject, V: Vertex, I: MeshIndex {
+    /// Creates a fragment shader for the given screen-space geometry,
+    /// starting out with the current render state of the pipeline.
+    pub ( in ::pipeline) fn from_parts(pipeline: &'a mut P,
+                                       mesh: Arc<Mesh<V, I>>,
+                                       stencil_value: StencilValue<P>,
+                                       indexed_vertices: Arc<Option<Vec<ScreenVertex<V::Scalar, K>>>>,
+                                       generated_primitives: Arc<SeparableScreenPrimitiveStorage<V::Scalar, K>>) -> FragmentShader<'a, P, V, T, K, (), I> {
+        let state = *pipeline.render_state();
+        let subsample = SubsampleField::new(state.subsample, pipeline.arena().frame());
+
+        FragmentShader {
+            pipeline,
+            mesh,
+            indexed_primitive: PhantomData,
+            stencil_value,
+            indexed_vertices,
+            generated_primitives,
+            cull_faces: state.desc.cull_faces,
+            blend: (),
+            antialiased_lines: state.desc.antialiased_lines,
+            line_width: 1.0,
+            color_mask: state.desc.color_mask,
+            tile_size: state.desc.tile_size.unwrap_or(DEFAULT_TILE_SIZE),
+            depth_test: state.desc.depth_test,
+            stencil_config: state.desc.stencil,
+            scissor: state.scissor,
+            fog: None,
+            guard: PrimitiveGuard::Disabled,
+            sort_mode: SortMode::None,
+            interpolation: Interpolation::default(),
+            vertex_snap: None,
+            raster_backend: RasterBackend::EdgeFunction,
+            progress: None,
+            cancellation: None,
+            subsample,
+        }
+    }
+}
+
+impl<'a, P: 'a, V, T, K, B, I> FragmentShader<'a, P, V, T, K, B, I> where P: PipelineObject, V: Vertex, I: MeshIndex {
     /// Cull faces based on winding order. For more information on how and why this works,
     /// check out the documentation for the [`FaceWinding`](../geometry/winding/enum.FaceWinding.html) enum.
     pub fn cull_faces(&mut self, cull: Option<FaceWinding>) {
@@ -91,8 +222,7 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
     }
 
     /// Enables drawing antialiased lines for `Line` primitives
-    /// primitives using Xiaolin Wu's algorithm,
-    /// otherwise Bresenham's Algorithm is used.
+    /// using the Gupta-Sproull algorithm, otherwise Bresenham's Algorithm is used.
     pub fn antialiased_lines(&mut self, enable: bool) {
         self.antialiased_lines = enable;
     }
@@ -104,6 +234,32 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
         }
     }
 
+    /// Sets the width of `Line` primitives in pixels, which defaults to one pixel.
+    ///
+    /// Wide lines are always drawn with the Gupta-Sproull algorithm, but only antialiased if enabled.
+    pub fn line_width(&mut self, width: f64) {
+        self.line_width = width;
+    }
+
+    pub fn with_line_width(self, width: f64) -> Self {
+        FragmentShader {
+            line_width: width,
+            ..self
+        }
+    }
+
+    /// Sets which color channels are written, after blending. Depth and stencil are written regardless.
+    pub fn color_mask(&mut self, mask: ColorMask) {
+        self.color_mask = mask;
+    }
+
+    pub fn with_color_mask(self, mask: ColorMask) -> Self {
+        FragmentShader {
+            color_mask: mask,
+            ..self
+        }
+    }
+
     pub fn tile_size(&mut self, tile_size: Dimensions) {
         self.tile_size = tile_size;
     }
@@ -115,11 +271,228 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
         }
     }
 
+    /// Sets the depth test performed before fragment shading.
+    ///
+    /// The default is `DepthTest::GreaterThanEq`, which only shades fragments at or in front of existing geometry.
+    pub fn depth_test(&mut self, depth_test: DepthTest) {
+        self.depth_test = depth_test;
+    }
+
+    pub fn with_depth_test(self, depth_test: DepthTest) -> Self {
+        FragmentShader {
+            depth_test,
+            ..self
+        }
+    }
+
+    /// Overrides the pipeline stencil configuration for this draw only.
+    ///
+    /// Passing `None` uses the pipeline stencil configuration again.
+    pub fn stencil_config(&mut self, stencil_config: Option<GenericStencilConfig>) {
+        self.stencil_config = stencil_config;
+    }
+
+    pub fn with_stencil_config(self, stencil_config: Option<GenericStencilConfig>) -> Self {
+        FragmentShader {
+            stencil_config,
+            ..self
+        }
+    }
+
+    /// Restricts rendering to the given inclusive rectangle of the framebuffer.
+    ///
+    /// Passing `None` renders to the whole framebuffer again.
+    pub fn scissor(&mut self, scissor: Option<Tile>) {
+        self.scissor = scissor;
+    }
+
+    pub fn with_scissor(self, scissor: Option<Tile>) -> Self {
+        FragmentShader {
+            scissor,
+            ..self
+        }
+    }
+
+    /// Applies distance fog to shaded fragments before blending.
+    ///
+    /// Passing `None` disables fog.
+    pub fn fog(&mut self, fog: Option<Fog<Pixel<P>>>) where Pixel<P>: Interpolate {
+        self.fog = fog.map(|fog| -> FogFunction<P> {
+            Arc::new(move |color, distance| fog.apply(color, distance))
+        });
+    }
+
+    pub fn with_fog(mut self, fog: Option<Fog<Pixel<P>>>) -> Self where Pixel<P>: Interpolate {
+        self.fog(fog);
+        self
+    }
+
+    /// Calls `progress` with the number of tiles rendered so far and the total number of tiles
+    /// every time a tile is finished, such as for drawing progress bars during large offline renders.
+    ///
+    /// The callback is invoked from the render threads, possibly from several at once,
+    /// so it should return quickly. Calls from different threads may arrive slightly out of order.
+    pub fn on_progress<G>(&mut self, progress: G) where G: Fn(usize, usize) + Send + Sync + 'static {
+        self.progress = Some(Arc::new(progress));
+    }
+
+    pub fn with_progress<G>(mut self, progress: G) -> Self where G: Fn(usize, usize) + Send + Sync + 'static {
+        self.on_progress(progress);
+        self
+    }
+
+    /// Sends `(tiles_done, tiles_total)` over the channel every time a tile is finished,
+    /// for receiving progress on another thread. Send errors from a closed channel are ignored.
+    pub fn progress_channel(&mut self, sender: Sender<(usize, usize)>) {
+        let sender = Mutex::new(sender);
+
+        self.on_progress(move |done, total| { let _ = sender.lock().send((done, total)); });
+    }
+
+    pub fn with_progress_channel(mut self, sender: Sender<(usize, usize)>) -> Self {
+        self.progress_channel(sender);
+        self
+    }
+
+    /// Stops starting new tiles once the token is cancelled, leaving the remaining tiles untouched.
+    ///
+    /// See the [`cancel`](../cancel/index.html) module for details.
+    pub fn cancellation(&mut self, cancellation: Option<CancellationToken>) {
+        self.cancellation = cancellation;
+    }
+
+    pub fn with_cancellation(self, cancellation: Option<CancellationToken>) -> Self {
+        FragmentShader {
+            cancellation,
+            ..self
+        }
+    }
+
+    /// Sets the pixels this draw renders when subsampling, which defaults to the field of the current frame
+    /// for the subsample mode of the render state.
+    ///
+    /// See the [`subsample`](../subsample/index.html) module for details.
+    pub fn subsample(&mut self, subsample: SubsampleField) {
+        self.subsample = subsample;
+    }
+
+    pub fn with_subsample(self, subsample: SubsampleField) -> Self {
+        FragmentShader {
+            subsample,
+            ..self
+        }
+    }
+
+    /// Checks primitives for NaN or infinite screen coordinates and zero area before rasterization,
+    /// skipping any broken ones as determined by the guard.
+    pub fn guard(&mut self, guard: PrimitiveGuard) {
+        self.guard = guard;
+    }
+
+    pub fn with_guard(self, guard: PrimitiveGuard) -> Self {
+        FragmentShader {
+            guard,
+            ..self
+        }
+    }
+
+    /// Sets the order triangles and points are rasterized in.
+    ///
+    /// Use `SortMode::BackToFront` for alpha blended geometry, so it composites correctly
+    /// without splitting and sorting meshes by hand, or `SortMode::FrontToBack` for opaque geometry
+    /// to skip shading occluded fragments. See the [`sort`](../sort/index.html) module for details.
+    pub fn sort_mode(&mut self, sort_mode: SortMode) {
+        self.sort_mode = sort_mode;
+    }
+
+    pub fn with_sort_mode(self, sort_mode: SortMode) -> Self {
+        FragmentShader {
+            sort_mode,
+            ..self
+        }
+    }
+
+    /// Sets how uniforms are interpolated across triangles. Defaults to `Interpolation::Affine`.
+    pub fn interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+    }
+
+    pub fn with_interpolation(self, interpolation: Interpolation) -> Self {
+        FragmentShader {
+            interpolation,
+            ..self
+        }
+    }
+
+    /// Snaps triangle vertices to a grid of the given number of subpixels per pixel, or not at all for `None`.
+    pub fn vertex_snap(&mut self, vertex_snap: Option<u32>) {
+        self.vertex_snap = vertex_snap;
+    }
+
+    pub fn with_vertex_snap(self, vertex_snap: Option<u32>) -> Self {
+        FragmentShader {
+            vertex_snap,
+            ..self
+        }
+    }
+
+    /// Applies the interpolation and vertex snapping of a retro style.
+    ///
+    /// The texture filter of the style has to be used by the fragment shader itself.
+    /// See the [`retro`](../retro/index.html) module for details.
+    pub fn with_retro(self, retro: Retro) -> Self {
+        FragmentShader {
+            interpolation: retro.interpolation,
+            vertex_snap: retro.vertex_snap,
+            ..self
+        }
+    }
+
+    /// Sets the algorithm used to fill triangles shaded with a fragment shader. Defaults to `RasterBackend::EdgeFunction`.
+    pub fn raster_backend(&mut self, raster_backend: RasterBackend) {
+        self.raster_backend = raster_backend;
+    }
+
+    pub fn with_raster_backend(self, raster_backend: RasterBackend) -> Self {
+        FragmentShader {
+            raster_backend,
+            ..self
+        }
+    }
+
+    /// Returns how well the geometry being rendered reuses transformed vertices,
+    /// including any vertices generated by a geometry shader.
+    pub fn vertex_cache_stats(&self) -> VertexCacheStats {
+        let mut stats = match *self.indexed_vertices {
+            Some(ref indexed_vertices) => indexed_stats(indexed_vertices.len(), self.mesh.indices.iter().map(|index| index.to_usize())),
+            None => VertexCacheStats::default(),
+        };
+
+        let generated = &*self.generated_primitives;
+
+        // Generated triangles are indexed, generated points and lines are not
+        let tris = indexed_stats(generated.tris.len(), generated.tri_indices.iter().cloned());
+
+        let unindexed = generated.points.len() + generated.lines.len();
+
+        stats.references += tris.references + unindexed;
+        stats.transformed += tris.transformed + unindexed;
+        stats.unreferenced += tris.unreferenced;
+
+        stats
+    }
+
+    /// Returns the transformed geometry being rendered, which can be kept around
+    /// to render it again later without running the vertex and geometry shaders again.
+    pub fn geometry(&self) -> TransformedGeometry<V, T, K, I> {
+        TransformedGeometry::from_parts(self.mesh.clone(), self.indexed_vertices.clone(), self.generated_primitives.clone())
+    }
+
     /// Duplicates all references to internal state to return a cloned fragment shader,
     /// which can be used to efficiently render the same geometry with different
     /// rasterization methods in quick succession.
     #[must_use]
-    pub fn duplicate<'b>(&'b mut self) -> FragmentShader<'b, P, V, T, K, B> where 'a: 'b, B: Clone {
+    pub fn duplicate<'b>(&'b mut self) -> FragmentShader<'b, P, V, T, K, B, I> where 'a: 'b, B: Clone {
         FragmentShader {
             pipeline: self.pipeline,
             mesh: self.mesh.clone(),
@@ -130,14 +503,28 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
             cull_faces: self.cull_faces.clone(),
             blend: self.blend.clone(),
             antialiased_lines: self.antialiased_lines,
+            line_width: self.line_width,
+            color_mask: self.color_mask,
             tile_size: self.tile_size,
+            depth_test: self.depth_test,
+            stencil_config: self.stencil_config,
+            scissor: self.scissor,
+            fog: self.fog.clone(),
+            guard: self.guard.clone(),
+            sort_mode: self.sort_mode,
+            interpolation: self.interpolation,
+            vertex_snap: self.vertex_snap,
+            raster_backend: self.raster_backend,
+            progress: self.progress.clone(),
+            cancellation: self.cancellation.clone(),
+            subsample: self.subsample,
         }
     }
 }
 
-impl<'a, P: 'a, V, T, K, O> FragmentShader<'a, P, V, T, K, O> where P: PipelineObject, V: Vertex {
+impl<'a, P: 'a, V, T, K, O, I> FragmentShader<'a, P, V, T, K, O, I> where P: PipelineObject, V: Vertex, I: MeshIndex {
     #[must_use]
-    pub fn with_blend<B>(self, blend: B) -> FragmentShader<'a, P, V, T, K, B>
+    pub fn with_blend<B>(self, blend: B) -> FragmentShader<'a, P, V, T, K, B, I>
         where B: Blend<Pixel<P>> {
         FragmentShader {
             pipeline: self.pipeline,
@@ -149,75 +536,320 @@ impl<'a, P: 'a, V, T, K, O> FragmentShader<'a, P, V, T, K, O> where P: PipelineO
             cull_faces: self.cull_faces,
             blend: blend,
             antialiased_lines: self.antialiased_lines,
+            line_width: self.line_width,
+            color_mask: self.color_mask,
             tile_size: self.tile_size,
+            depth_test: self.depth_test,
+            stencil_config: self.stencil_config,
+            scissor: self.scissor,
+            fog: self.fog,
+            guard: self.guard,
+            sort_mode: self.sort_mode,
+            interpolation: self.interpolation,
+            vertex_snap: self.vertex_snap,
+            raster_backend: self.raster_backend,
+            progress: self.progress,
+            cancellation: self.cancellation,
+            subsample: self.subsample,
         }
     }
 
     #[must_use]
-    pub fn with_default_blend<B>(self) -> FragmentShader<'a, P, V, T, K, B>
+    pub fn with_default_blend<B>(self) -> FragmentShader<'a, P, V, T, K, B, I>
         where B: Blend<Pixel<P>> + Default {
         self.with_blend(B::default())
     }
+
+    /// Uses the blend preset of the current pipeline render state
+    #[must_use]
+    pub fn with_state_blend(self) -> FragmentShader<'a, P, V, T, K, BlendPreset, I>
+        where BlendPreset: Blend<Pixel<P>> {
+        let blend = self.pipeline.render_state().desc.blend;
+
+        self.with_blend(blend)
+    }
+
+    /// Applies all the state from a render state description, replacing the current blend function with its preset.
+    #[must_use]
+    pub fn with_render_state(self, state: &RenderStateDesc) -> FragmentShader<'a, P, V, T, K, BlendPreset, I>
+        where BlendPreset: Blend<Pixel<P>> {
+        let RenderStateDesc { cull_faces, blend, depth_test, stencil, tile_size, antialiased_lines, color_mask } = *state;
+
+        let tile_size = tile_size.unwrap_or(self.tile_size);
+
+        FragmentShader {
+            cull_faces,
+            antialiased_lines,
+            color_mask,
+            tile_size,
+            depth_test,
+            stencil_config: stencil,
+            ..self.with_blend(blend)
+        }
+    }
 }
 
-impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineObject,
+impl<'a, P: 'a, V, T, K, B, I> FragmentShader<'a, P, V, T, K, B, I> where P: PipelineObject,
                                                                     V: Vertex,
                                                                     T: Primitive,
                                                                     K: Send + Sync + Interpolate,
-                                                                    B: Blend<Pixel<P>> {
+                                                                    B: Blend<Pixel<P>>,
+                                                                    I: MeshIndex {
+    /// Bins every primitive to the tiles its screen-space bounding box overlaps, without rendering anything.
+    ///
+    /// Combined with `debug::draw_tile_overlay`, this helps with choosing a `tile_size`
+    /// that spreads the work evenly between threads.
+    pub fn tile_binning(&self) -> TileBinning {
+        let dimensions = self.pipeline.framebuffer().dimensions();
+
+        let mut tiles = generate_tiles(dimensions, self.tile_size);
+
+        if let Some(scissor) = self.scissor {
+            tiles = scissor_tiles(tiles, scissor);
+        }
+
+        let mut binning = TileBinning::new(self.tile_size, tiles);
+
+        {
+            let mut bin_vertices = |vertices: &[&ScreenVertex<V::Scalar, K>]| {
+                let mut min = (::std::f64::INFINITY, ::std::f64::INFINITY);
+                let mut max = (::std::f64::NEG_INFINITY, ::std::f64::NEG_INFINITY);
+
+                for vertex in vertices {
+                    let x: f64 = cast(vertex.position.x).unwrap();
+                    let y: f64 = cast(vertex.position.y).unwrap();
+
+                    min = (min.0.min(x), min.1.min(y));
+                    max = (max.0.max(x), max.1.max(y));
+                }
+
+                binning.bin(min, max);
+            };
+
+            if let Some(ref indexed_vertices) = *self.indexed_vertices {
+                if T::is_triangle() {
+                    for triangle in self.mesh.indices.chunks(3) {
+                        bin_vertices(&[&indexed_vertices[triangle[0].to_usize()],
+                                         &indexed_vertices[triangle[1].to_usize()],
+                                         &indexed_vertices[triangle[2].to_usize()]]);
+                    }
+                } else if T::is_line() {
+                    for line in self.mesh.indices.chunks(2) {
+                        bin_vertices(&[&indexed_vertices[line[0].to_usize()], &indexed_vertices[line[1].to_usize()]]);
+                    }
+                } else if T::is_point() {
+                    for index in &self.mesh.indices {
+                        bin_vertices(&[&indexed_vertices[index.to_usize()]]);
+                    }
+                }
+            }
+
+            for j in 0..self.generated_primitives.num_triangles() {
+                bin_vertices(&self.generated_primitives.triangle(j));
+            }
+
+            for line in self.generated_primitives.lines.chunks(2) {
+                bin_vertices(&[&line[0], &line[1]]);
+            }
+
+            for point in &self.generated_primitives.points {
+                bin_vertices(&[point]);
+            }
+        }
+
+        binning
+    }
+
     pub fn run<S>(self, fragment_shader: S)
         where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+        self.run_materials(&[fragment_shader], |_| 0)
+    }
+
+    /// Same as `run`, but stops starting new tiles once `budget` has passed since the call,
+    /// returning which tiles were rendered and which were skipped.
+    ///
+    /// See the [`budget`](../budget/index.html) module for details.
+    pub fn run_with_budget<S>(mut self, budget: Duration, fragment_shader: S) -> BudgetReport
+        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+        let limit = TileLimit { deadline: Some(Instant::now() + budget), ..TileLimit::all() };
+
+        self.rasterize(&[fragment_shader], |_| 0, None::<fn(&K) -> Pixel<P>>, limit, None)
+    }
+
+    /// Same as `run`, but takes the shader as a trait object.
+    ///
+    /// Every closure type passed to `run` creates a new copy of the whole rasterizer, which adds up quickly
+    /// for applications that create shaders at runtime, such as editors or scripting hosts.
+    /// `run_dyn` is only instantiated once per pipeline and uniforms type, no matter how many shaders are used.
+    ///
+    /// The cost is one indirect call per shaded fragment, and the shader can no longer be inlined into the rasterizer.
+    /// That only matters for shaders so cheap the call itself is a large part of their cost.
+    /// The `run_dyn` example times both methods on a full-screen quad, with a constant color shader and
+    /// a bilinearly textured shader, to measure the difference on a given machine.
+    pub fn run_dyn(self, fragment_shader: &(Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync)) {
+        self.run(fragment_shader)
+    }
+
+    /// Same as `run`, but loads the shader from a `ShaderSlot` at the start of the draw.
+    ///
+    /// Replacing the shader in the slot while drawing only affects later draws.
+    pub fn run_slot<S>(self, slot: &ShaderSlot<S>)
+        where S: ?Sized + Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+        let fragment_shader = slot.load();
+
+        self.run(&*fragment_shader)
+    }
+
+    /// Renders all primitives in a single pass, choosing a fragment shader from `shaders` for each primitive.
+    ///
+    /// The `material` function is given the uniforms of the first (provoking) vertex of each primitive
+    /// and returns the index of the shader to use for the whole primitive. Primitives with an index out of range are skipped.
+    ///
+    /// The easiest way to give primitives a material is to output the material index from the vertex shader,
+    /// but since uniforms are interpolated, only the provoking vertex is considered.
+    ///
+    /// Shaders may be closures of the same type or trait objects, like `&Fn(...) -> Fragment<_>`, for a table of different materials:
+    ///
+    /// ```ignore
+    /// let materials: Vec<&(Fn(&ScreenVertex<f32, Uniforms>, &GlobalUniforms) -> Fragment<RGBAf32Color> + Send + Sync)> = vec![&metal, &plastic, &glass];
+    ///
+    /// fragment_shader.run_materials(&materials, |uniforms| uniforms.material as usize);
+    /// ```
+    pub fn run_materials<S, M>(mut self, shaders: &[S], material: M)
+        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync,
+              M: Fn(&K) -> usize + Send + Sync {
+        self.rasterize(shaders, material, None::<fn(&K) -> Pixel<P>>, TileLimit::all(), None);
+    }
+
+    /// Renders all primitives using colors computed per-vertex, also known as Gouraud shading.
+    ///
+    /// Lighting is done in the vertex shader, which outputs uniforms that convert into the final color,
+    /// such as the color itself. Triangles are then filled by a specialized loop that only interpolates
+    /// depth and the vertex colors, without invoking a fragment shader for every pixel at all.
+    /// Lines and points are drawn as usual with a shader writing the converted uniforms.
+    ///
+    /// Fog is not applied to triangles, but can be applied per-vertex in the vertex shader with `Fog::apply`.
+    ///
+    /// This is much faster than `run` for low-poly or retro-styled scenes,
+    /// at the cost of lighting detail between vertices.
+    pub fn run_gouraud(mut self) where K: Clone + Into<Pixel<P>> {
+        let color = |uniforms: &K| -> Pixel<P> { uniforms.clone().into() };
+
+        let shader = |vertex: &ScreenVertex<V::Scalar, K>, _: &PipelineUniforms<P>| Fragment::Color(vertex.uniforms.clone().into());
+
+        self.rasterize(&[shader], |_| 0, Some(color), TileLimit::all(), None);
+    }
+
+    /// Returns the geometry being rendered, clipped to the current scissor rectangle and drawn with `shaders`,
+    /// to be rasterized together with other parts by `rasterize_parts`.
+    pub ( in ::pipeline) fn raster_part<'s, S>(&self, shaders: &'s [S]) -> RasterPart<'s, V, K, I, S> {
+        RasterPart {
+            mesh: self.mesh.clone(),
+            indexed_vertices: self.indexed_vertices.clone(),
+            generated_primitives: self.generated_primitives.clone(),
+            scissor: self.scissor,
+            shaders,
+        }
+    }
+
+    /// Shared implementation of every way to run the fragment shader, rendering the tiles within `limit`,
+    /// where triangles are filled with `gouraud` colors instead of fragment shaders if given.
+    ///
+    /// The report only includes tiles within the limit. If `finished` is given, tiles already marked in it
+    /// are not rendered again or included in the report, and tiles rendered by this pass are marked.
+    pub ( in ::pipeline) fn rasterize<S, M, G>(&mut self, shaders: &[S], material: M, gouraud: Option<G>,
+                                               limit: TileLimit, finished: Option<&mut [bool]>) -> BudgetReport
+        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync,
+              M: Fn(&K) -> usize + Send + Sync,
+              G: Fn(&K) -> Pixel<P> + Send + Sync {
+        let part = self.raster_part(shaders);
+
+        self.rasterize_parts(&[part], material, gouraud, limit, finished)
+    }
+
+    /// Same as `rasterize`, but renders the geometry of every part in the same pass, using the render state of this stage.
+    ///
+    /// The tiles of each part are clipped to its scissor rectangle and only draw its own geometry with its own shaders,
+    /// and are all handed out to the thread pool together, followed by the tiles of the next part.
+    pub ( in ::pipeline) fn rasterize_parts<S, M, G>(&mut self, parts: &[RasterPart<V, K, I, S>], material: M, gouraud: Option<G>,
+                                                     limit: TileLimit, finished: Option<&mut [bool]>) -> BudgetReport
+        where S: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync,
+              M: Fn(&K) -> usize + Send + Sync,
+              G: Fn(&K) -> Pixel<P> + Send + Sync {
+        assert!(!parts.is_empty(), "Expected at least one part to rasterize");
+
         let FragmentShader {
-            pipeline,
-            mesh,
-            indexed_vertices,
+            ref mut pipeline,
             stencil_value,
-            generated_primitives,
             cull_faces,
-            blend,
+            ref blend,
             antialiased_lines,
+            line_width,
+            color_mask,
             tile_size,
+            depth_test,
+            stencil_config,
+            ref fog,
+            ref guard,
+            sort_mode,
+            interpolation,
+            vertex_snap,
+            raster_backend,
+            ref progress,
+            ref cancellation,
+            subsample,
             ..
-        } = self;
+        } = *self;
+
+        trace_span!("fragment");
 
         // Basically constant
         let one_half = <V::Scalar as NumCast>::from(0.5).unwrap();
 
         let dimensions = pipeline.framebuffer().dimensions();
+        let stride = pipeline.framebuffer().stride();
+        let pixel_center = pipeline.render_state().pixel_center;
 
-        let tiles = {
-            let mut tiles = Vec::new();
+        let (mut tiles, part_ends, prepared) = {
+            profile_scope!("binning");
 
-            let xmax = dimensions.width - 1;
-            let ymax = dimensions.height - 1;
+            // Reuse the tile list of earlier draws for the first part
+            let mut tiles = pipeline.arena_mut().take_tiles(dimensions, tile_size, parts[0].scissor);
 
-            let mut y = 0;
+            let mut part_ends = Vec::with_capacity(parts.len());
 
-            while y < ymax {
-                let mut x = 0;
+            part_ends.push(tiles.len());
 
-                let next_y = min(y + tile_size.height, ymax);
+            for part in &parts[1..] {
+                pipeline.arena().append_tiles(&mut tiles, dimensions, tile_size, part.scissor);
 
-                while x < xmax {
-                    let next_x = min(x + tile_size.width, xmax);
+                part_ends.push(tiles.len());
+            }
 
-                    tiles.push((
-                        Coordinate::new(x, y),
-                        Coordinate::new(next_x, next_y)
-                    ));
+            let prepared: Vec<(RejectedPrimitives, Option<DrawOrder>)> = parts.iter().map(|part| {
+                let indexed_vertices = (*part.indexed_vertices).as_ref().map(|v| &v[..]);
 
-                    x = next_x;
-                }
+                // Check every primitive once up front rather than in every tile
+                let rejected = RejectedPrimitives::check(&guard, T::num_vertices(), &part.mesh.indices,
+                                                         indexed_vertices, &part.generated_primitives);
 
-                y = next_y;
-            }
+                // Sort once up front, so every tile draws primitives in the same order
+                let order = DrawOrder::sort(sort_mode, T::num_vertices(), &part.mesh.indices,
+                                            indexed_vertices, &part.generated_primitives);
+
+                (rejected, order)
+            }).collect();
 
-            tiles
+            (tiles, part_ends, prepared)
         };
 
+        trace_event!(rejected = prepared.iter().map(|&(ref rejected, _)| rejected.count()).sum::<usize>(), "primitives culled");
+
         // Fetch stencil test and operation before tile loop
-        let stencil_test = pipeline.stencil_config().get_test();
-        let stencil_op = pipeline.stencil_config().get_op();
+        let (stencil_test, stencil_op) = match stencil_config {
+            Some(ref config) => (config.get_test(), config.get_op()),
+            None => (pipeline.stencil_config().get_test(), pipeline.stencil_config().get_op()),
+        };
 
         /// There is simply no way around this right now. The only reason I'm comfortable doing it is because
         /// all the code using the pipeline is my own and not available to the user.
@@ -231,90 +863,285 @@ impl<'a, P: 'a, V, T, K, B> FragmentShader<'a, P, V, T, K, B> where P: PipelineO
         unsafe impl<P> Sync for NeverDoThis<P> {}
 
         /// Create unsafe mutable point to the pipeline
-        let seriously_dont = NeverDoThis { pipeline: pipeline as *mut P };
+        let seriously_dont = NeverDoThis { pipeline: &mut **pipeline as *mut P };
+
+        let (_, framebuffer, pool) = pipeline.all_mut();
 
-        let (_, _, pool) = pipeline.all_mut();
+        framebuffer.prepare_write();
 
         let thread_count = pool.thread_count();
 
-        let i = AtomicUsize::new(0);
+        let end_tile = min(limit.end, tiles.len());
+        let first_tile = min(limit.start, end_tile);
+
+        let i = AtomicUsize::new(first_tile);
+
+        let completed = Mutex::new(Vec::with_capacity(end_tile - first_tile));
+
+        let is_finished = |i: usize| finished.as_ref().map_or(false, |finished| finished.get(i).cloned().unwrap_or(false));
+
+        // Finished tiles, or tiles before the limit, count as done, so progress carries over between passes of a `RenderJob`
+        let tiles_done = AtomicUsize::new(match finished {
+            Some(ref finished) => finished.iter().filter(|&&done| done).count(),
+            None => first_tile,
+        });
 
         pool.scoped(|scope| {
             for _ in 0..thread_count {
                 scope.execute(|| {
-                    use super::rasterization::{RasterArguments, rasterize_triangle, rasterize_line, rasterize_point};
+                    use super::rasterization::{RasterArguments, rasterize_triangle, rasterize_triangle_gouraud, rasterize_triangle_scanline, rasterize_triangle_hierarchical, rasterize_line, rasterize_point};
 
                     // Get the unsafe mutable reference to the pipeline
                     let pipeline: &mut P = unsafe { &mut *seriously_dont.pipeline };
 
+                    let fog = fog.as_ref();
+
+                    let mut completed_tiles = Vec::new();
+
                     loop {
+                        if let Some(deadline) = limit.deadline {
+                            if Instant::now() >= deadline { break; }
+                        }
+
+                        if let Some(ref token) = *cancellation {
+                            if token.is_cancelled() { break; }
+                        }
+
+                        // Only claim a tile after the checks, so every claimed tile is rendered
+                        // and the rendered tiles always come before the skipped ones.
                         let i = i.fetch_add(1, Ordering::Relaxed);
 
-                        if i < tiles.len() {
+                        if i < end_tile {
+                            if is_finished(i) { continue; }
+
+                            profile_scope!("raster");
+
                             let tile = tiles[i];
 
+                            // Tiles are grouped by part, in order
+                            let part_index = part_ends.iter().position(|&end| i < end).unwrap();
+
+                            let part = &parts[part_index];
+                            let (ref rejected, ref order) = prepared[part_index];
+
+                            let mesh = &part.mesh;
+                            let indexed_vertices = (*part.indexed_vertices).as_ref().map(|v| &v[..]);
+                            let generated_primitives = &*part.generated_primitives;
+                            let shaders = part.shaders;
+
+                            // Select the shader for a primitive from its provoking vertex, skipping the primitive if there is none.
+                            //
+                            // Fog is applied to the shaded fragment here, so it happens before blending.
+                            macro_rules! material_shader {
+                                ($vertex:expr) => {
+                                    match shaders.get(material(&$vertex.uniforms)) {
+                                        Some(shader) => move |vertex: &ScreenVertex<V::Scalar, K>, uniforms: &PipelineUniforms<P>| {
+                                            match (shader(vertex, uniforms), fog) {
+                                                (Fragment::Color(color), Some(fog)) => {
+                                                    let distance: f32 = cast(<V::Scalar as One>::one() / vertex.position.w).unwrap_or(::std::f32::INFINITY);
+
+                                                    Fragment::Color((&**fog)(color, distance))
+                                                }
+                                                (Fragment::Targets(TargetColors { colors, mask }), Some(fog)) => {
+                                                    let distance: f32 = cast(<V::Scalar as One>::one() / vertex.position.w).unwrap_or(::std::f32::INFINITY);
+
+                                                    Fragment::Targets(TargetColors { colors: (&**fog)(colors, distance), mask })
+                                                }
+                                                (fragment, _) => fragment,
+                                            }
+                                        },
+                                        None => continue,
+                                    }
+                                }
+                            }
+
+                            // Fill a triangle with either the fragment shader or the per-vertex colors
+                            macro_rules! draw_triangle {
+                                ($args:expr, $a:expr, $b:expr, $c:expr) => {
+                                    match gouraud {
+                                        Some(ref color) => rasterize_triangle_gouraud($args, pipeline, &blend, color, $a, $b, $c),
+                                        None => match raster_backend {
+                                            RasterBackend::EdgeFunction => rasterize_triangle($args, pipeline, &blend, material_shader!($a), $a, $b, $c),
+                                            RasterBackend::Scanline => rasterize_triangle_scanline($args, pipeline, &blend, material_shader!($a), $a, $b, $c),
+                                            RasterBackend::Hierarchical => rasterize_triangle_hierarchical($args, pipeline, &blend, material_shader!($a), $a, $b, $c),
+                                        },
+                                    }
+                                }
+                            }
+
                             let mut args: RasterArguments<P, V> = RasterArguments {
                                 dimensions,
+                                stride,
                                 tile: tile,
+                                pixel_end: tile_pixel_end(tile, dimensions, tile_size),
                                 bounds: ((cast(tile.0.x).unwrap(), cast(tile.0.y).unwrap()),
                                          (cast(tile.1.x).unwrap(), cast(tile.1.y).unwrap())),
                                 stencil_value,
                                 stencil_test,
                                 stencil_op,
                                 antialiased_lines,
+                                line_width,
+                                color_mask,
                                 cull_faces,
+                                depth_test,
+                                interpolation,
+                                vertex_snap,
+                                field: subsample,
+                                pixel_center,
                             };
 
-                            if T::is_triangle() {
-                                if let Some(ref indexed_vertices) = *indexed_vertices {
-                                    for triangle in mesh.indices.chunks(3) {
-                                        let a = &indexed_vertices[triangle[0]];
-                                        let b = &indexed_vertices[triangle[1]];
-                                        let c = &indexed_vertices[triangle[2]];
+                            if let Some(ref order) = *order {
+                                for &source in &order.tris {
+                                    let (a, b, c) = match source {
+                                        PrimitiveSource::Indexed(j) => {
+                                            if RejectedPrimitives::is_rejected(&rejected.indexed, j) { continue; }
+
+                                            let indexed_vertices = indexed_vertices.unwrap();
+                                            let triangle = &mesh.indices[j * 3..j * 3 + 3];
 
-                                        rasterize_triangle(&args, pipeline, &blend, &fragment_shader, a, b, c);
+                                            (&indexed_vertices[triangle[0].to_usize()],
+                                             &indexed_vertices[triangle[1].to_usize()],
+                                             &indexed_vertices[triangle[2].to_usize()])
+                                        }
+                                        PrimitiveSource::Generated(j) => {
+                                            if RejectedPrimitives::is_rejected(&rejected.tris, j) { continue; }
+
+                                            let triangle = generated_primitives.triangle(j);
+
+                                            (triangle[0], triangle[1], triangle[2])
+                                        }
+                                    };
+
+                                    draw_triangle!(&args, a, b, c);
+                                }
+                            } else {
+                                if T::is_triangle() {
+                                    if let Some(indexed_vertices) = indexed_vertices {
+                                        for (j, triangle) in mesh.indices.chunks(3).enumerate() {
+                                            if RejectedPrimitives::is_rejected(&rejected.indexed, j) { continue; }
+
+                                            let a = &indexed_vertices[triangle[0].to_usize()];
+                                            let b = &indexed_vertices[triangle[1].to_usize()];
+                                            let c = &indexed_vertices[triangle[2].to_usize()];
+
+                                            draw_triangle!(&args, a, b, c);
+                                        }
                                     }
                                 }
-                            }
 
-                            for triangle in generated_primitives.tris.chunks(3) {
-                                rasterize_triangle(&args, pipeline, &blend, &fragment_shader, &triangle[0], &triangle[1], &triangle[2]);
+                                for j in 0..generated_primitives.num_triangles() {
+                                    if RejectedPrimitives::is_rejected(&rejected.tris, j) { continue; }
+
+                                    let triangle = generated_primitives.triangle(j);
+
+                                    draw_triangle!(&args, triangle[0], triangle[1], triangle[2]);
+                                }
                             }
 
                             if T::is_line() {
-                                if let Some(ref indexed_vertices) = *indexed_vertices {
-                                    for line in mesh.indices.chunks(2) {
-                                        let start = &indexed_vertices[line[0]];
-                                        let end = &indexed_vertices[line[1]];
+                                if let Some(indexed_vertices) = indexed_vertices {
+                                    for (j, line) in mesh.indices.chunks(2).enumerate() {
+                                        if RejectedPrimitives::is_rejected(&rejected.indexed, j) { continue; }
+
+                                        let start = &indexed_vertices[line[0].to_usize()];
+                                        let end = &indexed_vertices[line[1].to_usize()];
 
-                                        rasterize_line(&args, pipeline, &blend, &fragment_shader, start, end);
+                                        rasterize_line(&args, pipeline, &blend, material_shader!(start), start, end);
                                     }
                                 }
                             }
 
-                            for line in generated_primitives.lines.chunks(2) {
-                                rasterize_line(&args, pipeline, &blend, &fragment_shader, &line[0], &line[1]);
+                            for (j, line) in generated_primitives.lines.chunks(2).enumerate() {
+                                if RejectedPrimitives::is_rejected(&rejected.lines, j) { continue; }
+
+                                rasterize_line(&args, pipeline, &blend, material_shader!(line[0]), &line[0], &line[1]);
                             }
 
-                            if T::is_point() {
-                                if let Some(ref indexed_vertices) = *indexed_vertices {
-                                    for index in &mesh.indices {
-                                        let point = &indexed_vertices[*index];
+                            if let Some(ref order) = *order {
+                                for &source in &order.points {
+                                    let point = match source {
+                                        PrimitiveSource::Indexed(j) => {
+                                            if RejectedPrimitives::is_rejected(&rejected.indexed, j) { continue; }
+
+                                            &indexed_vertices.unwrap()[mesh.indices[j].to_usize()]
+                                        }
+                                        PrimitiveSource::Generated(j) => {
+                                            if RejectedPrimitives::is_rejected(&rejected.points, j) { continue; }
 
-                                        rasterize_point(&args, pipeline, &blend, &fragment_shader, point);
+                                            &generated_primitives.points[j]
+                                        }
+                                    };
+
+                                    rasterize_point(&args, pipeline, &blend, material_shader!(point), point);
+                                }
+                            } else {
+                                if T::is_point() {
+                                    if let Some(indexed_vertices) = indexed_vertices {
+                                        for (j, index) in mesh.indices.iter().enumerate() {
+                                            if RejectedPrimitives::is_rejected(&rejected.indexed, j) { continue; }
+
+                                            let point = &indexed_vertices[index.to_usize()];
+
+                                            rasterize_point(&args, pipeline, &blend, material_shader!(point), point);
+                                        }
                                     }
                                 }
+
+                                for (j, point) in generated_primitives.points.iter().enumerate() {
+                                    if RejectedPrimitives::is_rejected(&rejected.points, j) { continue; }
+
+                                    rasterize_point(&args, pipeline, &blend, material_shader!(point), point);
+                                }
                             }
 
-                            for point in &generated_primitives.points {
-                                rasterize_point(&args, pipeline, &blend, &fragment_shader, point);
+                            completed_tiles.push(i);
+
+                            if let Some(ref progress) = *progress {
+                                let done = tiles_done.fetch_add(1, Ordering::Relaxed) + 1;
+
+                                progress(done, tiles.len());
                             }
                         } else {
                             break;
                         }
                     }
+
+                    completed.lock().extend(completed_tiles);
                 });
             }
         });
+
+        let completed_indices = completed.into_inner();
+
+        let mut done = vec![false; end_tile - first_tile];
+
+        for &i in &completed_indices {
+            done[i - first_tile] = true;
+        }
+
+        let mut report = BudgetReport::default();
+
+        for (i, (&tile, done)) in tiles[first_tile..end_tile].iter().zip(done).enumerate() {
+            let i = first_tile + i;
+
+            if is_finished(i) { continue; }
+
+            if done { report.completed.push(tile); } else { report.skipped.push(tile); }
+        }
+
+        if let Some(finished) = finished {
+            for tile in completed_indices {
+                finished[tile] = true;
+            }
+        }
+
+        trace_event!(tiles = report.completed.len(), skipped = report.skipped.len(), "tiles processed");
+
+        // Only the tiles of the first part match the scissor rectangle the arena keeps them for
+        tiles.truncate(part_ends[0]);
+
+        pipeline.arena_mut().return_tiles(tiles);
+
+        report
     }
 }
\ No newline at end of file
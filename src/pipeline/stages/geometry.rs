@@ -1,23 +1,88 @@
 use std::marker::PhantomData;
 use std::sync::Arc;
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{ptr, mem};
+use std::cmp::{min, max};
 
 use smallvec::SmallVec;
-use parking_lot::Mutex;
+use num_traits::{Float, One};
 
-use ::parallel::{TrustedThreadSafe, CACHE_LINE_SIZE, Mapper};
+use ::parallel::{TrustedThreadSafe, CACHE_LINE_SIZE, CHUNKS_PER_THREAD, Mapper};
 
 use ::primitive::{Primitive, PrimitiveRef, Point, Line, Triangle};
-use ::mesh::{Vertex, Mesh};
-use ::geometry::{ClipVertex, Viewport, ScreenVertex, ALL_CLIPPING_PLANES, ClippingPlane};
+use ::numeric::FloatScalar;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::geometry::{HasDimensions, ClipVertex, Viewport, ScreenVertex, ALL_CLIPPING_PLANES, ClippingPlane};
 use ::interpolate::Interpolate;
 use ::pipeline::storage::{PrimitiveStorage, SeparablePrimitiveStorage, SeparableScreenPrimitiveStorage};
 use ::pipeline::{PipelineObject, FragmentShader};
-use ::pipeline::stages::fragment::DEFAULT_TILE_SIZE;
+use ::pipeline::feedback::TransformFeedback;
+use ::pipeline::arena::PrimitiveCapacity;
 
 use ::pipeline::types::{PipelineUniforms, StencilValue};
 
+/// Smallest number of primitives processed by a single job of the geometry shader
+const MIN_PRIMITIVES_PER_CHUNK: usize = 64;
+
+/// Order of the planes primitives are clipped against by `GeometryShader::clip_primitives_guard_band`,
+/// with the depth planes first, so nothing behind the viewer is left when clipping against the sides
+const GUARD_BAND_CLIP_ORDER: [ClippingPlane; 6] = [
+    ClippingPlane::Near,
+    ClippingPlane::Far,
+    ClippingPlane::Left,
+    ClippingPlane::Right,
+    ClippingPlane::Top,
+    ClippingPlane::Bottom,
+];
+
+/// Returns true if all vertices lie outside of the same plane of the viewport, so the primitive can't be visible
+#[inline]
+fn outside_viewport<N: FloatScalar, K>(vertices: &[&ClipVertex<N, K>]) -> bool {
+    ALL_CLIPPING_PLANES.iter().any(|plane| vertices.iter().all(|v| !plane.has_inside(v)))
+}
+
+/// Returns true if all vertices lie within the guard band and between the near and far planes
+#[inline]
+fn inside_guard_band<N: FloatScalar, K>(vertices: &[&ClipVertex<N, K>], guard_band: N) -> bool {
+    ALL_CLIPPING_PLANES.iter().all(|plane| vertices.iter().all(|v| plane.has_inside_scaled(v, guard_band)))
+}
+
+/// Input primitives a range of vertices refers to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PrimitiveInput {
+    Points,
+    Lines,
+    Tris,
+    Indexed,
+}
+
+/// Contiguous range of vertices or indices making up whole primitives
+#[derive(Debug, Clone, Copy)]
+struct PrimitiveRange {
+    input: PrimitiveInput,
+    start: usize,
+    end: usize,
+    stride: usize,
+}
+
+/// Splits `len` vertices of primitives with `stride` vertices each into about `chunks` ranges
+fn split_ranges(ranges: &mut Vec<PrimitiveRange>, input: PrimitiveInput, len: usize, stride: usize, chunks: usize) {
+    let chunks = max(chunks, 1);
+
+    let primitives = (len + stride - 1) / stride;
+
+    let chunk_len = max(MIN_PRIMITIVES_PER_CHUNK, (primitives + chunks - 1) / chunks) * stride;
+
+    let mut start = 0;
+
+    while start < len {
+        let end = min(start + chunk_len, len);
+
+        ranges.push(PrimitiveRange { input, start, end, stride });
+
+        start = end;
+    }
+}
+
 /// Geometry shader stage
 ///
 /// The geometry shader can edit and generate new vertices from the output of the vertex shader.
@@ -26,21 +91,21 @@ use ::pipeline::types::{PipelineUniforms, StencilValue};
 /// and geometry visualisations like normal vector lines.
 ///
 /// The geometry shader can be ran multiple times.
-pub struct GeometryShader<'a, P: 'a, V: Vertex, T, K> where P: PipelineObject {
+pub struct GeometryShader<'a, P: 'a, V: Vertex, T, K, I: MeshIndex = usize> where P: PipelineObject {
     pub ( in ::pipeline) pipeline: &'a mut P,
-    pub ( in ::pipeline) mesh: Arc<Mesh<V>>,
+    pub ( in ::pipeline) mesh: Arc<Mesh<V, I>>,
     pub ( in ::pipeline) indexed_primitive: PhantomData<T>,
     pub ( in ::pipeline) stencil_value: StencilValue<P>,
     pub ( in ::pipeline) indexed_vertices: Option<Vec<ClipVertex<V::Scalar, K>>>,
     pub ( in ::pipeline) generated_primitives: SeparablePrimitiveStorage<V::Scalar, K>,
 }
 
-impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject, V: Vertex {
+impl<'a, P: 'a, V, T, K, I> GeometryShader<'a, P, V, T, K, I> where P: PipelineObject, V: Vertex, I: MeshIndex {
     /// Duplicate the geometry shader, and copies any processed geometry.
     ///
     /// Geometry are not synced between duplicated geometry shaders.
     #[must_use]
-    pub fn duplicate<'b>(&'b mut self) -> GeometryShader<'b, P, V, T, K> where 'a: 'b, K: Clone {
+    pub fn duplicate<'b>(&'b mut self) -> GeometryShader<'b, P, V, T, K, I> where 'a: 'b, K: Clone {
         GeometryShader {
             pipeline: self.pipeline,
             mesh: self.mesh.clone(),
@@ -52,17 +117,56 @@ impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
     }
 }
 
-impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
-                                                              V: Vertex,
-                                                              T: Primitive,
-                                                              K: Send + Sync + Interpolate {
+impl<'a, P: 'a, V, T, K, I> GeometryShader<'a, P, V, T, K, I> where P: PipelineObject,
+                                                                    V: Vertex,
+                                                                    T: Primitive,
+                                                                    K: Send + Sync + Interpolate,
+                                                                    I: MeshIndex {
+    /// Copies the current primitive stream, including any primitives from the mesh itself, into a `TransformFeedback`.
+    ///
+    /// Use `clip_primitives` first to capture clipped primitives.
+    pub fn capture(&self) -> TransformFeedback<V::Scalar, K> where K: Clone {
+        let SeparablePrimitiveStorage { ref points, ref lines, ref tris, ref tri_indices } = self.generated_primitives;
+
+        let mut feedback = TransformFeedback {
+            points: points.clone(),
+            lines: lines.clone(),
+            tris: tri_indices.iter().map(|&index| tris[index].clone()).collect(),
+        };
+
+        if let Some(ref indexed_vertices) = self.indexed_vertices {
+            let target = if T::is_point() {
+                &mut feedback.points
+            } else if T::is_line() {
+                &mut feedback.lines
+            } else {
+                &mut feedback.tris
+            };
+
+            target.extend(self.mesh.indices.iter().map(|index| indexed_vertices[index.to_usize()].clone()));
+        }
+
+        feedback
+    }
+
+    /// Finishes geometry processing using the viewport of the current pipeline render state,
+    /// or a viewport covering the whole framebuffer if it has none.
+    #[must_use]
+    pub fn finish_default(self) -> FragmentShader<'a, P, V, T, K, (), I> {
+        let viewport = self.pipeline.render_state().viewport_or_full(self.pipeline.framebuffer().dimensions());
+
+        self.finish(viewport)
+    }
+
     #[must_use]
-    pub fn finish(self, viewport: Viewport<V::Scalar>) -> FragmentShader<'a, P, V, T, K, ()> {
+    pub fn finish(self, viewport: Viewport<V::Scalar>) -> FragmentShader<'a, P, V, T, K, (), I> {
         let GeometryShader { pipeline, mesh, indexed_vertices, stencil_value, generated_primitives, .. } = self;
 
-        let SeparablePrimitiveStorage { mut points, mut lines, mut tris } = generated_primitives;
+        let SeparablePrimitiveStorage { mut points, mut lines, mut tris, tri_indices } = generated_primitives;
 
         let (indexed_screen_vertices, generated_primitives) = {
+            profile_scope!("viewport");
+
             let pool = pipeline.threadpool_mut();
 
             let thread_count = pool.thread_count();
@@ -95,6 +199,7 @@ impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
                 points: point_mapper.into_target(),
                 lines: line_mapper.into_target(),
                 tris: tri_mapper.into_target(),
+                tri_indices,
             };
 
             let indexed_vertices = indexed_mapper.map(|im| im.into_target());
@@ -114,136 +219,110 @@ impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
             unsafe { indexed_vertices.set_len(0); }
         }
 
-        FragmentShader {
-            pipeline: pipeline,
-            mesh: mesh,
-            indexed_primitive: PhantomData,
-            stencil_value,
-            indexed_vertices: Arc::new(indexed_screen_vertices),
-            generated_primitives: Arc::new(generated_primitives),
-            cull_faces: None,
-            blend: (),
-            antialiased_lines: false,
-            tile_size: DEFAULT_TILE_SIZE,
-        }
+        FragmentShader::from_parts(pipeline, mesh, stencil_value,
+                                   Arc::new(indexed_screen_vertices),
+                                   Arc::new(generated_primitives))
     }
 
+    /// Runs the geometry shader on every primitive in parallel.
+    ///
+    /// Primitives are split into contiguous ranges with their own output, which are merged in order at the end,
+    /// so emitted primitives keep the order of the primitives they came from.
     #[must_use]
-    pub fn run<S, Y>(self, geometry_shader: S) -> GeometryShader<'a, P, V, T, Y>
-        where S: for<'s, 'p> Fn(PrimitiveStorage<'s, V::Scalar, Y>, PrimitiveRef<'p, V::Scalar, K>, &PipelineUniforms<P>) + Send + Sync + 'static,
+    pub fn run<S, Y>(self, geometry_shader: S) -> GeometryShader<'a, P, V, T, Y, I>
+        where S: for<'s, 'p> Fn(PrimitiveStorage<'s, V::Scalar, Y>, PrimitiveRef<'p, V::Scalar, K>, &PipelineUniforms<P>) + Send + Sync,
               Y: Send + Sync + Interpolate {
         let GeometryShader { pipeline, mesh, indexed_vertices, stencil_value, generated_primitives, .. } = self;
 
         let replaced_primitives = {
-            let SeparablePrimitiveStorage { ref points, ref lines, ref tris } = generated_primitives;
+            profile_scope!("geometry");
+
+            let SeparablePrimitiveStorage { ref points, ref lines, ref tris, ref tri_indices } = generated_primitives;
 
             let mut replaced_primitives_unmerged = {
+                let capacity = pipeline.arena().primitive_capacity();
+
                 let (uniforms, _, pool) = pipeline.all_mut();
 
-                let thread_count = pool.thread_count();
+                let chunks = pool.thread_count() as usize * CHUNKS_PER_THREAD;
 
-                let point_i = AtomicUsize::new(0);
-                let line_i = AtomicUsize::new(0);
-                let tri_i = AtomicUsize::new(0);
-                let indexed_i = AtomicUsize::new(0);
+                let mut ranges = Vec::new();
 
-                let replaced_primitives_unmerged = Mutex::new(Vec::with_capacity(pool.thread_count() as usize));
+                split_ranges(&mut ranges, PrimitiveInput::Points, points.len(), Point::num_vertices(), chunks);
+                split_ranges(&mut ranges, PrimitiveInput::Lines, lines.len(), Line::num_vertices(), chunks);
+                split_ranges(&mut ranges, PrimitiveInput::Tris, tri_indices.len(), Triangle::num_vertices(), chunks);
 
-                pool.scoped(|scope| {
-                    for _ in 0..thread_count {
-                        scope.execute(|| {
-                            let mut storage = SeparablePrimitiveStorage::default();
-
-                            loop {
-                                let i = point_i.fetch_add(Point::num_vertices(), Ordering::Relaxed);
-
-                                if i < points.len() {
-                                    geometry_shader(
-                                        PrimitiveStorage { inner: &mut storage },
-                                        Point::create_ref_from_vertices(&points[i..]),
-                                        uniforms,
-                                    );
-                                } else {
-                                    break;
-                                }
-                            }
+                if indexed_vertices.is_some() {
+                    split_ranges(&mut ranges, PrimitiveInput::Indexed, mesh.indices.len(), T::num_vertices(), chunks);
+                }
 
-                            loop {
-                                let i = line_i.fetch_add(Line::num_vertices(), Ordering::Relaxed);
-
-                                if i < lines.len() {
-                                    geometry_shader(
-                                        PrimitiveStorage { inner: &mut storage },
-                                        Line::create_ref_from_vertices(&lines[i..]),
-                                        uniforms,
-                                    );
-                                } else {
-                                    break;
-                                }
-                            }
+                // Start each chunk with its share of the primitives generated by earlier passes
+                let capacity = capacity.split(ranges.len());
 
-                            loop {
-                                let i = tri_i.fetch_add(Triangle::num_vertices(), Ordering::Relaxed);
-
-                                if i < tris.len() {
-                                    geometry_shader(
-                                        PrimitiveStorage { inner: &mut storage },
-                                        Triangle::create_ref_from_vertices(&tris[i..]),
-                                        uniforms,
-                                    );
-                                } else {
-                                    break;
-                                }
-                            }
+                let mut replaced_primitives_unmerged: Vec<SeparablePrimitiveStorage<V::Scalar, Y>> =
+                    ranges.iter().map(|_| SeparablePrimitiveStorage::default()).collect();
 
-                            if let Some(ref indexed_vertices) = indexed_vertices {
-                                let len = mesh.indices.len();
-
-                                loop {
-                                    let mut i = indexed_i.fetch_add(T::num_vertices(), Ordering::Relaxed);
-
-                                    if i < len {
-                                        geometry_shader(
-                                            PrimitiveStorage { inner: &mut storage },
-                                            T::create_ref_from_indexed_vertices(&indexed_vertices, &mesh.indices[i..]),
-                                            uniforms,
-                                        );
-                                    } else {
-                                        break;
+                let indexed_vertices = &indexed_vertices;
+                let mesh = &mesh;
+                let geometry_shader = &geometry_shader;
+
+                pool.scoped(|scope| {
+                    for (range, storage) in ranges.iter().zip(replaced_primitives_unmerged.iter_mut()) {
+                        scope.execute(move || {
+                            *storage = SeparablePrimitiveStorage::with_capacity(capacity);
+
+                            let mut i = range.start;
+
+                            while i < range.end {
+                                let primitive = match range.input {
+                                    PrimitiveInput::Points => Point::create_ref_from_vertices(&points[i..]),
+                                    PrimitiveInput::Lines => Line::create_ref_from_vertices(&lines[i..]),
+                                    PrimitiveInput::Tris => Triangle::create_ref_from_indexed_vertices(tris, &tri_indices[i..]),
+                                    PrimitiveInput::Indexed => {
+                                        T::create_ref_from_indexed_vertices(indexed_vertices.as_ref().unwrap(), &mesh.indices[i..])
                                     }
-                                }
-                            }
+                                };
 
-                            let mut replaced_primitives_unmerged = replaced_primitives_unmerged.lock();
+                                geometry_shader(PrimitiveStorage { inner: &mut *storage }, primitive, uniforms);
 
-                            replaced_primitives_unmerged.push(storage);
+                                i += range.stride;
+                            }
                         });
                     }
                 });
 
-                replaced_primitives_unmerged.into_inner()
+                replaced_primitives_unmerged
             };
 
             let mut num_point_vertices = 0;
             let mut num_line_vertices = 0;
             let mut num_tri_vertices = 0;
+            let mut num_tri_indices = 0;
 
             for v in &replaced_primitives_unmerged {
                 num_point_vertices += v.points.len();
                 num_line_vertices += v.lines.len();
                 num_tri_vertices += v.tris.len();
+                num_tri_indices += v.tri_indices.len();
             }
 
             let mut storage = SeparablePrimitiveStorage {
                 points: Vec::with_capacity(num_point_vertices),
                 lines: Vec::with_capacity(num_line_vertices),
                 tris: Vec::with_capacity(num_tri_vertices),
+                tri_indices: Vec::with_capacity(num_tri_indices),
             };
 
             for v in &mut replaced_primitives_unmerged {
                 storage.append(v);
             }
 
+            pipeline.arena_mut().record_primitives(PrimitiveCapacity {
+                points: num_point_vertices,
+                lines: num_line_vertices,
+                tris: num_tri_vertices.max(num_tri_indices),
+            });
+
             storage
         };
 
@@ -284,7 +363,7 @@ impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
                     }
 
                     if polygon.len() == 3 {
-                        storage.inner.tris.extend_from_slice(&polygon);
+                        storage.emit_triangle(polygon[0].clone(), polygon[1].clone(), polygon[2].clone());
                     } else if polygon.len() > 3 {
                         let last = polygon.last().unwrap();
 
@@ -334,4 +413,93 @@ impl<'a, P: 'a, V, T, K> GeometryShader<'a, P, V, T, K> where P: PipelineObject,
             }
         })
     }
+
+    /// Clips primitives against a guard band around the viewport, `guard_band` times its size, like hardware does.
+    ///
+    /// Primitives partially outside of the viewport but within the guard band are passed through unchanged,
+    /// and the rasterizer only draws their pixels within the screen, which is cheaper than clipping them
+    /// and doesn't split them up into more triangles. Only primitives reaching beyond the guard band,
+    /// or crossing the near or far planes, are clipped geometrically, and only against the planes they cross.
+    /// Primitives entirely outside of the viewport are dropped.
+    ///
+    /// A larger guard band means less clipping, but rasterizing primitives far outside the screen
+    /// loses precision. Guard bands smaller than one clip against the viewport itself.
+    #[must_use]
+    pub fn clip_primitives_guard_band(self, guard_band: V::Scalar) -> Self where K: Clone + Interpolate {
+        let guard_band = guard_band.max(One::one());
+
+        self.run(move |mut storage, primitive, _| {
+            match primitive {
+                PrimitiveRef::Triangle { a, b, c } => {
+                    let vertices = [a, b, c];
+
+                    if outside_viewport(&vertices) {
+                        return;
+                    }
+
+                    if inside_guard_band(&vertices, guard_band) {
+                        return storage.emit_triangle(a.clone(), b.clone(), c.clone());
+                    }
+
+                    // Clipping a triangle against all six planes leaves at most nine vertices
+                    let mut polygon: SmallVec<[ClipVertex<V::Scalar, K>; 9]> = vertices.iter().map(|&v| v.clone()).collect();
+
+                    for &plane in GUARD_BAND_CLIP_ORDER.iter() {
+                        if polygon.iter().all(|v| plane.has_inside_scaled(v, guard_band)) {
+                            continue;
+                        }
+
+                        // Sutherland-Hodgman clipping against a single plane
+                        let mut clipped = SmallVec::new();
+
+                        for i in 0..polygon.len() {
+                            let (s, p) = (&polygon[i], &polygon[(i + 1) % polygon.len()]);
+
+                            let s_in = plane.has_inside_scaled(s, guard_band);
+
+                            if s_in {
+                                clipped.push(s.clone());
+                            }
+
+                            if s_in != plane.has_inside_scaled(p, guard_band) {
+                                clipped.push(plane.intersect_scaled(s, p, guard_band));
+                            }
+                        }
+
+                        polygon = clipped;
+
+                        if polygon.len() < 3 {
+                            return;
+                        }
+                    }
+
+                    storage.emit_triangle_fan(&polygon);
+                }
+                PrimitiveRef::Line { start, end } => {
+                    if outside_viewport(&[start, end]) {
+                        return;
+                    }
+
+                    let (mut start, mut end) = (start.clone(), end.clone());
+
+                    for &plane in GUARD_BAND_CLIP_ORDER.iter() {
+                        match (plane.has_inside_scaled(&start, guard_band), plane.has_inside_scaled(&end, guard_band)) {
+                            (true, true) => (),
+                            (false, false) => return,
+                            (true, false) => end = plane.intersect_scaled(&start, &end, guard_band),
+                            (false, true) => start = plane.intersect_scaled(&start, &end, guard_band),
+                        }
+                    }
+
+                    storage.emit_line(start, end)
+                }
+                PrimitiveRef::Point(point) => {
+                    // Points don't cover anything beyond the viewport
+                    if !outside_viewport(&[point]) {
+                        storage.emit_point(point.clone());
+                    }
+                }
+            }
+        })
+    }
 }
\ No newline at end of file
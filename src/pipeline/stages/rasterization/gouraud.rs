@@ -0,0 +1,144 @@
+use super::RasterArguments;
+use super::triangle::{snap_positions, interpolation_weights, is_finite_position};
+
+use num_traits::{Float, One, Zero, cast};
+use nalgebra::coordinates::XYZW;
+
+use ::color::{Color, ColorAlpha};
+use ::color::blend::Blend;
+use ::pixels::{PixelRead, PixelWrite};
+use ::framebuffer::UnsafeFramebuffer;
+use ::attachments::depth::Depth;
+use ::mesh::Vertex;
+use ::geometry::{Coordinate, ScreenVertex, FaceWinding};
+use ::interpolate::Interpolate;
+
+use ::pipeline::PipelineObject;
+
+use ::framebuffer::types::DepthAttachment;
+use ::pipeline::types::Pixel;
+
+/// Rasterizes a triangle with colors computed per-vertex, without invoking a fragment shader.
+///
+/// Covers exactly the same pixels as `rasterize_triangle`, but steps the barycentric coordinates
+/// incrementally along each row and only interpolates depth and the vertex colors.
+pub fn rasterize_triangle_gouraud<P, V, K, B, G>(args: &RasterArguments<P, V>,
+                                                 pipeline: &mut P,
+                                                 blend: B,
+                                                 color: G,
+                                                 a: &ScreenVertex<V::Scalar, K>,
+                                                 b: &ScreenVertex<V::Scalar, K>,
+                                                 c: &ScreenVertex<V::Scalar, K>)
+    where P: PipelineObject,
+          V: Vertex,
+          K: Send + Sync + Interpolate,
+          B: Blend<Pixel<P>>,
+          G: Fn(&K) -> Pixel<P> {
+    let RasterArguments {
+        stride,
+        tile,
+        pixel_end,
+        stencil_value,
+        stencil_test,
+        stencil_op,
+        color_mask,
+        cull_faces,
+        depth_test,
+        interpolation,
+        vertex_snap,
+        field,
+        pixel_center,
+        ..
+    } = *args;
+
+    let (_, framebuffer, _) = pipeline.all_mut();
+
+    let (pa, pb, pc) = snap_positions(vertex_snap, a.position, b.position, c.position);
+
+    if !(is_finite_position(&pa) && is_finite_position(&pb) && is_finite_position(&pc)) {
+        return;
+    }
+
+    let XYZW { x: x1, y: y1, z: z1, w: w1 } = *pa;
+    let XYZW { x: x2, y: y2, z: z2, w: w2 } = *pb;
+    let XYZW { x: x3, y: y3, z: z3, w: w3 } = *pc;
+
+    if let Some(winding) = cull_faces {
+        // Shoelace algorithm for a triangle
+        let area = x1 * y2 + x2 * y3 + x3 * y1 - x2 * y1 - x3 * y2 - x1 * y3;
+
+        if winding == if area.is_sign_negative() { FaceWinding::Clockwise } else { FaceWinding::CounterClockwise } {
+            return;
+        }
+    }
+
+    let det = (y2 - y3) * (x1 - x3) + (x3 - x2) * (y1 - y3);
+
+    macro_rules! clamp_as_int {
+        ($value:expr, $min:expr, $max:expr) => {{
+            let value = $value; let min = $min; let max = $max;
+            if value < cast(min).unwrap() { min } else if value > cast(max).unwrap() { max } else { cast(value).unwrap() }
+        }}
+    }
+
+    // Neighboring tiles share their edges, so only one of them draws triangles on them
+    let last = Coordinate::new(pixel_end.x - 1, pixel_end.y - 1);
+
+    let min = Coordinate::new(clamp_as_int!(x1.min(x2).min(x3), tile.0.x, last.x),
+                              clamp_as_int!(y1.min(y2).min(y3), tile.0.y, last.y));
+
+    let max = Coordinate::new(clamp_as_int!(x1.max(x2).max(x3), tile.0.x, last.x),
+                              clamp_as_int!(y1.max(y2).max(y3), tile.0.y, last.y));
+
+    let offset: V::Scalar = pixel_center.offset();
+
+    // Change in the barycentric coordinates for every step to the right
+    let du = (y2 - y3) / det;
+    let dv = (y3 - y1) / det;
+
+    for py in min.y..(max.y + 1) {
+        let y = cast::<_, V::Scalar>(py).unwrap() + offset;
+        let x = cast::<_, V::Scalar>(min.x).unwrap() + offset;
+
+        let mut u = ((y2 - y3) * (x - x3) + (x3 - x2) * (y - y3)) / det;
+        let mut v = ((y3 - y1) * (x - x3) + (x1 - x3) * (y - y3)) / det;
+
+        for px in min.x..(max.x + 1) {
+            let index = Coordinate::new(px, py).into_strided_index(stride);
+
+            let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
+
+            if field.covers(px, py) && stencil_test.test(framebuffer_stencil_value, stencil_value) {
+                unsafe { framebuffer.set_stencil_unchecked(index, stencil_op.op(framebuffer_stencil_value, stencil_value)); }
+
+                let w = <V::Scalar as One>::one() - u - v;
+
+                if !(u < Zero::zero() || v < Zero::zero() || w < Zero::zero()) {
+                    let z = u * z1 + v * z2 + w * z3;
+
+                    if z < Zero::zero() {
+                        let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
+
+                        let dt = unsafe { framebuffer.get_depth_unchecked(index) };
+
+                        if depth_test.test(d, dt) {
+                            let (u, v, w) = interpolation_weights(interpolation, (u, v, w), (w1, w2, w3));
+
+                            let shaded = color(&Interpolate::barycentric_interpolate(u, &a.uniforms, v, &b.uniforms, w, &c.uniforms));
+
+                            let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                            unsafe {
+                                framebuffer.set_pixel_unchecked(index, blend.blend(shaded, p).mask_channels(p, color_mask));
+                                framebuffer.set_depth_unchecked(index, d);
+                            }
+                        }
+                    }
+                }
+            }
+
+            u = u + du;
+            v = v + dv;
+        }
+    }
+}
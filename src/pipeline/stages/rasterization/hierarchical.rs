@@ -0,0 +1,332 @@
+use super::RasterArguments;
+use super::triangle::{snap_positions, interpolation_weights, is_finite_position};
+
+use num_traits::{Float, One, Zero, cast};
+use nalgebra::coordinates::XYZW;
+
+use ::numeric::FloatScalar;
+use ::numeric::utils::min;
+use ::color::{Color, ColorAlpha};
+use ::color::blend::Blend;
+use ::pixels::{PixelRead, PixelWrite};
+use ::framebuffer::UnsafeFramebuffer;
+use ::attachments::depth::Depth;
+use ::mesh::Vertex;
+use ::geometry::{Coordinate, ScreenVertex, FaceWinding};
+use ::interpolate::Interpolate;
+
+use ::pipeline::PipelineObject;
+
+use ::framebuffer::types::DepthAttachment;
+use ::pipeline::types::{PipelineUniforms, Pixel};
+
+use ::pipeline::stages::fragment::{Fragment, TargetColors};
+
+/// Width and height of the blocks triangles are coarsely tested against
+pub const BLOCK_SIZE: u32 = 8;
+
+/// Linear function `a * x + b * y + c` of a screen-space position,
+/// which is non-negative on the inner side of a triangle edge.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EdgeFunction<N: FloatScalar> {
+    pub a: N,
+    pub b: N,
+    pub c: N,
+}
+
+impl<N: FloatScalar> EdgeFunction<N> {
+    #[inline]
+    pub fn at(&self, x: N, y: N) -> N {
+        self.a * x + self.b * y + self.c
+    }
+}
+
+/// Edge functions of a triangle, which are also its barycentric coordinates,
+/// or `None` for degenerate triangles.
+pub fn barycentric_edges<N: FloatScalar>((x1, y1): (N, N), (x2, y2): (N, N), (x3, y3): (N, N)) -> Option<[EdgeFunction<N>; 3]> {
+    let det = (y2 - y3) * (x1 - x3) + (x3 - x2) * (y1 - y3);
+
+    if det == N::zero() {
+        return None;
+    }
+
+    let (ua, ub) = ((y2 - y3) / det, (x3 - x2) / det);
+    let (va, vb) = ((y3 - y1) / det, (x1 - x3) / det);
+
+    let u = EdgeFunction { a: ua, b: ub, c: -(ua * x3 + ub * y3) };
+    let v = EdgeFunction { a: va, b: vb, c: -(va * x3 + vb * y3) };
+
+    // The barycentric coordinates always sum to one
+    let w = EdgeFunction { a: -(u.a + v.a), b: -(u.b + v.b), c: N::one() - u.c - v.c };
+
+    Some([u, v, w])
+}
+
+/// Coverage of a block of pixels by a triangle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockCoverage {
+    /// No pixel centers are inside the triangle
+    Outside,
+    /// Some pixel centers may be inside the triangle, so each has to be tested
+    Partial,
+    /// All pixel centers are inside the triangle
+    Inside,
+}
+
+/// Tests the pixel centers in the rectangle from `min` to `max` against the edges of a triangle.
+///
+/// Edge functions are linear, so their extremes over the rectangle are at its corners.
+pub fn classify_block<N: FloatScalar>(edges: &[EdgeFunction<N>; 3], min: (N, N), max: (N, N)) -> BlockCoverage {
+    let corners = [(min.0, min.1), (max.0, min.1), (min.0, max.1), (max.0, max.1)];
+
+    let mut coverage = BlockCoverage::Inside;
+
+    for edge in edges {
+        let inside = corners.iter().filter(|&&(x, y)| edge.at(x, y) >= N::zero()).count();
+
+        if inside == 0 {
+            return BlockCoverage::Outside;
+        } else if inside < corners.len() {
+            coverage = BlockCoverage::Partial;
+        }
+    }
+
+    coverage
+}
+
+/// Rasterizes a triangle by first testing blocks of `BLOCK_SIZE` by `BLOCK_SIZE` pixels against its edges.
+///
+/// Blocks entirely outside the triangle are skipped, and blocks entirely inside are shaded without testing
+/// individual pixels, so only blocks along the edges of the triangle are tested per-pixel.
+/// Produces the same image as `rasterize_triangle`, but the stencil buffer is only tested and updated
+/// for pixels inside the triangle.
+pub fn rasterize_triangle_hierarchical<P, V, K, B, F>(args: &RasterArguments<P, V>,
+                                                      pipeline: &mut P,
+                                                      blend: B,
+                                                      fragment_shader: F,
+                                                      a: &ScreenVertex<V::Scalar, K>,
+                                                      b: &ScreenVertex<V::Scalar, K>,
+                                                      c: &ScreenVertex<V::Scalar, K>)
+    where P: PipelineObject,
+          V: Vertex,
+          K: Send + Sync + Interpolate,
+          B: Blend<Pixel<P>>,
+          F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+    let RasterArguments {
+        dimensions,
+        stride,
+        tile,
+        pixel_end,
+        stencil_value,
+        stencil_test,
+        stencil_op,
+        color_mask,
+        cull_faces,
+        depth_test,
+        interpolation,
+        vertex_snap,
+        field,
+        pixel_center,
+        ..
+    } = *args;
+
+    let (uniforms, framebuffer, _) = pipeline.all_mut();
+
+    let (pa, pb, pc) = snap_positions(vertex_snap, a.position, b.position, c.position);
+
+    if !(is_finite_position(&pa) && is_finite_position(&pb) && is_finite_position(&pc)) {
+        return;
+    }
+
+    let XYZW { x: x1, y: y1, .. } = *pa;
+    let XYZW { x: x2, y: y2, .. } = *pb;
+    let XYZW { x: x3, y: y3, .. } = *pc;
+
+    if let Some(winding) = cull_faces {
+        // Shoelace algorithm for a triangle
+        let area = x1 * y2 + x2 * y3 + x3 * y1 - x2 * y1 - x3 * y2 - x1 * y3;
+
+        if winding == if area.is_sign_negative() { FaceWinding::Clockwise } else { FaceWinding::CounterClockwise } {
+            return;
+        }
+    }
+
+    let edges = match barycentric_edges((x1, y1), (x2, y2), (x3, y3)) {
+        Some(edges) => edges,
+        None => return,
+    };
+
+    macro_rules! clamp_as_int {
+        ($value:expr, $min:expr, $max:expr) => {{
+            let value = $value; let min = $min; let max = $max;
+            if value < cast(min).unwrap() { min } else if value > cast(max).unwrap() { max } else { cast(value).unwrap() }
+        }}
+    }
+
+    // Neighboring tiles share their edges, so only one of them draws triangles on them
+    let last = Coordinate::new(pixel_end.x - 1, pixel_end.y - 1);
+
+    let bounds_min = Coordinate::new(clamp_as_int!(x1.min(x2).min(x3), tile.0.x, last.x),
+                                     clamp_as_int!(y1.min(y2).min(y3), tile.0.y, last.y));
+
+    let bounds_max = Coordinate::new(clamp_as_int!(x1.max(x2).max(x3), tile.0.x, last.x),
+                                     clamp_as_int!(y1.max(y2).max(y3), tile.0.y, last.y));
+
+    let offset: V::Scalar = pixel_center.offset();
+
+    let center = |p: u32| cast::<_, V::Scalar>(p).unwrap() + offset;
+
+    let mut shade = |index: usize, u: V::Scalar, v: V::Scalar, w: V::Scalar| {
+        let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
+
+        if !stencil_test.test(framebuffer_stencil_value, stencil_value) {
+            return;
+        }
+
+        unsafe { framebuffer.set_stencil_unchecked(index, stencil_op.op(framebuffer_stencil_value, stencil_value)); }
+
+        let position = Interpolate::barycentric_interpolate(u, &pa, v, &pb, w, &pc);
+
+        let z = position.z;
+
+        if !(z < Zero::zero()) {
+            return;
+        }
+
+        let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
+
+        let dt = unsafe { framebuffer.get_depth_unchecked(index) };
+
+        if !depth_test.test(d, dt) {
+            return;
+        }
+
+        let (u, v, w) = interpolation_weights(interpolation, (u, v, w), (pa.w, pb.w, pc.w));
+
+        let fragment = fragment_shader(&ScreenVertex {
+            position,
+            uniforms: Interpolate::barycentric_interpolate(u, &a.uniforms,
+                                                           v, &b.uniforms,
+                                                           w, &c.uniforms),
+        }, uniforms);
+
+        match fragment {
+            Fragment::Discard => (),
+            Fragment::Color(c) => {
+                let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                unsafe {
+                    framebuffer.set_pixel_unchecked(index, blend.blend(c, p).mask_channels(p, color_mask));
+                    framebuffer.set_depth_unchecked(index, d);
+                }
+            }
+            Fragment::Targets(TargetColors { colors: c, mask }) => if mask != 0 {
+                let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                unsafe {
+                    framebuffer.set_pixel_unchecked(index, blend.blend(c, p).select_targets(p, mask).mask_channels(p, color_mask));
+                    framebuffer.set_depth_unchecked(index, d);
+                }
+            },
+        }
+    };
+
+    let (u_edge, v_edge) = (edges[0], edges[1]);
+
+    let mut block_y = bounds_min.y;
+
+    while block_y <= bounds_max.y {
+        let block_max_y = min(block_y + BLOCK_SIZE - 1, bounds_max.y);
+
+        let mut block_x = bounds_min.x;
+
+        while block_x <= bounds_max.x {
+            let block_max_x = min(block_x + BLOCK_SIZE - 1, bounds_max.x);
+
+            let coverage = classify_block(&edges, (center(block_x), center(block_y)), (center(block_max_x), center(block_max_y)));
+
+            if coverage != BlockCoverage::Outside {
+                for py in block_y..(block_max_y + 1) {
+                    let (x, y) = (center(block_x), center(py));
+
+                    let mut u = u_edge.at(x, y);
+                    let mut v = v_edge.at(x, y);
+
+                    for px in block_x..(block_max_x + 1) {
+                        let index = Coordinate::new(px, py).into_strided_index(stride);
+
+                        debug_assert!(index < stride * dimensions.height as usize);
+
+                        let w = <V::Scalar as One>::one() - u - v;
+
+                        let inside = coverage == BlockCoverage::Inside || !(u < Zero::zero() || v < Zero::zero() || w < Zero::zero());
+
+                        if inside && field.covers(px, py) {
+                            shade(index, u, v, w);
+                        }
+
+                        u = u + u_edge.a;
+                        v = v + v_edge.a;
+                    }
+                }
+            }
+
+            block_x += BLOCK_SIZE;
+        }
+
+        block_y += BLOCK_SIZE;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_classify_block() {
+        let edges = barycentric_edges((2.0f64, 2.0), (60.0, 4.0), (20.0, 50.0)).unwrap();
+
+        let mut counts = [0; 3];
+
+        for by in 0..8 {
+            for bx in 0..8 {
+                let (x0, y0) = (bx as f64 * 8.0 + 0.5, by as f64 * 8.0 + 0.5);
+                let (x1, y1) = (x0 + 7.0, y0 + 7.0);
+
+                let coverage = classify_block(&edges, (x0, y0), (x1, y1));
+
+                let mut inside = 0;
+
+                for py in 0..8 {
+                    for px in 0..8 {
+                        let (x, y) = (x0 + px as f64, y0 + py as f64);
+
+                        if edges.iter().all(|edge| edge.at(x, y) >= 0.0) {
+                            inside += 1;
+                        }
+                    }
+                }
+
+                match coverage {
+                    BlockCoverage::Outside => { assert_eq!(inside, 0); counts[0] += 1; }
+                    BlockCoverage::Inside => { assert_eq!(inside, 64); counts[2] += 1; }
+                    BlockCoverage::Partial => { counts[1] += 1; }
+                }
+            }
+        }
+
+        // A triangle this size has blocks of every kind
+        assert!(counts.iter().all(|&count| count > 0));
+    }
+
+    #[test]
+    fn test_barycentric_edges() {
+        let edges = barycentric_edges((0.0f64, 0.0), (4.0, 0.0), (0.0, 4.0)).unwrap();
+
+        assert!((edges[0].at(0.0, 0.0) - 1.0).abs() < 1e-12);
+        assert!((edges[1].at(4.0, 0.0) - 1.0).abs() < 1e-12);
+        assert!((edges[2].at(0.0, 4.0) - 1.0).abs() < 1e-12);
+
+        assert!(barycentric_edges((0.0f64, 0.0), (1.0, 1.0), (2.0, 2.0)).is_none());
+    }
+}
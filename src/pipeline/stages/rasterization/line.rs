@@ -1,8 +1,10 @@
 use super::RasterArguments;
+use super::triangle::is_finite_position;
 
-use num_traits::{Float, Zero, NumCast, cast};
+use num_traits::{Float, Zero, One, cast};
 use nalgebra::coordinates::XYZW;
 
+use ::numeric::utils::{min, max};
 use ::color::{Color, ColorAlpha};
 use ::color::blend::Blend;
 use ::pixels::{PixelRead, PixelWrite};
@@ -17,7 +19,7 @@ use ::pipeline::PipelineObject;
 use ::framebuffer::types::DepthAttachment;
 use ::pipeline::types::{PipelineUniforms, Pixel};
 
-use ::pipeline::stages::fragment::Fragment;
+use ::pipeline::stages::fragment::{Fragment, TargetColors};
 
 pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                      pipeline: &mut P,
@@ -32,36 +34,86 @@ pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
           F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
     let RasterArguments {
         dimensions,
+        stride,
         tile,
+        pixel_end,
         bounds,
         stencil_value,
         stencil_test,
         stencil_op,
         antialiased_lines,
+        line_width,
+        color_mask,
         cull_faces,
+        depth_test,
+        field,
+        pixel_center,
+        ..
     } = *args;
 
     let (uniforms, framebuffer, _) = pipeline.all_mut();
 
-    use ::geometry::line::liang_barsky_iterative;
+    use ::geometry::line::liang_barsky_parametric;
+
+    if !(is_finite_position(&start.position) && is_finite_position(&end.position)) {
+        return;
+    }
 
     let XYZW { x: x1, y: y1, .. } = *start.position;
     let XYZW { x: x2, y: y2, .. } = *end.position;
 
-    if let Some(((x1, y1), (x2, y2))) = liang_barsky_iterative((x1, y1), (x2, y2), bounds) {
-        let d = (x1 - x2).hypot(y1 - y2);
+    let (line_start, line_end) = ((x1, y1), (x2, y2));
+
+    // Lines wider than the framebuffer cover all of it anyway, and would only take longer to walk
+    let widest = 2.0 * (dimensions.width as f64 + dimensions.height as f64);
+    let line_width = if line_width > widest { widest } else { line_width };
+
+    let wide = line_width > 1.0;
+
+    // Wide and antialiased lines cover pixels around the line itself, so clip against bounds grown by their reach,
+    // and leave out any pixels outside of the tile instead
+    let bounds = if wide || antialiased_lines {
+        let reach: V::Scalar = cast(line_width.max(1.0) / 2.0 + 1.0).unwrap();
+        let ((xmin, ymin), (xmax, ymax)) = bounds;
+
+        ((xmin - reach, ymin - reach), (xmax + reach, ymax + reach))
+    } else {
+        bounds
+    };
+
+    if let Some((t0, t1)) = liang_barsky_parametric((x1, y1), (x2, y2), bounds) {
+        // Interpolate varyings at the clipped endpoints, so pixels are interpolated along the visible segment only
+        let (start, end): (ScreenVertex<V::Scalar, K>, ScreenVertex<V::Scalar, K>) =
+            (Interpolate::linear_interpolate(t0, start, end), Interpolate::linear_interpolate(t1, start, end));
+
+        // Rounding can leave far away endpoints slightly outside of the bounds after clipping
+        let ((xmin, ymin), (xmax, ymax)) = bounds;
+
+        let clamp = |x: V::Scalar, y: V::Scalar| (x.max(xmin).min(xmax), y.max(ymin).min(ymax));
+
+        let (x1, y1) = clamp(start.position.x, start.position.y);
+        let (x2, y2) = clamp(end.position.x, end.position.y);
+
+        let (dx, dy) = (x2 - x1, y2 - y1);
+        let length_squared = dx * dx + dy * dy;
+
+        let (tile_min, _) = tile;
+
+        // Neighboring tiles share their edges, so only one of them draws lines on them,
+        // otherwise blended pixels on tile edges would be drawn twice
+        let (xend, yend) = (pixel_end.x as i64, pixel_end.y as i64);
 
         let rasterize_fragment = |x: i64, y: i64, alpha: f64| {
-            if x >= 0 && y >= 0 {
+            if x >= tile_min.x as i64 && y >= tile_min.y as i64 && x < xend && y < yend {
                 let coord = Coordinate::new(x as u32, y as u32);
 
-                let index = coord.into_index(dimensions);
+                let index = coord.into_strided_index(stride);
 
                 // Get stencil buffer value for this pixel
                 let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
 
                 // perform stencil test
-                if stencil_test.test(framebuffer_stencil_value, stencil_value) {
+                if field.covers(coord.x, coord.y) && stencil_test.test(framebuffer_stencil_value, stencil_value) {
                     // Calculate new stencil value
                     let new_stencil_value = stencil_op.op(framebuffer_stencil_value, stencil_value);
 
@@ -69,10 +121,17 @@ pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
                     unsafe { framebuffer.set_stencil_unchecked(index, new_stencil_value); }
 
                     // Real screen position should be in the center of the pixel.
-                    let (xf, yf) = (cast::<_, V::Scalar>(x).unwrap() + NumCast::from(0.5).unwrap(),
-                                    cast::<_, V::Scalar>(y).unwrap() + NumCast::from(0.5).unwrap());
+                    let (xf, yf) = (cast::<_, V::Scalar>(x).unwrap() + pixel_center.offset(),
+                                    cast::<_, V::Scalar>(y).unwrap() + pixel_center.offset());
+
+                    // Project the pixel center onto the line
+                    let t = if length_squared > Zero::zero() {
+                        ((xf - x1) * dx + (yf - y1) * dy) / length_squared
+                    } else {
+                        Zero::zero()
+                    };
 
-                    let t = (x1 - xf).hypot(y1 - yf) / d;
+                    let t = t.max(Zero::zero()).min(One::one());
 
                     let position = Interpolate::linear_interpolate(t, &start.position, &end.position);
 
@@ -83,8 +142,8 @@ pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
 
                         let dt = unsafe { framebuffer.get_depth_unchecked(index) };
 
-                        // Check if point is in front of other geometry
-                        if d >= dt {
+                        // Perform depth test against existing geometry
+                        if depth_test.test(d, dt) {
                             // Perform fragment shading
                             let fragment = fragment_shader(&ScreenVertex {
                                 position,
@@ -97,10 +156,18 @@ pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                     let p = unsafe { framebuffer.get_pixel_unchecked(index) };
 
                                     unsafe {
-                                        framebuffer.set_pixel_unchecked(index, blend.blend(c.mul_alpha(ColorAlpha::from_scalar(alpha)), p));
+                                        framebuffer.set_pixel_unchecked(index, blend.blend(c.mul_alpha(ColorAlpha::from_scalar(alpha)), p).mask_channels(p, color_mask));
                                         framebuffer.set_depth_unchecked(index, d);
                                     }
                                 }
+                                Fragment::Targets(TargetColors { colors: c, mask }) => if mask != 0 {
+                                    let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                                    unsafe {
+                                        framebuffer.set_pixel_unchecked(index, blend.blend(c.mul_alpha(ColorAlpha::from_scalar(alpha)), p).select_targets(p, mask).mask_channels(p, color_mask));
+                                        framebuffer.set_depth_unchecked(index, d);
+                                    }
+                                },
                             }
                         }
                     }
@@ -109,11 +176,36 @@ pub fn rasterize_line<P, V, K, B, F>(args: &RasterArguments<P, V>,
         };
 
         if antialiased_lines {
-            draw_line_xiaolin_wu(cast(x1).unwrap(), cast(y1).unwrap(),
-                                 cast(x2).unwrap(), cast(y2).unwrap(), rasterize_fragment);
+            draw_line_gupta_sproull(cast(x1).unwrap(), cast(y1).unwrap(),
+                                    cast(x2).unwrap(), cast(y2).unwrap(), line_width, rasterize_fragment);
+        } else if wide {
+            let mut rasterize_fragment = rasterize_fragment;
+
+            // Without antialiasing, pixels are either fully covered or not at all
+            draw_line_gupta_sproull(cast(x1).unwrap(), cast(y1).unwrap(),
+                                    cast(x2).unwrap(), cast(y2).unwrap(), line_width, |x, y, coverage| {
+                if coverage >= 0.5 { rasterize_fragment(x, y, 1.0) }
+            });
         } else {
-            draw_line_bresenham(cast(x1).unwrap(), cast(y1).unwrap(),
-                                cast(x2).unwrap(), cast(y2).unwrap(), rasterize_fragment)
+            // Every tile walks the same line between the same pixels, only plotting its own part of it,
+            // since lines drawn separately in each tile would not line up at the edges between them
+            let (a, b) = ((pixel_center.to_half_integer(line_start.0), pixel_center.to_half_integer(line_start.1)),
+                          (pixel_center.to_half_integer(line_end.0), pixel_center.to_half_integer(line_end.1)));
+
+            let (width, height): (V::Scalar, V::Scalar) = (cast(dimensions.width).unwrap(), cast(dimensions.height).unwrap());
+
+            if let Some((t0, t1)) = liang_barsky_parametric(a, b, ((Zero::zero(), Zero::zero()), (width, height))) {
+                // Endpoints are drawn in the pixels containing them, kept on screen in case of rounding
+                let pixel = |t: V::Scalar| -> (i64, i64) {
+                    let x = (a.0 + (b.0 - a.0) * t).max(Zero::zero()).min(width);
+                    let y = (a.1 + (b.1 - a.1) * t).max(Zero::zero()).min(height);
+
+                    (cast(x.floor()).unwrap(), cast(y.floor()).unwrap())
+                };
+
+                draw_line_bresenham_within(pixel(t0), pixel(t1),
+                                           (tile_min.x as i64, tile_min.y as i64), (xend, yend), rasterize_fragment);
+            }
         }
     }
 }
@@ -150,6 +242,36 @@ pub fn draw_line_bresenham<F>(mut x0: i64, mut y0: i64, x1: i64, y1: i64, mut pl
     }
 }
 
+/// Draws the part of a line between two pixels that lies from `min_pixel` up to but excluding `end_pixel`.
+///
+/// Pixels are chosen like Bresenham's algorithm would, but are computed directly for each step along the line,
+/// so drawing a line in parts, like once for every tile, gives exactly the same pixels as drawing it all at once.
+pub fn draw_line_bresenham_within<F>((x0, y0): (i64, i64), (x1, y1): (i64, i64),
+                                     min_pixel: (i64, i64), end_pixel: (i64, i64), mut plot: F) where F: FnMut(i64, i64, f64) {
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+
+    // Step along the major axis `a`, always from the lower end so both directions give the same pixels
+    let (a0, b0, a1, b1) = if steep { (y0, x0, y1, x1) } else { (x0, y0, x1, y1) };
+    let (a0, b0, a1, b1) = if a0 <= a1 { (a0, b0, a1, b1) } else { (a1, b1, a0, b0) };
+
+    let (amin, aend) = if steep { (min_pixel.1, end_pixel.1) } else { (min_pixel.0, end_pixel.0) };
+
+    let (da, db) = (a1 - a0, b1 - b0);
+
+    for a in max(a0, amin)..min(a1 + 1, aend) {
+        // Pixel closest to the line on the minor axis, rounding halfway cases up
+        let b = if da == 0 { b0 } else { b0 + div_floor(2 * (a - a0) * db + da, 2 * da) };
+
+        if steep { plot(b, a, 1.0) } else { plot(a, b, 1.0) }
+    }
+}
+
+/// Integer division rounding towards negative infinity, for positive divisors
+#[inline]
+fn div_floor(n: i64, d: i64) -> i64 {
+    if n >= 0 { n / d } else { -((d - 1 - n) / d) }
+}
+
 /// Uses Xiaolin Wu's algorithm to draw an anti-aliased line.
 ///
 /// [https://en.wikipedia.org/wiki/Xiaolin_Wu%27s_line_algorithm](https://en.wikipedia.org/wiki/Xiaolin_Wu%27s_line_algorithm)
@@ -236,4 +358,110 @@ pub fn draw_line_xiaolin_wu<F>(mut x0: f64, mut y0: f64, mut x1: f64, mut y1: f6
             x += 1.0;
         }
     }
-}
\ No newline at end of file
+}
+
+/// Fraction of a cone filter with a radius of one pixel lying beyond a straight edge,
+/// for distances from the filter center to the edge of zero to one pixel, in steps of 1/16th of a pixel.
+const CONE_FILTER_TABLE: [f64; 17] = [
+    0.5000, 0.4402, 0.3829, 0.3272, 0.2757, 0.2273, 0.1838, 0.1443, 0.1101,
+    0.0803, 0.0559, 0.0360, 0.0210, 0.0104, 0.0039, 0.0007, 0.0000
+];
+
+/// Fraction of the cone filter beyond an edge at the signed distance `e`
+fn cone_filter(e: f64) -> f64 {
+    if e < 0.0 {
+        return 1.0 - cone_filter(-e);
+    }
+
+    if e >= 1.0 {
+        return 0.0;
+    }
+
+    let f = e * 16.0;
+    let i = f as usize;
+    let t = f - i as f64;
+
+    CONE_FILTER_TABLE[i] * (1.0 - t) + CONE_FILTER_TABLE[i + 1] * t
+}
+
+/// Coverage of a pixel whose center is `distance` away from the center of a line of the given width,
+/// normalized so the center of the line is fully covered.
+pub fn line_coverage(distance: f64, width: f64) -> f64 {
+    let half = width.max(1.0) / 2.0;
+
+    let coverage = |d: f64| cone_filter(-half - d) - cone_filter(half - d);
+
+    (coverage(distance) / coverage(0.0)).min(1.0).max(0.0)
+}
+
+/// Uses the Gupta-Sproull algorithm to draw an anti-aliased line of any width.
+///
+/// [https://en.wikipedia.org/wiki/Spatial_anti-aliasing](https://en.wikipedia.org/wiki/Spatial_anti-aliasing)
+///
+/// Every pixel near the line is shaded with a coverage derived from its distance to the line,
+/// convolved with a cone filter, so lines have the same intensity at every angle, unlike Xiaolin Wu's algorithm.
+/// Line ends are rounded.
+pub fn draw_line_gupta_sproull<F>(x0: f64, y0: f64, x1: f64, y1: f64, width: f64, mut plot: F) where F: FnMut(i64, i64, f64) {
+    use std::mem::swap;
+
+    let (dx, dy) = (x1 - x0, y1 - y0);
+    let length = dx.hypot(dy);
+
+    // Coverage reaches zero one pixel beyond the edge of the line
+    let reach = width.max(1.0) / 2.0 + 1.0;
+
+    let distance = |px: f64, py: f64| -> f64 {
+        if length < 1e-9 {
+            return (px - x0).hypot(py - y0);
+        }
+
+        let t = (((px - x0) * dx + (py - y0) * dy) / (length * length)).max(0.0).min(1.0);
+
+        (px - (x0 + dx * t)).hypot(py - (y0 + dy * t))
+    };
+
+    // Walk along the major axis, visiting every pixel within reach of the line on the minor axis
+    let steep = dy.abs() > dx.abs();
+
+    let (mut a0, mut b0, mut a1, mut b1) = if steep { (y0, x0, y1, x1) } else { (x0, y0, x1, y1) };
+
+    if a0 > a1 {
+        swap(&mut a0, &mut a1);
+        swap(&mut b0, &mut b1);
+    }
+
+    let slope = if a1 - a0 < 1e-9 { 0.0 } else { (b1 - b0) / (a1 - a0) };
+
+    // Half the extent of the line on the minor axis, for lines at an angle
+    let spread = reach * (1.0 + slope * slope).sqrt();
+
+    let (bmin, bmax) = if b0 < b1 { (b0, b1) } else { (b1, b0) };
+
+    let mut a = (a0 - reach).floor() as i64;
+
+    while a as f64 <= a1 + reach {
+        let ac = a as f64 + 0.5;
+
+        let center = (b0 + (ac - a0) * slope).max(bmin).min(bmax);
+
+        let mut b = (center - spread).floor() as i64;
+
+        while b as f64 <= center + spread {
+            let bc = b as f64 + 0.5;
+
+            let coverage = if steep {
+                line_coverage(distance(bc, ac), width)
+            } else {
+                line_coverage(distance(ac, bc), width)
+            };
+
+            if coverage > 0.0 {
+                if steep { plot(b, a, coverage) } else { plot(a, b, coverage) }
+            }
+
+            b += 1;
+        }
+
+        a += 1;
+    }
+}
@@ -1,27 +1,70 @@
 pub mod point;
 pub mod line;
 pub mod triangle;
+pub mod gouraud;
+pub mod scanline;
+pub mod hierarchical;
+pub mod tile;
+pub mod reference;
 
 use ::stencil::{StencilTest, StencilOp};
+use ::attachments::depth::DepthTest;
+use ::color::ColorMask;
 use ::mesh::{Vertex, Mesh};
-use ::geometry::{Dimensions, Coordinate, FaceWinding};
+use ::geometry::{Dimensions, Coordinate, FaceWinding, PixelCenter};
 
 use ::pipeline::PipelineObject;
+use ::pipeline::retro::Interpolation;
+use ::pipeline::subsample::SubsampleField;
 
 use ::pipeline::types::{Pixel, StencilValue};
 
 #[derive(Clone, Copy)]
 pub struct RasterArguments<P, V> where P: PipelineObject, V: Vertex {
     pub dimensions: Dimensions,
+    /// Row stride of the framebuffer, for computing pixel indices
+    pub stride: usize,
     pub tile: (Coordinate, Coordinate),
+    /// Exclusive end of the pixels drawn into, see `tile_pixel_end`
+    pub pixel_end: Coordinate,
     pub bounds: ((V::Scalar, V::Scalar), (V::Scalar, V::Scalar)),
     pub stencil_value: StencilValue<P>,
     pub stencil_test: StencilTest,
     pub stencil_op: StencilOp,
     pub antialiased_lines: bool,
+    pub line_width: f64,
+    pub color_mask: ColorMask,
     pub cull_faces: Option<FaceWinding>,
+    pub depth_test: DepthTest,
+    pub interpolation: Interpolation,
+    pub vertex_snap: Option<u32>,
+    /// Pixels rendered this frame when subsampling
+    pub field: SubsampleField,
+    /// Where pixels are sampled within their area
+    pub pixel_center: PixelCenter,
+}
+
+/// Algorithm used to fill triangles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum RasterBackend {
+    /// Tests every pixel in the bounding box of the triangle against its edge functions
+    EdgeFunction,
+    /// Walks the edges of the triangle and fills the rows between them
+    Scanline,
+    /// Tests blocks of 8x8 pixels against the edge functions first, only testing individual pixels
+    /// in blocks partially covered by the triangle
+    Hierarchical,
+}
+
+impl Default for RasterBackend {
+    fn default() -> RasterBackend { RasterBackend::EdgeFunction }
 }
 
 pub use self::triangle::rasterize_triangle;
+pub use self::gouraud::rasterize_triangle_gouraud;
+pub use self::scanline::rasterize_triangle_scanline;
+pub use self::hierarchical::rasterize_triangle_hierarchical;
 pub use self::line::rasterize_line;
-pub use self::point::rasterize_point;
\ No newline at end of file
+pub use self::point::rasterize_point;
+pub use self::tile::{Tile, generate_tiles, generate_tiles_into, scissor_tiles, scissor_tiles_in_place, tile_pixel_end};
\ No newline at end of file
@@ -3,6 +3,7 @@ use super::RasterArguments;
 use num_traits::{One, Zero, NumCast, cast};
 use nalgebra::coordinates::XYZW;
 
+use ::color::Color;
 use ::color::blend::Blend;
 use ::pixels::{PixelRead, PixelWrite};
 use ::framebuffer::UnsafeFramebuffer;
@@ -16,7 +17,7 @@ use ::pipeline::PipelineObject;
 use ::framebuffer::types::DepthAttachment;
 use ::pipeline::types::{PipelineUniforms, Pixel};
 
-use ::pipeline::stages::fragment::Fragment;
+use ::pipeline::stages::fragment::{Fragment, TargetColors};
 
 pub fn rasterize_point<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                       pipeline: &mut P,
@@ -29,30 +30,42 @@ pub fn rasterize_point<P, V, K, B, F>(args: &RasterArguments<P, V>,
           B: Blend<Pixel<P>>,
           F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
     let RasterArguments {
-        dimensions,
-        tile,
+        stride,
+        pixel_end,
         bounds,
         stencil_value,
         stencil_test,
         stencil_op,
         antialiased_lines,
+        line_width,
+        color_mask,
         cull_faces,
+        depth_test,
+        field,
+        pixel_center,
+        ..
     } = *args;
 
     let (uniforms, framebuffer, _) = pipeline.all_mut();
 
     let XYZW { x, y, z, .. } = *point.position;
 
-    if (bounds.0).0 <= x && x < (bounds.1).0 && (bounds.0).1 <= y && y < (bounds.1).1 {
+    // The point covers the pixel whose center is closest to it
+    let (x, y) = (pixel_center.to_half_integer(x), pixel_center.to_half_integer(y));
+
+    // Neighboring tiles share their edges, so only one of them draws points on them
+    let (xend, yend): (V::Scalar, V::Scalar) = (cast(pixel_end.x).unwrap(), cast(pixel_end.y).unwrap());
+
+    if (bounds.0).0 <= x && x < xend && (bounds.0).1 <= y && y < yend {
         let coord = Coordinate::new(cast(x).unwrap(), cast(y).unwrap());
 
-        let index = coord.into_index(dimensions);
+        let index = coord.into_strided_index(stride);
 
         // Get stencil buffer value for this pixel
         let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
 
         // perform stencil test
-        if stencil_test.test(framebuffer_stencil_value, stencil_value) {
+        if field.covers(coord.x, coord.y) && stencil_test.test(framebuffer_stencil_value, stencil_value) {
             // Calculate new stencil value
             let new_stencil_value = stencil_op.op(framebuffer_stencil_value, stencil_value);
 
@@ -64,8 +77,8 @@ pub fn rasterize_point<P, V, K, B, F>(args: &RasterArguments<P, V>,
 
                 let dt = unsafe { framebuffer.get_depth_unchecked(index) };
 
-                // Check if point is in front of other geometry
-                if d >= dt {
+                // Perform depth test against existing geometry
+                if depth_test.test(d, dt) {
                     // Perform fragment shading
                     let fragment = fragment_shader(point, &uniforms);
 
@@ -75,10 +88,18 @@ pub fn rasterize_point<P, V, K, B, F>(args: &RasterArguments<P, V>,
                             let p = unsafe { framebuffer.get_pixel_unchecked(index) };
 
                             unsafe {
-                                framebuffer.set_pixel_unchecked(index, blend.blend(c, p));
+                                framebuffer.set_pixel_unchecked(index, blend.blend(c, p).mask_channels(p, color_mask));
                                 framebuffer.set_depth_unchecked(index, d);
                             }
                         }
+                        Fragment::Targets(TargetColors { colors: c, mask }) => if mask != 0 {
+                            let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                            unsafe {
+                                framebuffer.set_pixel_unchecked(index, blend.blend(c, p).select_targets(p, mask).mask_channels(p, color_mask));
+                                framebuffer.set_depth_unchecked(index, d);
+                            }
+                        },
                     }
                 }
             }
@@ -0,0 +1,290 @@
+//! Reference rasterization
+//!
+//! Slow but straightforward implementations of the coverage rules followed by the optimized rasterizers,
+//! evaluated for every pixel of the framebuffer in double precision, without tiles, bounding boxes or incremental
+//! updates. They exist to check the optimized paths against, and are far too slow for anything else.
+//!
+//! Pixel centers within `EDGE_EPSILON` of an edge are `Coverage::Ambiguous`, since whether they are covered
+//! comes down to rounding, so optimized paths are free to go either way there.
+
+use ::geometry::{Dimensions, Coordinate, HasDimensions, PixelCenter};
+
+use super::Tile;
+
+/// Distance in pixels from an edge within which pixel centers may or may not be covered
+pub const EDGE_EPSILON: f64 = 1e-3;
+
+/// Largest distance in pixels from a thin line to the center of a pixel covered by it.
+///
+/// Thin lines are drawn between the pixels containing their endpoints, which can be up to half a pixel
+/// away from the line on each axis, and stepping between them adds up to another half a pixel.
+pub const LINE_REACH: f64 = 1.25;
+
+/// Whether a pixel is covered by a primitive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Coverage {
+    /// The pixel must not be covered
+    Outside,
+    /// The pixel may or may not be covered
+    Ambiguous,
+    /// The pixel must be covered
+    Inside,
+}
+
+impl Coverage {
+    #[inline]
+    fn from_distance(distance: f64) -> Coverage {
+        if distance > EDGE_EPSILON {
+            Coverage::Inside
+        } else if distance < -EDGE_EPSILON {
+            Coverage::Outside
+        } else {
+            Coverage::Ambiguous
+        }
+    }
+
+    /// Returns true if a rasterizer covering the pixel or not, as given by `covered`, agrees with this coverage
+    #[inline]
+    pub fn accepts(self, covered: bool) -> bool {
+        match self {
+            Coverage::Outside => !covered,
+            Coverage::Ambiguous => true,
+            Coverage::Inside => covered,
+        }
+    }
+}
+
+/// Coverage of every pixel of a framebuffer by a single primitive
+#[derive(Debug, Clone, PartialEq)]
+pub struct CoverageMask {
+    dimensions: Dimensions,
+    coverage: Vec<Coverage>,
+}
+
+impl HasDimensions for CoverageMask {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl CoverageMask {
+    /// Evaluates the coverage of every pixel with the given function
+    pub fn from_fn<F>(dimensions: Dimensions, mut f: F) -> CoverageMask where F: FnMut(Coordinate) -> Coverage {
+        let mut coverage = Vec::with_capacity(dimensions.area());
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                coverage.push(f(Coordinate::new(x, y)));
+            }
+        }
+
+        CoverageMask { dimensions, coverage }
+    }
+
+    /// Coverage of a single pixel, or `Coverage::Outside` if it lies outside of the framebuffer
+    pub fn get(&self, coord: Coordinate) -> Coverage {
+        if coord.x < self.dimensions.width && coord.y < self.dimensions.height {
+            self.coverage[self.index_of(coord)]
+        } else {
+            Coverage::Outside
+        }
+    }
+
+    /// Number of pixels with the given coverage
+    pub fn count(&self, coverage: Coverage) -> usize {
+        self.coverage.iter().filter(|&&c| c == coverage).count()
+    }
+
+    /// Returns every pixel where a rasterizer covering the pixels given by `covered` disagrees with the reference
+    pub fn mismatches<F>(&self, mut covered: F) -> Vec<Coordinate> where F: FnMut(Coordinate) -> bool {
+        let mut mismatches = Vec::new();
+
+        for y in 0..self.dimensions.height {
+            for x in 0..self.dimensions.width {
+                let coord = Coordinate::new(x, y);
+
+                if !self.get(coord).accepts(covered(coord)) {
+                    mismatches.push(coord);
+                }
+            }
+        }
+
+        mismatches
+    }
+}
+
+/// Signed distance from `p` to the line through `a` and `b`, positive on the left as seen from `a` towards `b`
+#[inline]
+fn edge_distance(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+
+    (dx * (p.1 - a.1) - dy * (p.0 - a.0)) / dx.hypot(dy)
+}
+
+/// Coverage of a point by a triangle, which includes its edges.
+///
+/// Zero-area triangles cover nothing.
+pub fn triangle_coverage(vertices: &[(f64, f64); 3], x: f64, y: f64) -> Coverage {
+    let (a, b, c) = (vertices[0], vertices[1], vertices[2]);
+
+    let area = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+
+    if !(area.abs() > 0.0) {
+        return Coverage::Outside;
+    }
+
+    let sign = area.signum();
+
+    let distance = [(a, b), (b, c), (c, a)].iter()
+                                           .map(|&(p, q)| edge_distance(p, q, (x, y)) * sign)
+                                           .fold(::std::f64::INFINITY, f64::min);
+
+    Coverage::from_distance(distance)
+}
+
+/// Reference coverage of a triangle given in screen-space coordinates
+pub fn reference_triangle(vertices: &[(f64, f64); 3], dimensions: Dimensions, pixel_center: PixelCenter) -> CoverageMask {
+    let offset: f64 = pixel_center.offset();
+
+    CoverageMask::from_fn(dimensions, |coord| {
+        triangle_coverage(vertices, coord.x as f64 + offset, coord.y as f64 + offset)
+    })
+}
+
+/// Signed distance from a pixel-space position to the nearest edge of pixel `coord`, positive inside of it
+#[inline]
+fn pixel_distance(coord: Coordinate, (x, y): (f64, f64)) -> f64 {
+    let (left, top) = (coord.x as f64, coord.y as f64);
+
+    (x - left).min(left + 1.0 - x).min(y - top).min(top + 1.0 - y)
+}
+
+/// Reference coverage of a point, which covers the pixel whose center is closest to it
+pub fn reference_point(position: (f64, f64), dimensions: Dimensions, pixel_center: PixelCenter) -> CoverageMask {
+    let position = (pixel_center.to_half_integer(position.0), pixel_center.to_half_integer(position.1));
+
+    CoverageMask::from_fn(dimensions, |coord| Coverage::from_distance(pixel_distance(coord, position)))
+}
+
+/// Distance from `p` to the segment from `a` to `b`
+fn segment_distance(a: (f64, f64), b: (f64, f64), p: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+
+    let length_squared = dx * dx + dy * dy;
+
+    let t = if length_squared > 0.0 {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared).max(0.0).min(1.0)
+    } else {
+        0.0
+    };
+
+    (p.0 - (a.0 + dx * t)).hypot(p.1 - (a.1 + dy * t))
+}
+
+/// Reference coverage of a thin line one pixel wide.
+///
+/// The pixels containing the endpoints are always covered, and no pixel with its center further than
+/// `LINE_REACH` from the line is. Which pixels in between are covered depends on the line algorithm,
+/// so they are `Coverage::Ambiguous`, and gaps have to be checked separately with `line_major_steps`.
+pub fn reference_line(start: (f64, f64), end: (f64, f64), dimensions: Dimensions, pixel_center: PixelCenter) -> CoverageMask {
+    let offset: f64 = pixel_center.offset();
+
+    let to_half_integer = |(x, y): (f64, f64)| (pixel_center.to_half_integer(x), pixel_center.to_half_integer(y));
+
+    let (a, b) = (to_half_integer(start), to_half_integer(end));
+
+    CoverageMask::from_fn(dimensions, |coord| {
+        let center = (coord.x as f64 + offset, coord.y as f64 + offset);
+
+        if pixel_distance(coord, a) > EDGE_EPSILON || pixel_distance(coord, b) > EDGE_EPSILON {
+            Coverage::Inside
+        } else if segment_distance(start, end, center) > LINE_REACH + EDGE_EPSILON {
+            Coverage::Outside
+        } else {
+            Coverage::Ambiguous
+        }
+    })
+}
+
+/// Rows or columns of pixels, one for every step along the major axis of a thin line from the pixel containing
+/// its start to the one containing its end, each of which must contain at least one covered pixel.
+pub fn line_major_steps(start: (f64, f64), end: (f64, f64), dimensions: Dimensions, pixel_center: PixelCenter) -> Vec<Tile> {
+    if dimensions.width == 0 || dimensions.height == 0 {
+        return Vec::new();
+    }
+
+    let (x0, y0) = (pixel_center.to_half_integer(start.0).floor(), pixel_center.to_half_integer(start.1).floor());
+    let (x1, y1) = (pixel_center.to_half_integer(end.0).floor(), pixel_center.to_half_integer(end.1).floor());
+
+    let steep = (y1 - y0).abs() > (x1 - x0).abs();
+
+    let ((from, to), size) = if steep { ((y0, y1), dimensions.height) } else { ((x0, x1), dimensions.width) };
+
+    let (low, high) = (from.min(to), from.max(to));
+
+    if high < 0.0 || low >= size as f64 {
+        return Vec::new();
+    }
+
+    (low.max(0.0) as u32..high.min(size as f64 - 1.0) as u32 + 1).map(|step| {
+        if steep {
+            (Coordinate::new(0, step), Coordinate::new(dimensions.width - 1, step))
+        } else {
+            (Coordinate::new(step, 0), Coordinate::new(step, dimensions.height - 1))
+        }
+    }).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reference_triangle() {
+        let triangle = [(1.0, 1.0), (7.0, 1.0), (1.0, 7.0)];
+
+        let mask = reference_triangle(&triangle, Dimensions::new(8, 8), PixelCenter::HalfInteger);
+
+        assert_eq!(mask.get(Coordinate::new(1, 1)), Coverage::Inside);
+        assert_eq!(mask.get(Coordinate::new(0, 0)), Coverage::Outside);
+        assert_eq!(mask.get(Coordinate::new(6, 6)), Coverage::Outside);
+
+        // Centers exactly on the diagonal edge
+        assert_eq!(mask.get(Coordinate::new(3, 4)), Coverage::Ambiguous);
+        assert_eq!(mask.get(Coordinate::new(3, 3)), Coverage::Inside);
+
+        // Winding doesn't matter
+        let flipped = [triangle[0], triangle[2], triangle[1]];
+
+        assert_eq!(reference_triangle(&flipped, Dimensions::new(8, 8), PixelCenter::HalfInteger), mask);
+
+        // Integer pixel centers put the corner exactly on the edges
+        let integer = reference_triangle(&triangle, Dimensions::new(8, 8), PixelCenter::Integer);
+
+        assert_eq!(integer.get(Coordinate::new(1, 1)), Coverage::Ambiguous);
+        assert_eq!(integer.get(Coordinate::new(2, 2)), Coverage::Inside);
+    }
+
+    #[test]
+    fn test_reference_point_and_line() {
+        let dimensions = Dimensions::new(8, 8);
+
+        let point = reference_point((3.25, 4.75), dimensions, PixelCenter::HalfInteger);
+
+        assert_eq!(point.count(Coverage::Inside), 1);
+        assert_eq!(point.get(Coordinate::new(3, 4)), Coverage::Inside);
+
+        assert_eq!(reference_point((3.25, 4.75), dimensions, PixelCenter::Integer).get(Coordinate::new(3, 5)), Coverage::Inside);
+
+        let line = reference_line((0.5, 0.5), (6.5, 2.5), dimensions, PixelCenter::HalfInteger);
+
+        assert_eq!(line.get(Coordinate::new(0, 0)), Coverage::Inside);
+        assert_eq!(line.get(Coordinate::new(6, 2)), Coverage::Inside);
+        assert_eq!(line.get(Coordinate::new(3, 1)), Coverage::Ambiguous);
+        assert_eq!(line.get(Coordinate::new(3, 6)), Coverage::Outside);
+
+        let steps = line_major_steps((0.5, 0.5), (6.5, 2.5), dimensions, PixelCenter::HalfInteger);
+
+        assert_eq!(steps.len(), 7);
+        assert_eq!(steps[3], (Coordinate::new(3, 0), Coordinate::new(3, 7)));
+    }
+}
@@ -0,0 +1,275 @@
+use super::RasterArguments;
+use super::triangle::{snap_positions, interpolation_weights, is_finite_position};
+
+use std::cmp::Ordering;
+
+use num_traits::{Float, One, Zero, cast};
+use nalgebra::coordinates::XYZW;
+
+use ::numeric::FloatScalar;
+use ::color::{Color, ColorAlpha};
+use ::color::blend::Blend;
+use ::pixels::{PixelRead, PixelWrite};
+use ::framebuffer::UnsafeFramebuffer;
+use ::attachments::depth::Depth;
+use ::mesh::Vertex;
+use ::geometry::{Coordinate, ScreenVertex, FaceWinding};
+use ::interpolate::Interpolate;
+
+use ::pipeline::PipelineObject;
+
+use ::framebuffer::types::DepthAttachment;
+use ::pipeline::types::{PipelineUniforms, Pixel};
+
+use ::pipeline::stages::fragment::{Fragment, TargetColors};
+
+/// Horizontal extent of an edge at the given height, which is the whole edge if it's horizontal
+#[inline]
+fn edge_x<N: FloatScalar>(a: (N, N), b: (N, N), y: N) -> (N, N) {
+    if a.1 == b.1 {
+        (a.0.min(b.0), a.0.max(b.0))
+    } else {
+        let x = a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1);
+
+        (x, x)
+    }
+}
+
+/// Horizontal extent of a triangle at the given height, given its vertices sorted by `y`,
+/// or `None` if the triangle doesn't reach that height.
+pub fn triangle_span<N: FloatScalar>(vertices: &[(N, N); 3], y: N) -> Option<(N, N)> {
+    let (top, middle, bottom) = (vertices[0], vertices[1], vertices[2]);
+
+    if y < top.1 || y > bottom.1 || top.1 == bottom.1 {
+        return None;
+    }
+
+    // The long edge spans the whole height, and the short edges meet at the middle vertex
+    let long = edge_x(top, bottom, y);
+    let short = if y < middle.1 { edge_x(top, middle, y) } else { edge_x(middle, bottom, y) };
+
+    Some((long.0.min(short.0), long.1.max(short.1)))
+}
+
+/// Rasterizes a triangle by walking its edges from top to bottom and filling each row between them.
+///
+/// Produces the same image as `rasterize_triangle`, except for pixel centers lying exactly on an edge,
+/// but only visits pixels inside the triangle, so it's faster for large triangles that cover
+/// a small part of their bounding box. The stencil buffer is also only tested and updated for pixels
+/// inside the triangle.
+pub fn rasterize_triangle_scanline<P, V, K, B, F>(args: &RasterArguments<P, V>,
+                                                  pipeline: &mut P,
+                                                  blend: B,
+                                                  fragment_shader: F,
+                                                  a: &ScreenVertex<V::Scalar, K>,
+                                                  b: &ScreenVertex<V::Scalar, K>,
+                                                  c: &ScreenVertex<V::Scalar, K>)
+    where P: PipelineObject,
+          V: Vertex,
+          K: Send + Sync + Interpolate,
+          B: Blend<Pixel<P>>,
+          F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
+    let RasterArguments {
+        dimensions,
+        stride,
+        tile,
+        pixel_end,
+        stencil_value,
+        stencil_test,
+        stencil_op,
+        color_mask,
+        cull_faces,
+        depth_test,
+        interpolation,
+        vertex_snap,
+        field,
+        pixel_center,
+        ..
+    } = *args;
+
+    let (uniforms, framebuffer, _) = pipeline.all_mut();
+
+    let (pa, pb, pc) = snap_positions(vertex_snap, a.position, b.position, c.position);
+
+    if !(is_finite_position(&pa) && is_finite_position(&pb) && is_finite_position(&pc)) {
+        return;
+    }
+
+    let XYZW { x: x1, y: y1, .. } = *pa;
+    let XYZW { x: x2, y: y2, .. } = *pb;
+    let XYZW { x: x3, y: y3, .. } = *pc;
+
+    if let Some(winding) = cull_faces {
+        // Shoelace algorithm for a triangle
+        let area = x1 * y2 + x2 * y3 + x3 * y1 - x2 * y1 - x3 * y2 - x1 * y3;
+
+        if winding == if area.is_sign_negative() { FaceWinding::Clockwise } else { FaceWinding::CounterClockwise } {
+            return;
+        }
+    }
+
+    let det = (y2 - y3) * (x1 - x3) + (x3 - x2) * (y1 - y3);
+
+    // Degenerate triangles cover no pixels
+    if det == Zero::zero() {
+        return;
+    }
+
+    let mut sorted = [(x1, y1), (x2, y2), (x3, y3)];
+
+    sorted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(Ordering::Equal));
+
+    let offset: V::Scalar = pixel_center.offset();
+
+    // Neighboring tiles share their edges, so only one of them draws triangles on them
+    let (tile_min_x, tile_max_x) = (cast::<_, V::Scalar>(tile.0.x).unwrap(), cast::<_, V::Scalar>(pixel_end.x - 1).unwrap());
+    let (tile_min_y, tile_max_y) = (cast::<_, V::Scalar>(tile.0.y).unwrap(), cast::<_, V::Scalar>(pixel_end.y - 1).unwrap());
+
+    // Rows with their centers inside the triangle
+    let first_row = (sorted[0].1 - offset).ceil().max(tile_min_y);
+    let last_row = (sorted[2].1 - offset).floor().min(tile_max_y);
+
+    if first_row > last_row {
+        return;
+    }
+
+    // Change in the barycentric coordinates for every step to the right
+    let du = (y2 - y3) / det;
+    let dv = (y3 - y1) / det;
+
+    for py in cast::<_, u32>(first_row).unwrap()..(cast::<_, u32>(last_row).unwrap() + 1) {
+        let y = cast::<_, V::Scalar>(py).unwrap() + offset;
+
+        let (left, right) = match triangle_span(&sorted, y) {
+            Some(span) => span,
+            None => continue,
+        };
+
+        let first_column = (left - offset).ceil().max(tile_min_x);
+        let last_column = (right - offset).floor().min(tile_max_x);
+
+        if first_column > last_column {
+            continue;
+        }
+
+        let x = first_column + offset;
+
+        let mut u = ((y2 - y3) * (x - x3) + (x3 - x2) * (y - y3)) / det;
+        let mut v = ((y3 - y1) * (x - x3) + (x1 - x3) * (y - y3)) / det;
+
+        for px in cast::<_, u32>(first_column).unwrap()..(cast::<_, u32>(last_column).unwrap() + 1) {
+            let index = Coordinate::new(px, py).into_strided_index(stride);
+
+            debug_assert!(index < stride * dimensions.height as usize);
+
+            let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
+
+            if field.covers(px, py) && stencil_test.test(framebuffer_stencil_value, stencil_value) {
+                unsafe { framebuffer.set_stencil_unchecked(index, stencil_op.op(framebuffer_stencil_value, stencil_value)); }
+
+                let w = <V::Scalar as One>::one() - u - v;
+
+                let position = Interpolate::barycentric_interpolate(u, &pa, v, &pb, w, &pc);
+
+                let z = position.z;
+
+                if z < Zero::zero() {
+                    let d: DepthAttachment<P::Framebuffer> = Depth::from_scalar(z);
+
+                    let dt = unsafe { framebuffer.get_depth_unchecked(index) };
+
+                    if depth_test.test(d, dt) {
+                        let (u, v, w) = interpolation_weights(interpolation, (u, v, w), (pa.w, pb.w, pc.w));
+
+                        let fragment = fragment_shader(&ScreenVertex {
+                            position,
+                            uniforms: Interpolate::barycentric_interpolate(u, &a.uniforms,
+                                                                           v, &b.uniforms,
+                                                                           w, &c.uniforms),
+                        }, uniforms);
+
+                        match fragment {
+                            Fragment::Discard => (),
+                            Fragment::Color(c) => {
+                                let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                                unsafe {
+                                    framebuffer.set_pixel_unchecked(index, blend.blend(c, p).mask_channels(p, color_mask));
+                                    framebuffer.set_depth_unchecked(index, d);
+                                }
+                            }
+                            Fragment::Targets(TargetColors { colors: c, mask }) => if mask != 0 {
+                                let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                                unsafe {
+                                    framebuffer.set_pixel_unchecked(index, blend.blend(c, p).select_targets(p, mask).mask_channels(p, color_mask));
+                                    framebuffer.set_depth_unchecked(index, d);
+                                }
+                            },
+                        }
+                    }
+                }
+            }
+
+            u = u + du;
+            v = v + dv;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Coverage of a pixel center according to the edge functions used by `rasterize_triangle`,
+    /// or `None` if it's too close to an edge to tell.
+    fn edge_function_coverage(t: &[(f64, f64); 3], x: f64, y: f64) -> Option<bool> {
+        let ((x1, y1), (x2, y2), (x3, y3)) = (t[0], t[1], t[2]);
+
+        let det = (y2 - y3) * (x1 - x3) + (x3 - x2) * (y1 - y3);
+
+        let u = ((y2 - y3) * (x - x3) + (x3 - x2) * (y - y3)) / det;
+        let v = ((y3 - y1) * (x - x3) + (x1 - x3) * (y - y3)) / det;
+        let w = 1.0 - u - v;
+
+        if u.abs() < 1e-9 || v.abs() < 1e-9 || w.abs() < 1e-9 {
+            None
+        } else {
+            Some(u > 0.0 && v > 0.0 && w > 0.0)
+        }
+    }
+
+    #[test]
+    fn test_scanline_matches_edge_functions() {
+        let triangles = [
+            [(1.0, 1.0), (30.0, 4.5), (12.25, 28.0)],
+            [(31.0, 0.0), (0.0, 31.0), (31.0, 31.0)],
+            [(5.5, 2.5), (26.0, 2.5), (15.75, 20.3)],
+            [(16.0, 30.0), (2.2, 10.1), (29.9, 9.7)],
+            [(0.3, 15.0), (31.7, 15.2), (16.0, 15.9)],
+        ];
+
+        for triangle in &triangles {
+            let mut sorted = *triangle;
+
+            sorted.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+            for py in 0..32 {
+                let y = py as f64 + 0.5;
+
+                for px in 0..32 {
+                    let x = px as f64 + 0.5;
+
+                    let scanline = match triangle_span(&sorted, y) {
+                        Some((left, right)) => x >= left && x <= right,
+                        None => false,
+                    };
+
+                    if let Some(expected) = edge_function_coverage(triangle, x, y) {
+                        assert_eq!(scanline, expected, "pixel ({}, {}) of {:?}", px, py, triangle);
+                    }
+                }
+            }
+        }
+    }
+}
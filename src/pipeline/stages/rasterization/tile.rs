@@ -0,0 +1,133 @@
+//! Framebuffer tiling
+
+use ::numeric::utils::{min, max};
+use ::geometry::{Dimensions, Coordinate};
+
+/// A single tile, given as the inclusive top-left and bottom-right coordinates
+pub type Tile = (Coordinate, Coordinate);
+
+/// Splits the framebuffer dimensions up into tiles of at most `tile_size`,
+/// which are then rasterized in parallel.
+pub fn generate_tiles(dimensions: Dimensions, tile_size: Dimensions) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+
+    generate_tiles_into(&mut tiles, dimensions, tile_size);
+
+    tiles
+}
+
+/// Same as `generate_tiles`, but replaces the contents of an existing list to reuse its allocation
+pub fn generate_tiles_into(tiles: &mut Vec<Tile>, dimensions: Dimensions, tile_size: Dimensions) {
+    tiles.clear();
+
+    if dimensions.width == 0 || dimensions.height == 0 {
+        return;
+    }
+
+    // Empty tiles would never make any progress
+    let tile_size = Dimensions::new(max(tile_size.width, 1), max(tile_size.height, 1));
+
+    let xmax = dimensions.width - 1;
+    let ymax = dimensions.height - 1;
+
+    let mut y = 0;
+
+    while y < ymax {
+        let mut x = 0;
+
+        let next_y = min(y + tile_size.height, ymax);
+
+        while x < xmax {
+            let next_x = min(x + tile_size.width, xmax);
+
+            tiles.push((
+                Coordinate::new(x, y),
+                Coordinate::new(next_x, next_y)
+            ));
+
+            x = next_x;
+        }
+
+        y = next_y;
+    }
+}
+
+/// Clips tiles to the inclusive `scissor` rectangle, removing any tiles entirely outside of it.
+pub fn scissor_tiles(mut tiles: Vec<Tile>, scissor: Tile) -> Vec<Tile> {
+    scissor_tiles_in_place(&mut tiles, scissor);
+
+    tiles
+}
+
+/// Same as `scissor_tiles`, but clips the tiles in place to reuse the allocation
+pub fn scissor_tiles_in_place(tiles: &mut Vec<Tile>, scissor: Tile) {
+    let (smin, smax) = scissor;
+
+    // Far edges of the last tiles, which aren't shared with another tile
+    let last = tiles.iter().fold(Coordinate::new(0, 0), |last, &(_, tmax)| Coordinate::new(max(last.x, tmax.x), max(last.y, tmax.y)));
+
+    let mut kept = 0;
+
+    for i in 0..tiles.len() {
+        let (tmin, tmax) = tiles[i];
+
+        let start = Coordinate::new(max(tmin.x, smin.x), max(tmin.y, smin.y));
+        let end = Coordinate::new(min(tmax.x, smax.x), min(tmax.y, smax.y));
+
+        // A tile clipped down to the edge it shares with the next tile would only duplicate part of that tile
+        let shared = (start.x == tmax.x && tmax.x < last.x) || (start.y == tmax.y && tmax.y < last.y);
+
+        if start.x <= end.x && start.y <= end.y && !shared {
+            tiles[kept] = (start, end);
+            kept += 1;
+        }
+    }
+
+    tiles.truncate(kept);
+}
+
+/// Exclusive end of the pixels a tile draws into, so each is drawn by exactly one tile.
+///
+/// Neighboring tiles share the pixels on their edges, so a tile leaves its far edges to the next tile,
+/// unless they're the edges of the framebuffer or were cut short by a scissor rectangle.
+pub fn tile_pixel_end(tile: Tile, dimensions: Dimensions, tile_size: Dimensions) -> Coordinate {
+    let (start, end) = tile;
+
+    // Same as in `generate_tiles_into`
+    let tile_size = Dimensions::new(max(tile_size.width, 1), max(tile_size.height, 1));
+
+    // Tiles start on multiples of the tile size, and a tile clipped to a single pixel always keeps it
+    let shared = |start: u32, end: u32, size: u32, length: u32| start < end && end % size == 0 && end + 1 < length;
+
+    Coordinate::new(if shared(start.x, end.x, tile_size.width, dimensions.width) { end.x } else { end.x + 1 },
+                    if shared(start.y, end.y, tile_size.height, dimensions.height) { end.y } else { end.y + 1 })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_scissor_shared_edges() {
+        let tiles = generate_tiles(Dimensions::new(16, 16), Dimensions::new(8, 8));
+
+        // A single pixel on the corner shared by all four tiles is only kept by the tile starting there
+        let pixel = (Coordinate::new(8, 8), Coordinate::new(8, 8));
+
+        assert_eq!(scissor_tiles(tiles.clone(), pixel), vec![pixel]);
+
+        // ...and drawn by that tile alone
+        assert_eq!(tile_pixel_end(pixel, Dimensions::new(16, 16), Dimensions::new(8, 8)), Coordinate::new(9, 9));
+
+        // Shared far edges are left to the next tile, other edges are kept
+        assert_eq!(tile_pixel_end(tiles[0], Dimensions::new(16, 16), Dimensions::new(8, 8)), Coordinate::new(8, 8));
+        assert_eq!(tile_pixel_end(tiles[3], Dimensions::new(16, 16), Dimensions::new(8, 8)), Coordinate::new(16, 16));
+
+        let clipped = scissor_tiles(tiles, (Coordinate::new(2, 2), Coordinate::new(5, 12)));
+
+        assert_eq!(clipped, vec![(Coordinate::new(2, 2), Coordinate::new(5, 8)), (Coordinate::new(2, 8), Coordinate::new(5, 12))]);
+
+        assert_eq!(tile_pixel_end(clipped[0], Dimensions::new(16, 16), Dimensions::new(8, 8)), Coordinate::new(6, 8));
+        assert_eq!(tile_pixel_end(clipped[1], Dimensions::new(16, 16), Dimensions::new(8, 8)), Coordinate::new(6, 13));
+    }
+}
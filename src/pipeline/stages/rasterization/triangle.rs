@@ -1,10 +1,12 @@
 use super::RasterArguments;
 
-use num_traits::{Float, One, Zero, NumCast, cast};
+use num_traits::{Float, One, Zero, cast};
+use nalgebra::Vector4;
 use nalgebra::coordinates::XYZW;
 
+use ::numeric::FloatScalar;
 use ::numeric::utils::min;
-use ::color::ColorAlpha;
+use ::color::{Color, ColorAlpha};
 use ::color::blend::Blend;
 use ::pixels::{PixelRead, PixelWrite};
 use ::framebuffer::UnsafeFramebuffer;
@@ -14,11 +16,12 @@ use ::geometry::{HasDimensions, Coordinate, ScreenVertex, FaceWinding};
 use ::interpolate::Interpolate;
 
 use ::pipeline::PipelineObject;
+use ::pipeline::retro::{Interpolation, snap_to_grid};
 
 use ::framebuffer::types::DepthAttachment;
 use ::pipeline::types::{PipelineUniforms, Pixel};
 
-use ::pipeline::stages::fragment::Fragment;
+use ::pipeline::stages::fragment::{Fragment, TargetColors};
 
 pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                          pipeline: &mut P,
@@ -34,21 +37,36 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
           F: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<P>) -> Fragment<Pixel<P>> + Send + Sync {
     let RasterArguments {
         dimensions,
+        stride,
         tile,
+        pixel_end,
         bounds,
         stencil_value,
         stencil_test,
         stencil_op,
         antialiased_lines,
+        line_width,
+        color_mask,
         cull_faces,
+        depth_test,
+        interpolation,
+        vertex_snap,
+        field,
+        pixel_center,
     } = *args;
 
     let (uniforms, framebuffer, _) = pipeline.all_mut();
 
+    let (pa, pb, pc) = snap_positions(vertex_snap, a.position, b.position, c.position);
+
+    if !(is_finite_position(&pa) && is_finite_position(&pb) && is_finite_position(&pc)) {
+        return;
+    }
+
     // Dereference/transmute required position components at once
-    let XYZW { x: x1, y: y1, .. } = *a.position;
-    let XYZW { x: x2, y: y2, .. } = *b.position;
-    let XYZW { x: x3, y: y3, .. } = *c.position;
+    let XYZW { x: x1, y: y1, .. } = *pa;
+    let XYZW { x: x2, y: y2, .. } = *pb;
+    let XYZW { x: x3, y: y3, .. } = *pc;
 
     // do backface culling
     if let Some(winding) = cull_faces {
@@ -71,11 +89,16 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
         }}
     }
 
-    let min = Coordinate::new(clamp_as_int!(x1.min(x2).min(x3), tile.0.x, tile.1.x),
-                              clamp_as_int!(y1.min(y2).min(y3), tile.0.y, tile.1.y));
+    // Neighboring tiles share their edges, so only one of them draws triangles on them
+    let last = Coordinate::new(pixel_end.x - 1, pixel_end.y - 1);
+
+    let min = Coordinate::new(clamp_as_int!(x1.min(x2).min(x3), tile.0.x, last.x),
+                              clamp_as_int!(y1.min(y2).min(y3), tile.0.y, last.y));
+
+    let max = Coordinate::new(clamp_as_int!(x1.max(x2).max(x3), tile.0.x, last.x),
+                              clamp_as_int!(y1.max(y2).max(y3), tile.0.y, last.y));
 
-    let max = Coordinate::new(clamp_as_int!(x1.max(x2).max(x3), tile.0.x, tile.1.x),
-                              clamp_as_int!(y1.max(y2).max(y3), tile.0.y, tile.1.y));
+    let offset: V::Scalar = pixel_center.offset();
 
     let mut pixel = min;
 
@@ -83,15 +106,15 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
         pixel.x = min.x;
 
         while pixel.x <= max.x {
-            let index = pixel.into_index(dimensions);
+            let index = pixel.into_strided_index(stride);
 
-            debug_assert!(index < dimensions.area());
+            debug_assert!(index < stride * dimensions.height as usize);
 
             // Get stencil buffer value for this pixel
             let framebuffer_stencil_value = unsafe { framebuffer.get_stencil_unchecked(index) };
 
             // perform stencil test
-            if stencil_test.test(framebuffer_stencil_value, stencil_value) {
+            if field.covers(pixel.x, pixel.y) && stencil_test.test(framebuffer_stencil_value, stencil_value) {
                 // Calculate new stencil value
                 let new_stencil_value = stencil_op.op(framebuffer_stencil_value, stencil_value);
 
@@ -100,9 +123,9 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
 
                 //continue on to fragment shading
 
-                // Real screen position should be in the center of the pixel.
-                let (x, y) = (cast::<_, V::Scalar>(pixel.x).unwrap() + NumCast::from(0.5).unwrap(),
-                              cast::<_, V::Scalar>(pixel.y).unwrap() + NumCast::from(0.5).unwrap());
+                // Real screen position should be at the pixel center
+                let (x, y) = (cast::<_, V::Scalar>(pixel.x).unwrap() + offset,
+                              cast::<_, V::Scalar>(pixel.y).unwrap() + offset);
 
                 // calculate barycentric coordinates of the current point
                 let u = ((y2 - y3) * (x - x3) + (x3 - x2) * (y - y3)) / det;
@@ -112,7 +135,7 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
                 // Determine if pixel is even within the triangle
                 if !(u < Zero::zero() || v < Zero::zero() || w < Zero::zero()) {
                     // interpolate screen-space position
-                    let position = Interpolate::barycentric_interpolate(u, &a.position, v, &b.position, w, &c.position);
+                    let position = Interpolate::barycentric_interpolate(u, &pa, v, &pb, w, &pc);
 
                     let z = position.z;
 
@@ -122,8 +145,10 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
 
                         let dt = unsafe { framebuffer.get_depth_unchecked(index) };
 
-                        // Check if point is in front of other geometry
-                        if d >= dt {
+                        // Perform depth test against existing geometry
+                        if depth_test.test(d, dt) {
+                            let (u, v, w) = interpolation_weights(interpolation, (u, v, w), (pa.w, pb.w, pc.w));
+
                             // Perform fragment shading
                             let fragment = fragment_shader(&ScreenVertex {
                                 position,
@@ -138,10 +163,18 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
                                     let p = unsafe { framebuffer.get_pixel_unchecked(index) };
 
                                     unsafe {
-                                        framebuffer.set_pixel_unchecked(index, blend.blend(c, p));
+                                        framebuffer.set_pixel_unchecked(index, blend.blend(c, p).mask_channels(p, color_mask));
                                         framebuffer.set_depth_unchecked(index, d);
                                     }
                                 }
+                                Fragment::Targets(TargetColors { colors: c, mask }) => if mask != 0 {
+                                    let p = unsafe { framebuffer.get_pixel_unchecked(index) };
+
+                                    unsafe {
+                                        framebuffer.set_pixel_unchecked(index, blend.blend(c, p).select_targets(p, mask).mask_channels(p, color_mask));
+                                        framebuffer.set_depth_unchecked(index, d);
+                                    }
+                                },
                             }
                         }
                     }
@@ -153,4 +186,41 @@ pub fn rasterize_triangle<P, V, K, B, F>(args: &RasterArguments<P, V>,
 
         pixel.y += 1;
     }
-}
\ No newline at end of file
+}
+
+/// Returns true if every component of a screen-space position is finite.
+///
+/// Vertices with NaN or infinite positions can't be rasterized, so primitives containing them are skipped.
+#[inline]
+pub fn is_finite_position<N: FloatScalar>(p: &Vector4<N>) -> bool {
+    p.x.is_finite() && p.y.is_finite() && p.z.is_finite() && p.w.is_finite()
+}
+
+/// Snaps the screen-space positions of a triangle to a subpixel grid, if enabled
+#[inline]
+pub fn snap_positions<N: FloatScalar>(vertex_snap: Option<u32>, a: Vector4<N>, b: Vector4<N>, c: Vector4<N>) -> (Vector4<N>, Vector4<N>, Vector4<N>) {
+    match vertex_snap {
+        Some(subpixels) => {
+            let snap = |p: Vector4<N>| Vector4::new(snap_to_grid(p.x, subpixels), snap_to_grid(p.y, subpixels), p.z, p.w);
+
+            (snap(a), snap(b), snap(c))
+        }
+        None => (a, b, c),
+    }
+}
+
+/// Converts screen-space barycentric coordinates into the weights used to interpolate uniforms,
+/// given the screen-space `w` of each vertex, which is the reciprocal of its clip-space `w`.
+#[inline]
+pub fn interpolation_weights<N: FloatScalar>(interpolation: Interpolation, (u, v, w): (N, N, N), (w1, w2, w3): (N, N, N)) -> (N, N, N) {
+    match interpolation {
+        Interpolation::Affine => (u, v, w),
+        Interpolation::Perspective => {
+            let (u, v, w) = (u * w1, v * w2, w * w3);
+
+            let sum = u + v + w;
+
+            if sum == N::zero() { (u, v, w) } else { (u / sum, v / sum, w / sum) }
+        }
+    }
+}
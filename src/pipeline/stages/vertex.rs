@@ -3,13 +3,15 @@ use std::sync::Arc;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::{ptr, mem};
 
-use ::parallel::{TrustedThreadSafe, CACHE_LINE_SIZE, Mapper};
+use ::parallel::{TrustedThreadSafe, CACHE_LINE_SIZE, map_chunks, map_chunks_until};
 
 use ::pipeline::storage::{SeparablePrimitiveStorage, SeparableScreenPrimitiveStorage};
 use ::pipeline::{PipelineObject, GeometryShader, FragmentShader};
-use ::pipeline::stages::fragment::DEFAULT_TILE_SIZE;
+use ::pipeline::slot::ShaderSlot;
+use ::pipeline::cancel::CancellationToken;
+use ::pipeline::stats::{VertexCacheStats, indexed_stats};
 use ::primitive::Primitive;
-use ::mesh::{Vertex, Mesh};
+use ::mesh::{Vertex, Mesh, MeshIndex};
 use ::interpolate::Interpolate;
 use ::geometry::{ScreenVertex, Viewport, ClipVertex};
 
@@ -27,29 +29,53 @@ use ::pipeline::types::{PipelineUniforms, StencilValue};
 /// and for the given mesh given to it when created.
 /// These cannot be modified while the vertex shader exists.
 
-pub struct VertexShader<'a, P: 'a, V: Vertex, T> where P: PipelineObject {
+pub struct VertexShader<'a, P: 'a, V: Vertex, T, I: MeshIndex = usize> where P: PipelineObject {
     pub ( in ::pipeline) pipeline: &'a mut P,
-    pub ( in ::pipeline) mesh: Arc<Mesh<V>>,
+    pub ( in ::pipeline) mesh: Arc<Mesh<V, I>>,
     pub ( in ::pipeline) indexed_primitive: PhantomData<T>,
-    pub ( in ::pipeline) stencil_value: StencilValue<P>
+    pub ( in ::pipeline) stencil_value: StencilValue<P>,
+    pub ( in ::pipeline) cancellation: Option<CancellationToken>,
 }
 
-impl<'a, P: 'a, V, T> VertexShader<'a, P, V, T> where P: PipelineObject,
-                                                      V: Vertex,
-                                                      T: Primitive {
+impl<'a, P: 'a, V, T, I> VertexShader<'a, P, V, T, I> where P: PipelineObject,
+                                                            V: Vertex,
+                                                            T: Primitive,
+                                                            I: MeshIndex {
     /// Duplicates all references to internal state to return a cloned vertex shader,
     /// though since the vertex shader itself has very little internal state at this point,
     /// it's not that useful.
     #[must_use]
-    pub fn duplicate<'b>(&'b mut self) -> VertexShader<'b, P, V, T> where 'a: 'b {
+    pub fn duplicate<'b>(&'b mut self) -> VertexShader<'b, P, V, T, I> where 'a: 'b {
         VertexShader {
             pipeline: self.pipeline,
             mesh: self.mesh.clone(),
             indexed_primitive: PhantomData,
             stencil_value: self.stencil_value,
+            cancellation: self.cancellation.clone(),
         }
     }
 
+    /// Stops shading vertices once the token is cancelled, in which case no geometry is passed on to later stages.
+    ///
+    /// See the [`cancel`](../cancel/index.html) module for details.
+    pub fn cancellation(&mut self, cancellation: Option<CancellationToken>) {
+        self.cancellation = cancellation;
+    }
+
+    pub fn with_cancellation(self, cancellation: Option<CancellationToken>) -> Self {
+        VertexShader {
+            cancellation,
+            ..self
+        }
+    }
+
+    /// Returns how well the mesh will reuse transformed vertices.
+    ///
+    /// Every vertex in the mesh is transformed once, even if it isn't referenced by any primitive.
+    pub fn vertex_cache_stats(&self) -> VertexCacheStats {
+        indexed_stats(self.mesh.vertices.len(), self.mesh.indices.iter().map(|index| index.to_usize()))
+    }
+
     /// Executes the vertex shader on every vertex in the mesh,
     /// (hopefully) returning a `ClipVertex` with the transformed vertex in clip-space
     /// and any uniforms to be passed into the fragment shader.
@@ -84,29 +110,22 @@ impl<'a, P: 'a, V, T> VertexShader<'a, P, V, T> where P: PipelineObject,
     ///
     /// See the [`full_example`](https://github.com/novacrazy/rust-softrender/tree/master/full_example) project for this in action.
     #[must_use]
-    pub fn run<S, K>(self, vertex_shader: S) -> GeometryShader<'a, P, V, T, K>
+    pub fn run<S, K>(self, vertex_shader: S) -> GeometryShader<'a, P, V, T, K, I>
         where S: Fn(&V, &PipelineUniforms<P>) -> ClipVertex<V::Scalar, K> + Send + Sync,
               K: Send + Sync + Interpolate {
-        let VertexShader { pipeline, mesh, stencil_value, .. } = self;
+        let VertexShader { pipeline, mesh, stencil_value, cancellation, .. } = self;
 
         let indexed_vertices = {
-            let (uniforms, _, pool) = pipeline.all_mut();
-
-            let thread_count = pool.thread_count();
+            profile_scope!("vertex");
 
-            let mapper = Mapper::new(mesh.vertices.len());
+            let (uniforms, _, pool) = pipeline.all_mut();
 
-            pool.scoped(|scope| {
-                for _ in 0..thread_count {
-                    scope.execute(|| {
-                        mapper.map(&mesh.vertices, |vertex| {
-                            vertex_shader(vertex, uniforms)
-                        });
-                    })
-                }
-            });
+            let shader = |vertex: &V| vertex_shader(vertex, uniforms);
 
-            mapper.into_target()
+            match cancellation {
+                Some(ref token) => map_chunks_until(pool, &mesh.vertices, token.flag(), shader),
+                None => Some(map_chunks(pool, &mesh.vertices, shader)),
+            }
         };
 
         GeometryShader {
@@ -114,48 +133,71 @@ impl<'a, P: 'a, V, T> VertexShader<'a, P, V, T> where P: PipelineObject,
             mesh,
             indexed_primitive: PhantomData,
             stencil_value,
-            indexed_vertices: Some(indexed_vertices),
+            indexed_vertices,
             generated_primitives: SeparablePrimitiveStorage::default(),
         }
     }
 
+    /// Same as `run`, but takes the shader as a trait object.
+    ///
+    /// This avoids instantiating the vertex stage for every closure type, at the cost of an indirect call per vertex.
+    /// See `FragmentShader::run_dyn` for more details.
+    #[must_use]
+    pub fn run_dyn<K>(self, vertex_shader: &(Fn(&V, &PipelineUniforms<P>) -> ClipVertex<V::Scalar, K> + Send + Sync)) -> GeometryShader<'a, P, V, T, K, I>
+        where K: Send + Sync + Interpolate {
+        self.run(vertex_shader)
+    }
+
+    /// Same as `run_to_fragment`, but takes the shader as a trait object.
+    #[must_use]
+    pub fn run_to_fragment_dyn<K>(self, viewport: Viewport<V::Scalar>,
+                                  vertex_shader: &(Fn(&V, &PipelineUniforms<P>) -> ClipVertex<V::Scalar, K> + Send + Sync)) -> FragmentShader<'a, P, V, T, K, (), I>
+        where K: Send + Sync + Interpolate {
+        self.run_to_fragment(viewport, vertex_shader)
+    }
+
+    /// Same as `run`, but loads the shader from a `ShaderSlot` at the start of the draw.
+    #[must_use]
+    pub fn run_slot<S, K>(self, slot: &ShaderSlot<S>) -> GeometryShader<'a, P, V, T, K, I>
+        where S: ?Sized + Fn(&V, &PipelineUniforms<P>) -> ClipVertex<V::Scalar, K> + Send + Sync,
+              K: Send + Sync + Interpolate {
+        let vertex_shader = slot.load();
+
+        self.run(&*vertex_shader)
+    }
+
+    /// Same as `run_to_fragment`, but loads the shader from a `ShaderSlot` at the start of the draw.
     #[must_use]
-    pub fn run_to_fragment<S, K>(self, viewport: Viewport<V::Scalar>, vertex_shader: S) -> FragmentShader<'a, P, V, T, K, ()>
+    pub fn run_slot_to_fragment<S, K>(self, viewport: Viewport<V::Scalar>, slot: &ShaderSlot<S>) -> FragmentShader<'a, P, V, T, K, (), I>
+        where S: ?Sized + Fn(&V, &PipelineUniforms<P>) -> ClipVertex<V::Scalar, K> + Send + Sync,
+              K: Send + Sync + Interpolate {
+        let vertex_shader = slot.load();
+
+        self.run_to_fragment(viewport, &*vertex_shader)
+    }
+
+    #[must_use]
+    pub fn run_to_fragment<S, K>(self, viewport: Viewport<V::Scalar>, vertex_shader: S) -> FragmentShader<'a, P, V, T, K, (), I>
         where S: Fn(&V, &PipelineUniforms<P>) -> ClipVertex<V::Scalar, K> + Send + Sync,
               K: Send + Sync + Interpolate {
-        let VertexShader { pipeline, mesh, stencil_value, .. } = self;
+        let VertexShader { pipeline, mesh, stencil_value, cancellation, .. } = self;
 
         let indexed_vertices = {
-            let (uniforms, _, pool) = pipeline.all_mut();
-
-            let thread_count = pool.thread_count();
+            profile_scope!("vertex");
 
-            let mapper = Mapper::new(mesh.vertices.len());
+            let (uniforms, _, pool) = pipeline.all_mut();
 
-            pool.scoped(|scope| {
-                for _ in 0..thread_count {
-                    scope.execute(|| {
-                        mapper.map(&mesh.vertices, |vertex| {
-                            vertex_shader(vertex, uniforms).normalize(viewport)
-                        });
-                    })
-                }
-            });
+            let shader = |vertex: &V| vertex_shader(vertex, uniforms).normalize(viewport);
 
-            mapper.into_target()
+            match cancellation {
+                Some(ref token) => map_chunks_until(pool, &mesh.vertices, token.flag(), shader),
+                None => Some(map_chunks(pool, &mesh.vertices, shader)),
+            }
         };
 
-        FragmentShader {
-            pipeline,
-            mesh,
-            indexed_primitive: PhantomData,
-            stencil_value,
-            indexed_vertices: Arc::new(Some(indexed_vertices)),
-            generated_primitives: Arc::new(SeparableScreenPrimitiveStorage::default()),
-            cull_faces: None,
-            blend: (),
-            antialiased_lines: false,
-            tile_size: DEFAULT_TILE_SIZE,
-        }
+        FragmentShader::from_parts(pipeline, mesh, stencil_value,
+                                   Arc::new(indexed_vertices),
+                                   Arc::new(SeparableScreenPrimitiveStorage::default()))
+            .with_cancellation(cancellation)
     }
 }
\ No newline at end of file
@@ -0,0 +1,140 @@
+//! Data-driven render state descriptions
+//!
+//! A `RenderStateDesc` collects the fixed-function state of a draw into a single plain structure,
+//! which can be stored in scene or material files and applied to a `FragmentShader` at runtime.
+//!
+//! With the `ron_compat` or `toml_compat` cargo features, descriptions can be parsed directly from RON or TOML.
+//! For example, in RON:
+//!
+//! ```text
+//! (
+//!     cull_faces: Some(Clockwise),
+//!     blend: AlphaOver,
+//!     depth_test: GreaterThan,
+//!     stencil: None,
+//!     tile_size: Some((width: 64, height: 64)),
+//! )
+//! ```
+
+use ::numeric::FloatScalar;
+use ::geometry::{Dimensions, FaceWinding, PixelCenter, Viewport};
+use ::pipeline::stages::rasterization::Tile;
+use ::pipeline::subsample::Subsample;
+use ::stencil::GenericStencilConfig;
+use ::attachments::depth::DepthTest;
+use ::color::ColorMask;
+use ::color::blend::BlendPreset;
+
+/// Describes the fixed-function state of a draw.
+///
+/// Fields left out of a serialized description take their default values.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde_compat", serde(default))]
+pub struct RenderStateDesc {
+    /// Face winding to cull, if any
+    pub cull_faces: Option<FaceWinding>,
+    /// Blend function applied to shaded fragments
+    pub blend: BlendPreset,
+    /// Depth test performed before fragment shading
+    pub depth_test: DepthTest,
+    /// Stencil configuration overriding the pipeline stencil configuration, if any
+    pub stencil: Option<GenericStencilConfig>,
+    /// Tile size used for rasterization, if different from the default
+    pub tile_size: Option<Dimensions>,
+    /// Whether to draw antialiased lines
+    pub antialiased_lines: bool,
+    /// Color channels written by draws
+    pub color_mask: ColorMask,
+}
+
+/// Complete render state of a pipeline, which every new draw starts out with.
+///
+/// Use `Pipeline::push_state` and `Pipeline::pop_state` to temporarily change it,
+/// so helper functions can tweak state without affecting their callers.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde_compat", serde(default))]
+pub struct RenderState {
+    /// Fixed-function state
+    pub desc: RenderStateDesc,
+    /// Scissor rectangle applied to draws, if any
+    pub scissor: Option<Tile>,
+    /// Viewport used by `GeometryShader::finish_default`, if any
+    pub viewport: Option<Viewport<f64>>,
+    /// Which pixels draws skip every other frame, if any
+    pub subsample: Subsample,
+    /// Where pixels are sampled within their area
+    pub pixel_center: PixelCenter,
+}
+
+impl RenderState {
+    /// Returns the state viewport converted to the given scalar type,
+    /// or a viewport covering the whole framebuffer with a depth range of `[0, 1]`
+    pub fn viewport_or_full<N: FloatScalar>(&self, dimensions: Dimensions) -> Viewport<N> {
+        match self.viewport {
+            Some(ref viewport) => viewport.cast(),
+            None => Viewport::new(dimensions, Default::default(), N::zero(), N::one()),
+        }
+    }
+}
+
+#[cfg(feature = "ron_compat")]
+impl RenderStateDesc {
+    /// Parses a render state description from a RON string
+    pub fn from_ron_str(s: &str) -> Result<RenderStateDesc, ::ron::de::Error> {
+        ::ron::de::from_str(s)
+    }
+}
+
+#[cfg(feature = "toml_compat")]
+impl RenderStateDesc {
+    /// Parses a render state description from a TOML string
+    pub fn from_toml_str(s: &str) -> Result<RenderStateDesc, ::toml::de::Error> {
+        ::toml::from_str(s)
+    }
+}
+
+#[cfg(all(test, any(feature = "ron_compat", feature = "toml_compat")))]
+mod test {
+    use super::*;
+
+    use ::stencil::{StencilOp, StencilTest};
+
+    /// Description with every field set away from its default
+    fn desc() -> RenderStateDesc {
+        RenderStateDesc {
+            cull_faces: Some(FaceWinding::Clockwise),
+            blend: BlendPreset::AlphaOver,
+            depth_test: DepthTest::GreaterThan,
+            stencil: Some(GenericStencilConfig { op: StencilOp::Replace, test: StencilTest::Equal }),
+            tile_size: Some(Dimensions::new(64, 32)),
+            antialiased_lines: true,
+            color_mask: ColorMask::rgb(),
+        }
+    }
+
+    #[cfg(feature = "ron_compat")]
+    #[test]
+    fn test_ron_round_trip() {
+        let s = ::ron::ser::to_string(&desc()).unwrap();
+
+        assert_eq!(RenderStateDesc::from_ron_str(&s).unwrap(), desc());
+
+        // Fields left out take their default values
+        assert_eq!(RenderStateDesc::from_ron_str("(blend: Additive)").unwrap(),
+                   RenderStateDesc { blend: BlendPreset::Additive, ..RenderStateDesc::default() });
+    }
+
+    #[cfg(feature = "toml_compat")]
+    #[test]
+    fn test_toml_round_trip() {
+        // Going through `Value` emits plain values before tables, as TOML requires
+        let s = ::toml::Value::try_from(desc()).unwrap().to_string();
+
+        assert_eq!(RenderStateDesc::from_toml_str(&s).unwrap(), desc());
+
+        assert_eq!(RenderStateDesc::from_toml_str("blend = \"Additive\"").unwrap(),
+                   RenderStateDesc { blend: BlendPreset::Additive, ..RenderStateDesc::default() });
+    }
+}
@@ -0,0 +1,57 @@
+//! Vertex reuse statistics
+
+/// Statistics on how well transformed vertices are reused by primitives.
+///
+/// Indexed vertices are transformed exactly once and shared by every primitive referencing them,
+/// so meshes with a lot of shared vertices get the most out of this. Triangles generated by
+/// a geometry shader are indexed too, so triangle strips, fans and billboards share their vertices,
+/// but every vertex of generated points and lines counts as a miss.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct VertexCacheStats {
+    /// Number of vertex references by all primitives
+    pub references: usize,
+    /// Number of vertices transformed
+    pub transformed: usize,
+    /// Number of transformed vertices not referenced by any primitive
+    pub unreferenced: usize,
+}
+
+impl VertexCacheStats {
+    /// Number of vertex references that reused an already transformed vertex
+    #[inline]
+    pub fn hits(&self) -> usize {
+        self.references.saturating_sub(self.transformed - self.unreferenced)
+    }
+
+    /// Fraction of vertex references that reused an already transformed vertex
+    pub fn hit_ratio(&self) -> f32 {
+        if self.references == 0 { 0.0 } else { self.hits() as f32 / self.references as f32 }
+    }
+
+    /// Average number of vertices transformed per primitive with the given number of vertices
+    pub fn transformed_per_primitive(&self, vertices_per_primitive: usize) -> f32 {
+        let primitives = self.references / vertices_per_primitive;
+
+        if primitives == 0 { 0.0 } else { self.transformed as f32 / primitives as f32 }
+    }
+}
+
+/// Computes vertex reuse statistics for indexed vertices
+pub ( in ::pipeline ) fn indexed_stats<I>(num_vertices: usize, indices: I) -> VertexCacheStats where I: Iterator<Item = usize> {
+    let mut referenced = vec![false; num_vertices];
+    let mut references = 0;
+
+    for index in indices {
+        if let Some(r) = referenced.get_mut(index) {
+            *r = true;
+        }
+
+        references += 1;
+    }
+
+    VertexCacheStats {
+        references,
+        transformed: num_vertices,
+        unreferenced: referenced.iter().filter(|&&r| !r).count(),
+    }
+}
@@ -0,0 +1,124 @@
+//! Stereo rendering
+//!
+//! Renders a mesh twice, once per eye, into the left and right halves of a single framebuffer.
+//!
+//! The vertex shader is only ran once, and should output vertices in a space shared by both eyes,
+//! such as world-space or head-space. The per-eye view and projection transforms are then applied
+//! to the shared vertices before each eye is rasterized.
+
+use std::sync::Arc;
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::primitive::{Primitive, PrimitiveRef};
+use ::color::blend::Blend;
+use ::geometry::{Dimensions, ClipVertex, ScreenVertex};
+use ::interpolate::Interpolate;
+use ::pipeline::{Pipeline, PipelineObject};
+use ::pipeline::split::{SplitScreen, Partition};
+use ::pipeline::stages::fragment::Fragment;
+
+use ::pipeline::types::{PipelineUniforms, Pixel};
+
+/// Identifies an eye in stereo rendering
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+impl Eye {
+    /// Both eyes, in rendering order
+    pub const BOTH: [Eye; 2] = [Eye::Left, Eye::Right];
+
+    /// Returns the horizontal offset of the eye from the center of the head,
+    /// given the interpupillary distance.
+    ///
+    /// The left eye has a negative offset, so to create the view matrix for an eye
+    /// translate the head view matrix by the negated offset.
+    pub fn offset<N: FloatScalar>(self, ipd: N) -> N {
+        let half = ipd / cast(2.0).unwrap();
+
+        match self {
+            Eye::Left => -half,
+            Eye::Right => half,
+        }
+    }
+
+    /// Index of the eye, zero for left and one for right
+    #[inline]
+    pub fn index(self) -> usize {
+        match self {
+            Eye::Left => 0,
+            Eye::Right => 1,
+        }
+    }
+}
+
+/// Side-by-side stereo layout of a framebuffer
+#[derive(Debug, Clone)]
+pub struct Stereo<N> where N: FloatScalar {
+    split: SplitScreen<N>,
+}
+
+impl<N> Stereo<N> where N: FloatScalar {
+    /// Splits the framebuffer into left and right halves
+    pub fn side_by_side(dimensions: Dimensions, near: N, far: N) -> Stereo<N> {
+        Stereo { split: SplitScreen::horizontal(dimensions, 2, near, far) }
+    }
+
+    /// Returns the framebuffer partition of the given eye
+    #[inline]
+    pub fn partition(&self, eye: Eye) -> &Partition<N> {
+        &self.split.partitions()[eye.index()]
+    }
+
+    /// Aspect ratio of a single eye, for creating per-eye projection matrices
+    #[inline]
+    pub fn aspect_ratio(&self) -> N {
+        self.partition(Eye::Left).viewport.aspect_ratio()
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Renders a mesh for both eyes of a side-by-side stereo layout.
+    ///
+    /// The vertex shader is ran once for the whole mesh. Then, for each eye, `eye_transform` is given
+    /// each shared vertex and returns the vertex in clip-space for that eye, which is rasterized
+    /// into the eye's half of the framebuffer.
+    pub fn render_stereo<T, V, I, K, B, VS, ES, FS>(&mut self, stereo: &Stereo<V::Scalar>, primitive: T, mesh: Arc<Mesh<V, I>>,
+                                                 vertex_shader: VS, eye_transform: ES, fragment_shader: FS)
+        where T: Primitive,
+              V: Vertex,
+              I: MeshIndex,
+              K: Send + Sync + Clone + Interpolate,
+              B: Blend<Pixel<Self>> + Default,
+              VS: Fn(&V, &PipelineUniforms<Self>) -> ClipVertex<V::Scalar, K> + Send + Sync,
+              ES: Fn(&ClipVertex<V::Scalar, K>, Eye, &PipelineUniforms<Self>) -> ClipVertex<V::Scalar, K> + Send + Sync,
+              FS: Fn(&ScreenVertex<V::Scalar, K>, &PipelineUniforms<Self>, Eye) -> Fragment<Pixel<Self>> + Send + Sync {
+        let mut shared = self.render_mesh(primitive, mesh, None).run(vertex_shader);
+
+        for &eye in &Eye::BOTH {
+            let partition = stereo.partition(eye);
+
+            shared.duplicate()
+                  .run(|mut storage, primitive, uniforms| {
+                      let transform = |vertex| eye_transform(vertex, eye, uniforms);
+
+                      match primitive {
+                          PrimitiveRef::Point(point) => storage.emit_point(transform(point)),
+                          PrimitiveRef::Line { start, end } => storage.emit_line(transform(start), transform(end)),
+                          PrimitiveRef::Triangle { a, b, c } => storage.emit_triangle(transform(a), transform(b), transform(c)),
+                      }
+                  })
+                  .clip_primitives()
+                  .finish(partition.viewport)
+                  .with_scissor(Some(partition.scissor))
+                  .with_default_blend::<B>()
+                  .run(|vertex, uniforms| fragment_shader(vertex, uniforms, eye));
+        }
+    }
+}
@@ -1,14 +1,24 @@
 //! Storage structures
 
+use nalgebra::{Vector2, Vector4};
+
+use num_traits::cast;
+
 use ::numeric::FloatScalar;
+use ::numeric::utils::min;
+use ::mesh::MeshIndex;
 use ::geometry::{ClipVertex, ScreenVertex};
+use ::geometry::billboard::{BILLBOARD_CORNERS, BILLBOARD_INDICES};
 use ::primitive::PrimitiveRef;
+use ::pipeline::arena::PrimitiveCapacity;
 
+/// Triangles are indexed, so vertices shared by triangle strips and fans are only stored and transformed once.
 #[derive(Clone)]
 pub ( in ::pipeline ) struct SeparablePrimitiveStorage<N: FloatScalar, K> {
     pub points: Vec<ClipVertex<N, K>>,
     pub lines: Vec<ClipVertex<N, K>>,
     pub tris: Vec<ClipVertex<N, K>>,
+    pub tri_indices: Vec<usize>,
 }
 
 impl<N, K> Default for SeparablePrimitiveStorage<N, K> where N: FloatScalar {
@@ -17,15 +27,37 @@ impl<N, K> Default for SeparablePrimitiveStorage<N, K> where N: FloatScalar {
             points: Vec::new(),
             lines: Vec::new(),
             tris: Vec::new(),
+            tri_indices: Vec::new(),
         }
     }
 }
 
 impl<N, K> SeparablePrimitiveStorage<N, K> where N: FloatScalar {
+    pub fn with_capacity(capacity: PrimitiveCapacity) -> SeparablePrimitiveStorage<N, K> {
+        SeparablePrimitiveStorage {
+            points: Vec::with_capacity(capacity.points),
+            lines: Vec::with_capacity(capacity.lines),
+            tris: Vec::with_capacity(capacity.tris),
+            tri_indices: Vec::with_capacity(capacity.tris),
+        }
+    }
+
+    /// Stores triangles given as consecutive triples of vertices, without any sharing
+    pub fn from_unindexed(points: Vec<ClipVertex<N, K>>,
+                          lines: Vec<ClipVertex<N, K>>,
+                          tris: Vec<ClipVertex<N, K>>) -> SeparablePrimitiveStorage<N, K> {
+        let tri_indices = (0..tris.len()).collect();
+
+        SeparablePrimitiveStorage { points, lines, tris, tri_indices }
+    }
+
     pub fn append(&mut self, other: &mut SeparablePrimitiveStorage<N, K>) {
+        let offset = self.tris.len();
+
         self.points.append(&mut other.points);
         self.lines.append(&mut other.lines);
         self.tris.append(&mut other.tris);
+        self.tri_indices.extend(other.tri_indices.drain(..).map(|index| index + offset));
     }
 
     #[inline]
@@ -42,10 +74,26 @@ impl<N, K> SeparablePrimitiveStorage<N, K> where N: FloatScalar {
 
     #[inline]
     pub fn push_triangle(&mut self, a: ClipVertex<N, K>, b: ClipVertex<N, K>, c: ClipVertex<N, K>) {
+        let first = self.tris.len();
+
         self.tris.reserve(3);
         self.tris.push(a);
         self.tris.push(b);
         self.tris.push(c);
+
+        self.push_tri_indices(first, first + 1, first + 2);
+    }
+
+    /// Adds a vertex for indexed triangles, returning its index
+    #[inline]
+    pub fn push_tri_vertex(&mut self, vertex: ClipVertex<N, K>) -> usize {
+        self.tris.push(vertex);
+        self.tris.len() - 1
+    }
+
+    #[inline]
+    pub fn push_tri_indices(&mut self, a: usize, b: usize, c: usize) {
+        self.tri_indices.extend_from_slice(&[a, b, c]);
     }
 }
 
@@ -55,6 +103,7 @@ pub ( in ::pipeline ) struct SeparableScreenPrimitiveStorage<N: FloatScalar, K>
     pub points: Vec<ScreenVertex<N, K>>,
     pub lines: Vec<ScreenVertex<N, K>>,
     pub tris: Vec<ScreenVertex<N, K>>,
+    pub tri_indices: Vec<usize>,
 }
 
 impl<N, K> Default for SeparableScreenPrimitiveStorage<N, K> where N: FloatScalar {
@@ -63,10 +112,39 @@ impl<N, K> Default for SeparableScreenPrimitiveStorage<N, K> where N: FloatScala
             points: Vec::new(),
             lines: Vec::new(),
             tris: Vec::new(),
+            tri_indices: Vec::new(),
         }
     }
 }
 
+impl<N, K> SeparableScreenPrimitiveStorage<N, K> where N: FloatScalar {
+    #[inline]
+    pub fn num_triangles(&self) -> usize {
+        self.tri_indices.len() / 3
+    }
+
+    /// Returns the three vertices of triangle `j`
+    #[inline]
+    pub fn triangle(&self, j: usize) -> [&ScreenVertex<N, K>; 3] {
+        let t = &self.tri_indices[j * 3..j * 3 + 3];
+
+        [&self.tris[t[0]], &self.tris[t[1]], &self.tris[t[2]]]
+    }
+}
+
+/// Looks up the vertices of an indexed primitive of up to three vertices without allocating,
+/// returning them along with how many there are.
+pub ( in ::pipeline ) fn primitive_vertices<'a, N, K, I>(vertices: &'a [ScreenVertex<N, K>], primitive: &[I]) -> ([&'a ScreenVertex<N, K>; 3], usize)
+    where N: FloatScalar, I: MeshIndex {
+    let mut gathered = [&vertices[primitive[0].to_usize()]; 3];
+
+    for (vertex, index) in gathered.iter_mut().zip(primitive) {
+        *vertex = &vertices[index.to_usize()];
+    }
+
+    (gathered, min(primitive.len(), 3))
+}
+
 /// Holds a reference to the internal storage structure for primitives
 pub struct PrimitiveStorage<'s, N: FloatScalar, K: 's> {
     pub ( in ::pipeline ) inner: &'s mut SeparablePrimitiveStorage<N, K>,
@@ -91,6 +169,72 @@ impl<'s, N, K: 's> PrimitiveStorage<'s, N, K> where N: FloatScalar {
         self.inner.push_triangle(a, b, c)
     }
 
+    /// Adds triangles from a triangle strip, where every vertex after the first two forms a triangle with the previous two.
+    ///
+    /// Every other triangle is flipped to keep a consistent winding order.
+    /// Each vertex is stored once and shared by all the triangles using it.
+    pub fn emit_triangle_strip(&mut self, vertices: &[ClipVertex<N, K>]) where K: Clone {
+        if vertices.len() < 3 { return; }
+
+        let first = self.inner.tris.len();
+
+        self.inner.tris.extend_from_slice(vertices);
+
+        for i in first + 2..first + vertices.len() {
+            if (i - first) % 2 == 0 {
+                self.inner.push_tri_indices(i - 2, i - 1, i);
+            } else {
+                self.inner.push_tri_indices(i - 1, i - 2, i);
+            }
+        }
+    }
+
+    /// Adds triangles from a triangle fan, where every vertex after the first two forms a triangle with the first and previous vertex.
+    ///
+    /// Each vertex is stored once and shared by all the triangles using it.
+    pub fn emit_triangle_fan(&mut self, vertices: &[ClipVertex<N, K>]) where K: Clone {
+        if vertices.len() < 3 { return; }
+
+        let first = self.inner.tris.len();
+
+        self.inner.tris.extend_from_slice(vertices);
+
+        for i in first + 2..first + vertices.len() {
+            self.inner.push_tri_indices(first, i - 1, i);
+        }
+    }
+
+    /// Expands a point into a screen-aligned quad of two triangles, for particles and sprites.
+    ///
+    /// `half_size` is added to the clip-space position of the corners, so multiplied by the `(0, 0)` and `(1, 1)` entries
+    /// of the projection matrix it is a view-space size that shrinks with distance, and multiplied by the `w` coordinate
+    /// of the point it is a size in normalized device coordinates that stays the same on screen.
+    ///
+    /// `uniforms` is called for each corner with the uniforms of the point and the texture coordinates of the corner.
+    pub fn emit_billboard<J, F>(&mut self, center: &ClipVertex<N, J>, half_size: Vector2<N>, mut uniforms: F)
+        where K: Clone, F: FnMut(&J, Vector2<N>) -> K {
+        let corner = |i: usize, uniforms: &mut F| {
+            let (offset, uv) = BILLBOARD_CORNERS[i];
+
+            let (dx, dy, u, v): (N, N, N, N) = (cast(offset[0]).unwrap(), cast(offset[1]).unwrap(),
+                                                cast(uv[0]).unwrap(), cast(uv[1]).unwrap());
+
+            let position = center.position + Vector4::new(dx * half_size.x, dy * half_size.y, N::zero(), N::zero());
+
+            ClipVertex::new(position, uniforms(&center.uniforms, Vector2::new(u, v)))
+        };
+
+        let first = self.inner.tris.len();
+
+        for i in 0..BILLBOARD_CORNERS.len() {
+            self.inner.push_tri_vertex(corner(i, &mut uniforms));
+        }
+
+        for triangle in BILLBOARD_INDICES.chunks(3) {
+            self.inner.push_tri_indices(first + triangle[0], first + triangle[1], first + triangle[2]);
+        }
+    }
+
     #[inline]
     pub fn emit<'p>(&mut self, primitive: PrimitiveRef<'p, N, K>) where K: Clone {
         match primitive {
@@ -0,0 +1,268 @@
+//! Interlaced and checkerboard rendering
+//!
+//! On slow machines, rendering only half the pixels every frame can double the framerate of interactive demos.
+//! With a `Subsample` mode set in the render state, every draw only renders one field of pixels,
+//! either every other row or every other pixel in a checkerboard pattern, alternating between the two fields
+//! every time `Pipeline::begin_frame` is called. The other field still holds the previous frame,
+//! and is filled in by `Pipeline::reconstruct_field` at the end of the frame:
+//!
+//! ```ignore
+//! pipeline.render_state_mut().subsample = Subsample::Checkerboard;
+//!
+//! pipeline.begin_frame();
+//! pipeline.clear_field(background);
+//!
+//! draw_scene(&mut pipeline);
+//!
+//! pipeline.reconstruct_field(Reconstruction::default());
+//! ```
+//!
+//! The framebuffer must not be cleared with `Framebuffer::clear`, since that throws away the previous field.
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite};
+use ::framebuffer::{Framebuffer, UnsafeFramebuffer};
+use ::framebuffer::attachments::Depth;
+use ::framebuffer::types::ColorAttachment;
+use ::pipeline::{Pipeline, PipelineObject};
+
+/// Which pixels are rendered each frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Subsample {
+    /// Every pixel is rendered every frame
+    Off,
+    /// Even rows are rendered in even frames, odd rows in odd frames
+    Interlaced,
+    /// Pixels with an even sum of their coordinates are rendered in even frames, the others in odd frames
+    Checkerboard,
+}
+
+impl Default for Subsample {
+    fn default() -> Subsample { Subsample::Off }
+}
+
+/// Half of the pixels of a subsample mode, rendered in the same frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SubsampleField {
+    mode: Subsample,
+    parity: u32,
+}
+
+impl SubsampleField {
+    /// Field rendered in the given frame, as counted by `FrameArena::frame`
+    pub fn new(mode: Subsample, frame: u64) -> SubsampleField {
+        SubsampleField { mode, parity: (frame & 1) as u32 }
+    }
+
+    /// Field covering every pixel
+    pub fn full() -> SubsampleField {
+        SubsampleField::default()
+    }
+
+    #[inline]
+    pub fn mode(&self) -> Subsample { self.mode }
+
+    /// Either `0` or `1`, alternating every frame
+    #[inline]
+    pub fn parity(&self) -> u32 { self.parity }
+
+    /// The field rendered in the next frame
+    pub fn other(&self) -> SubsampleField {
+        SubsampleField { parity: self.parity ^ 1, ..*self }
+    }
+
+    /// Returns true if the pixel is rendered in this field
+    #[inline]
+    pub fn covers(&self, x: u32, y: u32) -> bool {
+        match self.mode {
+            Subsample::Off => true,
+            Subsample::Interlaced => y & 1 == self.parity,
+            Subsample::Checkerboard => (x ^ y) & 1 == self.parity,
+        }
+    }
+
+    /// Offsets to the neighbors of a pixel outside the field that are inside it
+    fn neighbors(&self) -> &'static [(i64, i64)] {
+        match self.mode {
+            Subsample::Off => &[],
+            Subsample::Interlaced => &[(0, -1), (0, 1)],
+            Subsample::Checkerboard => &[(-1, 0), (1, 0), (0, -1), (0, 1)],
+        }
+    }
+}
+
+/// How pixels outside the rendered field are filled in
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Reconstruction {
+    /// Keeps the previous frame, which is sharp for still images but shows combing artifacts on movement
+    Weave,
+    /// Averages the rendered neighbors, which is blurrier but never lags behind
+    Spatial,
+    /// Keeps the previous frame where it's within `threshold` of the average of the rendered neighbors
+    /// in every channel, and uses that average elsewhere, so only moving edges are blurred
+    Adaptive { threshold: f32 },
+}
+
+impl Default for Reconstruction {
+    fn default() -> Reconstruction { Reconstruction::Adaptive { threshold: 0.1 } }
+}
+
+/// Fills in the pixels of `buffer` outside of `field`, which hold the previous frame, from the pixels inside it
+pub fn reconstruct_field<P>(buffer: &mut P, field: SubsampleField, reconstruction: Reconstruction)
+    where P: PixelWrite, P::Color: ToChannels + FromChannels {
+    if field.mode == Subsample::Off || reconstruction == Reconstruction::Weave {
+        return;
+    }
+
+    let dimensions = buffer.dimensions();
+    let (width, height) = (dimensions.width as i64, dimensions.height as i64);
+
+    buffer.prepare_write();
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            if field.covers(x, y) {
+                continue;
+            }
+
+            let mut sum = [0.0; 4];
+            let mut count = 0;
+
+            for &(dx, dy) in field.neighbors() {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+
+                if nx >= 0 && ny >= 0 && nx < width && ny < height {
+                    let neighbor = unsafe { buffer.get_pixel_unchecked(buffer.index_of(Coordinate::new(nx as u32, ny as u32))).to_channels() };
+
+                    for (s, n) in sum.iter_mut().zip(neighbor.iter()) {
+                        *s += n;
+                    }
+
+                    count += 1;
+                }
+            }
+
+            if count == 0 {
+                continue;
+            }
+
+            for s in &mut sum {
+                *s /= count as f32;
+            }
+
+            let index = buffer.index_of(Coordinate::new(x, y));
+
+            if let Reconstruction::Adaptive { threshold } = reconstruction {
+                let previous = unsafe { buffer.get_pixel_unchecked(index).to_channels() };
+
+                if previous.iter().zip(sum.iter()).all(|(p, s)| (p - s).abs() <= threshold) {
+                    continue;
+                }
+            }
+
+            unsafe { buffer.set_pixel_unchecked(index, P::Color::from_channels(sum)); }
+        }
+    }
+}
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Field rendered by draws in the current frame
+    pub fn subsample_field(&self) -> SubsampleField {
+        SubsampleField::new(self.render_state().subsample, self.arena().frame())
+    }
+
+    /// Clears color, depth and stencil of the pixels in the current field only,
+    /// or the whole framebuffer if subsampling is off.
+    pub fn clear_field(&mut self, color: ColorAttachment<<Self as PipelineObject>::Framebuffer>) {
+        let field = self.subsample_field();
+
+        if field.mode() == Subsample::Off {
+            return self.framebuffer_mut().clear(color);
+        }
+
+        let framebuffer = self.framebuffer_mut();
+
+        let dimensions = framebuffer.dimensions();
+
+        framebuffer.prepare_write();
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                if field.covers(x, y) {
+                    let index = framebuffer.index_of(Coordinate::new(x, y));
+
+                    unsafe {
+                        framebuffer.set_pixel_unchecked(index, color);
+                        framebuffer.set_depth_unchecked(index, Depth::far());
+                        framebuffer.set_stencil_unchecked(index, Default::default());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fills in the pixels outside of the current field, after everything has been drawn
+    pub fn reconstruct_field(&mut self, reconstruction: Reconstruction)
+        where ColorAttachment<<Self as PipelineObject>::Framebuffer>: ToChannels + FromChannels {
+        let field = self.subsample_field();
+
+        reconstruct_field(self.framebuffer_mut(), field, reconstruction);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+    use ::color::predefined::formats::Rf32Color;
+
+    use super::*;
+
+    #[test]
+    fn test_subsample_fields() {
+        let even = SubsampleField::new(Subsample::Checkerboard, 4);
+        let odd = SubsampleField::new(Subsample::Checkerboard, 5);
+
+        assert_eq!(even.other(), odd);
+
+        for &(x, y) in &[(0, 0), (3, 1), (2, 7), (5, 2)] {
+            assert!(even.covers(x, y) != odd.covers(x, y));
+            assert!(SubsampleField::full().covers(x, y));
+        }
+
+        assert!(SubsampleField::new(Subsample::Interlaced, 1).covers(7, 3));
+        assert!(!SubsampleField::new(Subsample::Interlaced, 1).covers(7, 4));
+    }
+
+    #[test]
+    fn test_reconstruction() {
+        let field = SubsampleField::new(Subsample::Interlaced, 0);
+
+        // Rendered rows hold their row index, the others hold a stale value from the previous frame
+        let mut spatial = ColorBuffer::from_fn(Dimensions::new(2, 5), |coord| {
+            Rf32Color::new(if field.covers(coord.x, coord.y) { coord.y as f32 } else { 10.0 })
+        });
+
+        reconstruct_field(&mut spatial, field, Reconstruction::Spatial);
+
+        assert_eq!(spatial.pixel_ref(Coordinate::new(1, 1)).unwrap().get(), Rf32Color::new(1.0));
+        assert_eq!(spatial.pixel_ref(Coordinate::new(0, 3)).unwrap().get(), Rf32Color::new(3.0));
+        assert_eq!(spatial.pixel_ref(Coordinate::new(0, 4)).unwrap().get(), Rf32Color::new(4.0));
+
+        // Close enough to the neighbors keeps the previous frame
+        let mut adaptive = ColorBuffer::from_fn(Dimensions::new(2, 3), |coord| {
+            Rf32Color::new(if coord.y == 1 { 0.55 } else { 0.5 })
+        });
+
+        reconstruct_field(&mut adaptive, field, Reconstruction::Adaptive { threshold: 0.1 });
+
+        assert_eq!(adaptive.pixel_ref(Coordinate::new(0, 1)).unwrap().get(), Rf32Color::new(0.55));
+
+        reconstruct_field(&mut adaptive, field, Reconstruction::Adaptive { threshold: 0.01 });
+
+        assert_eq!(adaptive.pixel_ref(Coordinate::new(0, 1)).unwrap().get(), Rf32Color::new(0.5));
+    }
+}
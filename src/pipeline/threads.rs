@@ -0,0 +1,86 @@
+//! Thread pool configuration
+//!
+//! Pipelines start with one render thread per logical CPU. Applications with other time-critical threads,
+//! like audio or game logic, can shrink the pool at runtime to leave cores free for them:
+//!
+//! ```ignore
+//! pipeline.set_thread_count(num_cpus::get() as u32 - 2);
+//!
+//! // With the `affinity_compat` feature, keep the render threads off the first two cores
+//! pipeline.pin_threads(&[2, 3, 4, 5, 6, 7]);
+//! ```
+
+use scoped_threadpool::Pool;
+
+#[cfg(feature = "affinity_compat")]
+use std::sync::Barrier;
+#[cfg(feature = "affinity_compat")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[cfg(feature = "affinity_compat")]
+use core_affinity::{self, CoreId};
+
+use ::pipeline::{Pipeline, PipelineObject};
+
+impl<U, F, S> Pipeline<U, F, S> where Self: PipelineObject {
+    /// Number of threads used for rendering
+    #[inline]
+    pub fn thread_count(&self) -> u32 {
+        self.threadpool().thread_count()
+    }
+
+    /// Replaces the thread pool with one of `threads` threads, with a minimum of one.
+    ///
+    /// Does nothing if the pool already has that many threads. Otherwise, the old threads are joined,
+    /// so any core affinity set with `pin_threads` has to be set again.
+    pub fn set_thread_count(&mut self, threads: u32) {
+        let threads = threads.max(1);
+
+        if threads != self.thread_count() {
+            *self.threadpool_mut() = Pool::new(threads);
+        }
+    }
+
+    /// Pins the render threads to the CPU cores with the given indices, assigning them in order
+    /// and wrapping around if there are more threads than cores.
+    ///
+    /// Returns `false` if none of the cores exist or the core affinity couldn't be queried,
+    /// in which case the threads are left unpinned.
+    #[cfg(feature = "affinity_compat")]
+    pub fn pin_threads(&mut self, cores: &[usize]) -> bool {
+        let available = match core_affinity::get_core_ids() {
+            Some(available) => available,
+            None => return false,
+        };
+
+        let cores: Vec<CoreId> = cores.iter().filter_map(|&core| available.get(core).cloned()).collect();
+
+        if cores.is_empty() {
+            return false;
+        }
+
+        let pool = self.threadpool_mut();
+
+        let thread_count = pool.thread_count() as usize;
+
+        let next = AtomicUsize::new(0);
+
+        // Jobs are handed to whichever thread is free, so every job waits until all threads
+        // have one, making sure each thread pins itself exactly once.
+        let barrier = Barrier::new(thread_count);
+
+        pool.scoped(|scope| {
+            for _ in 0..thread_count {
+                scope.execute(|| {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+
+                    core_affinity::set_for_current(cores[i % cores.len()]);
+
+                    barrier.wait();
+                });
+            }
+        });
+
+        true
+    }
+}
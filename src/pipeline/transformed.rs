@@ -0,0 +1,75 @@
+//! Reusable transformed geometry
+//!
+//! Vertex and geometry shading usually happens once per draw, but multi-pass techniques like
+//! depth pre-passes, shadow masks or outlines render the exact same geometry several times with different fragment shaders.
+//!
+//! A `TransformedGeometry` keeps the screen-space output of the geometry stage around independently of the pipeline,
+//! so it can be rasterized any number of times, even into other pipelines and framebuffers,
+//! without running the vertex and geometry shaders again.
+
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::geometry::ScreenVertex;
+use ::pipeline::storage::SeparableScreenPrimitiveStorage;
+use ::pipeline::{PipelineObject, FragmentShader};
+use ::pipeline::types::StencilValue;
+
+/// Screen-space geometry ready for rasterization, created by `FragmentShader::geometry`.
+///
+/// Cloning is cheap, since all the geometry is shared.
+pub struct TransformedGeometry<V: Vertex, T, K, I: MeshIndex = usize> {
+    mesh: Arc<Mesh<V, I>>,
+    indexed_primitive: PhantomData<T>,
+    indexed_vertices: Arc<Option<Vec<ScreenVertex<V::Scalar, K>>>>,
+    generated_primitives: Arc<SeparableScreenPrimitiveStorage<V::Scalar, K>>,
+}
+
+impl<V, T, K, I> Clone for TransformedGeometry<V, T, K, I> where V: Vertex, I: MeshIndex {
+    fn clone(&self) -> Self {
+        TransformedGeometry {
+            mesh: self.mesh.clone(),
+            indexed_primitive: PhantomData,
+            indexed_vertices: self.indexed_vertices.clone(),
+            generated_primitives: self.generated_primitives.clone(),
+        }
+    }
+}
+
+impl<V, T, K, I> TransformedGeometry<V, T, K, I> where V: Vertex, I: MeshIndex {
+    pub ( in ::pipeline) fn from_parts(mesh: Arc<Mesh<V, I>>,
+                                       indexed_vertices: Arc<Option<Vec<ScreenVertex<V::Scalar, K>>>>,
+                                       generated_primitives: Arc<SeparableScreenPrimitiveStorage<V::Scalar, K>>) -> TransformedGeometry<V, T, K, I> {
+        TransformedGeometry {
+            mesh,
+            indexed_primitive: PhantomData,
+            indexed_vertices,
+            generated_primitives,
+        }
+    }
+
+    /// The mesh the geometry was transformed from
+    #[inline]
+    pub fn mesh(&self) -> &Arc<Mesh<V, I>> { &self.mesh }
+
+    /// Screen-space vertices for the mesh indices, if the mesh primitives were kept by the geometry shader
+    #[inline]
+    pub fn indexed_vertices(&self) -> Option<&[ScreenVertex<V::Scalar, K>]> {
+        (*self.indexed_vertices).as_ref().map(|vertices| &vertices[..])
+    }
+
+    /// Starts a new fragment stage for the geometry, using the render state of the given pipeline.
+    ///
+    /// The geometry was transformed for a specific viewport, so the framebuffer of the pipeline
+    /// should have the same dimensions as the one it was transformed for.
+    #[must_use]
+    pub fn render<'a, P>(&self, pipeline: &'a mut P, stencil: Option<StencilValue<P>>) -> FragmentShader<'a, P, V, T, K, (), I>
+        where P: PipelineObject {
+        FragmentShader::from_parts(pipeline,
+                                   self.mesh.clone(),
+                                   stencil.unwrap_or_default(),
+                                   self.indexed_vertices.clone(),
+                                   self.generated_primitives.clone())
+    }
+}
@@ -36,6 +36,8 @@ impl<'a, P: 'a> PixelRef<'a, P> where P: PixelRead {
 impl<'a, P: 'a> PixelMut<'a, P> where P: PixelWrite {
     #[inline(always)]
     pub ( in ::pixels ) fn new(index: usize, framebuffer: &'a mut P) -> PixelMut<'a, P> {
+        framebuffer.prepare_write();
+
         PixelMut(index, framebuffer)
     }
 
@@ -0,0 +1,91 @@
+//! Simple owned pixel buffer
+
+use ::color::Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+
+use super::{PixelBuffer, PixelRead, PixelWrite};
+
+/// A plain owned buffer of colors, without any depth or stencil attachments.
+///
+/// Mostly used for intermediate results of post-processing passes.
+#[derive(Debug, Clone)]
+pub struct ColorBuffer<C: Color> {
+    dimensions: Dimensions,
+    pixels: Vec<C>,
+}
+
+impl<C: Color> ColorBuffer<C> {
+    /// Create a new buffer with every pixel set to `Color::empty()`
+    pub fn new(dimensions: Dimensions) -> ColorBuffer<C> {
+        ColorBuffer::filled(dimensions, C::empty())
+    }
+
+    /// Create a new buffer with every pixel set to the given color
+    pub fn filled(dimensions: Dimensions, color: C) -> ColorBuffer<C> {
+        ColorBuffer { dimensions, pixels: vec![color; dimensions.area()] }
+    }
+
+    /// Create a new buffer by calling `f` for every pixel coordinate
+    pub fn from_fn<F>(dimensions: Dimensions, mut f: F) -> ColorBuffer<C> where F: FnMut(Coordinate) -> C {
+        let mut pixels = Vec::with_capacity(dimensions.area());
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                pixels.push(f(Coordinate::new(x, y)));
+            }
+        }
+
+        ColorBuffer { dimensions, pixels }
+    }
+
+    /// Copies the colors of any readable pixel buffer
+    pub fn from_buffer<P>(buffer: &P) -> ColorBuffer<C> where P: PixelRead<Color = C> {
+        let dimensions = buffer.dimensions();
+
+        ColorBuffer {
+            dimensions,
+            pixels: (0..dimensions.height).flat_map(|y| (0..dimensions.width).map(move |x| Coordinate::new(x, y)))
+                                          .map(|coord| unsafe { buffer.get_pixel_unchecked(buffer.index_of(coord)) })
+                                          .collect(),
+        }
+    }
+
+    /// Wraps existing pixels, which must be in row-major order and match the dimensions
+    pub fn from_vec(dimensions: Dimensions, pixels: Vec<C>) -> ColorBuffer<C> {
+        assert_eq!(dimensions.area(), pixels.len(), "Pixel count does not match dimensions");
+
+        ColorBuffer { dimensions, pixels }
+    }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[C] { &self.pixels }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [C] { &mut self.pixels }
+
+    #[inline]
+    pub fn into_vec(self) -> Vec<C> { self.pixels }
+}
+
+impl<C: Color> HasDimensions for ColorBuffer<C> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<C: Color> PixelBuffer for ColorBuffer<C> {
+    type Color = C;
+}
+
+impl<C: Color> PixelRead for ColorBuffer<C> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> C {
+        *self.pixels.get_unchecked(index)
+    }
+}
+
+impl<C: Color> PixelWrite for ColorBuffer<C> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: C) {
+        *self.pixels.get_unchecked_mut(index) = color;
+    }
+}
@@ -0,0 +1,139 @@
+//! Byte views of 8-bit color buffers
+//!
+//! Window surfaces and image APIs usually take plain bytes in RGBA or BGRA order, with rows that may be padded.
+//! `ColorBuffer<RGBAu8Color>` can be viewed as RGBA bytes without copying, and `copy_to_bytes` writes any
+//! pixel buffer into a byte slice with the given byte order and row pitch.
+//!
+//! Rendered buffers start with their top row, but OpenGL textures and bottom-up bitmaps start with the bottom one,
+//! so the rows can be flipped while copying instead of flipping the whole buffer beforehand.
+
+use std::mem::size_of;
+use std::slice;
+
+use ::color::predefined::formats::RGBAu8Color;
+use ::color::ToChannels;
+use ::geometry::{Coordinate, HasDimensions};
+
+use super::{PixelRead, ColorBuffer};
+
+/// Order of the color channels of a pixel in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Rgba,
+    Bgra,
+}
+
+impl ByteOrder {
+    /// Offsets of the red, green, blue and alpha bytes within a pixel
+    #[inline]
+    pub fn offsets(self) -> [usize; 4] {
+        match self {
+            ByteOrder::Rgba => [0, 1, 2, 3],
+            ByteOrder::Bgra => [2, 1, 0, 3],
+        }
+    }
+}
+
+impl Default for ByteOrder {
+    fn default() -> ByteOrder { ByteOrder::Rgba }
+}
+
+/// Order of the rows of an image in memory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RowOrder {
+    /// The first row is the top of the image, as rendered
+    TopDown,
+    /// The first row is the bottom of the image
+    BottomUp,
+}
+
+impl RowOrder {
+    /// Returns the row of a rendered image that is stored at row `y` of an image with `height` rows
+    #[inline]
+    pub fn source_row(self, y: u32, height: u32) -> u32 {
+        match self {
+            RowOrder::TopDown => y,
+            RowOrder::BottomUp => height - 1 - y,
+        }
+    }
+}
+
+impl Default for RowOrder {
+    fn default() -> RowOrder { RowOrder::TopDown }
+}
+
+impl ColorBuffer<RGBAu8Color> {
+    /// Views the pixels as tightly packed RGBA bytes, without copying
+    pub fn as_bytes(&self) -> &[u8] {
+        assert_eq!(size_of::<RGBAu8Color>(), 4);
+
+        let pixels = self.as_slice();
+
+        unsafe { slice::from_raw_parts(pixels.as_ptr() as *const u8, pixels.len() * 4) }
+    }
+
+    /// Views the pixels as tightly packed mutable RGBA bytes, without copying
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        assert_eq!(size_of::<RGBAu8Color>(), 4);
+
+        let pixels = self.as_mut_slice();
+
+        unsafe { slice::from_raw_parts_mut(pixels.as_mut_ptr() as *mut u8, pixels.len() * 4) }
+    }
+}
+
+/// Writes every pixel of `buffer` into `out` as 8-bit channels in the given byte order,
+/// with each row starting `stride` bytes after the previous one, and rows stored in the given order.
+///
+/// A `stride` of `None` packs rows tightly. Padding bytes between rows are left untouched.
+pub fn copy_to_bytes<P>(buffer: &P, out: &mut [u8], stride: Option<usize>, order: ByteOrder, rows: RowOrder) where P: PixelRead, P::Color: ToChannels {
+    let dimensions = buffer.dimensions();
+    let (width, height) = (dimensions.width as usize, dimensions.height as usize);
+
+    let stride = stride.unwrap_or(width * 4);
+
+    assert!(stride >= width * 4, "Stride must be at least four bytes per pixel");
+
+    if height > 0 {
+        assert!(out.len() >= stride * (height - 1) + width * 4, "Output is too small for the buffer");
+    }
+
+    let offsets = order.offsets();
+
+    for y in 0..height {
+        let row = &mut out[y * stride..y * stride + width * 4];
+
+        let source = rows.source_row(y as u32, height as u32);
+
+        for x in 0..width {
+            let channels = unsafe { buffer.get_pixel_unchecked(buffer.index_of(Coordinate::new(x as u32, source))) }.to_channels();
+
+            for c in 0..4 {
+                row[x * 4 + offsets[c]] = (channels[c].max(0.0).min(1.0) * 255.0).round() as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::RGBAu8Color;
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_copy_flipped() {
+        let top = RGBAu8Color::new(255, 0, 0, 255);
+        let bottom = RGBAu8Color::new(0, 0, 255, 255);
+
+        let buffer = ColorBuffer::from_vec(Dimensions::new(1, 2), vec![top, bottom]);
+
+        let mut out = [0u8; 8];
+
+        copy_to_bytes(&buffer, &mut out, None, ByteOrder::Bgra, RowOrder::BottomUp);
+
+        assert_eq!(out, [255, 0, 0, 255, 0, 0, 255, 255]);
+    }
+}
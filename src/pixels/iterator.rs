@@ -1,5 +1,7 @@
 //! Iterator structures for pixelbuffers
 
+use ::geometry::Coordinate;
+
 use super::{PixelRead, PixelRef};
 
 /// `PixelBuffer` iterator structure
@@ -7,6 +9,7 @@ pub struct PixelBufferIter<'a, P: 'a> where P: PixelRead {
     pub ( in ::pixels) buffer: &'a P,
     pub ( in ::pixels) position: usize,
     pub ( in ::pixels) max_len: usize,
+    pub ( in ::pixels) width: usize,
 }
 
 impl<'a, P: 'a> Clone for PixelBufferIter<'a, P> where P: PixelRead {
@@ -17,10 +20,18 @@ impl<'a, P: 'a> Clone for PixelBufferIter<'a, P> where P: PixelRead {
 
 impl<'a, P: 'a> Copy for PixelBufferIter<'a, P> where P: PixelRead {}
 
+impl<'a, P: 'a> PixelBufferIter<'a, P> where P: PixelRead {
+    /// Converts the position into an index of the buffer, skipping any padding at the end of rows
+    #[inline]
+    fn index(&self) -> usize {
+        self.buffer.index_of(Coordinate::new((self.position % self.width) as u32, (self.position / self.width) as u32))
+    }
+}
+
 impl<'a, P: 'a> DoubleEndedIterator for PixelBufferIter<'a, P> where P: PixelRead {
     fn next_back(&mut self) -> Option<PixelRef<'a, P>> {
         if self.position == 0 { None } else {
-            let res = PixelRef(self.position, self.buffer);
+            let res = PixelRef(self.index(), self.buffer);
             self.position -= 1;
             Some(res)
         }
@@ -32,7 +43,7 @@ impl<'a, P: 'a> Iterator for PixelBufferIter<'a, P> where P: PixelRead {
 
     fn next(&mut self) -> Option<PixelRef<'a, P>> {
         if self.position >= self.max_len { None } else {
-            let res = PixelRef(self.position, self.buffer);
+            let res = PixelRef(self.index(), self.buffer);
             self.position += 1;
             Some(res)
         }
@@ -6,8 +6,14 @@ use ::geometry::{Coordinate, HasDimensions};
 pub mod accessor;
 pub mod iterator;
 pub mod partial;
+pub mod buffer;
+pub mod bytes;
+pub mod pool;
 
 pub use self::iterator::PixelBufferIter;
+pub use self::buffer::ColorBuffer;
+pub use self::bytes::{ByteOrder, RowOrder, copy_to_bytes};
+pub use self::pool::BufferPool;
 
 pub use self::partial::{PartialPixelBuffer, PartialPixelBufferRef, PartialPixelBufferMut};
 
@@ -57,7 +63,7 @@ pub trait PixelRead: PixelBuffer {
         let dim = self.dimensions();
 
         if dim.in_bounds(coord) {
-            Ok(PixelRef::new(coord.into_index(dim), self))
+            Ok(PixelRef::new(self.index_of(coord), self))
         } else {
             throw!(RenderError::InvalidPixelCoordinate);
         }
@@ -71,7 +77,8 @@ pub trait PixelRead: PixelBuffer {
         PixelBufferIter {
             buffer: self,
             position: 0,
-            max_len: self.dimensions().area()
+            max_len: self.dimensions().area(),
+            width: self.dimensions().width as usize,
         }
     }
 }
@@ -82,8 +89,14 @@ pub trait PixelWrite: PixelRead {
     ///
     /// This is meant for internal use, do not attempt to use it directly. Please use
     /// `pixel_mut` to access pixel values safely.
+    ///
+    /// `prepare_write` must have been called since the buffer was last shared, such as with a snapshot.
     unsafe fn set_pixel_unchecked(&mut self, index: usize, color: Self::Color);
 
+    /// Called before writing to the buffer with the unchecked setters, possibly from several threads at once,
+    /// so it can do anything that is not thread-safe up front, like copying storage still shared with a snapshot.
+    fn prepare_write(&mut self) {}
+
     /// Get a mutable "reference" to the pixel at the given coordinate.
     ///
     /// Throws `RenderError::InvalidPixelCoordinate` on invalid pixel coordinates.
@@ -91,7 +104,7 @@ pub trait PixelWrite: PixelRead {
         let dim = self.dimensions();
 
         if dim.in_bounds(coord) {
-            Ok(PixelMut::new(coord.into_index(dim), self))
+            Ok(PixelMut::new(self.index_of(coord), self))
         } else {
             throw!(RenderError::InvalidPixelCoordinate);
         }
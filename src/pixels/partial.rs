@@ -64,22 +64,36 @@ impl<'a, P: 'a> PartialPixelBuffer for PartialPixelBufferMut<'a, P> where P: Pix
     fn parent(&self) -> &Self::PixelBuffer { self.parent }
 }
 
-impl<'a, P: 'a> HasDimensions for PartialPixelBufferRef<'a, P> {
+impl<'a, P: 'a> HasDimensions for PartialPixelBufferRef<'a, P> where P: HasDimensions {
     fn dimensions(&self) -> Dimensions {
         Dimensions {
             width: self.end.x - self.start.x,
             height: self.end.y - self.start.y
         }
     }
+
+    /// Pixels are indexed in the parent buffer
+    #[inline]
+    fn stride(&self) -> usize { self.parent.stride() }
+
+    #[inline]
+    fn index_of(&self, coord: Coordinate) -> usize { self.parent.index_of(coord + self.start) }
 }
 
-impl<'a, P: 'a> HasDimensions for PartialPixelBufferMut<'a, P> {
+impl<'a, P: 'a> HasDimensions for PartialPixelBufferMut<'a, P> where P: HasDimensions {
     fn dimensions(&self) -> Dimensions {
         Dimensions {
             width: self.end.x - self.start.x,
             height: self.end.y - self.start.y
         }
     }
+
+    /// Pixels are indexed in the parent buffer
+    #[inline]
+    fn stride(&self) -> usize { self.parent.stride() }
+
+    #[inline]
+    fn index_of(&self, coord: Coordinate) -> usize { self.parent.index_of(coord + self.start) }
 }
 
 impl<'a, P: 'a> PixelBuffer for PartialPixelBufferRef<'a, P> where P: PixelBuffer {
@@ -120,6 +134,11 @@ impl<'a, P: 'a> PixelWrite for PartialPixelBufferMut<'a, P> where P: PixelWrite
         self.parent.set_pixel_unchecked(index, color);
     }
 
+    #[inline]
+    fn prepare_write(&mut self) {
+        self.parent.prepare_write();
+    }
+
     fn pixel_mut<'b>(&'b mut self, coord: Coordinate) -> RenderResult<PixelMut<'b, Self>> {
         let PixelMut(index, _) = self.parent.pixel_mut(coord + self.start)?;
         Ok(PixelMut(index, self))
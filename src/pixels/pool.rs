@@ -0,0 +1,217 @@
+//! Recycling of temporary pixel buffers
+//!
+//! Multi-pass effects need large intermediate buffers every frame, like blur ping-pong targets or downsampled bloom levels,
+//! and allocating and filling them from scratch each time can cost more than the passes themselves.
+//! A `BufferPool` keeps released buffers around, keyed by their color format and dimensions,
+//! and hands them out again the next time a buffer of the same kind is needed:
+//!
+//! ```ignore
+//! let pool = pipeline.arena_mut().buffers_mut();
+//!
+//! let mut scratch = pool.acquire::<RGBAf32Color>(dimensions);
+//! // ... use scratch as a temporary target ...
+//! pool.release(scratch);
+//! ```
+//!
+//! Buffers are only freed by `purge` or `purge_unused`, so call one of them after resizing or between scenes.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::mem::size_of;
+
+use ::color::Color;
+use ::geometry::{Dimensions, HasDimensions};
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::ColorBuffer;
+
+/// Released buffer waiting to be reused
+struct Entry {
+    buffer: Box<Any + Send>,
+    bytes: usize,
+    /// Frame the buffer was released in
+    released: u64,
+}
+
+/// Pool of idle pixel buffers, reused by color format and dimensions
+#[derive(Default)]
+pub struct BufferPool {
+    frame: u64,
+    free: HashMap<(TypeId, Dimensions), Vec<Entry>>,
+    retained_bytes: usize,
+}
+
+impl Debug for BufferPool {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        f.debug_struct("BufferPool")
+         .field("frame", &self.frame)
+         .field("idle", &self.idle_count())
+         .field("retained_bytes", &self.retained_bytes)
+         .finish()
+    }
+}
+
+/// Cloning a pool gives an empty pool, since idle buffers are only a cache
+impl Clone for BufferPool {
+    fn clone(&self) -> BufferPool {
+        BufferPool { frame: self.frame, ..BufferPool::default() }
+    }
+}
+
+impl MemoryUsage for BufferPool {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add("idle", self.retained_bytes);
+
+        report
+    }
+}
+
+impl BufferPool {
+    pub fn new() -> BufferPool { BufferPool::default() }
+
+    /// Takes a buffer with the given dimensions from the pool, or allocates a new one filled with `Color::empty()`.
+    ///
+    /// Recycled buffers still hold whatever was last drawn into them.
+    pub fn acquire<C>(&mut self, dimensions: Dimensions) -> ColorBuffer<C> where C: Color {
+        self.take(dimensions).unwrap_or_else(|| ColorBuffer::new(dimensions))
+    }
+
+    /// Takes a buffer with the given dimensions from the pool, or allocates a new one, with every pixel set to `color`
+    pub fn acquire_filled<C>(&mut self, dimensions: Dimensions, color: C) -> ColorBuffer<C> where C: Color {
+        match self.take(dimensions) {
+            Some(mut buffer) => {
+                for pixel in buffer.as_mut_slice() {
+                    *pixel = color;
+                }
+
+                buffer
+            }
+            None => ColorBuffer::filled(dimensions, color),
+        }
+    }
+
+    fn take<C>(&mut self, dimensions: Dimensions) -> Option<ColorBuffer<C>> where C: Color {
+        let entry = self.free.get_mut(&(TypeId::of::<C>(), dimensions))?.pop()?;
+
+        self.retained_bytes -= entry.bytes;
+
+        entry.buffer.downcast::<ColorBuffer<C>>().ok().map(|buffer| *buffer)
+    }
+
+    /// Returns a buffer to the pool for a later `acquire` of the same color format and dimensions
+    pub fn release<C>(&mut self, buffer: ColorBuffer<C>) where C: Color {
+        let dimensions = buffer.dimensions();
+
+        if dimensions.area() == 0 {
+            return;
+        }
+
+        let bytes = buffer.as_slice().len() * size_of::<C>();
+
+        self.retained_bytes += bytes;
+
+        self.free.entry((TypeId::of::<C>(), dimensions)).or_insert_with(Vec::new).push(Entry {
+            buffer: Box::new(buffer),
+            bytes,
+            released: self.frame,
+        });
+    }
+
+    /// Advances the frame counter used by `purge_unused`, called by `FrameArena::reset`
+    pub fn next_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Frees every idle buffer
+    pub fn purge(&mut self) {
+        self.free.clear();
+        self.retained_bytes = 0;
+    }
+
+    /// Frees idle buffers that have not been used for more than `frames` frames,
+    /// such as those left over from before the framebuffer was resized
+    pub fn purge_unused(&mut self, frames: u64) {
+        let oldest = self.frame.saturating_sub(frames);
+
+        let mut freed = 0;
+
+        for entries in self.free.values_mut() {
+            entries.retain(|entry| {
+                let keep = entry.released >= oldest;
+
+                if !keep {
+                    freed += entry.bytes;
+                }
+
+                keep
+            });
+        }
+
+        self.free.retain(|_, entries| !entries.is_empty());
+
+        self.retained_bytes -= freed;
+    }
+
+    /// Number of idle buffers in the pool
+    pub fn idle_count(&self) -> usize {
+        self.free.values().map(|entries| entries.len()).sum()
+    }
+
+    /// Bytes of pixels held by idle buffers
+    #[inline]
+    pub fn retained_bytes(&self) -> usize { self.retained_bytes }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::{RGBAf32Color, Rf32Color};
+
+    use super::*;
+
+    #[test]
+    fn test_buffer_reuse() {
+        let mut pool = BufferPool::new();
+
+        let dimensions = Dimensions::new(16, 8);
+
+        let buffer = pool.acquire_filled(dimensions, RGBAf32Color::new(1.0, 0.0, 0.0, 1.0));
+        let pixels = buffer.as_slice().as_ptr();
+
+        pool.release(buffer);
+
+        assert_eq!(pool.idle_count(), 1);
+        assert_eq!(pool.retained_bytes(), 16 * 8 * size_of::<RGBAf32Color>());
+
+        // Other formats and sizes don't share buffers
+        let other: ColorBuffer<Rf32Color> = pool.acquire(dimensions);
+        let smaller: ColorBuffer<RGBAf32Color> = pool.acquire(Dimensions::new(8, 8));
+
+        assert_eq!(pool.idle_count(), 1);
+
+        let reused: ColorBuffer<RGBAf32Color> = pool.acquire(dimensions);
+
+        assert_eq!(reused.as_slice().as_ptr(), pixels);
+        assert_eq!(reused.as_slice()[0], RGBAf32Color::new(1.0, 0.0, 0.0, 1.0));
+        assert_eq!(pool.retained_bytes(), 0);
+
+        pool.release(reused);
+        pool.release(other);
+
+        pool.next_frame();
+        pool.next_frame();
+        pool.release(smaller);
+
+        pool.purge_unused(1);
+
+        assert_eq!(pool.idle_count(), 1);
+        assert_eq!(pool.retained_bytes(), 8 * 8 * size_of::<RGBAf32Color>());
+
+        pool.purge();
+
+        assert_eq!(pool.idle_count(), 0);
+        assert_eq!(pool.retained_bytes(), 0);
+    }
+}
@@ -0,0 +1,193 @@
+//! Bloom
+//!
+//! Bright parts of the image are extracted with a soft threshold, then blurred at several scales
+//! and added back on top of the original image, approximating light scattering in a lens.
+
+use nalgebra::Vector4;
+
+use ::color::predefined::formats::RGBAf32Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite, ColorBuffer};
+use ::texture::{Filter, sample_channels};
+
+use super::blur::GaussianBlur;
+
+/// Bloom settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct BloomSettings {
+    /// Brightness above which pixels start to bloom
+    pub threshold: f32,
+    /// Width of the soft transition around the threshold, relative to the threshold
+    pub knee: f32,
+    /// Strength of the bloom added back to the image
+    pub intensity: f32,
+    /// Number of half-resolution levels the bloom is blurred at
+    pub levels: u32,
+    /// Standard deviation in pixels of the blur at each level
+    pub sigma: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> BloomSettings {
+        BloomSettings {
+            threshold: 1.0,
+            knee: 0.5,
+            intensity: 0.25,
+            levels: 4,
+            sigma: 2.0,
+        }
+    }
+}
+
+/// Multi-scale bloom pass.
+///
+/// All intermediate buffers are kept between frames and only reallocated when the image size changes.
+#[derive(Debug, Clone)]
+pub struct Bloom {
+    settings: BloomSettings,
+    blur: GaussianBlur,
+    levels: Vec<ColorBuffer<RGBAf32Color>>,
+    blurred: ColorBuffer<RGBAf32Color>,
+}
+
+/// Soft threshold, returning the fraction of the color that blooms
+fn bloom_factor(brightness: f32, threshold: f32, knee: f32) -> f32 {
+    let soft = threshold * knee;
+
+    let curve = if soft > 0.0 {
+        let x = (brightness - threshold + soft).max(0.0).min(2.0 * soft);
+        x * x / (4.0 * soft)
+    } else {
+        0.0
+    };
+
+    curve.max(brightness - threshold) / brightness.max(1e-5)
+}
+
+impl Bloom {
+    pub fn new(settings: BloomSettings) -> Bloom {
+        Bloom {
+            settings,
+            blur: GaussianBlur::new(settings.sigma),
+            levels: Vec::new(),
+            blurred: ColorBuffer::new(Dimensions::new(0, 0)),
+        }
+    }
+
+    #[inline]
+    pub fn settings(&self) -> &BloomSettings { &self.settings }
+
+    fn allocate(&mut self, dimensions: Dimensions) {
+        let valid = self.levels.len() == self.settings.levels as usize &&
+            self.levels.first().map_or(false, |level| level.dimensions() == half(dimensions));
+
+        if !valid {
+            let mut size = dimensions;
+
+            self.levels = (0..self.settings.levels).map(|_| {
+                size = half(size);
+                ColorBuffer::new(size)
+            }).collect();
+        }
+    }
+
+    /// Adds bloom to the image in place
+    pub fn apply<P>(&mut self, image: &mut P) where P: PixelWrite<Color = RGBAf32Color> {
+        let dimensions = image.dimensions();
+
+        if self.settings.levels == 0 || dimensions.width < 2 || dimensions.height < 2 {
+            return;
+        }
+
+        self.allocate(dimensions);
+
+        let BloomSettings { threshold, knee, intensity, .. } = self.settings;
+
+        // Extract bright pixels while downsampling to the first level
+        {
+            let first = &mut self.levels[0];
+            let size = first.dimensions();
+
+            for y in 0..size.height {
+                for x in 0..size.width {
+                    let c = sample_channels(image, x as f32 * 2.0 + 1.0, y as f32 * 2.0 + 1.0, Filter::Bilinear);
+                    let color = Vector4::new(c[0], c[1], c[2], 0.0);
+
+                    let brightness = c[0].max(c[1]).max(c[2]);
+
+                    let index = first.index_of(Coordinate::new(x, y));
+
+                    unsafe { first.set_pixel_unchecked(index, color * bloom_factor(brightness, threshold, knee)); }
+                }
+            }
+        }
+
+        // Successively downsample into smaller levels
+        for i in 1..self.levels.len() {
+            let (larger, smaller) = self.levels.split_at_mut(i);
+            downsample(&larger[i - 1], &mut smaller[0]);
+        }
+
+        // Blur each level, then accumulate upwards from the smallest
+        for i in (0..self.levels.len()).rev() {
+            self.blur.blur(&self.levels[i], &mut self.blurred);
+
+            if i + 1 < self.levels.len() {
+                let (larger, smaller) = self.levels.split_at_mut(i + 1);
+
+                upsample_add(&smaller[0], &mut self.blurred, 1.0);
+                ::std::mem::swap(&mut larger[i], &mut self.blurred);
+            } else {
+                ::std::mem::swap(&mut self.levels[i], &mut self.blurred);
+            }
+        }
+
+        let bloom = &self.levels[0];
+        let scale = 1.0 / self.levels.len() as f32;
+
+        upsample_add(bloom, image, intensity * scale);
+    }
+}
+
+fn half(dimensions: Dimensions) -> Dimensions {
+    Dimensions::new((dimensions.width / 2).max(1), (dimensions.height / 2).max(1))
+}
+
+fn downsample<P>(input: &P, out: &mut ColorBuffer<RGBAf32Color>) where P: PixelRead<Color = RGBAf32Color> {
+    let size = out.dimensions();
+
+    for y in 0..size.height {
+        for x in 0..size.width {
+            let c = sample_channels(input, x as f32 * 2.0 + 1.0, y as f32 * 2.0 + 1.0, Filter::Bilinear);
+
+            let index = out.index_of(Coordinate::new(x, y));
+
+            unsafe { out.set_pixel_unchecked(index, Vector4::new(c[0], c[1], c[2], c[3])); }
+        }
+    }
+}
+
+/// Bilinearly upsamples `input` to the size of `out`, adding it to the existing pixels scaled by `scale`
+fn upsample_add<P, O>(input: &P, out: &mut O, scale: f32) where P: PixelRead<Color = RGBAf32Color>, O: PixelWrite<Color = RGBAf32Color> {
+    let from = input.dimensions();
+    let to = out.dimensions();
+
+    let sx = from.width as f32 / to.width as f32;
+    let sy = from.height as f32 / to.height as f32;
+
+    out.prepare_write();
+
+    for y in 0..to.height {
+        for x in 0..to.width {
+            let c = sample_channels(input, (x as f32 + 0.5) * sx, (y as f32 + 0.5) * sy, Filter::Bilinear);
+
+            let index = out.index_of(Coordinate::new(x, y));
+
+            unsafe {
+                let existing = out.get_pixel_unchecked(index);
+                out.set_pixel_unchecked(index, existing + Vector4::new(c[0], c[1], c[2], 0.0) * scale);
+            }
+        }
+    }
+}
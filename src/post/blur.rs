@@ -0,0 +1,145 @@
+//! Separable Gaussian blur
+
+use nalgebra::Vector4;
+
+use ::color::predefined::formats::RGBAf32Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, ColorBuffer};
+
+/// Returns the normalized weights of a Gaussian kernel from the center outwards.
+///
+/// The kernel radius is three standard deviations, which covers over 99% of the distribution.
+pub fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+
+    let radius = (sigma * 3.0).ceil() as usize;
+
+    let mut weights: Vec<f32> = (0..radius + 1).map(|i| {
+        let x = i as f32;
+        (-(x * x) / (2.0 * sigma * sigma)).exp()
+    }).collect();
+
+    // Every weight except the center is used twice
+    let total = weights.iter().skip(1).fold(weights[0], |sum, w| sum + w * 2.0);
+
+    for w in &mut weights {
+        *w /= total;
+    }
+
+    weights
+}
+
+/// Gaussian blur with an internally managed intermediate buffer.
+///
+/// The blur is separated into a horizontal and a vertical pass. The intermediate buffer
+/// is kept between uses, so blurring many frames of the same size doesn't allocate.
+#[derive(Debug, Clone)]
+pub struct GaussianBlur {
+    sigma: f32,
+    kernel: Vec<f32>,
+    scratch: ColorBuffer<RGBAf32Color>,
+}
+
+impl GaussianBlur {
+    /// Create a new blur with the given standard deviation in pixels
+    pub fn new(sigma: f32) -> GaussianBlur {
+        GaussianBlur {
+            sigma,
+            kernel: gaussian_kernel(sigma),
+            scratch: ColorBuffer::new(Dimensions::new(0, 0)),
+        }
+    }
+
+    #[inline]
+    pub fn sigma(&self) -> f32 { self.sigma }
+
+    /// Blurs `input` into `out`, which are allowed to be different sizes only if `out` is resized to match.
+    pub fn blur<P>(&mut self, input: &P, out: &mut ColorBuffer<RGBAf32Color>) where P: PixelRead<Color = RGBAf32Color> {
+        let dimensions = input.dimensions();
+
+        if self.scratch.dimensions() != dimensions {
+            self.scratch = ColorBuffer::new(dimensions);
+        }
+
+        if out.dimensions() != dimensions {
+            *out = ColorBuffer::new(dimensions);
+        }
+
+        convolve(input, self.scratch.as_mut_slice(), &self.kernel, true);
+        convolve(&self.scratch, out.as_mut_slice(), &self.kernel, false);
+    }
+
+    /// Blurs the buffer in place
+    pub fn blur_in_place(&mut self, buffer: &mut ColorBuffer<RGBAf32Color>) {
+        let dimensions = buffer.dimensions();
+
+        if self.scratch.dimensions() != dimensions {
+            self.scratch = ColorBuffer::new(dimensions);
+        }
+
+        convolve(buffer, self.scratch.as_mut_slice(), &self.kernel, true);
+        convolve(&self.scratch, buffer.as_mut_slice(), &self.kernel, false);
+    }
+}
+
+/// Convolves one axis of `input` with a symmetric kernel, clamping samples to the edge
+fn convolve<P>(input: &P, out: &mut [RGBAf32Color], kernel: &[f32], horizontal: bool) where P: PixelRead<Color = RGBAf32Color> {
+    let dimensions = input.dimensions();
+
+    let fetch = |x: i64, y: i64| {
+        let x = x.max(0).min(dimensions.width as i64 - 1) as u32;
+        let y = y.max(0).min(dimensions.height as i64 - 1) as u32;
+
+        unsafe { input.get_pixel_unchecked(input.index_of(Coordinate::new(x, y))) }
+    };
+
+    for y in 0..dimensions.height as i64 {
+        for x in 0..dimensions.width as i64 {
+            let mut sum: Vector4<f32> = fetch(x, y) * kernel[0];
+
+            for (i, &w) in kernel.iter().enumerate().skip(1) {
+                let i = i as i64;
+
+                let (a, b) = if horizontal {
+                    (fetch(x - i, y), fetch(x + i, y))
+                } else {
+                    (fetch(x, y - i), fetch(x, y + i))
+                };
+
+                sum += (a + b) * w;
+            }
+
+            out[(y as usize) * dimensions.width as usize + x as usize] = sum;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kernel_normalized() {
+        for &sigma in &[0.5, 1.0, 2.5, 6.0] {
+            let kernel = gaussian_kernel(sigma);
+            let total = kernel.iter().skip(1).fold(kernel[0], |sum, w| sum + w * 2.0);
+
+            assert!((total - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn test_blur_preserves_constant() {
+        let dimensions = Dimensions::new(16, 9);
+        let mut buffer = ColorBuffer::filled(dimensions, Vector4::new(0.25, 0.5, 0.75, 1.0));
+
+        GaussianBlur::new(2.0).blur_in_place(&mut buffer);
+
+        for pixel in buffer.as_slice() {
+            assert!((pixel.x - 0.25).abs() < 1e-5);
+            assert!((pixel.w - 1.0).abs() < 1e-5);
+        }
+    }
+}
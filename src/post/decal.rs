@@ -0,0 +1,235 @@
+//! Projected decals
+//!
+//! Decals put textures like bullet holes, footprints or graffiti onto already rendered geometry,
+//! without changing the geometry itself. Each decal is a box in world space, projecting its texture along the
+//! local z-axis of the box onto whatever surfaces lie inside it.
+//!
+//! The pass reconstructs the world-space position of every pixel covered by the box from the depth attachment,
+//! using the viewport and the inverse view-projection matrix the scene was rendered with,
+//! then blends the decal texture over the color attachment with its alpha.
+
+use num_traits::{NumCast, cast};
+
+use nalgebra::{Point3, Vector2, Vector3, Vector4, Matrix4, Isometry3};
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Coordinate, Viewport};
+use ::pixels::PixelRead;
+use ::framebuffer::UnsafeFramebuffer;
+use ::framebuffer::types::DepthAttachment;
+use ::texture::{Filter, sample_channels};
+
+/// A box projecting a texture onto the scene
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decal {
+    /// Transforms world-space positions into the decal box, which spans `[-0.5, 0.5]` on every axis
+    pub world_to_decal: Matrix4<f32>,
+    /// Opacity multiplied with the alpha of the texture
+    pub opacity: f32,
+    /// Fraction of the box depth over which the decal fades out towards the front and back of the box,
+    /// hiding the hard cutoff on surfaces that cross them
+    pub fade: f32,
+}
+
+impl Decal {
+    /// Creates a decal from the transform of the unit box into world space, or `None` if it can't be inverted
+    pub fn new(box_to_world: &Matrix4<f32>) -> Option<Decal> {
+        box_to_world.try_inverse().map(|world_to_decal| Decal {
+            world_to_decal,
+            opacity: 1.0,
+            fade: 0.1,
+        })
+    }
+
+    /// Creates a decal centered on the origin of `transform`, projecting along its local z-axis,
+    /// with the texture covering `size.x` by `size.y` and reaching `size.z / 2` in front of and behind the center.
+    pub fn from_box(transform: Isometry3<f32>, size: Vector3<f32>) -> Decal {
+        let scale = Matrix4::new_nonuniform_scaling(&Vector3::new(1.0 / size.x, 1.0 / size.y, 1.0 / size.z));
+
+        Decal {
+            world_to_decal: scale * transform.inverse().to_homogeneous(),
+            opacity: 1.0,
+            fade: 0.1,
+        }
+    }
+
+    /// Returns the texture coordinates and weight of the decal at a world-space position,
+    /// or `None` if the position is outside the box.
+    ///
+    /// Texture coordinates go from `(0, 0)` at the top-left of the decal to `(1, 1)` at the bottom-right.
+    pub fn project(&self, position: Point3<f32>) -> Option<(Vector2<f32>, f32)> {
+        let p = self.world_to_decal * position.to_homogeneous();
+
+        if p.x.abs() > 0.5 || p.y.abs() > 0.5 || p.z.abs() > 0.5 {
+            return None;
+        }
+
+        let edge = 0.5 - p.z.abs();
+
+        let fade = if self.fade > 0.0 { (edge / self.fade).min(1.0) } else { 1.0 };
+
+        Some((Vector2::new(p.x + 0.5, 0.5 - p.y), self.opacity * fade))
+    }
+
+    /// Pixel bounds of the box on screen, as inclusive minimum and exclusive maximum coordinates,
+    /// or `None` if the box crosses the camera plane and could cover the whole screen.
+    fn screen_bounds(&self, view_projection: &Matrix4<f32>, viewport: &Viewport<f32>) -> Option<((f32, f32), (f32, f32))> {
+        let box_to_clip = match self.world_to_decal.try_inverse() {
+            Some(box_to_world) => view_projection * box_to_world,
+            None => return None,
+        };
+
+        let mut min = (::std::f32::INFINITY, ::std::f32::INFINITY);
+        let mut max = (::std::f32::NEG_INFINITY, ::std::f32::NEG_INFINITY);
+
+        for i in 0..8 {
+            let corner = Vector4::new(if i & 1 == 0 { -0.5 } else { 0.5 },
+                                      if i & 2 == 0 { -0.5 } else { 0.5 },
+                                      if i & 4 == 0 { -0.5 } else { 0.5 }, 1.0);
+
+            let clip = box_to_clip * corner;
+
+            if clip.w <= 0.0 {
+                return None;
+            }
+
+            let x = viewport.x + (clip.x / clip.w + 1.0) * 0.5 * viewport.width;
+            let y = viewport.y + (1.0 - clip.y / clip.w) * 0.5 * viewport.height;
+
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+
+        Some((min, max))
+    }
+}
+
+/// Reconstructs the world-space position at a pixel from its raw depth,
+/// or `None` if nothing was drawn there.
+pub fn reconstruct_position(x: f32, y: f32, depth: f32, viewport: &Viewport<f32>, inverse_view_projection: &Matrix4<f32>) -> Option<Point3<f32>> {
+    let ndc = viewport.screen_to_ndc(x, y, depth);
+
+    // Cleared depth lies far beyond the far plane
+    if ndc.z.is_nan() || ndc.z.abs() > 1.0 {
+        return None;
+    }
+
+    Point3::from_homogeneous(inverse_view_projection * ndc.to_homogeneous())
+}
+
+/// Blends a decal texture over the color attachment of a framebuffer, wherever the depth attachment
+/// places geometry inside the decal box.
+///
+/// `view_projection` and `viewport` must be the ones the scene was rendered with.
+/// The alpha of the framebuffer is left unchanged.
+pub fn apply_decal<F, T>(framebuffer: &mut F, decal: &Decal, texture: &T, filter: Filter,
+                         view_projection: &Matrix4<f32>, viewport: &Viewport<f32>)
+    where F: UnsafeFramebuffer, F::Color: ToChannels + FromChannels, DepthAttachment<F>: NumCast,
+          T: PixelRead, T::Color: ToChannels {
+    let inverse_view_projection = match view_projection.try_inverse() {
+        Some(inverse) => inverse,
+        None => return,
+    };
+
+    let dimensions = framebuffer.dimensions();
+    let texture_dimensions = texture.dimensions();
+
+    let (width, height) = (dimensions.width as f32, dimensions.height as f32);
+
+    // Only visit pixels the box can cover
+    let ((x0, y0), (x1, y1)) = decal.screen_bounds(view_projection, viewport)
+        .unwrap_or(((0.0, 0.0), (width, height)));
+
+    let (x0, y0) = (x0.max(0.0).floor() as u32, y0.max(0.0).floor() as u32);
+    let (x1, y1) = (x1.min(width).ceil() as u32, y1.min(height).ceil() as u32);
+
+    framebuffer.prepare_write();
+
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let index = framebuffer.index_of(Coordinate::new(x, y));
+
+            let depth: f32 = match cast(unsafe { framebuffer.get_depth_unchecked(index) }) {
+                Some(depth) => depth,
+                None => continue,
+            };
+
+            let position = match reconstruct_position(x as f32 + 0.5, y as f32 + 0.5, depth, viewport, &inverse_view_projection) {
+                Some(position) => position,
+                None => continue,
+            };
+
+            if let Some((uv, weight)) = decal.project(position) {
+                let sample = sample_channels(texture,
+                                             uv.x * texture_dimensions.width as f32,
+                                             uv.y * texture_dimensions.height as f32,
+                                             filter);
+
+                let alpha = sample[3] * weight;
+
+                if alpha <= 0.0 {
+                    continue;
+                }
+
+                let mut color = unsafe { framebuffer.get_pixel_unchecked(index).to_channels() };
+
+                for i in 0..3 {
+                    color[i] += (sample[i] - color[i]) * alpha;
+                }
+
+                unsafe { framebuffer.set_pixel_unchecked(index, F::Color::from_channels(color)); }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::{Perspective3, Translation3, UnitQuaternion};
+
+    use ::geometry::Dimensions;
+
+    use super::*;
+
+    #[test]
+    fn test_reconstruct_position() {
+        let viewport = Viewport::new(Dimensions::new(64, 32), Coordinate::new(0, 0), 0.0, 1.0);
+
+        let view_projection = Perspective3::new(2.0, 1.0, 0.1, 100.0).to_homogeneous();
+
+        let world = Point3::new(0.5, -0.25, -4.0);
+
+        // Project the same way the vertex shader and viewport transform do
+        let clip = view_projection * world.to_homogeneous();
+        let ndc = Vector3::new(clip.x, clip.y, clip.z) / clip.w;
+
+        let x = (ndc.x + 1.0) * 0.5 * 64.0;
+        let y = (1.0 - ndc.y) * 0.5 * 32.0;
+        let z = -0.5 * ndc.z - 0.5;
+
+        let reconstructed = reconstruct_position(x, y, z, &viewport, &view_projection.try_inverse().unwrap()).unwrap();
+
+        assert!((reconstructed - world).norm() < 1e-3);
+
+        assert!(reconstruct_position(x, y, ::std::f32::MIN, &viewport, &view_projection.try_inverse().unwrap()).is_none());
+    }
+
+    #[test]
+    fn test_decal_project() {
+        let decal = Decal::from_box(Isometry3::from_parts(Translation3::new(0.0, 0.0, -5.0), UnitQuaternion::identity()),
+                                    Vector3::new(2.0, 2.0, 1.0));
+
+        let (uv, weight) = decal.project(Point3::new(-0.5, 0.5, -5.0)).unwrap();
+
+        assert!((uv - Vector2::new(0.25, 0.25)).norm() < 1e-6);
+        assert_eq!(weight, 1.0);
+
+        assert!(decal.project(Point3::new(0.0, 0.0, -4.0)).is_none());
+        assert!(decal.project(Point3::new(1.5, 0.0, -5.0)).is_none());
+
+        // Fading out towards the back of the box
+        let (_, weight) = decal.project(Point3::new(0.0, 0.0, -5.48)).unwrap();
+
+        assert!(weight < 1.0);
+    }
+}
@@ -0,0 +1,284 @@
+//! Depth of field
+//!
+//! Blurs the image based on distance from a focal plane, using the depth attachment of a framebuffer.
+//!
+//! The pass first computes a circle of confusion radius for every pixel from its depth,
+//! then gathers samples on a disk around each pixel, where a sample only contributes if its own
+//! circle of confusion reaches the pixel. This keeps in-focus objects from bleeding into their blurred surroundings.
+
+use num_traits::{NumCast, cast};
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite};
+use ::framebuffer::UnsafeFramebuffer;
+use ::framebuffer::types::DepthAttachment;
+use ::error::{RenderResult, RenderError};
+
+/// Lens settings for the depth of field pass
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct DepthOfField {
+    /// Distance from the camera that is perfectly in focus
+    pub focal_distance: f32,
+    /// Circle of confusion radius in pixels for objects infinitely far away.
+    ///
+    /// Larger apertures give a shallower depth of field.
+    pub aperture: f32,
+    /// Largest circle of confusion radius in pixels, which also limits the cost of the pass
+    pub max_radius: f32,
+    /// Number of sample rings in the gather disk. Each ring `i` has `6 * i` samples.
+    pub rings: u32,
+}
+
+impl Default for DepthOfField {
+    fn default() -> DepthOfField {
+        DepthOfField {
+            focal_distance: 10.0,
+            aperture: 8.0,
+            max_radius: 12.0,
+            rings: 4,
+        }
+    }
+}
+
+impl DepthOfField {
+    /// Circle of confusion radius in pixels for an object at the given distance from the camera
+    pub fn coc_radius(&self, distance: f32) -> f32 {
+        if distance <= 0.0 {
+            return self.max_radius;
+        }
+
+        (self.aperture * (1.0 - self.focal_distance / distance).abs()).min(self.max_radius)
+    }
+}
+
+/// Computes the circle of confusion radius of every pixel in the framebuffer.
+///
+/// `linearize` converts raw depth attachment values into distances from the camera,
+/// which depends on how depth was written by the vertex shader and viewport.
+pub fn circle_of_confusion<F, L>(framebuffer: &F, settings: &DepthOfField, linearize: L) -> Vec<f32>
+    where F: UnsafeFramebuffer, DepthAttachment<F>: NumCast, L: Fn(f32) -> f32 {
+    let dimensions = framebuffer.dimensions();
+
+    let mut coc = Vec::with_capacity(dimensions.area());
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            // Padded framebuffers index depth by their stride, while `coc` stays tightly packed
+            let index = framebuffer.index_of(Coordinate::new(x, y));
+
+            let depth: f32 = cast(unsafe { framebuffer.get_depth_unchecked(index) }).unwrap_or(::std::f32::MIN);
+
+            coc.push(settings.coc_radius(linearize(depth)));
+        }
+    }
+
+    coc
+}
+
+/// Applies a depth of field blur to `color`, given the circle of confusion radius of every pixel.
+///
+/// `color`, `out` and `coc` must all match in size.
+pub fn depth_of_field<P, O>(color: &P, coc: &[f32], out: &mut O, settings: &DepthOfField) -> RenderResult<()>
+    where P: PixelRead, P::Color: ToChannels, O: PixelWrite, O::Color: FromChannels {
+    let dimensions = color.dimensions();
+
+    if dimensions != out.dimensions() || dimensions.area() != coc.len() {
+        throw!(RenderError::DimensionMismatch);
+    }
+
+    let offsets = disk_offsets(settings.rings, settings.max_radius);
+
+    out.prepare_write();
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            let coord = Coordinate::new(x, y);
+            let index = coord.into_index(dimensions);
+
+            let radius = coc[index];
+
+            let mut sum = unsafe { color.get_pixel_unchecked(color.index_of(coord)).to_channels() };
+            let mut weight = 1.0;
+
+            // In focus, so skip the gather entirely
+            if radius >= 0.5 {
+                for &(dx, dy, distance) in &offsets {
+                    let sx = x as f32 + dx;
+                    let sy = y as f32 + dy;
+
+                    if let Some(sample) = sample_coordinate(dimensions, sx, sy) {
+                        let sample_radius = coc[sample.into_index(dimensions)];
+
+                        // Samples only contribute if their own blur reaches this pixel,
+                        // and background samples can't be spread further than this pixel is blurred.
+                        let reach = if sample_radius < radius { sample_radius } else { radius };
+
+                        let w = (reach - distance + 1.0).max(0.0).min(1.0);
+
+                        if w > 0.0 {
+                            let c = unsafe { color.get_pixel_unchecked(color.index_of(sample)).to_channels() };
+
+                            for i in 0..4 {
+                                sum[i] += c[i] * w;
+                            }
+
+                            weight += w;
+                        }
+                    }
+                }
+            }
+
+            for c in &mut sum {
+                *c /= weight;
+            }
+
+            let index = out.index_of(coord);
+
+            unsafe { out.set_pixel_unchecked(index, O::Color::from_channels(sum)); }
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies depth of field to the color attachment of a framebuffer, writing the result to `out`.
+pub fn apply_depth_of_field<F, O, L>(framebuffer: &F, out: &mut O, settings: &DepthOfField, linearize: L) -> RenderResult<()>
+    where F: UnsafeFramebuffer, F::Color: ToChannels, DepthAttachment<F>: NumCast,
+          O: PixelWrite, O::Color: FromChannels, L: Fn(f32) -> f32 {
+    let coc = circle_of_confusion(framebuffer, settings, linearize);
+
+    depth_of_field(framebuffer, &coc, out, settings)
+}
+
+fn sample_coordinate(dimensions: Dimensions, x: f32, y: f32) -> Option<Coordinate> {
+    let (x, y) = (x.round(), y.round());
+
+    if x < 0.0 || y < 0.0 || x >= dimensions.width as f32 || y >= dimensions.height as f32 {
+        None
+    } else {
+        Some(Coordinate::new(x as u32, y as u32))
+    }
+}
+
+/// Sample offsets on concentric rings, along with their distance from the center
+fn disk_offsets(rings: u32, max_radius: f32) -> Vec<(f32, f32, f32)> {
+    let mut offsets = Vec::new();
+
+    for ring in 1..(rings + 1) {
+        let distance = ring as f32 / rings as f32 * max_radius;
+        let count = ring * 6;
+
+        for i in 0..count {
+            // Offset every other ring by half a step to avoid radial streaks
+            let angle = (i as f32 + (ring % 2) as f32 * 0.5) / count as f32 * 2.0 * ::std::f32::consts::PI;
+
+            offsets.push((angle.cos() * distance, angle.sin() * distance, distance));
+        }
+    }
+
+    offsets
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use nalgebra::Vector4;
+
+    use ::color::predefined::formats::{RGBAf32Color, RGBAu8Color};
+    use ::framebuffer::BorrowedFramebuffer;
+    use ::pixels::ColorBuffer;
+    use ::pixels::bytes::ByteOrder;
+
+    #[test]
+    fn test_coc_radius() {
+        let settings = DepthOfField { focal_distance: 10.0, aperture: 8.0, max_radius: 12.0, rings: 2 };
+
+        assert_eq!(settings.coc_radius(10.0), 0.0);
+        assert_eq!(settings.coc_radius(20.0), 4.0);
+        assert_eq!(settings.coc_radius(5.0), 8.0);
+
+        // Limited to the max radius, including for anything behind the camera
+        assert_eq!(settings.coc_radius(2.5), 12.0);
+        assert_eq!(settings.coc_radius(0.0), 12.0);
+        assert_eq!(settings.coc_radius(-1.0), 12.0);
+    }
+
+    /// Padded 3x3 framebuffer, with one color and depth in the center and another everywhere else
+    fn apply(center_depth: f32) -> ColorBuffer<RGBAf32Color> {
+        let mut bytes = vec![0u8; 16 * 3];
+
+        let mut framebuffer: BorrowedFramebuffer<f32> = BorrowedFramebuffer::new(&mut bytes, Dimensions::new(3, 3), Some(16), ByteOrder::Rgba);
+
+        for y in 0..3 {
+            for x in 0..3 {
+                let index = framebuffer.index_of(Coordinate::new(x, y));
+
+                let (color, depth) = if (x, y) == (1, 1) {
+                    (RGBAu8Color::new(0, 0, 0, 255), center_depth)
+                } else {
+                    (RGBAu8Color::new(210, 140, 70, 255), 100.0)
+                };
+
+                unsafe {
+                    framebuffer.set_pixel_unchecked(index, color);
+                    framebuffer.set_depth_unchecked(index, depth);
+                }
+            }
+        }
+
+        // A single ring of six samples one pixel away, which all land on neighbors of the center
+        let settings = DepthOfField { focal_distance: 10.0, aperture: 8.0, max_radius: 1.0, rings: 1 };
+
+        let mut out = ColorBuffer::new(Dimensions::new(3, 3));
+
+        apply_depth_of_field(&framebuffer, &mut out, &settings, |depth| depth).unwrap();
+
+        out
+    }
+
+    fn assert_close(a: RGBAf32Color, b: RGBAf32Color) {
+        assert!((a - b).norm() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn test_gather() {
+        let neighbor = Vector4::new(210.0, 140.0, 70.0, 255.0) / 255.0;
+
+        // Out of focus, the center averages itself with its six samples
+        let out = apply(100.0);
+
+        assert_close(out.as_slice()[4], Vector4::new(180.0, 120.0, 60.0, 255.0) / 255.0);
+
+        // In focus, the center is unchanged and doesn't bleed into its blurred neighbors
+        let out = apply(10.0);
+
+        assert_close(out.as_slice()[4], Vector4::new(0.0, 0.0, 0.0, 1.0));
+
+        for (i, &pixel) in out.as_slice().iter().enumerate() {
+            if i != 4 {
+                assert_close(pixel, neighbor);
+            }
+        }
+    }
+
+    #[test]
+    fn test_circle_of_confusion_padded() {
+        // Rows are padded to four pixels, so strided and packed indices differ after the first row
+        let mut bytes = vec![0u8; 16 * 2];
+
+        let mut framebuffer: BorrowedFramebuffer<f32> = BorrowedFramebuffer::new(&mut bytes, Dimensions::new(2, 2), Some(16), ByteOrder::Rgba);
+
+        for &(x, y, depth) in &[(0, 0, 10.0), (1, 0, 20.0), (0, 1, 5.0), (1, 1, 40.0)] {
+            let index = framebuffer.index_of(Coordinate::new(x, y));
+
+            unsafe { framebuffer.set_depth_unchecked(index, depth); }
+        }
+
+        let settings = DepthOfField { focal_distance: 10.0, aperture: 8.0, max_radius: 12.0, rings: 2 };
+
+        assert_eq!(circle_of_confusion(&framebuffer, &settings, |depth| depth), vec![0.0, 4.0, 8.0, 6.0]);
+    }
+}
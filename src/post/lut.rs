@@ -0,0 +1,266 @@
+//! 3D color lookup tables
+//!
+//! Color grading LUTs map every input color to an output color, sampled from a cube of
+//! `size * size * size` entries with trilinear interpolation. They are usually authored in other tools
+//! and exported in the Adobe/Resolve `.cube` format, which can be loaded here.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Dimensions, Coordinate};
+use ::pixels::PixelWrite;
+
+/// Errors that may occur while loading a `.cube` file
+#[derive(Debug)]
+pub enum LutError {
+    Io(io::Error),
+    /// A line could not be parsed, with the one-based line number
+    Parse(usize, &'static str),
+    /// The file ended without a size or with the wrong number of entries
+    Incomplete,
+}
+
+impl Display for LutError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            LutError::Io(ref err) => write!(f, "LUT IO Error: {}", err),
+            LutError::Parse(line, err) => write!(f, "LUT Parse Error on line {}: {}", line, err),
+            LutError::Incomplete => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for LutError {
+    fn description(&self) -> &str {
+        match *self {
+            LutError::Io(_) => "LUT IO Error",
+            LutError::Parse(_, _) => "LUT Parse Error",
+            LutError::Incomplete => "Incomplete LUT",
+        }
+    }
+}
+
+impl From<io::Error> for LutError {
+    fn from(err: io::Error) -> LutError { LutError::Io(err) }
+}
+
+/// A 3D color lookup table
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut3D {
+    size: usize,
+    domain_min: [f32; 3],
+    domain_max: [f32; 3],
+    /// Entries with red changing fastest, then green, then blue
+    table: Vec<[f32; 3]>,
+}
+
+impl Lut3D {
+    /// Creates an identity LUT that leaves colors unchanged
+    pub fn identity(size: usize) -> Lut3D {
+        assert!(size >= 2, "LUTs must have at least two entries per axis");
+
+        let scale = 1.0 / (size - 1) as f32;
+        let mut table = Vec::with_capacity(size * size * size);
+
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    table.push([r as f32 * scale, g as f32 * scale, b as f32 * scale]);
+                }
+            }
+        }
+
+        Lut3D { size, domain_min: [0.0; 3], domain_max: [1.0; 3], table }
+    }
+
+    /// Number of entries along each axis
+    #[inline]
+    pub fn size(&self) -> usize { self.size }
+
+    /// Loads a LUT from a `.cube` file
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Lut3D, LutError> {
+        let mut source = String::new();
+
+        File::open(path)?.read_to_string(&mut source)?;
+
+        Lut3D::parse_cube(&source)
+    }
+
+    /// Parses a LUT from the contents of a `.cube` file
+    pub fn parse_cube(source: &str) -> Result<Lut3D, LutError> {
+        let mut size = None;
+        let mut domain_min = [0.0; 3];
+        let mut domain_max = [1.0; 3];
+        let mut table = Vec::new();
+
+        fn parse_triple<'a, I: Iterator<Item = &'a str>>(parts: I, line: usize) -> Result<[f32; 3], LutError> {
+            let mut triple = [0.0; 3];
+            let mut count = 0;
+
+            for part in parts {
+                if count == 3 {
+                    return Err(LutError::Parse(line, "Expected three values"));
+                }
+
+                triple[count] = part.parse().map_err(|_| LutError::Parse(line, "Invalid number"))?;
+                count += 1;
+            }
+
+            if count != 3 {
+                return Err(LutError::Parse(line, "Expected three values"));
+            }
+
+            Ok(triple)
+        }
+
+        for (i, line) in source.lines().enumerate() {
+            let line_number = i + 1;
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+
+            match parts.next() {
+                Some("TITLE") => {}
+                Some("LUT_1D_SIZE") => return Err(LutError::Parse(line_number, "1D LUTs are not supported")),
+                Some("LUT_3D_SIZE") => {
+                    let n: usize = parts.next().and_then(|n| n.parse().ok())
+                                        .ok_or(LutError::Parse(line_number, "Invalid LUT size"))?;
+
+                    if n < 2 {
+                        return Err(LutError::Parse(line_number, "LUT size must be at least 2"));
+                    }
+
+                    size = Some(n);
+                    table.reserve(n * n * n);
+                }
+                Some("DOMAIN_MIN") => domain_min = parse_triple(parts, line_number)?,
+                Some("DOMAIN_MAX") => domain_max = parse_triple(parts, line_number)?,
+                Some(first) => {
+                    if size.is_none() {
+                        return Err(LutError::Parse(line_number, "Table data before LUT_3D_SIZE"));
+                    }
+
+                    table.push(parse_triple(Some(first).into_iter().chain(parts), line_number)?);
+                }
+                None => {}
+            }
+        }
+
+        match size {
+            Some(size) if table.len() == size * size * size => Ok(Lut3D { size, domain_min, domain_max, table }),
+            _ => Err(LutError::Incomplete),
+        }
+    }
+
+    #[inline]
+    fn entry(&self, r: usize, g: usize, b: usize) -> [f32; 3] {
+        self.table[(b * self.size + g) * self.size + r]
+    }
+
+    /// Looks up an RGB color with trilinear interpolation
+    pub fn lookup(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let max = (self.size - 1) as f32;
+
+        let mut index = [0usize; 3];
+        let mut fract = [0.0f32; 3];
+
+        for i in 0..3 {
+            let range = self.domain_max[i] - self.domain_min[i];
+            let t = if range > 0.0 { (rgb[i] - self.domain_min[i]) / range } else { 0.0 };
+            let x = t.max(0.0).min(1.0) * max;
+
+            // Keep the base index one below the last entry so the upper neighbor always exists
+            let base = x.floor().min(max - 1.0);
+
+            index[i] = base as usize;
+            fract[i] = x - base;
+        }
+
+        let (r, g, b) = (index[0], index[1], index[2]);
+
+        let mut out = [0.0; 3];
+
+        for c in 0..3 {
+            let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+
+            let c00 = lerp(self.entry(r, g, b)[c], self.entry(r + 1, g, b)[c], fract[0]);
+            let c10 = lerp(self.entry(r, g + 1, b)[c], self.entry(r + 1, g + 1, b)[c], fract[0]);
+            let c01 = lerp(self.entry(r, g, b + 1)[c], self.entry(r + 1, g, b + 1)[c], fract[0]);
+            let c11 = lerp(self.entry(r, g + 1, b + 1)[c], self.entry(r + 1, g + 1, b + 1)[c], fract[0]);
+
+            out[c] = lerp(lerp(c00, c10, fract[1]), lerp(c01, c11, fract[1]), fract[2]);
+        }
+
+        out
+    }
+
+    /// Grades every pixel of the buffer in place. Alpha is left unchanged.
+    pub fn apply<P>(&self, buffer: &mut P) where P: PixelWrite, P::Color: ToChannels + FromChannels {
+        let Dimensions { width, height } = buffer.dimensions();
+
+        buffer.prepare_write();
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = buffer.index_of(Coordinate::new(x, y));
+
+                unsafe {
+                    let c = buffer.get_pixel_unchecked(index).to_channels();
+                    let graded = self.lookup([c[0], c[1], c[2]]);
+
+                    buffer.set_pixel_unchecked(index, P::Color::from_channels([graded[0], graded[1], graded[2], c[3]]));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_identity_lookup() {
+        let lut = Lut3D::identity(17);
+
+        for &c in &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0], [0.25, 0.5, 0.75], [0.1, 0.9, 0.33]] {
+            let out = lut.lookup(c);
+
+            for i in 0..3 {
+                assert!((out[i] - c[i]).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_cube() {
+        let source = "# comment\nTITLE \"invert\"\nLUT_3D_SIZE 2\n\
+                      1 1 1\n0 1 1\n1 0 1\n0 0 1\n1 1 0\n0 1 0\n1 0 0\n0 0 0\n";
+
+        let lut = Lut3D::parse_cube(source).unwrap();
+
+        assert_eq!(lut.size(), 2);
+
+        let out = lut.lookup([0.25, 0.5, 1.0]);
+
+        assert!((out[0] - 0.75).abs() < 1e-5);
+        assert!((out[1] - 0.5).abs() < 1e-5);
+        assert!(out[2].abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_parse_incomplete() {
+        match Lut3D::parse_cube("LUT_3D_SIZE 2\n0 0 0\n") {
+            Err(LutError::Incomplete) => {}
+            other => panic!("Expected incomplete LUT, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Post-processing passes
+//!
+//! Post-processing passes read from one or more finished pixel buffers and write the result into another.
+//! Colors are converted to normalized channels with `ToChannels` and `FromChannels`,
+//! so passes work on any color format implementing those.
+
+pub mod stereo;
+pub mod panorama;
+pub mod dof;
+pub mod blur;
+pub mod bloom;
+pub mod motion;
+pub mod lut;
+pub mod decal;
+pub mod upscale;
@@ -0,0 +1,130 @@
+//! Motion vectors and motion blur
+//!
+//! Motion vectors are written by the fragment shader into a second `RGf32Color` attachment,
+//! such as the one in `RGBAf32MotionTextureBuffer`. To compute them, the vertex shader transforms
+//! each vertex with both the current and previous frame's model-view-projection matrices,
+//! passing both clip-space positions on as uniforms:
+//!
+//! ```ignore
+//! let vertex_shader = |v: &Vertex, global: &GlobalUniforms| {
+//!     let current = global.mvp * v.position.to_homogeneous();
+//!     let previous = global.previous_mvp * v.position.to_homogeneous();
+//!
+//!     ClipVertex::new(current, Uniforms { current, previous })
+//! };
+//!
+//! let fragment_shader = |v: &ScreenVertex<f32, Uniforms>, _: &GlobalUniforms| {
+//!     Fragment::Color((shade(v), motion_vector(v.uniforms.current, v.uniforms.previous, dimensions)))
+//! };
+//! ```
+//!
+//! The motion blur pass then samples the color attachment along those vectors.
+
+use num_traits::cast;
+
+use nalgebra::{Vector2, Vector4};
+
+use ::numeric::FloatScalar;
+use ::color::{ToChannels, FromChannels};
+use ::color::predefined::formats::RGf32Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite};
+use ::texture::{Filter, sample_channels};
+use ::error::{RenderResult, RenderError};
+
+/// Computes the screen-space motion of a fragment in pixels, from the previous frame to the current one.
+///
+/// Both positions are clip-space positions before the perspective divide, interpolated across the primitive.
+pub fn motion_vector<N: FloatScalar>(current: Vector4<N>, previous: Vector4<N>, dimensions: Dimensions) -> RGf32Color {
+    let to_ndc = |p: Vector4<N>| -> (f32, f32) {
+        (cast(p.x / p.w).unwrap(), cast(p.y / p.w).unwrap())
+    };
+
+    let (cx, cy) = to_ndc(current);
+    let (px, py) = to_ndc(previous);
+
+    // NDC spans two units across the screen, and the y-axis is flipped in screen-space
+    Vector2::new((cx - px) * 0.5 * dimensions.width as f32,
+                 (py - cy) * 0.5 * dimensions.height as f32)
+}
+
+/// Motion blur settings
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct MotionBlur {
+    /// Number of samples taken along each motion vector
+    pub samples: u32,
+    /// Fraction of the frame the shutter is open for, scaling the motion vectors
+    pub shutter: f32,
+    /// Longest blur in pixels
+    pub max_length: f32,
+}
+
+impl Default for MotionBlur {
+    fn default() -> MotionBlur {
+        MotionBlur {
+            samples: 8,
+            shutter: 0.5,
+            max_length: 32.0,
+        }
+    }
+}
+
+/// Blurs `color` along the per-pixel motion vectors in `motion`, writing the result to `out`.
+///
+/// Samples are spread evenly along the motion vector, centered on the pixel.
+/// All buffers must have the same dimensions.
+pub fn motion_blur<P, M, O>(color: &P, motion: &M, out: &mut O, settings: &MotionBlur) -> RenderResult<()>
+    where P: PixelRead, P::Color: ToChannels, M: PixelRead<Color = RGf32Color>, O: PixelWrite, O::Color: FromChannels {
+    let dimensions = color.dimensions();
+
+    if dimensions != motion.dimensions() || dimensions != out.dimensions() {
+        throw!(RenderError::DimensionMismatch);
+    }
+
+    let samples = settings.samples.max(1);
+
+    out.prepare_write();
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            let coord = Coordinate::new(x, y);
+
+            let mut velocity = unsafe { motion.get_pixel_unchecked(motion.index_of(coord)) } * settings.shutter;
+
+            let length = velocity.norm();
+
+            if length > settings.max_length {
+                velocity *= settings.max_length / length;
+            }
+
+            let result = if length < 0.5 || samples == 1 {
+                unsafe { color.get_pixel_unchecked(color.index_of(coord)).to_channels() }
+            } else {
+                let mut sum = [0.0; 4];
+
+                for i in 0..samples {
+                    let t = (i as f32 + 0.5) / samples as f32 - 0.5;
+
+                    let c = sample_channels(color, x as f32 + 0.5 - velocity.x * t, y as f32 + 0.5 - velocity.y * t, Filter::Bilinear);
+
+                    for j in 0..4 {
+                        sum[j] += c[j];
+                    }
+                }
+
+                for c in &mut sum {
+                    *c /= samples as f32;
+                }
+
+                sum
+            };
+
+            let index = out.index_of(coord);
+
+            unsafe { out.set_pixel_unchecked(index, O::Color::from_channels(result)); }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+//! Panorama rendering
+//!
+//! Full 360 degree images are rendered by first rendering the scene into the six faces of a cubemap,
+//! then resampling the cubemap into an equirectangular projection.
+
+use alga::general::Real;
+
+use nalgebra::{Point3, Vector3, Matrix4};
+
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite};
+use ::texture::{Filter, CubeFace, Cubemap};
+
+/// Resamples a cubemap into an equirectangular image.
+///
+/// The horizontal axis of the output covers longitude from -180 to 180 degrees, with the center looking down -Z,
+/// and the vertical axis covers latitude from straight up at the top row to straight down at the bottom row.
+/// Outputs are usually twice as wide as they are tall.
+pub fn equirectangular<T, O>(cubemap: &Cubemap<T>, out: &mut O, filter: Filter)
+    where T: PixelRead, T::Color: ToChannels, O: PixelWrite, O::Color: FromChannels {
+    let dimensions = out.dimensions();
+
+    let pi = ::std::f32::consts::PI;
+
+    out.prepare_write();
+
+    for y in 0..dimensions.height {
+        let latitude = pi * 0.5 - (y as f32 + 0.5) / dimensions.height as f32 * pi;
+
+        for x in 0..dimensions.width {
+            let longitude = (x as f32 + 0.5) / dimensions.width as f32 * 2.0 * pi - pi;
+
+            let direction = Vector3::new(
+                latitude.cos() * longitude.sin(),
+                latitude.sin(),
+                -latitude.cos() * longitude.cos(),
+            );
+
+            let color = cubemap.sample(direction, filter);
+
+            unsafe {
+                let index = out.index_of(Coordinate::new(x, y));
+
+                out.set_pixel_unchecked(index, O::Color::from_channels(color));
+            }
+        }
+    }
+}
+
+/// Renders a panorama from the given position.
+///
+/// `render_face` is called once per cubemap face with the face, its view matrix and a 90 degree projection matrix,
+/// and should render the scene with those and return a copy of the resulting color buffer.
+/// Faces should be square, and the face size should be about a quarter of the output width to avoid blurring.
+pub fn render_panorama<N, T, R, O>(eye: Point3<N>, near: N, far: N, mut render_face: R, out: &mut O, filter: Filter)
+    where N: Real, T: PixelRead, T::Color: ToChannels, O: PixelWrite, O::Color: FromChannels,
+          R: FnMut(CubeFace, Matrix4<N>, Matrix4<N>) -> T {
+    let projection = CubeFace::projection_matrix(near, far);
+
+    let cubemap = Cubemap::from_fn(|face| render_face(face, face.view_matrix(eye), projection));
+
+    equirectangular(&cubemap, out, filter);
+}
+
+/// Returns the equirectangular output size that matches the resolution of cubemap faces of the given size
+pub fn panorama_dimensions(face_size: u32) -> Dimensions {
+    Dimensions::new(face_size * 4, face_size * 2)
+}
@@ -0,0 +1,249 @@
+//! Stereo compositors
+//!
+//! Combines the left and right eye images of a stereo render into a single image for display.
+//! See `pipeline::stereo` for rendering the eye images.
+
+use ::error::{RenderResult, RenderError};
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite};
+
+/// How colors are combined into a red-cyan anaglyph
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Anaglyph {
+    /// Red channel from the left eye, green and blue channels from the right eye.
+    ///
+    /// Keeps the most color, but strongly red or cyan objects only appear to one eye.
+    Color,
+    /// Like `Color`, but the red channel is the luminance of the left eye,
+    /// which reduces retinal rivalry on red objects.
+    HalfColor,
+    /// Luminance of the left eye in red, and of the right eye in green and blue.
+    Gray,
+}
+
+impl Default for Anaglyph {
+    fn default() -> Anaglyph { Anaglyph::HalfColor }
+}
+
+/// Lines to alternate between eyes in an interlaced image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Interlace {
+    /// Even rows from the left eye, odd rows from the right eye
+    Rows,
+    /// Even columns from the left eye, odd columns from the right eye
+    Columns,
+}
+
+#[inline]
+fn luminance(c: &[f32; 4]) -> f32 {
+    0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2]
+}
+
+fn check_same_dimensions(a: Dimensions, b: Dimensions) -> RenderResult<()> {
+    if a != b {
+        throw!(RenderError::DimensionMismatch);
+    }
+
+    Ok(())
+}
+
+/// Composites the left and right eye images into a red-cyan anaglyph.
+///
+/// All three buffers must have the same dimensions. Alpha is averaged between both eyes.
+pub fn anaglyph<L, R, O>(left: &L, right: &R, out: &mut O, mode: Anaglyph) -> RenderResult<()>
+    where L: PixelRead, R: PixelRead, O: PixelWrite,
+          L::Color: ToChannels, R::Color: ToChannels, O::Color: FromChannels {
+    let dimensions = out.dimensions();
+
+    check_same_dimensions(left.dimensions(), dimensions)?;
+    check_same_dimensions(right.dimensions(), dimensions)?;
+
+    out.prepare_write();
+
+    for coord in (0..dimensions.height).flat_map(|y| (0..dimensions.width).map(move |x| Coordinate::new(x, y))) {
+        let (l, r) = unsafe {
+            (left.get_pixel_unchecked(left.index_of(coord)).to_channels(), right.get_pixel_unchecked(right.index_of(coord)).to_channels())
+        };
+
+        let alpha = (l[3] + r[3]) * 0.5;
+
+        let color = match mode {
+            Anaglyph::Color => [l[0], r[1], r[2], alpha],
+            Anaglyph::HalfColor => [luminance(&l), r[1], r[2], alpha],
+            Anaglyph::Gray => {
+                let rl = luminance(&r);
+
+                [luminance(&l), rl, rl, alpha]
+            }
+        };
+
+        let index = out.index_of(coord);
+
+        unsafe { out.set_pixel_unchecked(index, O::Color::from_channels(color)); }
+    }
+
+    Ok(())
+}
+
+/// Composites the left and right eye images by alternating rows or columns,
+/// as used by passive polarized displays.
+///
+/// All three buffers must have the same dimensions.
+pub fn interlaced<L, R, O>(left: &L, right: &R, out: &mut O, interlace: Interlace) -> RenderResult<()>
+    where L: PixelRead, R: PixelRead<Color = L::Color>, O: PixelWrite<Color = L::Color> {
+    let dimensions = out.dimensions();
+
+    check_same_dimensions(left.dimensions(), dimensions)?;
+    check_same_dimensions(right.dimensions(), dimensions)?;
+
+    out.prepare_write();
+
+    for y in 0..dimensions.height {
+        for x in 0..dimensions.width {
+            let coord = Coordinate::new(x, y);
+
+            let line = match interlace {
+                Interlace::Rows => y,
+                Interlace::Columns => x,
+            };
+
+            unsafe {
+                let color = if line % 2 == 0 {
+                    left.get_pixel_unchecked(left.index_of(coord))
+                } else {
+                    right.get_pixel_unchecked(right.index_of(coord))
+                };
+
+                let index = out.index_of(coord);
+
+                out.set_pixel_unchecked(index, color);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Places the left and right eye images next to each other.
+///
+/// Both eye images must have the same dimensions, and the output must be exactly twice as wide.
+pub fn side_by_side<L, R, O>(left: &L, right: &R, out: &mut O) -> RenderResult<()>
+    where L: PixelRead, R: PixelRead<Color = L::Color>, O: PixelWrite<Color = L::Color> {
+    let eye = left.dimensions();
+    let dimensions = out.dimensions();
+
+    check_same_dimensions(right.dimensions(), eye)?;
+    check_same_dimensions(Dimensions::new(eye.width * 2, eye.height), dimensions)?;
+
+    out.prepare_write();
+
+    for y in 0..eye.height {
+        for x in 0..eye.width {
+            let coord = Coordinate::new(x, y);
+
+            let (left_index, right_index) = (out.index_of(coord), out.index_of(Coordinate::new(x + eye.width, y)));
+
+            unsafe {
+                out.set_pixel_unchecked(left_index, left.get_pixel_unchecked(left.index_of(coord)));
+                out.set_pixel_unchecked(right_index, right.get_pixel_unchecked(right.index_of(coord)));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use nalgebra::Vector4;
+
+    use ::color::predefined::formats::RGBAf32Color;
+    use ::pixels::ColorBuffer;
+
+    /// Eye images encoding their coordinates, with blue telling the eyes apart
+    fn eye(blue: f32) -> ColorBuffer<RGBAf32Color> {
+        ColorBuffer::from_fn(Dimensions::new(2, 2), |coord| Vector4::new(coord.x as f32, coord.y as f32, blue, 1.0))
+    }
+
+    fn get(buffer: &ColorBuffer<RGBAf32Color>, x: u32, y: u32) -> RGBAf32Color {
+        buffer.as_slice()[buffer.index_of(Coordinate::new(x, y))]
+    }
+
+    fn assert_mismatch(result: RenderResult<()>) {
+        match result.map_err(|trace| trace.into_error()) {
+            Err(RenderError::DimensionMismatch) => (),
+            r => panic!("Unexpected result {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_anaglyph() {
+        let left = ColorBuffer::filled(Dimensions::new(2, 2), Vector4::new(0.8, 0.2, 0.1, 1.0));
+        let right = ColorBuffer::filled(Dimensions::new(2, 2), Vector4::new(0.1, 0.6, 0.4, 0.5));
+
+        let (luminance_left, luminance_right): (f32, f32) = (0.299 * 0.8 + 0.587 * 0.2 + 0.114 * 0.1, 0.299 * 0.1 + 0.587 * 0.6 + 0.114 * 0.4);
+
+        for &(mode, expected) in &[(Anaglyph::Color, Vector4::new(0.8, 0.6, 0.4, 0.75)),
+                                   (Anaglyph::HalfColor, Vector4::new(luminance_left, 0.6, 0.4, 0.75)),
+                                   (Anaglyph::Gray, Vector4::new(luminance_left, luminance_right, luminance_right, 0.75))] {
+            let mut out: ColorBuffer<RGBAf32Color> = ColorBuffer::new(Dimensions::new(2, 2));
+
+            anaglyph(&left, &right, &mut out, mode).unwrap();
+
+            for pixel in out.as_slice() {
+                assert!((*pixel - expected).norm() < 1e-5, "{:?}: {:?}", mode, pixel);
+            }
+        }
+
+        assert_mismatch(anaglyph(&left, &right, &mut ColorBuffer::<RGBAf32Color>::new(Dimensions::new(2, 1)), Anaglyph::Color));
+    }
+
+    #[test]
+    fn test_interlaced() {
+        let (left, right) = (eye(0.0), eye(1.0));
+
+        for &interlace in &[Interlace::Rows, Interlace::Columns] {
+            let mut out = ColorBuffer::new(Dimensions::new(2, 2));
+
+            interlaced(&left, &right, &mut out, interlace).unwrap();
+
+            for y in 0..2 {
+                for x in 0..2 {
+                    let line = if interlace == Interlace::Rows { y } else { x };
+
+                    // Pixels stay in place, and odd lines come from the right eye
+                    assert_eq!(get(&out, x, y), Vector4::new(x as f32, y as f32, line as f32, 1.0), "{:?}", interlace);
+                }
+            }
+        }
+
+        assert_mismatch(interlaced(&left, &eye(1.0), &mut ColorBuffer::new(Dimensions::new(4, 2)), Interlace::Rows));
+        assert_mismatch(interlaced(&left, &ColorBuffer::new(Dimensions::new(1, 2)), &mut ColorBuffer::new(Dimensions::new(2, 2)), Interlace::Rows));
+    }
+
+    #[test]
+    fn test_side_by_side() {
+        let (left, right) = (eye(0.0), eye(1.0));
+
+        let mut out = ColorBuffer::new(Dimensions::new(4, 2));
+
+        side_by_side(&left, &right, &mut out).unwrap();
+
+        for y in 0..2 {
+            for x in 0..4 {
+                let expected = if x < 2 { get(&left, x, y) } else { get(&right, x - 2, y) };
+
+                assert_eq!(get(&out, x, y), expected, "({}, {})", x, y);
+            }
+        }
+
+        // The output has to be exactly twice as wide as the eyes
+        assert_mismatch(side_by_side(&left, &right, &mut ColorBuffer::new(Dimensions::new(2, 2))));
+        assert_mismatch(side_by_side(&left, &right, &mut ColorBuffer::new(Dimensions::new(4, 3))));
+    }
+}
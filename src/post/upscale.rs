@@ -0,0 +1,209 @@
+//! Upscaling
+//!
+//! Scales a low-resolution image up to the size of the output, for presenting frames rendered
+//! at a lower internal resolution. Besides plain bilinear filtering, there is a filter modelled after
+//! AMD FidelityFX Super Resolution 1, which upsamples with a deringed bicubic filter and then restores detail
+//! with contrast-adaptive sharpening. The sharpening assumes channels between zero and one.
+
+use ::numeric::utils::min;
+use ::color::{ToChannels, FromChannels};
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::{PixelRead, PixelWrite};
+use ::texture::{Filter, sample_channels};
+
+/// Most negative weight of the neighbors when sharpening, which keeps the sharpening filter stable
+const SHARPEN_LIMIT: f32 = 0.25 - 1.0 / 16.0;
+
+/// Filter used for upscaling
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum UpscaleFilter {
+    /// Bilinear filtering, which is fast but soft
+    Bilinear,
+    /// Edge-preserving bicubic upsampling followed by contrast-adaptive sharpening,
+    /// with `sharpness` from `0.0` for none to `1.0` for the most
+    Fsr { sharpness: f32 },
+}
+
+impl Default for UpscaleFilter {
+    fn default() -> UpscaleFilter { UpscaleFilter::Bilinear }
+}
+
+/// Scales `input` up or down to the size of `out`
+pub fn upscale<P, O>(input: &P, out: &mut O, filter: UpscaleFilter)
+    where P: PixelRead, P::Color: ToChannels, O: PixelWrite, O::Color: FromChannels {
+    let from = input.dimensions();
+    let to = out.dimensions();
+
+    if from.area() == 0 || to.area() == 0 {
+        return;
+    }
+
+    let sx = from.width as f32 / to.width as f32;
+    let sy = from.height as f32 / to.height as f32;
+
+    out.prepare_write();
+
+    match filter {
+        UpscaleFilter::Bilinear => {
+            for y in 0..to.height {
+                for x in 0..to.width {
+                    let c = sample_channels(input, (x as f32 + 0.5) * sx, (y as f32 + 0.5) * sy, Filter::Bilinear);
+
+                    let index = out.index_of(Coordinate::new(x, y));
+
+                    unsafe { out.set_pixel_unchecked(index, O::Color::from_channels(c)); }
+                }
+            }
+        }
+        UpscaleFilter::Fsr { sharpness } => {
+            let mut upsampled = Vec::with_capacity(to.area());
+
+            for y in 0..to.height {
+                for x in 0..to.width {
+                    upsampled.push(sample_deringed_bicubic(input, (x as f32 + 0.5) * sx, (y as f32 + 0.5) * sy));
+                }
+            }
+
+            let (width, height) = (to.width as usize, to.height as usize);
+
+            for y in 0..height {
+                for x in 0..width {
+                    let at = |x: usize, y: usize| upsampled[y * width + x];
+
+                    let c = sharpen(at(x, y),
+                                    [at(x, y.saturating_sub(1)), at(x.saturating_sub(1), y),
+                                     at(min(x + 1, width - 1), y), at(x, min(y + 1, height - 1))],
+                                    sharpness);
+
+                    let index = out.index_of(Coordinate::new(x as u32, y as u32));
+
+                    unsafe { out.set_pixel_unchecked(index, O::Color::from_channels(c)); }
+                }
+            }
+        }
+    }
+}
+
+/// Catmull-Rom weights for the four texels around a fractional position `t`
+#[inline]
+fn catmull_rom(t: f32) -> [f32; 4] {
+    [
+        ((-0.5 * t + 1.0) * t - 0.5) * t,
+        (1.5 * t - 2.5) * t * t + 1.0,
+        ((-1.5 * t + 2.0) * t + 0.5) * t,
+        (0.5 * t - 0.5) * t * t,
+    ]
+}
+
+/// Bicubic sample at a pixel-space position, clamped to the range of the four nearest texels so edges don't ring
+fn sample_deringed_bicubic<P>(input: &P, x: f32, y: f32) -> [f32; 4] where P: PixelRead, P::Color: ToChannels {
+    let dimensions = input.dimensions();
+
+    let (xmax, ymax) = (dimensions.width as i64 - 1, dimensions.height as i64 - 1);
+
+    let fetch = |x: i64, y: i64| {
+        let coord = Coordinate::new(x.max(0).min(xmax) as u32, y.max(0).min(ymax) as u32);
+
+        unsafe { input.get_pixel_unchecked(input.index_of(coord)).to_channels() }
+    };
+
+    let (x, y) = (x - 0.5, y - 0.5);
+    let (x0, y0) = (x.floor(), y.floor());
+
+    let (wx, wy) = (catmull_rom(x - x0), catmull_rom(y - y0));
+    let (x0, y0) = (x0 as i64, y0 as i64);
+
+    let mut sum = [0.0; 4];
+    let mut low = [::std::f32::INFINITY; 4];
+    let mut high = [::std::f32::NEG_INFINITY; 4];
+
+    for j in 0..4 {
+        for i in 0..4 {
+            let texel = fetch(x0 + i as i64 - 1, y0 + j as i64 - 1);
+            let weight = wx[i] * wy[j];
+
+            let nearest = (i == 1 || i == 2) && (j == 1 || j == 2);
+
+            for k in 0..4 {
+                sum[k] += texel[k] * weight;
+
+                if nearest {
+                    low[k] = low[k].min(texel[k]);
+                    high[k] = high[k].max(texel[k]);
+                }
+            }
+        }
+    }
+
+    for k in 0..4 {
+        sum[k] = sum[k].max(low[k]).min(high[k]);
+    }
+
+    sum
+}
+
+/// Contrast-adaptive sharpening of a pixel given its four direct neighbors, leaving alpha alone.
+///
+/// Neighbors get a negative weight as large as possible without pushing the result out of `[0, 1]`,
+/// so flat areas and strong edges are left mostly untouched while soft detail is sharpened.
+fn sharpen(center: [f32; 4], neighbors: [[f32; 4]; 4], sharpness: f32) -> [f32; 4] {
+    let mut lobe = ::std::f32::NEG_INFINITY;
+
+    for k in 0..3 {
+        let low = neighbors.iter().fold(center[k], |low, n| low.min(n[k]));
+        let high = neighbors.iter().fold(center[k], |high, n| high.max(n[k]));
+
+        let hit_low = low / (4.0 * high).max(1e-5);
+        let hit_high = (1.0 - high) / (4.0 * low - 4.0).min(-1e-5);
+
+        lobe = lobe.max((-hit_low).max(hit_high));
+    }
+
+    let lobe = lobe.min(0.0).max(-SHARPEN_LIMIT) * sharpness.max(0.0).min(1.0);
+
+    let mut result = center;
+
+    for k in 0..3 {
+        let sum = neighbors.iter().fold(0.0, |sum, n| sum + n[k]);
+
+        result[k] = (lobe * sum + center[k]) / (4.0 * lobe + 1.0);
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+    use ::color::predefined::formats::Rf32Color;
+
+    use super::*;
+
+    #[test]
+    fn test_upscale() {
+        let flat = ColorBuffer::filled(Dimensions::new(3, 2), Rf32Color::new(0.25));
+
+        for &filter in &[UpscaleFilter::Bilinear, UpscaleFilter::Fsr { sharpness: 1.0 }] {
+            let mut out = ColorBuffer::<Rf32Color>::new(Dimensions::new(7, 5));
+
+            upscale(&flat, &mut out, filter);
+
+            assert!(out.as_slice().iter().all(|&c| (c.to_channels()[0] - 0.25).abs() < 1e-5));
+        }
+
+        // A hard edge doesn't ring
+        let edge = ColorBuffer::from_fn(Dimensions::new(4, 1), |coord| Rf32Color::new(if coord.x < 2 { 0.0 } else { 1.0 }));
+
+        let mut out = ColorBuffer::<Rf32Color>::new(Dimensions::new(16, 1));
+
+        upscale(&edge, &mut out, UpscaleFilter::Fsr { sharpness: 1.0 });
+
+        let values: Vec<f32> = out.as_slice().iter().map(|c| c.to_channels()[0]).collect();
+
+        assert!(values.iter().all(|&v| v >= 0.0 && v <= 1.0));
+        assert_eq!(values[0], 0.0);
+        assert_eq!(values[15], 1.0);
+    }
+}
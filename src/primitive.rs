@@ -2,6 +2,7 @@
 
 use ::numeric::FloatScalar;
 use ::geometry::ClipVertex;
+use ::mesh::MeshIndex;
 
 /// Defines the kinds of primitives that can be rendered by themselves.
 pub trait Primitive {
@@ -28,7 +29,7 @@ pub trait Primitive {
     /// Creates a `PrimitiveRef` from some indexed vertices.
     ///
     /// This are used internally.
-    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K>(vertices: &'p [ClipVertex<N, K>], indices: &[usize]) -> PrimitiveRef<'p, N, K>;
+    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K, I: MeshIndex>(vertices: &'p [ClipVertex<N, K>], indices: &[I]) -> PrimitiveRef<'p, N, K>;
 }
 
 /// Holds references to primitive vertices for each primitive type
@@ -92,10 +93,10 @@ impl Primitive for Point {
         PrimitiveMut::Point(&mut vertices[0])
     }
 
-    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K>(vertices: &'p [ClipVertex<N, K>], indices: &[usize]) -> PrimitiveRef<'p, N, K> {
+    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K, I: MeshIndex>(vertices: &'p [ClipVertex<N, K>], indices: &[I]) -> PrimitiveRef<'p, N, K> {
         debug_assert!(indices.len() >= Self::num_vertices());
 
-        PrimitiveRef::Point(&vertices[indices[0]])
+        PrimitiveRef::Point(&vertices[indices[0].to_usize()])
     }
 }
 
@@ -120,12 +121,12 @@ impl Primitive for Line {
         PrimitiveMut::Line { start: &mut start[0], end: &mut end[0] }
     }
 
-    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K>(vertices: &'p [ClipVertex<N, K>], indices: &[usize]) -> PrimitiveRef<'p, N, K> {
+    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K, I: MeshIndex>(vertices: &'p [ClipVertex<N, K>], indices: &[I]) -> PrimitiveRef<'p, N, K> {
         debug_assert!(indices.len() >= Self::num_vertices());
 
         PrimitiveRef::Line {
-            start: &vertices[indices[0]],
-            end: &vertices[indices[1]],
+            start: &vertices[indices[0].to_usize()],
+            end: &vertices[indices[1].to_usize()],
         }
     }
 }
@@ -156,13 +157,13 @@ impl Primitive for Triangle {
         PrimitiveMut::Triangle { a: &mut a[0], b: &mut b[0], c: &mut c[0] }
     }
 
-    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K>(vertices: &'p [ClipVertex<N, K>], indices: &[usize]) -> PrimitiveRef<'p, N, K> {
+    fn create_ref_from_indexed_vertices<'p, N: FloatScalar, K, I: MeshIndex>(vertices: &'p [ClipVertex<N, K>], indices: &[I]) -> PrimitiveRef<'p, N, K> {
         debug_assert!(indices.len() >= Self::num_vertices());
 
         PrimitiveRef::Triangle {
-            a: &vertices[indices[0]],
-            b: &vertices[indices[1]],
-            c: &vertices[indices[2]],
+            a: &vertices[indices[0].to_usize()],
+            b: &vertices[indices[1].to_usize()],
+            c: &vertices[indices[2].to_usize()],
         }
     }
 }
\ No newline at end of file
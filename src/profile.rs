@@ -0,0 +1,197 @@
+//! Lightweight profiler
+//!
+//! With the `profile` cargo feature enabled, the pipeline records named spans around its stages on every thread:
+//!
+//! * `vertex` - vertex shading, including the viewport transform when going straight to fragment shading
+//! * `geometry` - geometry shading, including primitive clipping
+//! * `viewport` - transforming clip-space vertices into screen-space
+//! * `binning` - tile setup and primitive checks before rasterization
+//! * `raster` - rasterization of a single tile, including fragment shading and blending
+//!
+//! Blending has no span of its own. It's done per fragment right after shading, inlined into the rasterizer,
+//! where timing it separately would cost more than the blending itself.
+//!
+//! Spans are only recorded between `start_recording` and `stop_recording`, and the resulting `Trace`
+//! can be exported as JSON for `chrome://tracing` to see where frame time goes across threads.
+//! Without the feature, none of this exists and the pipeline has no profiling overhead at all.
+//!
+//! ```ignore
+//! softrender::profile::start_recording();
+//!
+//! // render a frame...
+//!
+//! let trace = softrender::profile::stop_recording();
+//!
+//! trace.write_chrome_json(File::create("frame.json")?)?;
+//! ```
+
+use std::io::{self, Write};
+use std::mem;
+use std::sync::{Once, ONCE_INIT};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering, ATOMIC_BOOL_INIT, ATOMIC_USIZE_INIT};
+use std::time::{Duration, Instant};
+
+use parking_lot::Mutex;
+
+/// A single recorded span
+#[derive(Debug, Clone)]
+pub struct Span {
+    /// Name of the span, such as `"raster"`
+    pub name: &'static str,
+    /// Index of the thread the span was recorded on, in the order threads first recorded anything
+    pub thread: usize,
+    /// Time from the creation of the profiler to the start of the span
+    pub start: Duration,
+    /// Duration of the span
+    pub duration: Duration,
+}
+
+struct Profiler {
+    epoch: Instant,
+    spans: Mutex<Vec<Span>>,
+}
+
+static INIT: Once = ONCE_INIT;
+static mut PROFILER: *const Profiler = 0 as *const Profiler;
+
+static RECORDING: AtomicBool = ATOMIC_BOOL_INIT;
+static NEXT_THREAD: AtomicUsize = ATOMIC_USIZE_INIT;
+
+thread_local!(static THREAD: usize = NEXT_THREAD.fetch_add(1, Ordering::Relaxed));
+
+fn profiler() -> &'static Profiler {
+    unsafe {
+        INIT.call_once(|| {
+            // Lives for the rest of the program
+            PROFILER = Box::into_raw(Box::new(Profiler {
+                epoch: Instant::now(),
+                spans: Mutex::new(Vec::new()),
+            }));
+        });
+
+        &*PROFILER
+    }
+}
+
+/// Starts recording spans, discarding any spans recorded but not yet collected
+pub fn start_recording() {
+    profiler().spans.lock().clear();
+
+    RECORDING.store(true, Ordering::SeqCst);
+}
+
+/// Stops recording spans, returning everything recorded since `start_recording`
+pub fn stop_recording() -> Trace {
+    RECORDING.store(false, Ordering::SeqCst);
+
+    Trace { spans: mem::replace(&mut *profiler().spans.lock(), Vec::new()) }
+}
+
+/// Returns true if spans are currently being recorded
+#[inline]
+pub fn is_recording() -> bool {
+    RECORDING.load(Ordering::Relaxed)
+}
+
+/// Records a span from its creation until it's dropped.
+///
+/// Usually created with the `profile_scope!` macro inside the crate.
+pub struct Scope {
+    name: &'static str,
+    start: Option<Instant>,
+}
+
+impl Scope {
+    #[inline]
+    pub fn new(name: &'static str) -> Scope {
+        Scope { name, start: if is_recording() { Some(Instant::now()) } else { None } }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(start) = self.start {
+            let duration = start.elapsed();
+            let profiler = profiler();
+
+            let span = Span {
+                name: self.name,
+                thread: THREAD.with(|thread| *thread),
+                start: start.duration_since(profiler.epoch),
+                duration,
+            };
+
+            profiler.spans.lock().push(span);
+        }
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e6 + duration.subsec_nanos() as f64 / 1e3
+}
+
+/// Spans recorded between `start_recording` and `stop_recording`
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub spans: Vec<Span>,
+}
+
+impl Trace {
+    /// Total time spent in spans with the given name, summed over all threads
+    pub fn total(&self, name: &str) -> Duration {
+        self.spans.iter()
+            .filter(|span| span.name == name)
+            .fold(Duration::new(0, 0), |total, span| total + span.duration)
+    }
+
+    /// Writes the trace in the Trace Event Format understood by `chrome://tracing`
+    pub fn write_chrome_json<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "{{\"traceEvents\":[")?;
+
+        for (i, span) in self.spans.iter().enumerate() {
+            if i > 0 {
+                write!(writer, ",")?;
+            }
+
+            let name = span.name.replace('\\', "\\\\").replace('"', "\\\"");
+
+            write!(writer, "{{\"name\":\"{}\",\"cat\":\"softrender\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":0,\"tid\":{}}}",
+                   name, micros(span.start), micros(span.duration), span.thread)?;
+        }
+
+        write!(writer, "]}}")
+    }
+
+    /// Returns the trace in the Trace Event Format understood by `chrome://tracing`
+    pub fn to_chrome_json(&self) -> String {
+        let mut json = Vec::new();
+
+        self.write_chrome_json(&mut json).expect("Writing to a Vec can't fail");
+
+        String::from_utf8(json).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_chrome_json() {
+        let trace = Trace {
+            spans: vec![
+                Span { name: "raster", thread: 1, start: Duration::new(0, 1500), duration: Duration::new(1, 250) },
+                Span { name: "say \"hi\" \\", thread: 0, start: Duration::new(0, 0), duration: Duration::new(0, 0) },
+            ],
+        };
+
+        assert_eq!(trace.to_chrome_json(),
+                   concat!("{\"traceEvents\":[",
+                           "{\"name\":\"raster\",\"cat\":\"softrender\",\"ph\":\"X\",\"ts\":1.500,\"dur\":1000000.250,\"pid\":0,\"tid\":1},",
+                           "{\"name\":\"say \\\"hi\\\" \\\\\",\"cat\":\"softrender\",\"ph\":\"X\",\"ts\":0.000,\"dur\":0.000,\"pid\":0,\"tid\":0}",
+                           "]}"));
+
+        assert_eq!(Trace::default().to_chrome_json(), "{\"traceEvents\":[]}");
+        assert_eq!(trace.total("raster"), Duration::new(1, 250));
+    }
+}
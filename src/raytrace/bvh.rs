@@ -0,0 +1,303 @@
+//! Bounding volume hierarchy over triangles
+
+use nalgebra::{Point3, Vector3};
+
+use num_traits::cast;
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::{Ray, Hit, intersect_triangle};
+
+/// Most triangles stored in a single leaf
+const MAX_LEAF_TRIANGLES: usize = 4;
+
+/// Axis-aligned bounding box
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// A box containing nothing, which grows to fit the first point added
+    pub fn empty() -> Aabb {
+        let inf = ::std::f32::INFINITY;
+
+        Aabb { min: Point3::new(inf, inf, inf), max: Point3::new(-inf, -inf, -inf) }
+    }
+
+    /// Grows the box to contain `point`
+    pub fn grow(&mut self, point: Point3<f32>) {
+        for i in 0..3 {
+            self.min[i] = self.min[i].min(point[i]);
+            self.max[i] = self.max[i].max(point[i]);
+        }
+    }
+
+    /// Size of the box along each axis
+    #[inline]
+    pub fn extent(&self) -> Vector3<f32> { self.max - self.min }
+
+    #[inline]
+    pub fn center(&self) -> Point3<f32> { self.min + self.extent() * 0.5 }
+
+    /// Distance along the ray at which it enters the box, if it does so within `(t_min, t_max)`,
+    /// given the reciprocal of the ray direction
+    pub fn intersect(&self, ray: &Ray, inv_direction: &Vector3<f32>, t_min: f32, t_max: f32) -> Option<f32> {
+        let mut near = t_min;
+        let mut far = t_max;
+
+        for i in 0..3 {
+            let t0 = (self.min[i] - ray.origin[i]) * inv_direction[i];
+            let t1 = (self.max[i] - ray.origin[i]) * inv_direction[i];
+
+            // `min` and `max` ignore the NaNs from rays parallel to a face
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+
+        if near <= far { Some(near) } else { None }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BvhNode {
+    bounds: Aabb,
+    /// First triangle of a leaf, or the index of the second child of an interior node,
+    /// with the first child directly following the node
+    first: u32,
+    /// Number of triangles in a leaf, or zero for interior nodes
+    count: u32,
+}
+
+/// Bounding volume hierarchy over the triangles of a mesh, for fast ray queries.
+///
+/// The hierarchy is built by splitting triangles at the median of their centroids along the longest axis,
+/// which is quick to build and good enough for most scenes.
+#[derive(Debug, Clone)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    /// Triangles in the order of the leaves
+    triangles: Vec<[Point3<f32>; 3]>,
+    /// Original index of each triangle in `triangles`
+    indices: Vec<usize>,
+}
+
+impl MemoryUsage for Bvh {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("nodes", &self.nodes);
+        report.add_vec("triangles", &self.triangles);
+        report.add_vec("indices", &self.indices);
+
+        report
+    }
+}
+
+impl Bvh {
+    /// Builds a hierarchy over the triangles of a mesh
+    pub fn from_mesh<V, I>(mesh: &Mesh<V, I>) -> Bvh where V: Vertex, I: MeshIndex {
+        let position = |index: &I| {
+            let p = mesh.vertices[index.to_usize()].position();
+
+            Point3::new(cast(p.x).unwrap(), cast(p.y).unwrap(), cast(p.z).unwrap())
+        };
+
+        Bvh::new(mesh.indices.chunks(3).filter(|triangle| triangle.len() == 3).map(|triangle| {
+            [position(&triangle[0]), position(&triangle[1]), position(&triangle[2])]
+        }).collect())
+    }
+
+    /// Builds a hierarchy over a list of triangles
+    pub fn new(triangles: Vec<[Point3<f32>; 3]>) -> Bvh {
+        let centroids: Vec<Point3<f32>> = triangles.iter().map(|t| {
+            Point3::from_coordinates((t[0].coords + t[1].coords + t[2].coords) / 3.0)
+        }).collect();
+
+        let mut order: Vec<usize> = (0..triangles.len()).collect();
+        let mut nodes = Vec::with_capacity(2 * triangles.len() / MAX_LEAF_TRIANGLES + 1);
+
+        if !triangles.is_empty() {
+            let len = order.len();
+
+            build_node(&mut nodes, &triangles, &centroids, &mut order, 0, len);
+        }
+
+        Bvh {
+            nodes,
+            triangles: order.iter().map(|&i| triangles[i]).collect(),
+            indices: order,
+        }
+    }
+
+    /// Number of triangles in the hierarchy
+    #[inline]
+    pub fn len(&self) -> usize { self.triangles.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.triangles.is_empty() }
+
+    /// Bounds of every triangle, or `None` if there are none
+    pub fn bounds(&self) -> Option<Aabb> {
+        self.nodes.first().map(|node| node.bounds)
+    }
+
+    /// Finds the nearest intersection with distance in `(t_min, t_max)`
+    pub fn closest_hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit> {
+        let mut closest = None;
+        let mut t_max = t_max;
+
+        self.traverse(ray, t_min, &mut t_max, |i, t, u, v| {
+            closest = Some((i, t, u, v));
+            false
+        });
+
+        closest.map(|(i, t, u, v)| {
+            let triangle = &self.triangles[i];
+
+            let normal = (triangle[1] - triangle[0]).cross(&(triangle[2] - triangle[0])).normalize();
+
+            Hit {
+                t, u, v,
+                triangle: self.indices[i],
+                normal: if normal.dot(&ray.direction) > 0.0 { -normal } else { normal },
+            }
+        })
+    }
+
+    /// Returns true if the ray hits anything with distance in `(t_min, t_max)`, stopping at the first intersection found.
+    ///
+    /// This is faster than `closest_hit` for shadow and occlusion rays.
+    pub fn any_hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> bool {
+        let mut hit = false;
+        let mut t_max = t_max;
+
+        self.traverse(ray, t_min, &mut t_max, |_, _, _, _| {
+            hit = true;
+            true
+        });
+
+        hit
+    }
+
+    /// Visits intersected triangles, shrinking `t_max` to each hit, until `on_hit` returns true
+    fn traverse<F>(&self, ray: &Ray, t_min: f32, t_max: &mut f32, mut on_hit: F) where F: FnMut(usize, f32, f32, f32) -> bool {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let inv_direction = Vector3::new(1.0 / ray.direction.x, 1.0 / ray.direction.y, 1.0 / ray.direction.z);
+
+        let mut stack = Vec::with_capacity(64);
+
+        stack.push(0);
+
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+
+            if node.bounds.intersect(ray, &inv_direction, t_min, *t_max).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                let first = node.first as usize;
+
+                for i in first..first + node.count as usize {
+                    if let Some((t, u, v)) = intersect_triangle(ray, &self.triangles[i], t_min, *t_max) {
+                        *t_max = t;
+
+                        if on_hit(i, t, u, v) {
+                            return;
+                        }
+                    }
+                }
+            } else {
+                stack.push(node.first as usize);
+                stack.push(index + 1);
+            }
+        }
+    }
+}
+
+/// Builds the node for `order[start..end]` and its children, returning its index
+fn build_node(nodes: &mut Vec<BvhNode>, triangles: &[[Point3<f32>; 3]], centroids: &[Point3<f32>],
+              order: &mut Vec<usize>, start: usize, end: usize) -> usize {
+    let index = nodes.len();
+
+    let mut bounds = Aabb::empty();
+    let mut centroid_bounds = Aabb::empty();
+
+    for &i in &order[start..end] {
+        for &vertex in &triangles[i] {
+            bounds.grow(vertex);
+        }
+
+        centroid_bounds.grow(centroids[i]);
+    }
+
+    nodes.push(BvhNode { bounds, first: start as u32, count: (end - start) as u32 });
+
+    let extent = centroid_bounds.extent();
+
+    let axis = if extent.x >= extent.y && extent.x >= extent.z { 0 } else if extent.y >= extent.z { 1 } else { 2 };
+
+    // Leaves for few triangles, or triangles that can't be told apart by their centroids
+    if end - start <= MAX_LEAF_TRIANGLES || !(extent[axis] > 0.0) {
+        return index;
+    }
+
+    order[start..end].sort_by(|&a, &b| {
+        centroids[a][axis].partial_cmp(&centroids[b][axis]).unwrap_or(::std::cmp::Ordering::Equal)
+    });
+
+    let mid = start + (end - start) / 2;
+
+    build_node(nodes, triangles, centroids, order, start, mid);
+
+    let second = build_node(nodes, triangles, centroids, order, mid, end);
+
+    nodes[index].first = second as u32;
+    nodes[index].count = 0;
+
+    index
+}
+
+#[cfg(test)]
+mod test {
+    use ::sampling::SampleRng;
+
+    use super::*;
+
+    #[test]
+    fn test_bvh_matches_brute_force() {
+        let mut rng = SampleRng::new(7);
+
+        let mut point = || Point3::new(rng.next_f32() * 10.0, rng.next_f32() * 10.0, rng.next_f32() * 10.0);
+
+        let triangles: Vec<[Point3<f32>; 3]> = (0..200).map(|_| {
+            let a = point();
+
+            [a, a + (point() - a) * 0.1, a + (point() - a) * 0.1]
+        }).collect();
+
+        let bvh = Bvh::new(triangles.clone());
+
+        for _ in 0..200 {
+            let ray = Ray::new(point(), point() - Point3::new(5.0, 5.0, 5.0));
+
+            let expected = triangles.iter().enumerate().filter_map(|(i, triangle)| {
+                intersect_triangle(&ray, triangle, 0.0, ::std::f32::INFINITY).map(|(t, _, _)| (i, t))
+            }).fold(None, |closest: Option<(usize, f32)>, (i, t)| match closest {
+                Some((_, best)) if best <= t => closest,
+                _ => Some((i, t)),
+            });
+
+            let hit = bvh.closest_hit(&ray, 0.0, ::std::f32::INFINITY);
+
+            assert_eq!(hit.map(|hit| hit.triangle), expected.map(|(i, _)| i));
+            assert_eq!(bvh.any_hit(&ray, 0.0, ::std::f32::INFINITY), expected.is_some());
+        }
+    }
+}
@@ -0,0 +1,127 @@
+//! Software ray tracing
+//!
+//! Rays are traced against a bounding volume hierarchy built from the triangles of a `Mesh`,
+//! the same meshes rasterized by the pipeline. That allows hybrid techniques, like ray-traced shadows
+//! or ambient occlusion computed from the positions and normals of a rasterized G-buffer,
+//! and rendering ground-truth images with the included path tracer to compare rasterized approximations against:
+//!
+//! ```ignore
+//! let bvh = Bvh::from_mesh(&scene);
+//!
+//! // Ray-traced shadows in a deferred lighting pass
+//! let lit = !bvh.any_hit(&Ray::new(position + normal * 1e-3, to_light), 0.0, light_distance);
+//!
+//! // Reference render into any framebuffer
+//! PathTracer::new(64).render(pipeline.threadpool_mut(), &mut reference, &inverse_view_projection, &bvh,
+//!     |_| Surface::diffuse([0.8, 0.8, 0.8]),
+//!     |_| [1.0, 1.0, 1.0]);
+//! ```
+//!
+//! Everything is computed in `f32`, converting mesh positions on construction.
+
+use nalgebra::{Point3, Vector3, Vector4, Matrix4};
+
+use ::geometry::Viewport;
+
+pub mod bvh;
+pub mod trace;
+pub mod picking;
+
+pub use self::bvh::{Aabb, Bvh};
+pub use self::trace::{Surface, PathTracer, ambient_occlusion};
+pub use self::picking::MeshBvh;
+
+/// A half-line from an origin in a direction
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    /// Direction of the ray, which does not need to be normalized.
+    ///
+    /// Distances along the ray are measured in multiples of this vector.
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    #[inline]
+    pub fn new(origin: Point3<f32>, direction: Vector3<f32>) -> Ray {
+        Ray { origin, direction }
+    }
+
+    /// Ray through a point in normalized device coordinates, from the near plane towards the far plane,
+    /// given the inverse of the view-projection matrix.
+    pub fn from_ndc(inverse_view_projection: &Matrix4<f32>, x: f32, y: f32) -> Ray {
+        let unproject = |z: f32| {
+            let p = inverse_view_projection * Vector4::new(x, y, z, 1.0);
+
+            Point3::new(p.x / p.w, p.y / p.w, p.z / p.w)
+        };
+
+        let near = unproject(-1.0);
+
+        Ray::new(near, unproject(1.0) - near)
+    }
+
+    /// Ray through a screen-space position, such as the mouse cursor when picking,
+    /// given the inverse of the view-projection matrix and the viewport the scene is rendered with.
+    pub fn from_screen(inverse_view_projection: &Matrix4<f32>, viewport: &Viewport<f32>, x: f32, y: f32) -> Ray {
+        let ndc = viewport.screen_to_ndc(x, y, viewport.near);
+
+        Ray::from_ndc(inverse_view_projection, ndc.x, ndc.y)
+    }
+
+    /// Point at distance `t` along the ray
+    #[inline]
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+}
+
+/// Intersection of a ray with a triangle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Hit {
+    /// Distance along the ray
+    pub t: f32,
+    /// Index of the triangle within the mesh, so the vertices are at `indices[triangle * 3..triangle * 3 + 3]`
+    pub triangle: usize,
+    /// Barycentric weight of the second vertex
+    pub u: f32,
+    /// Barycentric weight of the third vertex, with the first vertex weighted by `1 - u - v`
+    pub v: f32,
+    /// Normalized geometric normal of the triangle, facing against the ray
+    pub normal: Vector3<f32>,
+}
+
+/// Intersects a ray with a triangle from either side using the Möller–Trumbore algorithm,
+/// returning the distance and barycentric weights of the second and third vertices
+/// if the hit lies within `(t_min, t_max)`.
+pub fn intersect_triangle(ray: &Ray, triangle: &[Point3<f32>; 3], t_min: f32, t_max: f32) -> Option<(f32, f32, f32)> {
+    let edge1 = triangle[1] - triangle[0];
+    let edge2 = triangle[2] - triangle[0];
+
+    let p = ray.direction.cross(&edge2);
+    let det = edge1.dot(&p);
+
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+
+    let s = ray.origin - triangle[0];
+    let u = s.dot(&p) * inv_det;
+
+    if u < 0.0 || u > 1.0 {
+        return None;
+    }
+
+    let q = s.cross(&edge1);
+    let v = ray.direction.dot(&q) * inv_det;
+
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = edge2.dot(&q) * inv_det;
+
+    if t > t_min && t < t_max { Some((t, u, v)) } else { None }
+}
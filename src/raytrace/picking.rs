@@ -0,0 +1,140 @@
+//! Ray queries against meshes
+//!
+//! A `MeshBvh` pairs a mesh with its hierarchy, so hits can be turned back into vertex data
+//! for CPU picking, collision and gizmo hit-testing on the same meshes that are rendered:
+//!
+//! ```ignore
+//! let bvh = mesh.build_bvh();
+//!
+//! let ray = Ray::from_screen(&inverse_view_projection, &viewport, mouse_x, mouse_y);
+//!
+//! if let Some(hit) = bvh.ray_intersect(&ray) {
+//!     let uv = bvh.interpolate(&hit, |vertex| vertex.data.uv);
+//! }
+//! ```
+
+use nalgebra::Point3;
+
+use num_traits::cast;
+
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::interpolate::Interpolate;
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::{Ray, Hit, Bvh};
+
+/// Hierarchy over the triangles of a borrowed mesh
+#[derive(Debug, Clone)]
+pub struct MeshBvh<'a, V: 'a + Vertex, I: 'a + MeshIndex> {
+    mesh: &'a Mesh<V, I>,
+    bvh: Bvh,
+}
+
+impl<'a, V, I> MemoryUsage for MeshBvh<'a, V, I> where V: Vertex, I: MeshIndex {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.merge("bvh", self.bvh.memory_report());
+
+        report
+    }
+}
+
+impl<V, I> Mesh<V, I> where V: Vertex, I: MeshIndex {
+    /// Builds a bounding volume hierarchy over the triangles of the mesh for ray queries.
+    ///
+    /// The hierarchy has to be rebuilt if the mesh is changed.
+    pub fn build_bvh(&self) -> MeshBvh<V, I> {
+        MeshBvh { mesh: self, bvh: Bvh::from_mesh(self) }
+    }
+}
+
+impl<'a, V, I> MeshBvh<'a, V, I> where V: Vertex, I: MeshIndex {
+    /// Mesh the hierarchy was built from
+    #[inline]
+    pub fn mesh(&self) -> &'a Mesh<V, I> { self.mesh }
+
+    #[inline]
+    pub fn bvh(&self) -> &Bvh { &self.bvh }
+
+    /// Releases the borrow of the mesh, keeping only the hierarchy
+    #[inline]
+    pub fn into_bvh(self) -> Bvh { self.bvh }
+
+    /// Finds the nearest triangle in front of the ray origin
+    #[inline]
+    pub fn ray_intersect(&self, ray: &Ray) -> Option<Hit> {
+        self.bvh.closest_hit(ray, 0.0, ::std::f32::INFINITY)
+    }
+
+    /// Finds the nearest triangle with distance along the ray in `(t_min, t_max)`
+    #[inline]
+    pub fn ray_intersect_within(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<Hit> {
+        self.bvh.closest_hit(ray, t_min, t_max)
+    }
+
+    /// Vertices of the hit triangle
+    pub fn vertices(&self, hit: &Hit) -> [&'a V; 3] {
+        let indices = &self.mesh.indices[hit.triangle * 3..hit.triangle * 3 + 3];
+
+        let vertices = &self.mesh.vertices;
+
+        [&vertices[indices[0].to_usize()], &vertices[indices[1].to_usize()], &vertices[indices[2].to_usize()]]
+    }
+
+    /// Interpolates a vertex attribute across the hit triangle with the barycentric coordinates of the hit
+    pub fn interpolate<T, F>(&self, hit: &Hit, attribute: F) -> T where T: Interpolate, F: Fn(&V) -> T {
+        let vertices = self.vertices(hit);
+
+        T::barycentric_interpolate(1.0 - hit.u - hit.v, &attribute(vertices[0]),
+                                   hit.u, &attribute(vertices[1]),
+                                   hit.v, &attribute(vertices[2]))
+    }
+
+    /// Object-space position of the hit, interpolated from the mesh vertices
+    pub fn position(&self, hit: &Hit) -> Point3<V::Scalar> {
+        let vertices = self.vertices(hit);
+
+        let (u, v): (V::Scalar, V::Scalar) = (cast(hit.u).unwrap(), cast(hit.v).unwrap());
+        let w = V::Scalar::one() - u - v;
+
+        Point3::from_coordinates(vertices[0].position().coords * w +
+                                 vertices[1].position().coords * u +
+                                 vertices[2].position().coords * v)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector3;
+
+    use ::mesh::SimpleVertex;
+
+    use super::*;
+
+    #[test]
+    fn test_ray_intersect() {
+        let vertex = |x: f32, y: f32, value: f32| SimpleVertex { position: Point3::new(x, y, 0.0), data: value };
+
+        // Unit square in the xy plane, with data equal to x + y
+        let mesh: Mesh<SimpleVertex<f32, f32>, u16> = Mesh {
+            indices: vec![0, 1, 2, 1, 3, 2],
+            vertices: vec![vertex(0.0, 0.0, 0.0), vertex(1.0, 0.0, 1.0), vertex(0.0, 1.0, 1.0), vertex(1.0, 1.0, 2.0)],
+        };
+
+        let bvh = mesh.build_bvh();
+
+        let hit = bvh.ray_intersect(&Ray::new(Point3::new(0.75, 0.5, 1.0), -Vector3::z())).unwrap();
+
+        assert_eq!(hit.triangle, 1);
+        assert!((hit.t - 1.0).abs() < 1e-6);
+        assert!((hit.normal - Vector3::z()).norm() < 1e-6);
+
+        assert!((bvh.interpolate(&hit, |v| v.data) - 1.25).abs() < 1e-5);
+        assert!((bvh.position(&hit) - Point3::new(0.75, 0.5, 0.0)).norm() < 1e-5);
+
+        // Pointing away, or past the side of the square
+        assert!(bvh.ray_intersect(&Ray::new(Point3::new(0.5, 0.5, 1.0), Vector3::z())).is_none());
+        assert!(bvh.ray_intersect(&Ray::new(Point3::new(1.5, 0.5, 1.0), -Vector3::z())).is_none());
+    }
+}
@@ -0,0 +1,225 @@
+//! Path tracing and ray-traced effects
+
+use nalgebra::{Point3, Vector3, Matrix4};
+
+use scoped_threadpool::Pool;
+
+use ::color::FromChannels;
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::PixelWrite;
+use ::sampling::SampleRng;
+use ::parallel::map_chunks;
+
+use super::{Ray, Hit, Bvh};
+
+/// Offset along the normal for rays leaving a surface, so they don't hit the surface they start on
+const SURFACE_EPSILON: f32 = 1e-4;
+
+/// Material of a surface hit by the path tracer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Surface {
+    /// Fraction of incoming light reflected diffusely, for red, green and blue
+    pub albedo: [f32; 3],
+    /// Light emitted by the surface itself, for red, green and blue
+    pub emission: [f32; 3],
+}
+
+impl Surface {
+    /// Lambertian surface emitting no light
+    pub fn diffuse(albedo: [f32; 3]) -> Surface {
+        Surface { albedo, emission: [0.0; 3] }
+    }
+
+    /// Black surface emitting light
+    pub fn emissive(emission: [f32; 3]) -> Surface {
+        Surface { albedo: [0.0; 3], emission }
+    }
+}
+
+/// Random direction in the hemisphere around a normalized normal, distributed by the cosine to it
+fn cosine_direction(normal: &Vector3<f32>, rng: &mut SampleRng) -> Vector3<f32> {
+    let r = rng.next_f32().sqrt();
+    let phi = 2.0 * ::std::f32::consts::PI * rng.next_f32();
+
+    let tangent = if normal.x.abs() < 0.9 { normal.cross(&Vector3::x()) } else { normal.cross(&Vector3::y()) }.normalize();
+    let bitangent = normal.cross(&tangent);
+
+    tangent * (r * phi.cos()) + bitangent * (r * phi.sin()) + normal * (1.0 - r * r).max(0.0).sqrt()
+}
+
+/// Fraction of `samples` cosine-distributed rays from a surface that escape within `radius`,
+/// from `0.0` for fully occluded to `1.0` for fully open.
+///
+/// `normal` should be normalized, and point away from the surface.
+pub fn ambient_occlusion(bvh: &Bvh, position: Point3<f32>, normal: Vector3<f32>, samples: u32, radius: f32, rng: &mut SampleRng) -> f32 {
+    if samples == 0 {
+        return 1.0;
+    }
+
+    let origin = position + normal * SURFACE_EPSILON;
+
+    let open = (0..samples).filter(|_| {
+        !bvh.any_hit(&Ray::new(origin, cosine_direction(&normal, rng)), 0.0, radius)
+    }).count();
+
+    open as f32 / samples as f32
+}
+
+/// Simple unidirectional path tracer with Lambertian surfaces,
+/// meant for ground-truth reference images rather than speed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PathTracer {
+    /// Paths traced per pixel
+    pub samples: u32,
+    /// Most surface bounces of a single path
+    pub max_bounces: u32,
+    /// Seed for the random numbers of every pixel, so renders are reproducible
+    pub seed: u64,
+}
+
+impl PathTracer {
+    /// Creates a path tracer with `samples` paths per pixel and up to four bounces
+    pub fn new(samples: u32) -> PathTracer {
+        PathTracer { samples, max_bounces: 4, seed: 0 }
+    }
+
+    pub fn with_max_bounces(mut self, max_bounces: u32) -> PathTracer {
+        self.max_bounces = max_bounces;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> PathTracer {
+        self.seed = seed;
+        self
+    }
+
+    /// Radiance arriving along a ray, for red, green and blue
+    pub fn trace<S, E>(&self, ray: Ray, bvh: &Bvh, surface: &S, environment: &E, rng: &mut SampleRng) -> [f32; 3]
+        where S: Fn(&Hit) -> Surface, E: Fn(Vector3<f32>) -> [f32; 3] {
+        let mut radiance = [0.0; 3];
+        let mut throughput = [1.0; 3];
+
+        let mut ray = ray;
+
+        for bounce in 0..self.max_bounces + 1 {
+            let hit = match bvh.closest_hit(&ray, 0.0, ::std::f32::INFINITY) {
+                Some(hit) => hit,
+                None => {
+                    let light = environment(ray.direction.normalize());
+
+                    for i in 0..3 {
+                        radiance[i] += throughput[i] * light[i];
+                    }
+
+                    break;
+                }
+            };
+
+            let Surface { albedo, emission } = surface(&hit);
+
+            for i in 0..3 {
+                radiance[i] += throughput[i] * emission[i];
+                throughput[i] *= albedo[i];
+            }
+
+            if bounce == self.max_bounces || throughput.iter().all(|&t| t <= 0.0) {
+                break;
+            }
+
+            // Cosine-weighted sampling cancels out the cosine and the 1/pi of the Lambertian BRDF
+            ray = Ray::new(ray.at(hit.t) + hit.normal * SURFACE_EPSILON, cosine_direction(&hit.normal, rng));
+        }
+
+        radiance
+    }
+
+    /// Renders the scene in `bvh` into `target`, given the inverse of the view-projection matrix
+    /// the rasterized scene would be rendered with, so both line up pixel for pixel.
+    ///
+    /// `surface` gives the material of each hit, typically by looking up the mesh data of `hit.triangle`,
+    /// and `environment` gives the light arriving from a normalized direction that hits nothing.
+    ///
+    /// Pixels are traced in parallel on `pool`.
+    /// Use `Pipeline::threadpool_mut` to share the thread pool of a pipeline.
+    pub fn render<P, S, E>(&self, pool: &mut Pool, target: &mut P, inverse_view_projection: &Matrix4<f32>, bvh: &Bvh,
+                           surface: S, environment: E) where P: PixelWrite,
+                                                             P::Color: FromChannels,
+                                                             S: Fn(&Hit) -> Surface + Sync,
+                                                             E: Fn(Vector3<f32>) -> [f32; 3] + Sync {
+        let dimensions = target.dimensions();
+
+        let (width, height) = (dimensions.width, dimensions.height);
+
+        let rows: Vec<u32> = (0..height).collect();
+
+        let samples = self.samples.max(1);
+
+        let traced = map_chunks(pool, &rows, |&y| {
+            (0..width).map(|x| {
+                let mut rng = SampleRng::with_stream(self.seed, (y as u64) * (width as u64) + x as u64);
+
+                let mut sum = [0.0; 3];
+
+                for _ in 0..samples {
+                    let sx = (x as f32 + rng.next_f32()) / width as f32 * 2.0 - 1.0;
+                    let sy = 1.0 - (y as f32 + rng.next_f32()) / height as f32 * 2.0;
+
+                    let radiance = self.trace(Ray::from_ndc(inverse_view_projection, sx, sy), bvh, &surface, &environment, &mut rng);
+
+                    for i in 0..3 {
+                        sum[i] += radiance[i];
+                    }
+                }
+
+                let scale = 1.0 / samples as f32;
+
+                [sum[0] * scale, sum[1] * scale, sum[2] * scale, 1.0]
+            }).collect::<Vec<_>>()
+        });
+
+        target.prepare_write();
+
+        for (y, row) in traced.into_iter().enumerate() {
+            for (x, channels) in row.into_iter().enumerate() {
+                let index = target.index_of(Coordinate::new(x as u32, y as u32));
+
+                unsafe { target.set_pixel_unchecked(index, P::Color::from_channels(channels)); }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use nalgebra::Vector4;
+
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_path_tracer() {
+        let mut pool = Pool::new(2);
+
+        let mut target = ColorBuffer::<Vector4<f32>>::new(Dimensions::new(8, 8));
+
+        // An empty scene shows only the environment
+        PathTracer::new(1).render(&mut pool, &mut target, &Matrix4::identity(), &Bvh::new(Vec::new()),
+                                  |_| Surface::diffuse([1.0; 3]), |_| [0.25, 0.5, 1.0]);
+
+        assert!(target.as_slice().iter().all(|c| *c == Vector4::new(0.25, 0.5, 1.0, 1.0)));
+
+        // A point enclosed by an emitter receives exactly its emission
+        let bvh = Bvh::new(vec![[Point3::new(-1.0, -1.0, 1.0), Point3::new(1.0, -1.0, 1.0), Point3::new(0.0, 2.0, 1.0)]]);
+
+        let tracer = PathTracer::new(1);
+        let mut rng = SampleRng::new(1);
+
+        let radiance = tracer.trace(Ray::new(Point3::origin(), Vector3::z()), &bvh,
+                                    &|_: &Hit| Surface::emissive([2.0; 3]), &|_: Vector3<f32>| [0.0; 3], &mut rng);
+
+        assert_eq!(radiance, [2.0; 3]);
+        assert_eq!(ambient_occlusion(&bvh, Point3::origin(), -Vector3::z(), 16, 10.0, &mut rng), 1.0);
+    }
+}
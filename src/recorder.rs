@@ -0,0 +1,299 @@
+//! Animation recording
+//!
+//! A `Recorder` captures successive frames from any pixel buffer and encodes them as an animated GIF or APNG,
+//! or as a sequence of numbered PNG files, which is handy for documenting demos.
+//!
+//! Encoding, especially GIF quantization, can take longer than rendering a frame, so `Recorder::spawn`
+//! moves it to a background thread. Capturing a frame then only costs converting it to bytes.
+
+extern crate gif;
+extern crate png;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use ::color::ToChannels;
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelRead, ByteOrder, RowOrder, copy_to_bytes};
+
+/// Number of captured frames that may wait for a background recorder before capturing blocks
+const BACKGROUND_QUEUE: usize = 4;
+
+/// Errors that may occur while recording
+#[derive(Debug)]
+pub enum RecorderError {
+    Io(io::Error),
+    Gif(gif::EncodingError),
+    Png(png::EncodingError),
+    /// A captured buffer did not have the dimensions the recorder was created with
+    DimensionMismatch,
+    /// The dimensions are zero or too large for the format
+    InvalidDimensions,
+    /// An animation was finished without any frames
+    Empty,
+    /// The background thread stopped after an error, which is returned by `finish`
+    Disconnected,
+}
+
+impl Display for RecorderError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            RecorderError::Io(ref err) => write!(f, "Recorder IO Error: {}", err),
+            RecorderError::Gif(ref err) => write!(f, "Recorder GIF Error: {}", err),
+            RecorderError::Png(ref err) => write!(f, "Recorder PNG Error: {}", err),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for RecorderError {
+    fn description(&self) -> &str {
+        match *self {
+            RecorderError::Io(_) => "Recorder IO Error",
+            RecorderError::Gif(_) => "Recorder GIF Error",
+            RecorderError::Png(_) => "Recorder PNG Error",
+            RecorderError::DimensionMismatch => "Dimension Mismatch",
+            RecorderError::InvalidDimensions => "Invalid Dimensions",
+            RecorderError::Empty => "No Frames Recorded",
+            RecorderError::Disconnected => "Recorder Thread Stopped",
+        }
+    }
+}
+
+impl From<io::Error> for RecorderError {
+    fn from(err: io::Error) -> RecorderError { RecorderError::Io(err) }
+}
+
+impl From<gif::EncodingError> for RecorderError {
+    fn from(err: gif::EncodingError) -> RecorderError { RecorderError::Gif(err) }
+}
+
+impl From<png::EncodingError> for RecorderError {
+    fn from(err: png::EncodingError) -> RecorderError { RecorderError::Png(err) }
+}
+
+/// Output format of a `Recorder`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// Looping animated GIF, limited to 256 colors per frame and delays in hundredths of a second
+    Gif,
+    /// Looping animated PNG, with full color and alpha.
+    ///
+    /// The frame count is written before any frames, so frames are kept in memory until `finish`.
+    Apng,
+    /// Numbered PNG files in a directory, such as `frame_000000.png`, for assembling with other tools
+    PngSequence,
+}
+
+enum Sink {
+    Gif(gif::Encoder<BufWriter<File>>),
+    Apng(BufWriter<File>, Vec<Vec<u8>>),
+    PngSequence(PathBuf),
+}
+
+/// Records frames into an animation or image sequence
+pub struct Recorder {
+    dimensions: Dimensions,
+    fps: u32,
+    frames: usize,
+    sink: Sink,
+}
+
+impl Recorder {
+    /// Creates a recorder writing to `path`, which is a directory for `RecordFormat::PngSequence` and a file otherwise.
+    ///
+    /// Every captured buffer must have the given dimensions, and frames are played back at `fps` frames per second.
+    pub fn create<P: AsRef<Path>>(path: P, format: RecordFormat, dimensions: Dimensions, fps: u32) -> Result<Recorder, RecorderError> {
+        let Dimensions { width, height } = dimensions;
+
+        if width == 0 || height == 0 || fps == 0 || fps > u16::max_value() as u32 {
+            return Err(RecorderError::InvalidDimensions);
+        }
+
+        let path = path.as_ref();
+
+        let sink = match format {
+            RecordFormat::Gif => {
+                if width > u16::max_value() as u32 || height > u16::max_value() as u32 {
+                    return Err(RecorderError::InvalidDimensions);
+                }
+
+                let mut encoder = gif::Encoder::new(BufWriter::new(File::create(path)?), width as u16, height as u16, &[])?;
+
+                encoder.set_repeat(gif::Repeat::Infinite)?;
+
+                Sink::Gif(encoder)
+            }
+            RecordFormat::Apng => Sink::Apng(BufWriter::new(File::create(path)?), Vec::new()),
+            RecordFormat::PngSequence => {
+                fs::create_dir_all(path)?;
+
+                Sink::PngSequence(path.to_path_buf())
+            }
+        };
+
+        Ok(Recorder { dimensions, fps, frames: 0, sink })
+    }
+
+    /// Number of frames captured so far
+    #[inline]
+    pub fn frames(&self) -> usize { self.frames }
+
+    /// Captures the current contents of `buffer` as the next frame
+    pub fn capture<B>(&mut self, buffer: &B) -> Result<(), RecorderError> where B: PixelRead, B::Color: ToChannels {
+        let bytes = capture_bytes(buffer, self.dimensions)?;
+
+        self.encode(bytes)
+    }
+
+    /// Moves encoding to a background thread
+    pub fn spawn(self) -> BackgroundRecorder {
+        let dimensions = self.dimensions;
+
+        let (sender, receiver) = sync_channel::<Vec<u8>>(BACKGROUND_QUEUE);
+
+        let handle = thread::spawn(move || {
+            let mut recorder = self;
+
+            for bytes in receiver {
+                recorder.encode(bytes)?;
+            }
+
+            recorder.finish()
+        });
+
+        BackgroundRecorder { dimensions, sender: Some(sender), handle: Some(handle) }
+    }
+
+    /// Finishes writing the animation, returning the number of frames recorded
+    pub fn finish(self) -> Result<usize, RecorderError> {
+        let Recorder { dimensions, fps, frames, sink } = self;
+
+        match sink {
+            Sink::Gif(encoder) => {
+                // The trailer is written when the encoder is dropped
+                drop(encoder);
+            }
+            Sink::Apng(writer, pending) => {
+                if pending.is_empty() {
+                    return Err(RecorderError::Empty);
+                }
+
+                let mut encoder = png_encoder(writer, dimensions);
+
+                encoder.set_animated(pending.len() as u32, 0)?;
+                encoder.set_frame_delay(1, fps as u16)?;
+
+                let mut writer = encoder.write_header()?;
+
+                for bytes in &pending {
+                    writer.write_image_data(bytes)?;
+                }
+
+                writer.finish()?;
+            }
+            Sink::PngSequence(_) => {}
+        }
+
+        Ok(frames)
+    }
+
+    fn encode(&mut self, mut bytes: Vec<u8>) -> Result<(), RecorderError> {
+        let Dimensions { width, height } = self.dimensions;
+
+        match self.sink {
+            Sink::Gif(ref mut encoder) => {
+                let mut frame = gif::Frame::from_rgba_speed(width as u16, height as u16, &mut bytes, 10);
+
+                // GIF delays are in hundredths of a second
+                frame.delay = ((100 + self.fps / 2) / self.fps) as u16;
+
+                encoder.write_frame(&frame)?;
+            }
+            Sink::Apng(_, ref mut pending) => pending.push(bytes),
+            Sink::PngSequence(ref directory) => {
+                let file = File::create(directory.join(format!("frame_{:06}.png", self.frames)))?;
+
+                let mut writer = png_encoder(BufWriter::new(file), self.dimensions).write_header()?;
+
+                writer.write_image_data(&bytes)?;
+                writer.finish()?;
+            }
+        }
+
+        self.frames += 1;
+
+        Ok(())
+    }
+}
+
+/// A `Recorder` encoding frames on a background thread
+pub struct BackgroundRecorder {
+    dimensions: Dimensions,
+    sender: Option<SyncSender<Vec<u8>>>,
+    handle: Option<JoinHandle<Result<usize, RecorderError>>>,
+}
+
+impl BackgroundRecorder {
+    /// Captures the current contents of `buffer` as the next frame.
+    ///
+    /// Blocks if the background thread has fallen several frames behind.
+    pub fn capture<B>(&mut self, buffer: &B) -> Result<(), RecorderError> where B: PixelRead, B::Color: ToChannels {
+        let bytes = capture_bytes(buffer, self.dimensions)?;
+
+        match self.sender {
+            Some(ref sender) => sender.send(bytes).map_err(|_| RecorderError::Disconnected),
+            None => Err(RecorderError::Disconnected),
+        }
+    }
+
+    /// Waits for all captured frames to be encoded and finishes the animation,
+    /// returning the number of frames recorded or the error that stopped the background thread
+    pub fn finish(mut self) -> Result<usize, RecorderError> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<usize, RecorderError> {
+        // Closing the channel ends the loop on the background thread
+        drop(self.sender.take());
+
+        match self.handle.take() {
+            Some(handle) => handle.join().unwrap_or(Err(RecorderError::Disconnected)),
+            None => Err(RecorderError::Disconnected),
+        }
+    }
+}
+
+impl Drop for BackgroundRecorder {
+    fn drop(&mut self) {
+        if self.handle.is_some() {
+            let _ = self.join();
+        }
+    }
+}
+
+fn capture_bytes<B>(buffer: &B, dimensions: Dimensions) -> Result<Vec<u8>, RecorderError> where B: PixelRead, B::Color: ToChannels {
+    if buffer.dimensions() != dimensions {
+        return Err(RecorderError::DimensionMismatch);
+    }
+
+    let mut bytes = vec![0; dimensions.area() * 4];
+
+    copy_to_bytes(buffer, &mut bytes, None, ByteOrder::Rgba, RowOrder::TopDown);
+
+    Ok(bytes)
+}
+
+fn png_encoder<W: Write>(writer: W, dimensions: Dimensions) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(writer, dimensions.width, dimensions.height);
+
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    encoder
+}
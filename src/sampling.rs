@@ -0,0 +1,278 @@
+//! Deterministic sample patterns and jitter helpers
+//!
+//! Everything in here is fully deterministic for a given seed, so techniques built on top of it
+//! (multisampling, temporal antialiasing, ambient occlusion, soft shadows) produce identical images across runs.
+
+/// Small and fast pseudo-random number generator (PCG-XSH-RR 64/32).
+///
+/// This is not cryptographically secure in any way, but it's more than enough for picking sample positions,
+/// and unlike a thread-local or OS-seeded generator it always produces the same sequence for the same seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SampleRng {
+    state: u64,
+    increment: u64,
+}
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+
+impl SampleRng {
+    /// Create a new generator from the given seed
+    pub fn new(seed: u64) -> SampleRng {
+        SampleRng::with_stream(seed, 0)
+    }
+
+    /// Create a new generator from the given seed and stream.
+    ///
+    /// Generators with the same seed but different streams produce unrelated sequences,
+    /// which is useful for giving each frame or each effect its own sequence.
+    pub fn with_stream(seed: u64, stream: u64) -> SampleRng {
+        let mut rng = SampleRng {
+            state: 0,
+            increment: (stream << 1) | 1,
+        };
+
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+
+        rng
+    }
+
+    /// Returns the next random `u32`
+    pub fn next_u32(&mut self) -> u32 {
+        let old = self.state;
+
+        self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.increment);
+
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Returns the next random `f32` in the range `[0, 1)`
+    pub fn next_f32(&mut self) -> f32 {
+        // Only use the top 24 bits so every value is exactly representable
+        (self.next_u32() >> 8) as f32 * (1.0 / (1u32 << 24) as f32)
+    }
+}
+
+/// Computes the radical inverse of `index` in the given `base`, which is the basis of the Halton sequence.
+pub fn radical_inverse(mut index: u32, base: u32) -> f32 {
+    debug_assert!(base >= 2);
+
+    let inv_base = 1.0 / base as f64;
+
+    let mut inv = inv_base;
+    let mut result = 0.0;
+
+    while index > 0 {
+        result += (index % base) as f64 * inv;
+        index /= base;
+        inv *= inv_base;
+    }
+
+    result as f32
+}
+
+/// Returns the `index`-th point of the 2D Halton sequence (bases 2 and 3), in the range `[0, 1)`.
+pub fn halton(index: u32) -> (f32, f32) {
+    (radical_inverse(index, 2), radical_inverse(index, 3))
+}
+
+/// Returns the `index`-th point of a Hammersley set of `count` points, in the range `[0, 1)`.
+///
+/// Unlike the Halton sequence, the total number of points must be known ahead of time.
+pub fn hammersley(index: u32, count: u32) -> (f32, f32) {
+    debug_assert!(index < count);
+
+    (index as f32 / count as f32, radical_inverse(index, 2))
+}
+
+/// Generates the first `count` points of the 2D Halton sequence, skipping the degenerate first point at the origin.
+pub fn halton_sequence(count: usize) -> Vec<(f32, f32)> {
+    (1..count as u32 + 1).map(halton).collect()
+}
+
+/// Generates a full Hammersley set of `count` points.
+pub fn hammersley_sequence(count: usize) -> Vec<(f32, f32)> {
+    (0..count as u32).map(|i| hammersley(i, count as u32)).collect()
+}
+
+/// Generates `count` blue-noise distributed points in the range `[0, 1)` using Mitchell's best-candidate algorithm.
+///
+/// For each new point, several random candidates are generated and the one farthest away from all existing points
+/// is chosen, with distances measured on the unit torus so the pattern tiles seamlessly.
+///
+/// Point `i` tries `8 * i` candidates against all `i` existing points, which makes this `O(n^3)`,
+/// so it's meant for precomputing small sample kernels, not per-pixel use.
+pub fn blue_noise(count: usize, seed: u64) -> Vec<(f32, f32)> {
+    const CANDIDATES_PER_POINT: usize = 8;
+
+    let mut rng = SampleRng::new(seed);
+
+    let mut points: Vec<(f32, f32)> = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let mut best = (rng.next_f32(), rng.next_f32());
+        let mut best_distance = 0.0;
+
+        if i > 0 {
+            best_distance = nearest_toroidal_distance(&points, best);
+
+            for _ in 1..CANDIDATES_PER_POINT * i {
+                let candidate = (rng.next_f32(), rng.next_f32());
+                let distance = nearest_toroidal_distance(&points, candidate);
+
+                if distance > best_distance {
+                    best = candidate;
+                    best_distance = distance;
+                }
+            }
+        }
+
+        points.push(best);
+    }
+
+    points
+}
+
+fn nearest_toroidal_distance(points: &[(f32, f32)], p: (f32, f32)) -> f32 {
+    let mut nearest = ::std::f32::INFINITY;
+
+    for &(x, y) in points {
+        let dx = (x - p.0).abs();
+        let dy = (y - p.1).abs();
+
+        let dx = if dx > 0.5 { 1.0 - dx } else { dx };
+        let dy = if dy > 0.5 { 1.0 - dy } else { dy };
+
+        let d = dx * dx + dy * dy;
+
+        if d < nearest {
+            nearest = d;
+        }
+    }
+
+    nearest
+}
+
+/// Sample patterns available to `Jitter`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum SamplePattern {
+    /// Halton sequence with bases 2 and 3
+    Halton,
+    /// Hammersley set, which requires knowing the period ahead of time
+    Hammersley,
+    /// Seeded best-candidate blue noise
+    BlueNoise,
+    /// Seeded uniform random samples
+    Random,
+}
+
+/// Per-frame sub-pixel jitter, as used by temporal antialiasing and progressive multisampling.
+///
+/// The offsets repeat every `period` frames, and are centered on zero in the range `[-0.5, 0.5)` pixels.
+#[derive(Debug, Clone)]
+pub struct Jitter {
+    offsets: Vec<(f32, f32)>,
+}
+
+impl Jitter {
+    /// Create a new jitter sequence with the given pattern, period and seed.
+    ///
+    /// The seed is ignored for the Halton and Hammersley patterns, which are deterministic anyway.
+    pub fn new(pattern: SamplePattern, period: usize, seed: u64) -> Jitter {
+        assert!(period > 0, "Jitter period must be non-zero");
+
+        let points = match pattern {
+            SamplePattern::Halton => halton_sequence(period),
+            SamplePattern::Hammersley => hammersley_sequence(period),
+            SamplePattern::BlueNoise => blue_noise(period, seed),
+            SamplePattern::Random => {
+                let mut rng = SampleRng::new(seed);
+
+                (0..period).map(|_| (rng.next_f32(), rng.next_f32())).collect()
+            }
+        };
+
+        Jitter {
+            offsets: points.into_iter().map(|(x, y)| (x - 0.5, y - 0.5)).collect()
+        }
+    }
+
+    /// Number of frames before the offsets repeat
+    #[inline]
+    pub fn period(&self) -> usize { self.offsets.len() }
+
+    /// Sub-pixel offset for the given frame
+    #[inline]
+    pub fn offset(&self, frame: u64) -> (f32, f32) {
+        self.offsets[(frame % self.offsets.len() as u64) as usize]
+    }
+
+    /// Offset for the given frame converted to normalized device coordinates for a framebuffer of the given size,
+    /// which can be added to the projection matrix's third column to jitter a whole render.
+    pub fn ndc_offset(&self, frame: u64, width: u32, height: u32) -> (f32, f32) {
+        let (x, y) = self.offset(frame);
+
+        (2.0 * x / width as f32, 2.0 * y / height as f32)
+    }
+
+    /// All offsets in the sequence
+    #[inline]
+    pub fn offsets(&self) -> &[(f32, f32)] { &self.offsets }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rng_deterministic() {
+        let mut a = SampleRng::new(1234);
+        let mut b = SampleRng::new(1234);
+
+        for _ in 0..100 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+
+        let mut c = SampleRng::with_stream(1234, 1);
+
+        assert!((0..10).any(|_| a.next_u32() != c.next_u32()));
+    }
+
+    #[test]
+    fn test_halton() {
+        let expected = [(0.5, 1.0 / 3.0), (0.25, 2.0 / 3.0), (0.75, 1.0 / 9.0)];
+
+        for (i, &(x, y)) in expected.iter().enumerate() {
+            let (hx, hy) = halton(i as u32 + 1);
+
+            assert!((hx - x).abs() < 1e-6);
+            assert!((hy - y).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_blue_noise_deterministic() {
+        let a = blue_noise(16, 42);
+        let b = blue_noise(16, 42);
+
+        assert_eq!(a, b);
+
+        for &(x, y) in &a {
+            assert!(x >= 0.0 && x < 1.0);
+            assert!(y >= 0.0 && y < 1.0);
+        }
+    }
+
+    #[test]
+    fn test_jitter_period() {
+        let jitter = Jitter::new(SamplePattern::Halton, 8, 0);
+
+        assert_eq!(jitter.period(), 8);
+        assert_eq!(jitter.offset(3), jitter.offset(11));
+    }
+}
@@ -0,0 +1,409 @@
+//! Scripted shaders using [rhai](https://github.com/rhaiscript/rhai)
+//!
+//! Requires the `script_compat` cargo feature.
+//!
+//! Scripted shaders are compiled once and then evaluated for every vertex or fragment,
+//! which is many times slower than native closures, but allows editing shaders while the application is running.
+//!
+//! Vertex scripts have access to:
+//!
+//! * `position`: the object-space vertex position as an array of three floats
+//! * `vertex`: a map of any extra vertex data
+//! * `uniforms`: a map of global uniforms
+//!
+//! and must evaluate to a map with a `position` field containing the clip-space position as an array of four floats.
+//! Any other float or array-of-float fields are passed on to the fragment script as interpolated varyings.
+//!
+//! Fragment scripts have access to:
+//!
+//! * `position`: the screen-space fragment position as an array of four floats
+//! * `varyings`: a map of interpolated varyings from the vertex script
+//! * `uniforms`: a map of global uniforms
+//!
+//! and must evaluate to an array of four floats for the RGBA color, or `()` to discard the fragment.
+//!
+//! ```text
+//! // vertex script
+//! let p = uniforms.mvp * [position[0], position[1], position[2], 1.0];
+//! #{ position: p, shade: vertex.ao }
+//!
+//! // fragment script
+//! if varyings.shade < 0.01 { () } else { [varyings.shade, varyings.shade, varyings.shade, 1.0] }
+//! ```
+//!
+//! Matrix math is up to the application, by registering functions on an engine from `ScriptShader::engine`
+//! and compiling with `ScriptShader::compile_with_engine`.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use num_traits::{Float, NumCast, cast};
+
+use nalgebra::{Point3, Vector4};
+
+use parking_lot::Mutex;
+
+use rhai::{Engine, AST, Scope, Dynamic, Array, Map, FLOAT};
+
+use ::numeric::FloatScalar;
+use ::interpolate::Interpolate;
+use ::geometry::{ClipVertex, ScreenVertex};
+use ::pipeline::stages::fragment::Fragment;
+
+/// Dynamic map of named values passed into scripts
+pub type ScriptMap = Map;
+
+/// Errors that may occur while compiling or running scripted shaders
+#[derive(Debug, Clone)]
+pub enum ScriptError {
+    /// The script failed to compile
+    Parse(String),
+    /// The script failed while running
+    Eval(String),
+    /// The script returned a value of the wrong shape
+    InvalidOutput(&'static str),
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            ScriptError::Parse(ref err) => write!(f, "Script Parse Error: {}", err),
+            ScriptError::Eval(ref err) => write!(f, "Script Evaluation Error: {}", err),
+            ScriptError::InvalidOutput(err) => write!(f, "Invalid Script Output: {}", err),
+        }
+    }
+}
+
+impl Error for ScriptError {
+    fn description(&self) -> &str {
+        match *self {
+            ScriptError::Parse(_) => "Script Parse Error",
+            ScriptError::Eval(_) => "Script Evaluation Error",
+            ScriptError::InvalidOutput(_) => "Invalid Script Output",
+        }
+    }
+}
+
+/// Interpolated values passed from a vertex script to a fragment script.
+///
+/// Every value is stored as a list of floats, so scalars are lists of length one.
+/// Both sides of an interpolation are expected to come from the same script, and therefore have the same layout.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptVaryings {
+    values: BTreeMap<String, Vec<FLOAT>>,
+}
+
+impl ScriptVaryings {
+    /// Returns the varying with the given name
+    pub fn get(&self, name: &str) -> Option<&[FLOAT]> {
+        self.values.get(name).map(|value| &value[..])
+    }
+
+    /// Converts the varyings into a script map, where lists of length one become plain floats
+    pub fn to_map(&self) -> ScriptMap {
+        let mut map = ScriptMap::new();
+
+        for (name, value) in &self.values {
+            let value = if value.len() == 1 {
+                Dynamic::from(value[0])
+            } else {
+                Dynamic::from(value.iter().map(|&v| Dynamic::from(v)).collect::<Array>())
+            };
+
+            map.insert(name.as_str().into(), value);
+        }
+
+        map
+    }
+
+    fn combine<F>(&self, others: &[&ScriptVaryings], f: F) -> ScriptVaryings where F: Fn(&[FLOAT]) -> FLOAT {
+        let mut values = BTreeMap::new();
+
+        for (name, value) in &self.values {
+            let combined = (0..value.len()).map(|i| {
+                let mut components = vec![value[i]];
+
+                for other in others {
+                    components.push(other.values.get(name).and_then(|v| v.get(i).cloned()).unwrap_or(0.0));
+                }
+
+                f(&components)
+            }).collect();
+
+            values.insert(name.clone(), combined);
+        }
+
+        ScriptVaryings { values }
+    }
+}
+
+impl Interpolate for ScriptVaryings {
+    fn barycentric_interpolate<R: Float>(u: R, ux: &Self, v: R, vx: &Self, w: R, wx: &Self) -> Self {
+        let (u, v, w): (FLOAT, FLOAT, FLOAT) = (cast(u).unwrap(), cast(v).unwrap(), cast(w).unwrap());
+
+        ux.combine(&[vx, wx], |c| c[0] * u + c[1] * v + c[2] * w)
+    }
+
+    fn linear_interpolate<R: Float>(t: R, x1: &Self, x2: &Self) -> Self {
+        let t: FLOAT = cast(t).unwrap();
+
+        x1.combine(&[x2], |c| c[0] * (1.0 - t) + c[1] * t)
+    }
+}
+
+fn float_list(value: Dynamic) -> Option<Vec<FLOAT>> {
+    if let Ok(f) = value.as_float() {
+        return Some(vec![f]);
+    }
+
+    if let Ok(i) = value.as_int() {
+        return Some(vec![i as FLOAT]);
+    }
+
+    value.try_cast::<Array>().and_then(|array| {
+        array.into_iter().map(|v| {
+            v.as_float().ok().or_else(|| v.as_int().ok().map(|i| i as FLOAT))
+        }).collect()
+    })
+}
+
+fn vector4<N: FloatScalar>(value: Dynamic) -> Option<Vector4<N>> {
+    float_list(value).and_then(|v| {
+        if v.len() == 4 {
+            Some(Vector4::new(cast(v[0]).unwrap(), cast(v[1]).unwrap(), cast(v[2]).unwrap(), cast(v[3]).unwrap()))
+        } else {
+            None
+        }
+    })
+}
+
+fn dynamic_array<I, N>(values: I) -> Dynamic where I: IntoIterator<Item=N>, N: NumCast {
+    Dynamic::from(values.into_iter().map(|v| Dynamic::from(cast::<N, FLOAT>(v).unwrap())).collect::<Array>())
+}
+
+/// A compiled shader script.
+///
+/// Because scripts are usually being edited when used, the `vertex` and `fragment` convenience methods
+/// don't return errors directly. Instead, failing vertices are collapsed and failing fragments are discarded,
+/// and the first error is stored to be retrieved with `take_error`.
+pub struct ScriptShader {
+    engine: Engine,
+    ast: AST,
+    error: Mutex<Option<ScriptError>>,
+}
+
+impl ScriptShader {
+    /// Create a new script engine to register custom functions on before compiling
+    pub fn engine() -> Engine {
+        Engine::new()
+    }
+
+    /// Compiles the script source with a default engine
+    pub fn compile(source: &str) -> Result<ScriptShader, ScriptError> {
+        ScriptShader::compile_with_engine(Engine::new(), source)
+    }
+
+    /// Compiles the script source with the given engine, which may have custom functions registered on it
+    pub fn compile_with_engine(engine: Engine, source: &str) -> Result<ScriptShader, ScriptError> {
+        let ast = engine.compile(source).map_err(|err| ScriptError::Parse(err.to_string()))?;
+
+        Ok(ScriptShader { engine, ast, error: Mutex::new(None) })
+    }
+
+    /// Recompiles the script in place, keeping the engine and any registered functions
+    pub fn recompile(&mut self, source: &str) -> Result<(), ScriptError> {
+        self.ast = self.engine.compile(source).map_err(|err| ScriptError::Parse(err.to_string()))?;
+        *self.error.lock() = None;
+
+        Ok(())
+    }
+
+    /// Takes the first error that occurred in `vertex` or `fragment`, if any
+    pub fn take_error(&self) -> Option<ScriptError> {
+        self.error.lock().take()
+    }
+
+    fn record_error(&self, err: ScriptError) {
+        let mut error = self.error.lock();
+
+        if error.is_none() {
+            *error = Some(err);
+        }
+    }
+
+    fn eval(&self, scope: &mut Scope) -> Result<Dynamic, ScriptError> {
+        self.engine.eval_ast_with_scope::<Dynamic>(scope, &self.ast).map_err(|err| ScriptError::Eval(err.to_string()))
+    }
+
+    /// Runs the script as a vertex shader
+    pub fn eval_vertex<N: FloatScalar>(&self, position: Point3<N>, vertex: &ScriptMap, uniforms: &ScriptMap) -> Result<ClipVertex<N, ScriptVaryings>, ScriptError> {
+        let mut scope = Scope::new();
+
+        scope.push("position", dynamic_array(vec![position.x, position.y, position.z]));
+        scope.push("vertex", vertex.clone());
+        scope.push("uniforms", uniforms.clone());
+
+        let output = self.eval(&mut scope)?.try_cast::<Map>().ok_or(ScriptError::InvalidOutput("Vertex scripts must return a map"))?;
+
+        let mut varyings = ScriptVaryings::default();
+        let mut clip_position = None;
+
+        for (name, value) in output {
+            if name.as_str() == "position" {
+                clip_position = vector4(value);
+            } else if let Some(list) = float_list(value) {
+                varyings.values.insert(name.to_string(), list);
+            }
+        }
+
+        match clip_position {
+            Some(clip_position) => Ok(ClipVertex { position: clip_position, uniforms: varyings }),
+            None => Err(ScriptError::InvalidOutput("Vertex scripts must return a position of four floats")),
+        }
+    }
+
+    /// Runs the script as a fragment shader, returning `None` for discarded fragments
+    pub fn eval_fragment<N: FloatScalar>(&self, vertex: &ScreenVertex<N, ScriptVaryings>, uniforms: &ScriptMap) -> Result<Option<[f32; 4]>, ScriptError> {
+        let mut scope = Scope::new();
+
+        scope.push("position", dynamic_array(vec![vertex.position.x, vertex.position.y, vertex.position.z, vertex.position.w]));
+        scope.push("varyings", vertex.uniforms.to_map());
+        scope.push("uniforms", uniforms.clone());
+
+        let output = self.eval(&mut scope)?;
+
+        if output.is_unit() {
+            return Ok(None);
+        }
+
+        match vector4::<f32>(output) {
+            Some(color) => Ok(Some([color.x, color.y, color.z, color.w])),
+            None => Err(ScriptError::InvalidOutput("Fragment scripts must return four floats or ()")),
+        }
+    }
+
+    /// Vertex shader convenience method.
+    ///
+    /// On errors, the vertex is collapsed to the origin with `w` of zero, so the primitive is clipped away.
+    pub fn vertex<N: FloatScalar>(&self, position: Point3<N>, vertex: &ScriptMap, uniforms: &ScriptMap) -> ClipVertex<N, ScriptVaryings> {
+        match self.eval_vertex(position, vertex, uniforms) {
+            Ok(vertex) => vertex,
+            Err(err) => {
+                self.record_error(err);
+
+                ClipVertex {
+                    position: Vector4::new(N::zero(), N::zero(), N::zero(), N::zero()),
+                    uniforms: ScriptVaryings::default(),
+                }
+            }
+        }
+    }
+
+    /// Fragment shader convenience method, converting the RGBA result to the framebuffer color with `to_color`.
+    ///
+    /// On errors, the fragment is discarded.
+    pub fn fragment<N: FloatScalar, C, F>(&self, vertex: &ScreenVertex<N, ScriptVaryings>, uniforms: &ScriptMap, to_color: F) -> Fragment<C>
+        where C: ::color::Color, F: FnOnce([f32; 4]) -> C {
+        match self.eval_fragment(vertex, uniforms) {
+            Ok(Some(color)) => Fragment::Color(to_color(color)),
+            Ok(None) => Fragment::Discard,
+            Err(err) => {
+                self.record_error(err);
+
+                Fragment::Discard
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use ::color::predefined::formats::RGBAf32Color;
+
+    fn map(entries: &[(&str, FLOAT)]) -> ScriptMap {
+        entries.iter().map(|&(name, value)| (name.into(), Dynamic::from(value))).collect()
+    }
+
+    fn varyings(entries: &[(&str, &[FLOAT])]) -> ScriptVaryings {
+        ScriptVaryings { values: entries.iter().map(|&(name, value)| (name.to_string(), value.to_vec())).collect() }
+    }
+
+    fn to_color(c: [f32; 4]) -> RGBAf32Color {
+        RGBAf32Color::new(c[0], c[1], c[2], c[3])
+    }
+
+    #[test]
+    fn test_vertex_and_fragment() {
+        let vertex_script = ScriptShader::compile("#{ position: [position[0], position[1], position[2], uniforms.w], shade: vertex.ao }").unwrap();
+
+        let clip = vertex_script.vertex(Point3::new(1.0f32, 2.0, 3.0), &map(&[("ao", 0.5)]), &map(&[("w", 2.0)]));
+
+        assert_eq!(clip.position, Vector4::new(1.0, 2.0, 3.0, 2.0));
+        assert_eq!(clip.uniforms.get("shade"), Some(&[0.5][..]));
+
+        let fragment_script = ScriptShader::compile("[varyings.shade, position[0], 0, 1.0]").unwrap();
+
+        let screen = ScreenVertex { position: Vector4::new(0.25f32, 0.0, 0.0, 1.0), uniforms: clip.uniforms };
+
+        match fragment_script.fragment(&screen, &ScriptMap::new(), to_color) {
+            Fragment::Color(color) => assert_eq!(color, RGBAf32Color::new(0.5, 0.25, 0.0, 1.0)),
+            _ => panic!("Expected a color"),
+        }
+
+        assert!(vertex_script.take_error().is_none());
+        assert!(fragment_script.take_error().is_none());
+    }
+
+    #[test]
+    fn test_discard() {
+        let script = ScriptShader::compile("if varyings.shade < 0.01 { () } else { [1.0, 1.0, 1.0, 1.0] }").unwrap();
+
+        let screen = ScreenVertex { position: Vector4::new(0.0f32, 0.0, 0.0, 1.0), uniforms: varyings(&[("shade", &[0.0])]) };
+
+        assert_eq!(script.eval_fragment(&screen, &ScriptMap::new()).unwrap(), None);
+
+        match script.fragment(&screen, &ScriptMap::new(), to_color) {
+            Fragment::Discard => (),
+            _ => panic!("Expected a discarded fragment"),
+        }
+
+        // Discarding isn't an error
+        assert!(script.take_error().is_none());
+    }
+
+    #[test]
+    fn test_error_recorded_once() {
+        let script = ScriptShader::compile("#{ position: missing }").unwrap();
+
+        for _ in 0..3 {
+            let clip = script.vertex(Point3::new(0.0f32, 0.0, 0.0), &ScriptMap::new(), &ScriptMap::new());
+
+            // Failing vertices are collapsed so their primitives are clipped away
+            assert_eq!(clip.position, Vector4::new(0.0, 0.0, 0.0, 0.0));
+        }
+
+        match script.take_error() {
+            Some(ScriptError::Eval(_)) => (),
+            e => panic!("Unexpected error {:?}", e),
+        }
+
+        assert!(script.take_error().is_none());
+    }
+
+    #[test]
+    fn test_varyings_missing_key() {
+        let a = varyings(&[("shade", &[1.0]), ("uv", &[1.0, 2.0])]);
+        let b = varyings(&[("shade", &[3.0])]);
+
+        // Keys missing from the other side count as zero
+        assert_eq!(ScriptVaryings::linear_interpolate(0.5, &a, &b), varyings(&[("shade", &[2.0]), ("uv", &[0.5, 1.0])]));
+
+        // Keys only on the other side are dropped
+        assert_eq!(ScriptVaryings::linear_interpolate(0.5, &b, &a), varyings(&[("shade", &[2.0])]));
+
+        assert_eq!(ScriptVaryings::barycentric_interpolate(0.5, &a, 0.25, &b, 0.25, &b), varyings(&[("shade", &[2.0]), ("uv", &[0.5, 1.0])]));
+    }
+}
@@ -0,0 +1,442 @@
+//! Signed distance fields
+//!
+//! `DistanceField` bakes a 2D signed distance field from a binary coverage buffer, such as a glyph rendered
+//! at high resolution, which can be stored at a much lower resolution and still give sharp edges
+//! when thresholded at `0.5` in a fragment shader, as in SDF text rendering.
+//!
+//! `DistanceVolume` bakes a 3D signed distance field from a closed mesh into a `Texture3D`,
+//! for raymarched soft shadows and ambient occlusion.
+//!
+//! Distances are negative inside and positive outside.
+
+use nalgebra::{Point3, Vector3};
+
+use num_traits::cast;
+
+use scoped_threadpool::Pool;
+
+use ::color::{Color, ToChannels, FromChannels};
+use ::color::predefined::formats::Rf32Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelRead, ColorBuffer};
+use ::texture::{Texture3D, Filter};
+use ::mesh::{Vertex, Mesh, MeshIndex};
+use ::raytrace::{Ray, Bvh};
+use ::memory::{MemoryReport, MemoryUsage};
+use ::parallel::map_chunks;
+
+/// Algorithm used to find the nearest edge pixel of every pixel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum DistanceTransform {
+    /// Propagates the nearest edge pixels in `log2(size)` passes with halving step sizes.
+    ///
+    /// Fast, but distances can be very slightly off for a few pixels.
+    JumpFlood,
+    /// Compares every pixel against every edge pixel, giving exact distances very slowly
+    BruteForce,
+}
+
+impl Default for DistanceTransform {
+    fn default() -> DistanceTransform { DistanceTransform::JumpFlood }
+}
+
+/// Nearest seed of every pixel, or `None` if there are no seeds
+fn nearest_seeds(dimensions: Dimensions, seeds: &[bool], method: DistanceTransform) -> Vec<Option<(u32, u32)>> {
+    let (width, height) = (dimensions.width as i64, dimensions.height as i64);
+
+    let distance = |(x, y): (i64, i64), (sx, sy): (u32, u32)| {
+        let (dx, dy) = (sx as i64 - x, sy as i64 - y);
+
+        dx * dx + dy * dy
+    };
+
+    let mut nearest: Vec<Option<(u32, u32)>> = (0..seeds.len()).map(|i| {
+        if seeds[i] { Some(((i as i64 % width) as u32, (i as i64 / width) as u32)) } else { None }
+    }).collect();
+
+    match method {
+        DistanceTransform::BruteForce => {
+            let positions: Vec<(u32, u32)> = nearest.iter().filter_map(|&seed| seed).collect();
+
+            for (i, pixel) in nearest.iter_mut().enumerate() {
+                let position = (i as i64 % width, i as i64 / width);
+
+                *pixel = positions.iter().cloned().min_by_key(|&seed| distance(position, seed));
+            }
+        }
+        DistanceTransform::JumpFlood => {
+            let mut step = (width.max(height) as u64).next_power_of_two() as i64 / 2;
+
+            let mut steps = Vec::new();
+
+            while step >= 1 {
+                steps.push(step);
+                step /= 2;
+            }
+
+            // An extra pass with a step of one fixes most of the remaining errors
+            steps.push(1);
+
+            for step in steps {
+                let previous = nearest.clone();
+
+                for y in 0..height {
+                    for x in 0..width {
+                        let mut best = previous[(y * width + x) as usize];
+
+                        for &(dx, dy) in &[(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                            let (nx, ny) = (x + dx * step, y + dy * step);
+
+                            if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                                continue;
+                            }
+
+                            if let Some(seed) = previous[(ny * width + nx) as usize] {
+                                if best.map_or(true, |best| distance((x, y), seed) < distance((x, y), best)) {
+                                    best = Some(seed);
+                                }
+                            }
+                        }
+
+                        nearest[(y * width + x) as usize] = best;
+                    }
+                }
+            }
+        }
+    }
+
+    nearest
+}
+
+/// 2D signed distance field, in pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct DistanceField {
+    dimensions: Dimensions,
+    distances: Vec<f32>,
+}
+
+impl HasDimensions for DistanceField {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl MemoryUsage for DistanceField {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("distances", &self.distances);
+
+        report
+    }
+}
+
+impl DistanceField {
+    /// Bakes the distance field of the pixels for which `inside` returns true.
+    ///
+    /// The edge lies halfway between inside and outside pixels, so their distances are at least half a pixel.
+    /// Buffers entirely inside or outside have infinite distances.
+    pub fn from_coverage<P, F>(buffer: &P, method: DistanceTransform, inside: F) -> DistanceField where P: PixelRead,
+                                                                                                      F: Fn(P::Color) -> bool {
+        let dimensions = buffer.dimensions();
+
+        let mut coverage = Vec::with_capacity(dimensions.area());
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                coverage.push(inside(unsafe { buffer.get_pixel_unchecked(buffer.index_of(Coordinate::new(x, y))) }));
+            }
+        }
+
+        let outside: Vec<bool> = coverage.iter().map(|&inside| !inside).collect();
+
+        let nearest_inside = nearest_seeds(dimensions, &coverage, method);
+        let nearest_outside = nearest_seeds(dimensions, &outside, method);
+
+        let width = dimensions.width as usize;
+
+        let distances = (0..coverage.len()).map(|i| {
+            let (x, y) = ((i % width) as f32, (i / width) as f32);
+
+            let distance = |seed: Option<(u32, u32)>| match seed {
+                Some((sx, sy)) => ((sx as f32 - x).powi(2) + (sy as f32 - y).powi(2)).sqrt() - 0.5,
+                None => ::std::f32::INFINITY,
+            };
+
+            if coverage[i] { -distance(nearest_outside[i]) } else { distance(nearest_inside[i]) }
+        }).collect();
+
+        DistanceField { dimensions, distances }
+    }
+
+    /// Bakes the distance field of the pixels with an alpha of at least `threshold`
+    pub fn from_alpha<P>(buffer: &P, threshold: f32, method: DistanceTransform) -> DistanceField where P: PixelRead,
+                                                                                                       P::Color: ToChannels {
+        DistanceField::from_coverage(buffer, method, |color| color.to_channels()[3] >= threshold)
+    }
+
+    /// Signed distance at a pixel, or `None` if out of bounds
+    pub fn get(&self, coord: Coordinate) -> Option<f32> {
+        if self.dimensions.in_bounds(coord) {
+            Some(self.distances[coord.into_index(self.dimensions)])
+        } else {
+            None
+        }
+    }
+
+    /// Distances of every pixel, row by row
+    #[inline]
+    pub fn as_slice(&self) -> &[f32] { &self.distances }
+
+    /// Encodes the distances into every channel of a color buffer, mapping the edge to `0.5`,
+    /// `spread` pixels inside to `1.0` and `spread` pixels outside to `0.0`.
+    ///
+    /// Downscale the result to the size stored in the atlas, and threshold at `0.5` when rendering.
+    pub fn to_buffer<C>(&self, spread: f32) -> ColorBuffer<C> where C: Color + FromChannels {
+        let pixels = self.distances.iter().map(|&distance| {
+            let value = (0.5 - distance / (2.0 * spread)).max(0.0).min(1.0);
+
+            C::from_channels([value; 4])
+        }).collect();
+
+        ColorBuffer::from_vec(self.dimensions, pixels)
+    }
+}
+
+/// Closest point to `p` on the triangle `abc`, from Ericson's Real-Time Collision Detection
+fn closest_point_on_triangle(p: Point3<f32>, a: Point3<f32>, b: Point3<f32>, c: Point3<f32>) -> Point3<f32> {
+    let (ab, ac, ap) = (b - a, c - a, p - a);
+
+    let (d1, d2) = (ab.dot(&ap), ac.dot(&ap));
+
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = p - b;
+    let (d3, d4) = (ab.dot(&bp), ac.dot(&bp));
+
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + ab * (d1 / (d1 - d3));
+    }
+
+    let cp = p - c;
+    let (d5, d6) = (ab.dot(&cp), ac.dot(&cp));
+
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + ac * (d2 / (d2 - d6));
+    }
+
+    let va = d3 * d6 - d5 * d4;
+
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (c - b) * ((d4 - d3) / ((d4 - d3) + (d5 - d6)));
+    }
+
+    let sum = va + vb + vc;
+
+    if sum == 0.0 {
+        return a;
+    }
+
+    a + ab * (vb / sum) + ac * (vc / sum)
+}
+
+/// 3D signed distance field of a mesh, stored in a `Texture3D` with world-space distances at voxel centers
+#[derive(Debug, Clone)]
+pub struct DistanceVolume {
+    origin: Point3<f32>,
+    voxel_size: f32,
+    texture: Texture3D<Rf32Color>,
+}
+
+impl MemoryUsage for DistanceVolume {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.merge("texture", self.texture.memory_report());
+
+        report
+    }
+}
+
+impl DistanceVolume {
+    /// Bakes the distance field of a mesh into a volume fitted around it, with `resolution` voxels along its longest side
+    /// and `padding` extra voxels on every side, so distances outside the mesh are captured as well.
+    ///
+    /// Every voxel is compared against every triangle, split across `pool`, so this is meant for baking small meshes offline.
+    /// Use `Pipeline::threadpool_mut` to share the thread pool of a pipeline.
+    ///
+    /// Voxels are inside if a ray from them crosses the mesh an odd number of times,
+    /// so the mesh should be closed for the sign to be meaningful.
+    pub fn from_mesh<V, I>(pool: &mut Pool, mesh: &Mesh<V, I>, resolution: u32, padding: u32) -> DistanceVolume where V: Vertex,
+                                                                                                                     I: MeshIndex {
+        let positions: Vec<Point3<f32>> = mesh.vertices.iter().map(|vertex| {
+            let p = vertex.position();
+
+            Point3::new(cast(p.x).unwrap(), cast(p.y).unwrap(), cast(p.z).unwrap())
+        }).collect();
+
+        let triangles: Vec<[Point3<f32>; 3]> = mesh.indices.chunks(3).filter(|triangle| triangle.len() == 3).map(|triangle| {
+            [positions[triangle[0].to_usize()], positions[triangle[1].to_usize()], positions[triangle[2].to_usize()]]
+        }).collect();
+
+        let bvh = Bvh::new(triangles.clone());
+
+        let (min, max) = match bvh.bounds() {
+            Some(bounds) => (bounds.min, bounds.max),
+            None => (Point3::origin(), Point3::origin()),
+        };
+
+        let extent = max - min;
+        let longest = extent.x.max(extent.y).max(extent.z);
+
+        let resolution = resolution.max(1);
+
+        let voxel_size = if longest > 0.0 { longest / resolution as f32 } else { 1.0 };
+
+        let count = |length: f32| ((length / voxel_size).ceil() as u32).max(1).min(resolution) + 2 * padding;
+
+        let (width, height, depth) = (count(extent.x), count(extent.y), count(extent.z));
+
+        let origin = min - Vector3::new(1.0, 1.0, 1.0) * (padding as f32 * voxel_size);
+
+        let slices: Vec<u32> = (0..depth).collect();
+
+        // Slightly off-axis, so parity rays don't pass exactly through edges of axis-aligned meshes
+        let direction = Vector3::new(1.0, 0.0013, 0.0007);
+
+        let distances = map_chunks(pool, &slices, |&z| {
+            let mut slice = Vec::with_capacity(width as usize * height as usize);
+
+            for y in 0..height {
+                for x in 0..width {
+                    let center = origin + Vector3::new(x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5) * voxel_size;
+
+                    let distance = triangles.iter().map(|t| {
+                        (closest_point_on_triangle(center, t[0], t[1], t[2]) - center).norm()
+                    }).fold(::std::f32::INFINITY, f32::min);
+
+                    let mut crossings = 0;
+                    let mut t_min = 0.0;
+
+                    while let Some(hit) = bvh.closest_hit(&Ray::new(center, direction), t_min, ::std::f32::INFINITY) {
+                        crossings += 1;
+                        t_min = hit.t + 1e-5;
+                    }
+
+                    slice.push(if crossings % 2 == 1 { -distance } else { distance });
+                }
+            }
+
+            slice
+        });
+
+        let texture = Texture3D::from_fn(Dimensions::new(width, height), depth, |coord, z| {
+            Rf32Color::new(distances[z as usize][coord.into_index(Dimensions::new(width, height))])
+        });
+
+        DistanceVolume { origin, voxel_size, texture }
+    }
+
+    /// World-space position of the minimum corner of the volume
+    #[inline]
+    pub fn origin(&self) -> Point3<f32> { self.origin }
+
+    /// Length of the edges of each voxel
+    #[inline]
+    pub fn voxel_size(&self) -> f32 { self.voxel_size }
+
+    /// Distances at voxel centers, with x and y as texel coordinates and z as the slice
+    #[inline]
+    pub fn texture(&self) -> &Texture3D<Rf32Color> { &self.texture }
+
+    #[inline]
+    pub fn into_texture(self) -> Texture3D<Rf32Color> { self.texture }
+
+    /// Normalized texture coordinate of a world-space position, for sampling the texture in shaders
+    pub fn texture_coordinate(&self, position: Point3<f32>) -> Vector3<f32> {
+        let dimensions = self.texture.dimensions();
+
+        let size = Vector3::new(dimensions.width as f32, dimensions.height as f32, self.texture.depth() as f32) * self.voxel_size;
+
+        let offset = position - self.origin;
+
+        Vector3::new(offset.x / size.x, offset.y / size.y, offset.z / size.z)
+    }
+
+    /// Trilinearly interpolated distance at a world-space position, clamped to the edges of the volume
+    pub fn distance_at(&self, position: Point3<f32>) -> f32 {
+        self.texture.sample(self.texture_coordinate(position), Filter::Bilinear)[0]
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::mesh::SimpleVertex;
+
+    use super::*;
+
+    #[test]
+    fn test_jump_flood_matches_brute_force() {
+        let image = ColorBuffer::from_fn(Dimensions::new(32, 24), |coord| {
+            let inside = (coord.x as i32 - 12).pow(2) + (coord.y as i32 - 10).pow(2) < 49 || (coord.x > 25 && coord.y > 18);
+
+            Rf32Color::new(if inside { 1.0 } else { 0.0 })
+        });
+
+        let inside = |color: Rf32Color| color.x > 0.5;
+
+        let jump_flood = DistanceField::from_coverage(&image, DistanceTransform::JumpFlood, inside);
+        let brute_force = DistanceField::from_coverage(&image, DistanceTransform::BruteForce, inside);
+
+        for (a, b) in jump_flood.as_slice().iter().zip(brute_force.as_slice()) {
+            assert!((a - b).abs() < 1.0);
+        }
+
+        assert_eq!(brute_force.get(Coordinate::new(12, 10)), Some(-6.5));
+        assert_eq!(brute_force.get(Coordinate::new(0, 10)), Some(5.5));
+
+        let encoded: ColorBuffer<Rf32Color> = brute_force.to_buffer(4.0);
+
+        assert_eq!(encoded.as_slice()[10 * 32 + 12].x, 1.0);
+        assert_eq!(encoded.as_slice()[10 * 32].x, 0.0);
+    }
+
+    #[test]
+    fn test_mesh_distance_volume() {
+        let vertices = (0..8).map(|i| SimpleVertex {
+            position: Point3::new((i & 1) as f32, ((i >> 1) & 1) as f32, ((i >> 2) & 1) as f32),
+            data: (),
+        }).collect();
+
+        let cube: Mesh<SimpleVertex<f32, ()>> = Mesh {
+            indices: vec![0, 2, 6, 0, 6, 4, 1, 3, 7, 1, 7, 5,
+                          0, 1, 5, 0, 5, 4, 2, 3, 7, 2, 7, 6,
+                          0, 1, 3, 0, 3, 2, 4, 5, 7, 4, 7, 6],
+            vertices,
+        };
+
+        let mut pool = Pool::new(2);
+
+        let volume = DistanceVolume::from_mesh(&mut pool, &cube, 8, 2);
+
+        assert_eq!(volume.texture().dimensions(), Dimensions::new(12, 12));
+        assert_eq!(volume.texture().depth(), 12);
+
+        assert!((volume.distance_at(Point3::new(0.5, 0.5, 0.5)) + 0.5).abs() < 0.1);
+        assert!((volume.distance_at(Point3::new(1.25, 0.5, 0.5)) - 0.25).abs() < 0.1);
+    }
+}
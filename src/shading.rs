@@ -0,0 +1,292 @@
+//! Light sources for fragment shaders
+//!
+//! Each light returns the direction towards it and the radiance arriving at a surface position,
+//! which fragment shaders combine with their own material model:
+//!
+//! ```ignore
+//! let (to_light, radiance) = spot.incident(position);
+//!
+//! color += albedo * radiance * normal.dot(&to_light).max(0.0);
+//! ```
+//!
+//! Point and spot lights fade out smoothly to nothing at their range, so they can be culled
+//! with a [`LightGrid`](../pipeline/lights/struct.LightGrid.html).
+//!
+//! Rectangular area lights have no single direction, so they are evaluated by integrating
+//! over the polygon of the light with linearly transformed cosines (LTC), from Heitz et al. 2016.
+//! Diffuse lighting needs no fitted data, as it integrates the clamped cosine itself, while specular lighting
+//! takes the inverse LTC matrix for the roughness and view angle from the user's fitted table.
+
+use nalgebra::{Point3, Vector3, Matrix3};
+
+use ::pipeline::LightBounds;
+
+/// Inverse square falloff windowed to reach zero at `range`, from Karis 2013
+#[inline]
+pub fn range_attenuation(distance: f32, range: f32) -> f32 {
+    if range <= 0.0 {
+        return 0.0;
+    }
+
+    let ratio = distance / range;
+    let window = (1.0 - ratio * ratio * ratio * ratio).max(0.0).min(1.0);
+
+    window * window / (distance * distance + 1.0)
+}
+
+/// Light radiating equally in every direction from a point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PointLight {
+    pub position: Point3<f32>,
+    /// Linear color multiplied by intensity
+    pub color: Vector3<f32>,
+    /// Distance at which the light fades out completely
+    pub range: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Point3<f32>, color: Vector3<f32>, range: f32) -> PointLight {
+        PointLight { position, color, range }
+    }
+
+    /// Returns the normalized direction from `position` towards the light, and the radiance arriving there
+    pub fn incident(&self, position: Point3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let offset = self.position - position;
+        let distance = offset.norm();
+
+        let direction = if distance > 0.0 { offset / distance } else { Vector3::z() };
+
+        (direction, self.color * range_attenuation(distance, self.range))
+    }
+}
+
+impl LightBounds<f32> for PointLight {
+    #[inline]
+    fn bounding_sphere(&self) -> (Point3<f32>, f32) { (self.position, self.range) }
+}
+
+/// Point light restricted to a cone, fading out between an inner and outer angle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SpotLight {
+    pub position: Point3<f32>,
+    /// Normalized direction the cone points in
+    pub direction: Vector3<f32>,
+    /// Linear color multiplied by intensity
+    pub color: Vector3<f32>,
+    /// Distance at which the light fades out completely
+    pub range: f32,
+    /// Angle from the direction in radians within which the light is at full intensity
+    pub inner_angle: f32,
+    /// Angle from the direction in radians beyond which there is no light
+    pub outer_angle: f32,
+}
+
+impl SpotLight {
+    pub fn new(position: Point3<f32>, direction: Vector3<f32>, color: Vector3<f32>, range: f32,
+               inner_angle: f32, outer_angle: f32) -> SpotLight {
+        SpotLight { position, direction: direction.normalize(), color, range, inner_angle, outer_angle }
+    }
+
+    /// Falloff of the cone for a normalized direction from the light, from `1.0` inside the inner angle
+    /// to `0.0` outside the outer angle, with a smooth step between them
+    pub fn cone_falloff(&self, direction: Vector3<f32>) -> f32 {
+        let cos_outer = self.outer_angle.cos();
+        let cos_inner = self.inner_angle.cos().max(cos_outer + 1e-4);
+
+        let t = ((direction.dot(&self.direction) - cos_outer) / (cos_inner - cos_outer)).max(0.0).min(1.0);
+
+        t * t * (3.0 - 2.0 * t)
+    }
+
+    /// Returns the normalized direction from `position` towards the light, and the radiance arriving there
+    pub fn incident(&self, position: Point3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+        let offset = self.position - position;
+        let distance = offset.norm();
+
+        if distance <= 0.0 {
+            return (-self.direction, self.color);
+        }
+
+        let direction = offset / distance;
+
+        (direction, self.color * (range_attenuation(distance, self.range) * self.cone_falloff(-direction)))
+    }
+}
+
+impl LightBounds<f32> for SpotLight {
+    #[inline]
+    fn bounding_sphere(&self) -> (Point3<f32>, f32) { (self.position, self.range) }
+}
+
+/// Rectangular area light, like a window or a panel.
+///
+/// Light is emitted from the side `up.cross(&right)` points to, or both sides if it is two-sided,
+/// so a light with `right` along x and `up` along y shines towards negative z.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RectLight {
+    pub center: Point3<f32>,
+    /// Vector from the center to the middle of the right edge
+    pub right: Vector3<f32>,
+    /// Vector from the center to the middle of the top edge
+    pub up: Vector3<f32>,
+    /// Linear color multiplied by intensity, as the radiance of the surface of the light
+    pub color: Vector3<f32>,
+    /// Distance beyond which the light is ignored when culling
+    pub range: f32,
+    pub two_sided: bool,
+}
+
+impl RectLight {
+    /// Corners of the light, counter-clockwise around `right.cross(&up)`
+    pub fn corners(&self) -> [Point3<f32>; 4] {
+        [self.center - self.right - self.up,
+         self.center + self.right - self.up,
+         self.center + self.right + self.up,
+         self.center - self.right + self.up]
+    }
+
+    /// Diffuse irradiance arriving at a surface position with the given normalized normal,
+    /// integrating the clamped cosine over the light exactly
+    pub fn irradiance(&self, position: Point3<f32>, normal: Vector3<f32>) -> Vector3<f32> {
+        let view = if normal.x.abs() < 0.9 { Vector3::x() } else { Vector3::y() };
+
+        self.color * self.ltc_evaluate(position, normal, view, &Matrix3::identity())
+    }
+
+    /// Integrates the linearly transformed cosine with inverse matrix `m_inverse` over the light,
+    /// giving the fraction of light reflected towards the normalized `view` direction,
+    /// which points from the surface towards the viewer.
+    ///
+    /// `m_inverse` is the inverse LTC matrix fitted for the roughness and view angle, such as from the tables of Heitz et al.,
+    /// in the frame with the normal along z and the view direction in the xz plane.
+    /// The identity matrix gives the diffuse form factor.
+    pub fn ltc_evaluate(&self, position: Point3<f32>, normal: Vector3<f32>, view: Vector3<f32>, m_inverse: &Matrix3<f32>) -> f32 {
+        // Orthonormal frame around the normal, with the view direction in the xz plane
+        let tangent = view - normal * normal.dot(&view);
+
+        let tangent = if tangent.norm_squared() > 1e-12 {
+            tangent.normalize()
+        } else if normal.x.abs() < 0.9 {
+            normal.cross(&Vector3::x()).normalize()
+        } else {
+            normal.cross(&Vector3::y()).normalize()
+        };
+
+        let bitangent = normal.cross(&tangent);
+
+        let world_to_local = Matrix3::new(tangent.x, tangent.y, tangent.z,
+                                          bitangent.x, bitangent.y, bitangent.z,
+                                          normal.x, normal.y, normal.z);
+
+        let transform = m_inverse * world_to_local;
+
+        let corners = self.corners();
+
+        let mut polygon: Vec<Vector3<f32>> = corners.iter().map(|&corner| transform * (corner - position)).collect();
+
+        clip_to_horizon(&mut polygon);
+
+        if polygon.len() < 3 {
+            return 0.0;
+        }
+
+        let mut sum = 0.0;
+
+        for i in 0..polygon.len() {
+            let a = polygon[i].normalize();
+            let b = polygon[(i + 1) % polygon.len()].normalize();
+
+            sum += edge_integral(a, b);
+        }
+
+        let form_factor = sum / (2.0 * ::std::f32::consts::PI);
+
+        if self.two_sided { form_factor.abs() } else { form_factor.max(0.0) }
+    }
+}
+
+impl LightBounds<f32> for RectLight {
+    #[inline]
+    fn bounding_sphere(&self) -> (Point3<f32>, f32) { (self.center, self.range + (self.right + self.up).norm()) }
+}
+
+/// Integral of the cosine over the spherical edge between two normalized directions
+fn edge_integral(a: Vector3<f32>, b: Vector3<f32>) -> f32 {
+    let cos_theta = a.dot(&b).max(-1.0).min(1.0);
+    let theta = cos_theta.acos();
+
+    let cross = a.cross(&b);
+
+    // theta / sin(theta) tends to one for nearly parallel edges
+    let scale = if theta > 1e-4 { theta / theta.sin() } else { 1.0 };
+
+    cross.z * scale
+}
+
+/// Clips a polygon to the upper hemisphere where z >= 0
+fn clip_to_horizon(polygon: &mut Vec<Vector3<f32>>) {
+    let input = ::std::mem::replace(polygon, Vec::with_capacity(5));
+
+    for i in 0..input.len() {
+        let current = input[i];
+        let next = input[(i + 1) % input.len()];
+
+        if current.z >= 0.0 {
+            polygon.push(current);
+        }
+
+        if (current.z >= 0.0) != (next.z >= 0.0) {
+            let t = current.z / (current.z - next.z);
+
+            polygon.push(current + (next - current) * t);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_spot_cone() {
+        let spot = SpotLight::new(Point3::new(0.0, 2.0, 0.0), Vector3::new(0.0, -1.0, 0.0), Vector3::new(1.0, 1.0, 1.0),
+                                  10.0, 0.2, 0.4);
+
+        let (direction, below) = spot.incident(Point3::origin());
+        let (_, beside) = spot.incident(Point3::new(5.0, 0.0, 0.0));
+
+        assert!((direction - Vector3::y()).norm() < 1e-6);
+        assert!(below.x > 0.0);
+        assert_eq!(beside.x, 0.0);
+
+        assert_eq!(range_attenuation(10.0, 10.0), 0.0);
+    }
+
+    #[test]
+    fn test_rect_form_factor() {
+        // Light facing down, one unit above a surface facing up
+        let light = |size: f32| RectLight {
+            center: Point3::new(0.0, 0.0, 1.0),
+            right: Vector3::x() * size,
+            up: Vector3::y() * size,
+            color: Vector3::new(1.0, 1.0, 1.0),
+            range: 10.0,
+            two_sided: false,
+        };
+
+        let normal = Vector3::z();
+
+        // An infinite light covers the whole hemisphere
+        assert!((light(1000.0).irradiance(Point3::origin(), normal).x - 1.0).abs() < 1e-2);
+
+        // A small light approaches area * cos / (pi * distance^2)
+        let small = light(0.01).irradiance(Point3::origin(), normal).x;
+        let expected = 0.02 * 0.02 / ::std::f32::consts::PI;
+
+        assert!((small - expected).abs() / expected < 1e-2);
+
+        // Nothing reaches a surface facing away, or from behind a one-sided light
+        assert_eq!(light(1.0).irradiance(Point3::origin(), -normal).x, 0.0);
+        assert_eq!(light(1.0).irradiance(Point3::new(0.0, 0.0, 2.0), normal).x, 0.0);
+    }
+}
@@ -87,6 +87,7 @@ impl Stencil for () {
 
 /// Defines tests which can be performed on stencil buffers
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub enum StencilTest {
     /// Always pass
     Always,
@@ -126,6 +127,7 @@ impl StencilTest {
 
 /// Defines the operation to be performed upon a passing stencil test
 #[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub enum StencilOp {
     /// Keep the previous stencil value
     Keep,
@@ -176,6 +178,7 @@ impl StencilConfig for () {
 
 /// Generic stencil config that just stores the `StencilOp` and `StencilTest` structures.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
 pub struct GenericStencilConfig {
     pub op: StencilOp,
     pub test: StencilTest,
@@ -0,0 +1,238 @@
+//! Terminal output
+//!
+//! Prints color buffers straight to a terminal, for inspecting output on headless servers or over SSH
+//! without copying image files around:
+//!
+//! * `write_truecolor` draws two pixels per character cell with the upper half block and 24-bit color escapes.
+//! * `write_ascii` draws a luminance ramp of plain characters, for terminals and logs without color.
+//! * `write_sixel` encodes Sixel graphics, shown at full resolution by terminals such as xterm, mlterm and foot.
+//!
+//! Channels are taken as already gamma-encoded, like the values written to image files.
+
+use std::io::{self, Write};
+
+use ::color::ToChannels;
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::PixelRead;
+
+/// Characters from dark to bright used by `write_ascii`
+const ASCII_RAMP: &'static [u8] = b" .:-=+*#%@";
+
+/// Levels per channel of the color cube used as the Sixel palette
+const SIXEL_LEVELS: u32 = 6;
+
+#[inline]
+fn to_rgb8(c: [f32; 4]) -> [u8; 3] {
+    let byte = |c: f32| (c.max(0.0).min(1.0) * 255.0).round() as u8;
+
+    [byte(c[0]), byte(c[1]), byte(c[2])]
+}
+
+#[inline]
+fn luminance(c: [f32; 4]) -> f32 {
+    ::analysis::luminance(&c).max(0.0).min(1.0)
+}
+
+#[inline]
+fn channels<P>(buffer: &P, x: u32, y: u32) -> [f32; 4] where P: PixelRead, P::Color: ToChannels {
+    unsafe { buffer.get_pixel_unchecked(buffer.index_of(Coordinate::new(x, y))) }.to_channels()
+}
+
+/// Writes the buffer with one character cell for every two rows of pixels, using 24-bit color escape sequences
+pub fn write_truecolor<P, W>(buffer: &P, writer: &mut W) -> io::Result<()> where P: PixelRead, P::Color: ToChannels, W: Write {
+    let dimensions = buffer.dimensions();
+
+    for y in (0..dimensions.height).filter(|y| y % 2 == 0) {
+        for x in 0..dimensions.width {
+            let top = to_rgb8(channels(buffer, x, y));
+
+            write!(writer, "\x1b[38;2;{};{};{}m", top[0], top[1], top[2])?;
+
+            if y + 1 < dimensions.height {
+                let bottom = to_rgb8(channels(buffer, x, y + 1));
+
+                write!(writer, "\x1b[48;2;{};{};{}m", bottom[0], bottom[1], bottom[2])?;
+            } else {
+                writer.write_all(b"\x1b[49m")?;
+            }
+
+            writer.write_all("\u{2580}".as_bytes())?;
+        }
+
+        writer.write_all(b"\x1b[0m\n")?;
+    }
+
+    Ok(())
+}
+
+/// Writes the buffer as plain characters by luminance, with one character for every two rows of pixels
+/// to roughly keep the aspect ratio of terminal fonts
+pub fn write_ascii<P, W>(buffer: &P, writer: &mut W) -> io::Result<()> where P: PixelRead, P::Color: ToChannels, W: Write {
+    let dimensions = buffer.dimensions();
+
+    let mut line = Vec::with_capacity(dimensions.width as usize + 1);
+
+    for y in (0..dimensions.height).filter(|y| y % 2 == 0) {
+        line.clear();
+
+        for x in 0..dimensions.width {
+            let mut l = luminance(channels(buffer, x, y));
+
+            if y + 1 < dimensions.height {
+                l = (l + luminance(channels(buffer, x, y + 1))) * 0.5;
+            }
+
+            line.push(ASCII_RAMP[(l * (ASCII_RAMP.len() - 1) as f32).round() as usize]);
+        }
+
+        line.push(b'\n');
+
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Index of the nearest color in the Sixel palette
+#[inline]
+fn sixel_color(c: [f32; 4]) -> usize {
+    let level = |c: f32| (c.max(0.0).min(1.0) * (SIXEL_LEVELS - 1) as f32).round() as usize;
+
+    let n = SIXEL_LEVELS as usize;
+
+    level(c[0]) * n * n + level(c[1]) * n + level(c[2])
+}
+
+/// Writes a run of identical sixels, using run-length encoding for longer runs
+fn write_sixel_run<W: Write>(writer: &mut W, sixel: u8, count: usize) -> io::Result<()> {
+    match count {
+        0 => Ok(()),
+        1 | 2 | 3 => writer.write_all(&vec![sixel; count]),
+        _ => write!(writer, "!{}{}", count, sixel as char),
+    }
+}
+
+/// Writes the buffer as Sixel graphics, with colors reduced to a 216 color palette
+pub fn write_sixel<P, W>(buffer: &P, writer: &mut W) -> io::Result<()> where P: PixelRead, P::Color: ToChannels, W: Write {
+    let dimensions = buffer.dimensions();
+    let (width, height) = (dimensions.width as usize, dimensions.height as usize);
+
+    let n = SIXEL_LEVELS;
+    let palette_size = (n * n * n) as usize;
+
+    // Start of Sixel data, with square pixels and the image size
+    write!(writer, "\x1bPq\"1;1;{};{}", width, height)?;
+
+    // Color registers use percentages
+    for r in 0..n {
+        for g in 0..n {
+            for b in 0..n {
+                let percent = |c: u32| c * 100 / (n - 1);
+
+                write!(writer, "#{};2;{};{};{}", (r * n + g) * n + b, percent(r), percent(g), percent(b))?;
+            }
+        }
+    }
+
+    let mut colors = vec![0usize; width * 6];
+    let mut used = vec![false; palette_size];
+
+    // Each band of sixels covers six rows
+    for band in (0..height).filter(|y| y % 6 == 0) {
+        let rows = (height - band).min(6);
+
+        for flag in &mut used {
+            *flag = false;
+        }
+
+        for dy in 0..rows {
+            for x in 0..width {
+                let color = sixel_color(channels(buffer, x as u32, (band + dy) as u32));
+
+                colors[dy * width + x] = color;
+                used[color] = true;
+            }
+        }
+
+        let mut first = true;
+
+        for color in (0..palette_size).filter(|&color| used[color]) {
+            // Return to the start of the band to overlay the next color
+            if !first {
+                writer.write_all(b"$")?;
+            }
+
+            first = false;
+
+            write!(writer, "#{}", color)?;
+
+            let mut run = (0u8, 0usize);
+
+            for x in 0..width {
+                let mut bits = 0u8;
+
+                for dy in 0..rows {
+                    if colors[dy * width + x] == color {
+                        bits |= 1 << dy;
+                    }
+                }
+
+                let sixel = 63 + bits;
+
+                if sixel == run.0 {
+                    run.1 += 1;
+                } else {
+                    write_sixel_run(writer, run.0, run.1)?;
+
+                    run = (sixel, 1);
+                }
+            }
+
+            write_sixel_run(writer, run.0, run.1)?;
+        }
+
+        // Move down to the next band
+        writer.write_all(b"-")?;
+    }
+
+    // String terminator
+    writer.write_all(b"\x1b\\")
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::RGBAf32Color;
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_ascii_output() {
+        let buffer = ColorBuffer::from_fn(Dimensions::new(2, 3), |coord| {
+            let v = coord.x as f32;
+
+            RGBAf32Color::new(v, v, v, 1.0)
+        });
+
+        let mut out = Vec::new();
+
+        write_ascii(&buffer, &mut out).unwrap();
+
+        assert_eq!(out, b" @\n @\n".to_vec());
+    }
+
+    #[test]
+    fn test_sixel_output() {
+        let buffer = ColorBuffer::filled(Dimensions::new(8, 6), RGBAf32Color::new(1.0, 0.0, 0.0, 1.0));
+
+        let mut out = Vec::new();
+
+        write_sixel(&buffer, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("\x1bPq\"1;1;8;6"));
+        assert!(out.ends_with("#180!8~-\x1b\\"));
+    }
+}
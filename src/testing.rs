@@ -0,0 +1,460 @@
+//! Golden-image regression testing utilities
+//!
+//! Rendered framebuffers are compared against previously approved "golden" images stored on disk.
+//! Comparison is perceptual rather than exact, so tiny floating point differences between platforms
+//! don't cause spurious failures, but visible changes do.
+//!
+//! To create or update golden images, run the tests with the `SOFTRENDER_BLESS` environment variable set.
+//!
+//! See the [`render_test!`](../macro.render_test.html) macro for declaring tests.
+
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Read, Write, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::PixelRead;
+
+pub use ::color::channels::ToChannels;
+
+/// Environment variable which, if set, causes golden images to be overwritten instead of compared.
+pub const BLESS_ENV_VAR: &'static str = "SOFTRENDER_BLESS";
+
+const GOLDEN_MAGIC: &'static [u8; 4] = b"SRGI";
+
+/// Comparison tolerances for golden-image tests
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct Tolerance {
+    /// Largest allowed absolute difference of any single channel of any pixel
+    pub channel: f32,
+    /// Fraction of pixels allowed to exceed the channel tolerance, in the range `[0, 1]`
+    pub max_outlier_fraction: f32,
+    /// Smallest allowed structural similarity index, in the range `[0, 1]`
+    pub min_ssim: f32,
+}
+
+impl Default for Tolerance {
+    fn default() -> Tolerance {
+        Tolerance {
+            channel: 2.0 / 255.0,
+            max_outlier_fraction: 0.001,
+            min_ssim: 0.99,
+        }
+    }
+}
+
+impl Tolerance {
+    /// Requires a bit-exact match
+    pub fn exact() -> Tolerance {
+        Tolerance {
+            channel: 0.0,
+            max_outlier_fraction: 0.0,
+            min_ssim: 1.0,
+        }
+    }
+}
+
+/// An image stored as normalized RGBA floating point channels, used for golden files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoldenImage {
+    pub dimensions: Dimensions,
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl HasDimensions for GoldenImage {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl GoldenImage {
+    /// Copies all the pixels out of a pixel buffer
+    pub fn from_buffer<P>(buffer: &P) -> GoldenImage where P: PixelRead, P::Color: ToChannels {
+        GoldenImage {
+            dimensions: buffer.dimensions(),
+            pixels: buffer.pixel_iter().map(|pixel| pixel.get().to_channels()).collect(),
+        }
+    }
+
+    /// Loads a golden image from the given file
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<GoldenImage> {
+        let file = File::open(path)?;
+
+        let file_length = file.metadata()?.len();
+
+        let mut reader = BufReader::new(file);
+
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+
+        if &magic != GOLDEN_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Not a golden image file"));
+        }
+
+        let width = read_u32(&mut reader)?;
+        let height = read_u32(&mut reader)?;
+
+        // Check the header against the file before trusting it with an allocation
+        let expected_length = (width as u64).checked_mul(height as u64)
+                                            .and_then(|area| area.checked_mul(16))
+                                            .and_then(|length| length.checked_add(12));
+
+        if expected_length != Some(file_length) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Golden image dimensions don't match the file length"));
+        }
+
+        let dimensions = Dimensions::new(width, height);
+
+        let mut pixels = Vec::with_capacity(dimensions.area());
+
+        for _ in 0..dimensions.area() {
+            let mut pixel = [0.0; 4];
+
+            for channel in &mut pixel {
+                *channel = f32::from_bits(read_u32(&mut reader)?);
+            }
+
+            pixels.push(pixel);
+        }
+
+        Ok(GoldenImage { dimensions, pixels })
+    }
+
+    /// Saves the golden image to the given file, creating any parent directories
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        writer.write_all(GOLDEN_MAGIC)?;
+        write_u32(&mut writer, self.dimensions.width)?;
+        write_u32(&mut writer, self.dimensions.height)?;
+
+        for pixel in &self.pixels {
+            for channel in pixel {
+                write_u32(&mut writer, channel.to_bits())?;
+            }
+        }
+
+        writer.flush()
+    }
+
+    #[inline]
+    fn luminance(&self, x: u32, y: u32) -> f32 {
+        let pixel = self.pixels[Coordinate::new(x, y).into_index(self.dimensions)];
+
+        0.2126 * pixel[0] + 0.7152 * pixel[1] + 0.0722 * pixel[2]
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes[0] as u32 | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24)
+}
+
+fn write_u32<W: Write>(writer: &mut W, value: u32) -> io::Result<()> {
+    writer.write_all(&[value as u8, (value >> 8) as u8, (value >> 16) as u8, (value >> 24) as u8])
+}
+
+/// Result of comparing two images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImageDiff {
+    /// Largest absolute difference of any single channel
+    pub max_channel_difference: f32,
+    /// Number of pixels with any channel over the channel tolerance
+    pub outliers: usize,
+    /// Fraction of pixels with any channel over the channel tolerance
+    pub outlier_fraction: f32,
+    /// Mean structural similarity index of the luminance of both images
+    pub ssim: f32,
+}
+
+impl ImageDiff {
+    /// Checks if the difference is within the given tolerance
+    pub fn passes(&self, tolerance: &Tolerance) -> bool {
+        self.outlier_fraction <= tolerance.max_outlier_fraction && self.ssim >= tolerance.min_ssim
+    }
+}
+
+/// Compares two images of the same dimensions, counting outlier pixels using the channel tolerance.
+///
+/// Returns `None` if the dimensions differ.
+pub fn compare(expected: &GoldenImage, actual: &GoldenImage, tolerance: &Tolerance) -> Option<ImageDiff> {
+    if expected.dimensions != actual.dimensions {
+        return None;
+    }
+
+    let mut max_channel_difference = 0.0f32;
+    let mut outliers = 0;
+
+    for (a, b) in expected.pixels.iter().zip(actual.pixels.iter()) {
+        let mut outlier = false;
+
+        for c in 0..4 {
+            let d = (a[c] - b[c]).abs();
+
+            max_channel_difference = max_channel_difference.max(d);
+
+            if d > tolerance.channel {
+                outlier = true;
+            }
+        }
+
+        if outlier {
+            outliers += 1;
+        }
+    }
+
+    let area = expected.dimensions.area();
+
+    Some(ImageDiff {
+        max_channel_difference,
+        outliers,
+        outlier_fraction: if area > 0 { outliers as f32 / area as f32 } else { 0.0 },
+        ssim: ssim(expected, actual),
+    })
+}
+
+/// Computes the mean structural similarity index of the luminance of two images with the same dimensions,
+/// using 8x8 windows with a stride of 4 pixels.
+///
+/// Identical images have an SSIM of exactly `1.0`.
+pub fn ssim(a: &GoldenImage, b: &GoldenImage) -> f32 {
+    const WINDOW: u32 = 8;
+    const STRIDE: u32 = 4;
+
+    // Stabilization constants for a dynamic range of 1.0
+    const C1: f64 = 0.01 * 0.01;
+    const C2: f64 = 0.03 * 0.03;
+
+    assert_eq!(a.dimensions, b.dimensions);
+
+    let Dimensions { width, height } = a.dimensions;
+
+    let window_width = if width < WINDOW { width } else { WINDOW };
+    let window_height = if height < WINDOW { height } else { WINDOW };
+
+    if window_width == 0 || window_height == 0 {
+        return 1.0;
+    }
+
+    let mut total = 0.0;
+    let mut windows = 0;
+
+    let mut y = 0;
+
+    while y + window_height <= height {
+        let mut x = 0;
+
+        while x + window_width <= width {
+            let n = (window_width * window_height) as f64;
+
+            let (mut sum_a, mut sum_b) = (0.0, 0.0);
+
+            for wy in y..y + window_height {
+                for wx in x..x + window_width {
+                    sum_a += a.luminance(wx, wy) as f64;
+                    sum_b += b.luminance(wx, wy) as f64;
+                }
+            }
+
+            let (mean_a, mean_b) = (sum_a / n, sum_b / n);
+
+            let (mut var_a, mut var_b, mut covar) = (0.0, 0.0, 0.0);
+
+            for wy in y..y + window_height {
+                for wx in x..x + window_width {
+                    let da = a.luminance(wx, wy) as f64 - mean_a;
+                    let db = b.luminance(wx, wy) as f64 - mean_b;
+
+                    var_a += da * da;
+                    var_b += db * db;
+                    covar += da * db;
+                }
+            }
+
+            var_a /= n;
+            var_b /= n;
+            covar /= n;
+
+            total += ((2.0 * mean_a * mean_b + C1) * (2.0 * covar + C2)) /
+                ((mean_a * mean_a + mean_b * mean_b + C1) * (var_a + var_b + C2));
+
+            windows += 1;
+
+            x += STRIDE;
+        }
+
+        y += STRIDE;
+    }
+
+    if windows == 0 { 1.0 } else { (total / windows as f64) as f32 }
+}
+
+/// Returns `true` if golden images should be overwritten instead of compared.
+pub fn blessing() -> bool {
+    env::var_os(BLESS_ENV_VAR).is_some()
+}
+
+/// Compares the buffer against the golden image at the given path, panicking with a description of the difference
+/// if they don't match within the tolerance.
+///
+/// If the `SOFTRENDER_BLESS` environment variable is set, the golden image is written instead.
+///
+/// On failure, the actual image is written next to the golden image with an `.actual` extension for inspection.
+pub fn assert_golden<B, P>(buffer: &B, path: P, tolerance: Tolerance) where B: PixelRead,
+                                                                           B::Color: ToChannels,
+                                                                           P: AsRef<Path> {
+    let path = path.as_ref();
+
+    let actual = GoldenImage::from_buffer(buffer);
+
+    if blessing() {
+        actual.save(path).expect("Unable to write golden image");
+        return;
+    }
+
+    let expected = match GoldenImage::load(path) {
+        Ok(expected) => expected,
+        Err(err) => panic!("Unable to load golden image {}: {}. Run with {} set to create it.",
+                           path.display(), err, BLESS_ENV_VAR),
+    };
+
+    let actual_path: PathBuf = path.with_extension("actual");
+
+    match compare(&expected, &actual, &tolerance) {
+        Some(ref diff) if diff.passes(&tolerance) => {
+            // Clean up after any previous failures
+            let _ = fs::remove_file(&actual_path);
+        }
+        Some(diff) => {
+            let _ = actual.save(&actual_path);
+
+            panic!("Rendered image does not match golden image {}: {:?} with {:?}",
+                   path.display(), diff, tolerance);
+        }
+        None => {
+            let _ = actual.save(&actual_path);
+
+            panic!("Rendered image dimensions {:?} do not match golden image {} dimensions {:?}",
+                   actual.dimensions, path.display(), expected.dimensions);
+        }
+    }
+}
+
+/// Declares golden-image render tests.
+///
+/// Each test body must evaluate to something implementing `PixelRead` with a color implementing
+/// [`ToChannels`](testing/trait.ToChannels.html), which is then compared against the golden image at the given path.
+///
+/// ```ignore
+/// render_test! {
+///     /// Renders a single triangle
+///     fn single_triangle("tests/golden/single_triangle.srgi") {
+///         let mut framebuffer = RenderBuffer::<ColorDepthAttachments<RGBAf32Color, f32>>::with_dimensions(Dimensions::new(64, 64));
+///
+///         // ... render into the framebuffer ...
+///
+///         framebuffer
+///     }
+///
+///     fn noisy_render("tests/golden/noisy_render.srgi", Tolerance { min_ssim: 0.9, ..Tolerance::default() }) {
+///         // ...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! render_test {
+    () => {};
+
+    ($(#[$($attrs:tt)*])* fn $name:ident($path:expr) $body:block $($rest:tt)*) => {
+        render_test! {
+            $(#[$($attrs)*])* fn $name($path, $crate::testing::Tolerance::default()) $body $($rest)*
+        }
+    };
+
+    ($(#[$($attrs:tt)*])* fn $name:ident($path:expr, $tolerance:expr) $body:block $($rest:tt)*) => {
+        $(#[$($attrs)*])*
+        #[test]
+        fn $name() {
+            let buffer = $body;
+
+            $crate::testing::assert_golden(&buffer, $path, $tolerance);
+        }
+
+        render_test! { $($rest)* }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn gradient(dimensions: Dimensions, offset: f32) -> GoldenImage {
+        GoldenImage {
+            dimensions,
+            pixels: (0..dimensions.area()).map(|i| {
+                let v = (i as f32 / dimensions.area() as f32 + offset).min(1.0);
+                [v, v, v, 1.0]
+            }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_identical_images() {
+        let a = gradient(Dimensions::new(32, 16), 0.0);
+
+        let diff = compare(&a, &a, &Tolerance::exact()).unwrap();
+
+        assert_eq!(diff.outliers, 0);
+        assert_eq!(diff.ssim, 1.0);
+        assert!(diff.passes(&Tolerance::exact()));
+    }
+
+    #[test]
+    fn test_different_images() {
+        let a = gradient(Dimensions::new(32, 16), 0.0);
+        let b = gradient(Dimensions::new(32, 16), 0.25);
+
+        let diff = compare(&a, &b, &Tolerance::default()).unwrap();
+
+        assert!(diff.outliers > 0);
+        assert!(!diff.passes(&Tolerance::default()));
+
+        assert!(compare(&a, &gradient(Dimensions::new(16, 16), 0.0), &Tolerance::default()).is_none());
+    }
+
+    #[test]
+    fn test_load_validates_header() {
+        let path = env::temp_dir().join(format!("softrender-golden-{}.bin", ::std::process::id()));
+
+        let image = gradient(Dimensions::new(8, 4), 0.0);
+
+        image.save(&path).unwrap();
+
+        assert_eq!(GoldenImage::load(&path).unwrap(), image);
+
+        // A header claiming far more pixels than the file holds is rejected before allocating
+        {
+            let mut writer = BufWriter::new(File::create(&path).unwrap());
+
+            writer.write_all(GOLDEN_MAGIC).unwrap();
+            write_u32(&mut writer, u32::max_value()).unwrap();
+            write_u32(&mut writer, u32::max_value()).unwrap();
+            write_u32(&mut writer, 0).unwrap();
+        }
+
+        let error = GoldenImage::load(&path).err().unwrap();
+
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,520 @@
+//! Block-compressed textures
+//!
+//! Loads BC1, BC3, BC4 and BC5 textures from `.dds` and `.ktx` (version 1) files, the formats most game assets are shipped in,
+//! and decodes them on the CPU into `ColorBuffer`s that can be sampled like any other texture.
+//!
+//! Every format decodes to `RGBAu8Color`. BC4 fills only the red channel and BC5 the red and green channels,
+//! as with GPUs, with the remaining channels set to zero and alpha to opaque.
+
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use ::color::predefined::formats::RGBAu8Color;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{ColorBuffer, PixelWrite};
+
+/// Errors that may occur while loading a compressed texture
+#[derive(Debug)]
+pub enum CompressedTextureError {
+    Io(io::Error),
+    /// The file is not a valid DDS or KTX file
+    InvalidHeader(&'static str),
+    /// The file uses a pixel format other than BC1, BC3, BC4 or BC5, or features such as arrays, cubemaps or volumes
+    UnsupportedFormat,
+    /// The file or data ended before all blocks were read
+    Truncated,
+}
+
+impl Display for CompressedTextureError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            CompressedTextureError::Io(ref err) => write!(f, "Compressed Texture IO Error: {}", err),
+            CompressedTextureError::InvalidHeader(err) => write!(f, "Invalid Texture Header: {}", err),
+            _ => f.write_str(self.description()),
+        }
+    }
+}
+
+impl Error for CompressedTextureError {
+    fn description(&self) -> &str {
+        match *self {
+            CompressedTextureError::Io(_) => "Compressed Texture IO Error",
+            CompressedTextureError::InvalidHeader(_) => "Invalid Texture Header",
+            CompressedTextureError::UnsupportedFormat => "Unsupported Texture Format",
+            CompressedTextureError::Truncated => "Truncated Texture Data",
+        }
+    }
+}
+
+impl From<io::Error> for CompressedTextureError {
+    fn from(err: io::Error) -> CompressedTextureError { CompressedTextureError::Io(err) }
+}
+
+/// Block compression formats that can be decoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BlockFormat {
+    /// RGB with optional 1-bit alpha, also known as DXT1
+    Bc1,
+    /// RGBA with interpolated alpha, also known as DXT5
+    Bc3,
+    /// Single channel, also known as ATI1 or RGTC1
+    Bc4,
+    /// Two channels, also known as ATI2 or RGTC2, commonly used for normal maps
+    Bc5,
+}
+
+impl BlockFormat {
+    /// Size of each 4x4 block in bytes
+    #[inline]
+    pub fn block_bytes(self) -> usize {
+        match self {
+            BlockFormat::Bc1 | BlockFormat::Bc4 => 8,
+            BlockFormat::Bc3 | BlockFormat::Bc5 => 16,
+        }
+    }
+
+    /// Size in bytes of an image with the given dimensions
+    pub fn image_bytes(self, dimensions: Dimensions) -> usize {
+        blocks_wide(dimensions) * blocks_high(dimensions) * self.block_bytes()
+    }
+
+    /// Decodes a single block into its 16 texels in row-major order
+    pub fn decode_block(self, block: &[u8]) -> [RGBAu8Color; 16] {
+        let mut texels = [RGBAu8Color::new(0, 0, 0, 255); 16];
+
+        match self {
+            BlockFormat::Bc1 => decode_color_block(&block[0..8], true, &mut texels),
+            BlockFormat::Bc3 => {
+                decode_color_block(&block[8..16], false, &mut texels);
+
+                let alpha = decode_channel_block(&block[0..8]);
+
+                for (texel, a) in texels.iter_mut().zip(alpha.iter()) {
+                    texel.w = *a;
+                }
+            }
+            BlockFormat::Bc4 => {
+                let red = decode_channel_block(&block[0..8]);
+
+                for (texel, r) in texels.iter_mut().zip(red.iter()) {
+                    texel.x = *r;
+                }
+            }
+            BlockFormat::Bc5 => {
+                let red = decode_channel_block(&block[0..8]);
+                let green = decode_channel_block(&block[8..16]);
+
+                for i in 0..16 {
+                    texels[i].x = red[i];
+                    texels[i].y = green[i];
+                }
+            }
+        }
+
+        texels
+    }
+}
+
+#[inline]
+fn blocks_wide(dimensions: Dimensions) -> usize { (dimensions.width as usize + 3) / 4 }
+
+#[inline]
+fn blocks_high(dimensions: Dimensions) -> usize { (dimensions.height as usize + 3) / 4 }
+
+#[inline]
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    bytes[offset] as u16 | (bytes[offset + 1] as u16) << 8
+}
+
+#[inline]
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    read_u16(bytes, offset) as u32 | (read_u16(bytes, offset + 2) as u32) << 16
+}
+
+/// Expands an RGB565 color to 8 bits per channel
+#[inline]
+fn rgb565(c: u16) -> [u32; 3] {
+    let (r, g, b) = ((c >> 11) as u32 & 31, (c >> 5) as u32 & 63, c as u32 & 31);
+
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// Decodes the color part of BC1 and BC3 blocks. BC3 always uses four colors, while BC1 switches to three colors
+/// and transparent black when the first endpoint is not greater than the second.
+fn decode_color_block(block: &[u8], punchthrough: bool, texels: &mut [RGBAu8Color; 16]) {
+    let (c0, c1) = (read_u16(block, 0), read_u16(block, 2));
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+
+    let mut palette = [RGBAu8Color::new(0, 0, 0, 255); 4];
+
+    for i in 0..3 {
+        let (a, b) = (e0[i], e1[i]);
+
+        let (p2, p3) = if c0 > c1 || !punchthrough {
+            ((2 * a + b) / 3, (a + 2 * b) / 3)
+        } else {
+            ((a + b) / 2, 0)
+        };
+
+        palette[0][i] = a as u8;
+        palette[1][i] = b as u8;
+        palette[2][i] = p2 as u8;
+        palette[3][i] = p3 as u8;
+    }
+
+    if c0 <= c1 && punchthrough {
+        palette[3].w = 0;
+    }
+
+    let indices = read_u32(block, 4);
+
+    for i in 0..16 {
+        let color = palette[(indices >> (2 * i)) as usize & 3];
+
+        texels[i].x = color.x;
+        texels[i].y = color.y;
+        texels[i].z = color.z;
+        texels[i].w = color.w;
+    }
+}
+
+/// Decodes a BC4 style block of a single interpolated channel, also used for the alpha of BC3 and both channels of BC5
+fn decode_channel_block(block: &[u8]) -> [u8; 16] {
+    let (a, b) = (block[0] as u32, block[1] as u32);
+
+    let mut palette = [0u8; 8];
+
+    palette[0] = a as u8;
+    palette[1] = b as u8;
+
+    if a > b {
+        for i in 1..7 {
+            palette[i + 1] = (((7 - i as u32) * a + i as u32 * b) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            palette[i + 1] = (((5 - i as u32) * a + i as u32 * b) / 5) as u8;
+        }
+
+        palette[6] = 0;
+        palette[7] = 255;
+    }
+
+    let mut bits = 0u64;
+
+    for i in 0..6 {
+        bits |= (block[2 + i] as u64) << (8 * i);
+    }
+
+    let mut out = [0u8; 16];
+
+    for i in 0..16 {
+        out[i] = palette[(bits >> (3 * i)) as usize & 7];
+    }
+
+    out
+}
+
+/// Decodes tightly packed blocks of an image with the given dimensions, cropping partial blocks at the edges
+pub fn decode_blocks(format: BlockFormat, dimensions: Dimensions, data: &[u8]) -> Result<ColorBuffer<RGBAu8Color>, CompressedTextureError> {
+    if data.len() < format.image_bytes(dimensions) {
+        return Err(CompressedTextureError::Truncated);
+    }
+
+    let mut buffer = ColorBuffer::new(dimensions);
+
+    let block_bytes = format.block_bytes();
+
+    for by in 0..blocks_high(dimensions) {
+        for bx in 0..blocks_wide(dimensions) {
+            let offset = (by * blocks_wide(dimensions) + bx) * block_bytes;
+
+            let texels = format.decode_block(&data[offset..offset + block_bytes]);
+
+            for ty in 0..4 {
+                for tx in 0..4 {
+                    let coord = Coordinate::new((bx * 4 + tx) as u32, (by * 4 + ty) as u32);
+
+                    if dimensions.in_bounds(coord) {
+                        unsafe {
+                            let index = buffer.index_of(coord);
+
+                            buffer.set_pixel_unchecked(index, texels[ty * 4 + tx]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(buffer)
+}
+
+/// A block-compressed texture with all of its mipmap levels, as stored in a DDS or KTX file
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompressedTexture {
+    format: BlockFormat,
+    dimensions: Dimensions,
+    /// Compressed data of every mipmap level, starting with the full size image
+    levels: Vec<Vec<u8>>,
+}
+
+const DDS_MAGIC: &'static [u8] = b"DDS ";
+const KTX_IDENTIFIER: &'static [u8] = b"\xABKTX 11\xBB\r\n\x1A\n";
+
+const DDPF_FOURCC: u32 = 0x4;
+
+impl CompressedTexture {
+    /// Creates a texture from the compressed data of a single image
+    pub fn new(format: BlockFormat, dimensions: Dimensions, data: Vec<u8>) -> Result<CompressedTexture, CompressedTextureError> {
+        if data.len() < format.image_bytes(dimensions) {
+            return Err(CompressedTextureError::Truncated);
+        }
+
+        Ok(CompressedTexture { format, dimensions, levels: vec![data] })
+    }
+
+    /// Loads a `.dds` or `.ktx` file, detected by its contents
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<CompressedTexture, CompressedTextureError> {
+        let mut bytes = Vec::new();
+
+        File::open(path)?.read_to_end(&mut bytes)?;
+
+        if bytes.starts_with(KTX_IDENTIFIER) {
+            CompressedTexture::from_ktx(&bytes)
+        } else {
+            CompressedTexture::from_dds(&bytes)
+        }
+    }
+
+    /// Parses the contents of a DDS file, including files with the DX10 extended header
+    pub fn from_dds(bytes: &[u8]) -> Result<CompressedTexture, CompressedTextureError> {
+        if !bytes.starts_with(DDS_MAGIC) {
+            return Err(CompressedTextureError::InvalidHeader("Missing DDS magic number"));
+        }
+
+        if bytes.len() < 128 || read_u32(bytes, 4) != 124 {
+            return Err(CompressedTextureError::InvalidHeader("Invalid DDS header size"));
+        }
+
+        let height = read_u32(bytes, 12);
+        let width = read_u32(bytes, 16);
+        let mip_count = read_u32(bytes, 28).max(1);
+
+        let pixel_flags = read_u32(bytes, 80);
+
+        if pixel_flags & DDPF_FOURCC == 0 {
+            return Err(CompressedTextureError::UnsupportedFormat);
+        }
+
+        let mut offset = 128;
+
+        let format = match &bytes[84..88] {
+            b"DXT1" => BlockFormat::Bc1,
+            b"DXT4" | b"DXT5" => BlockFormat::Bc3,
+            b"ATI1" | b"BC4U" => BlockFormat::Bc4,
+            b"ATI2" | b"BC5U" => BlockFormat::Bc5,
+            b"DX10" => {
+                if bytes.len() < 148 {
+                    return Err(CompressedTextureError::Truncated);
+                }
+
+                // Only 2D textures without arrays
+                if read_u32(bytes, 132) != 3 || read_u32(bytes, 140) > 1 {
+                    return Err(CompressedTextureError::UnsupportedFormat);
+                }
+
+                offset = 148;
+
+                match read_u32(bytes, 128) {
+                    70 | 71 | 72 => BlockFormat::Bc1,
+                    76 | 77 | 78 => BlockFormat::Bc3,
+                    79 | 80 => BlockFormat::Bc4,
+                    82 | 83 => BlockFormat::Bc5,
+                    _ => return Err(CompressedTextureError::UnsupportedFormat),
+                }
+            }
+            _ => return Err(CompressedTextureError::UnsupportedFormat),
+        };
+
+        let dimensions = Dimensions::new(width, height);
+
+        let mut levels = Vec::with_capacity(mip_count as usize);
+
+        for level in 0..mip_count {
+            let size = format.image_bytes(level_dimensions(dimensions, level as usize));
+
+            if offset + size > bytes.len() {
+                return Err(CompressedTextureError::Truncated);
+            }
+
+            levels.push(bytes[offset..offset + size].to_vec());
+
+            offset += size;
+        }
+
+        Ok(CompressedTexture { format, dimensions, levels })
+    }
+
+    /// Parses the contents of a KTX version 1 file
+    pub fn from_ktx(bytes: &[u8]) -> Result<CompressedTexture, CompressedTextureError> {
+        if !bytes.starts_with(KTX_IDENTIFIER) {
+            return Err(CompressedTextureError::InvalidHeader("Missing KTX identifier"));
+        }
+
+        if bytes.len() < 64 {
+            return Err(CompressedTextureError::Truncated);
+        }
+
+        if read_u32(bytes, 12) != 0x04030201 {
+            return Err(CompressedTextureError::InvalidHeader("Big-endian KTX files are not supported"));
+        }
+
+        let format = match read_u32(bytes, 28) {
+            // COMPRESSED_RGB_S3TC_DXT1, COMPRESSED_RGBA_S3TC_DXT1 and their sRGB variants
+            0x83F0 | 0x83F1 | 0x8C4C | 0x8C4D => BlockFormat::Bc1,
+            // COMPRESSED_RGBA_S3TC_DXT5 and COMPRESSED_SRGB_ALPHA_S3TC_DXT5
+            0x83F3 | 0x8C4F => BlockFormat::Bc3,
+            // COMPRESSED_RED_RGTC1 and COMPRESSED_SIGNED_RED_RGTC1
+            0x8DBB => BlockFormat::Bc4,
+            // COMPRESSED_RG_RGTC2
+            0x8DBD => BlockFormat::Bc5,
+            _ => return Err(CompressedTextureError::UnsupportedFormat),
+        };
+
+        let width = read_u32(bytes, 36);
+        let height = read_u32(bytes, 40).max(1);
+
+        // Volumes, arrays and cubemaps
+        if read_u32(bytes, 44) > 1 || read_u32(bytes, 48) > 1 || read_u32(bytes, 52) > 1 {
+            return Err(CompressedTextureError::UnsupportedFormat);
+        }
+
+        let mip_count = read_u32(bytes, 56).max(1);
+        let key_value_bytes = read_u32(bytes, 60) as usize;
+
+        let dimensions = Dimensions::new(width, height);
+
+        let mut offset = 64 + key_value_bytes;
+
+        let mut levels = Vec::with_capacity(mip_count as usize);
+
+        for level in 0..mip_count {
+            if offset + 4 > bytes.len() {
+                return Err(CompressedTextureError::Truncated);
+            }
+
+            let size = read_u32(bytes, offset) as usize;
+
+            offset += 4;
+
+            if size < format.image_bytes(level_dimensions(dimensions, level as usize)) || offset + size > bytes.len() {
+                return Err(CompressedTextureError::Truncated);
+            }
+
+            levels.push(bytes[offset..offset + size].to_vec());
+
+            // Levels are padded to four bytes
+            offset += (size + 3) & !3;
+        }
+
+        Ok(CompressedTexture { format, dimensions, levels })
+    }
+
+    #[inline]
+    pub fn format(&self) -> BlockFormat { self.format }
+
+    /// Number of mipmap levels, including the full size image
+    #[inline]
+    pub fn levels(&self) -> usize { self.levels.len() }
+
+    /// Compressed data of the given mipmap level
+    #[inline]
+    pub fn level_data(&self, level: usize) -> &[u8] { &self.levels[level] }
+
+    /// Decodes the given mipmap level. Panics if the level does not exist.
+    pub fn decode_level(&self, level: usize) -> ColorBuffer<RGBAu8Color> {
+        decode_blocks(self.format, level_dimensions(self.dimensions, level), &self.levels[level])
+            .expect("Level data was checked when loading")
+    }
+
+    /// Decodes the full size image
+    pub fn decode(&self) -> ColorBuffer<RGBAu8Color> {
+        self.decode_level(0)
+    }
+}
+
+impl HasDimensions for CompressedTexture {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+/// Dimensions of a mipmap level, halving each side down to a minimum of one pixel
+#[inline]
+pub fn level_dimensions(dimensions: Dimensions, level: usize) -> Dimensions {
+    Dimensions::new((dimensions.width >> level).max(1), (dimensions.height >> level).max(1))
+}
+
+#[cfg(test)]
+mod test {
+    use ::pixels::PixelRead;
+
+    use super::*;
+
+    #[test]
+    fn test_bc1_block() {
+        // Endpoints pure red and pure blue, with every texel using a different palette entry per row
+        let block = [0x00, 0xF8, 0x1F, 0x00, 0x00, 0x55, 0xAA, 0xFF];
+
+        let texels = BlockFormat::Bc1.decode_block(&block);
+
+        assert_eq!(texels[0], RGBAu8Color::new(255, 0, 0, 255));
+        assert_eq!(texels[4], RGBAu8Color::new(0, 0, 255, 255));
+        assert_eq!(texels[8], RGBAu8Color::new(170, 0, 85, 255));
+        assert_eq!(texels[12], RGBAu8Color::new(85, 0, 170, 255));
+    }
+
+    #[test]
+    fn test_bc4_block() {
+        let block = [255, 0, 0, 0, 0, 0, 0, 0];
+
+        assert!(BlockFormat::Bc4.decode_block(&block).iter().all(|&t| t == RGBAu8Color::new(255, 0, 0, 255)));
+
+        // Six value mode, with index 7 selecting full white
+        let block = [0, 10, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+        assert!(BlockFormat::Bc4.decode_block(&block).iter().all(|&t| t.x == 255));
+    }
+
+    #[test]
+    fn test_dds() {
+        let mut bytes = vec![0u8; 128];
+
+        bytes[0..4].copy_from_slice(b"DDS ");
+        bytes[4] = 124;
+        bytes[12] = 2;
+        bytes[16] = 6;
+        bytes[80] = DDPF_FOURCC as u8;
+        bytes[84..88].copy_from_slice(b"DXT1");
+
+        bytes.extend_from_slice(&[0x00, 0xF8, 0x00, 0xF8, 0, 0, 0, 0]);
+        bytes.extend_from_slice(&[0xE0, 0x07, 0xE0, 0x07, 0, 0, 0, 0]);
+
+        let texture = CompressedTexture::from_dds(&bytes).unwrap();
+
+        assert_eq!(texture.levels(), 1);
+
+        let image = texture.decode();
+
+        assert_eq!(image.dimensions(), Dimensions::new(6, 2));
+        assert_eq!(image.pixel_ref(Coordinate::new(0, 0)).unwrap().get(), RGBAu8Color::new(255, 0, 0, 255));
+        assert_eq!(image.pixel_ref(Coordinate::new(5, 1)).unwrap().get(), RGBAu8Color::new(0, 255, 0, 255));
+
+        bytes.truncate(140);
+
+        assert!(CompressedTexture::from_dds(&bytes).is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! Cubemap textures
+//!
+//! Each face of a cubemap is a separate texture, so by default filtering clamps to the edge of each face.
+//! That leaves visible seams along the edges of the cube, especially on the small, blurry faces
+//! of prefiltered environment maps. A `CubeSampler` can instead filter across the edges
+//! using the texels of the adjacent faces, like `GL_TEXTURE_CUBE_MAP_SEAMLESS`:
+//!
+//! ```ignore
+//! let sampler = CubeSampler::seamless(Filter::Bilinear);
+//!
+//! let color = cubemap.sample_with(direction, &sampler);
+//! ```
+
+use alga::general::Real;
+
+use nalgebra::{Vector3, Point3, Matrix4, Isometry3, Perspective3};
+
+use num_traits::cast;
+
+use ::color::ToChannels;
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::PixelRead;
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::{Filter, sample_channels, filter_texels};
+
+/// Identifies a face of a cubemap
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum CubeFace {
+    PositiveX,
+    NegativeX,
+    PositiveY,
+    NegativeY,
+    PositiveZ,
+    NegativeZ,
+}
+
+impl CubeFace {
+    /// All faces, in the order they are stored in a `Cubemap`
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PositiveX, CubeFace::NegativeX,
+        CubeFace::PositiveY, CubeFace::NegativeY,
+        CubeFace::PositiveZ, CubeFace::NegativeZ,
+    ];
+
+    /// Index of the face within a `Cubemap`
+    #[inline]
+    pub fn index(self) -> usize {
+        match self {
+            CubeFace::PositiveX => 0,
+            CubeFace::NegativeX => 1,
+            CubeFace::PositiveY => 2,
+            CubeFace::NegativeY => 3,
+            CubeFace::PositiveZ => 4,
+            CubeFace::NegativeZ => 5,
+        }
+    }
+
+    /// Returns the forward, right and up directions of the face.
+    ///
+    /// A face image is expected to be rendered looking along forward, with right towards increasing x
+    /// and up towards the top row, which is what `view_matrix` produces.
+    pub fn basis<N: Real>(self) -> (Vector3<N>, Vector3<N>, Vector3<N>) {
+        let (o, z) = (N::one(), N::zero());
+
+        match self {
+            CubeFace::PositiveX => (Vector3::new(o, z, z), Vector3::new(z, z, o), Vector3::new(z, o, z)),
+            CubeFace::NegativeX => (Vector3::new(-o, z, z), Vector3::new(z, z, -o), Vector3::new(z, o, z)),
+            CubeFace::PositiveY => (Vector3::new(z, o, z), Vector3::new(o, z, z), Vector3::new(z, z, o)),
+            CubeFace::NegativeY => (Vector3::new(z, -o, z), Vector3::new(o, z, z), Vector3::new(z, z, -o)),
+            CubeFace::PositiveZ => (Vector3::new(z, z, o), Vector3::new(-o, z, z), Vector3::new(z, o, z)),
+            CubeFace::NegativeZ => (Vector3::new(z, z, -o), Vector3::new(o, z, z), Vector3::new(z, o, z)),
+        }
+    }
+
+    /// View matrix for rendering this face from the given position
+    pub fn view_matrix<N: Real>(self, eye: Point3<N>) -> Matrix4<N> {
+        let (forward, _, up) = self.basis::<N>();
+
+        Isometry3::look_at_rh(&eye, &(eye + forward), &up).to_homogeneous()
+    }
+
+    /// 90 degree square projection matrix for rendering a face
+    pub fn projection_matrix<N: Real>(near: N, far: N) -> Matrix4<N> {
+        Perspective3::new(N::one(), N::frac_pi_2(), near, far).to_homogeneous()
+    }
+
+    /// Returns the face a direction points into, and the normalized `[0, 1]` coordinates on that face,
+    /// with `(0, 0)` at the top-left.
+    pub fn from_direction<N: Real>(direction: Vector3<N>) -> (CubeFace, N, N) {
+        let (ax, ay, az) = (direction.x.abs(), direction.y.abs(), direction.z.abs());
+
+        let face = if ax >= ay && ax >= az {
+            if direction.x >= N::zero() { CubeFace::PositiveX } else { CubeFace::NegativeX }
+        } else if ay >= az {
+            if direction.y >= N::zero() { CubeFace::PositiveY } else { CubeFace::NegativeY }
+        } else {
+            if direction.z >= N::zero() { CubeFace::PositiveZ } else { CubeFace::NegativeZ }
+        };
+
+        let (forward, right, up) = face.basis::<N>();
+
+        let depth = direction.dot(&forward);
+        let half: N = cast(0.5).unwrap();
+
+        let u = (direction.dot(&right) / depth + N::one()) * half;
+        let v = (N::one() - direction.dot(&up) / depth) * half;
+
+        (face, u, v)
+    }
+}
+
+/// How cubemap filtering treats texels beyond the edges of a face
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum CubeEdge {
+    /// Clamps to the edge of each face, which can leave visible seams
+    Clamp,
+    /// Uses the texels of the adjacent faces, filtering across the edges without seams
+    Seamless,
+    /// Uses a constant color beyond the edges of each face
+    Border([f32; 4]),
+}
+
+impl Default for CubeEdge {
+    fn default() -> CubeEdge { CubeEdge::Clamp }
+}
+
+/// Settings for sampling cubemaps
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub struct CubeSampler {
+    /// Filter used within each face
+    pub filter: Filter,
+    /// Handling of texels beyond the edges of each face
+    pub edge: CubeEdge,
+    /// Maximum number of samples `Cubemap::sample_grad` takes along the longer axis of the footprint.
+    ///
+    /// `1` disables anisotropic filtering.
+    pub max_anisotropy: u32,
+}
+
+impl Default for CubeSampler {
+    fn default() -> CubeSampler { CubeSampler::new(Filter::default()) }
+}
+
+impl CubeSampler {
+    /// Sampler clamping to the edge of each face, without anisotropic filtering
+    pub fn new(filter: Filter) -> CubeSampler {
+        CubeSampler { filter, edge: CubeEdge::Clamp, max_anisotropy: 1 }
+    }
+
+    /// Sampler filtering across the edges of faces, without anisotropic filtering
+    pub fn seamless(filter: Filter) -> CubeSampler {
+        CubeSampler { filter, edge: CubeEdge::Seamless, max_anisotropy: 1 }
+    }
+}
+
+/// Six square textures forming the faces of a cube, indexed by direction
+#[derive(Debug, Clone)]
+pub struct Cubemap<T> {
+    faces: Vec<T>,
+}
+
+impl<T> MemoryUsage for Cubemap<T> where T: MemoryUsage {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        for (&face, texture) in CubeFace::ALL.iter().zip(&self.faces) {
+            report.merge(&format!("{:?}", face), texture.memory_report());
+        }
+
+        report
+    }
+}
+
+impl<T> Cubemap<T> {
+    /// Creates a cubemap from six faces, in the order of `CubeFace::ALL`
+    pub fn new(faces: Vec<T>) -> Cubemap<T> {
+        assert_eq!(faces.len(), 6, "Cubemaps must have exactly six faces");
+
+        Cubemap { faces }
+    }
+
+    /// Creates a cubemap by calling `f` for each face, in the order of `CubeFace::ALL`
+    pub fn from_fn<F>(mut f: F) -> Cubemap<T> where F: FnMut(CubeFace) -> T {
+        Cubemap { faces: CubeFace::ALL.iter().map(|&face| f(face)).collect() }
+    }
+
+    /// Returns the texture of the given face
+    #[inline]
+    pub fn face(&self, face: CubeFace) -> &T {
+        &self.faces[face.index()]
+    }
+
+    /// Returns the mutable texture of the given face
+    #[inline]
+    pub fn face_mut(&mut self, face: CubeFace) -> &mut T {
+        &mut self.faces[face.index()]
+    }
+
+    /// Samples normalized channels in the given direction, which does not need to be normalized.
+    ///
+    /// Filtering does not cross face edges.
+    pub fn sample<N: Real>(&self, direction: Vector3<N>, filter: Filter) -> [f32; 4]
+        where T: PixelRead, T::Color: ToChannels {
+        let (face, u, v) = CubeFace::from_direction(direction);
+
+        let texture = self.face(face);
+        let dimensions = texture.dimensions();
+
+        let u: f32 = cast(u).unwrap();
+        let v: f32 = cast(v).unwrap();
+
+        sample_channels(texture, u * dimensions.width as f32, v * dimensions.height as f32, filter)
+    }
+
+    /// Samples normalized channels in the given direction, which does not need to be normalized,
+    /// handling the edges of faces as set by the sampler.
+    pub fn sample_with<N: Real>(&self, direction: Vector3<N>, sampler: &CubeSampler) -> [f32; 4]
+        where T: PixelRead, T::Color: ToChannels {
+        let (face, u, v) = CubeFace::from_direction(direction);
+
+        let dimensions = self.face(face).dimensions();
+
+        let u: f32 = cast(u).unwrap();
+        let v: f32 = cast(v).unwrap();
+
+        filter_texels(u * dimensions.width as f32, v * dimensions.height as f32, sampler.filter,
+                      |x, y| self.fetch(face, x, y, sampler.edge))
+    }
+
+    /// Samples normalized channels in the given direction, given how much the direction changes
+    /// from one pixel to the next in x and y, such as from the differences between neighboring fragments.
+    ///
+    /// Where the footprint of the pixel on the cubemap is stretched, up to `max_anisotropy` samples are averaged
+    /// along its longer axis, keeping surfaces seen at grazing angles sharp without aliasing.
+    pub fn sample_grad<N: Real>(&self, direction: Vector3<N>, ddx: Vector3<N>, ddy: Vector3<N>, sampler: &CubeSampler) -> [f32; 4]
+        where T: PixelRead, T::Color: ToChannels {
+        let (face, _, _) = CubeFace::from_direction(direction);
+
+        let dimensions = self.face(face).dimensions();
+        let (forward, right, up) = face.basis::<N>();
+
+        // Texel coordinates on the face of the sampled direction, or None behind it
+        let project = |d: Vector3<N>| {
+            let depth: f32 = cast(d.dot(&forward)).unwrap();
+
+            if depth > 0.0 {
+                let x: f32 = cast(d.dot(&right)).unwrap();
+                let y: f32 = cast(d.dot(&up)).unwrap();
+
+                Some((x / depth * 0.5 * dimensions.width as f32, y / depth * -0.5 * dimensions.height as f32))
+            } else {
+                None
+            }
+        };
+
+        let footprint = match (project(direction), project(direction + ddx), project(direction + ddy)) {
+            (Some(p), Some(px), Some(py)) => {
+                let lx = ((px.0 - p.0).powi(2) + (px.1 - p.1).powi(2)).sqrt();
+                let ly = ((py.0 - p.0).powi(2) + (py.1 - p.1).powi(2)).sqrt();
+
+                Some((lx, ly))
+            }
+            _ => None,
+        };
+
+        let (taps, axis) = match footprint {
+            Some((lx, ly)) => {
+                let (major, minor, axis) = if lx >= ly { (lx, ly, ddx) } else { (ly, lx, ddy) };
+
+                let ratio = (major / minor.max(1.0)).ceil();
+
+                (if ratio.is_finite() { (ratio as u32).max(1).min(sampler.max_anisotropy.max(1)) } else { 1 }, axis)
+            }
+            None => (1, ddx),
+        };
+
+        if taps <= 1 {
+            return self.sample_with(direction, sampler);
+        }
+
+        let mut sum = [0.0; 4];
+
+        let weight = 1.0 / taps as f32;
+
+        for i in 0..taps {
+            let t: N = cast((i as f32 + 0.5) * weight - 0.5).unwrap();
+
+            let sample = self.sample_with(direction + axis * t, sampler);
+
+            for c in 0..4 {
+                sum[c] += sample[c] * weight;
+            }
+        }
+
+        sum
+    }
+
+    /// Fetches a texel of a face, which may lie beyond the edges of the face
+    fn fetch(&self, face: CubeFace, x: i64, y: i64, edge: CubeEdge) -> [f32; 4] where T: PixelRead, T::Color: ToChannels {
+        let texture = self.face(face);
+        let dimensions = texture.dimensions();
+
+        let (width, height) = (dimensions.width as i64, dimensions.height as i64);
+
+        if x < 0 || y < 0 || x >= width || y >= height {
+            match edge {
+                CubeEdge::Clamp => {}
+                CubeEdge::Border(color) => return color,
+                CubeEdge::Seamless => {
+                    // Extend the face plane to the center of the texel, and find where that direction lands on the cube
+                    let u = (x as f32 + 0.5) / width as f32;
+                    let v = (y as f32 + 0.5) / height as f32;
+
+                    let (forward, right, up) = face.basis::<f32>();
+
+                    let direction = forward + right * (u * 2.0 - 1.0) + up * (1.0 - v * 2.0);
+
+                    let (face, u, v) = CubeFace::from_direction(direction);
+
+                    let texture = self.face(face);
+                    let dimensions = texture.dimensions();
+
+                    let x = (u * dimensions.width as f32).floor().max(0.0).min(dimensions.width as f32 - 1.0);
+                    let y = (v * dimensions.height as f32).floor().max(0.0).min(dimensions.height as f32 - 1.0);
+
+                    let coord = Coordinate::new(x as u32, y as u32);
+
+                    return unsafe { texture.get_pixel_unchecked(texture.index_of(coord)).to_channels() };
+                }
+            }
+        }
+
+        let coord = Coordinate::new(x.max(0).min(width - 1) as u32, y.max(0).min(height - 1) as u32);
+
+        unsafe { texture.get_pixel_unchecked(texture.index_of(coord)).to_channels() }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::pixels::ColorBuffer;
+    use ::color::predefined::formats::RGBAf32Color;
+
+    use super::*;
+
+    #[test]
+    fn test_seamless_edges() {
+        // +X is black and every other face white, so only filtering across the edge brightens +X
+        let cubemap = Cubemap::from_fn(|face| {
+            let v = if face == CubeFace::PositiveX { 0.0 } else { 1.0 };
+
+            ColorBuffer::from_fn(Dimensions::new(4, 4), |_| RGBAf32Color::new(v, v, v, 1.0))
+        });
+
+        // Half a texel from the edge towards +Z
+        let direction = Vector3::new(1.0f32, 0.0, 0.99);
+
+        let clamped = cubemap.sample_with(direction, &CubeSampler::new(Filter::Bilinear));
+        let seamless = cubemap.sample_with(direction, &CubeSampler::seamless(Filter::Bilinear));
+
+        let mut border = CubeSampler::new(Filter::Bilinear);
+        border.edge = CubeEdge::Border([0.5; 4]);
+
+        let bordered = cubemap.sample_with(direction, &border);
+
+        assert_eq!(clamped[0], 0.0);
+        assert!(seamless[0] > 0.4 && seamless[0] < 0.6);
+        assert!(bordered[0] > 0.2 && bordered[0] < 0.3);
+
+        // Away from the edges, all of them match
+        let center = Vector3::new(1.0f32, 0.1, 0.1);
+
+        assert_eq!(cubemap.sample_with(center, &CubeSampler::seamless(Filter::Bilinear)),
+                   cubemap.sample(center, Filter::Bilinear));
+    }
+}
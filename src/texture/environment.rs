@@ -0,0 +1,230 @@
+//! Environment-mapped reflections and refraction
+//!
+//! An `EnvironmentMap` is a cubemap with a full chain of progressively blurrier mipmap levels,
+//! so reflections can be sampled sharply for smooth surfaces and blurry for rough ones,
+//! by selecting a level of detail from the roughness of the surface.
+//!
+//! Levels are built with a simple box filter, which is a cheap approximation of prefiltering
+//! the environment for each roughness, but is good enough for most materials.
+//!
+//! The faces of the blurrier levels are only a few texels wide, so levels are sampled seamlessly by default,
+//! filtering across the edges of faces instead of showing the outline of the cube on rough surfaces.
+
+use alga::general::Real;
+
+use nalgebra::Vector3;
+
+use ::color::{Color, ToChannels, FromChannels};
+use ::geometry::{Coordinate, HasDimensions};
+use ::pixels::{ColorBuffer, PixelRead};
+
+use super::Filter;
+use super::cubemap::{CubeFace, Cubemap, CubeEdge, CubeSampler};
+use super::compressed::level_dimensions;
+
+/// Reflects the incident direction about the normal, which must be normalized.
+///
+/// Same as `reflect` in GLSL, with the incident direction pointing towards the surface.
+pub fn reflect<N: Real>(incident: Vector3<N>, normal: Vector3<N>) -> Vector3<N> {
+    incident - normal * ((N::one() + N::one()) * normal.dot(&incident))
+}
+
+/// Refracts the incident direction through a surface with the given normal,
+/// where `eta` is the ratio of the indices of refraction on the incident side to the other side.
+/// Both directions must be normalized.
+///
+/// Returns `None` for total internal reflection.
+pub fn refract<N: Real>(incident: Vector3<N>, normal: Vector3<N>, eta: N) -> Option<Vector3<N>> {
+    let cos_i = normal.dot(&incident);
+
+    let k = N::one() - eta * eta * (N::one() - cos_i * cos_i);
+
+    if k < N::zero() {
+        None
+    } else {
+        Some(incident * eta - normal * (eta * cos_i + k.sqrt()))
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance, given the cosine of the angle between
+/// the view direction and the normal, and the reflectance at normal incidence.
+pub fn fresnel_schlick(cos_theta: f32, f0: f32) -> f32 {
+    let m = (1.0 - cos_theta.max(0.0).min(1.0)).powi(5);
+
+    f0 + (1.0 - f0) * m
+}
+
+/// Halves a buffer in each dimension, averaging each block of two by two pixels
+fn downsample<C>(buffer: &ColorBuffer<C>) -> ColorBuffer<C> where C: Color + ToChannels + FromChannels {
+    let dimensions = buffer.dimensions();
+
+    let xmax = dimensions.width - 1;
+    let ymax = dimensions.height - 1;
+
+    ColorBuffer::from_fn(level_dimensions(dimensions, 1), |coord| {
+        let mut sum = [0.0; 4];
+
+        for &(dx, dy) in &[(0, 0), (1, 0), (0, 1), (1, 1)] {
+            let sample = Coordinate::new((coord.x * 2 + dx).min(xmax), (coord.y * 2 + dy).min(ymax));
+
+            let c = unsafe { buffer.get_pixel_unchecked(buffer.index_of(sample)).to_channels() };
+
+            for i in 0..4 {
+                sum[i] += c[i] * 0.25;
+            }
+        }
+
+        C::from_channels(sum)
+    })
+}
+
+/// A cubemap with a chain of mipmap levels for roughness-based reflections
+#[derive(Debug, Clone)]
+pub struct EnvironmentMap<C: Color> {
+    levels: Vec<Cubemap<ColorBuffer<C>>>,
+    edge: CubeEdge,
+}
+
+impl<C> EnvironmentMap<C> where C: Color + ToChannels + FromChannels {
+    /// Builds every mipmap level of the cubemap, down to faces of a single pixel
+    pub fn new(cubemap: Cubemap<ColorBuffer<C>>) -> EnvironmentMap<C> {
+        let mut levels = vec![cubemap];
+
+        loop {
+            let next = {
+                let last = levels.last().unwrap();
+
+                let dimensions = last.face(CubeFace::PositiveX).dimensions();
+
+                if dimensions.width <= 1 && dimensions.height <= 1 {
+                    break;
+                }
+
+                Cubemap::from_fn(|face| downsample(last.face(face)))
+            };
+
+            levels.push(next);
+        }
+
+        EnvironmentMap { levels, edge: CubeEdge::Seamless }
+    }
+
+    /// Handling of the edges of faces when sampling, which is `CubeEdge::Seamless` by default
+    #[inline]
+    pub fn edge(&self) -> CubeEdge { self.edge }
+
+    /// Sets the handling of the edges of faces when sampling
+    #[inline]
+    pub fn set_edge(&mut self, edge: CubeEdge) {
+        self.edge = edge;
+    }
+
+    /// Number of mipmap levels, including the full size cubemap
+    #[inline]
+    pub fn levels(&self) -> usize { self.levels.len() }
+
+    /// Returns the cubemap of the given mipmap level
+    #[inline]
+    pub fn level(&self, level: usize) -> &Cubemap<ColorBuffer<C>> {
+        &self.levels[level]
+    }
+
+    /// Level of detail used for the given roughness, from `0.0` for mirror-like surfaces
+    /// to the smallest level for fully rough surfaces
+    pub fn roughness_lod(&self, roughness: f32) -> f32 {
+        roughness.max(0.0).min(1.0) * (self.levels.len() - 1) as f32
+    }
+
+    /// Samples normalized channels in the given direction at a fractional level of detail,
+    /// filtering bilinearly within levels and linearly between them.
+    pub fn sample_lod<N: Real>(&self, direction: Vector3<N>, lod: f32) -> [f32; 4] {
+        let lod = lod.max(0.0).min((self.levels.len() - 1) as f32);
+
+        let lower = lod.floor() as usize;
+        let t = lod - lower as f32;
+
+        let sampler = CubeSampler { edge: self.edge, ..CubeSampler::new(Filter::Bilinear) };
+
+        let a = self.levels[lower].sample_with(direction, &sampler);
+
+        if t <= 0.0 {
+            return a;
+        }
+
+        let b = self.levels[lower + 1].sample_with(direction, &sampler);
+
+        [a[0] + (b[0] - a[0]) * t,
+         a[1] + (b[1] - a[1]) * t,
+         a[2] + (b[2] - a[2]) * t,
+         a[3] + (b[3] - a[3]) * t]
+    }
+
+    /// Samples the environment in the given direction, blurred by the roughness of the surface
+    pub fn sample_rough<N: Real>(&self, direction: Vector3<N>, roughness: f32) -> [f32; 4] {
+        self.sample_lod(direction, self.roughness_lod(roughness))
+    }
+
+    /// Samples the environment reflected by a surface, given the normalized direction from the eye
+    /// to the surface and the normalized surface normal
+    pub fn reflection<N: Real>(&self, view: Vector3<N>, normal: Vector3<N>, roughness: f32) -> [f32; 4] {
+        self.sample_rough(reflect(view, normal), roughness)
+    }
+
+    /// Samples the environment refracted through a surface, given the normalized direction from the eye
+    /// to the surface, the normalized surface normal, and the ratio of indices of refraction.
+    ///
+    /// Falls back to the reflection for total internal reflection.
+    pub fn refraction<N: Real>(&self, view: Vector3<N>, normal: Vector3<N>, eta: N, roughness: f32) -> [f32; 4] {
+        let direction = refract(view, normal, eta).unwrap_or_else(|| reflect(view, normal));
+
+        self.sample_rough(direction, roughness)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::geometry::Dimensions;
+    use ::color::predefined::formats::RGBAf32Color;
+
+    use super::*;
+
+    #[test]
+    fn test_reflect_refract() {
+        let incident = Vector3::new(1.0f32, -1.0, 0.0).normalize();
+        let normal = Vector3::y();
+
+        assert!((reflect(incident, normal) - Vector3::new(1.0, 1.0, 0.0).normalize()).norm() < 1e-6);
+
+        // Matching indices pass straight through
+        assert!((refract(incident, normal, 1.0).unwrap() - incident).norm() < 1e-6);
+
+        // Leaving glass at a shallow angle is totally reflected
+        let shallow = Vector3::new(1.0f32, -0.2, 0.0).normalize();
+
+        assert!(refract(shallow, normal, 1.5).is_none());
+
+        assert!((fresnel_schlick(1.0, 0.04) - 0.04).abs() < 1e-6);
+        assert!((fresnel_schlick(0.0, 0.04) - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_environment_levels() {
+        // Checkerboard faces, which average out to grey in the smallest level
+        let cubemap = Cubemap::from_fn(|_| ColorBuffer::from_fn(Dimensions::new(8, 8), |coord| {
+            let v = ((coord.x + coord.y) % 2) as f32;
+
+            RGBAf32Color::new(v, v, v, 1.0)
+        }));
+
+        let environment = EnvironmentMap::new(cubemap);
+
+        assert_eq!(environment.levels(), 4);
+        assert_eq!(environment.roughness_lod(1.0), 3.0);
+
+        let sharp = environment.sample_rough(Vector3::new(0.0f32, 0.0, 1.0), 0.0);
+        let rough = environment.sample_rough(Vector3::new(0.0f32, 0.0, 1.0), 1.0);
+
+        assert!((rough[0] - 0.5).abs() < 1e-6);
+        assert!(sharp[0] >= 0.0 && sharp[0] <= 1.0);
+    }
+}
@@ -0,0 +1,141 @@
+//! Texture handling
+use nalgebra::Vector2;
+
+use ::error::RenderResult;
+
+use ::numeric::FloatScalar;
+use ::color::{Color, ToChannels};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::geometry::{Coordinate, HasDimensions, PixelCenter};
+
+pub mod cubemap;
+pub mod compressed;
+pub mod volume;
+pub mod ramp;
+pub mod environment;
+pub mod registry;
+pub mod shadow;
+
+pub use self::cubemap::{CubeFace, Cubemap, CubeEdge, CubeSampler};
+pub use self::compressed::{BlockFormat, CompressedTexture, CompressedTextureError};
+pub use self::volume::{Texture3D, Texture3DSlice, Texture3DSliceMut};
+pub use self::ramp::Texture1D;
+pub use self::environment::{EnvironmentMap, reflect, refract};
+pub use self::registry::{TextureRegistry, TextureHandle};
+pub use self::shadow::{ShadowMap, ShadowSampler, Pcss};
+
+pub type TextureColor<T> = <T as PixelBuffer>::Color;
+
+/// A more traditional texture sampling method reminiscent of OpenGL.
+pub fn texture<T: TextureRead, N: FloatScalar>(t: &T, coord: Vector2<N>,
+                                               filter: Filter,
+                                               edge: Edge<TextureColor<T>>) -> RenderResult<TextureColor<T>> {
+    t.sample(coord, filter, edge)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_compat", derive(Serialize, Deserialize))]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+impl Default for Filter {
+    fn default() -> Filter {
+        Filter::Nearest
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Edge<C: Color> {
+    /// Equivalent to `GL_CLAMP_TO_EDGE`
+    Clamp,
+    /// Equivalent to `GL_REPEAT`
+    Wrap,
+    /// Equivalent to `GL_CLAMP_TO_BORDER`
+    Border(C)
+}
+
+impl<C: Color> Default for Edge<C> {
+    fn default() -> Edge<C> { Edge::Clamp }
+}
+
+/// Samples normalized channels at a pixel-space coordinate, where pixel centers lie at half-integer coordinates.
+///
+/// Coordinates outside of the buffer are clamped to the edge.
+pub fn sample_channels<T>(t: &T, x: f32, y: f32, filter: Filter) -> [f32; 4] where T: PixelRead, T::Color: ToChannels {
+    let dimensions = t.dimensions();
+
+    let xmax = dimensions.width as i64 - 1;
+    let ymax = dimensions.height as i64 - 1;
+
+    filter_texels(x, y, filter, |x, y| {
+        let coord = Coordinate::new(x.max(0).min(xmax) as u32, y.max(0).min(ymax) as u32);
+
+        unsafe { t.get_pixel_unchecked(t.index_of(coord)).to_channels() }
+    })
+}
+
+/// Samples normalized channels at a pixel-space coordinate given in the `center` convention.
+///
+/// With `PixelCenter::Integer`, texel `(x, y)` is sampled exactly at `(x, y)`, matching the half-texel offset of Direct3D 9.
+pub fn sample_channels_centered<T>(t: &T, x: f32, y: f32, filter: Filter, center: PixelCenter) -> [f32; 4] where T: PixelRead, T::Color: ToChannels {
+    sample_channels(t, center.to_half_integer(x), center.to_half_integer(y), filter)
+}
+
+/// Filters texels at a pixel-space coordinate, leaving coordinates outside of the texture to `fetch`
+pub ( in ::texture ) fn filter_texels<F>(x: f32, y: f32, filter: Filter, fetch: F) -> [f32; 4] where F: Fn(i64, i64) -> [f32; 4] {
+    match filter {
+        Filter::Nearest => fetch(x.floor() as i64, y.floor() as i64),
+        Filter::Bilinear => {
+            let x = x - 0.5;
+            let y = y - 0.5;
+
+            let (x0, y0) = (x.floor(), y.floor());
+            let (fx, fy) = (x - x0, y - y0);
+            let (x0, y0) = (x0 as i64, y0 as i64);
+
+            let (a, b, c, d) = (fetch(x0, y0), fetch(x0 + 1, y0), fetch(x0, y0 + 1), fetch(x0 + 1, y0 + 1));
+
+            let mut out = [0.0; 4];
+
+            for i in 0..4 {
+                let top = a[i] + (b[i] - a[i]) * fx;
+                let bottom = c[i] + (d[i] - c[i]) * fx;
+
+                out[i] = top + (bottom - top) * fy;
+            }
+
+            out
+        }
+    }
+}
+
+pub trait Texture: PixelBuffer {}
+
+pub trait TextureRead: Texture + PixelRead {
+    /// Samples a pixel from a floating-point coordinate, applying the selected `Filter` and `Edge` behavior.
+    fn sample<N: FloatScalar>(&self, _coord: Vector2<N>, _filter: Filter, _edge: Edge<TextureColor<Self>>) -> RenderResult<TextureColor<Self>> {
+        unimplemented!()
+    }
+}
+
+pub trait TextureWrite: Texture + PixelWrite {
+    /// "Unsamples", or writes, to a floating-point coordinate, applying the selected `Filter` and `Edge` behavior.
+    ///
+    /// This allows writing to multiple pixels based on fractional coordinates.
+    fn unsample<N: FloatScalar>(&mut self, _coord: Vector2<N>, _filter: Filter, _edge: Edge<TextureColor<Self>>) -> RenderResult<()> {
+        unimplemented!()
+    }
+}
+
+impl<T> Texture for T where T: PixelBuffer {}
+
+impl<T> TextureRead for T where T: Texture + PixelRead {}
+
+impl<T> TextureWrite for T where T: Texture + PixelWrite {}
+
+//pub struct SliceTexture<'a, C: Color + 'a> {
+//    slice: &'a C,
+//    dimensions: Dimensions,
+//}
\ No newline at end of file
@@ -0,0 +1,239 @@
+//! 1D textures and gradient ramps
+//!
+//! Lookup tables sampled with a single normalized coordinate, for transfer functions, toon shading ramps and colormaps.
+//! The perceptually uniform `viridis` and `inferno` colormaps from matplotlib are built in, for visualizing scalar data.
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::color::{Color, ToChannels, FromChannels};
+use ::color::predefined::formats::RGBAf32Color;
+use ::geometry::{Dimensions, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::Filter;
+
+/// Resolution of the built-in colormaps
+const COLORMAP_WIDTH: usize = 256;
+
+/// Viridis sampled at nine evenly spaced points
+const VIRIDIS: [u32; 9] = [0x440154, 0x472C7A, 0x3B518B, 0x2C718E, 0x21908D, 0x27AD81, 0x5CC863, 0xAADC32, 0xFDE725];
+
+/// Inferno sampled at nine evenly spaced points
+const INFERNO: [u32; 9] = [0x000004, 0x1F0C48, 0x550F6D, 0x88226A, 0xBA3655, 0xE35933, 0xF98E09, 0xF9CB35, 0xFCFFA4];
+
+/// A row of texels sampled with a normalized coordinate, where `0` is the left edge of the first texel
+/// and `1` the right edge of the last.
+///
+/// It is also a pixel buffer with a height of one, so it can be rendered into or converted like any other.
+#[derive(Debug, Clone)]
+pub struct Texture1D<C: Color> {
+    texels: Vec<C>,
+}
+
+impl<C: Color> Texture1D<C> {
+    /// Creates a texture of the given width with every texel set to `Color::empty()`
+    pub fn new(width: usize) -> Texture1D<C> {
+        Texture1D::from_vec(vec![C::empty(); width])
+    }
+
+    /// Wraps existing texels. Panics if there are none.
+    pub fn from_vec(texels: Vec<C>) -> Texture1D<C> {
+        assert!(!texels.is_empty(), "1D textures need at least one texel");
+
+        Texture1D { texels }
+    }
+
+    /// Creates a texture by calling `f` with the normalized coordinate of the center of every texel
+    pub fn from_fn<F>(width: usize, mut f: F) -> Texture1D<C> where F: FnMut(f32) -> C {
+        Texture1D::from_vec((0..width).map(|i| f((i as f32 + 0.5) / width as f32)).collect())
+    }
+
+    /// Creates a gradient from `(position, color)` stops sorted by position, interpolating linearly between them.
+    ///
+    /// Positions before the first stop or after the last take the color of that stop.
+    pub fn from_stops(width: usize, stops: &[(f32, C)]) -> Texture1D<C> where C: ToChannels + FromChannels {
+        assert!(!stops.is_empty(), "Gradients need at least one stop");
+
+        Texture1D::from_fn(width, |t| {
+            let next = stops.iter().position(|&(position, _)| position > t).unwrap_or(stops.len());
+
+            if next == 0 {
+                return stops[0].1;
+            } else if next == stops.len() {
+                return stops[stops.len() - 1].1;
+            }
+
+            let (p0, c0) = stops[next - 1];
+            let (p1, c1) = stops[next];
+
+            let f = (t - p0) / (p1 - p0);
+
+            let (a, b) = (c0.to_channels(), c1.to_channels());
+
+            let mut out = [0.0; 4];
+
+            for i in 0..4 {
+                out[i] = a[i] + (b[i] - a[i]) * f;
+            }
+
+            C::from_channels(out)
+        })
+    }
+
+    /// A toon shading ramp of `bands` flat steps from `dark` to `light`
+    pub fn bands(width: usize, bands: usize, dark: C, light: C) -> Texture1D<C> where C: ToChannels + FromChannels {
+        assert!(bands > 1, "Ramps need at least two bands");
+
+        let (a, b) = (dark.to_channels(), light.to_channels());
+
+        Texture1D::from_fn(width, |t| {
+            let f = (t * bands as f32).floor().min(bands as f32 - 1.0) / (bands - 1) as f32;
+
+            let mut out = [0.0; 4];
+
+            for i in 0..4 {
+                out[i] = a[i] + (b[i] - a[i]) * f;
+            }
+
+            C::from_channels(out)
+        })
+    }
+
+    /// Number of texels
+    #[inline]
+    pub fn width(&self) -> usize { self.texels.len() }
+
+    #[inline]
+    pub fn as_slice(&self) -> &[C] { &self.texels }
+
+    #[inline]
+    pub fn as_mut_slice(&mut self) -> &mut [C] { &mut self.texels }
+
+    /// Samples normalized channels at a normalized coordinate, clamping coordinates outside of `[0, 1]` to the edge
+    pub fn sample<N: FloatScalar>(&self, t: N, filter: Filter) -> [f32; 4] where C: ToChannels {
+        let x: f32 = cast::<N, f32>(t).unwrap() * self.texels.len() as f32;
+
+        let max = self.texels.len() as i64 - 1;
+
+        let fetch = |i: i64| self.texels[i.max(0).min(max) as usize].to_channels();
+
+        match filter {
+            Filter::Nearest => fetch(x.floor() as i64),
+            Filter::Bilinear => {
+                let x = x - 0.5;
+                let x0 = x.floor();
+                let f = x - x0;
+
+                let (a, b) = (fetch(x0 as i64), fetch(x0 as i64 + 1));
+
+                let mut out = [0.0; 4];
+
+                for i in 0..4 {
+                    out[i] = a[i] + (b[i] - a[i]) * f;
+                }
+
+                out
+            }
+        }
+    }
+
+    /// Samples the texture with linear filtering, returning a color
+    #[inline]
+    pub fn sample_color<N: FloatScalar>(&self, t: N) -> C where C: ToChannels + FromChannels {
+        C::from_channels(self.sample(t, Filter::Bilinear))
+    }
+}
+
+fn colormap(stops: &[u32]) -> Texture1D<RGBAf32Color> {
+    let last = (stops.len() - 1) as f32;
+
+    let stops: Vec<_> = stops.iter().enumerate().map(|(i, &c)| {
+        let channel = |shift: u32| ((c >> shift) & 0xFF) as f32 / 255.0;
+
+        (i as f32 / last, RGBAf32Color::new(channel(16), channel(8), channel(0), 1.0))
+    }).collect();
+
+    Texture1D::from_stops(COLORMAP_WIDTH, &stops)
+}
+
+impl Texture1D<RGBAf32Color> {
+    /// The viridis colormap, going from dark blue through green to yellow.
+    ///
+    /// Colors are gamma-encoded, ready for display.
+    pub fn viridis() -> Texture1D<RGBAf32Color> {
+        colormap(&VIRIDIS)
+    }
+
+    /// The inferno colormap, going from black through purple and red to pale yellow.
+    ///
+    /// Colors are gamma-encoded, ready for display.
+    pub fn inferno() -> Texture1D<RGBAf32Color> {
+        colormap(&INFERNO)
+    }
+}
+
+impl<C: Color> HasDimensions for Texture1D<C> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { Dimensions::new(self.texels.len() as u32, 1) }
+}
+
+impl<C: Color> PixelBuffer for Texture1D<C> {
+    type Color = C;
+}
+
+impl<C: Color> PixelRead for Texture1D<C> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> C {
+        *self.texels.get_unchecked(index)
+    }
+}
+
+impl<C: Color> PixelWrite for Texture1D<C> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: C) {
+        *self.texels.get_unchecked_mut(index) = color;
+    }
+}
+
+impl<C: Color> MemoryUsage for Texture1D<C> {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("texels", &self.texels);
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::Rf32Color;
+
+    use super::*;
+
+    #[test]
+    fn test_gradient_stops() {
+        let ramp = Texture1D::from_stops(4, &[(0.25, Rf32Color::new(0.0)), (0.75, Rf32Color::new(1.0))]);
+
+        assert_eq!(ramp.as_slice(), &[Rf32Color::new(0.0), Rf32Color::new(0.25), Rf32Color::new(0.75), Rf32Color::new(1.0)]);
+
+        assert_eq!(ramp.sample(0.5f32, Filter::Bilinear)[0], 0.5);
+        assert_eq!(ramp.sample(-1.0f32, Filter::Nearest)[0], 0.0);
+    }
+
+    #[test]
+    fn test_colormaps() {
+        let viridis = Texture1D::viridis();
+
+        assert_eq!(viridis.width(), COLORMAP_WIDTH);
+        assert!((viridis.sample_color(0.0f32).x - 0x44 as f32 / 255.0).abs() < 0.01);
+
+        let bands = Texture1D::bands(8, 4, Rf32Color::new(0.0), Rf32Color::new(0.9));
+
+        assert_eq!(bands.as_slice()[0], Rf32Color::new(0.0));
+        assert_eq!(bands.as_slice()[2], bands.as_slice()[3]);
+        assert_eq!(bands.as_slice()[7], Rf32Color::new(0.9));
+    }
+}
@@ -0,0 +1,254 @@
+//! Shared texture storage with typed handles
+//!
+//! Fragment shaders run on every render thread at once, so any textures they sample have to be shared between threads.
+//! Capturing owned textures in shader closures works, but copies them around with the closure and ties them to a single draw.
+//!
+//! A `TextureRegistry` owns textures behind `Arc`s and hands out small `Copy` handles instead,
+//! which can be stored in uniforms or captured by closures for free:
+//!
+//! ```ignore
+//! let mut textures = TextureRegistry::new();
+//!
+//! let albedo = textures.insert(load_albedo());
+//! let normals = textures.insert(load_normals());
+//!
+//! let textures = Arc::new(textures);
+//!
+//! fragment_shader.run(|v, _| {
+//!     let color = texture(&textures[albedo], v.uniforms.uv, Filter::Bilinear, Edge::Wrap).unwrap();
+//!     // ...
+//! });
+//! ```
+//!
+//! Textures can't be changed once inserted, so registries can be shared between pipelines and threads
+//! without any locking. To replace a texture, insert the new one and use its handle instead.
+//!
+//! ## Binding textures through uniforms
+//!
+//! Rather than capturing it in every shader, the registry can be kept in the pipeline uniforms,
+//! which are passed to the fragment shader and never interpolated. Handles chosen per mesh or per vertex
+//! can then be passed along in the intermediate uniforms, since interpolating a handle just keeps the first vertex's:
+//!
+//! ```ignore
+//! struct Globals {
+//!     textures: Arc<TextureRegistry<Texture>>,
+//! }
+//!
+//! declare_uniforms!(
+//!     pub struct MyUniforms {
+//!         pub uv: Vector2<f32>,
+//!         pub albedo: TextureHandle<Texture>,
+//!     }
+//! );
+//!
+//! fragment_shader.run(|v, globals| {
+//!     let albedo = &globals.textures[v.uniforms.albedo];
+//!     // ...
+//! });
+//! ```
+//!
+//! Other values that shouldn't be interpolated can be wrapped in [`Flat`](../../numeric/interpolate/struct.Flat.html).
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering, ATOMIC_USIZE_INIT};
+use std::marker::PhantomData;
+use std::ops::Index;
+use std::hash::{Hash, Hasher};
+use std::fmt::{Debug, Formatter, Result as FmtResult};
+
+use num_traits::Float;
+
+use ::memory::{MemoryUsage, MemoryReport};
+use ::interpolate::Interpolate;
+
+static NEXT_REGISTRY_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Handle to a texture of type `T` in a `TextureRegistry`
+pub struct TextureHandle<T> {
+    registry: usize,
+    index: usize,
+    texture: PhantomData<fn() -> T>,
+}
+
+// Manual implementations, so handles are `Copy` and comparable regardless of the texture type
+
+impl<T> Clone for TextureHandle<T> {
+    fn clone(&self) -> TextureHandle<T> { *self }
+}
+
+impl<T> Copy for TextureHandle<T> {}
+
+impl<T> PartialEq for TextureHandle<T> {
+    fn eq(&self, other: &TextureHandle<T>) -> bool {
+        self.registry == other.registry && self.index == other.index
+    }
+}
+
+impl<T> Eq for TextureHandle<T> {}
+
+impl<T> Hash for TextureHandle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.registry.hash(state);
+        self.index.hash(state);
+    }
+}
+
+impl<T> Debug for TextureHandle<T> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "TextureHandle({}:{})", self.registry, self.index)
+    }
+}
+
+impl<T> TextureHandle<T> {
+    /// Position of the texture within its registry, in order of insertion
+    #[inline]
+    pub fn index(&self) -> usize { self.index }
+}
+
+/// Handles aren't interpolated, every fragment uses the handle of the first vertex
+impl<T> Interpolate for TextureHandle<T> {
+    #[inline(always)]
+    fn barycentric_interpolate<R: Float>(_: R, ux: &Self, _: R, _: &Self, _: R, _: &Self) -> Self { *ux }
+
+    #[inline(always)]
+    fn linear_interpolate<R: Float>(_: R, x1: &Self, _: &Self) -> Self { *x1 }
+}
+
+/// Immutable textures of a single type, looked up with `TextureHandle`s
+#[derive(Debug)]
+pub struct TextureRegistry<T> {
+    id: usize,
+    textures: Vec<Arc<T>>,
+}
+
+impl<T> Default for TextureRegistry<T> {
+    fn default() -> TextureRegistry<T> {
+        TextureRegistry::new()
+    }
+}
+
+impl<T> TextureRegistry<T> {
+    /// Creates an empty registry
+    pub fn new() -> TextureRegistry<T> {
+        TextureRegistry {
+            id: NEXT_REGISTRY_ID.fetch_add(1, Ordering::Relaxed),
+            textures: Vec::new(),
+        }
+    }
+
+    /// Adds a texture to the registry, returning its handle
+    pub fn insert(&mut self, texture: T) -> TextureHandle<T> {
+        self.insert_shared(Arc::new(texture))
+    }
+
+    /// Adds a texture that may already be shared elsewhere, returning its handle
+    pub fn insert_shared(&mut self, texture: Arc<T>) -> TextureHandle<T> {
+        self.textures.push(texture);
+
+        TextureHandle {
+            registry: self.id,
+            index: self.textures.len() - 1,
+            texture: PhantomData,
+        }
+    }
+
+    /// Returns the texture of a handle, or `None` if the handle is from another registry
+    #[inline]
+    pub fn get(&self, handle: TextureHandle<T>) -> Option<&T> {
+        self.get_shared(handle).map(|texture| &**texture)
+    }
+
+    /// Returns the shared texture of a handle, which can outlive the registry,
+    /// or `None` if the handle is from another registry
+    #[inline]
+    pub fn get_shared(&self, handle: TextureHandle<T>) -> Option<&Arc<T>> {
+        if handle.registry == self.id { self.textures.get(handle.index) } else { None }
+    }
+
+    /// Returns true if the handle refers to a texture in this registry
+    #[inline]
+    pub fn contains(&self, handle: TextureHandle<T>) -> bool {
+        self.get_shared(handle).is_some()
+    }
+
+    /// Number of textures in the registry
+    #[inline]
+    pub fn len(&self) -> usize { self.textures.len() }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool { self.textures.is_empty() }
+
+    /// Iterates over the handles and textures of the registry, in order of insertion
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (TextureHandle<T>, &'a T)> + 'a> {
+        let id = self.id;
+
+        Box::new(self.textures.iter().enumerate().map(move |(index, texture)| {
+            (TextureHandle { registry: id, index, texture: PhantomData }, &**texture)
+        }))
+    }
+}
+
+impl<T> Index<TextureHandle<T>> for TextureRegistry<T> {
+    type Output = T;
+
+    /// Panics if the handle is from another registry
+    fn index(&self, handle: TextureHandle<T>) -> &T {
+        self.get(handle).expect("TextureHandle used with a different TextureRegistry")
+    }
+}
+
+impl<T> MemoryUsage for TextureRegistry<T> where T: MemoryUsage {
+    /// Reports each texture as `textures.<index>`, including textures that are also shared elsewhere
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("handles", &self.textures);
+
+        for (index, texture) in self.textures.iter().enumerate() {
+            report.merge(&format!("textures.{}", index), texture.memory_report());
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_registry_handles() {
+        let mut a = TextureRegistry::new();
+        let mut b = TextureRegistry::new();
+
+        let first = a.insert(vec![1u8, 2, 3]);
+        let second = a.insert(vec![4u8]);
+        let other = b.insert(vec![5u8]);
+
+        assert_eq!(a.len(), 2);
+        assert_eq!(a[first], vec![1, 2, 3]);
+        assert_eq!(a.get(second), Some(&vec![4]));
+
+        // Handles from another registry with the same index don't resolve
+        assert_eq!(first.index(), other.index());
+        assert!(!a.contains(other));
+        assert!(a.get(other).is_none());
+
+        let shared = a.get_shared(first).unwrap().clone();
+
+        drop(a);
+
+        assert_eq!(*shared, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn test_handle_not_interpolated() {
+        let mut registry = TextureRegistry::new();
+
+        let a = registry.insert(0u8);
+        let b = registry.insert(1u8);
+
+        assert_eq!(Interpolate::barycentric_interpolate(0.1f32, &a, 0.8, &b, 0.1, &b), a);
+        assert_eq!(Interpolate::linear_interpolate(0.9f32, &b, &a), b);
+    }
+}
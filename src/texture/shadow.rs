@@ -0,0 +1,330 @@
+//! Shadow maps and depth comparison sampling
+//!
+//! A shadow map is the depth attachment of the scene rendered from the light. A fragment is lit if it is
+//! at least as near to the light as whatever the shadow map recorded at the same position.
+//!
+//! `ShadowSampler` performs that comparison while sampling, like `sampler2DShadow` in GLSL,
+//! comparing the four nearest texels and filtering the results bilinearly, so shadow edges are smooth
+//! instead of blocky:
+//!
+//! ```ignore
+//! // Render the scene from the light into `light_pipeline`, then
+//! let shadows = ShadowSampler::new(ShadowMap::from_framebuffer(light_pipeline.framebuffer()));
+//!
+//! fragment_shader.run(|v, u| {
+//!     let visibility = shadows.sample_clip(u.light_view_projection * v.uniforms.world_position);
+//!     // ...
+//! });
+//! ```
+//!
+//! Depth values are compared as stored in the depth attachment, where they increase towards the light.
+//!
+//! ## Soft shadows
+//!
+//! Real lights have a size, so shadows are sharp near where the occluder touches the receiver and blurrier further away.
+//! `ShadowSampler::sample_pcss` approximates that with percentage-closer soft shadows: it searches the shadow map
+//! around the fragment for occluders, estimates the width of the penumbra from their average depth,
+//! and filters the comparison over that width:
+//!
+//! ```ignore
+//! let pcss = Pcss::new(24.0);
+//!
+//! let visibility = shadows.sample_clip_pcss(u.light_view_projection * v.uniforms.world_position, &pcss);
+//! ```
+
+use num_traits::{ToPrimitive, cast};
+
+use nalgebra::Vector4;
+
+use ::numeric::FloatScalar;
+use ::geometry::{Coordinate, Dimensions, HasDimensions, ClipVertex, Viewport};
+use ::framebuffer::Framebuffer;
+use ::framebuffer::types::DepthAttachment;
+use ::framebuffer::attachments::DepthTest;
+use ::memory::{MemoryReport, MemoryUsage};
+use ::sampling::blue_noise;
+
+/// Depth values of a scene rendered from a light
+#[derive(Debug, Clone)]
+pub struct ShadowMap {
+    dimensions: Dimensions,
+    depth: Vec<f32>,
+    viewport: Viewport<f32>,
+}
+
+impl HasDimensions for ShadowMap {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl MemoryUsage for ShadowMap {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("depth", &self.depth);
+
+        report
+    }
+}
+
+impl ShadowMap {
+    /// Creates a shadow map by calling `f` for the depth of every texel
+    pub fn from_fn<F>(dimensions: Dimensions, mut f: F) -> ShadowMap where F: FnMut(Coordinate) -> f32 {
+        let mut depth = Vec::with_capacity(dimensions.area());
+
+        for y in 0..dimensions.height {
+            for x in 0..dimensions.width {
+                depth.push(f(Coordinate::new(x, y)));
+            }
+        }
+
+        ShadowMap { dimensions, depth, viewport: Viewport::new(dimensions, Coordinate::default(), 0.0, 1.0) }
+    }
+
+    /// Copies the depth attachment of a framebuffer.
+    ///
+    /// The viewport is assumed to cover the whole framebuffer with the default depth range,
+    /// as with `GeometryShader::finish_default` and no viewport set. Otherwise, use `set_viewport`.
+    pub fn from_framebuffer<F>(framebuffer: &F) -> ShadowMap where F: Framebuffer, DepthAttachment<F>: ToPrimitive {
+        ShadowMap::from_fn(framebuffer.dimensions(), |coord| {
+            let depth = unsafe { framebuffer.get_depth_unchecked(framebuffer.index_of(coord)) };
+
+            depth.to_f32().unwrap_or(::std::f32::MIN)
+        })
+    }
+
+    /// Viewport the shadow map was rendered with, used to find the texel of a clip-space position
+    #[inline]
+    pub fn viewport(&self) -> Viewport<f32> { self.viewport }
+
+    /// Sets the viewport the shadow map was rendered with
+    #[inline]
+    pub fn set_viewport(&mut self, viewport: Viewport<f32>) {
+        self.viewport = viewport;
+    }
+
+    /// Depth of a texel, or the farthest possible depth outside of the shadow map
+    #[inline]
+    pub fn depth(&self, x: i64, y: i64) -> f32 {
+        if x < 0 || y < 0 || x >= self.dimensions.width as i64 || y >= self.dimensions.height as i64 {
+            ::std::f32::MIN
+        } else {
+            self.depth[y as usize * self.dimensions.width as usize + x as usize]
+        }
+    }
+}
+
+/// Settings for percentage-closer soft shadows
+#[derive(Debug, Clone)]
+pub struct Pcss {
+    /// Size of the light, as the width of the penumbra in texels per unit of depth between the occluder and the receiver.
+    ///
+    /// Depth differences are treated as linear distances, which is exact for orthographic shadow maps of directional lights
+    /// and an approximation for the perspective shadow maps of spot lights.
+    pub light_size: f32,
+    /// Radius searched for occluders around the fragment, in texels
+    pub search_radius: f32,
+    /// Largest penumbra width, in texels, limiting how blurry shadows can get
+    pub max_penumbra: f32,
+    /// Offsets within the unit disk used for both the occluder search and the filtering
+    kernel: Vec<(f32, f32)>,
+}
+
+impl Pcss {
+    /// Default number of samples for the occluder search and the filtering
+    pub const DEFAULT_SAMPLES: usize = 16;
+
+    /// Soft shadows for a light of the given size, searching up to 16 texels away with 16 samples
+    pub fn new(light_size: f32) -> Pcss {
+        Pcss::with_samples(light_size, Pcss::DEFAULT_SAMPLES)
+    }
+
+    /// Soft shadows for a light of the given size, using `samples` samples for each of the occluder search and the filtering
+    pub fn with_samples(light_size: f32, samples: usize) -> Pcss {
+        let kernel = blue_noise(samples.max(1), 0).into_iter().map(|(u, v)| {
+            let r = u.sqrt();
+            let theta = v * 2.0 * ::std::f32::consts::PI;
+
+            (r * theta.cos(), r * theta.sin())
+        }).collect();
+
+        Pcss { light_size, search_radius: 16.0, max_penumbra: 16.0, kernel }
+    }
+
+    /// Offsets within the unit disk used for sampling
+    #[inline]
+    pub fn kernel(&self) -> &[(f32, f32)] { &self.kernel }
+}
+
+/// Samples a shadow map with depth comparison
+#[derive(Debug, Clone)]
+pub struct ShadowSampler {
+    map: ShadowMap,
+    /// Comparison between the reference depth and the shadow map, passing where the fragment is lit.
+    ///
+    /// Defaults to `DepthTest::GreaterThanEq`, the same as the default depth test.
+    pub compare: DepthTest,
+    /// Offset added to the reference depth before comparing, moving it towards the light
+    /// to avoid surfaces shadowing themselves.
+    pub bias: f32,
+}
+
+impl ShadowSampler {
+    /// Creates a sampler for the shadow map, with the default comparison and no bias
+    pub fn new(map: ShadowMap) -> ShadowSampler {
+        ShadowSampler { map, compare: DepthTest::GreaterThanEq, bias: 0.0 }
+    }
+
+    /// The sampled shadow map
+    #[inline]
+    pub fn map(&self) -> &ShadowMap { &self.map }
+
+    /// Returns the shadow map, consuming the sampler
+    #[inline]
+    pub fn into_map(self) -> ShadowMap { self.map }
+
+    /// Compares a single texel with the reference depth, returning `0.0` or `1.0`
+    #[inline]
+    pub fn compare_texel(&self, x: i64, y: i64, depth: f32) -> f32 {
+        if self.compare.test(depth + self.bias, self.map.depth(x, y)) { 1.0 } else { 0.0 }
+    }
+
+    /// Returns the visibility at a pixel-space position of the shadow map, from `0.0` in shadow to `1.0` lit,
+    /// by comparing the four nearest texels and filtering the results bilinearly.
+    pub fn sample(&self, x: f32, y: f32, depth: f32) -> f32 {
+        let x = x - 0.5;
+        let y = y - 0.5;
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.compare_texel(x0, y0, depth) * (1.0 - fx) + self.compare_texel(x0 + 1, y0, depth) * fx;
+        let bottom = self.compare_texel(x0, y0 + 1, depth) * (1.0 - fx) + self.compare_texel(x0 + 1, y0 + 1, depth) * fx;
+
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// Returns the visibility of a position in the clip-space of the light, from `0.0` in shadow to `1.0` lit.
+    ///
+    /// Positions behind the light are always lit.
+    pub fn sample_clip<N: FloatScalar>(&self, position: Vector4<N>) -> f32 {
+        let (x, y, depth) = match self.project(position) {
+            Some(screen) => screen,
+            None => return 1.0,
+        };
+
+        self.sample(x, y, depth)
+    }
+
+    /// Returns the soft shadow visibility at a pixel-space position of the shadow map, from `0.0` in shadow to `1.0` lit.
+    ///
+    /// Fragments with no occluders within the search radius are lit without any filtering.
+    pub fn sample_pcss(&self, x: f32, y: f32, depth: f32, pcss: &Pcss) -> f32 {
+        let reference = depth + self.bias;
+
+        let mut blockers = 0;
+        let mut blocker_depth = 0.0;
+
+        for &(ox, oy) in &pcss.kernel {
+            let sx = (x + ox * pcss.search_radius).floor() as i64;
+            let sy = (y + oy * pcss.search_radius).floor() as i64;
+
+            let d = self.map.depth(sx, sy);
+
+            if !self.compare.test(reference, d) {
+                blockers += 1;
+                blocker_depth += d;
+            }
+        }
+
+        if blockers == 0 {
+            return 1.0;
+        }
+
+        let blocker_depth = blocker_depth / blockers as f32;
+
+        let penumbra = (pcss.light_size * (blocker_depth - reference).abs()).min(pcss.max_penumbra);
+
+        let weight = 1.0 / pcss.kernel.len() as f32;
+
+        pcss.kernel.iter().map(|&(ox, oy)| self.sample(x + ox * penumbra, y + oy * penumbra, depth) * weight).sum()
+    }
+
+    /// Returns the soft shadow visibility of a position in the clip-space of the light, from `0.0` in shadow to `1.0` lit.
+    ///
+    /// Positions behind the light are always lit.
+    pub fn sample_clip_pcss<N: FloatScalar>(&self, position: Vector4<N>, pcss: &Pcss) -> f32 {
+        let (x, y, depth) = match self.project(position) {
+            Some(screen) => screen,
+            None => return 1.0,
+        };
+
+        self.sample_pcss(x, y, depth, pcss)
+    }
+
+    /// Converts a position in the clip-space of the light to the pixel-space position and depth in the shadow map,
+    /// or `None` if the position is behind the light.
+    pub fn project<N: FloatScalar>(&self, position: Vector4<N>) -> Option<(f32, f32, f32)> {
+        let position: Vector4<f32> = Vector4::new(cast(position.x).unwrap(), cast(position.y).unwrap(),
+                                                  cast(position.z).unwrap(), cast(position.w).unwrap());
+
+        if position.w <= 0.0 {
+            return None;
+        }
+
+        let screen = ClipVertex::new(position, ()).normalize(self.map.viewport).position;
+
+        Some((screen.x, screen.y, screen.z))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_shadow_comparison() {
+        // Occluder at depth -0.5 on the left half, nothing on the right half
+        let map = ShadowMap::from_fn(Dimensions::new(4, 4), |coord| if coord.x < 2 { -0.5 } else { ::std::f32::MIN });
+
+        let mut sampler = ShadowSampler::new(map);
+
+        // Behind the occluder, and in front of it
+        assert_eq!(sampler.sample(0.5, 0.5, -0.75), 0.0);
+        assert_eq!(sampler.sample(0.5, 0.5, -0.25), 1.0);
+
+        // Unoccluded half and outside of the map
+        assert_eq!(sampler.sample(3.5, 1.5, -0.75), 1.0);
+        assert_eq!(sampler.sample(-10.0, 1.5, -0.75), 1.0);
+
+        // Halfway across the shadow edge
+        assert!((sampler.sample(2.0, 1.5, -0.75) - 0.5).abs() < 1e-6);
+
+        // Bias moves the surface towards the light
+        sampler.bias = 0.5;
+
+        assert_eq!(sampler.sample(0.5, 0.5, -0.75), 1.0);
+    }
+
+    #[test]
+    fn test_pcss_penumbra() {
+        // Occluder at depth -0.2 on the left half
+        let map = ShadowMap::from_fn(Dimensions::new(16, 16), |coord| if coord.x < 8 { -0.2 } else { ::std::f32::MIN });
+
+        let sampler = ShadowSampler::new(map);
+
+        let pcss = Pcss::new(4.0);
+
+        // Lit just outside the occluder when the receiver is close to it, but blurred further away
+        let near = sampler.sample_pcss(9.5, 8.0, -0.3, &pcss);
+        let far = sampler.sample_pcss(9.5, 8.0, -0.9, &pcss);
+
+        assert_eq!(near, 1.0);
+        assert!(far > 0.0 && far < 1.0);
+
+        // Deep in shadow and far from any occluder
+        assert_eq!(sampler.sample_pcss(2.0, 8.0, -0.3, &pcss), 0.0);
+        assert_eq!(sampler.sample_pcss(2.0, 8.0, -0.1, &pcss), 1.0);
+    }
+}
@@ -0,0 +1,274 @@
+//! 3D textures
+//!
+//! Volumes of voxels sampled with normalized `[0, 1]` coordinates in all three dimensions,
+//! for volumetric fog and density lookups, color LUTs and raymarching in fragment shaders.
+
+use nalgebra::Vector3;
+
+use num_traits::cast;
+
+use ::numeric::FloatScalar;
+use ::color::{Color, ToChannels};
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::{PixelBuffer, PixelRead, PixelWrite};
+use ::memory::{MemoryReport, MemoryUsage};
+
+use super::Filter;
+
+/// A volume of colors, stored as consecutive slices along the depth axis
+#[derive(Debug, Clone)]
+pub struct Texture3D<C: Color> {
+    dimensions: Dimensions,
+    depth: u32,
+    voxels: Vec<C>,
+}
+
+impl<C: Color> Texture3D<C> {
+    /// Creates a volume with every voxel set to `Color::empty()`
+    pub fn new(dimensions: Dimensions, depth: u32) -> Texture3D<C> {
+        Texture3D::filled(dimensions, depth, C::empty())
+    }
+
+    /// Creates a volume with every voxel set to the given color
+    pub fn filled(dimensions: Dimensions, depth: u32, color: C) -> Texture3D<C> {
+        Texture3D { dimensions, depth, voxels: vec![color; dimensions.area() * depth as usize] }
+    }
+
+    /// Creates a volume by calling `f` with the coordinate and slice of every voxel
+    pub fn from_fn<F>(dimensions: Dimensions, depth: u32, mut f: F) -> Texture3D<C> where F: FnMut(Coordinate, u32) -> C {
+        let mut voxels = Vec::with_capacity(dimensions.area() * depth as usize);
+
+        for z in 0..depth {
+            for y in 0..dimensions.height {
+                for x in 0..dimensions.width {
+                    voxels.push(f(Coordinate::new(x, y), z));
+                }
+            }
+        }
+
+        Texture3D { dimensions, depth, voxels }
+    }
+
+    /// Stacks the given images as slices, from front to back. Every image must have the same dimensions.
+    pub fn from_slices<P>(slices: &[P]) -> Texture3D<C> where P: PixelRead<Color = C> {
+        assert!(!slices.is_empty(), "Volumes need at least one slice");
+
+        let dimensions = slices[0].dimensions();
+
+        Texture3D::from_fn(dimensions, slices.len() as u32, |coord, z| {
+            let slice = &slices[z as usize];
+
+            assert_eq!(slice.dimensions(), dimensions, "Slices must all have the same dimensions");
+
+            unsafe { slice.get_pixel_unchecked(slice.index_of(coord)) }
+        })
+    }
+
+    /// Splits a single image into `depth` slices stacked from top to bottom,
+    /// the layout often used to store volumes and LUTs as regular image files.
+    pub fn from_stacked<P>(image: &P, depth: u32) -> Texture3D<C> where P: PixelRead<Color = C> {
+        let full = image.dimensions();
+
+        assert!(depth > 0 && full.height % depth == 0, "Image height must be a multiple of the depth");
+
+        let dimensions = Dimensions::new(full.width, full.height / depth);
+
+        Texture3D::from_fn(dimensions, depth, |coord, z| {
+            unsafe { image.get_pixel_unchecked(image.index_of(Coordinate::new(coord.x, coord.y + z * dimensions.height))) }
+        })
+    }
+
+    /// Number of slices
+    #[inline]
+    pub fn depth(&self) -> u32 { self.depth }
+
+    #[inline]
+    fn voxel_index(&self, coord: Coordinate, z: u32) -> usize {
+        z as usize * self.dimensions.area() + coord.into_index(self.dimensions)
+    }
+
+    /// Returns the voxel at the given coordinate and slice, or `None` if out of bounds
+    pub fn get(&self, coord: Coordinate, z: u32) -> Option<C> {
+        if self.dimensions.in_bounds(coord) && z < self.depth {
+            Some(self.voxels[self.voxel_index(coord, z)])
+        } else {
+            None
+        }
+    }
+
+    /// Sets the voxel at the given coordinate and slice, returning false if out of bounds
+    pub fn set(&mut self, coord: Coordinate, z: u32, color: C) -> bool {
+        if self.dimensions.in_bounds(coord) && z < self.depth {
+            let index = self.voxel_index(coord, z);
+
+            self.voxels[index] = color;
+
+            true
+        } else {
+            false
+        }
+    }
+
+    /// 2D view of a single slice, which can be read like any other pixel buffer. Panics if the slice does not exist.
+    pub fn slice(&self, z: u32) -> Texture3DSlice<C> {
+        assert!(z < self.depth, "Slice out of bounds");
+
+        let area = self.dimensions.area();
+
+        Texture3DSlice { dimensions: self.dimensions, voxels: &self.voxels[z as usize * area..(z as usize + 1) * area] }
+    }
+
+    /// Mutable 2D view of a single slice, which can be rendered into. Panics if the slice does not exist.
+    pub fn slice_mut(&mut self, z: u32) -> Texture3DSliceMut<C> {
+        assert!(z < self.depth, "Slice out of bounds");
+
+        let area = self.dimensions.area();
+
+        Texture3DSliceMut { dimensions: self.dimensions, voxels: &mut self.voxels[z as usize * area..(z as usize + 1) * area] }
+    }
+
+    /// Samples normalized channels at a normalized coordinate, where `(0, 0, 0)` is the top-left corner of the first slice.
+    ///
+    /// `Filter::Bilinear` interpolates between neighboring slices as well, giving trilinear filtering.
+    /// Coordinates outside of the volume are clamped to the edge.
+    pub fn sample<N: FloatScalar>(&self, coord: Vector3<N>, filter: Filter) -> [f32; 4] where C: ToChannels {
+        let x: f32 = cast::<N, f32>(coord.x).unwrap() * self.dimensions.width as f32;
+        let y: f32 = cast::<N, f32>(coord.y).unwrap() * self.dimensions.height as f32;
+        let z: f32 = cast::<N, f32>(coord.z).unwrap() * self.depth as f32;
+
+        let (xmax, ymax, zmax) = (self.dimensions.width as i64 - 1, self.dimensions.height as i64 - 1, self.depth as i64 - 1);
+
+        let fetch = |x: i64, y: i64, z: i64| {
+            let coord = Coordinate::new(x.max(0).min(xmax) as u32, y.max(0).min(ymax) as u32);
+
+            self.voxels[self.voxel_index(coord, z.max(0).min(zmax) as u32)].to_channels()
+        };
+
+        match filter {
+            Filter::Nearest => fetch(x.floor() as i64, y.floor() as i64, z.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y, z) = (x - 0.5, y - 0.5, z - 0.5);
+
+                let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+                let (fx, fy, fz) = (x - x0, y - y0, z - z0);
+                let (x0, y0, z0) = (x0 as i64, y0 as i64, z0 as i64);
+
+                let mut out = [0.0; 4];
+
+                for &(dz, wz) in &[(0, 1.0 - fz), (1, fz)] {
+                    let (a, b) = (fetch(x0, y0, z0 + dz), fetch(x0 + 1, y0, z0 + dz));
+                    let (c, d) = (fetch(x0, y0 + 1, z0 + dz), fetch(x0 + 1, y0 + 1, z0 + dz));
+
+                    for i in 0..4 {
+                        let top = a[i] + (b[i] - a[i]) * fx;
+                        let bottom = c[i] + (d[i] - c[i]) * fx;
+
+                        out[i] += (top + (bottom - top) * fy) * wz;
+                    }
+                }
+
+                out
+            }
+        }
+    }
+}
+
+impl<C: Color> HasDimensions for Texture3D<C> {
+    /// Dimensions of each slice
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<C: Color> MemoryUsage for Texture3D<C> {
+    fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::new();
+
+        report.add_vec("voxels", &self.voxels);
+
+        report
+    }
+}
+
+/// Borrowed slice of a `Texture3D`
+#[derive(Debug, Clone, Copy)]
+pub struct Texture3DSlice<'a, C: Color + 'a> {
+    dimensions: Dimensions,
+    voxels: &'a [C],
+}
+
+/// Mutably borrowed slice of a `Texture3D`
+#[derive(Debug)]
+pub struct Texture3DSliceMut<'a, C: Color + 'a> {
+    dimensions: Dimensions,
+    voxels: &'a mut [C],
+}
+
+impl<'a, C: Color> HasDimensions for Texture3DSlice<'a, C> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<'a, C: Color> PixelBuffer for Texture3DSlice<'a, C> {
+    type Color = C;
+}
+
+impl<'a, C: Color> PixelRead for Texture3DSlice<'a, C> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> C {
+        *self.voxels.get_unchecked(index)
+    }
+}
+
+impl<'a, C: Color> HasDimensions for Texture3DSliceMut<'a, C> {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+impl<'a, C: Color> PixelBuffer for Texture3DSliceMut<'a, C> {
+    type Color = C;
+}
+
+impl<'a, C: Color> PixelRead for Texture3DSliceMut<'a, C> {
+    #[inline]
+    unsafe fn get_pixel_unchecked(&self, index: usize) -> C {
+        *self.voxels.get_unchecked(index)
+    }
+}
+
+impl<'a, C: Color> PixelWrite for Texture3DSliceMut<'a, C> {
+    #[inline]
+    unsafe fn set_pixel_unchecked(&mut self, index: usize, color: C) {
+        *self.voxels.get_unchecked_mut(index) = color;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::Rf32Color;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_trilinear_sampling() {
+        let volume = Texture3D::from_fn(Dimensions::new(2, 2), 2, |_, z| Rf32Color::new(z as f32));
+
+        assert_eq!(volume.sample(Vector3::new(0.5f32, 0.5, 0.5), Filter::Bilinear)[0], 0.5);
+        assert_eq!(volume.sample(Vector3::new(0.5f32, 0.5, 0.25), Filter::Bilinear)[0], 0.0);
+        assert_eq!(volume.sample(Vector3::new(0.5f32, 0.5, 0.6), Filter::Nearest)[0], 1.0);
+    }
+
+    #[test]
+    fn test_stacked_slices() {
+        let image = ColorBuffer::from_fn(Dimensions::new(2, 6), |coord| Rf32Color::new((coord.y / 2) as f32));
+
+        let volume = Texture3D::from_stacked(&image, 3);
+
+        assert_eq!(volume.dimensions(), Dimensions::new(2, 2));
+        assert_eq!(volume.get(Coordinate::new(1, 1), 2), Some(Rf32Color::new(2.0)));
+
+        let slice = volume.slice(1);
+
+        assert_eq!(slice.pixel_ref(Coordinate::new(0, 0)).unwrap().get(), Rf32Color::new(1.0));
+    }
+}
@@ -31,6 +31,16 @@ macro_rules! tuple_impls {
                 fn get_alpha(&self) -> Self::Alpha {
                     ($(<$T as $crate::color::Color>::get_alpha(&self.$idx),)+)
                 }
+
+                #[inline]
+                fn select_targets(self, other: Self, mask: u32) -> Self {
+                    ($(if mask & (1 << $idx) != 0 { self.$idx } else { other.$idx },)+)
+                }
+
+                #[inline]
+                fn mask_channels(self, other: Self, mask: $crate::color::ColorMask) -> Self {
+                    ($(<$T as $crate::color::Color>::mask_channels(self.$idx, other.$idx, mask),)+)
+                }
             }
 
             impl<$($T),+> $crate::interpolate::Interpolate for ($($T,)+) where $($T: $crate::interpolate::Interpolate,)+ {
@@ -0,0 +1,62 @@
+//! Validation layer
+//!
+//! Checks performed before rendering to turn common mistakes into errors rather than panics or garbage output.
+//!
+//! Validation is enabled in debug builds and disabled in release builds, unless the `validation`
+//! cargo feature is enabled to keep it on everywhere. When disabled, every check passes immediately.
+
+use num_traits::{NumCast, cast};
+
+use ::error::{RenderError, RenderResult};
+use ::geometry::Dimensions;
+use ::primitive::Primitive;
+use ::mesh::{Vertex, Mesh, MeshIndex};
+
+/// Whether the validation layer is active in this build
+pub const VALIDATION_ENABLED: bool = cfg!(any(debug_assertions, feature = "validation"));
+
+/// Checks that both dimensions are non-zero
+pub fn validate_dimensions(dimensions: Dimensions) -> RenderResult<()> {
+    if VALIDATION_ENABLED && (dimensions.width == 0 || dimensions.height == 0) {
+        throw!(RenderError::InvalidDimensions);
+    }
+
+    Ok(())
+}
+
+/// Checks that the mesh indices form whole primitives of type `T`,
+/// that every index refers to an existing vertex, and that every vertex position is finite.
+pub fn validate_mesh<T, V, I>(mesh: &Mesh<V, I>) -> RenderResult<()> where T: Primitive, V: Vertex, I: MeshIndex {
+    if !VALIDATION_ENABLED {
+        return Ok(());
+    }
+
+    if mesh.indices.len() % T::num_vertices() != 0 {
+        throw!(RenderError::IndexOutOfRange);
+    }
+
+    let num_vertices = mesh.vertices.len();
+
+    if mesh.indices.iter().any(|index| index.to_usize() >= num_vertices) {
+        throw!(RenderError::IndexOutOfRange);
+    }
+
+    for vertex in &mesh.vertices {
+        let position = vertex.position();
+
+        if !(position.x.is_finite() && position.y.is_finite() && position.z.is_finite()) {
+            throw!(RenderError::InvalidVertex);
+        }
+    }
+
+    Ok(())
+}
+
+/// Casts between numeric types, failing with `RenderError::InvalidCast` if the value can't be represented
+#[inline]
+pub fn checked_cast<T: NumCast, U: NumCast>(value: T) -> RenderResult<U> {
+    match cast(value) {
+        Some(value) => Ok(value),
+        None => throw!(RenderError::InvalidCast),
+    }
+}
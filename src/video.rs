@@ -0,0 +1,212 @@
+//! Video frame output
+//!
+//! Converts color buffers into BT.709 Y'CbCr frames and writes them as a YUV4MPEG2 (`.y4m`) stream or as raw planes,
+//! which `ffmpeg` and most other encoders read directly, for example with `ffmpeg -i - output.mp4` reading from stdout.
+//!
+//! Channels are taken as already gamma-encoded, like the values written to image files,
+//! so buffers rendered in linear space should be converted with `encode_srgb` first.
+//! Output uses the limited "TV" range expected by video encoders.
+
+use std::io::{self, Write};
+
+use ::color::ToChannels;
+use ::geometry::{Dimensions, Coordinate, HasDimensions};
+use ::pixels::PixelRead;
+
+/// Resolution of the chroma planes relative to the luma plane
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Full resolution chroma
+    C444,
+    /// Half resolution chroma in both directions, which nearly all video codecs and players expect
+    C420,
+}
+
+impl ChromaSubsampling {
+    /// Dimensions of each chroma plane for a frame with the given dimensions
+    pub fn chroma_dimensions(self, dimensions: Dimensions) -> Dimensions {
+        match self {
+            ChromaSubsampling::C444 => dimensions,
+            ChromaSubsampling::C420 => Dimensions::new((dimensions.width + 1) / 2, (dimensions.height + 1) / 2),
+        }
+    }
+
+    fn y4m_tag(self) -> &'static str {
+        match self {
+            ChromaSubsampling::C444 => "444",
+            ChromaSubsampling::C420 => "420jpeg",
+        }
+    }
+}
+
+/// Converts gamma-encoded RGB in `0..1` to BT.709 Y'CbCr, with Y' in `0..1` and Cb and Cr in `-0.5..0.5`
+#[inline]
+pub fn rgb_to_ycbcr709(rgb: [f32; 3]) -> [f32; 3] {
+    let y = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+
+    [y, (rgb[2] - y) / 1.8556, (rgb[0] - y) / 1.5748]
+}
+
+#[inline]
+fn limited_luma(y: f32) -> u8 {
+    (16.0 + y.max(0.0).min(1.0) * 219.0).round() as u8
+}
+
+#[inline]
+fn limited_chroma(c: f32) -> u8 {
+    (128.0 + c.max(-0.5).min(0.5) * 224.0).round() as u8
+}
+
+/// A frame of 8-bit planar Y'CbCr
+#[derive(Debug, Clone, PartialEq)]
+pub struct YuvFrame {
+    dimensions: Dimensions,
+    subsampling: ChromaSubsampling,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+impl YuvFrame {
+    /// Converts every pixel of `buffer`, averaging the chroma of each block of pixels if subsampled
+    pub fn from_buffer<P>(buffer: &P, subsampling: ChromaSubsampling) -> YuvFrame where P: PixelRead, P::Color: ToChannels {
+        let dimensions = buffer.dimensions();
+        let chroma = subsampling.chroma_dimensions(dimensions);
+
+        let mut y = Vec::with_capacity(dimensions.area());
+        let mut cbcr = vec![(0.0f32, 0.0f32, 0u32); chroma.area()];
+
+        for py in 0..dimensions.height {
+            for px in 0..dimensions.width {
+                let c = unsafe { buffer.get_pixel_unchecked(buffer.index_of(Coordinate::new(px, py))) }.to_channels();
+
+                let ycbcr = rgb_to_ycbcr709([c[0], c[1], c[2]]);
+
+                y.push(limited_luma(ycbcr[0]));
+
+                let chroma_coord = match subsampling {
+                    ChromaSubsampling::C444 => Coordinate::new(px, py),
+                    ChromaSubsampling::C420 => Coordinate::new(px / 2, py / 2),
+                };
+
+                let sum = &mut cbcr[chroma_coord.into_index(chroma)];
+
+                sum.0 += ycbcr[1];
+                sum.1 += ycbcr[2];
+                sum.2 += 1;
+            }
+        }
+
+        YuvFrame {
+            dimensions,
+            subsampling,
+            y,
+            u: cbcr.iter().map(|&(cb, _, n)| limited_chroma(cb / n as f32)).collect(),
+            v: cbcr.iter().map(|&(_, cr, n)| limited_chroma(cr / n as f32)).collect(),
+        }
+    }
+
+    #[inline]
+    pub fn subsampling(&self) -> ChromaSubsampling { self.subsampling }
+
+    /// Luma plane
+    #[inline]
+    pub fn y(&self) -> &[u8] { &self.y }
+
+    /// Blue-difference chroma plane
+    #[inline]
+    pub fn u(&self) -> &[u8] { &self.u }
+
+    /// Red-difference chroma plane
+    #[inline]
+    pub fn v(&self) -> &[u8] { &self.v }
+
+    /// Writes the Y, U and V planes one after another, as in raw `.yuv` files
+    pub fn write_planes<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(&self.y)?;
+        writer.write_all(&self.u)?;
+        writer.write_all(&self.v)
+    }
+}
+
+impl HasDimensions for YuvFrame {
+    #[inline]
+    fn dimensions(&self) -> Dimensions { self.dimensions }
+}
+
+/// Writes frames as a YUV4MPEG2 stream
+pub struct Y4mWriter<W: Write> {
+    writer: W,
+    dimensions: Dimensions,
+    subsampling: ChromaSubsampling,
+}
+
+impl<W: Write> Y4mWriter<W> {
+    /// Writes the stream header for frames of the given dimensions, played back at `fps_num / fps_den` frames per second
+    pub fn new(mut writer: W, dimensions: Dimensions, fps_num: u32, fps_den: u32, subsampling: ChromaSubsampling) -> io::Result<Y4mWriter<W>> {
+        writeln!(writer, "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C{} XCOLORRANGE=LIMITED",
+                 dimensions.width, dimensions.height, fps_num, fps_den, subsampling.y4m_tag())?;
+
+        Ok(Y4mWriter { writer, dimensions, subsampling })
+    }
+
+    /// Converts and writes the current contents of `buffer` as the next frame
+    pub fn write_buffer<P>(&mut self, buffer: &P) -> io::Result<()> where P: PixelRead, P::Color: ToChannels {
+        let frame = YuvFrame::from_buffer(buffer, self.subsampling);
+
+        self.write_frame(&frame)
+    }
+
+    /// Writes an already converted frame, which must match the dimensions and subsampling of the stream
+    pub fn write_frame(&mut self, frame: &YuvFrame) -> io::Result<()> {
+        if frame.dimensions != self.dimensions || frame.subsampling != self.subsampling {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Frame does not match the stream"));
+        }
+
+        self.writer.write_all(b"FRAME\n")?;
+
+        frame.write_planes(&mut self.writer)
+    }
+
+    /// Flushes and returns the underlying writer
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ::color::predefined::formats::RGBAf32Color;
+    use ::pixels::ColorBuffer;
+
+    use super::*;
+
+    #[test]
+    fn test_limited_range() {
+        let white = rgb_to_ycbcr709([1.0, 1.0, 1.0]);
+        let black = rgb_to_ycbcr709([0.0, 0.0, 0.0]);
+
+        assert_eq!((limited_luma(white[0]), limited_chroma(white[1]), limited_chroma(white[2])), (235, 128, 128));
+        assert_eq!((limited_luma(black[0]), limited_chroma(black[1]), limited_chroma(black[2])), (16, 128, 128));
+
+        assert_eq!(limited_chroma(rgb_to_ycbcr709([0.0, 0.0, 1.0])[1]), 240);
+    }
+
+    #[test]
+    fn test_y4m_stream() {
+        let buffer = ColorBuffer::filled(Dimensions::new(3, 3), RGBAf32Color::new(1.0, 1.0, 1.0, 1.0));
+
+        let mut writer = Y4mWriter::new(Vec::new(), buffer.dimensions(), 30, 1, ChromaSubsampling::C420).unwrap();
+
+        writer.write_buffer(&buffer).unwrap();
+
+        let bytes = writer.into_inner().unwrap();
+
+        let header = b"YUV4MPEG2 W3 H3 F30:1 Ip A1:1 C420jpeg XCOLORRANGE=LIMITED\nFRAME\n";
+
+        assert_eq!(&bytes[..header.len()], &header[..]);
+        assert_eq!(bytes.len(), header.len() + 9 + 4 + 4);
+    }
+}
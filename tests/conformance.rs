@@ -0,0 +1,228 @@
+//! Checks the optimized rasterizers against the reference rasterizer on randomized primitives.
+//!
+//! Primitives go through the whole pipeline with small tiles, so tile seams and framebuffer edges are covered as well.
+//! Triangles are also drawn with additive blending, so pixels drawn by more than one tile show up.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::geometry::PixelCenter;
+use softrender::pipeline::RasterBackend;
+use softrender::pipeline::stages::rasterization::reference::{CoverageMask, reference_triangle, reference_line,
+                                                             line_major_steps, reference_point};
+use softrender::sampling::SampleRng;
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+const WIDTH: u32 = 64;
+const HEIGHT: u32 = 48;
+
+fn dimensions() -> Dimensions { Dimensions::new(WIDTH, HEIGHT) }
+
+fn background() -> RGBAf32Color { RGBAf32Color::new(0.0, 0.0, 0.0, 0.0) }
+
+fn new_pipeline() -> TestPipeline {
+    Pipeline::from_framebuffer(RenderBuffer::with_dimensions(dimensions()), ())
+}
+
+/// Random position in pixel space, reaching `margin` pixels beyond the framebuffer on every side
+fn random_position(rng: &mut SampleRng, margin: f64) -> (f64, f64) {
+    (rng.next_f32() as f64 * (WIDTH as f64 + 2.0 * margin) - margin,
+     rng.next_f32() as f64 * (HEIGHT as f64 + 2.0 * margin) - margin)
+}
+
+/// Vertex whose position maps to the given pixel-space position with the default viewport
+fn vertex_at((x, y): (f64, f64)) -> SimpleVertex<f32, ()> {
+    SimpleVertex {
+        position: Point3::new((x * 2.0 / WIDTH as f64 - 1.0) as f32, (1.0 - y * 2.0 / HEIGHT as f64) as f32, 0.0),
+        data: (),
+    }
+}
+
+/// Pixel-space position the vertex actually ends up at, after rounding to single precision
+fn screen_position(vertex: &SimpleVertex<f32, ()>) -> (f64, f64) {
+    ((vertex.position.x as f64 + 1.0) * WIDTH as f64 / 2.0,
+     (1.0 - vertex.position.y as f64) * HEIGHT as f64 / 2.0)
+}
+
+/// Draws a single primitive in white over a cleared framebuffer, and returns its pixel-space positions
+fn draw<T: Primitive>(pipeline: &mut TestPipeline, primitive: T, positions: &[(f64, f64)], backend: RasterBackend) -> Vec<(f64, f64)> {
+    draw_blended(pipeline, primitive, positions, backend, BlendPreset::Replace, RGBAf32Color::new(1.0, 1.0, 1.0, 1.0))
+}
+
+/// Draws a single primitive in the given color and blend function over a cleared framebuffer
+fn draw_blended<T: Primitive>(pipeline: &mut TestPipeline, primitive: T, positions: &[(f64, f64)], backend: RasterBackend,
+                              blend: BlendPreset, color: RGBAf32Color) -> Vec<(f64, f64)> {
+    pipeline.framebuffer_mut().clear(background());
+
+    let vertices: Vec<_> = positions.iter().map(|&p| vertex_at(p)).collect();
+    let screen = vertices.iter().map(screen_position).collect();
+
+    let mesh = Arc::new(Mesh { indices: (0..vertices.len()).collect(), vertices });
+
+    let vertex_shader = pipeline.render_mesh(primitive, mesh, None);
+
+    let geometry_shader = vertex_shader.run(|vertex: &SimpleVertex<f32, ()>, _: &()| -> ClipVertex<f32, ()> {
+        ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ())
+    });
+
+    geometry_shader.finish_default()
+                   .with_raster_backend(backend)
+                   .with_tile_size(Dimensions::new(16, 16))
+                   .with_blend(blend)
+                   .run(move |_, _| Fragment::Color(color));
+
+    screen
+}
+
+fn covered(pipeline: &TestPipeline, coord: Coordinate) -> bool {
+    pipeline.framebuffer().pixel_ref(coord).unwrap().get() != background()
+}
+
+fn assert_conforms(pipeline: &TestPipeline, reference: &CoverageMask, what: &str) {
+    let mismatches = reference.mismatches(|coord| covered(pipeline, coord));
+
+    assert!(mismatches.is_empty(), "{} differs from the reference at {:?}", what, mismatches);
+}
+
+#[test]
+fn test_triangle_conformance() {
+    let mut pipeline = new_pipeline();
+
+    let mut rng = SampleRng::new(1959);
+
+    for &pixel_center in &[PixelCenter::HalfInteger, PixelCenter::Integer] {
+        pipeline.render_state_mut().pixel_center = pixel_center;
+
+        let mut tested = 0;
+
+        while tested < 100 {
+            let vertices = [random_position(&mut rng, 16.0), random_position(&mut rng, 16.0), random_position(&mut rng, 16.0)];
+
+            let triangle = {
+                let screen: Vec<_> = vertices.iter().map(|&p| screen_position(&vertex_at(p))).collect();
+
+                [screen[0], screen[1], screen[2]]
+            };
+
+            // Slivers come down to rounding, so leave them out
+            let area = (triangle[1].0 - triangle[0].0) * (triangle[2].1 - triangle[0].1) -
+                       (triangle[1].1 - triangle[0].1) * (triangle[2].0 - triangle[0].0);
+
+            if area.abs() < 2.0 {
+                continue;
+            }
+
+            let reference = reference_triangle(&triangle, dimensions(), pixel_center);
+
+            for &backend in &[RasterBackend::EdgeFunction, RasterBackend::Scanline, RasterBackend::Hierarchical] {
+                draw(&mut pipeline, Triangle, &vertices, backend);
+
+                assert_conforms(&pipeline, &reference, &format!("{:?} triangle {:?} with {:?} pixel centers", backend, triangle, pixel_center));
+            }
+
+            tested += 1;
+        }
+    }
+}
+
+#[test]
+fn test_triangle_blended_once() {
+    let mut pipeline = new_pipeline();
+
+    let mut rng = SampleRng::new(1962);
+
+    let color = RGBAf32Color::new(0.25, 0.25, 0.25, 0.25);
+
+    let mut tested = 0;
+
+    while tested < 50 {
+        // Large triangles, so most of them cross the edges between tiles
+        let vertices = [random_position(&mut rng, 16.0), random_position(&mut rng, 16.0), random_position(&mut rng, 16.0)];
+
+        let triangle = {
+            let screen: Vec<_> = vertices.iter().map(|&p| screen_position(&vertex_at(p))).collect();
+
+            [screen[0], screen[1], screen[2]]
+        };
+
+        let area = (triangle[1].0 - triangle[0].0) * (triangle[2].1 - triangle[0].1) -
+                   (triangle[1].1 - triangle[0].1) * (triangle[2].0 - triangle[0].0);
+
+        if area.abs() < 2.0 {
+            continue;
+        }
+
+        let reference = reference_triangle(&triangle, dimensions(), PixelCenter::HalfInteger);
+
+        for &backend in &[RasterBackend::EdgeFunction, RasterBackend::Scanline, RasterBackend::Hierarchical] {
+            draw_blended(&mut pipeline, Triangle, &vertices, backend, BlendPreset::Additive, color);
+
+            // Pixels drawn by more than one tile would have the color added twice
+            for y in 0..HEIGHT {
+                for x in 0..WIDTH {
+                    let pixel = pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+                    assert!(pixel == color || pixel == background(), "{:?} triangle {:?} has {:?} at ({}, {})", backend, triangle, pixel, x, y);
+                }
+            }
+
+            assert_conforms(&pipeline, &reference, &format!("{:?} blended triangle {:?}", backend, triangle));
+        }
+
+        tested += 1;
+    }
+}
+
+#[test]
+fn test_line_conformance() {
+    let mut pipeline = new_pipeline();
+
+    let mut rng = SampleRng::new(1960);
+
+    for _ in 0..200 {
+        // Endpoints stay a pixel away from the far edges, where clipping moves them into the last pixel
+        let (start, end) = (random_position(&mut rng, -1.0), random_position(&mut rng, -1.0));
+
+        let screen = draw(&mut pipeline, Line, &[start, end], RasterBackend::default());
+
+        let (start, end) = (screen[0], screen[1]);
+
+        let reference = reference_line(start, end, dimensions(), PixelCenter::HalfInteger);
+
+        assert_conforms(&pipeline, &reference, &format!("line from {:?} to {:?}", start, end));
+
+        // Every row or column along the line has to be covered, so the line has no gaps
+        for (first, last) in line_major_steps(start, end, dimensions(), PixelCenter::HalfInteger) {
+            let gap = !(first.y..last.y + 1).any(|y| (first.x..last.x + 1).any(|x| covered(&pipeline, Coordinate::new(x, y))));
+
+            assert!(!gap, "line from {:?} to {:?} has a gap from {:?} to {:?}", start, end, first, last);
+        }
+    }
+}
+
+#[test]
+fn test_point_conformance() {
+    let mut pipeline = new_pipeline();
+
+    let mut rng = SampleRng::new(1961);
+
+    for &pixel_center in &[PixelCenter::HalfInteger, PixelCenter::Integer] {
+        pipeline.render_state_mut().pixel_center = pixel_center;
+
+        for _ in 0..200 {
+            let screen = draw(&mut pipeline, Point, &[random_position(&mut rng, 2.0)], RasterBackend::default());
+
+            let reference = reference_point(screen[0], dimensions(), pixel_center);
+
+            assert_conforms(&pipeline, &reference, &format!("point at {:?} with {:?} pixel centers", screen[0], pixel_center));
+        }
+    }
+}
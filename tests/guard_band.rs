@@ -0,0 +1,99 @@
+//! Checks which primitives guard-band clipping passes through unchanged, clips or drops,
+//! and that the result renders the same as the primitives without any clipping.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pipeline::feedback::TransformFeedback;
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+const GUARD_BAND: f32 = 4.0;
+
+fn new_pipeline() -> TestPipeline {
+    Pipeline::from_framebuffer(RenderBuffer::with_dimensions(Dimensions::new(32, 32)), ())
+}
+
+fn mesh(positions: &[(f32, f32)]) -> Arc<Mesh<SimpleVertex<f32, ()>>> {
+    Arc::new(Mesh {
+        indices: (0..positions.len()).collect(),
+        vertices: positions.iter().map(|&(x, y)| SimpleVertex { position: Point3::new(x, y, 0.5), data: () }).collect(),
+    })
+}
+
+fn vertex_shader(vertex: &SimpleVertex<f32, ()>, _: &()) -> ClipVertex<f32, ()> {
+    ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ())
+}
+
+fn clip_triangle(pipeline: &mut TestPipeline, positions: &[(f32, f32)]) -> TransformFeedback<f32, ()> {
+    pipeline.render_mesh(Triangle, mesh(positions), None)
+            .run(vertex_shader)
+            .clip_primitives_guard_band(GUARD_BAND)
+            .capture()
+}
+
+fn render(pipeline: &mut TestPipeline, positions: &[(f32, f32)], guard_band: bool) -> Vec<RGBAf32Color> {
+    pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 0.0));
+
+    {
+        let geometry_shader = pipeline.render_mesh(Triangle, mesh(positions), None).run(vertex_shader);
+
+        let geometry_shader = if guard_band { geometry_shader.clip_primitives_guard_band(GUARD_BAND) } else { geometry_shader };
+
+        geometry_shader.finish_default().run(|_, _| Fragment::Color(RGBAf32Color::new(1.0, 1.0, 1.0, 1.0)));
+    }
+
+    pipeline.framebuffer().pixel_iter().map(|pixel| pixel.get()).collect()
+}
+
+#[test]
+fn test_guard_band_classification() {
+    let mut pipeline = new_pipeline();
+
+    // Partially off screen but within the guard band, so left alone
+    let within = [(-2.0, -2.0), (3.0, -2.0), (0.0, 3.0)];
+
+    let feedback = clip_triangle(&mut pipeline, &within);
+
+    assert_eq!(feedback.tris.len(), 3);
+
+    for (vertex, &(x, y)) in feedback.tris.iter().zip(within.iter()) {
+        assert_eq!((vertex.position.x, vertex.position.y), (x, y));
+    }
+
+    // Entirely outside of the viewport, so dropped, even though it's within the guard band
+    assert!(clip_triangle(&mut pipeline, &[(1.5, 0.0), (3.0, 0.0), (2.0, 1.0)]).tris.is_empty());
+
+    // Reaching far beyond the guard band, so clipped to it
+    let feedback = clip_triangle(&mut pipeline, &[(-100.0, -0.5), (100.0, -0.5), (0.0, 0.5)]);
+
+    assert!(feedback.tris.len() >= 6 && feedback.tris.len() % 3 == 0);
+
+    for vertex in &feedback.tris {
+        assert!(vertex.position.x.abs() <= GUARD_BAND * vertex.position.w + 1e-4);
+    }
+}
+
+#[test]
+fn test_guard_band_rendering() {
+    let mut pipeline = new_pipeline();
+
+    for triangle in &[[(-2.0, -2.0), (3.0, -2.0), (0.0, 3.0)],
+                      [(-100.0, -0.5), (100.0, -0.5), (0.0, 0.5)],
+                      [(-0.5, -0.5), (0.5, -0.5), (0.0, 0.5)]] {
+        let unclipped = render(&mut pipeline, triangle, false);
+        let guarded = render(&mut pipeline, triangle, true);
+
+        let differences = unclipped.iter().zip(guarded.iter()).filter(|&(a, b)| a != b).count();
+
+        // Clipped edges may round differently, but only in a few pixels along them
+        assert!(differences <= 4, "{} pixels differ for {:?}", differences, triangle);
+    }
+}
@@ -0,0 +1,85 @@
+//! Checks that affine and perspective-correct interpolation give different results for triangles
+//! whose vertices have different depths, and that affine interpolation is the default.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pipeline::RasterBackend;
+use softrender::pipeline::retro::Interpolation;
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+const SIZE: u32 = 16;
+
+/// Interpolates a value of zero at the left corners and one at the bottom-right corner, whose clip-space `w` is four
+fn render(interpolation: Option<Interpolation>, backend: RasterBackend) -> TestPipeline {
+    let mut pipeline: TestPipeline = Pipeline::from_framebuffer(RenderBuffer::with_dimensions(Dimensions::new(SIZE, SIZE)), ());
+
+    pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 0.0));
+
+    // Vertex data holds the interpolated value and clip-space w
+    let mesh = Arc::new(Mesh {
+        indices: vec![0, 1, 2],
+        vertices: vec![
+            SimpleVertex { position: Point3::new(-1.0, -1.0, 0.0), data: (0.0f32, 1.0f32) },
+            SimpleVertex { position: Point3::new(1.0, -1.0, 0.0), data: (1.0, 4.0) },
+            SimpleVertex { position: Point3::new(-1.0, 1.0, 0.0), data: (0.0, 1.0) },
+        ],
+    });
+
+    {
+        let vertex_shader = pipeline.render_mesh(Triangle, mesh, None);
+
+        let geometry_shader = vertex_shader.run(|vertex: &SimpleVertex<f32, (f32, f32)>, _: &()| -> ClipVertex<f32, f32> {
+            let (value, w) = vertex.data;
+
+            ClipVertex::new(Vector4::new(vertex.position.x * w, vertex.position.y * w, vertex.position.z * w, w), value)
+        });
+
+        let fragment_shader = geometry_shader.finish_default().with_raster_backend(backend);
+
+        let fragment_shader = match interpolation {
+            Some(interpolation) => fragment_shader.with_interpolation(interpolation),
+            None => fragment_shader,
+        };
+
+        fragment_shader.run(|vertex, _| Fragment::Color(RGBAf32Color::new(vertex.uniforms, 0.0, 0.0, 1.0)));
+    }
+
+    pipeline
+}
+
+fn value_at(pipeline: &TestPipeline, x: u32, y: u32) -> f32 {
+    pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get().x
+}
+
+#[test]
+fn test_affine_and_perspective_interpolation() {
+    for &backend in &[RasterBackend::EdgeFunction, RasterBackend::Scanline, RasterBackend::Hierarchical] {
+        let default = render(None, backend);
+        let affine = render(Some(Interpolation::Affine), backend);
+        let perspective = render(Some(Interpolation::Perspective), backend);
+
+        let (x, y) = (4, 13);
+
+        // In screen-space the value increases linearly to the right
+        let linear = (x as f32 + 0.5) / SIZE as f32;
+
+        assert!((value_at(&affine, x, y) - linear).abs() < 1e-4, "{:?}", backend);
+        assert_eq!(value_at(&default, x, y), value_at(&affine, x, y), "{:?}", backend);
+
+        // Perspective correction weighs the far vertex less
+        let screen_w = 0.25;
+        let expected = linear * screen_w / (linear * screen_w + (1.0 - linear));
+
+        assert!((value_at(&perspective, x, y) - expected).abs() < 1e-4, "{:?}", backend);
+        assert!(value_at(&perspective, x, y) < value_at(&affine, x, y) - 0.1, "{:?}", backend);
+    }
+}
@@ -0,0 +1,146 @@
+//! Checks that split-screen partitions only render into their own region,
+//! and that scissor rectangles down to a single pixel are respected without drawing points twice.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pipeline::SplitScreen;
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+fn new_pipeline(dimensions: Dimensions) -> TestPipeline {
+    let mut pipeline: TestPipeline = Pipeline::from_framebuffer(RenderBuffer::with_dimensions(dimensions), ());
+
+    pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 0.0));
+
+    pipeline
+}
+
+/// Quad from `left` to `right` in clip-space, covering the full height
+fn quad(left: f32, right: f32) -> Arc<Mesh<SimpleVertex<f32, ()>>> {
+    Arc::new(Mesh {
+        indices: vec![0, 1, 2, 2, 1, 3],
+        vertices: [(left, -1.0), (right, -1.0), (left, 1.0), (right, 1.0)].iter().map(|&(x, y)| {
+            SimpleVertex { position: Point3::new(x, y, 0.5), data: () }
+        }).collect(),
+    })
+}
+
+fn vertex_shader(vertex: &SimpleVertex<f32, ()>, _: &()) -> ClipVertex<f32, ()> {
+    ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ())
+}
+
+fn get(pipeline: &TestPipeline, x: u32, y: u32) -> RGBAf32Color {
+    pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get()
+}
+
+#[test]
+fn test_split_output() {
+    let dimensions = Dimensions::new(16, 8);
+
+    let mut pipeline = new_pipeline(dimensions);
+
+    let split = SplitScreen::horizontal(dimensions, 2, 0.0, 1.0);
+
+    // The left partition is filled, the right partition only has its left half covered
+    let meshes = [quad(-1.0, 1.0), quad(-1.0, 0.0)];
+
+    pipeline.render_split::<_, _, _, _, BlendPreset, _, _>(&split, Triangle, &meshes,
+                                                           |vertex, uniforms, _| vertex_shader(vertex, uniforms),
+                                                           |_, _, partition| Fragment::Color(RGBAf32Color::new(partition.index as f32, 1.0, 0.0, 1.0)));
+
+    for y in 0..8 {
+        for x in 0..16 {
+            let pixel = get(&pipeline, x, y);
+
+            if x < 8 {
+                assert_eq!((pixel.x, pixel.y), (0.0, 1.0), "({}, {})", x, y);
+            } else if x < 12 {
+                assert_eq!((pixel.x, pixel.y), (1.0, 1.0), "({}, {})", x, y);
+            } else {
+                assert_eq!(pixel.w, 0.0, "({}, {})", x, y);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_one_pixel_scissor() {
+    let dimensions = Dimensions::new(16, 16);
+
+    for &(scissor, expected) in &[((Coordinate::new(3, 5), Coordinate::new(3, 5)), 1),
+                                  ((Coordinate::new(3, 0), Coordinate::new(3, 15)), 16),
+                                  ((Coordinate::new(0, 7), Coordinate::new(15, 7)), 16)] {
+        let mut pipeline = new_pipeline(dimensions);
+
+        pipeline.render_mesh(Triangle, quad(-1.0, 1.0), None)
+                .run(vertex_shader)
+                .finish_default()
+                .with_scissor(Some(scissor))
+                .run(|_, _| Fragment::Color(RGBAf32Color::new(1.0, 1.0, 1.0, 1.0)));
+
+        let (start, end) = scissor;
+
+        let mut written = 0;
+
+        for y in 0..16 {
+            for x in 0..16 {
+                if get(&pipeline, x, y).w > 0.0 {
+                    assert!(x >= start.x && x <= end.x && y >= start.y && y <= end.y, "({}, {}) outside of {:?}", x, y, scissor);
+
+                    written += 1;
+                }
+            }
+        }
+
+        assert_eq!(written, expected, "{:?}", scissor);
+    }
+}
+
+#[test]
+fn test_points_on_scissor_edges() {
+    let dimensions = Dimensions::new(16, 16);
+
+    // A point at the center of every pixel
+    let points = Arc::new(Mesh {
+        indices: (0..256).collect(),
+        vertices: (0..256).map(|i| {
+            let (x, y) = ((i % 16) as f32, (i / 16) as f32);
+
+            SimpleVertex { position: Point3::new((x + 0.5) / 8.0 - 1.0, (y + 0.5) / 8.0 - 1.0, 0.5), data: () }
+        }).collect(),
+    });
+
+    // Scissor edges both on and off the edges between tiles
+    for &scissor in &[(Coordinate::new(3, 3), Coordinate::new(8, 8)),
+                      (Coordinate::new(8, 0), Coordinate::new(8, 15)),
+                      (Coordinate::new(0, 0), Coordinate::new(15, 15))] {
+        let mut pipeline = new_pipeline(dimensions);
+
+        pipeline.render_mesh(Point, points.clone(), None)
+                .run(vertex_shader)
+                .finish_default()
+                .with_tile_size(Dimensions::new(8, 8))
+                .with_scissor(Some(scissor))
+                .with_blend(BlendPreset::Additive)
+                .run(|_, _| Fragment::Color(RGBAf32Color::new(0.25, 0.25, 0.25, 0.25)));
+
+        let (start, end) = scissor;
+
+        // Every pixel within the scissor is drawn exactly once
+        for y in 0..16 {
+            for x in 0..16 {
+                let inside = x >= start.x && x <= end.x && y >= start.y && y <= end.y;
+
+                assert_eq!(get(&pipeline, x, y).w, if inside { 0.25 } else { 0.0 }, "({}, {}) with {:?}", x, y, scissor);
+            }
+        }
+    }
+}
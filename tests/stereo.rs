@@ -0,0 +1,64 @@
+//! Checks that stereo rendering draws each eye into its own half of the framebuffer,
+//! without either eye touching the column at the seam twice.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pipeline::{Stereo, Eye};
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+/// Adds colors together, so pixels drawn by both eyes stand out
+#[derive(Default)]
+struct Add;
+
+impl Blend<RGBAf32Color> for Add {
+    fn blend(&self, a: RGBAf32Color, b: RGBAf32Color) -> RGBAf32Color {
+        a + b
+    }
+}
+
+#[test]
+fn test_stereo_halves() {
+    // Eyes are 8x6, so no pixel center lies on the diagonal shared by the two triangles of the quad
+    let dimensions = Dimensions::new(16, 6);
+
+    let mut pipeline: TestPipeline = Pipeline::from_framebuffer(RenderBuffer::with_dimensions(dimensions), ());
+
+    pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 0.0));
+
+    // Quad covering all of clip-space
+    let mesh = Arc::new(Mesh {
+        indices: vec![0, 1, 2, 2, 1, 3],
+        vertices: [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)].iter().map(|&(x, y)| {
+            SimpleVertex { position: Point3::new(x, y, 0.5), data: () }
+        }).collect(),
+    });
+
+    let stereo = Stereo::side_by_side(dimensions, 0.0, 1.0);
+
+    pipeline.render_stereo::<_, _, _, _, Add, _, _, _>(&stereo, Triangle, mesh,
+                                                       |vertex, _| ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ()),
+                                                       |vertex, _, _| vertex.clone(),
+                                                       |_, _, eye| Fragment::Color(match eye {
+                                                           Eye::Left => RGBAf32Color::new(1.0, 0.0, 0.0, 1.0),
+                                                           Eye::Right => RGBAf32Color::new(0.0, 1.0, 0.0, 1.0),
+                                                       }));
+
+    for y in 0..6 {
+        for x in 0..16 {
+            let pixel = pipeline.framebuffer().pixel_ref(Coordinate::new(x, y)).unwrap().get();
+
+            let expected = if x < 8 { RGBAf32Color::new(1.0, 0.0, 0.0, 1.0) } else { RGBAf32Color::new(0.0, 1.0, 0.0, 1.0) };
+
+            assert_eq!(pixel, expected, "({}, {})", x, y);
+        }
+    }
+}
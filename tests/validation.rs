@@ -0,0 +1,131 @@
+//! Checks that the validation layer turns invalid input into errors instead of panics.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::error::{RenderError, RenderResult};
+use softrender::validation::{VALIDATION_ENABLED, checked_cast};
+use softrender::color::ColorAlpha;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::Depth;
+use softrender::attachments::predefined::ColorDepthAttachments;
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+fn new_pipeline() -> TestPipeline {
+    Pipeline::from_framebuffer(RenderBuffer::with_dimensions(Dimensions::new(8, 8)), ())
+}
+
+fn mesh(indices: Vec<usize>, positions: &[(f32, f32, f32)]) -> Arc<Mesh<SimpleVertex<f32, ()>>> {
+    Arc::new(Mesh {
+        indices,
+        vertices: positions.iter().map(|&(x, y, z)| SimpleVertex { position: Point3::new(x, y, z), data: () }).collect(),
+    })
+}
+
+fn try_new_pipeline(dimensions: Dimensions) -> RenderResult<TestPipeline> {
+    Pipeline::new(()).try_with_framebuffer(RenderBuffer::with_dimensions(dimensions))
+}
+
+fn error<T>(result: RenderResult<T>) -> RenderError {
+    match result {
+        Ok(_) => panic!("Expected an error"),
+        Err(trace) => trace.into_error(),
+    }
+}
+
+#[test]
+fn test_invalid_dimensions() {
+    if !VALIDATION_ENABLED { return; }
+
+    match error(try_new_pipeline(Dimensions::new(0, 8))) {
+        RenderError::InvalidDimensions => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    assert!(try_new_pipeline(Dimensions::new(8, 8)).is_ok());
+}
+
+#[test]
+fn test_nan_vertex() {
+    if !VALIDATION_ENABLED { return; }
+
+    let mut pipeline = new_pipeline();
+
+    let nan = ::std::f32::NAN;
+
+    match error(pipeline.try_render_mesh(Triangle, mesh(vec![0, 1, 2], &[(0.0, 0.0, 0.0), (nan, 1.0, 0.0), (1.0, 0.0, 0.0)]), None)) {
+        RenderError::InvalidVertex => (),
+        e => panic!("Unexpected error {:?}", e),
+    };
+
+    assert!(pipeline.try_render_mesh(Triangle, mesh(vec![0, 1, 2], &[(0.0, 0.0, 0.0), (0.0, 1.0, 0.0), (1.0, 0.0, 0.0)]), None).is_ok());
+}
+
+#[test]
+fn test_index_out_of_range() {
+    let mut pipeline = new_pipeline();
+
+    let positions = [(0.0, 0.0, 0.0), (0.0, 1.0, 0.0), (1.0, 0.0, 0.0)];
+
+    // The index count is checked even without the validation layer
+    match error(pipeline.try_render_mesh(Triangle, mesh(vec![0, 1], &positions), None)) {
+        RenderError::IndexOutOfRange => (),
+        e => panic!("Unexpected error {:?}", e),
+    };
+
+    if !VALIDATION_ENABLED { return; }
+
+    match error(pipeline.try_render_mesh(Triangle, mesh(vec![0, 1, 3], &positions), None)) {
+        RenderError::IndexOutOfRange => (),
+        e => panic!("Unexpected error {:?}", e),
+    };
+}
+
+#[test]
+fn test_cast_failure() {
+    match error(checked_cast::<f32, u8>(300.0)) {
+        RenderError::InvalidCast => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    match error(<u16 as Depth>::try_from_scalar(-1.0f32)) {
+        RenderError::InvalidCast => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    match error(<u8 as ColorAlpha>::try_from_scalar(::std::f64::NAN)) {
+        RenderError::InvalidCast => (),
+        e => panic!("Unexpected error {:?}", e),
+    }
+
+    assert_eq!(checked_cast::<f32, u8>(255.0).ok(), Some(255));
+    assert_eq!(<f32 as Depth>::try_from_scalar(0.5f64).ok(), Some(0.5));
+}
+
+#[test]
+fn test_incomplete_feedback() {
+    let mut pipeline = new_pipeline();
+
+    let mut feedback = pipeline.render_mesh(Triangle, mesh(vec![0, 1, 2], &[(-1.0, -1.0, 0.5), (1.0, -1.0, 0.5), (0.0, 1.0, 0.5)]), None)
+                               .run(|vertex, _| ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ()))
+                               .capture();
+
+    // A trailing partial triangle and line are dropped rather than panicking in the geometry stage
+    let extra = feedback.tris[0].clone();
+
+    feedback.tris.push(extra.clone());
+    feedback.lines.push(extra);
+
+    pipeline.render_feedback(feedback, None)
+            .run(|mut storage, primitive, _| storage.emit(primitive))
+            .finish_default()
+            .run(|_, _| Fragment::Color(RGBAf32Color::new(1.0, 1.0, 1.0, 1.0)));
+
+    assert!(pipeline.framebuffer().pixel_iter().any(|pixel| pixel.get().x > 0.0));
+}
@@ -0,0 +1,114 @@
+//! Checks that triangle strips and fans emitted by a geometry shader share their vertices,
+//! and still render the same as emitting every triangle separately.
+
+extern crate nalgebra;
+extern crate softrender;
+
+use std::sync::Arc;
+
+use nalgebra::{Point3, Vector4};
+
+use softrender::prelude::*;
+use softrender::color::predefined::formats::RGBAf32Color;
+use softrender::attachments::predefined::ColorDepthAttachments;
+use softrender::pipeline::PrimitiveStorage;
+use softrender::pipeline::stats::VertexCacheStats;
+
+type TestPipeline = Pipeline<(), RenderBuffer<ColorDepthAttachments<RGBAf32Color, f32>>>;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Emit {
+    Strip,
+    Fan,
+    Separate,
+}
+
+/// Zig-zag strip covering the middle of the screen, as a strip and as separate triangles
+fn strip() -> Vec<ClipVertex<f32, ()>> {
+    (0..6).map(|i| {
+        let x = (i / 2) as f32 - 1.0;
+        let y = if i % 2 == 0 { -0.5 } else { 0.5 };
+
+        ClipVertex::new(Vector4::new(x * 0.8, y, 0.5, 1.0), ())
+    }).collect()
+}
+
+/// Hexagon around the center of the screen
+fn fan() -> Vec<ClipVertex<f32, ()>> {
+    (0..6).map(|i| {
+        let angle = i as f32 * ::std::f32::consts::PI / 3.0;
+
+        ClipVertex::new(Vector4::new(angle.cos() * 0.8, angle.sin() * 0.8, 0.5, 1.0), ())
+    }).collect()
+}
+
+fn emit(mut storage: PrimitiveStorage<f32, ()>, shape: Emit, fan_shape: bool) {
+    let vertices = if fan_shape { fan() } else { strip() };
+
+    match shape {
+        Emit::Strip => storage.emit_triangle_strip(&vertices),
+        Emit::Fan => storage.emit_triangle_fan(&vertices),
+        Emit::Separate => {
+            for i in 2..vertices.len() {
+                if fan_shape {
+                    storage.emit_triangle(vertices[0].clone(), vertices[i - 1].clone(), vertices[i].clone());
+                } else if i % 2 == 0 {
+                    storage.emit_triangle(vertices[i - 2].clone(), vertices[i - 1].clone(), vertices[i].clone());
+                } else {
+                    storage.emit_triangle(vertices[i - 1].clone(), vertices[i - 2].clone(), vertices[i].clone());
+                }
+            }
+        }
+    }
+}
+
+fn mesh() -> Arc<Mesh<SimpleVertex<f32, ()>>> {
+    Arc::new(Mesh {
+        indices: vec![0],
+        vertices: vec![SimpleVertex { position: Point3::new(0.0, 0.0, 0.0), data: () }],
+    })
+}
+
+fn vertex_shader(vertex: &SimpleVertex<f32, ()>, _: &()) -> ClipVertex<f32, ()> {
+    ClipVertex::new(Vector4::new(vertex.position.x, vertex.position.y, vertex.position.z, 1.0), ())
+}
+
+fn render(pipeline: &mut TestPipeline, shape: Emit, fan_shape: bool) -> (VertexCacheStats, Vec<RGBAf32Color>) {
+    pipeline.framebuffer_mut().clear(RGBAf32Color::new(0.0, 0.0, 0.0, 0.0));
+
+    let stats = {
+        let fragment_shader = pipeline.render_mesh(Point, mesh(), None)
+                                      .run(vertex_shader)
+                                      .run(move |storage, _, _| emit(storage, shape, fan_shape))
+                                      .finish_default();
+
+        let stats = fragment_shader.vertex_cache_stats();
+
+        fragment_shader.run(|_, _| Fragment::Color(RGBAf32Color::new(1.0, 1.0, 1.0, 1.0)));
+
+        stats
+    };
+
+    (stats, pipeline.framebuffer().pixel_iter().map(|pixel| pixel.get()).collect())
+}
+
+#[test]
+fn test_strips_and_fans_share_vertices() {
+    let mut pipeline: TestPipeline = Pipeline::from_framebuffer(RenderBuffer::with_dimensions(Dimensions::new(32, 32)), ());
+
+    for &(shape, fan_shape) in &[(Emit::Strip, false), (Emit::Fan, true)] {
+        let (shared, shared_pixels) = render(&mut pipeline, shape, fan_shape);
+        let (separate, separate_pixels) = render(&mut pipeline, Emit::Separate, fan_shape);
+
+        // Four triangles out of six vertices, each transformed once
+        assert_eq!(shared.references, 12, "{:?}", shape);
+        assert_eq!(shared.transformed, 6, "{:?}", shape);
+        assert_eq!(shared.hits(), 6, "{:?}", shape);
+
+        assert_eq!(separate.transformed, 12);
+        assert_eq!(separate.hits(), 0);
+
+        assert!(shared_pixels.iter().any(|pixel| pixel.x > 0.0));
+        assert!(shared_pixels == separate_pixels, "{:?}", shape);
+    }
+}